@@ -0,0 +1,13 @@
+use vergen::EmitBuilder;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Exposes `VERGEN_GIT_SHA` and `VERGEN_BUILD_TIMESTAMP` to the crate. The
+    // git hash can be overridden with the environment variable of the same
+    // name, e.g. when building without the `.git` directory.
+    EmitBuilder::builder()
+        .build_timestamp()
+        .git_sha(true)
+        .emit()?;
+
+    Ok(())
+}
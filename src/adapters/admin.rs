@@ -1,53 +1,88 @@
-use crate::primitives::{ChainAddress, ChainName, IdentityContext, JudgementStateBlanked};
+use crate::adapters::command_parser::ParseError;
+pub use crate::adapters::command_parser::{Command, RawFieldName, MIN_SEARCH_TERM_LENGTH};
+use crate::adapters::matrix::MatrixHandle;
+use crate::address_blocklist::{BlockedAddress, BlocklistAuditEntry};
+use crate::metrics;
+use crate::primitives::{
+    ChainAddress, ChainName, ChallengeType, ExternalMessageType, FailureCause, IdentityContext,
+    IdentityField, JudgementState, JudgementStateBlanked, JudgementType, OutboundMessage,
+    Timestamp, VerificationOutcome, VerificationRecord,
+};
+use crate::reserved_names::{ReservedDisplayName, ReservedKind};
 use crate::Database;
-use std::str::FromStr;
+use schemars::JsonSchema;
+use std::collections::{BTreeMap, HashMap};
 
-pub type Result<T> = std::result::Result<T, Response>;
+// Maximum number of identities returned by a search.
+pub const SEARCH_LIMIT: usize = 20;
+// Maximum number of sent messages displayed, the most recent ones are shown.
+pub const SENT_LIMIT: usize = 20;
+// Maximum number of verification outcomes displayed, the most recent ones are
+// shown.
+pub const RECEIVED_LIMIT: usize = 20;
+// Seconds within which the confirmation code of a command must be sent back.
+pub const CONFIRMATION_WINDOW: u64 = 60;
 
-#[derive(Debug, Clone, Eq, PartialEq)]
-pub enum Command {
-    Status(ChainAddress),
-    Verify(ChainAddress, Vec<RawFieldName>),
-    Help,
+/// How much damage a command can do if sent from a compromised admin account.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CommandSeverity {
+    ReadOnly,
+    Modifying,
+    // Issues judgements, changes the blocklist or stops the service.
+    Destructive,
 }
 
-impl FromStr for Command {
-    type Err = Response;
-
-    fn from_str(s: &str) -> Result<Self> {
-        // Convenience handler.
-        let s = s.trim().replace("  ", " ");
-
-        if s.starts_with("status") {
-            let parts: Vec<&str> = s.split(' ').skip(1).collect();
-            if parts.len() != 1 {
-                return Err(Response::UnknownCommand);
-            }
-
-            Ok(Command::Status(ChainAddress::from(parts[0].to_string())))
-        } else if s.starts_with("verify") {
-            let parts: Vec<&str> = s.split(' ').skip(1).collect();
-            if parts.len() < 2 {
-                return Err(Response::UnknownCommand);
+impl Command {
+    pub fn severity(&self) -> CommandSeverity {
+        match self {
+            Command::Verify(_, fields) if fields.iter().any(|f| matches!(f, RawFieldName::All)) => {
+                CommandSeverity::Destructive
             }
-
-            Ok(Command::Verify(
-                ChainAddress::from(parts[0].to_string()),
-                parts[1..]
-                    .iter()
-                    .map(|s| RawFieldName::from_str(s))
-                    .collect::<Result<Vec<RawFieldName>>>()?,
-            ))
-        } else if s.starts_with("help") {
-            let count = s.split(' ').count();
-
-            if count > 1 {
-                return Err(Response::UnknownCommand);
+            Command::Approve(_)
+            | Command::Erroneous(_, _)
+            | Command::SetJudgement(_, _)
+            | Command::Block(_, _)
+            | Command::Unblock(_)
+            | Command::PauseSubmissions(_)
+            | Command::PauseAdapter(_)
+            | Command::PauseRequests(_) => CommandSeverity::Destructive,
+            Command::Verify(_, _)
+            | Command::ResumeSubmissions(_)
+            | Command::ResumeAdapter(_)
+            | Command::ResumeRequests(_)
+            | Command::ReserveName(_)
+            | Command::ReservePattern(_)
+            | Command::Unreserve(_) => CommandSeverity::Modifying,
+            Command::Status(_)
+            | Command::Explain(_)
+            | Command::Sent(_)
+            | Command::Received(_)
+            | Command::Approvals
+            | Command::Confirm(_)
+            | Command::Reviews
+            | Command::Search(_)
+            | Command::Blocked
+            | Command::BlocklistHistory(_)
+            | Command::Reserved
+            | Command::Diagnostics
+            | Command::Help => CommandSeverity::ReadOnly,
+        }
+    }
+    /// Returns the high-impact action of this command which must be confirmed
+    /// by a second admin, if the two-person rule is enabled.
+    pub fn as_approval_action(&self) -> Option<ApprovalAction> {
+        match self {
+            Command::Verify(context, fields)
+                if fields.iter().any(|f| matches!(f, RawFieldName::All)) =>
+            {
+                Some(ApprovalAction::FullVerification(context.clone()))
             }
-
-            Ok(Command::Help)
-        } else {
-            Err(Response::UnknownCommand)
+            Command::Erroneous(context, reason) => Some(ApprovalAction::ErroneousJudgement(
+                context.clone(),
+                reason.clone(),
+            )),
+            _ => None,
         }
     }
 }
@@ -55,15 +90,64 @@ impl FromStr for Command {
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Response {
     Status(JudgementStateBlanked),
+    Explanation(Explanation),
+    SentMessages(Vec<OutboundMessage>),
+    VerificationRecords(Vec<VerificationRecord>),
     Verified(ChainAddress, Vec<RawFieldName>),
     UnknownCommand,
     IdentityNotFound,
     InvalidSyntax(Option<String>),
     FullyVerified(ChainAddress),
+    JudgedErroneous(ChainAddress),
+    JudgementTypeSet(ChainAddress, JudgementType),
+    ApprovalRequested(PendingApproval),
+    ApprovalNotFound,
+    ApprovalSameAdmin,
+    ApprovalsDisabled,
+    PendingApprovals(Vec<PendingApproval>),
+    // Contains the code which must be sent back.
+    ConfirmationRequired(String),
+    ConfirmationNotFound,
+    ConfirmationInvalid,
+    ManualReviews(Vec<JudgementState>),
+    SearchResults(Vec<SearchResult>),
+    AddressBlocked(ChainAddress),
+    AddressUnblocked(ChainAddress),
+    AddressAlreadyBlocked,
+    AddressNotBlocked,
+    BlockedAddresses(Vec<BlockedAddress>),
+    BlocklistHistory(Vec<BlocklistAuditEntry>),
+    DisplayNameReserved(String),
+    DisplayNameUnreserved(String),
+    DisplayNameAlreadyReserved,
+    DisplayNameNotReserved,
+    ReservedDisplayNames(Vec<ReservedDisplayName>),
+    SubmissionsPaused(ChainName),
+    SubmissionsResumed(ChainName),
+    SubmissionsAlreadyPaused,
+    SubmissionsNotPaused,
+    AdapterPaused(String),
+    AdapterResumed(String),
+    AdapterAlreadyPaused,
+    AdapterNotPaused,
+    RequestsPaused(Option<ChainName>),
+    RequestsResumed(Option<ChainName>),
+    RequestsAlreadyPaused,
+    RequestsNotPaused,
+    Diagnostics(Diagnostics),
     InternalError,
     Help,
 }
 
+impl From<ParseError> for Response {
+    fn from(err: ParseError) -> Self {
+        match err {
+            ParseError::UnknownCommand => Response::UnknownCommand,
+            ParseError::InvalidSyntax(input) => Response::InvalidSyntax(input),
+        }
+    }
+}
+
 impl std::fmt::Display for Response {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let msg = match self {
@@ -98,67 +182,988 @@ impl std::fmt::Display for Response {
             Response::InternalError => {
                 "An internal error occured. Please contact the architects.".to_string()
             }
+            Response::ApprovalRequested(approval) => {
+                format!(
+                    "The action '{}' requires the confirmation of a second admin within {} seconds: approve {}",
+                    approval.action,
+                    approval.expires_at.raw().saturating_sub(Timestamp::now().raw()),
+                    approval.id
+                )
+            }
+            Response::ApprovalNotFound => {
+                "No pending approval with that ID was found (it might have expired)".to_string()
+            }
+            Response::ApprovalSameAdmin => {
+                "The action must be approved by a different admin than the one who proposed it"
+                    .to_string()
+            }
+            Response::ApprovalsDisabled => {
+                "The two-person rule for admin actions is not enabled".to_string()
+            }
+            Response::ConfirmationRequired(code) => {
+                format!(
+                    "Send 'confirm {}' within {} seconds to execute this command",
+                    code, CONFIRMATION_WINDOW
+                )
+            }
+            Response::ConfirmationNotFound => {
+                "No command is waiting for confirmation (it might have expired)".to_string()
+            }
+            Response::ConfirmationInvalid => {
+                "Invalid confirmation code, the command was discarded".to_string()
+            }
+            Response::PendingApprovals(approvals) => {
+                if approvals.is_empty() {
+                    "There are no pending approvals".to_string()
+                } else {
+                    let mut all = String::new();
+                    for approval in approvals {
+                        all.push_str(&format!(
+                            "{}: '{}' proposed by {} (expires at {})\n",
+                            approval.id,
+                            approval.action,
+                            approval.proposer,
+                            approval.expires_at.raw()
+                        ));
+                    }
+
+                    // Remove `\n` suffix.
+                    all.pop();
+
+                    all
+                }
+            }
+            Response::ManualReviews(states) => {
+                if states.is_empty() {
+                    "There are no identities requiring a manual review".to_string()
+                } else {
+                    let mut all = String::new();
+                    for state in states {
+                        let fields: Vec<&str> = state
+                            .fields
+                            .iter()
+                            .map(|field| field.value.type_name())
+                            .collect();
+
+                        all.push_str(&format!(
+                            "{}:{} ({})\n",
+                            state.context.chain.as_str(),
+                            state.context.address.as_str(),
+                            fields.join(", ")
+                        ));
+
+                        for note in &state.annotations {
+                            all.push_str(&format!("  - {}\n", note));
+                        }
+                    }
+
+                    // Remove `\n` suffix.
+                    all.pop();
+
+                    all
+                }
+            }
+            Response::SearchResults(results) => {
+                if results.is_empty() {
+                    "No identities found".to_string()
+                } else {
+                    let mut all = String::new();
+                    for result in results {
+                        all.push_str(&format!(
+                            "{}:{} ({})\n",
+                            result.context.chain.as_str(),
+                            result.context.address.as_str(),
+                            match &result.state {
+                                Some(state) if state.judgement_submitted => "judged",
+                                Some(state) if state.is_fully_verified => "fully verified",
+                                Some(_) => "pending",
+                                None => "removed",
+                            }
+                        ));
+
+                        if let Some(state) = &result.state {
+                            for field in &state.fields {
+                                all.push_str(&format!(
+                                    "  - {}: {}\n",
+                                    field.value.type_name(),
+                                    field.value.as_account_type().1
+                                ));
+                            }
+                        }
+                    }
+
+                    // Remove `\n` suffix.
+                    all.pop();
+
+                    all
+                }
+            }
+            Response::AddressBlocked(address) => format!(
+                "Blocked {}, judgement requests of this address are refused",
+                address.as_str()
+            ),
+            Response::AddressUnblocked(address) => format!(
+                "Unblocked {}, the judgement request is processed once the Watcher sends it again",
+                address.as_str()
+            ),
+            Response::AddressAlreadyBlocked => "The address is already blocked".to_string(),
+            Response::AddressNotBlocked => "The address is not blocked".to_string(),
+            Response::BlockedAddresses(blocked) => {
+                if blocked.is_empty() {
+                    "There are no blocked addresses".to_string()
+                } else {
+                    let mut all = String::new();
+                    for entry in blocked {
+                        all.push_str(&format!(
+                            "{}:{} blocked by {} at {}: {}\n",
+                            entry.context.chain.as_str(),
+                            entry.context.address.as_str(),
+                            entry.blocked_by,
+                            entry.timestamp.raw(),
+                            entry.reason
+                        ));
+                    }
+
+                    // Remove `\n` suffix.
+                    all.pop();
+
+                    all
+                }
+            }
+            Response::BlocklistHistory(entries) => {
+                if entries.is_empty() {
+                    "The address was never blocked by an admin".to_string()
+                } else {
+                    let mut all = String::new();
+                    for entry in entries {
+                        all.push_str(&format!(
+                            "{}: {:?} by {}{}\n",
+                            entry.timestamp.raw(),
+                            entry.action,
+                            entry.admin,
+                            match &entry.reason {
+                                Some(reason) => format!(" ({})", reason),
+                                None => "".to_string(),
+                            }
+                        ));
+                    }
+
+                    // Remove `\n` suffix.
+                    all.pop();
+
+                    all
+                }
+            }
+            Response::DisplayNameReserved(value) => format!(
+                "Reserved {}, matching display names fail the display name check",
+                value
+            ),
+            Response::DisplayNameUnreserved(value) => format!(
+                "Removed the reserved display name {}, pending identities are checked again once updated",
+                value
+            ),
+            Response::DisplayNameAlreadyReserved => {
+                "The display name is already reserved".to_string()
+            }
+            Response::DisplayNameNotReserved => {
+                "The display name was not reserved by an admin".to_string()
+            }
+            Response::ReservedDisplayNames(reserved) => {
+                if reserved.is_empty() {
+                    "No display names were reserved by an admin".to_string()
+                } else {
+                    let mut all = String::new();
+                    for entry in reserved {
+                        all.push_str(&format!(
+                            "{} ({}) reserved by {} at {}\n",
+                            entry.value,
+                            match entry.kind {
+                                ReservedKind::Name => "name",
+                                ReservedKind::Pattern => "pattern",
+                            },
+                            entry.reserved_by,
+                            entry.timestamp.raw()
+                        ));
+                    }
+
+                    // Remove `\n` suffix.
+                    all.pop();
+
+                    all
+                }
+            }
+            Response::SubmissionsPaused(chain) => format!(
+                "Paused judgement submissions for {}, verified identities are judged once resumed",
+                chain.as_str()
+            ),
+            Response::SubmissionsResumed(chain) => {
+                format!("Resumed judgement submissions for {}", chain.as_str())
+            }
+            Response::SubmissionsAlreadyPaused => {
+                "Judgement submissions are already paused".to_string()
+            }
+            Response::SubmissionsNotPaused => "Judgement submissions are not paused".to_string(),
+            Response::AdapterPaused(adapter) => format!(
+                "Paused the {} adapter, no messages are fetched or sent until resumed",
+                adapter
+            ),
+            Response::AdapterResumed(adapter) => format!("Resumed the {} adapter", adapter),
+            Response::AdapterAlreadyPaused => "Adapter is already paused".to_string(),
+            Response::AdapterNotPaused => "Adapter is not paused".to_string(),
+            Response::RequestsPaused(chain) => format!(
+                "Ignoring new judgement requests for {}, accepted identities are still judged",
+                chain.as_ref().map(|chain| chain.as_str()).unwrap_or("all networks")
+            ),
+            Response::RequestsResumed(chain) => format!(
+                "Accepting new judgement requests for {} again",
+                chain.as_ref().map(|chain| chain.as_str()).unwrap_or("all networks")
+            ),
+            Response::RequestsAlreadyPaused => "New judgement requests are already paused".to_string(),
+            Response::RequestsNotPaused => "New judgement requests are not paused".to_string(),
+            Response::Explanation(explanation) => explanation.to_string(),
+            Response::SentMessages(messages) => {
+                if messages.is_empty() {
+                    "No messages were sent to this identity".to_string()
+                } else {
+                    messages
+                        .iter()
+                        .map(|message| {
+                            let result = if message.sandbox {
+                                "sandbox".to_string()
+                            } else if message.delivered {
+                                "delivered".to_string()
+                            } else {
+                                format!(
+                                    "failed: {}",
+                                    message.error.as_deref().unwrap_or("unknown error")
+                                )
+                            };
+
+                            format!(
+                                "{} {} {} to {} ({}): {}",
+                                message.timestamp.raw(),
+                                message.adapter,
+                                message.field.as_deref().unwrap_or("unknown field"),
+                                message.to,
+                                result,
+                                message.content
+                            )
+                        })
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                }
+            }
+            Response::VerificationRecords(records) => {
+                if records.is_empty() {
+                    "No messages were received from this identity".to_string()
+                } else {
+                    records
+                        .iter()
+                        .map(|record| {
+                            let (adapter, from) = match &record.origin {
+                                ExternalMessageType::Email(from) => ("email", from),
+                                ExternalMessageType::Twitter(from) => ("twitter", from),
+                                ExternalMessageType::Matrix(from) => ("matrix", from),
+                                ExternalMessageType::Web(from) => ("web", from),
+                            };
+                            let mut outcome = match record.outcome {
+                                VerificationOutcome::Verified => "verified",
+                                VerificationOutcome::Failed => "failed",
+                                VerificationOutcome::Vetoed => "vetoed",
+                                VerificationOutcome::Expired => "expired",
+                            }
+                            .to_string();
+                            if let Some(cause) = record.cause {
+                                outcome.push_str(match cause {
+                                    FailureCause::WrongChallenge => ", wrong challenge",
+                                    FailureCause::NoChallenge => ", no challenge found",
+                                    FailureCause::MissingDisambiguationToken => {
+                                        ", disambiguation token missing"
+                                    }
+                                    FailureCause::Expired | FailureCause::Vetoed => "",
+                                });
+                            }
+                            let provider_id = record
+                                .metadata
+                                .as_ref()
+                                .and_then(|metadata| metadata.provider_id.as_deref())
+                                .unwrap_or("no provider ID");
+
+                            format!(
+                                "{} {} from {} ({}): {}",
+                                record.timestamp.raw(),
+                                adapter,
+                                from,
+                                outcome,
+                                provider_id
+                            )
+                        })
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                }
+            }
+            Response::Diagnostics(diag) => diag.to_string(),
             Response::Help => "\
                 status <ADDR>\t\t\tShow the current verification status of the specified address.\n\
+                explain <ADDR>\t\t\tExplain whether and why the judgement of the specified address is submitted.\n\
+                sent <ADDR>\t\t\tShow the most recent messages which were sent to the specified address.\n\
+                received <ADDR>\t\tShow the verification outcomes of the most recent messages from the specified address.\n\
                 verify <ADDR> <FIELD>...\tVerify one or multiple fields of the specified address.\n\
+                erroneous <ADDR> [REASON]\tJudge the specified address as erroneous, e.g. if it is malicious.\n\
+                judgement <ADDR> <TYPE>\t\tSet the judgement of the specified address (reasonable, known_good or low_quality).\n\
+                approvals\t\t\tShow the actions waiting for the confirmation of a second admin.\n\
+                approve <ID>\t\t\tConfirm an action proposed by another admin.\n\
+                confirm <CODE>\t\t\tExecute the previous command with the code sent by the bot.\n\
+                reviews\t\t\t\tShow the identities which can only be verified manually.\n\
+                search <TERM>\t\t\tFind identities by (partial) address, display name or field value.\n\
+                block <ADDR> [REASON]\t\tRefuse all judgement requests of the specified address.\n\
+                unblock <ADDR>\t\t\tProcess judgement requests of the specified address again.\n\
+                blocked [ADDR]\t\t\tShow the blocked addresses or the blocklist history of the specified address.\n\
+                reserve <NAME>\t\t\tFail the display name check of the specified name and its lookalikes.\n\
+                reserve-pattern <REGEX>\t\tFail the display name check of names matching the specified pattern.\n\
+                unreserve <NAME|REGEX>\t\tRemove a reserved display name or pattern.\n\
+                reserved\t\t\tShow the display names and patterns reserved by admins.\n\
+                pause <CHAIN>\t\t\tStop submitting judgements for the specified network.\n\
+                resume <CHAIN>\t\t\tSubmit judgements for the specified network again.\n\
+                pause-adapter <NAME>\t\tStop fetching and sending messages of the email, matrix, twitter or web adapter.\n\
+                resume-adapter <NAME>\t\tFetch and send messages of the specified adapter again.\n\
+                pause-requests [CHAIN]\t\tIgnore new judgement requests of the specified or all networks.\n\
+                resume-requests [CHAIN]\t\tAccept new judgement requests of the specified or all networks again.\n\
+                diag\t\t\t\tShow pending judgements, adapter queues and the connection state.\n\
                 "
             .to_string(),
             Response::FullyVerified(_) => {
                 "Identity has been fully verified. The extrinsic will be submitted in a couple of minutes".to_string()
             },
+            Response::JudgementTypeSet(address, judgement_type) => format!(
+                "{} will be judged as {:?} once fully verified",
+                address.as_str(),
+                judgement_type
+            ),
+            Response::JudgedErroneous(address) => format!(
+                "{} will be judged as erroneous, the extrinsic will be submitted in a couple of minutes",
+                address.as_str()
+            ),
         };
 
         write!(f, "{}", msg)
     }
 }
 
+/// A high-impact admin action which, if the two-person rule is enabled, must be
+/// confirmed by a second admin before it gets applied.
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
-pub enum RawFieldName {
-    LegalName,
-    DisplayName,
-    Email,
-    Web,
-    Twitter,
-    Matrix,
-    // Represents the full identity
-    All,
+#[serde(rename_all = "snake_case", tag = "type", content = "value")]
+pub enum ApprovalAction {
+    FullVerification(IdentityContext),
+    ErroneousJudgement(IdentityContext, Option<String>),
+}
+
+impl ApprovalAction {
+    pub fn to_command(&self) -> Command {
+        match self {
+            ApprovalAction::FullVerification(context) => {
+                Command::Verify(context.clone(), vec![RawFieldName::All])
+            }
+            ApprovalAction::ErroneousJudgement(context, reason) => {
+                Command::Erroneous(context.clone(), reason.clone())
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ApprovalAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApprovalAction::FullVerification(context) => write!(
+                f,
+                "verify {}:{} all",
+                context.chain.as_str(),
+                context.address.as_str()
+            ),
+            ApprovalAction::ErroneousJudgement(context, reason) => write!(
+                f,
+                "erroneous {}:{}{}",
+                context.chain.as_str(),
+                context.address.as_str(),
+                match reason {
+                    Some(reason) => format!(" {}", reason),
+                    None => "".to_string(),
+                }
+            ),
+        }
+    }
+}
+
+/// Identities which were not judged yet, by chain and by unverified field.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct PendingOverview {
+    pub per_chain: BTreeMap<String, usize>,
+    pub unverified_fields: BTreeMap<String, usize>,
+}
+
+/// Compact health report of the registrar, readable on small screens.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostics {
+    // Missing if the database could not be queried.
+    pub pending: Option<PendingOverview>,
+    pub queue_depths: Vec<(String, i64)>,
+    pub watcher_last_messages: Vec<(String, Timestamp)>,
+    // Networks for which judgement submissions are paused.
+    pub paused_chains: Vec<String>,
+    pub paused_adapters: Vec<String>,
+    // Networks for which new judgement requests are paused, `all` if paused
+    // for every network.
+    pub paused_requests: Vec<String>,
+    pub db_connected: bool,
+    pub generated_at: Timestamp,
+}
+
+impl Diagnostics {
+    pub async fn collect(db: &Database) -> Self {
+        let db_connected = match db.connectivity_check().await {
+            Ok(()) => true,
+            Err(err) => {
+                error!("Diagnostics: database is not reachable: {:?}", err);
+                false
+            }
+        };
+
+        let pending = match db.fetch_pending_overview().await {
+            Ok(pending) => Some(pending),
+            Err(err) => {
+                error!("Diagnostics: failed to fetch pending judgements: {:?}", err);
+                None
+            }
+        };
+
+        let paused_chains = match db.fetch_paused_chains().await {
+            Ok(paused) => paused
+                .into_iter()
+                .map(|paused| paused.chain.as_str().to_string())
+                .collect(),
+            Err(err) => {
+                error!("Diagnostics: failed to fetch paused chains: {:?}", err);
+                vec![]
+            }
+        };
+
+        let paused_adapters = match db.fetch_paused_adapters().await {
+            Ok(paused) => paused.into_iter().map(|paused| paused.adapter).collect(),
+            Err(err) => {
+                error!("Diagnostics: failed to fetch paused adapters: {:?}", err);
+                vec![]
+            }
+        };
+
+        let paused_requests = match db.fetch_paused_requests().await {
+            Ok(paused) => paused
+                .into_iter()
+                .map(|paused| {
+                    paused
+                        .chain
+                        .as_ref()
+                        .map(|chain| chain.as_str())
+                        .unwrap_or("all")
+                        .to_string()
+                })
+                .collect(),
+            Err(err) => {
+                error!("Diagnostics: failed to fetch paused requests: {:?}", err);
+                vec![]
+            }
+        };
+
+        Diagnostics {
+            pending,
+            queue_depths: metrics::adapter_queue_depths(),
+            watcher_last_messages: metrics::watcher_last_messages(),
+            paused_chains,
+            paused_adapters,
+            paused_requests,
+            db_connected,
+            generated_at: Timestamp::now(),
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn list<T: std::fmt::Display>(entries: impl Iterator<Item = (String, T)>) -> String {
+            let entries: Vec<String> = entries
+                .map(|(name, value)| format!("{} {}", name, value))
+                .collect();
+
+            if entries.is_empty() {
+                "none".to_string()
+            } else {
+                entries.join(", ")
+            }
+        }
+
+        writeln!(
+            f,
+            "DB: {}",
+            if self.db_connected {
+                "ok"
+            } else {
+                "unreachable"
+            }
+        )?;
+
+        match &self.pending {
+            Some(pending) => {
+                writeln!(
+                    f,
+                    "Pending: {}",
+                    list(pending.per_chain.iter().map(|(k, v)| (k.clone(), v)))
+                )?;
+                writeln!(
+                    f,
+                    "Unverified: {}",
+                    list(
+                        pending
+                            .unverified_fields
+                            .iter()
+                            .map(|(k, v)| (k.clone(), v))
+                    )
+                )?;
+            }
+            None => writeln!(f, "Pending: unknown")?,
+        }
+
+        if !self.paused_chains.is_empty() {
+            writeln!(f, "Paused: {}", self.paused_chains.join(", "))?;
+        }
+
+        if !self.paused_adapters.is_empty() {
+            writeln!(f, "Paused adapters: {}", self.paused_adapters.join(", "))?;
+        }
+
+        if !self.paused_requests.is_empty() {
+            writeln!(f, "Paused requests: {}", self.paused_requests.join(", "))?;
+        }
+
+        writeln!(f, "Queues: {}", list(self.queue_depths.iter().cloned()))?;
+        write!(
+            f,
+            "Watcher: {}",
+            list(self.watcher_last_messages.iter().map(|(chain, time)| {
+                (
+                    chain.clone(),
+                    format!(
+                        "{}s ago",
+                        self.generated_at.raw().saturating_sub(time.raw())
+                    ),
+                )
+            }))
+        )
+    }
+}
+
+/// Dry-run of the judgement decision of an identity, evaluating the same
+/// conditions as the submission of judgements without any side effects.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Explanation {
+    pub context: IdentityContext,
+    // Type and status of each field.
+    pub fields: Vec<(String, String)>,
+    // Description and outcome of each evaluated condition, in order.
+    pub checks: Vec<(String, bool)>,
+    // Time at which a deferred edit of the identity is applied.
+    pub pending_edit: Option<Timestamp>,
+    pub decision: String,
+    pub generated_at: Timestamp,
+}
+
+impl Explanation {
+    pub fn new(
+        state: &JudgementState,
+        paused: bool,
+        pending_edit: Option<Timestamp>,
+        now: Timestamp,
+    ) -> Self {
+        let verified = state
+            .fields
+            .iter()
+            .filter(|field| field.challenge.is_verified())
+            .count();
+        let total = state.fields.len();
+        let all_verified = verified == total;
+        let delay_elapsed = state
+            .issue_judgement_at
+            .map(|at| at.raw() < now.raw())
+            .unwrap_or(false);
+
+        let checks = vec![
+            (
+                format!("all fields verified ({} of {})", verified, total),
+                all_verified,
+            ),
+            (
+                "marked as fully verified".to_string(),
+                state.is_fully_verified,
+            ),
+            ("not judged yet".to_string(), !state.judgement_submitted),
+            ("submissions not paused".to_string(), !paused),
+            (
+                match state.issue_judgement_at {
+                    Some(at) if delay_elapsed => format!(
+                        "judgement delay elapsed ({}s ago)",
+                        now.raw().saturating_sub(at.raw())
+                    ),
+                    Some(at) => format!(
+                        "judgement delay elapsed (in {}s)",
+                        at.raw().saturating_sub(now.raw())
+                    ),
+                    None => "judgement delay elapsed (not scheduled)".to_string(),
+                },
+                delay_elapsed,
+            ),
+        ];
+
+        // Judgements are submitted based on the flags of the identity, so
+        // inconsistent flags are reported as such.
+        let decision = if state.judgement_submitted {
+            "judged, nothing to do".to_string()
+        } else if !state.is_fully_verified && all_verified {
+            "stuck, all fields are verified but the identity is not marked as fully verified"
+                .to_string()
+        } else if !state.is_fully_verified && state.requires_manual_review {
+            "waiting for manual review, use `verify`".to_string()
+        } else if !state.is_fully_verified {
+            format!(
+                "waiting for the user to verify {} more field(s)",
+                total - verified
+            )
+        } else if paused {
+            format!(
+                "withheld, submissions are paused for {}",
+                state.context.chain.as_str()
+            )
+        } else if !delay_elapsed {
+            "waiting for the judgement delay".to_string()
+        } else {
+            "submitted with the next run".to_string()
+        };
+
+        Explanation {
+            context: state.context.clone(),
+            fields: state
+                .fields
+                .iter()
+                .map(|field| (field.value.type_name().to_string(), field_status(field)))
+                .collect(),
+            checks,
+            pending_edit,
+            decision,
+            generated_at: now,
+        }
+    }
+}
+
+fn field_status(field: &IdentityField) -> String {
+    let status = match &field.challenge {
+        ChallengeType::ExpectedMessage { expected, second } => match second {
+            _ if !expected.is_verified => "waiting for the challenge".to_string(),
+            Some(second) if !second.is_verified && second.is_expired() => {
+                "second challenge expired".to_string()
+            }
+            Some(second) if !second.is_verified => "waiting for the second challenge".to_string(),
+            _ => "verified".to_string(),
+        },
+        ChallengeType::DisplayNameCheck { passed: true, .. } => "verified".to_string(),
+        ChallengeType::DisplayNameCheck {
+            reserved: Some(reserved),
+            ..
+        } => format!("matches the reserved name {}", reserved),
+        ChallengeType::DisplayNameCheck { violations, .. } => {
+            format!("similar to {} existing display name(s)", violations.len())
+        }
+        ChallengeType::Unsupported {
+            is_verified: Some(true),
+        } => "verified by an admin".to_string(),
+        ChallengeType::Unsupported { .. } => "requires manual verification".to_string(),
+    };
+
+    if field.failed_attempts > 0 && !field.challenge.is_verified() {
+        format!("{}, {} failed attempt(s)", status, field.failed_attempts)
+    } else {
+        status
+    }
 }
 
-impl std::fmt::Display for RawFieldName {
+impl std::fmt::Display for Explanation {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", {
-            match self {
-                RawFieldName::LegalName => "legal_name",
-                RawFieldName::DisplayName => "display_name",
-                RawFieldName::Email => "email",
-                RawFieldName::Web => "web",
-                RawFieldName::Twitter => "twitter",
-                RawFieldName::Matrix => "matrix",
-                RawFieldName::All => "all",
-            }
-        })
+        writeln!(
+            f,
+            "Identity: {}:{}",
+            self.context.chain.as_str(),
+            self.context.address.as_str()
+        )?;
+
+        for (name, status) in &self.fields {
+            writeln!(f, "Field {}: {}", name, status)?;
+        }
+
+        for (check, passed) in &self.checks {
+            writeln!(f, "[{}] {}", if *passed { "ok" } else { "no" }, check)?;
+        }
+
+        if let Some(apply_at) = self.pending_edit {
+            writeln!(
+                f,
+                "Pending edit: applied in {}s",
+                apply_at.raw().saturating_sub(self.generated_at.raw())
+            )?;
+        }
+
+        write!(f, "Decision: {}", self.decision)
+    }
+}
+
+/// An identity matching an admin search. The state is missing if the identity
+/// only appears in the event log, e.g. because it was already removed.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SearchResult {
+    pub context: IdentityContext,
+    pub state: Option<JudgementStateBlanked>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct PendingApproval {
+    pub id: String,
+    pub action: ApprovalAction,
+    pub proposer: MatrixHandle,
+    pub expires_at: Timestamp,
+}
+
+impl PendingApproval {
+    pub fn new(action: ApprovalAction, proposer: MatrixHandle, window: u64) -> Self {
+        use rand::{thread_rng, Rng};
+
+        let random: [u8; 4] = thread_rng().gen();
+        PendingApproval {
+            id: hex::encode(random),
+            action,
+            proposer,
+            expires_at: Timestamp::with_offset(window),
+        }
+    }
+}
+
+struct PendingConfirmation {
+    command: Command,
+    code: String,
+    expires_at: Timestamp,
+}
+
+/// Commands which are held back until the admin sends the confirmation code
+/// back, tracked per admin and room.
+#[derive(Default)]
+pub struct PendingConfirmations {
+    pending: HashMap<(MatrixHandle, String), PendingConfirmation>,
+}
+
+impl PendingConfirmations {
+    /// Holds the command back and returns the code which must be sent back.
+    /// Replaces any previous command of the admin in the room.
+    pub fn request(&mut self, admin: &MatrixHandle, room: &str, command: Command) -> String {
+        self.request_at(admin, room, command, Timestamp::now())
+    }
+    fn request_at(
+        &mut self,
+        admin: &MatrixHandle,
+        room: &str,
+        command: Command,
+        now: Timestamp,
+    ) -> String {
+        use rand::{thread_rng, Rng};
+
+        self.pending
+            .retain(|_, pending| pending.expires_at.raw() > now.raw());
+
+        let code = hex::encode(thread_rng().gen::<[u8; 3]>());
+        self.pending.insert(
+            (admin.clone(), room.to_string()),
+            PendingConfirmation {
+                command,
+                code: code.clone(),
+                expires_at: Timestamp::from(now.raw() + CONFIRMATION_WINDOW),
+            },
+        );
+
+        code
+    }
+    /// Returns the held back command if the code matches. The command is
+    /// discarded either way, so each code can only be tried once.
+    #[allow(clippy::result_large_err)]
+    pub fn confirm(
+        &mut self,
+        admin: &MatrixHandle,
+        room: &str,
+        code: &str,
+    ) -> std::result::Result<Command, Response> {
+        self.confirm_at(admin, room, code, Timestamp::now())
+    }
+    #[allow(clippy::result_large_err)]
+    fn confirm_at(
+        &mut self,
+        admin: &MatrixHandle,
+        room: &str,
+        code: &str,
+        now: Timestamp,
+    ) -> std::result::Result<Command, Response> {
+        match self.pending.remove(&(admin.clone(), room.to_string())) {
+            Some(pending) if pending.expires_at.raw() <= now.raw() => {
+                Err(Response::ConfirmationNotFound)
+            }
+            Some(pending) if pending.code == code => Ok(pending.command),
+            Some(_) => Err(Response::ConfirmationInvalid),
+            None => Err(Response::ConfirmationNotFound),
+        }
     }
 }
 
-impl FromStr for RawFieldName {
-    type Err = Response;
-
-    fn from_str(s: &str) -> Result<Self> {
-        // Convenience handler.
-        let s = s.trim().replace('-', "").replace('_', "").to_lowercase();
-
-        let f = match s.as_str() {
-            "legalname" => RawFieldName::LegalName,
-            "displayname" => RawFieldName::DisplayName,
-            "email" => RawFieldName::Email,
-            "web" => RawFieldName::Web,
-            "twitter" => RawFieldName::Twitter,
-            "matrix" => RawFieldName::Matrix,
-            "all" => RawFieldName::All,
-            _ => return Err(Response::InvalidSyntax(Some(s.to_string()))),
+/// Processes the admin command while enforcing the two-person rule for
+/// high-impact actions. If `approval_window` is `None`, the rule is disabled and
+/// all commands are executed directly.
+pub async fn process_admin_with_approval(
+    db: &Database,
+    command: Command,
+    sender: &MatrixHandle,
+    approval_window: Option<u64>,
+) -> Response {
+    async fn local(
+        db: &Database,
+        command: Command,
+        sender: &MatrixHandle,
+        approval_window: Option<u64>,
+    ) -> crate::Result<Response> {
+        // Changes to the blocklist are recorded together with the admin.
+        let command = match command {
+            Command::Block(context, reason) => {
+                let reason = reason.unwrap_or_else(|| "Blocked by admin".to_string());
+                return if db.block_address(&context, &reason, sender).await? {
+                    Ok(Response::AddressBlocked(context.address))
+                } else {
+                    Ok(Response::AddressAlreadyBlocked)
+                };
+            }
+            Command::Unblock(context) => {
+                return if db.unblock_address(&context, sender).await? {
+                    Ok(Response::AddressUnblocked(context.address))
+                } else {
+                    Ok(Response::AddressNotBlocked)
+                };
+            }
+            Command::PauseSubmissions(chain) => {
+                return if db.pause_submissions(chain, sender).await? {
+                    info!("Judgement submissions for {:?} paused by {}", chain, sender);
+                    Ok(Response::SubmissionsPaused(chain))
+                } else {
+                    Ok(Response::SubmissionsAlreadyPaused)
+                };
+            }
+            Command::PauseAdapter(adapter) => {
+                return if db.pause_adapter(&adapter, sender).await? {
+                    info!("{} adapter paused by {}", adapter, sender);
+                    Ok(Response::AdapterPaused(adapter))
+                } else {
+                    Ok(Response::AdapterAlreadyPaused)
+                };
+            }
+            Command::ReserveName(name) => {
+                return if db
+                    .reserve_display_name(ReservedKind::Name, &name, sender)
+                    .await?
+                {
+                    Ok(Response::DisplayNameReserved(name))
+                } else {
+                    Ok(Response::DisplayNameAlreadyReserved)
+                };
+            }
+            Command::ReservePattern(pattern) => {
+                return if db
+                    .reserve_display_name(ReservedKind::Pattern, &pattern, sender)
+                    .await?
+                {
+                    Ok(Response::DisplayNameReserved(pattern))
+                } else {
+                    Ok(Response::DisplayNameAlreadyReserved)
+                };
+            }
+            Command::Unreserve(value) => {
+                return if db.unreserve_display_name(&value, sender).await? {
+                    Ok(Response::DisplayNameUnreserved(value))
+                } else {
+                    Ok(Response::DisplayNameNotReserved)
+                };
+            }
+            Command::PauseRequests(chain) => {
+                return if db.pause_requests(chain, sender).await? {
+                    info!(
+                        "New judgement requests for {:?} paused by {}",
+                        chain, sender
+                    );
+                    Ok(Response::RequestsPaused(chain))
+                } else {
+                    Ok(Response::RequestsAlreadyPaused)
+                };
+            }
+            command => command,
         };
 
-        Ok(f)
+        let window = match approval_window {
+            Some(window) => window,
+            None => return Ok(process_admin(db, command).await),
+        };
+
+        // Propose the action, the change is only applied once a second admin
+        // confirms it.
+        if let Some(action) = command.as_approval_action() {
+            let approval = PendingApproval::new(action, sender.clone(), window);
+            db.insert_pending_approval(&approval).await?;
+
+            return Ok(Response::ApprovalRequested(approval));
+        }
+
+        match command {
+            Command::Approve(id) => {
+                let approval = match db.fetch_pending_approval(&id).await? {
+                    Some(approval) => approval,
+                    None => return Ok(Response::ApprovalNotFound),
+                };
+
+                if &approval.proposer == sender {
+                    return Ok(Response::ApprovalSameAdmin);
+                }
+
+                // Remove the proposal before applying it, so it can only be
+                // approved once.
+                if !db.remove_pending_approval(&approval.id).await? {
+                    return Ok(Response::ApprovalNotFound);
+                }
+
+                info!(
+                    "Admin {} approved '{}' proposed by {}",
+                    sender, approval.action, approval.proposer
+                );
+
+                Ok(process_admin(db, approval.action.to_command()).await)
+            }
+            command => Ok(process_admin(db, command).await),
+        }
+    }
+
+    match local(db, command, sender, approval_window).await {
+        Ok(resp) => resp,
+        Err(err) => {
+            error!("Admin tool: {:?}", err);
+            Response::InternalError
+        }
     }
 }
 
@@ -166,8 +1171,7 @@ impl FromStr for RawFieldName {
 pub async fn process_admin<'a>(db: &'a Database, command: Command) -> Response {
     let local = |db: &'a Database, command: Command| async move {
         match command {
-            Command::Status(addr) => {
-                let context = create_context(addr);
+            Command::Status(context) => {
                 let state = db.fetch_judgement_state(&context).await?;
 
                 // Determine response based on database lookup.
@@ -176,8 +1180,34 @@ pub async fn process_admin<'a>(db: &'a Database, command: Command) -> Response {
                     None => Ok(Response::IdentityNotFound),
                 }
             }
-            Command::Verify(addr, fields) => {
-                let context = create_context(addr.clone());
+            Command::Explain(context) => {
+                let state = match db.fetch_judgement_state(&context).await? {
+                    Some(state) => state,
+                    None => return Ok(Response::IdentityNotFound),
+                };
+
+                let paused = db.is_submission_paused(context.chain).await?;
+                let pending_edit = db.fetch_pending_update_time(&context).await?;
+
+                Ok(Response::Explanation(Explanation::new(
+                    &state,
+                    paused,
+                    pending_edit,
+                    Timestamp::now(),
+                )))
+            }
+            Command::Sent(context) => {
+                let mut messages = db.fetch_outbound_messages(&context).await?;
+                let skip = messages.len().saturating_sub(SENT_LIMIT);
+                Ok(Response::SentMessages(messages.split_off(skip)))
+            }
+            Command::Received(context) => {
+                let mut records = db.fetch_verification_records(&context).await?;
+                let skip = records.len().saturating_sub(RECEIVED_LIMIT);
+                Ok(Response::VerificationRecords(records.split_off(skip)))
+            }
+            Command::Verify(context, fields) => {
+                let addr = context.address.clone();
 
                 // Check if _all_ should be verified (respectively the full identity)
                 #[allow(clippy::collapsible_if)]
@@ -202,6 +1232,80 @@ pub async fn process_admin<'a>(db: &'a Database, command: Command) -> Response {
 
                 Ok(Response::Verified(addr, fields))
             }
+            Command::Erroneous(context, reason) => {
+                let reason = reason.unwrap_or_else(|| "Judged as erroneous by admin".to_string());
+                if db.judge_erroneous(&context, &reason).await? {
+                    info!("{:?} judged as erroneous: {}", context, reason);
+                    Ok(Response::JudgedErroneous(context.address))
+                } else {
+                    Ok(Response::IdentityNotFound)
+                }
+            }
+            Command::SetJudgement(context, judgement_type) => {
+                if db.set_judgement_type(&context, judgement_type).await? {
+                    info!("{:?} will be judged as {:?}", context, judgement_type);
+                    Ok(Response::JudgementTypeSet(context.address, judgement_type))
+                } else {
+                    Ok(Response::IdentityNotFound)
+                }
+            }
+            Command::Approvals => Ok(Response::PendingApprovals(
+                db.fetch_pending_approvals().await?,
+            )),
+            Command::Reviews => Ok(Response::ManualReviews(
+                db.fetch_manual_review_states().await?,
+            )),
+            Command::Search(term) => Ok(Response::SearchResults(
+                db.search_identities(&term, SEARCH_LIMIT).await?,
+            )),
+            Command::Blocked => Ok(Response::BlockedAddresses(
+                db.fetch_blocked_addresses().await?,
+            )),
+            Command::BlocklistHistory(context) => Ok(Response::BlocklistHistory(
+                db.fetch_blocklist_audit(&context).await?,
+            )),
+            Command::Reserved => Ok(Response::ReservedDisplayNames(
+                db.fetch_reserved_display_names().await?,
+            )),
+            // Handled by `process_admin_with_approval`, which knows the
+            // identity of the admin for the audit trail.
+            Command::Block(_, _)
+            | Command::Unblock(_)
+            | Command::PauseSubmissions(_)
+            | Command::PauseAdapter(_)
+            | Command::PauseRequests(_)
+            | Command::ReserveName(_)
+            | Command::ReservePattern(_)
+            | Command::Unreserve(_) => Ok(Response::InternalError),
+            // Approvals require the identity of the admin, which is only known
+            // by `process_admin_with_approval`.
+            Command::Approve(_) => Ok(Response::ApprovalsDisabled),
+            Command::Confirm(_) => Ok(Response::ConfirmationNotFound),
+            Command::ResumeSubmissions(chain) => {
+                if db.resume_submissions(chain).await? {
+                    info!("Judgement submissions for {:?} resumed", chain);
+                    Ok(Response::SubmissionsResumed(chain))
+                } else {
+                    Ok(Response::SubmissionsNotPaused)
+                }
+            }
+            Command::ResumeAdapter(adapter) => {
+                if db.resume_adapter(&adapter).await? {
+                    info!("{} adapter resumed", adapter);
+                    Ok(Response::AdapterResumed(adapter))
+                } else {
+                    Ok(Response::AdapterNotPaused)
+                }
+            }
+            Command::ResumeRequests(chain) => {
+                if db.resume_requests(chain).await? {
+                    info!("New judgement requests for {:?} resumed", chain);
+                    Ok(Response::RequestsResumed(chain))
+                } else {
+                    Ok(Response::RequestsNotPaused)
+                }
+            }
+            Command::Diagnostics => Ok(Response::Diagnostics(Diagnostics::collect(db).await)),
             Command::Help => Ok(Response::Help),
         }
     };
@@ -217,100 +1321,332 @@ pub async fn process_admin<'a>(db: &'a Database, command: Command) -> Response {
     }
 }
 
-/// Convenience function for creating a full identity context when only the
-/// address itself is present. Only supports Kusama and Polkadot for now.
-pub fn create_context(address: ChainAddress) -> IdentityContext {
-    let chain = if address.as_str().starts_with('1') {
-        ChainName::Polkadot
-    } else {
-        ChainName::Kusama
-    };
-
-    IdentityContext { address, chain }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::primitives::JudgementState;
+    use crate::primitives::{IdentityFieldValue, JudgementState, MessageId, ProviderMetadata};
+    use std::str::FromStr;
 
     #[test]
-    fn command_status() {
-        let resp = Command::from_str("status Alice").unwrap();
+    fn command_approval_action() {
+        let cmd = Command::from_str(
+            "verify polkadot:1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP all",
+        )
+        .unwrap();
+        let action = cmd.as_approval_action().unwrap();
         assert_eq!(
-            resp,
-            Command::Status(ChainAddress::from("Alice".to_string()))
+            action,
+            ApprovalAction::FullVerification(IdentityContext::alice())
         );
+        assert_eq!(action.to_command(), cmd);
+        // The displayed action can be parsed again.
+        assert_eq!(Command::from_str(&action.to_string()).unwrap(), cmd);
+
+        let cmd = Command::from_str("verify 1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP email")
+            .unwrap();
+        assert_eq!(cmd.as_approval_action(), None);
+
+        let cmd = Command::from_str(
+            "erroneous polkadot:1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP impersonation",
+        )
+        .unwrap();
+        let action = cmd.as_approval_action().unwrap();
+        assert_eq!(action.to_command(), cmd);
+        assert_eq!(Command::from_str(&action.to_string()).unwrap(), cmd);
+    }
+
+    #[test]
+    fn response_from_parse_error() {
+        let resp: Response =
+            Command::from_str("verify 1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP pgp")
+                .unwrap_err()
+                .into();
+        assert_eq!(resp, Response::InvalidSyntax(Some("pgp".to_string())));
+
+        let resp: Response = Command::from_str("hello").unwrap_err().into();
+        assert_eq!(resp, Response::UnknownCommand);
+    }
+
+    #[test]
+    fn response_diagnostics() {
+        let now = Timestamp::now();
+        let diag = Diagnostics {
+            pending: Some(PendingOverview {
+                per_chain: BTreeMap::from([
+                    ("kusama".to_string(), 3),
+                    ("polkadot".to_string(), 12),
+                ]),
+                unverified_fields: BTreeMap::from([
+                    ("email".to_string(), 9),
+                    ("twitter".to_string(), 4),
+                ]),
+            }),
+            queue_depths: vec![("email".to_string(), 0), ("matrix".to_string(), 2)],
+            watcher_last_messages: vec![("polkadot".to_string(), Timestamp::from(now.raw() - 4))],
+            paused_chains: vec!["kusama".to_string()],
+            paused_adapters: vec!["twitter".to_string()],
+            paused_requests: vec!["all".to_string()],
+            db_connected: true,
+            generated_at: now,
+        };
 
-        let resp = Command::from_str("status  Alice").unwrap();
         assert_eq!(
-            resp,
-            Command::Status(ChainAddress::from("Alice".to_string()))
+            Response::Diagnostics(diag.clone()).to_string(),
+            "DB: ok\n\
+             Pending: kusama 3, polkadot 12\n\
+             Unverified: email 9, twitter 4\n\
+             Paused: kusama\n\
+             Paused adapters: twitter\n\
+             Paused requests: all\n\
+             Queues: email 0, matrix 2\n\
+             Watcher: polkadot 4s ago"
         );
 
-        let resp = Command::from_str("status");
-        assert!(resp.is_err())
+        let diag = Diagnostics {
+            pending: None,
+            queue_depths: vec![],
+            watcher_last_messages: vec![],
+            paused_chains: vec![],
+            paused_adapters: vec![],
+            paused_requests: vec![],
+            db_connected: false,
+            ..diag
+        };
+        assert_eq!(
+            diag.to_string(),
+            "DB: unreachable\nPending: unknown\nQueues: none\nWatcher: none"
+        );
     }
 
     #[test]
-    fn command_verify() {
-        let resp = Command::from_str("verify Alice email").unwrap();
+    #[ignore]
+    fn response_status_debug() {
+        let resp = Response::Status(JudgementState::alice().into());
+        println!("{}", resp);
+    }
+
+    #[test]
+    fn response_sent_messages() {
+        let message = OutboundMessage {
+            adapter: "email".to_string(),
+            context: IdentityContext::alice(),
+            to: "alice@email.com".to_string(),
+            field: Some("email".to_string()),
+            content: "1a2b3c".to_string(),
+            sandbox: false,
+            delivered: false,
+            error: Some("connection refused".to_string()),
+            timestamp: Timestamp::from(1_700_000_000),
+        };
+
         assert_eq!(
-            resp,
-            Command::Verify(
-                ChainAddress::from("Alice".to_string()),
-                vec![RawFieldName::Email]
-            )
+            Response::SentMessages(vec![message.clone()]).to_string(),
+            "1700000000 email email to alice@email.com (failed: connection refused): 1a2b3c"
         );
 
-        let resp = Command::from_str("verify Alice email displayname").unwrap();
+        let message = OutboundMessage {
+            field: None,
+            delivered: true,
+            error: None,
+            ..message
+        };
         assert_eq!(
-            resp,
-            Command::Verify(
-                ChainAddress::from("Alice".to_string()),
-                vec![RawFieldName::Email, RawFieldName::DisplayName]
-            )
+            Response::SentMessages(vec![message]).to_string(),
+            "1700000000 email unknown field to alice@email.com (delivered): 1a2b3c"
         );
-
-        let resp = Command::from_str("verify Alice email display_name").unwrap();
         assert_eq!(
-            resp,
-            Command::Verify(
-                ChainAddress::from("Alice".to_string()),
-                vec![RawFieldName::Email, RawFieldName::DisplayName]
-            )
+            Response::SentMessages(vec![]).to_string(),
+            "No messages were sent to this identity"
         );
+    }
+
+    #[test]
+    fn response_verification_records() {
+        let record = VerificationRecord {
+            context: IdentityContext::alice(),
+            origin: ExternalMessageType::Matrix("@alice:matrix.org".to_string()),
+            message_id: MessageId::from(0u64),
+            outcome: VerificationOutcome::Verified,
+            cause: None,
+            content_hash: None,
+            metadata: Some(ProviderMetadata {
+                provider_id: Some("$1a2b3c".to_string()),
+                homeserver: Some("matrix.org".to_string()),
+                ..Default::default()
+            }),
+            timestamp: Timestamp::from(1_700_000_000),
+        };
 
-        let resp = Command::from_str("verify Alice all").unwrap();
         assert_eq!(
-            resp,
-            Command::Verify(
-                ChainAddress::from("Alice".to_string()),
-                vec![RawFieldName::All]
-            )
+            Response::VerificationRecords(vec![record.clone()]).to_string(),
+            "1700000000 matrix from @alice:matrix.org (verified): $1a2b3c"
         );
 
-        let resp = Command::from_str("verify Alice");
-        assert!(resp.is_err());
+        let record = VerificationRecord {
+            outcome: VerificationOutcome::Failed,
+            cause: Some(FailureCause::WrongChallenge),
+            content_hash: Some("0011223344556677".to_string()),
+            metadata: None,
+            ..record
+        };
+        assert_eq!(
+            Response::VerificationRecords(vec![record]).to_string(),
+            "1700000000 matrix from @alice:matrix.org (failed, wrong challenge): no provider ID"
+        );
+        assert_eq!(
+            Response::VerificationRecords(vec![]).to_string(),
+            "No messages were received from this identity"
+        );
     }
 
     #[test]
-    fn command_help() {
-        let resp = Command::from_str("help").unwrap();
-        assert_eq!(resp, Command::Help);
+    fn explain_judgement_decision() {
+        let now = Timestamp::now();
+        let mut state = JudgementState::alice();
+        state
+            .get_field_mut(&IdentityFieldValue::ALICE_DISPLAY_NAME())
+            .challenge = ChallengeType::DisplayNameCheck {
+            passed: true,
+            violations: vec![],
+            reserved: None,
+        };
+        state
+            .get_field_mut(&IdentityFieldValue::ALICE_TWITTER())
+            .expected_message_mut()
+            .set_verified();
+        state
+            .get_field_mut(&IdentityFieldValue::ALICE_MATRIX())
+            .expected_message_mut()
+            .set_verified();
+        let email = state.get_field_mut(&IdentityFieldValue::ALICE_EMAIL());
+        email.expected_message_mut().set_verified();
+        email.failed_attempts = 2;
 
-        let resp = Command::from_str(" help  ").unwrap();
-        assert_eq!(resp, Command::Help);
+        let explanation = Explanation::new(&state, false, None, now);
+        assert_eq!(
+            explanation.to_string(),
+            "Identity: polkadot:1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP\n\
+             Field display_name: verified\n\
+             Field email: waiting for the second challenge, 2 failed attempt(s)\n\
+             Field twitter: verified\n\
+             Field matrix: verified\n\
+             [no] all fields verified (3 of 4)\n\
+             [no] marked as fully verified\n\
+             [ok] not judged yet\n\
+             [ok] submissions not paused\n\
+             [no] judgement delay elapsed (not scheduled)\n\
+             Decision: waiting for the user to verify 1 more field(s)"
+        );
+
+        // All fields verified, but the flag was never set.
+        state
+            .get_field_mut(&IdentityFieldValue::ALICE_EMAIL())
+            .expected_second_mut()
+            .set_verified();
+        let explanation = Explanation::new(&state, false, None, now);
+        assert!(explanation.decision.starts_with("stuck"));
 
-        let resp = Command::from_str("help stuff");
-        assert!(resp.is_err());
+        state.is_fully_verified = true;
+        state.issue_judgement_at = Some(Timestamp::from(now.raw() + 60));
+        let explanation =
+            Explanation::new(&state, false, Some(Timestamp::from(now.raw() + 30)), now);
+        assert_eq!(explanation.decision, "waiting for the judgement delay");
+        assert!(explanation
+            .to_string()
+            .contains("[no] judgement delay elapsed (in 60s)\nPending edit: applied in 30s\n"));
+
+        state.issue_judgement_at = Some(Timestamp::from(now.raw() - 10));
+        let explanation = Explanation::new(&state, true, None, now);
+        assert_eq!(
+            explanation.decision,
+            "withheld, submissions are paused for polkadot"
+        );
+        let explanation = Explanation::new(&state, false, None, now);
+        assert_eq!(explanation.decision, "submitted with the next run");
+        assert!(explanation.checks.iter().all(|(_, passed)| *passed));
+
+        state.judgement_submitted = true;
+        let explanation = Explanation::new(&state, false, None, now);
+        assert_eq!(explanation.decision, "judged, nothing to do");
     }
 
     #[test]
-    #[ignore]
-    fn response_status_debug() {
-        let resp = Response::Status(JudgementState::alice().into());
-        println!("{}", resp);
+    fn command_severity() {
+        let severity = |cmd: &str| Command::from_str(cmd).unwrap().severity();
+
+        assert_eq!(
+            severity("status 1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP"),
+            CommandSeverity::ReadOnly
+        );
+        assert_eq!(
+            severity("verify 1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP email"),
+            CommandSeverity::Modifying
+        );
+        assert_eq!(
+            severity("verify 1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP email all"),
+            CommandSeverity::Destructive
+        );
+        assert_eq!(
+            severity("pause-adapter email"),
+            CommandSeverity::Destructive
+        );
+        assert_eq!(severity("resume-adapter email"), CommandSeverity::Modifying);
+        assert_eq!(severity("reserve Polkadot"), CommandSeverity::Modifying);
+        assert_eq!(severity("reserved"), CommandSeverity::ReadOnly);
+        assert!(CommandSeverity::Destructive > CommandSeverity::Modifying);
+    }
+
+    #[test]
+    fn confirm_commands() {
+        let mut confirmations = PendingConfirmations::default();
+        let admin = MatrixHandle::from("@admin:matrix.org");
+        let other = MatrixHandle::from("@other:matrix.org");
+        let now = Timestamp::from(1_000);
+
+        let code = confirmations.request_at(&admin, "!room", Command::Diagnostics, now);
+        assert_eq!(code.len(), 6);
+
+        // Only the same admin in the same room can confirm.
+        assert_eq!(
+            confirmations.confirm_at(&other, "!room", &code, now),
+            Err(Response::ConfirmationNotFound)
+        );
+        assert_eq!(
+            confirmations.confirm_at(&admin, "!other", &code, now),
+            Err(Response::ConfirmationNotFound)
+        );
+        assert_eq!(
+            confirmations.confirm_at(&admin, "!room", &code, now),
+            Ok(Command::Diagnostics)
+        );
+        // Can only be confirmed once.
+        assert_eq!(
+            confirmations.confirm_at(&admin, "!room", &code, now),
+            Err(Response::ConfirmationNotFound)
+        );
+
+        // A wrong code discards the command.
+        let code = confirmations.request_at(&admin, "!room", Command::Diagnostics, now);
+        assert_eq!(
+            confirmations.confirm_at(&admin, "!room", "000000x", now),
+            Err(Response::ConfirmationInvalid)
+        );
+        assert_eq!(
+            confirmations.confirm_at(&admin, "!room", &code, now),
+            Err(Response::ConfirmationNotFound)
+        );
+
+        // Expires after the window.
+        let code = confirmations.request_at(&admin, "!room", Command::Diagnostics, now);
+        assert_eq!(
+            confirmations.confirm_at(
+                &admin,
+                "!room",
+                &code,
+                Timestamp::from(1_000 + CONFIRMATION_WINDOW)
+            ),
+            Err(Response::ConfirmationNotFound)
+        );
     }
 
     #[test]
@@ -0,0 +1,897 @@
+use crate::primitives::{ChainAddress, ChainName, IdentityContext, JudgementType};
+use crate::reserved_names::compile_pattern;
+use std::str::FromStr;
+
+pub type Result<T> = std::result::Result<T, ParseError>;
+
+// Shorter search terms would match most identities.
+pub const MIN_SEARCH_TERM_LENGTH: usize = 3;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseError {
+    // The message is not a command at all, e.g. regular chat between admins.
+    UnknownCommand,
+    // The message is a command, but with invalid arguments.
+    InvalidSyntax(Option<String>),
+}
+
+/// An admin command, as sent to the Matrix bot. Parsing is kept free of any I/O
+/// so it can be shared by other admin interfaces.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Command {
+    Status(IdentityContext),
+    // Dry-run of the judgement decision.
+    Explain(IdentityContext),
+    // Messages which were sent to the identity.
+    Sent(IdentityContext),
+    // Verification outcomes of the messages received from the identity.
+    Received(IdentityContext),
+    Verify(IdentityContext, Vec<RawFieldName>),
+    // Judges the identity as erroneous, with an optional reason which is sent
+    // to the user.
+    Erroneous(IdentityContext, Option<String>),
+    // Judgement which is submitted once the identity is fully verified.
+    SetJudgement(IdentityContext, JudgementType),
+    Approve(String),
+    Approvals,
+    // Code which was sent to the admin to confirm the previous command.
+    Confirm(String),
+    Reviews,
+    Search(String),
+    // Optional reason for the audit trail.
+    Block(IdentityContext, Option<String>),
+    Unblock(IdentityContext),
+    Blocked,
+    BlocklistHistory(IdentityContext),
+    // Display names which always fail the display name check.
+    ReserveName(String),
+    // Regular expression, validated when parsed.
+    ReservePattern(String),
+    // Removes a reserved name or pattern.
+    Unreserve(String),
+    Reserved,
+    PauseSubmissions(ChainName),
+    ResumeSubmissions(ChainName),
+    // Name of the adapter service, e.g. `twitter`.
+    PauseAdapter(String),
+    ResumeAdapter(String),
+    // New judgement requests of the network, or of all networks if not set.
+    PauseRequests(Option<ChainName>),
+    ResumeRequests(Option<ChainName>),
+    Diagnostics,
+    Help,
+}
+
+// Keywords of all commands. Messages starting with another word are not meant
+// for the bot, e.g. chat in the admin room.
+const KEYWORDS: &[&str] = &[
+    "status",
+    "explain",
+    "sent",
+    "received",
+    "verify",
+    "erroneous",
+    "judgement",
+    "approve",
+    "confirm",
+    "approvals",
+    "reviews",
+    "search",
+    "block",
+    "unblock",
+    "blocked",
+    "reserve",
+    "reserve-pattern",
+    "unreserve",
+    "reserved",
+    "pause",
+    "resume",
+    "pause-adapter",
+    "resume-adapter",
+    "pause-requests",
+    "resume-requests",
+    "diag",
+    "help",
+];
+
+impl FromStr for Command {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let tokens = match tokenize(s) {
+            Ok(tokens) => tokens,
+            // Unclosed quotes are only reported for commands, not for chat
+            // like "I'll check later".
+            Err(err) => {
+                let keyword = s.split_whitespace().next().unwrap_or_default();
+                return if KEYWORDS.contains(&keyword.to_lowercase().as_str()) {
+                    Err(err)
+                } else {
+                    Err(ParseError::UnknownCommand)
+                };
+            }
+        };
+        let (keyword, args) = match tokens.split_first() {
+            Some((keyword, args)) => (keyword.to_lowercase(), args),
+            None => return Err(ParseError::UnknownCommand),
+        };
+
+        match keyword.as_str() {
+            "status" => match args {
+                [target] => Ok(Command::Status(parse_target(target)?)),
+                _ => Err(ParseError::UnknownCommand),
+            },
+            "explain" => match args {
+                [target] => Ok(Command::Explain(parse_target(target)?)),
+                _ => Err(ParseError::UnknownCommand),
+            },
+            "sent" => match args {
+                [target] => Ok(Command::Sent(parse_target(target)?)),
+                _ => Err(ParseError::UnknownCommand),
+            },
+            "received" => match args {
+                [target] => Ok(Command::Received(parse_target(target)?)),
+                _ => Err(ParseError::UnknownCommand),
+            },
+            "verify" => match args {
+                [target, fields @ ..] if !fields.is_empty() => Ok(Command::Verify(
+                    parse_target(target)?,
+                    fields
+                        .iter()
+                        .map(|s| RawFieldName::from_str(s))
+                        .collect::<Result<Vec<RawFieldName>>>()?,
+                )),
+                _ => Err(ParseError::UnknownCommand),
+            },
+            "erroneous" => match args {
+                [target] => Ok(Command::Erroneous(parse_target(target)?, None)),
+                [target, reason @ ..] => Ok(Command::Erroneous(
+                    parse_target(target)?,
+                    Some(reason.join(" ")),
+                )),
+                _ => Err(ParseError::UnknownCommand),
+            },
+            "judgement" => match args {
+                [target, ty] => Ok(Command::SetJudgement(
+                    parse_target(target)?,
+                    parse_judgement_type(ty)?,
+                )),
+                _ => Err(ParseError::UnknownCommand),
+            },
+            "approve" => match args {
+                [id] => Ok(Command::Approve(id.to_string())),
+                _ => Err(ParseError::UnknownCommand),
+            },
+            "confirm" => match args {
+                [code] => Ok(Command::Confirm(code.to_lowercase())),
+                _ => Err(ParseError::UnknownCommand),
+            },
+            "approvals" if args.is_empty() => Ok(Command::Approvals),
+            "reviews" if args.is_empty() => Ok(Command::Reviews),
+            "search" if !args.is_empty() => {
+                // Display names can contain spaces, even if not quoted.
+                let term = args.join(" ");
+                if term.trim().chars().count() < MIN_SEARCH_TERM_LENGTH {
+                    return Err(ParseError::InvalidSyntax(Some(term)));
+                }
+
+                Ok(Command::Search(term.trim().to_string()))
+            }
+            "block" => match args {
+                [target] => Ok(Command::Block(parse_target(target)?, None)),
+                [target, reason @ ..] => Ok(Command::Block(
+                    parse_target(target)?,
+                    Some(reason.join(" ")),
+                )),
+                _ => Err(ParseError::UnknownCommand),
+            },
+            "unblock" => match args {
+                [target] => Ok(Command::Unblock(parse_target(target)?)),
+                _ => Err(ParseError::UnknownCommand),
+            },
+            "blocked" => match args {
+                [] => Ok(Command::Blocked),
+                [target] => Ok(Command::BlocklistHistory(parse_target(target)?)),
+                _ => Err(ParseError::UnknownCommand),
+            },
+            "reserve" if !args.is_empty() => Ok(Command::ReserveName(args.join(" "))),
+            "reserve-pattern" if !args.is_empty() => {
+                let pattern = args.join(" ");
+                if compile_pattern(&pattern).is_err() {
+                    return Err(ParseError::InvalidSyntax(Some(pattern)));
+                }
+
+                Ok(Command::ReservePattern(pattern))
+            }
+            "unreserve" if !args.is_empty() => Ok(Command::Unreserve(args.join(" "))),
+            "reserved" if args.is_empty() => Ok(Command::Reserved),
+            "pause" => match args {
+                [chain] => Ok(Command::PauseSubmissions(parse_chain(chain)?)),
+                _ => Err(ParseError::UnknownCommand),
+            },
+            "resume" => match args {
+                [chain] => Ok(Command::ResumeSubmissions(parse_chain(chain)?)),
+                _ => Err(ParseError::UnknownCommand),
+            },
+            "pause-adapter" => match args {
+                [name] => Ok(Command::PauseAdapter(parse_adapter(name)?)),
+                _ => Err(ParseError::UnknownCommand),
+            },
+            "resume-adapter" => match args {
+                [name] => Ok(Command::ResumeAdapter(parse_adapter(name)?)),
+                _ => Err(ParseError::UnknownCommand),
+            },
+            "pause-requests" => match args {
+                [] => Ok(Command::PauseRequests(None)),
+                [chain] => Ok(Command::PauseRequests(Some(parse_chain(chain)?))),
+                _ => Err(ParseError::UnknownCommand),
+            },
+            "resume-requests" => match args {
+                [] => Ok(Command::ResumeRequests(None)),
+                [chain] => Ok(Command::ResumeRequests(Some(parse_chain(chain)?))),
+                _ => Err(ParseError::UnknownCommand),
+            },
+            "diag" if args.is_empty() => Ok(Command::Diagnostics),
+            "help" if args.is_empty() => Ok(Command::Help),
+            _ => Err(ParseError::UnknownCommand),
+        }
+    }
+}
+
+/// Splits the input on (unicode) whitespace. Single or double quotes group
+/// multiple words into one token, e.g. when an address is copied with quotes.
+fn tokenize(s: &str) -> Result<Vec<String>> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    // Tracks whether a (possibly empty) quoted token was started.
+    let mut started = false;
+
+    for c in s.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                started = true;
+            }
+            None if c.is_whitespace() => {
+                if started || !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                    started = false;
+                }
+            }
+            None => current.push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(ParseError::InvalidSyntax(Some(current)));
+    }
+
+    if started || !current.is_empty() {
+        tokens.push(current);
+    }
+
+    Ok(tokens)
+}
+
+fn parse_chain(s: &str) -> Result<ChainName> {
+    s.parse()
+        .map_err(|_| ParseError::InvalidSyntax(Some(s.to_string())))
+}
+
+fn parse_judgement_type(s: &str) -> Result<JudgementType> {
+    match s.replace(['-', '_'], "").to_lowercase().as_str() {
+        "reasonable" => Ok(JudgementType::Reasonable),
+        "knowngood" => Ok(JudgementType::KnownGood),
+        "lowquality" => Ok(JudgementType::LowQuality),
+        _ => Err(ParseError::InvalidSyntax(Some(s.to_string()))),
+    }
+}
+
+fn parse_adapter(s: &str) -> Result<String> {
+    match s.to_lowercase().as_str() {
+        name @ ("email" | "matrix" | "twitter" | "web") => Ok(name.to_string()),
+        _ => Err(ParseError::InvalidSyntax(Some(s.to_string()))),
+    }
+}
+
+/// Parses the target identity of a command, optionally prefixed with the chain
+/// name (`polkadot:<ADDR>`, `kusama:<ADDR>`). Without prefix, the chain is
+/// derived from the address.
+fn parse_target(s: &str) -> Result<IdentityContext> {
+    let (chain, address) = match s.split_once(':') {
+        Some((prefix, address)) => {
+            let chain =
+                parse_chain(prefix).map_err(|_| ParseError::InvalidSyntax(Some(s.to_string())))?;
+
+            (Some(chain), address)
+        }
+        None => (None, s),
+    };
+
+    if address.is_empty() || address.contains(':') {
+        return Err(ParseError::InvalidSyntax(Some(s.to_string())));
+    }
+
+    let context = create_context(ChainAddress::from(address.to_string()));
+
+    // Reject addresses which obviously do not belong to the specified chain,
+    // in order to avoid verifying the wrong identity.
+    match chain {
+        Some(chain) if chain != context.chain => {
+            Err(ParseError::InvalidSyntax(Some(s.to_string())))
+        }
+        _ => Ok(context),
+    }
+}
+
+/// Convenience function for creating a full identity context when only the
+/// address itself is present. The chain is derived from the SS58 prefix of the
+/// address, addresses which cannot be decoded are assumed to be Polkadot
+/// addresses if starting with `1`, Kusama addresses otherwise.
+fn create_context(address: ChainAddress) -> IdentityContext {
+    let chain = address
+        .ss58_prefix()
+        .ok()
+        .and_then(ChainName::from_ss58_prefix)
+        .unwrap_or_else(|| {
+            if address.as_str().starts_with('1') {
+                ChainName::Polkadot
+            } else {
+                ChainName::Kusama
+            }
+        });
+
+    IdentityContext { address, chain }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum RawFieldName {
+    LegalName,
+    DisplayName,
+    Email,
+    Web,
+    Twitter,
+    Matrix,
+    // Represents the full identity
+    All,
+}
+
+impl std::fmt::Display for RawFieldName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", {
+            match self {
+                RawFieldName::LegalName => "legal_name",
+                RawFieldName::DisplayName => "display_name",
+                RawFieldName::Email => "email",
+                RawFieldName::Web => "web",
+                RawFieldName::Twitter => "twitter",
+                RawFieldName::Matrix => "matrix",
+                RawFieldName::All => "all",
+            }
+        })
+    }
+}
+
+impl FromStr for RawFieldName {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // Convenience handler.
+        let s = s.trim().replace('-', "").replace('_', "").to_lowercase();
+
+        let f = match s.as_str() {
+            "legalname" => RawFieldName::LegalName,
+            "displayname" => RawFieldName::DisplayName,
+            "email" => RawFieldName::Email,
+            "web" => RawFieldName::Web,
+            "twitter" => RawFieldName::Twitter,
+            "matrix" => RawFieldName::Matrix,
+            "all" => RawFieldName::All,
+            _ => return Err(ParseError::InvalidSyntax(Some(s))),
+        };
+
+        Ok(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLKADOT_ADDR: &str = "1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP";
+    const KUSAMA_ADDR: &str = "FHxoGrCJ4xjZgjQiexjHkFHGbWbxwbNKMjFtBDAzybt7qaK";
+
+    fn polkadot() -> IdentityContext {
+        IdentityContext {
+            address: ChainAddress::from(POLKADOT_ADDR.to_string()),
+            chain: ChainName::Polkadot,
+        }
+    }
+
+    fn kusama() -> IdentityContext {
+        IdentityContext {
+            address: ChainAddress::from(KUSAMA_ADDR.to_string()),
+            chain: ChainName::Kusama,
+        }
+    }
+
+    fn parse(s: &str) -> Result<Command> {
+        Command::from_str(s)
+    }
+
+    #[test]
+    fn tokenize_whitespace() {
+        assert_eq!(tokenize("").unwrap(), Vec::<String>::new());
+        assert_eq!(tokenize("   ").unwrap(), Vec::<String>::new());
+        assert_eq!(tokenize("a b").unwrap(), vec!["a", "b"]);
+        assert_eq!(tokenize("  a   b  ").unwrap(), vec!["a", "b"]);
+        assert_eq!(tokenize("a\tb\nc").unwrap(), vec!["a", "b", "c"]);
+        // Non-breaking and ideographic spaces.
+        assert_eq!(tokenize("a\u{a0}b\u{3000}c").unwrap(), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn tokenize_quotes() {
+        assert_eq!(tokenize(r#""a b" c"#).unwrap(), vec!["a b", "c"]);
+        assert_eq!(tokenize("'a b' c").unwrap(), vec!["a b", "c"]);
+        assert_eq!(tokenize(r#"'a "b"' c"#).unwrap(), vec![r#"a "b""#, "c"]);
+        assert_eq!(tokenize(r#"a"b c"d"#).unwrap(), vec!["ab cd"]);
+        assert_eq!(tokenize(r#"a "" b"#).unwrap(), vec!["a", "", "b"]);
+
+        assert_eq!(
+            tokenize(r#"a "b c"#),
+            Err(ParseError::InvalidSyntax(Some("b c".to_string())))
+        );
+        assert!(tokenize("a 'b").is_err());
+
+        // Unclosed quotes are only an error for commands.
+        assert_eq!(parse("I'll check"), Err(ParseError::UnknownCommand));
+        assert_eq!(parse("don't"), Err(ParseError::UnknownCommand));
+        assert_eq!(
+            parse("status 'abc"),
+            Err(ParseError::InvalidSyntax(Some("abc".to_string())))
+        );
+    }
+
+    #[test]
+    fn tokenize_unicode() {
+        assert_eq!(
+            tokenize("@ålice:mätrix.org 👋").unwrap(),
+            vec!["@ålice:mätrix.org", "👋"]
+        );
+        assert_eq!(tokenize("'Алиса Боб'").unwrap(), vec!["Алиса Боб"]);
+    }
+
+    #[test]
+    fn command_status() {
+        let expected = Ok(Command::Status(polkadot()));
+
+        assert_eq!(parse(&format!("status {}", POLKADOT_ADDR)), expected);
+        assert_eq!(parse(&format!("  status   {}  ", POLKADOT_ADDR)), expected);
+        assert_eq!(parse(&format!("Status {}", POLKADOT_ADDR)), expected);
+        assert_eq!(parse(&format!("status '{}'", POLKADOT_ADDR)), expected);
+        assert_eq!(parse(&format!("status \"{}\"", POLKADOT_ADDR)), expected);
+        assert_eq!(parse(&format!("status\t{}", POLKADOT_ADDR)), expected);
+
+        assert_eq!(
+            parse(&format!("status {}", KUSAMA_ADDR)),
+            Ok(Command::Status(kusama()))
+        );
+
+        assert_eq!(parse("status"), Err(ParseError::UnknownCommand));
+        assert_eq!(
+            parse(&format!("status {} {}", POLKADOT_ADDR, KUSAMA_ADDR)),
+            Err(ParseError::UnknownCommand)
+        );
+        // The keyword must be a separate token.
+        assert_eq!(
+            parse(&format!("status{}", POLKADOT_ADDR)),
+            Err(ParseError::UnknownCommand)
+        );
+    }
+
+    #[test]
+    fn command_chain_prefix() {
+        assert_eq!(
+            parse(&format!("status polkadot:{}", POLKADOT_ADDR)),
+            Ok(Command::Status(polkadot()))
+        );
+        assert_eq!(
+            parse(&format!("status Kusama:{}", KUSAMA_ADDR)),
+            Ok(Command::Status(kusama()))
+        );
+        assert_eq!(
+            parse(&format!("verify kusama:{} email", KUSAMA_ADDR)),
+            Ok(Command::Verify(kusama(), vec![RawFieldName::Email]))
+        );
+
+        // The prefix does not match the address.
+        let target = format!("kusama:{}", POLKADOT_ADDR);
+        assert_eq!(
+            parse(&format!("status {}", target)),
+            Err(ParseError::InvalidSyntax(Some(target)))
+        );
+        let target = format!("polkadot:{}", KUSAMA_ADDR);
+        assert_eq!(
+            parse(&format!("verify {} all", target)),
+            Err(ParseError::InvalidSyntax(Some(target)))
+        );
+        let target = format!("westend:{}", KUSAMA_ADDR);
+        assert_eq!(
+            parse(&format!("status {}", target)),
+            Err(ParseError::InvalidSyntax(Some(target)))
+        );
+
+        // Unknown or malformed prefixes.
+        let target = format!("rococo:{}", KUSAMA_ADDR);
+        assert_eq!(
+            parse(&format!("status {}", target)),
+            Err(ParseError::InvalidSyntax(Some(target)))
+        );
+        assert!(parse("status polkadot:").is_err());
+        assert!(parse(&format!("status polkadot:polkadot:{}", POLKADOT_ADDR)).is_err());
+        assert!(parse(&format!("status :{}", POLKADOT_ADDR)).is_err());
+    }
+
+    #[test]
+    fn command_verify() {
+        assert_eq!(
+            parse(&format!("verify {} email", POLKADOT_ADDR)),
+            Ok(Command::Verify(polkadot(), vec![RawFieldName::Email]))
+        );
+        assert_eq!(
+            parse(&format!("verify {} email displayname", POLKADOT_ADDR)),
+            Ok(Command::Verify(
+                polkadot(),
+                vec![RawFieldName::Email, RawFieldName::DisplayName]
+            ))
+        );
+        assert_eq!(
+            parse(&format!(
+                "verify {} Email display_name legal-name",
+                POLKADOT_ADDR
+            )),
+            Ok(Command::Verify(
+                polkadot(),
+                vec![
+                    RawFieldName::Email,
+                    RawFieldName::DisplayName,
+                    RawFieldName::LegalName
+                ]
+            ))
+        );
+        assert_eq!(
+            parse(&format!("verify {} web twitter matrix", KUSAMA_ADDR)),
+            Ok(Command::Verify(
+                kusama(),
+                vec![
+                    RawFieldName::Web,
+                    RawFieldName::Twitter,
+                    RawFieldName::Matrix
+                ]
+            ))
+        );
+        assert_eq!(
+            parse(&format!("verify {} all", POLKADOT_ADDR)),
+            Ok(Command::Verify(polkadot(), vec![RawFieldName::All]))
+        );
+
+        assert_eq!(
+            parse(&format!("verify {}", POLKADOT_ADDR)),
+            Err(ParseError::UnknownCommand)
+        );
+        assert_eq!(parse("verify"), Err(ParseError::UnknownCommand));
+        assert_eq!(
+            parse(&format!("verify {} email pgp", POLKADOT_ADDR)),
+            Err(ParseError::InvalidSyntax(Some("pgp".to_string())))
+        );
+        // A field name containing whitespace is never valid.
+        assert_eq!(
+            parse(&format!("verify {} 'display name'", POLKADOT_ADDR)),
+            Err(ParseError::InvalidSyntax(Some("display name".to_string())))
+        );
+        assert!(parse(&format!("verify {} 'email", POLKADOT_ADDR)).is_err());
+    }
+
+    #[test]
+    fn command_approvals() {
+        assert_eq!(parse("approvals"), Ok(Command::Approvals));
+        assert_eq!(parse(" Approvals "), Ok(Command::Approvals));
+        assert_eq!(parse("approvals stuff"), Err(ParseError::UnknownCommand));
+
+        assert_eq!(
+            parse("approve 1a2b3c4d"),
+            Ok(Command::Approve("1a2b3c4d".to_string()))
+        );
+        assert_eq!(
+            parse("approve '1a2b3c4d'"),
+            Ok(Command::Approve("1a2b3c4d".to_string()))
+        );
+        assert_eq!(parse("approve"), Err(ParseError::UnknownCommand));
+        assert_eq!(parse("approve a b"), Err(ParseError::UnknownCommand));
+    }
+
+    #[test]
+    fn command_confirm() {
+        assert_eq!(
+            parse("confirm 4F2A9C"),
+            Ok(Command::Confirm("4f2a9c".to_string()))
+        );
+        assert_eq!(parse("confirm"), Err(ParseError::UnknownCommand));
+        assert_eq!(parse("confirm a b"), Err(ParseError::UnknownCommand));
+    }
+
+    #[test]
+    fn command_reviews() {
+        assert_eq!(parse("reviews"), Ok(Command::Reviews));
+        assert_eq!(parse(" Reviews "), Ok(Command::Reviews));
+        assert_eq!(parse("reviews stuff"), Err(ParseError::UnknownCommand));
+        assert_eq!(parse("review"), Err(ParseError::UnknownCommand));
+    }
+
+    #[test]
+    fn command_search() {
+        assert_eq!(
+            parse("search 1a2YiG"),
+            Ok(Command::Search("1a2YiG".to_string()))
+        );
+        assert_eq!(
+            parse("Search Alice in  Wonderland"),
+            Ok(Command::Search("Alice in Wonderland".to_string()))
+        );
+        assert_eq!(
+            parse("search ' alice@email '"),
+            Ok(Command::Search("alice@email".to_string()))
+        );
+        assert_eq!(
+            parse("search ab"),
+            Err(ParseError::InvalidSyntax(Some("ab".to_string())))
+        );
+        assert_eq!(parse("search"), Err(ParseError::UnknownCommand));
+    }
+
+    #[test]
+    fn command_block() {
+        assert_eq!(
+            parse(&format!("erroneous {}", POLKADOT_ADDR)),
+            Ok(Command::Erroneous(polkadot(), None))
+        );
+        assert_eq!(
+            parse(&format!("erroneous {} impersonates W3F", KUSAMA_ADDR)),
+            Ok(Command::Erroneous(
+                kusama(),
+                Some("impersonates W3F".to_string())
+            ))
+        );
+        assert_eq!(parse("erroneous"), Err(ParseError::UnknownCommand));
+        assert_eq!(
+            parse(&format!("block {}", POLKADOT_ADDR)),
+            Ok(Command::Block(polkadot(), None))
+        );
+        assert_eq!(
+            parse(&format!("block {} sanctioned account", KUSAMA_ADDR)),
+            Ok(Command::Block(
+                kusama(),
+                Some("sanctioned account".to_string())
+            ))
+        );
+        assert_eq!(
+            parse(&format!("unblock polkadot:{}", POLKADOT_ADDR)),
+            Ok(Command::Unblock(polkadot()))
+        );
+        assert_eq!(parse("blocked"), Ok(Command::Blocked));
+        assert_eq!(
+            parse(&format!("blocked {}", POLKADOT_ADDR)),
+            Ok(Command::BlocklistHistory(polkadot()))
+        );
+
+        assert_eq!(parse("block"), Err(ParseError::UnknownCommand));
+        assert_eq!(
+            parse(&format!("unblock {} now", POLKADOT_ADDR)),
+            Err(ParseError::UnknownCommand)
+        );
+        assert_eq!(
+            parse(&format!("blocked {} {}", POLKADOT_ADDR, KUSAMA_ADDR)),
+            Err(ParseError::UnknownCommand)
+        );
+    }
+
+    #[test]
+    fn command_reserve() {
+        assert_eq!(
+            parse("reserve Web3   Foundation"),
+            Ok(Command::ReserveName("Web3 Foundation".to_string()))
+        );
+        assert_eq!(
+            parse(r"reserve-pattern '^binance\b'"),
+            Ok(Command::ReservePattern(r"^binance\b".to_string()))
+        );
+        assert_eq!(
+            parse("reserve-pattern ^(binance"),
+            Err(ParseError::InvalidSyntax(Some("^(binance".to_string())))
+        );
+        assert_eq!(
+            parse("unreserve Polkadot"),
+            Ok(Command::Unreserve("Polkadot".to_string()))
+        );
+        assert_eq!(parse("reserved"), Ok(Command::Reserved));
+
+        assert_eq!(parse("reserve"), Err(ParseError::UnknownCommand));
+        assert_eq!(parse("reserve-pattern"), Err(ParseError::UnknownCommand));
+        assert_eq!(parse("unreserve"), Err(ParseError::UnknownCommand));
+        assert_eq!(parse("reserved Polkadot"), Err(ParseError::UnknownCommand));
+    }
+
+    #[test]
+    fn derive_chain_from_address() {
+        // Valid SS58 addresses are mapped by their prefix.
+        let generic =
+            ChainAddress::from("5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY".to_string());
+        let kusama =
+            ChainAddress::from("HNZata7iMYWmk5RvZRTiAsSDhV8366zq2YGb3tLH5Upf74F".to_string());
+        assert_eq!(create_context(generic).chain, ChainName::Westend);
+        assert_eq!(create_context(kusama).chain, ChainName::Kusama);
+
+        // Others by their first character.
+        let invalid = ChainAddress::from("1a2b".to_string());
+        assert_eq!(create_context(invalid).chain, ChainName::Polkadot);
+        let invalid = ChainAddress::from("Fa2b".to_string());
+        assert_eq!(create_context(invalid).chain, ChainName::Kusama);
+    }
+
+    #[test]
+    fn command_pause() {
+        assert_eq!(
+            parse("pause polkadot"),
+            Ok(Command::PauseSubmissions(ChainName::Polkadot))
+        );
+        assert_eq!(
+            parse("resume Kusama"),
+            Ok(Command::ResumeSubmissions(ChainName::Kusama))
+        );
+
+        assert_eq!(parse("pause"), Err(ParseError::UnknownCommand));
+        assert_eq!(
+            parse("pause rococo"),
+            Err(ParseError::InvalidSyntax(Some("rococo".to_string())))
+        );
+        assert_eq!(
+            parse("resume polkadot kusama"),
+            Err(ParseError::UnknownCommand)
+        );
+    }
+
+    #[test]
+    fn command_pause_adapter() {
+        assert_eq!(
+            parse("pause-adapter twitter"),
+            Ok(Command::PauseAdapter("twitter".to_string()))
+        );
+        assert_eq!(
+            parse("resume-adapter Matrix"),
+            Ok(Command::ResumeAdapter("matrix".to_string()))
+        );
+
+        assert_eq!(parse("pause-adapter"), Err(ParseError::UnknownCommand));
+        assert_eq!(
+            parse("pause-adapter telegram"),
+            Err(ParseError::InvalidSyntax(Some("telegram".to_string())))
+        );
+        assert_eq!(
+            parse("resume-adapter email matrix"),
+            Err(ParseError::UnknownCommand)
+        );
+    }
+
+    #[test]
+    fn command_pause_requests() {
+        assert_eq!(parse("pause-requests"), Ok(Command::PauseRequests(None)));
+        assert_eq!(
+            parse("pause-requests kusama"),
+            Ok(Command::PauseRequests(Some(ChainName::Kusama)))
+        );
+        assert_eq!(
+            parse("resume-requests Polkadot"),
+            Ok(Command::ResumeRequests(Some(ChainName::Polkadot)))
+        );
+
+        assert_eq!(
+            parse("resume-requests rococo"),
+            Err(ParseError::InvalidSyntax(Some("rococo".to_string())))
+        );
+        assert_eq!(
+            parse("pause-requests polkadot kusama"),
+            Err(ParseError::UnknownCommand)
+        );
+    }
+
+    #[test]
+    fn command_explain() {
+        assert_eq!(
+            parse(&format!("explain {}", POLKADOT_ADDR)),
+            Ok(Command::Explain(polkadot()))
+        );
+        assert_eq!(
+            parse(&format!("Explain kusama:{}", KUSAMA_ADDR)),
+            Ok(Command::Explain(kusama()))
+        );
+        assert_eq!(parse("explain"), Err(ParseError::UnknownCommand));
+        assert_eq!(
+            parse(&format!("explain {} email", POLKADOT_ADDR)),
+            Err(ParseError::UnknownCommand)
+        );
+    }
+
+    #[test]
+    fn command_set_judgement() {
+        assert_eq!(
+            parse(&format!("judgement {} known_good", POLKADOT_ADDR)),
+            Ok(Command::SetJudgement(polkadot(), JudgementType::KnownGood))
+        );
+        assert_eq!(
+            parse(&format!("judgement {} LowQuality", POLKADOT_ADDR)),
+            Ok(Command::SetJudgement(polkadot(), JudgementType::LowQuality))
+        );
+        assert_eq!(
+            parse(&format!("judgement {} reasonable", POLKADOT_ADDR)),
+            Ok(Command::SetJudgement(polkadot(), JudgementType::Reasonable))
+        );
+        assert_eq!(
+            parse(&format!("judgement {} outofdate", POLKADOT_ADDR)),
+            Err(ParseError::InvalidSyntax(Some("outofdate".to_string())))
+        );
+        assert_eq!(
+            parse(&format!("judgement {}", POLKADOT_ADDR)),
+            Err(ParseError::UnknownCommand)
+        );
+    }
+
+    #[test]
+    fn command_sent() {
+        assert_eq!(
+            parse(&format!("sent {}", POLKADOT_ADDR)),
+            Ok(Command::Sent(polkadot()))
+        );
+        assert_eq!(parse("sent"), Err(ParseError::UnknownCommand));
+    }
+
+    #[test]
+    fn command_received() {
+        assert_eq!(
+            parse(&format!("received {}", POLKADOT_ADDR)),
+            Ok(Command::Received(polkadot()))
+        );
+        assert_eq!(parse("received"), Err(ParseError::UnknownCommand));
+    }
+
+    #[test]
+    fn command_diag() {
+        assert_eq!(parse("diag"), Ok(Command::Diagnostics));
+        assert_eq!(parse(" Diag "), Ok(Command::Diagnostics));
+        assert_eq!(parse("diag stuff"), Err(ParseError::UnknownCommand));
+    }
+
+    #[test]
+    fn command_help() {
+        assert_eq!(parse("help"), Ok(Command::Help));
+        assert_eq!(parse(" help  "), Ok(Command::Help));
+        assert_eq!(parse("HELP"), Ok(Command::Help));
+        assert_eq!(parse("help stuff"), Err(ParseError::UnknownCommand));
+        assert_eq!(parse("helpme"), Err(ParseError::UnknownCommand));
+    }
+
+    #[test]
+    fn command_unknown() {
+        assert_eq!(parse(""), Err(ParseError::UnknownCommand));
+        assert_eq!(parse("   "), Err(ParseError::UnknownCommand));
+        assert_eq!(parse("hello there"), Err(ParseError::UnknownCommand));
+        assert_eq!(parse("😀 status"), Err(ParseError::UnknownCommand));
+        assert_eq!(
+            parse(&format!("> status {}", POLKADOT_ADDR)),
+            Err(ParseError::UnknownCommand)
+        );
+    }
+}
@@ -1,12 +1,64 @@
 use std::collections::HashSet;
 
-use crate::adapters::Adapter;
+use crate::adapters::{Adapter, SecondChallenge};
+use crate::message_template::MessageTemplate;
 use crate::primitives::{
-    ExpectedMessage, ExternalMessage, ExternalMessageType, MessageId, Timestamp,
+    ExpectedMessage, ExternalMessage, ExternalMessageType, MessageId, ProviderMetadata, Timestamp,
 };
+use crate::retry::{retry, Backoff, RetryConfig};
 use crate::Result;
 use lettre::transport::smtp::authentication::Credentials;
 use lettre::{Message, SmtpTransport, Transport};
+use mailparse::MailHeader;
+use schemars::JsonSchema;
+use std::time::Duration;
+use tokio::time::sleep;
+
+const DEFAULT_CODE_DIGITS: usize = 6;
+// In seconds
+const DEFAULT_CODE_TTL: u64 = 900;
+// Failed attempts after which a code is no longer accepted.
+const CODE_ATTEMPTS: u32 = 5;
+// Servers may drop IDLE connections after 30 minutes (RFC 2177).
+const MAX_IDLE_DURATION: u64 = 29 * 60;
+// Seconds between two fetches if IDLE is used, since the adapter waits for
+// new emails itself.
+pub const IDLE_POLLING_INTERVAL: u64 = 1;
+// Headers recorded with the verification outcome, besides the `Message-ID`.
+const AUDIT_HEADERS: &[&str] = &["Date", "Received-SPF", "Authentication-Results"];
+
+/// What happens to an email in the watched folder once it was processed.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessedAction {
+    #[default]
+    Keep,
+    MarkAsRead,
+    // Requires `processed_folder`.
+    Move,
+    Delete,
+}
+
+/// Sends short numeric codes as second challenge instead of the long random
+/// string, which is hard to copy from mobile mail clients.
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SecondChallengeCodeConfig {
+    // Number of digits, between 6 and 8. Defaults to 6.
+    pub digits: Option<usize>,
+    // Validity of a code in seconds, defaults to 15 minutes.
+    pub ttl: Option<u64>,
+}
+
+impl SecondChallengeCodeConfig {
+    pub fn generate(&self) -> ExpectedMessage {
+        ExpectedMessage::code(
+            self.digits.unwrap_or(DEFAULT_CODE_DIGITS).clamp(6, 8),
+            self.ttl.unwrap_or(DEFAULT_CODE_TTL),
+            CODE_ATTEMPTS,
+        )
+    }
+}
 
 trait ExtractSender<T> {
     type Error;
@@ -37,6 +89,11 @@ pub struct EmailClientBuilder {
     inbox: Option<String>,
     user: Option<String>,
     password: Option<String>,
+    template: Option<MessageTemplate>,
+    processed_action: ProcessedAction,
+    processed_folder: Option<String>,
+    idle_timeout: Option<u64>,
+    retry: RetryConfig,
 }
 
 impl EmailClientBuilder {
@@ -47,6 +104,11 @@ impl EmailClientBuilder {
             inbox: None,
             user: None,
             password: None,
+            template: None,
+            processed_action: ProcessedAction::Keep,
+            processed_folder: None,
+            idle_timeout: None,
+            retry: RetryConfig::default(),
         }
     }
     pub fn smtp_server(mut self, server: String) -> Self {
@@ -69,8 +131,35 @@ impl EmailClientBuilder {
         self.password = Some(password);
         self
     }
+    pub fn message_template(mut self, template: MessageTemplate) -> Self {
+        self.template = Some(template);
+        self
+    }
+    pub fn processed_action(mut self, action: ProcessedAction, folder: Option<String>) -> Self {
+        self.processed_action = action;
+        self.processed_folder = folder;
+        self
+    }
+    /// Waits for changes of the inbox with IMAP IDLE before fetching, for at
+    /// most the given number of seconds.
+    pub fn idle(mut self, timeout: u64) -> Self {
+        self.idle_timeout = Some(timeout.min(MAX_IDLE_DURATION));
+        self
+    }
+    /// Retries failed deliveries and bounds the delay before reconnecting
+    /// after the IDLE connection failed.
+    pub fn retry(mut self, config: RetryConfig) -> Self {
+        self.retry = config;
+        self
+    }
     #[allow(clippy::or_fun_call)]
     pub fn build(self) -> Result<EmailClient> {
+        if self.processed_action == ProcessedAction::Move && self.processed_folder.is_none() {
+            return Err(anyhow!(
+                "moving processed emails requires `processed_folder` to be configured"
+            ));
+        }
+
         Ok(EmailClient {
             smtp_server: self.server.ok_or(anyhow!("SMTP server not specified"))?,
             imap_server: self
@@ -81,7 +170,17 @@ impl EmailClientBuilder {
             password: self
                 .password
                 .ok_or(anyhow!("password server not specified"))?,
+            template: self.template,
+            processed_action: self.processed_action,
+            processed_folder: self.processed_folder,
+            idle_timeout: self.idle_timeout.map(Duration::from_secs),
+            idle_started: false,
+            idle_supported: true,
+            reconnect: Backoff::from(&self.retry),
+            retry: self.retry,
             cache: HashSet::new(),
+            watermark: None,
+            processed: vec![],
         })
     }
 }
@@ -93,21 +192,160 @@ pub struct EmailClient {
     inbox: String,
     user: String,
     password: String,
+    // Body of the second challenge email, if set.
+    template: Option<MessageTemplate>,
+    processed_action: ProcessedAction,
+    processed_folder: Option<String>,
+    // Set if IMAP IDLE is used instead of polling.
+    idle_timeout: Option<Duration>,
+    // Messages already in the inbox are fetched without waiting.
+    idle_started: bool,
+    // Cleared if the server does not support IDLE, the inbox is then polled
+    // every `idle_timeout`.
+    idle_supported: bool,
+    reconnect: Backoff,
+    retry: RetryConfig,
     // Keep track of messages.
     cache: HashSet<MessageId>,
+    // UID of the last message processed before a restart.
+    watermark: Option<MessageId>,
+    // UIDs of processed messages, on which the processed action is applied
+    // with the next connection.
+    processed: Vec<MessageId>,
+}
+
+type ImapSession = imap::Session<native_tls::TlsStream<std::net::TcpStream>>;
+
+fn connect(imap_server: &str, user: &str, password: &str) -> Result<ImapSession> {
+    let tls = native_tls::TlsConnector::builder().build()?;
+    let client = imap::connect((imap_server, 993), imap_server, &tls)?;
+
+    Ok(client.login(user, password).map_err(|(err, _)| err)?)
+}
+
+// Extracts the `Message-ID` and the audit headers of the email. Only the first
+// occurrence of a header is kept, which is the one added by the receiving
+// server.
+fn email_metadata(headers: &[MailHeader]) -> ProviderMetadata {
+    let find = |name: &str| {
+        headers
+            .iter()
+            .find(|header| header.get_key_ref().eq_ignore_ascii_case(name))
+            .map(|header| header.get_value())
+    };
+
+    ProviderMetadata {
+        provider_id: find("Message-ID"),
+        details: AUDIT_HEADERS
+            .iter()
+            .filter_map(|name| find(name).map(|value| (name.to_lowercase(), value)))
+            .collect(),
+        ..Default::default()
+    }
 }
 
 impl EmailClient {
-    fn request_messages(&mut self) -> Result<Vec<ExternalMessage>> {
-        let tls = native_tls::TlsConnector::builder().build()?;
-        let client = imap::connect((self.imap_server.as_str(), 993), &self.imap_server, &tls)?;
+    // Applies the processed action to the messages processed since the last
+    // connection. On failure, the action is retried with the next connection.
+    fn apply_processed_action(&mut self, imap: &mut ImapSession) -> Result<()> {
+        if self.processed.is_empty() {
+            return Ok(());
+        }
+
+        let uids = self
+            .processed
+            .iter()
+            .map(|id| id.raw().to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+
+        match self.processed_action {
+            ProcessedAction::Keep => {}
+            ProcessedAction::MarkAsRead => {
+                imap.uid_store(&uids, "+FLAGS (\\Seen)")?;
+            }
+            ProcessedAction::Move => {
+                // Checked by the builder.
+                if let Some(folder) = &self.processed_folder {
+                    imap.uid_mv(&uids, folder)?;
+                }
+            }
+            ProcessedAction::Delete => {
+                imap.uid_store(&uids, "+FLAGS (\\Deleted)")?;
+                imap.expunge()?;
+            }
+        }
+
+        debug!(
+            "Applied {:?} to {} processed emails",
+            self.processed_action,
+            self.processed.len()
+        );
+        self.processed.clear();
+
+        Ok(())
+    }
+    // Blocks until the inbox changes or the timeout expires. Returns `false`
+    // without waiting if the server does not support IDLE.
+    async fn wait_for_changes(&self, timeout: Duration) -> Result<bool> {
+        let (imap_server, user, password, inbox) = (
+            self.imap_server.clone(),
+            self.user.clone(),
+            self.password.clone(),
+            self.inbox.clone(),
+        );
 
-        let mut imap = client
-            .login(&self.user, &self.password)
-            .map_err(|(err, _)| err)?;
+        tokio::task::spawn_blocking(move || {
+            let mut imap = connect(&imap_server, &user, &password)?;
+            let supported = imap.capabilities()?.has_str("IDLE");
+            if supported {
+                imap.select(&inbox)?;
+                imap.idle()?.wait_with_timeout(timeout)?;
+            }
+            let _ = imap.logout();
+
+            Result::Ok(supported)
+        })
+        .await?
+    }
+    // Waits for new emails before the next fetch. Connection failures are not
+    // returned, the inbox is fetched after reconnecting in case emails arrived
+    // in the meantime.
+    async fn wait(&mut self, timeout: Duration) {
+        if !self.idle_supported {
+            sleep(timeout).await;
+            return;
+        }
+
+        match self.wait_for_changes(timeout).await {
+            Ok(true) => self.reconnect = Backoff::from(&self.retry),
+            Ok(false) => {
+                warn!(
+                    "IMAP server does not support IDLE, polling every {:?} instead",
+                    timeout
+                );
+                self.idle_supported = false;
+                sleep(timeout).await;
+            }
+            Err(err) => {
+                let delay = self.reconnect.next_delay();
+                warn!(
+                    "IDLE connection to the IMAP server failed, reconnecting in {:?}: {:?}",
+                    delay, err
+                );
+                sleep(delay).await;
+            }
+        }
+    }
+    fn request_messages(&mut self) -> Result<Vec<ExternalMessage>> {
+        let mut imap = connect(&self.imap_server, &self.user, &self.password)?;
 
         imap.select(&self.inbox)?;
 
+        if let Err(err) = self.apply_processed_action(&mut imap) {
+            error!("Failed to apply action to processed emails: {:?}", err);
+        }
+
         // Fetch the messages of the last day. The database keeps track of which messages
         // have been processed.
         //
@@ -129,7 +367,12 @@ impl EmailClient {
             format!("{}:{}", min, max)
         };
 
-        let messages = imap.fetch(query, "(RFC822 UID)")?;
+        // Fetching the body marks the message as read, unless peeked.
+        let items = match self.processed_action {
+            ProcessedAction::Keep => "(RFC822 UID)",
+            _ => "(BODY.PEEK[] UID)",
+        };
+        let messages = imap.fetch(query, items)?;
         let mut parsed_messages = vec![];
         for message in &messages {
             if let Some(body) = message.body() {
@@ -149,7 +392,7 @@ impl EmailClient {
                     .into();
 
                 // Skip message if it was already processed.
-                if self.cache.contains(&id) {
+                if self.cache.contains(&id) || Some(id) <= self.watermark {
                     continue;
                 }
 
@@ -164,6 +407,7 @@ impl EmailClient {
                         .into(),
                     timestamp: Timestamp::now(),
                     values: vec![],
+                    metadata: Some(email_metadata(&mail.headers)),
                 };
 
                 // Add body content.
@@ -206,10 +450,20 @@ impl EmailClient {
             .to(to.parse()?)
             .from(self.user.as_str().parse()?)
             .subject("W3F Registrar Verification Service")
-            .body(format!(
-                "Insert the following challenge into the web interface: {}",
-                message
-            ))?;
+            .body(message.to_string())?;
+
+        // Only the connection is retried, it is kept in the pool of the
+        // transport for the delivery. A failed delivery is not retried, since
+        // the server might have accepted the message anyway (e.g. on a timeout
+        // after DATA), which would send it twice.
+        retry(&self.retry, "email", "SMTP connection", || async {
+            if !smtp.test_connection()? {
+                return Err(anyhow!("SMTP connection is not usable"));
+            }
+
+            Result::Ok(())
+        })
+        .await?;
 
         let _ = smtp.send(&email)?;
 
@@ -219,15 +473,150 @@ impl EmailClient {
 
 #[async_trait]
 impl Adapter for EmailClient {
-    type MessageType = ExpectedMessage;
+    type MessageType = SecondChallenge;
 
     fn name(&self) -> &'static str {
         "email"
     }
+    // IMAP UIDs are strictly increasing within a mailbox.
+    fn has_ordered_ids(&self) -> bool {
+        true
+    }
+    fn set_watermark(&mut self, id: MessageId) {
+        self.watermark = Some(id);
+    }
+    fn mark_processed(&mut self, id: MessageId) {
+        if self.processed_action != ProcessedAction::Keep {
+            self.processed.push(id);
+        }
+    }
     async fn fetch_messages(&mut self) -> Result<Vec<ExternalMessage>> {
+        if let Some(timeout) = self.idle_timeout {
+            if self.idle_started {
+                self.wait(timeout).await;
+            }
+
+            self.idle_started = true;
+        }
+
         self.request_messages()
     }
+    fn render_message(&self, content: &Self::MessageType) -> Option<String> {
+        let SecondChallenge { challenge, state } = content;
+        let message = match (&self.template, challenge.expires_at) {
+            (Some(template), _) => template.render(state, challenge),
+            (None, Some(expires_at)) => format!(
+                "Insert the following challenge into the web interface: {} (valid for {} minutes)",
+                challenge.value,
+                expires_at.raw().saturating_sub(Timestamp::now().raw()) / 60
+            ),
+            (None, None) => format!(
+                "Insert the following challenge into the web interface: {}",
+                challenge.value
+            ),
+        };
+
+        Some(message)
+    }
     async fn send_message(&mut self, to: &str, content: Self::MessageType) -> Result<()> {
-        Self::send_message(self, to, content.value.as_str()).await
+        let message = self.render_message(&content).unwrap_or_default();
+        Self::send_message(self, to, message.as_str()).await
+    }
+    async fn send_notification(&mut self, to: &str, text: &str) -> Result<()> {
+        Self::send_message(self, to, text).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder() -> EmailClientBuilder {
+        EmailClientBuilder::new()
+            .smtp_server("smtp.example.com".to_string())
+            .imap_server("imap.example.com".to_string())
+            .email_inbox("INBOX".to_string())
+            .email_user("registrar@example.com".to_string())
+            .email_password("password".to_string())
+    }
+
+    #[test]
+    fn retry_config() {
+        let config = RetryConfig {
+            max_attempts: 2,
+            initial_delay: 7,
+            max_delay: 20,
+            ..Default::default()
+        };
+        let mut client = builder().retry(config).build().unwrap();
+        assert_eq!(client.retry.max_attempts, 2);
+
+        let delays: Vec<Duration> = (0..3).map(|_| client.reconnect.next_delay()).collect();
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_secs(7),
+                Duration::from_secs(14),
+                Duration::from_secs(20)
+            ]
+        );
+    }
+
+    #[test]
+    fn processed_actions() {
+        assert!(builder()
+            .processed_action(ProcessedAction::Move, None)
+            .build()
+            .is_err());
+
+        let mut client = builder()
+            .processed_action(ProcessedAction::Move, Some("processed".to_string()))
+            .idle(3_600)
+            .build()
+            .unwrap();
+        assert_eq!(
+            client.idle_timeout,
+            Some(Duration::from_secs(MAX_IDLE_DURATION))
+        );
+
+        client.mark_processed(MessageId::from(5u64));
+        assert_eq!(client.processed, vec![MessageId::from(5u64)]);
+
+        // Nothing to apply if processed emails are kept.
+        let mut client = builder().build().unwrap();
+        client.mark_processed(MessageId::from(5u64));
+        assert!(client.processed.is_empty());
+    }
+
+    #[test]
+    fn email_audit_headers() {
+        let raw = b"Received-SPF: pass (example.com: domain of alice@example.com)\r\n\
+            Authentication-Results: mx.example.com; dkim=pass\r\n\
+            Authentication-Results: relay.example.org; dkim=fail\r\n\
+            From: Alice <alice@example.com>\r\n\
+            Message-ID: <1a2b3c@example.com>\r\n\
+            \r\n\
+            1a2b3c";
+        let mail = mailparse::parse_mail(raw).unwrap();
+
+        let metadata = email_metadata(&mail.headers);
+        assert_eq!(
+            metadata.provider_id.as_deref(),
+            Some("<1a2b3c@example.com>")
+        );
+        assert_eq!(metadata.homeserver, None);
+        assert_eq!(
+            metadata.details.into_iter().collect::<Vec<_>>(),
+            vec![
+                (
+                    "authentication-results".to_string(),
+                    "mx.example.com; dkim=pass".to_string()
+                ),
+                (
+                    "received-spf".to_string(),
+                    "pass (example.com: domain of alice@example.com)".to_string()
+                ),
+            ]
+        );
     }
 }
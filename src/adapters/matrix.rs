@@ -1,29 +1,216 @@
-use crate::adapters::admin::{process_admin, Command, Response};
+use crate::adapters::admin::{
+    process_admin_with_approval, Command, CommandSeverity, PendingConfirmations, Response,
+};
+use crate::adapters::command_parser::ParseError;
 use crate::adapters::Adapter;
-use crate::primitives::{ExternalMessage, ExternalMessageType, Timestamp};
+use crate::primitives::{
+    ExpectedMessage, ExternalMessage, ExternalMessageType, MessageId, ProviderMetadata, Timestamp,
+};
+use crate::retry::{retry, RetryConfig};
 use crate::{Database, Result};
-use matrix_sdk::events::room::member::MemberEventContent;
+use matrix_sdk::api::error::ErrorKind;
+use matrix_sdk::api::r0::room::create_room::{self, RoomPreset};
+use matrix_sdk::events::room::member::{MemberEventContent, MembershipState};
 use matrix_sdk::events::room::message::MessageEventContent;
-use matrix_sdk::events::{AnyMessageEventContent, StrippedStateEvent, SyncMessageEvent};
+use matrix_sdk::events::{
+    AnyMessageEventContent, StrippedStateEvent, SyncMessageEvent, SyncStateEvent,
+};
+use matrix_sdk::identifiers::{RoomId, UserId};
 use matrix_sdk::room::Room;
-use matrix_sdk::{Client, ClientConfig, EventHandler, SyncSettings};
+use matrix_sdk::{
+    Client, ClientConfig, EventHandler, FromHttpResponseError, HttpError, RequestConfig,
+    ServerError, Session, SyncSettings,
+};
 use ruma::events::room::message::{MessageType, TextMessageEventContent};
+use schemars::JsonSchema;
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
-use tokio::time::{self, Duration};
+use tokio::time::{sleep_until, Instant};
 use url::Url;
 
-const REJOIN_DELAY: u64 = 10;
-const REJOIN_MAX_ATTEMPTS: usize = 5;
+// Delay in seconds before retrying a rate limited request, if the homeserver
+// does not specify one.
+const RATE_LIMIT_DEFAULT_DELAY: u64 = 5;
+const RATE_LIMIT_MAX_ATTEMPTS: usize = 5;
+const DEVICE_NAME: &str = "w3f-registrar-bot";
+// Stored in the `db_path`, next to the crypto store of the device.
+const SESSION_FILE: &str = "registrar-session.json";
+
+/// Returns the delay requested by the homeserver if the request was rate
+/// limited (429 `M_LIMIT_EXCEEDED`).
+fn rate_limit_delay(err: &matrix_sdk::Error) -> Option<Duration> {
+    let default = Duration::from_secs(RATE_LIMIT_DEFAULT_DELAY);
+
+    match err {
+        matrix_sdk::Error::Http(HttpError::ClientApi(FromHttpResponseError::Http(
+            ServerError::Known(err),
+        ))) => match err.kind {
+            ErrorKind::LimitExceeded { retry_after_ms } => Some(retry_after_ms.unwrap_or(default)),
+            _ => None,
+        },
+        matrix_sdk::Error::Http(HttpError::Server(status)) if status.as_u16() == 429 => {
+            Some(default)
+        }
+        _ => None,
+    }
+}
+
+/// Delays outgoing requests while the homeserver rate limits the bot. Shared
+/// by all senders, so messages queue up and are sent one after the other
+/// once the limit expired, instead of extending it with retries.
+#[derive(Debug, Clone, Default)]
+struct RateLimiter {
+    until: Arc<Mutex<Option<Instant>>>,
+}
+
+impl RateLimiter {
+    async fn send<T, F, Fut>(&self, name: &str, mut call: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = matrix_sdk::Result<T>>,
+    {
+        let mut attempt = 1;
+
+        loop {
+            // Other senders wait until this request went through.
+            let mut until = self.until.lock().await;
+            if let Some(until) = until.take() {
+                sleep_until(until).await;
+            }
+
+            match call().await {
+                Ok(val) => return Ok(val),
+                Err(err) => match rate_limit_delay(&err) {
+                    Some(delay) if attempt < RATE_LIMIT_MAX_ATTEMPTS => {
+                        warn!(
+                            "Rate limited by the Matrix homeserver, retrying to {} in {:?}",
+                            name, delay
+                        );
+
+                        *until = Some(Instant::now() + delay);
+                        attempt += 1;
+                    }
+                    _ => return Err(err.into()),
+                },
+            }
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct MatrixClient {
+    client: Client,
+    db: Database,
     // TODO: This should just be a channel.
     messages: Arc<Mutex<Vec<ExternalMessage>>>,
+    rate_limiter: RateLimiter,
+    // Other accounts might receive the same messages.
+    shared: bool,
+}
+
+/// Derives the message ID from the event ID, so the same event received by
+/// several accounts has the same ID.
+fn event_message_id(event_id: &str) -> MessageId {
+    let hash = Sha256::digest(event_id.as_bytes());
+    let mut id = [0; 8];
+    id.copy_from_slice(&hash[..8]);
+
+    // IDs are stored as signed integers.
+    MessageId::from(u64::from_be_bytes(id) & i64::MAX as u64)
+}
+
+fn session_path(db_path: &str) -> PathBuf {
+    Path::new(db_path).join(SESSION_FILE)
+}
+
+/// Returns the stored session if it belongs to the configured user, which is
+/// either the full user ID or its localpart.
+fn load_session(path: &Path, username: &str) -> Option<Session> {
+    let session: Session = match fs::read(path) {
+        Ok(content) => match serde_json::from_slice(&content) {
+            Ok(session) => session,
+            Err(err) => {
+                warn!("Ignoring invalid Matrix session {:?}: {:?}", path, err);
+                return None;
+            }
+        },
+        Err(_) => return None,
+    };
+
+    if session.user_id.as_str() == username || session.user_id.localpart() == username {
+        Some(session)
+    } else {
+        None
+    }
+}
+
+fn save_session(path: &Path, session: &Session) -> Result<()> {
+    fs::write(path, serde_json::to_vec(session)?)?;
+    Ok(())
+}
+
+/// Checks whether the access token was revoked, e.g. after the password was
+/// changed.
+async fn is_valid_token(homeserver: &Url, access_token: &str) -> Result<bool> {
+    let resp = reqwest::Client::new()
+        .get(homeserver.join("_matrix/client/r0/account/whoami")?)
+        .bearer_auth(access_token)
+        .send()
+        .await?;
+
+    match resp.status().as_u16() {
+        200..=299 => Ok(true),
+        401 => Ok(false),
+        status => Err(anyhow!("homeserver returned status code {}", status)),
+    }
+}
+
+/// Returns the stored session if its access token is still valid. Otherwise,
+/// logs in with the password and reuses the device of the stored session, so
+/// the crypto store and the E2E sessions are kept after the password was
+/// rotated.
+async fn login(
+    client: &Client,
+    homeserver: &Url,
+    username: &str,
+    password: &str,
+    stored: Option<&Session>,
+) -> Result<Session> {
+    let device_id = match stored {
+        Some(session) if is_valid_token(homeserver, &session.access_token).await? => {
+            return Ok(session.clone())
+        }
+        Some(session) => {
+            warn!(
+                "Access token of Matrix device {} was revoked, logging in with the password",
+                session.device_id
+            );
+            Some(session.device_id.as_str())
+        }
+        None => None,
+    };
+
+    let resp = client
+        .login(username, password, device_id, Some(DEVICE_NAME))
+        .await
+        .map_err(|err| anyhow!("{:?}", err))?;
+
+    Ok(Session {
+        access_token: resp.access_token,
+        user_id: resp.user_id,
+        device_id: resp.device_id,
+    })
 }
 
 impl MatrixClient {
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
         homeserver: &str,
         username: &str,
@@ -31,32 +218,77 @@ impl MatrixClient {
         db_path: &str,
         db: Database,
         admins: Vec<MatrixHandle>,
+        approval_window: Option<u64>,
+        confirm_commands: Option<CommandSeverity>,
+        retry_config: RetryConfig,
     ) -> Result<MatrixClient> {
         info!("Setting up Matrix client");
-        // Setup client
-        let client_config = ClientConfig::new().store_path(db_path);
+        // Setup client. Requests are not retried by the SDK, since it ignores
+        // the delay requested by rate limits.
+        let client_config = ClientConfig::new()
+            .store_path(db_path)
+            .request_config(RequestConfig::new().retry_limit(1));
 
         let homeserver = Url::parse(homeserver)?;
-        let client = Client::new_with_config(homeserver, client_config)?;
+        let client = Client::new_with_config(homeserver.clone(), client_config)?;
 
-        // Login with credentials
+        // Login with the stored session or the credentials
         info!("Login with credentials");
-        client
-            .login(username, password, None, Some("w3f-registrar-bot"))
-            .await?;
+        let session_path = session_path(db_path);
+        let stored = load_session(&session_path, username);
+        let session = retry(&retry_config, "matrix", "Matrix login", || {
+            login(&client, &homeserver, username, password, stored.as_ref())
+        })
+        .await?;
+
+        if Some(&session) == stored.as_ref() {
+            info!("Restoring session of Matrix device {}", session.device_id);
+            client
+                .restore_login(session)
+                .await
+                .map_err(|err| anyhow!("{:?}", err))?;
+        } else if let Err(err) = save_session(&session_path, &session) {
+            warn!(
+                "Failed to store Matrix session in {:?}: {:?}",
+                session_path, err
+            );
+        }
 
         // Sync up, avoid responding to old messages.
         info!("Syncing client");
-        client.sync_once(SyncSettings::default()).await?;
+        retry(&retry_config, "matrix", "Matrix sync", || async {
+            client
+                .sync_once(SyncSettings::default())
+                .await
+                .map_err(|err| anyhow!("{:?}", err))
+        })
+        .await?;
+
+        // Forget the rooms which were left while the bot was offline.
+        let joined: Vec<String> = client
+            .joined_rooms()
+            .iter()
+            .map(|room| room.room_id().to_string())
+            .collect();
+
+        let pruned = db.prune_matrix_rooms(&joined).await?;
+        if pruned > 0 {
+            debug!("Removed {} left Matrix rooms from the database", pruned);
+        }
 
         // Add event handler
         let messages = Arc::new(Mutex::new(vec![]));
+        let rate_limiter = RateLimiter::default();
         client
             .set_event_handler(Box::new(Listener::new(
                 client.clone(),
                 Arc::clone(&messages),
-                db,
+                db.clone(),
                 admins,
+                approval_window,
+                confirm_commands,
+                rate_limiter.clone(),
+                retry_config,
             )))
             .await;
 
@@ -69,38 +301,126 @@ impl MatrixClient {
                 .ok_or_else(|| anyhow!("Failed to acquire sync token"))?,
         );
 
+        let sync_client = client.clone();
         actix::spawn(async move {
-            client.clone().sync(settings).await;
+            sync_client.sync(settings).await;
         });
 
-        Ok(MatrixClient { messages })
+        Ok(MatrixClient {
+            client,
+            db,
+            messages,
+            rate_limiter,
+            shared: false,
+        })
+    }
+    pub fn shared(mut self, shared: bool) -> Self {
+        self.shared = shared;
+        self
+    }
+    /// Returns the direct message room of the user. A room known from a
+    /// previous conversation is reused, otherwise a new one is created.
+    async fn direct_room(&self, user: &str) -> Result<RoomId> {
+        if let Some(room_id) = self.db.fetch_matrix_room(user).await? {
+            let room_id = RoomId::try_from(room_id.as_str())?;
+            if self.client.get_joined_room(&room_id).is_some() {
+                return Ok(room_id);
+            }
+
+            // The bot is no longer part of that room.
+            self.db.remove_matrix_room(room_id.as_str()).await?;
+        }
+
+        debug!("Creating direct message room with {}", user);
+        let invite = [UserId::try_from(user)?];
+        let mut request = create_room::Request::new();
+        request.invite = &invite;
+        request.is_direct = true;
+        request.preset = Some(RoomPreset::TrustedPrivateChat);
+
+        let room_id = self
+            .rate_limiter
+            .send("create room", || self.client.create_room(request.clone()))
+            .await?
+            .room_id;
+        self.db.store_matrix_room(user, room_id.as_str()).await?;
+
+        Ok(room_id)
+    }
+    /// Sends a plain text message to the user via the direct message room,
+    /// e.g. a challenge or an alert to an admin.
+    pub async fn send_text(&self, to: &str, text: &str) -> Result<()> {
+        let room_id = self.direct_room(to).await?;
+        let content =
+            AnyMessageEventContent::RoomMessage(MessageEventContent::text_plain(text.to_string()));
+
+        self.rate_limiter
+            .send("send message", || {
+                self.client.room_send(&room_id, content.clone(), None)
+            })
+            .await?;
+
+        Ok(())
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
 pub struct MatrixHandle(String);
 
+#[cfg(test)]
+impl From<&str> for MatrixHandle {
+    fn from(val: &str) -> Self {
+        MatrixHandle(val.to_string())
+    }
+}
+
+impl std::fmt::Display for MatrixHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 struct Listener {
     client: Client,
     messages: Arc<Mutex<Vec<ExternalMessage>>>,
     db: Database,
     admins: Vec<MatrixHandle>,
+    // Enables the two-person rule for high-impact admin actions.
+    approval_window: Option<u64>,
+    // Commands of at least this severity must be confirmed with a code.
+    confirm_commands: Option<CommandSeverity>,
+    confirmations: Arc<Mutex<PendingConfirmations>>,
+    rate_limiter: RateLimiter,
+    retry_config: RetryConfig,
 }
 
 impl Listener {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         client: Client,
         messages: Arc<Mutex<Vec<ExternalMessage>>>,
         db: Database,
         admins: Vec<MatrixHandle>,
+        approval_window: Option<u64>,
+        confirm_commands: Option<CommandSeverity>,
+        rate_limiter: RateLimiter,
+        retry_config: RetryConfig,
     ) -> Self {
         Self {
             client,
             messages,
             db,
             admins,
+            approval_window,
+            confirm_commands,
+            confirmations: Default::default(),
+            rate_limiter,
+            retry_config,
         }
     }
+    fn requires_confirmation(&self, command: &Command) -> bool {
+        matches!(self.confirm_commands, Some(min) if command.severity() >= min)
+    }
 }
 
 #[async_trait]
@@ -108,31 +428,56 @@ impl EventHandler for Listener {
     async fn on_stripped_state_member(
         &self,
         room: Room,
-        _: &StrippedStateEvent<MemberEventContent>,
+        event: &StrippedStateEvent<MemberEventContent>,
         _: Option<MemberEventContent>,
     ) {
         if let Room::Invited(room) = room {
-            let mut rejoin_attempts = 0;
-
-            while let Err(err) = self.client.join_room_by_id(room.room_id()).await {
-                warn!(
-                    "Failed to join room {} ({:?}), retrying in {}s",
-                    room.room_id(),
-                    err,
-                    REJOIN_DELAY,
-                );
+            let name = format!("Joining room {}", room.room_id());
+            if let Err(err) = retry(&self.retry_config, "matrix", &name, || async {
+                self.client
+                    .join_room_by_id(room.room_id())
+                    .await
+                    .map(|_| ())
+                    .map_err(|err| anyhow!("{:?}", err))
+            })
+            .await
+            {
+                error!("Can't join room {}, exiting ({:?})", room.room_id(), err);
+                return;
+            }
 
-                time::sleep(Duration::from_secs(REJOIN_DELAY)).await;
+            debug!("Joined room {}", room.room_id());
 
-                if rejoin_attempts == REJOIN_MAX_ATTEMPTS {
-                    error!("Can't join room {}, exiting ({:?})", room.room_id(), err);
-                    return;
+            // Remember direct message rooms, so they can be reused later on.
+            if event.content.is_direct == Some(true) {
+                if let Err(err) = self
+                    .db
+                    .store_matrix_room(event.sender.as_str(), room.room_id().as_str())
+                    .await
+                {
+                    error!("Failed to store Matrix room in database: {:?}", err);
                 }
-
-                rejoin_attempts += 1;
             }
+        }
+    }
+    async fn on_room_member(&self, room: Room, event: &SyncStateEvent<MemberEventContent>) {
+        let own_id = match self.client.user_id().await {
+            Some(id) => id,
+            None => return,
+        };
 
-            debug!("Joined room {}", room.room_id());
+        // Remove the rooms the bot has left (or was kicked/banned from).
+        if event.state_key == own_id.as_str()
+            && matches!(
+                event.content.membership,
+                MembershipState::Leave | MembershipState::Ban
+            )
+        {
+            match self.db.remove_matrix_room(room.room_id().as_str()).await {
+                Ok(removed) if removed > 0 => debug!("Left Matrix room {}", room.room_id()),
+                Ok(_) => {}
+                Err(err) => error!("Failed to remove Matrix room from database: {:?}", err),
+            }
         }
     }
     async fn on_room_message(&self, room: Room, event: &SyncMessageEvent<MessageEventContent>) {
@@ -153,25 +498,60 @@ impl EventHandler for Listener {
             };
 
             // Check for admin message
-            let sender = event.sender.to_string();
-            if self.admins.contains(&MatrixHandle(sender)) {
+            let sender = MatrixHandle(event.sender.to_string());
+            if self.admins.contains(&sender) {
+                let room_id = room.room_id().as_str();
                 let resp = match Command::from_str(msg_body) {
+                    Ok(Command::Confirm(code)) => {
+                        let confirmed = self
+                            .confirmations
+                            .lock()
+                            .await
+                            .confirm(&sender, room_id, &code);
+
+                        Some(match confirmed {
+                            Ok(cmd) => {
+                                info!("Admin {} confirmed command {:?}", sender, cmd);
+                                process_admin_with_approval(
+                                    &self.db,
+                                    cmd,
+                                    &sender,
+                                    self.approval_window,
+                                )
+                                .await
+                            }
+                            Err(resp) => resp,
+                        })
+                    }
+                    // Held back until the admin sends the code back.
+                    Ok(cmd) if self.requires_confirmation(&cmd) => {
+                        let code = self
+                            .confirmations
+                            .lock()
+                            .await
+                            .request(&sender, room_id, cmd);
+
+                        Some(Response::ConfirmationRequired(code))
+                    }
                     // If a valid admin command was found, execute it.
-                    Ok(cmd) => Some(process_admin(&self.db, cmd).await),
-                    Err(err @ Response::InvalidSyntax(_)) => Some(err),
+                    Ok(cmd) => Some(
+                        process_admin_with_approval(&self.db, cmd, &sender, self.approval_window)
+                            .await,
+                    ),
+                    Err(err @ ParseError::InvalidSyntax(_)) => Some(Response::from(err)),
                     // Ignore, allow noise (catches `UnknownCommand`).
                     Err(_) => None,
                 };
 
                 // If response should be sent, then do so.
                 if let Some(resp) = resp {
-                    if let Err(err) = room
-                        .send(
-                            AnyMessageEventContent::RoomMessage(MessageEventContent::text_plain(
-                                resp.to_string(),
-                            )),
-                            None,
-                        )
+                    let content = AnyMessageEventContent::RoomMessage(
+                        MessageEventContent::text_plain(resp.to_string()),
+                    );
+
+                    if let Err(err) = self
+                        .rate_limiter
+                        .send("send admin response", || room.send(content.clone(), None))
                         .await
                     {
                         error!("Failed to send message: {:?}", err);
@@ -188,12 +568,16 @@ impl EventHandler for Listener {
             let mut lock = self.messages.lock().await;
             (*lock).push(ExternalMessage {
                 origin: ExternalMessageType::Matrix(event.sender.to_string()),
-                // A message UID is not relevant regarding a live
-                // message listener. The Matrix SDK handles
-                // synchronization.
-                id: 0u32.into(),
+                // The Matrix SDK handles synchronization, the ID only tells
+                // apart the events received by several accounts.
+                id: event_message_id(event.event_id.as_str()),
                 timestamp: Timestamp::now(),
                 values: vec![msg_body.to_string().into()],
+                metadata: Some(ProviderMetadata {
+                    provider_id: Some(event.event_id.to_string()),
+                    homeserver: Some(event.sender.server_name().to_string()),
+                    ..Default::default()
+                }),
             });
         }
     }
@@ -201,17 +585,133 @@ impl EventHandler for Listener {
 
 #[async_trait]
 impl Adapter for MatrixClient {
-    type MessageType = ();
+    type MessageType = ExpectedMessage;
 
     fn name(&self) -> &'static str {
         "Matrix"
     }
+    fn service(&self) -> &'static str {
+        "matrix"
+    }
+    fn is_shared(&self) -> bool {
+        self.shared
+    }
     async fn fetch_messages(&mut self) -> Result<Vec<ExternalMessage>> {
         let mut lock = self.messages.lock().await;
         // Return messages and wipe inner field.
         Ok(std::mem::take(&mut *lock))
     }
-    async fn send_message(&mut self, _to: &str, _content: Self::MessageType) -> Result<()> {
-        unimplemented!()
+    async fn send_message(&mut self, to: &str, content: Self::MessageType) -> Result<()> {
+        self.send_text(to, &content.value.to_string()).await
+    }
+    async fn send_notification(&mut self, to: &str, text: &str) -> Result<()> {
+        self.send_text(to, text).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matrix_sdk::api::error::Error as ApiError;
+    use matrix_sdk::reqwest::StatusCode;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn rate_limited(retry_after_ms: Option<Duration>) -> matrix_sdk::Error {
+        matrix_sdk::Error::Http(HttpError::ClientApi(FromHttpResponseError::Http(
+            ServerError::Known(ApiError {
+                kind: ErrorKind::LimitExceeded { retry_after_ms },
+                message: "Too Many Requests".to_string(),
+                status_code: StatusCode::TOO_MANY_REQUESTS,
+            }),
+        )))
+    }
+
+    #[test]
+    fn message_id_of_event() {
+        let id = event_message_id("$1a2b3c:matrix.org");
+        assert_eq!(id, event_message_id("$1a2b3c:matrix.org"));
+        assert_ne!(id, event_message_id("$4d5e6f:matrix.org"));
+        assert!(id.raw() <= i64::MAX as u64);
+    }
+
+    #[test]
+    fn stored_session() {
+        let dir = std::env::temp_dir().join(format!("registrar-session-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(SESSION_FILE);
+
+        assert!(load_session(&path, "registrar").is_none());
+
+        let session = Session {
+            access_token: "token".to_string(),
+            user_id: UserId::try_from("@registrar:matrix.org").unwrap(),
+            device_id: "ABCDEFGH".into(),
+        };
+        save_session(&path, &session).unwrap();
+
+        assert_eq!(load_session(&path, "registrar"), Some(session.clone()));
+        assert_eq!(load_session(&path, "@registrar:matrix.org"), Some(session));
+        // Sessions of other users are not restored.
+        assert!(load_session(&path, "@registrar:example.com").is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parse_rate_limit_delay() {
+        assert_eq!(
+            rate_limit_delay(&rate_limited(Some(Duration::from_millis(2_500)))),
+            Some(Duration::from_millis(2_500))
+        );
+        assert_eq!(
+            rate_limit_delay(&rate_limited(None)),
+            Some(Duration::from_secs(RATE_LIMIT_DEFAULT_DELAY))
+        );
+        assert_eq!(
+            rate_limit_delay(&matrix_sdk::Error::Http(HttpError::Server(
+                StatusCode::TOO_MANY_REQUESTS
+            ))),
+            Some(Duration::from_secs(RATE_LIMIT_DEFAULT_DELAY))
+        );
+        assert_eq!(
+            rate_limit_delay(&matrix_sdk::Error::Http(HttpError::Server(
+                StatusCode::BAD_GATEWAY
+            ))),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn retry_after_rate_limit() {
+        let limiter = RateLimiter::default();
+        let calls = AtomicUsize::new(0);
+        let start = Instant::now();
+
+        // Rate limited twice, then sent.
+        let res = limiter
+            .send("send challenge", || async {
+                if calls.fetch_add(1, Ordering::Relaxed) < 2 {
+                    Err(rate_limited(Some(Duration::from_millis(50))))
+                } else {
+                    Ok(())
+                }
+            })
+            .await;
+
+        assert!(res.is_ok());
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        // Gives up eventually.
+        let calls = AtomicUsize::new(0);
+        let res: Result<()> = limiter
+            .send("send challenge", || async {
+                calls.fetch_add(1, Ordering::Relaxed);
+                Err(rate_limited(Some(Duration::from_millis(1))))
+            })
+            .await;
+
+        assert!(res.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), RATE_LIMIT_MAX_ATTEMPTS);
     }
 }
@@ -1,21 +1,30 @@
-use crate::database::{Database, EventCursor};
+use crate::database::Database;
+use crate::failure_alerts::{self, FailureAlerts};
+use crate::message_template::MessageTemplate;
+use crate::metrics;
 use crate::primitives::{
-    ExpectedMessage, ExternalMessage, IdentityFieldValue, NotificationMessage,
+    ExpectedMessage, ExternalMessage, ExternalMessageType, IdentityFieldValue, JudgementState,
+    MessageId, MessagePart, OutboundMessage, OutboxEntry, OutboxKind, Timestamp,
 };
-use crate::{AdapterConfig, Result};
-use tokio::time::{interval, Duration};
+use crate::retry::{CircuitBreaker, CircuitOpen, RetryConfig};
+use crate::transparency_report;
+use crate::{AdapterConfig, NudgeConfig, Result};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
 use tracing::Instrument;
 
 pub mod admin;
+pub mod command_parser;
 pub mod email;
 pub mod matrix;
 pub mod twitter;
+pub mod web;
 
-pub async fn run_adapters(config: AdapterConfig, db: Database) -> Result<()> {
-    let listener = AdapterListener::new(db.clone()).await;
-    // Convenience flat for logging
-    let mut started = false;
-
+pub async fn run_adapters(config: AdapterConfig, db: Database) -> Result<AdaptersHandle> {
     // Deconstruct struct to get around borrowing violations.
     let AdapterConfig {
         watcher: _,
@@ -23,36 +32,129 @@ pub async fn run_adapters(config: AdapterConfig, db: Database) -> Result<()> {
         twitter: twitter_config,
         email: email_config,
         display_name: _,
+        retry: retry_config,
+        compliance: _,
+        script_hook: _,
+        disposable_emails: _,
+        address_blocklist: _,
+        settle_delay: _,
+        outbound_archive_retention,
+        transparency_report,
+        web: web_config,
+        message_rate_limit: _,
+        failure_alerts,
+        nudges,
+        refuse_new_requests: _,
     } = config;
 
-    // Matrix client configuration and execution.
+    let retry_config = retry_config.unwrap_or_default();
+    let listener = AdapterListener::new(db.clone(), retry_config.clone()).await;
+    let handle = listener.handle();
+
+    if let Some(days) = outbound_archive_retention {
+        handle.add_task(start_archive_retention_task(db.clone(), days));
+    }
+
+    if let Some(report_config) = transparency_report {
+        handle.add_task(transparency_report::start_report_task(
+            db.clone(),
+            report_config,
+        ));
+    }
+
+    let nudges = nudges.unwrap_or(NudgeConfig {
+        enabled: true,
+        delay: None,
+    });
+    if nudges.enabled {
+        let delay = nudges.delay.unwrap_or(DEFAULT_NUDGE_DELAY);
+        handle.add_task(start_nudge_task(db.clone(), delay));
+    } else {
+        info!("Reminders about unverified fields are disabled");
+    }
+
+    let failure_alerts = failure_alerts
+        .map(|config| FailureAlerts::new(db.clone(), config))
+        .transpose()?;
+
+    // Convenience flat for logging
+    let mut started = false;
+    // The account handling admin commands, which also sends the alerts.
+    let mut admin_client = None;
+
+    // Matrix client configuration and execution, one adapter per account.
     if matrix_config.enabled {
         let config = matrix_config;
+        let accounts = config.accounts();
+        let sandbox = config.sandbox.unwrap_or_default();
 
-        let span = info_span!("matrix_adapter");
-        info!(
-            homeserver = config.homeserver.as_str(),
-            username = config.username.as_str()
-        );
+        let mut db_paths = HashSet::new();
+        if let Some(account) = accounts
+            .iter()
+            .find(|account| !db_paths.insert(account.db_path.as_str()))
+        {
+            return Err(anyhow!(
+                "Matrix accounts must not share the store at {}",
+                account.db_path
+            ));
+        }
 
-        async {
-            info!("Configuring client");
-            let matrix_client = matrix::MatrixClient::new(
-                &config.homeserver,
-                &config.username,
-                &config.password,
-                &config.db_path,
-                db,
-                config.admins.unwrap_or_default(),
-            )
-            .await?;
+        let shared = accounts.len() > 1;
+        if shared {
+            handle.add_task(start_processed_messages_pruning(db.clone()));
+        }
 
-            info!("Starting message adapter");
-            listener.start_message_adapter(matrix_client, 1).await;
-            Result::Ok(())
+        for (index, account) in accounts.iter().enumerate() {
+            let span = info_span!("matrix_adapter");
+            info!(
+                homeserver = account.homeserver.as_str(),
+                username = account.username.as_str()
+            );
+
+            async {
+                // Admin commands are only handled by the first account, so
+                // those are not executed multiple times.
+                let (admins, approval_window, confirm_commands) = if index == 0 {
+                    (
+                        config.admins.clone().unwrap_or_default(),
+                        config.approval_window,
+                        config.confirm_commands,
+                    )
+                } else {
+                    (vec![], None, None)
+                };
+
+                info!("Configuring client");
+                let matrix_client = matrix::MatrixClient::new(
+                    &account.homeserver,
+                    &account.username,
+                    &account.password,
+                    &account.db_path,
+                    db.clone(),
+                    admins,
+                    approval_window,
+                    confirm_commands,
+                    retry_config.clone(),
+                )
+                .await?
+                .shared(shared);
+
+                if index == 0 {
+                    admin_client = Some((
+                        matrix_client.clone(),
+                        config.admins.clone().unwrap_or_default(),
+                    ));
+                }
+
+                info!("Starting message adapter");
+                listener
+                    .start_message_adapter(matrix_client, PollingInterval::fixed(1), sandbox)
+                    .await;
+                Result::Ok(())
+            }
+            .instrument(span)
+            .await?;
         }
-        .instrument(span)
-        .await?;
 
         started = true;
     }
@@ -62,20 +164,47 @@ pub async fn run_adapters(config: AdapterConfig, db: Database) -> Result<()> {
         let config = twitter_config;
 
         let span = info_span!("twitter_adapter");
-        info!(api_key = config.api_key.as_str());
+        info!(api_key = config.api_key.as_deref().unwrap_or("<bearer token>"));
 
         async {
             info!("Configuring client");
-            let twitter_client = twitter::TwitterBuilder::new()
-                .consumer_key(config.api_key)
-                .consumer_secret(config.api_secret)
-                .token(config.token)
-                .token_secret(config.token_secret)
-                .build()?;
+            let mut builder = twitter::TwitterBuilder::new()
+                .db(db.clone())
+                .retry(retry_config.clone())
+                .id_cache_ttl(config.id_cache_ttl.unwrap_or(twitter::DEFAULT_ID_CACHE_TTL));
+
+            if let Some(bearer_token) = config.bearer_token {
+                builder = builder.bearer_token(bearer_token);
+            } else {
+                let missing =
+                    |field: &str| anyhow!("Twitter {} or bearer_token not specified", field);
+                builder = builder
+                    .consumer_key(config.api_key.ok_or_else(|| missing("api_key"))?)
+                    .consumer_secret(config.api_secret.ok_or_else(|| missing("api_secret"))?)
+                    .token(config.token.ok_or_else(|| missing("token"))?)
+                    .token_secret(config.token_secret.ok_or_else(|| missing("token_secret"))?);
+            }
+
+            let twitter_client = builder.build()?;
+
+            if let Some(interval) = config.profile_interval {
+                info!("Starting profile challenge adapter");
+                listener
+                    .start_message_adapter(
+                        twitter::TwitterProfiles::new(twitter_client.clone(), db.clone()),
+                        PollingInterval::fixed(interval),
+                        config.sandbox.unwrap_or_default(),
+                    )
+                    .await;
+            }
 
             info!("Starting message adapter");
             listener
-                .start_message_adapter(twitter_client, config.request_interval)
+                .start_message_adapter(
+                    twitter_client,
+                    PollingInterval::new(config.request_interval, config.max_request_interval),
+                    config.sandbox.unwrap_or_default(),
+                )
                 .await;
 
             Result::Ok(())
@@ -100,17 +229,66 @@ pub async fn run_adapters(config: AdapterConfig, db: Database) -> Result<()> {
 
         async {
             info!("Configuring client");
-            let email_client = email::EmailClientBuilder::new()
+            let mut builder = email::EmailClientBuilder::new()
                 .smtp_server(config.smtp_server)
                 .imap_server(config.imap_server)
                 .email_inbox(config.inbox)
                 .email_user(config.user)
                 .email_password(config.password)
-                .build()?;
+                .retry(retry_config.clone());
+
+            if let Some(template) = config.template {
+                builder = builder
+                    .message_template(MessageTemplate::new(&template, config.verification_url)?);
+            }
+
+            builder = builder.processed_action(
+                config.processed_action.unwrap_or_default(),
+                config.processed_folder,
+            );
+
+            // With IDLE, the adapter itself waits for new emails.
+            let interval = if config.idle.unwrap_or(true) {
+                builder = builder.idle(config.request_interval);
+                PollingInterval::fixed(email::IDLE_POLLING_INTERVAL)
+            } else {
+                PollingInterval::new(config.request_interval, config.max_request_interval)
+            };
+
+            let email_client = builder.build()?;
 
             info!("Starting message adapter");
             listener
-                .start_message_adapter(email_client, config.request_interval)
+                .start_message_adapter(email_client, interval, config.sandbox.unwrap_or_default())
+                .await;
+
+            Result::Ok(())
+        }
+        .instrument(span)
+        .await?;
+
+        started = true;
+    }
+
+    // Web client configuration and execution.
+    if let Some(config) = web_config.filter(|config| config.enabled) {
+        let span = info_span!("web_adapter");
+
+        async {
+            info!("Starting web adapter");
+            let web_verifier = web::WebVerifier::new(
+                db.clone(),
+                config
+                    .request_timeout
+                    .unwrap_or(web::DEFAULT_REQUEST_TIMEOUT),
+            )?;
+
+            listener
+                .start_message_adapter(
+                    web_verifier,
+                    PollingInterval::fixed(config.request_interval),
+                    false,
+                )
                 .await;
 
             Result::Ok(())
@@ -125,7 +303,115 @@ pub async fn run_adapters(config: AdapterConfig, db: Database) -> Result<()> {
         warn!("No adapters are enabled");
     }
 
-    Ok(())
+    if let Some(mut alerts) = failure_alerts {
+        if let Some((client, admins)) = admin_client {
+            alerts = alerts.with_matrix(client, admins);
+        }
+
+        handle.add_task(failure_alerts::start_alert_task(alerts));
+    }
+
+    Ok(handle)
+}
+
+/// Handle of the adapters started by an `AdapterListener`.
+#[derive(Clone)]
+pub struct AdaptersHandle {
+    stop: Arc<watch::Sender<bool>>,
+    adapters: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    // Maintenance tasks, e.g. the removal of expired messages.
+    tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl AdaptersHandle {
+    fn add_task(&self, task: JoinHandle<()>) {
+        self.tasks.lock().unwrap().push(task);
+    }
+    /// Stops the adapters and waits until they finished processing the
+    /// current batch of messages.
+    pub async fn shutdown(self) {
+        let _ = self.stop.send(true);
+
+        for task in self.tasks.lock().unwrap().drain(..) {
+            task.abort();
+        }
+
+        let adapters: Vec<JoinHandle<()>> = self.adapters.lock().unwrap().drain(..).collect();
+        for adapter in adapters {
+            let _ = adapter.await;
+        }
+    }
+}
+
+// Interval in seconds between removals of expired archived messages.
+const ARCHIVE_RETENTION_INTERVAL: u64 = 3_600;
+// IDs of processed messages are kept for a day, which covers the delivery of
+// a message to all accounts of a service.
+const PROCESSED_MESSAGES_RETENTION: u64 = 86_400;
+// Seconds after the judgement request before users are reminded about the
+// last unverified field, unless configured otherwise.
+const DEFAULT_NUDGE_DELAY: u64 = 86_400;
+// Interval in seconds between checks for identities to remind.
+const NUDGE_INTERVAL: u64 = 600;
+
+/// Periodically removes the archived outbound messages which are older than
+/// the retention period (in days).
+fn start_archive_retention_task(db: Database, days: u64) -> JoinHandle<()> {
+    actix::spawn(async move {
+        loop {
+            let before = Timestamp::now().raw().saturating_sub(days * 86_400);
+            match db.prune_outbound_messages(Timestamp::from(before)).await {
+                Ok(0) => {}
+                Ok(pruned) => debug!("Removed {} expired outbound messages", pruned),
+                Err(err) => error!("Failed to remove expired outbound messages: {:?}", err),
+            }
+
+            match db.prune_outbox(Timestamp::from(before)).await {
+                Ok(0) => {}
+                Ok(pruned) => debug!("Removed {} dispatched outbox entries", pruned),
+                Err(err) => error!("Failed to remove dispatched outbox entries: {:?}", err),
+            }
+
+            sleep(Duration::from_secs(ARCHIVE_RETENTION_INTERVAL)).await;
+        }
+    })
+}
+
+/// Periodically schedules the reminders of identities which were requested at
+/// least `delay` seconds ago, sent via the outbox.
+fn start_nudge_task(db: Database, delay: u64) -> JoinHandle<()> {
+    actix::spawn(async move {
+        loop {
+            let now = Timestamp::now();
+            let before = Timestamp::from(now.raw().saturating_sub(delay));
+            match db.schedule_nudges(before, now).await {
+                Ok(0) => {}
+                Ok(nudged) => debug!("Scheduled reminders for {} identities", nudged),
+                Err(err) => error!("Failed to schedule reminders: {:?}", err),
+            }
+
+            sleep(Duration::from_secs(NUDGE_INTERVAL)).await;
+        }
+    })
+}
+
+/// Periodically removes the IDs of messages which were claimed by one of
+/// several adapters of a service.
+fn start_processed_messages_pruning(db: Database) -> JoinHandle<()> {
+    actix::spawn(async move {
+        loop {
+            let before = Timestamp::now()
+                .raw()
+                .saturating_sub(PROCESSED_MESSAGES_RETENTION);
+            match db.prune_processed_messages(Timestamp::from(before)).await {
+                Ok(0) => {}
+                Ok(pruned) => debug!("Removed {} processed message IDs", pruned),
+                Err(err) => error!("Failed to remove processed message IDs: {:?}", err),
+            }
+
+            sleep(Duration::from_secs(ARCHIVE_RETENTION_INTERVAL)).await;
+        }
+    })
 }
 
 #[async_trait]
@@ -133,48 +419,476 @@ pub trait Adapter {
     type MessageType;
 
     fn name(&self) -> &'static str;
+    // The service the adapter talks to, as used by `pause-adapter`. Adapters of
+    // the same service are paused together.
+    fn service(&self) -> &'static str {
+        self.name()
+    }
+    // Whether message IDs are strictly increasing, in which case the ID of the
+    // last processed message is persisted and passed on via `set_watermark`
+    // after a restart.
+    fn has_ordered_ids(&self) -> bool {
+        false
+    }
+    fn set_watermark(&mut self, _id: MessageId) {}
+    // Whether the same message might be fetched by several adapters of the
+    // service, e.g. multiple Matrix accounts in the same room. Such messages
+    // are only processed once, by ID.
+    fn is_shared(&self) -> bool {
+        false
+    }
+    // Called once a fetched message was processed, e.g. to remove it from the
+    // source.
+    fn mark_processed(&mut self, _id: MessageId) {}
     async fn fetch_messages(&mut self) -> Result<Vec<ExternalMessage>>;
+    // The text which is sent for the message, as recorded in the outbound
+    // archive. Adapters which do not render messages archive the challenge.
+    fn render_message(&self, _content: &Self::MessageType) -> Option<String> {
+        None
+    }
     async fn send_message(&mut self, to: &str, content: Self::MessageType) -> Result<()>;
+    // Sends a plain text to the user, e.g. a reminder. Only supported by
+    // adapters which can contact users on their own.
+    async fn send_notification(&mut self, _to: &str, _text: &str) -> Result<()> {
+        Err(anyhow!(
+            "{} adapter does not send notifications",
+            self.name()
+        ))
+    }
+}
+
+// Consecutive polls without messages before the interval is increased.
+const IDLE_POLLS_BEFORE_BACKOFF: usize = 3;
+
+/// The interval between two fetches of an adapter. If a maximum is set, the
+/// interval is doubled (up to the maximum) whenever several consecutive polls
+/// returned no messages, and is reset once messages arrive again.
+#[derive(Debug, Clone)]
+pub struct PollingInterval {
+    base: Duration,
+    max: Duration,
+    current: Duration,
+    idle_polls: usize,
+}
+
+impl PollingInterval {
+    pub fn new(interval: u64, max_interval: Option<u64>) -> Self {
+        let base = Duration::from_secs(interval);
+
+        PollingInterval {
+            base,
+            max: max_interval
+                .map(Duration::from_secs)
+                .unwrap_or(base)
+                .max(base),
+            current: base,
+            idle_polls: 0,
+        }
+    }
+    pub fn fixed(interval: u64) -> Self {
+        Self::new(interval, None)
+    }
+    /// Records the number of fetched messages and returns the interval until
+    /// the next poll.
+    pub fn next(&mut self, messages: usize) -> Duration {
+        if messages > 0 {
+            self.current = self.base;
+            self.idle_polls = 0;
+        } else {
+            self.idle_polls += 1;
+            if self.idle_polls >= IDLE_POLLS_BEFORE_BACKOFF {
+                self.current = (self.current * 2).min(self.max);
+                self.idle_polls = 0;
+            }
+        }
+
+        self.current
+    }
+    pub fn current(&self) -> Duration {
+        self.current
+    }
+}
+
+// Messages of a sender are combined within this window, in seconds.
+const RECENT_MESSAGES_WINDOW: u64 = 60;
+// Maximum number of messages combined per sender.
+const RECENT_MESSAGES_LIMIT: usize = 5;
+
+/// Keeps the recent message parts of each sender, so challenges which were
+/// split across multiple messages (e.g. long pastes in Matrix clients) still
+/// verify.
+#[derive(Debug, Default)]
+struct RecentMessages {
+    senders: HashMap<ExternalMessageType, VecDeque<(Timestamp, Vec<MessagePart>)>>,
+}
+
+impl RecentMessages {
+    /// Records the message and, if the sender sent other messages within the
+    /// window, appends the concatenation of all recent parts as a value.
+    fn combine(&mut self, mut message: ExternalMessage) -> ExternalMessage {
+        let now = message.timestamp.raw();
+        let expired =
+            |timestamp: &Timestamp| now.saturating_sub(timestamp.raw()) > RECENT_MESSAGES_WINDOW;
+
+        self.senders.retain(|_, recent| {
+            recent.retain(|(timestamp, _)| !expired(timestamp));
+            !recent.is_empty()
+        });
+
+        let recent = self.senders.entry(message.origin.clone()).or_default();
+        recent.push_back((message.timestamp, message.values.clone()));
+        if recent.len() > RECENT_MESSAGES_LIMIT {
+            recent.pop_front();
+        }
+
+        if recent.len() > 1 {
+            let combined: String = recent
+                .iter()
+                .flat_map(|(_, parts)| parts.iter())
+                .map(|part| part.as_str().trim())
+                .collect();
+
+            message.values.push(combined.into());
+        }
+
+        message
+    }
+}
+
+/// The second challenge sent to the user, together with the current state of
+/// the identity for rendering message templates.
+#[derive(Debug, Clone)]
+pub struct SecondChallenge {
+    pub challenge: ExpectedMessage,
+    pub state: JudgementState,
+}
+
+impl From<SecondChallenge> for ExpectedMessage {
+    fn from(val: SecondChallenge) -> Self {
+        val.challenge
+    }
 }
 
 // Filler for adapters that do not send messages.
-impl From<ExpectedMessage> for () {
-    fn from(_: ExpectedMessage) -> Self {}
+impl From<SecondChallenge> for () {
+    fn from(_: SecondChallenge) -> Self {}
+}
+
+// Seconds for which a claimed outbox entry is not picked up by another
+// dispatcher.
+const OUTBOX_LEASE: u64 = 60;
+// Seconds before a failed delivery is retried, multiplied by the attempts.
+const OUTBOX_RETRY_DELAY: u64 = 60;
+const MAX_OUTBOX_ATTEMPTS: u32 = 5;
+
+/// Sends the message of the outbox entry and archives it. Fails if the message
+/// could not be delivered and should be retried.
+async fn send_outbox_message<T>(
+    db: &Database,
+    adapter: &mut T,
+    breaker: &CircuitBreaker,
+    sandbox: bool,
+    entry: &OutboxEntry,
+) -> Result<()>
+where
+    T: Adapter + Send,
+    <T as Adapter>::MessageType: From<SecondChallenge>,
+{
+    match entry.kind {
+        OutboxKind::SecondChallenge => {
+            send_second_challenge(db, adapter, breaker, sandbox, entry).await
+        }
+        OutboxKind::Nudge => send_nudge(db, adapter, breaker, sandbox, entry).await,
+    }
+}
+
+async fn send_second_challenge<T>(
+    db: &Database,
+    adapter: &mut T,
+    breaker: &CircuitBreaker,
+    sandbox: bool,
+    entry: &OutboxEntry,
+) -> Result<()>
+where
+    T: Adapter,
+    <T as Adapter>::MessageType: From<SecondChallenge>,
+{
+    let OutboxEntry { context, field, .. } = entry;
+
+    let to = match field {
+        IdentityFieldValue::Email(to) => to,
+        _ => return Err(anyhow!("Unsupported outbox entry: {:?}", entry)),
+    };
+
+    info!("Sending second challenge to {}", to);
+
+    // Issued once per entry, a retry must not replace a code which might have
+    // reached the user despite the failed delivery.
+    let challenge = match &entry.challenge {
+        Some(challenge) if !challenge.is_expired() => challenge.clone(),
+        _ => {
+            let challenge = db.issue_second_challenge(context, field).await?;
+            db.set_outbox_challenge(&entry.id, &challenge).await?;
+            challenge
+        }
+    };
+    let state = db
+        .fetch_judgement_state(context)
+        .await?
+        .ok_or_else(|| anyhow!("No judgement state found for {:?}", context))?;
+
+    let second = SecondChallenge { challenge, state };
+    let content: T::MessageType = second.clone().into();
+    let rendered = adapter
+        .render_message(&content)
+        .unwrap_or_else(|| second.challenge.value.to_string());
+
+    let result = if sandbox {
+        info!("Sandbox mode, not delivering second challenge to {}", to);
+        Ok(false)
+    } else {
+        breaker
+            .call(adapter.send_message(to.as_str(), content))
+            .await
+            .map(|_| true)
+            .map_err(|err| err.to_string())
+    };
+
+    archive_outbox_message(db, adapter.name(), entry, to, rendered, sandbox, result).await
+}
+
+/// Reminds the user about the last unverified field via a verified one. The
+/// reminder is dropped if the identity changed in the meantime.
+async fn send_nudge<T: Adapter + Send>(
+    db: &Database,
+    adapter: &mut T,
+    breaker: &CircuitBreaker,
+    sandbox: bool,
+    entry: &OutboxEntry,
+) -> Result<()> {
+    let OutboxEntry { context, field, .. } = entry;
+
+    let state = match db.fetch_judgement_state(context).await? {
+        Some(state) => state,
+        None => {
+            debug!("Dropping reminder for removed identity {:?}", context);
+            return Ok(());
+        }
+    };
+
+    let remaining = match state.last_unverified_field() {
+        Some(remaining) if !state.judgement_submitted && !state.requires_manual_review => remaining,
+        _ => {
+            debug!("Dropping outdated reminder for {:?}", context);
+            return Ok(());
+        }
+    };
+
+    let (_, to) = field.as_account_type();
+    let text = format!(
+        "Your identity {} on {} is almost verified, only the {} field ({}) remains. Please complete its challenge on the registrar website.",
+        context.display_address().short,
+        context.chain.as_str(),
+        remaining.value.type_name(),
+        remaining.value.as_account_type().1,
+    );
+
+    info!("Sending reminder to {}", to);
+    let result = if sandbox {
+        info!("Sandbox mode, not delivering reminder to {}", to);
+        Ok(false)
+    } else {
+        breaker
+            .call(adapter.send_notification(to.as_str(), text.as_str()))
+            .await
+            .map(|_| true)
+            .map_err(|err| err.to_string())
+    };
+
+    archive_outbox_message(db, adapter.name(), entry, &to, text, sandbox, result).await
+}
+
+async fn archive_outbox_message(
+    db: &Database,
+    adapter: &str,
+    entry: &OutboxEntry,
+    to: &str,
+    content: String,
+    sandbox: bool,
+    result: std::result::Result<bool, String>,
+) -> Result<()> {
+    let message = OutboundMessage {
+        adapter: adapter.to_string(),
+        context: entry.context.clone(),
+        to: to.to_string(),
+        field: Some(entry.field.type_name().to_string()),
+        content,
+        sandbox,
+        delivered: result == Ok(true),
+        error: result.clone().err(),
+        timestamp: Timestamp::now(),
+    };
+
+    let _ = db
+        .archive_outbound_message(&message)
+        .await
+        .map_err(|err| error!("Failed to archive outbound message: {:?}", err));
+
+    result
+        .map(|_| ())
+        .map_err(|err| anyhow!("{} adapter: {}", adapter, err))
 }
 
 pub struct AdapterListener {
     db: Database,
+    retry: RetryConfig,
+    handle: AdaptersHandle,
 }
 
 impl AdapterListener {
-    pub async fn new(db: Database) -> Self {
-        AdapterListener { db }
+    pub async fn new(db: Database, retry: RetryConfig) -> Self {
+        let (stop, _) = watch::channel(false);
+
+        AdapterListener {
+            db,
+            retry,
+            handle: AdaptersHandle {
+                stop: Arc::new(stop),
+                adapters: Default::default(),
+                tasks: Default::default(),
+            },
+        }
     }
-    pub async fn start_message_adapter<T>(&self, mut adapter: T, timeout: u64)
-    where
+    /// Handle to stop the adapters started by this listener.
+    pub fn handle(&self) -> AdaptersHandle {
+        self.handle.clone()
+    }
+    /// Starts fetching messages from the adapter. In `sandbox` mode, messages
+    /// which would be sent are only logged and archived, but not delivered.
+    pub async fn start_message_adapter<T>(
+        &self,
+        mut adapter: T,
+        mut interval: PollingInterval,
+        sandbox: bool,
+    ) where
         T: 'static + Adapter + Send,
-        <T as Adapter>::MessageType: From<ExpectedMessage>,
+        <T as Adapter>::MessageType: From<SecondChallenge>,
     {
-        let mut interval = interval(Duration::from_secs(timeout));
+        let db = self.db.clone();
+        let mut recent = RecentMessages::default();
+        let breaker = CircuitBreaker::new(adapter.name(), &self.retry);
+        let mut stop = self.handle.stop.subscribe();
+        let task = actix::spawn(async move {
+            // Skip messages which were processed before a restart.
+            if adapter.has_ordered_ids() {
+                match db.fetch_watermark(adapter.name()).await {
+                    Ok(Some(id)) => {
+                        debug!("Skipping {} messages up to {:?}", adapter.name(), id);
+                        adapter.set_watermark(id);
+                    }
+                    Ok(None) => {}
+                    Err(err) => error!("Failed to fetch watermark from database: {:?}", err),
+                }
+            }
 
-        let mut db = self.db.clone();
-        let mut cursor = EventCursor::new();
-        actix::spawn(async move {
+            let mut paused = false;
             loop {
-                // Timeout (skipped the first time);
-                interval.tick().await;
+                // Paused adapters neither fetch nor send messages. Pending
+                // events are processed once resumed, since the cursor is not
+                // advanced in the meantime.
+                match db.is_adapter_paused(adapter.service()).await {
+                    Ok(is_paused) => {
+                        if is_paused != paused {
+                            info!(
+                                "{} adapter {}",
+                                adapter.name(),
+                                if is_paused { "paused" } else { "resumed" }
+                            );
+                            paused = is_paused;
+                        }
+
+                        if is_paused {
+                            if wait_or_stop(interval.current(), &mut stop).await {
+                                break;
+                            }
+                            continue;
+                        }
+                    }
+                    Err(err) => error!("Failed to check whether adapter is paused: {:?}", err),
+                }
 
                 // Fetch message and send it to the listener, if any.
-                match adapter.fetch_messages().await {
+                match breaker.call(adapter.fetch_messages()).await {
                     Ok(messages) => {
-                        for message in messages {
+                        let previous = interval.current();
+                        let next = interval.next(messages.len());
+                        if next != previous {
+                            debug!("Polling {} adapter every {:?}", adapter.name(), next);
+                        }
+                        metrics::observe_adapter_poll(adapter.name(), messages.len(), next);
+
+                        let mut watermark = None;
+                        let mut failed = false;
+
+                        let total = messages.len();
+                        for (processed, message) in messages.into_iter().enumerate() {
+                            metrics::set_adapter_queue_depth(adapter.name(), total - processed);
+
+                            if adapter.is_shared() {
+                                match db.claim_message(adapter.service(), message.id).await {
+                                    Ok(true) => {}
+                                    Ok(false) => {
+                                        debug!(
+                                            "Skipping message {:?}, already processed by another {} adapter",
+                                            message.id,
+                                            adapter.name()
+                                        );
+                                        continue;
+                                    }
+                                    Err(err) => {
+                                        error!("Failed to claim message: {:?}", err);
+                                        failed = true;
+                                        continue;
+                                    }
+                                }
+                            }
+
                             info!("Processing message from: {:?}", message.origin);
+                            let message = recent.combine(message);
+                            match db.verify_message(&message).await {
+                                Ok(_) => {
+                                    watermark = watermark.max(Some(message.id));
+                                    adapter.mark_processed(message.id);
+                                }
+                                Err(err) => {
+                                    error!("Error when verifying message: {:?}", err);
+                                    failed = true;
+                                }
+                            }
+                        }
+
+                        metrics::set_adapter_queue_depth(adapter.name(), 0);
+
+                        // Only persist the watermark if all messages were
+                        // processed, so failed ones are retried after a restart.
+                        if let (Some(id), false, true) =
+                            (watermark, failed, adapter.has_ordered_ids())
+                        {
                             let _ = db
-                                .verify_message(&message)
+                                .update_watermark(adapter.name(), id)
                                 .await
-                                .map_err(|err| error!("Error when verifying message: {:?}", err));
+                                .map_err(|err| {
+                                    error!("Failed to update watermark in database: {:?}", err)
+                                });
                         }
                     }
+                    Err(err) if err.is::<CircuitOpen>() => {
+                        debug!(
+                            "{} ({} calls skipped in total)",
+                            err,
+                            breaker.stats().rejections.load(Ordering::Relaxed)
+                        );
+                    }
                     Err(err) => {
                         error!(
                             "Error fetching messages in {} adapter: {:?}",
@@ -184,40 +898,70 @@ impl AdapterListener {
                     }
                 }
 
-                // Check if a second challenge must be sent to the user directly.
-                match db.fetch_events(&mut cursor).await {
-                    Ok(events) => {
-                        for event in &events {
-                            if let NotificationMessage::AwaitingSecondChallenge { context, field } =
-                                event
-                            {
-                                if let IdentityFieldValue::Email(to) = field {
-                                    if adapter.name() == "email" {
-                                        info!("Sending second challenge to {}", to);
-                                        if let Ok(challenge) = db
-                                            .fetch_second_challenge(context, field)
-                                            .await
-                                            .map_err(|err| error!("Failed to fetch second challenge from database: {:?}", err)) {
-                                                let _ = adapter
-                                                    .send_message(to.as_str(), challenge.into())
-                                                    .await
-                                                    .map_err(|err| error!("Failed to send second challenge to {} ({} adapter): {:?}", to, adapter.name(), err));
-                                                }
-                                    }
-                                }
-                            }
+                // Send the messages requested via the outbox, e.g. second
+                // challenges.
+                loop {
+                    let entry = match db.claim_outbox_entry(adapter.name(), OUTBOX_LEASE).await {
+                        Ok(Some(entry)) => entry,
+                        Ok(None) => break,
+                        Err(err) => {
+                            error!("Failed to claim outbox entry: {:?}", err);
+                            break;
                         }
-                    }
-                    Err(err) => {
-                        error!(
-                            "Error fetching messages in {} adapter: {:?}",
-                            adapter.name(),
-                            err
-                        );
+                    };
+
+                    let result =
+                        send_outbox_message(&db, &mut adapter, &breaker, sandbox, &entry).await;
+                    let update = match result {
+                        Ok(()) => db.complete_outbox_entry(&entry.id).await,
+                        Err(err) if entry.attempts + 1 >= MAX_OUTBOX_ATTEMPTS => {
+                            error!(
+                                "Giving up sending {:?} to {:?} after {} attempts: {:?}",
+                                entry.kind, entry.context, MAX_OUTBOX_ATTEMPTS, err
+                            );
+                            db.complete_outbox_entry(&entry.id).await
+                        }
+                        Err(err) => {
+                            error!(
+                                "Failed to send {:?} to {:?}: {:?}",
+                                entry.kind, entry.context, err
+                            );
+                            let delay = OUTBOX_RETRY_DELAY * u64::from(entry.attempts + 1);
+                            db.retry_outbox_entry(&entry.id, Timestamp::with_offset(delay))
+                                .await
+                        }
+                    };
+
+                    if let Err(err) = update {
+                        error!("Failed to update outbox entry: {:?}", err);
+                        break;
                     }
                 }
+
+                if wait_or_stop(interval.current(), &mut stop).await {
+                    break;
+                }
             }
+
+            info!("Stopped {} adapter", adapter.name());
         });
+
+        self.handle.adapters.lock().unwrap().push(task);
+    }
+}
+
+/// Waits for the given duration. Returns `true` if the adapters were stopped
+/// in the meantime.
+async fn wait_or_stop(duration: Duration, stop: &mut watch::Receiver<bool>) -> bool {
+    if *stop.borrow() {
+        return true;
+    }
+
+    tokio::select! {
+        _ = sleep(duration) => false,
+        // Disabled if all handles were dropped, the adapter then runs until
+        // the system stops.
+        Ok(()) = stop.changed() => *stop.borrow(),
     }
 }
 
@@ -259,4 +1003,75 @@ pub mod tests {
             unimplemented!()
         }
     }
+
+    #[test]
+    fn adaptive_polling_interval() {
+        let secs = Duration::from_secs;
+        let mut interval = PollingInterval::new(5, Some(30));
+
+        // Backs off after consecutive empty polls.
+        assert_eq!(interval.next(0), secs(5));
+        assert_eq!(interval.next(0), secs(5));
+        assert_eq!(interval.next(0), secs(10));
+        for _ in 0..3 {
+            interval.next(0);
+        }
+        assert_eq!(interval.current(), secs(20));
+        for _ in 0..6 {
+            interval.next(0);
+        }
+        assert_eq!(interval.current(), secs(30));
+
+        // Resets once messages arrive.
+        assert_eq!(interval.next(2), secs(5));
+        assert_eq!(interval.next(0), secs(5));
+    }
+
+    #[test]
+    fn fixed_polling_interval() {
+        let mut interval = PollingInterval::fixed(1);
+        for _ in 0..10 {
+            assert_eq!(interval.next(0), Duration::from_secs(1));
+        }
+
+        // A maximum below the base is ignored.
+        let mut interval = PollingInterval::new(5, Some(1));
+        for _ in 0..10 {
+            assert_eq!(interval.next(0), Duration::from_secs(5));
+        }
+    }
+
+    #[test]
+    fn combine_split_messages() {
+        let message = |origin: &str, value: &str, timestamp: u64| ExternalMessage {
+            origin: ExternalMessageType::Matrix(origin.to_string()),
+            id: MessageId::from(0u32),
+            timestamp: Timestamp::from(timestamp),
+            values: vec![value.to_string().into()],
+            metadata: None,
+        };
+
+        let expected = ExpectedMessage {
+            value: "abcdef".to_string().into(),
+            ..ExpectedMessage::random()
+        };
+
+        let mut recent = RecentMessages::default();
+
+        let first = recent.combine(message("@alice:matrix.org", "abc\n", 100));
+        assert_eq!(first.values.len(), 1);
+        assert!(!expected.is_message_valid(&first));
+
+        // Messages of other senders are not combined.
+        let other = recent.combine(message("@bob:matrix.org", "def", 110));
+        assert!(!expected.is_message_valid(&other));
+
+        let second = recent.combine(message("@alice:matrix.org", "def", 120));
+        assert_eq!(second.values.len(), 2);
+        assert!(expected.is_message_valid(&second));
+
+        // Messages outside of the window are dropped.
+        let late = recent.combine(message("@alice:matrix.org", "def", 200));
+        assert_eq!(late.values.len(), 1);
+    }
 }
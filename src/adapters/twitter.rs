@@ -1,5 +1,9 @@
 use crate::adapters::Adapter;
-use crate::primitives::{ExternalMessage, ExternalMessageType, MessageId, Timestamp};
+use crate::database::Database;
+use crate::primitives::{
+    ExternalMessage, ExternalMessageType, MessageId, ProviderMetadata, Timestamp,
+};
+use crate::retry::{retry, RetryConfig};
 use crate::Result;
 use base64::engine::{general_purpose, Engine};
 use hmac::{Hmac, Mac};
@@ -56,11 +60,109 @@ impl TryFrom<String> for TwitterId {
     }
 }
 
+// Seconds for which the handle of a Twitter ID is cached by default.
+pub const DEFAULT_ID_CACHE_TTL: u64 = 7 * 86_400;
+
+// Up to 100 direct message events can be fetched per request.
+const DM_PAGE_SIZE: &str = "100";
+// Maximum number of pages fetched per poll, older messages are skipped.
+const DM_PAGE_LIMIT: usize = 10;
+
+/// How requests to the Twitter API are authenticated.
+#[derive(Clone)]
+enum TwitterAuth {
+    OAuth1(OAuth1Credentials),
+    // OAuth 2.0 user context access token.
+    Bearer(String),
+}
+
+/// OAuth 1.0a user context credentials.
+#[derive(Clone)]
+struct OAuth1Credentials {
+    consumer_key: String,
+    consumer_secret: String,
+    token: String,
+    token_secret: String,
+}
+
+impl OAuth1Credentials {
+    /// Creates a signature as documented here:
+    /// https://developer.twitter.com/en/docs/authentication/oauth-1-0a/creating-a-signature
+    fn header(&self, url: &str, params: Option<&[(&str, &str)]>) -> String {
+        use urlencoding::encode;
+
+        // Prepare  required data.
+        let nonce = gen_nonce();
+        let timestamp = gen_timestamp().to_string();
+
+        // Create  OAuth 1.0 fields.
+        let mut fields = vec![
+            ("oauth_consumer_key", self.consumer_key.as_str()),
+            ("oauth_nonce", nonce.as_str()),
+            ("oauth_signature_method", "HMAC-SHA1"),
+            ("oauth_timestamp", &timestamp),
+            ("oauth_token", self.token.as_str()),
+            ("oauth_version", "1.0"),
+        ];
+
+        if let Some(params) = params {
+            fields.append(&mut params.to_vec());
+        }
+
+        fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut params = String::new();
+        for (name, val) in &fields {
+            params.push_str(&format!("{}={}&", encode(name), encode(val)));
+        }
+
+        // Remove the trailing `&`.
+        params.pop();
+
+        let base = format!("GET&{}&{}", encode(url), encode(&params));
+
+        // Sign the base string.
+        let sign_key = format!(
+            "{}&{}",
+            encode(&self.consumer_secret),
+            encode(&self.token_secret)
+        );
+
+        let mut mac: Hmac<Sha1> = Hmac::new_from_slice(sign_key.as_bytes()).unwrap();
+        mac.update(base.as_bytes());
+
+        // Create the resulting hash.
+        let sig = general_purpose::STANDARD.encode(mac.finalize().into_bytes());
+
+        // Insert the signature;
+        fields.push(("oauth_signature", &sig));
+        fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        // Merge all fields into the OAuth header.
+        let mut oauth_header = String::new();
+        oauth_header.push_str("OAuth ");
+
+        for (name, val) in &fields {
+            oauth_header.push_str(&format!("{}={}, ", encode(name), encode(val)))
+        }
+
+        // Remove the trailing `, `.
+        oauth_header.pop();
+        oauth_header.pop();
+
+        oauth_header
+    }
+}
+
 pub struct TwitterBuilder {
     consumer_key: Option<String>,
     consumer_secret: Option<String>,
     token: Option<String>,
     token_secret: Option<String>,
+    bearer_token: Option<String>,
+    db: Option<Database>,
+    id_cache_ttl: u64,
+    retry: RetryConfig,
 }
 
 impl TwitterBuilder {
@@ -70,6 +172,10 @@ impl TwitterBuilder {
             consumer_secret: None,
             token: None,
             token_secret: None,
+            bearer_token: None,
+            db: None,
+            id_cache_ttl: DEFAULT_ID_CACHE_TTL,
+            retry: RetryConfig::default(),
         }
     }
     pub fn consumer_key(mut self, key: String) -> Self {
@@ -88,21 +194,50 @@ impl TwitterBuilder {
         self.token_secret = Some(secret);
         self
     }
+    /// Authenticates with an OAuth 2.0 access token instead of the OAuth 1.0a
+    /// credentials.
+    pub fn bearer_token(mut self, token: String) -> Self {
+        self.bearer_token = Some(token);
+        self
+    }
+    pub fn db(mut self, db: Database) -> Self {
+        self.db = Some(db);
+        self
+    }
+    pub fn id_cache_ttl(mut self, ttl: u64) -> Self {
+        self.id_cache_ttl = ttl;
+        self
+    }
+    pub fn retry(mut self, config: RetryConfig) -> Self {
+        self.retry = config;
+        self
+    }
     pub fn build(self) -> Result<TwitterClient> {
+        let auth = match self.bearer_token {
+            Some(token) => TwitterAuth::Bearer(token),
+            None => TwitterAuth::OAuth1(OAuth1Credentials {
+                consumer_key: self
+                    .consumer_key
+                    .ok_or_else(|| anyhow!("consumer key name not specified"))?,
+                consumer_secret: self
+                    .consumer_secret
+                    .ok_or_else(|| anyhow!("consumer secret name not specified"))?,
+                token: self.token.ok_or_else(|| anyhow!("token not specified"))?,
+                token_secret: self
+                    .token_secret
+                    .ok_or_else(|| anyhow!("token secret not specified"))?,
+            }),
+        };
+
         Ok(TwitterClient {
             client: Client::new(),
-            consumer_key: self
-                .consumer_key
-                .ok_or_else(|| anyhow!("consumer key name not specified"))?,
-            consumer_secret: self
-                .consumer_secret
-                .ok_or_else(|| anyhow!("consumer secret name not specified"))?,
-            token: self.token.ok_or_else(|| anyhow!("token not specified"))?,
-            token_secret: self
-                .token_secret
-                .ok_or_else(|| anyhow!("token secret not specified"))?,
+            auth,
+            db: self.db.ok_or_else(|| anyhow!("database not specified"))?,
+            id_cache_ttl: self.id_cache_ttl,
+            retry: self.retry,
             twitter_ids: HashMap::new(),
             cache: HashSet::new(),
+            watermark: None,
         })
     }
 }
@@ -123,13 +258,20 @@ fn gen_timestamp() -> u64 {
 #[derive(Clone)]
 pub struct TwitterClient {
     client: Client,
-    consumer_key: String,
-    consumer_secret: String,
-    token: String,
-    token_secret: String,
-    twitter_ids: HashMap<TwitterId, String>,
+    auth: TwitterAuth,
+    db: Database,
+    // Entries older than this (in seconds) are looked up again, in case the
+    // handle was changed.
+    id_cache_ttl: u64,
+    // Failed requests to the Twitter API are retried.
+    retry: RetryConfig,
+    // Handles of the Twitter IDs, together with the time of the lookup.
+    // Persisted in the database, so lookups survive restarts.
+    twitter_ids: HashMap<TwitterId, (String, Timestamp)>,
     // Keep track of messages.
     cache: HashSet<MessageId>,
+    // ID of the last message processed before a restart.
+    watermark: Option<MessageId>,
 }
 
 impl TwitterClient {
@@ -137,19 +279,41 @@ impl TwitterClient {
         debug!("Requesting Twitter messages");
         // Request message on parse those into a simpler type.
         let url = String::from("https://api.twitter.com/2/dm_events");
-        let mut params = vec![];
-        params.push(("event_types", "MessageCreate"));
-        params.push(("dm_event.fields", "id,text,created_at,sender_id"));
-        let mut messages = self
-            .get_request::<ApiMessageRequest>(
-                &url,
-                Some(&params),
-            )
-            .await?
-            .parse()?;
 
-        // Skip message if it was already processed.
-        messages.retain(|message| !self.cache.contains(&message.id.into()));
+        // Events are returned newest first, so pages are fetched until one
+        // contains an already processed message.
+        let mut messages = vec![];
+        let mut next_token: Option<String> = None;
+        for _ in 0..DM_PAGE_LIMIT {
+            let mut params = vec![
+                ("event_types", "MessageCreate"),
+                ("dm_event.fields", "id,text,created_at,sender_id"),
+                ("max_results", DM_PAGE_SIZE),
+            ];
+            if let Some(token) = next_token.as_deref() {
+                params.push(("pagination_token", token));
+            }
+
+            let resp = self
+                .get_request::<ApiMessageRequest>(&url, Some(&params))
+                .await?;
+            next_token = resp.next_token();
+
+            let mut page = resp.parse()?;
+            let fetched = page.len();
+
+            // Skip message if it was already processed.
+            page.retain(|message| {
+                let id: MessageId = message.id.into();
+                !self.cache.contains(&id) && Some(id) > self.watermark
+            });
+
+            let done = page.len() < fetched || next_token.is_none();
+            messages.append(&mut page);
+            if done {
+                break;
+            }
+        }
 
         if messages.is_empty() {
             debug!("No new Twitter messages found");
@@ -159,24 +323,41 @@ impl TwitterClient {
         }
 
         // Collect all the Twitter Ids that need to be looked-up.
+        let expired = Timestamp::from(Timestamp::now().raw().saturating_sub(self.id_cache_ttl));
         #[rustfmt::skip]
-        let mut to_lookup: Vec<&TwitterId> = messages
+        let mut to_lookup: Vec<TwitterId> = messages
             .iter()
             .filter(|message| {
                 // Only lookup Ids that aren't cached.
-                !self.twitter_ids.contains_key(&message.sender)
+                !self.is_cached(&message.sender, expired)
             })
-            .map(|message| &message.sender)
+            .map(|message| message.sender.clone())
             .collect();
 
         // Remove duplicates.
         to_lookup.sort();
         to_lookup.dedup();
 
+        // Check the persisted cache first, only the remaining Ids are
+        // requested from the API.
+        if !to_lookup.is_empty() {
+            let ids: Vec<String> = to_lookup.iter().map(|id| id.as_u64().to_string()).collect();
+            for (id, entry) in self.db.fetch_twitter_handles(&ids, expired).await? {
+                self.twitter_ids.insert(TwitterId::try_from(id)?, entry);
+            }
+
+            to_lookup.retain(|id| !self.is_cached(id, expired));
+        }
+
         // Lookup Twitter Ids and insert those into the cache.
         if !to_lookup.is_empty() {
+            let to_lookup: Vec<&TwitterId> = to_lookup.iter().collect();
             let lookup_results = self.lookup_twitter_id(Some(&to_lookup)).await?;
-            self.twitter_ids.extend(lookup_results);
+            let now = Timestamp::now();
+            for (id, handle) in lookup_results {
+                self.db.store_twitter_handle(&id.as_u64().to_string(), &handle).await?;
+                self.twitter_ids.insert(id, (handle, now));
+            }
         }
 
         // Parse all messages into `TwitterMessage`.
@@ -186,6 +367,7 @@ impl TwitterClient {
                 .twitter_ids
                 .get(&message.sender)
                 .ok_or_else(|| anyhow!("Failed to find Twitter handle based on Id"))?
+                .0
                 .clone();
 
             let id = message.id.into();
@@ -195,6 +377,13 @@ impl TwitterClient {
                 id,
                 timestamp: Timestamp::now(),
                 values: vec![message.message.into()],
+                metadata: Some(ProviderMetadata {
+                    provider_id: Some(message.id.to_string()),
+                    details: vec![("sender_id".to_string(), message.sender.as_u64().to_string())]
+                        .into_iter()
+                        .collect(),
+                    ..Default::default()
+                }),
             });
 
             self.cache.insert(id);
@@ -202,79 +391,24 @@ impl TwitterClient {
 
         Ok(parsed_messages)
     }
-    /// Creates a signature as documented here:
-    /// https://developer.twitter.com/en/docs/authentication/oauth-1-0a/creating-a-signature
+    // Whether the handle of the Id was looked up after `expired`.
+    fn is_cached(&self, id: &TwitterId, expired: Timestamp) -> bool {
+        matches!(self.twitter_ids.get(id), Some((_, cached)) if cached.raw() >= expired.raw())
+    }
     fn authenticate_request(
         &self,
         url: &str,
         request: &mut Request,
         params: Option<&[(&str, &str)]>,
     ) -> Result<()> {
-        use urlencoding::encode;
-
-        // Prepare  required data.
-        let nonce = gen_nonce();
-        let timestamp = gen_timestamp().to_string();
-
-        // Create  OAuth 1.0 fields.
-        let mut fields = vec![
-            ("oauth_consumer_key", self.consumer_key.as_str()),
-            ("oauth_nonce", nonce.as_str()),
-            ("oauth_signature_method", "HMAC-SHA1"),
-            ("oauth_timestamp", &timestamp),
-            ("oauth_token", self.token.as_str()),
-            ("oauth_version", "1.0"),
-        ];
+        let header = match &self.auth {
+            TwitterAuth::OAuth1(credentials) => credentials.header(url, params),
+            TwitterAuth::Bearer(token) => format!("Bearer {}", token),
+        };
 
-        if let Some(params) = params {
-            fields.append(&mut params.to_vec());
-        }
-
-        fields.sort_by(|(a, _), (b, _)| a.cmp(b));
-
-        let mut params = String::new();
-        for (name, val) in &fields {
-            params.push_str(&format!("{}={}&", encode(name), encode(val)));
-        }
-
-        // Remove the trailing `&`.
-        params.pop();
-
-        let base = format!("GET&{}&{}", encode(url), encode(&params));
-
-        // Sign the base string.
-        let sign_key = format!(
-            "{}&{}",
-            encode(&self.consumer_secret),
-            encode(&self.token_secret)
-        );
-
-        let mut mac: Hmac<Sha1> = Hmac::new_from_slice(sign_key.as_bytes()).unwrap();
-        mac.update(base.as_bytes());
-
-        // Create the resulting hash.
-        let sig = general_purpose::STANDARD.encode(mac.finalize().into_bytes());
-
-        // Insert the signature;
-        fields.push(("oauth_signature", &sig));
-        fields.sort_by(|(a, _), (b, _)| a.cmp(b));
-
-        // Merge all fields into the OAuth header.
-        let mut oauth_header = String::new();
-        oauth_header.push_str("OAuth ");
-
-        for (name, val) in &fields {
-            oauth_header.push_str(&format!("{}={}, ", encode(name), encode(val)))
-        }
-
-        // Remove the trailing `, `.
-        oauth_header.pop();
-        oauth_header.pop();
-
-        // Inserth the authentication header into the request.
         request
             .headers_mut()
-            .insert(header::AUTHORIZATION, HeaderValue::from_str(&oauth_header)?);
+            .insert(header::AUTHORIZATION, HeaderValue::from_str(&header)?);
 
         Ok(())
     }
@@ -295,10 +429,16 @@ impl TwitterClient {
             full_url.pop();
         }
 
-        let mut request = self.client.get(&full_url).build()?;
-        self.authenticate_request(url, &mut request, params)?;
-        let resp = self.client.execute(request).await?;
-        let txt = resp.text().await?;
+        // The request is built on each attempt, since the OAuth header
+        // contains a nonce.
+        let txt = retry(&self.retry, "twitter", "Twitter request", || async {
+            let mut request = self.client.get(&full_url).build()?;
+            self.authenticate_request(url, &mut request, params)?;
+            let resp = self.client.execute(request).await?;
+
+            Result::Ok(resp.text().await?)
+        })
+        .await?;
 
         debug!("Twitter response: {:?}", txt);
 
@@ -331,10 +471,10 @@ impl TwitterClient {
         }
 
         #[derive(Deserialize)]
-        // Only `name` required.
+        // Only `username` required.
         struct UserData {
             id: String,
-            name: String,
+            username: String,
         }
 
         debug!("Params: {:?}", params);
@@ -349,7 +489,7 @@ impl TwitterClient {
             .into_iter()
             .map(|user| {
                 let id = TwitterId(user.id.parse().expect("Failed to parse user ID"));
-                (id, format!("@{}", user.name.to_lowercase()))
+                (id, format!("@{}", user.username.to_lowercase()))
             })
             .collect();    
         Ok(result)
@@ -358,7 +498,16 @@ impl TwitterClient {
 
 #[derive(Debug, Deserialize, Serialize)]
 struct ApiMessageRequest {
+    // Not present if there are no (further) events.
+    #[serde(default)]
     data: Vec<ApiMessage>,
+    meta: Option<ApiMessageMeta>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct ApiMessageMeta {
+    // Token of the next (older) page, not present on the last page.
+    next_token: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -370,6 +519,9 @@ struct ApiMessage {
 }
 
 impl ApiMessageRequest {
+    fn next_token(&self) -> Option<String> {
+        self.meta.as_ref().and_then(|meta| meta.next_token.clone())
+    }
     fn parse(self) -> Result<Vec<ReceivedMessageContext>> {
         let mut messages = vec![];
 
@@ -397,6 +549,16 @@ impl Adapter for TwitterClient {
     fn name(&self) -> &'static str {
         "Twitter"
     }
+    fn service(&self) -> &'static str {
+        "twitter"
+    }
+    // Direct message event IDs are Snowflake IDs, which increase over time.
+    fn has_ordered_ids(&self) -> bool {
+        true
+    }
+    fn set_watermark(&mut self, id: MessageId) {
+        self.watermark = Some(id);
+    }
     async fn fetch_messages(&mut self) -> Result<Vec<ExternalMessage>> {
         self.request_messages().await
     }
@@ -404,3 +566,277 @@ impl Adapter for TwitterClient {
         unimplemented!()
     }
 }
+
+// Up to 100 accounts can be looked up per request.
+const PROFILE_LOOKUP_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+struct ApiProfileResponse {
+    #[serde(default)]
+    data: Vec<ApiProfile>,
+    includes: Option<ApiProfileIncludes>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiProfile {
+    id: String,
+    username: String,
+    description: Option<String>,
+    pinned_tweet_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiProfileIncludes {
+    #[serde(default)]
+    tweets: Vec<ApiTweet>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiTweet {
+    id: String,
+    text: String,
+}
+
+/// Polls the profiles of Twitter accounts with pending challenges, so users
+/// who cannot send direct messages to the registrar can place the challenge
+/// in their profile bio or pinned tweet instead.
+pub struct TwitterProfiles {
+    client: TwitterClient,
+    db: Database,
+    // Last seen bio and pinned tweet per handle, only changes are verified.
+    seen: HashMap<String, String>,
+}
+
+impl TwitterProfiles {
+    pub fn new(client: TwitterClient, db: Database) -> Self {
+        TwitterProfiles {
+            client,
+            db,
+            seen: HashMap::new(),
+        }
+    }
+    async fn request_profiles(&mut self) -> Result<Vec<ExternalMessage>> {
+        debug!("Requesting Twitter profiles");
+        let handles = self.db.fetch_unverified_twitter_handles().await?;
+
+        // Forget accounts which are no longer pending.
+        self.seen.retain(|handle, _| handles.contains(handle));
+
+        let url = String::from("https://api.twitter.com/2/users/by");
+        let mut messages = vec![];
+        for chunk in handles.chunks(PROFILE_LOOKUP_LIMIT) {
+            let usernames = chunk
+                .iter()
+                .map(|handle| handle.trim_start_matches('@'))
+                .collect::<Vec<&str>>()
+                .join(",");
+
+            let params = vec![
+                ("usernames", usernames.as_str()),
+                ("user.fields", "description,pinned_tweet_id"),
+                ("expansions", "pinned_tweet_id"),
+                ("tweet.fields", "text"),
+            ];
+
+            let resp = self
+                .client
+                .get_request::<ApiProfileResponse>(&url, Some(&params))
+                .await?;
+
+            // Keep the ID cache of the message adapter up to date, e.g. if a
+            // handle was taken over by a different account.
+            for profile in &resp.data {
+                self.db
+                    .store_twitter_handle(&profile.id, &format!("@{}", profile.username.to_lowercase()))
+                    .await?;
+            }
+
+            messages.append(&mut profile_messages(&mut self.seen, chunk, resp));
+        }
+
+        Ok(messages)
+    }
+}
+
+/// Converts the profiles into messages, one per handle, containing the bio and
+/// the pinned tweet. Profiles which did not change since the last poll are
+/// skipped.
+fn profile_messages(
+    seen: &mut HashMap<String, String>,
+    handles: &[String],
+    resp: ApiProfileResponse,
+) -> Vec<ExternalMessage> {
+    let tweets = resp
+        .includes
+        .map(|includes| includes.tweets)
+        .unwrap_or_default();
+
+    let mut messages = vec![];
+    for profile in resp.data {
+        let id = match profile.id.parse::<u64>() {
+            Ok(id) => id,
+            Err(_) => {
+                warn!("Unrecognized Twitter user ID: {}", profile.id);
+                continue;
+            }
+        };
+
+        let pinned_tweet = profile
+            .pinned_tweet_id
+            .as_ref()
+            .and_then(|pinned| tweets.iter().find(|tweet| &tweet.id == pinned));
+        let pinned = pinned_tweet.map(|tweet| tweet.text.clone());
+
+        let values: Vec<String> = profile
+            .description
+            .iter()
+            .cloned()
+            .chain(pinned)
+            .filter(|value| !value.is_empty())
+            .collect();
+
+        if values.is_empty() {
+            continue;
+        }
+
+        let content = values.join("\n");
+
+        // Handles are case-insensitive.
+        for handle in handles.iter().filter(|handle| {
+            handle
+                .trim_start_matches('@')
+                .eq_ignore_ascii_case(&profile.username)
+        }) {
+            if seen.get(handle) == Some(&content) {
+                continue;
+            }
+
+            seen.insert(handle.clone(), content.clone());
+
+            messages.push(ExternalMessage {
+                origin: ExternalMessageType::Twitter(handle.clone()),
+                id: id.into(),
+                timestamp: Timestamp::now(),
+                values: values.iter().cloned().map(|value| value.into()).collect(),
+                metadata: Some(ProviderMetadata {
+                    provider_id: pinned_tweet.map(|tweet| tweet.id.clone()),
+                    details: vec![("user_id".to_string(), profile.id.clone())]
+                        .into_iter()
+                        .collect(),
+                    ..Default::default()
+                }),
+            });
+        }
+    }
+
+    messages
+}
+
+#[async_trait]
+impl Adapter for TwitterProfiles {
+    type MessageType = ();
+
+    fn name(&self) -> &'static str {
+        "Twitter profiles"
+    }
+    fn service(&self) -> &'static str {
+        "twitter"
+    }
+    async fn fetch_messages(&mut self) -> Result<Vec<ExternalMessage>> {
+        self.request_profiles().await
+    }
+    // Challenges are only read from profiles.
+    async fn send_message(&mut self, _to: &str, _content: Self::MessageType) -> Result<()> {
+        Err(anyhow!("Twitter profiles do not support sending messages"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paginated_messages() {
+        let resp: ApiMessageRequest = serde_json::from_str(
+            r#"{
+                "data": [
+                    {
+                        "id": "1580705921830768647",
+                        "text": "challenge: abcd",
+                        "event_type": "MessageCreate",
+                        "sender_id": "2244994945",
+                        "created_at": "2022-10-14T00:00:00.000Z"
+                    }
+                ],
+                "meta": {
+                    "result_count": 1,
+                    "next_token": "18LAA581J5II7LA00C00ZZZZ"
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            resp.next_token(),
+            Some("18LAA581J5II7LA00C00ZZZZ".to_string())
+        );
+        assert_eq!(
+            resp.parse().unwrap(),
+            vec![ReceivedMessageContext {
+                sender: TwitterId::from(2244994945),
+                id: 1580705921830768647,
+                message: "challenge: abcd".to_string(),
+            }]
+        );
+
+        // The last page carries neither events nor a token.
+        let resp: ApiMessageRequest =
+            serde_json::from_str(r#"{ "meta": { "result_count": 0 } }"#).unwrap();
+        assert_eq!(resp.next_token(), None);
+        assert!(resp.parse().unwrap().is_empty());
+    }
+
+    #[test]
+    fn profile_challenges() {
+        let resp = || -> ApiProfileResponse {
+            serde_json::from_str(
+                r#"{
+                    "data": [
+                        {
+                            "id": "1",
+                            "username": "Alice",
+                            "description": "challenge: abcd",
+                            "pinned_tweet_id": "10"
+                        },
+                        {
+                            "id": "2",
+                            "username": "bob"
+                        }
+                    ],
+                    "includes": {
+                        "tweets": [{ "id": "10", "text": "efgh" }]
+                    }
+                }"#,
+            )
+            .unwrap()
+        };
+
+        let handles = vec!["@alice".to_string(), "@bob".to_string()];
+        let mut seen = HashMap::new();
+
+        let messages = profile_messages(&mut seen, &handles, resp());
+        assert_eq!(messages.len(), 1);
+        assert_eq!(
+            messages[0].origin,
+            ExternalMessageType::Twitter("@alice".to_string())
+        );
+        assert!(messages[0].values[0].contains("abcd"));
+        assert!(messages[0].values[1].contains("efgh"));
+
+        // Unchanged profiles are skipped.
+        assert!(profile_messages(&mut seen, &handles, resp()).is_empty());
+
+        seen.clear();
+        assert_eq!(profile_messages(&mut seen, &handles, resp()).len(), 1);
+    }
+}
@@ -0,0 +1,372 @@
+use crate::adapters::Adapter;
+use crate::database::Database;
+use crate::primitives::{ExternalMessage, ExternalMessageType, MessageId, Timestamp};
+use crate::Result;
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::{redirect, Client};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::lookup_host;
+use url::{Host, Url};
+
+// Path on the claimed domain at which the challenge is placed.
+pub const WELL_KNOWN_PATH: &str = "/.well-known/polkadot-registrar.txt";
+// Seconds after which a request to a website is aborted by default.
+pub const DEFAULT_REQUEST_TIMEOUT: u64 = 10;
+// Bytes read from the challenge file, the rest is ignored.
+const MAX_BODY_LENGTH: usize = 4_096;
+const MAX_REDIRECTS: usize = 3;
+
+/// The URL of the challenge file of the website, always fetched via HTTPS.
+/// Returns `None` for IP addresses and hosts without a domain, such as
+/// `localhost`, which could point to internal services.
+pub fn well_known_url(website: &str) -> Option<Url> {
+    let website = website.trim();
+    let url = if website.contains("://") {
+        Url::parse(website).ok()?
+    } else {
+        Url::parse(&format!("https://{}", website)).ok()?
+    };
+
+    let domain = public_domain(&url)?;
+    Url::parse(&format!("https://{}{}", domain, WELL_KNOWN_PATH)).ok()
+}
+
+/// The domain of an HTTP(S) URL, `None` for IP addresses and hosts without a
+/// domain. Checked for the claimed website and every redirect.
+fn public_domain(url: &Url) -> Option<&str> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+
+    match url.host()? {
+        Host::Domain(domain) if domain.contains('.') => Some(domain),
+        _ => None,
+    }
+}
+
+/// Whether the address is reachable on the public internet. Loopback,
+/// private, link-local, unique-local and other special-purpose addresses are
+/// refused, those could belong to internal services.
+fn is_public_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // "This network" (0.0.0.0/8).
+                || octets[0] == 0
+                // Shared address space (100.64.0.0/10).
+                || (octets[0] == 100 && (octets[1] & 0xc0) == 64)
+                // Benchmarking (198.18.0.0/15).
+                || (octets[0] == 198 && (octets[1] & 0xfe) == 18)
+                // Reserved (240.0.0.0/4).
+                || octets[0] >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_public_ip(&IpAddr::V4(mapped));
+            }
+
+            let segments = ip.segments();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                // Unique local (fc00::/7).
+                || (segments[0] & 0xfe00) == 0xfc00
+                // Link-local (fe80::/10).
+                || (segments[0] & 0xffc0) == 0xfe80
+                // Documentation (2001:db8::/32).
+                || (segments[0] == 0x2001 && segments[1] == 0x0db8))
+        }
+    }
+}
+
+/// Resolves the hosts of websites and refuses domains pointing to internal
+/// addresses. Applies to every connection, including redirects, so a claimed
+/// domain cannot be used to reach internal services.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(resolve_public(name))
+    }
+}
+
+async fn resolve_public(
+    name: Name,
+) -> std::result::Result<Addrs, Box<dyn std::error::Error + Send + Sync>> {
+    let addrs: Vec<SocketAddr> = lookup_host((name.as_str(), 0)).await?.collect();
+    if addrs.is_empty() || !addrs.iter().all(|addr| is_public_ip(&addr.ip())) {
+        return Err(format!("{} does not resolve to a public address", name).into());
+    }
+
+    Ok(Box::new(addrs.into_iter()))
+}
+
+/// The client fetching the challenge files. Redirects are only followed to
+/// other public domains.
+fn web_client(timeout: u64) -> Result<Client> {
+    let redirects = redirect::Policy::custom(|attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else if public_domain(attempt.url()).is_none() {
+            let err = format!("refused redirect to {}", attempt.url());
+            attempt.error(err)
+        } else {
+            attempt.follow()
+        }
+    });
+
+    Ok(Client::builder()
+        .timeout(Duration::from_secs(timeout))
+        .redirect(redirects)
+        .dns_resolver(Arc::new(PublicResolver))
+        .build()?)
+}
+
+async fn fetch_challenge_file(client: &Client, url: Url) -> Result<String> {
+    let mut resp = client.get(url).send().await?.error_for_status()?;
+
+    let mut body = vec![];
+    while let Some(chunk) = resp.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() >= MAX_BODY_LENGTH {
+            body.truncate(MAX_BODY_LENGTH);
+            break;
+        }
+    }
+
+    Ok(String::from_utf8_lossy(&body).to_string())
+}
+
+/// Verifies the web fields of pending identities by fetching the challenge
+/// file from the claimed domain.
+pub struct WebVerifier {
+    client: Client,
+    db: Database,
+    // Last seen content per website, only changes are verified.
+    seen: HashMap<String, String>,
+}
+
+impl WebVerifier {
+    pub fn new(db: Database, timeout: u64) -> Result<Self> {
+        Ok(WebVerifier {
+            client: web_client(timeout)?,
+            db,
+            seen: HashMap::new(),
+        })
+    }
+    async fn request_websites(&mut self) -> Result<Vec<ExternalMessage>> {
+        debug!("Requesting challenge files of websites");
+        let websites = self.db.fetch_unverified_websites().await?;
+
+        // Forget websites which are no longer pending.
+        self.seen.retain(|website, _| websites.contains(website));
+
+        let mut messages = vec![];
+        for website in websites {
+            let url = match well_known_url(&website) {
+                Some(url) => url,
+                None => {
+                    debug!("Skipping website without a valid domain: {}", website);
+                    continue;
+                }
+            };
+
+            // Unreachable websites are common, those are retried on the next
+            // poll.
+            let content = match fetch_challenge_file(&self.client, url.clone()).await {
+                Ok(content) => content,
+                Err(err) => {
+                    debug!("Failed to fetch {}: {:?}", url, err);
+                    continue;
+                }
+            };
+
+            if let Some(message) = website_message(&mut self.seen, &website, &content) {
+                messages.push(message);
+            }
+        }
+
+        Ok(messages)
+    }
+}
+
+/// Converts the content of the challenge file into a message, one value per
+/// line. Returns `None` if the file is empty or did not change since the last
+/// poll.
+fn website_message(
+    seen: &mut HashMap<String, String>,
+    website: &str,
+    content: &str,
+) -> Option<ExternalMessage> {
+    let values: Vec<String> = content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect();
+
+    if values.is_empty() || seen.get(website).map(|s| s.as_str()) == Some(content) {
+        return None;
+    }
+
+    seen.insert(website.to_string(), content.to_string());
+
+    let timestamp = Timestamp::now();
+    Some(ExternalMessage {
+        origin: ExternalMessageType::Web(website.to_string()),
+        // Challenge files carry no ID.
+        id: MessageId::from(timestamp.raw()),
+        timestamp,
+        values: values.into_iter().map(|value| value.into()).collect(),
+        metadata: None,
+    })
+}
+
+#[async_trait]
+impl Adapter for WebVerifier {
+    type MessageType = ();
+
+    fn name(&self) -> &'static str {
+        "web"
+    }
+    async fn fetch_messages(&mut self) -> Result<Vec<ExternalMessage>> {
+        self.request_websites().await
+    }
+    // Challenges are only read from websites.
+    async fn send_message(&mut self, _to: &str, _content: Self::MessageType) -> Result<()> {
+        Err(anyhow!("Websites do not support sending messages"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::MessagePart;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn website_urls() {
+        let expected = "https://alice.com/.well-known/polkadot-registrar.txt";
+        for website in [
+            "alice.com",
+            "Alice.com",
+            "https://alice.com/",
+            "http://alice.com/about?lang=en",
+            "https://alice.com:8443",
+            " alice.com ",
+        ] {
+            assert_eq!(well_known_url(website).unwrap().as_str(), expected);
+        }
+
+        for website in [
+            "",
+            "localhost",
+            "https://127.0.0.1",
+            "http://[::1]/",
+            "ftp://alice.com",
+            "alice .com",
+        ] {
+            assert!(well_known_url(website).is_none(), "{}", website);
+        }
+    }
+
+    #[test]
+    fn public_ips() {
+        for ip in ["1.1.1.1", "8.8.8.8", "2606:4700:4700::1111"] {
+            assert!(is_public_ip(&ip.parse().unwrap()), "{}", ip);
+        }
+
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(!is_public_ip(&ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn refuse_internal_domains() {
+        let res = PublicResolver
+            .resolve(Name::from_str("localhost").unwrap())
+            .await;
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn refuse_redirect_to_localhost() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        // Redirects every request to an internal service.
+        let counter = Arc::clone(&requests);
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut buf = [0; 1024];
+                let _ = stream.read(&mut buf).await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+
+                let resp = format!(
+                    "HTTP/1.1 302 Found\r\nLocation: http://127.0.0.1:{}/secret\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    port
+                );
+                stream.write_all(resp.as_bytes()).await.unwrap();
+            }
+        });
+
+        // The claimed website itself is checked by `well_known_url`, the
+        // redirect is refused by the client.
+        let client = web_client(5).unwrap();
+        let url = Url::parse(&format!("http://127.0.0.1:{}{}", port, WELL_KNOWN_PATH)).unwrap();
+        let err = fetch_challenge_file(&client, url).await.unwrap_err();
+        assert!(format!("{:?}", err).contains("refused redirect"));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn website_challenges() {
+        let mut seen = HashMap::new();
+
+        let message = website_message(&mut seen, "alice.com", "\nchallenge: abcd\r\n\n").unwrap();
+        assert_eq!(
+            message.origin,
+            ExternalMessageType::Web("alice.com".to_string())
+        );
+        assert_eq!(
+            message.values,
+            vec![MessagePart::from("challenge: abcd".to_string())]
+        );
+
+        // Unchanged files are skipped.
+        assert!(website_message(&mut seen, "alice.com", "\nchallenge: abcd\r\n\n").is_none());
+        assert!(website_message(&mut seen, "alice.com", "efgh").is_some());
+
+        assert!(website_message(&mut seen, "bob.com", " \n").is_none());
+    }
+}
@@ -0,0 +1,122 @@
+use crate::adapters::matrix::MatrixHandle;
+use crate::primitives::{ChainAddress, IdentityContext, Timestamp};
+use schemars::JsonSchema;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AddressBlocklistConfig {
+    // If set, only requests of these addresses are processed.
+    pub allowed: Option<Vec<ChainAddress>>,
+    // Requests of these addresses are never processed, e.g. sanctioned
+    // accounts. Admins can block further addresses with `block <ADDR>`.
+    pub denied: Option<Vec<ChainAddress>>,
+}
+
+/// Addresses which are refused by the config. Addresses blocked by admins are
+/// stored in the database.
+#[derive(Debug, Clone, Default)]
+pub struct AddressBlocklist {
+    allowed: Option<HashSet<ChainAddress>>,
+    denied: HashSet<ChainAddress>,
+}
+
+impl From<AddressBlocklistConfig> for AddressBlocklist {
+    fn from(config: AddressBlocklistConfig) -> Self {
+        AddressBlocklist {
+            allowed: config.allowed.map(|list| list.into_iter().collect()),
+            denied: config.denied.unwrap_or_default().into_iter().collect(),
+        }
+    }
+}
+
+impl AddressBlocklist {
+    /// Returns the reason if the address is refused by the config.
+    pub fn check(&self, address: &ChainAddress) -> Option<String> {
+        if self.denied.contains(address) {
+            return Some("The address is blocked by the registrar".to_string());
+        }
+
+        if let Some(allowed) = &self.allowed {
+            if !allowed.contains(address) {
+                return Some("The address is not served by the registrar".to_string());
+            }
+        }
+
+        None
+    }
+}
+
+/// An address blocked by an admin.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct BlockedAddress {
+    pub context: IdentityContext,
+    pub reason: String,
+    pub blocked_by: MatrixHandle,
+    pub timestamp: Timestamp,
+}
+
+/// A judgement request which was refused because the address is blocked. This
+/// is a terminal state, the identity is never challenged or judged.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct BlockedRequest {
+    pub context: IdentityContext,
+    pub reason: String,
+    pub timestamp: Timestamp,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlocklistAction {
+    Block,
+    Unblock,
+}
+
+/// Audit trail of changes to the blocklist.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct BlocklistAuditEntry {
+    pub action: BlocklistAction,
+    pub context: IdentityContext,
+    pub admin: MatrixHandle,
+    pub reason: Option<String>,
+    pub timestamp: Timestamp,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(value: &str) -> ChainAddress {
+        ChainAddress::from(value.to_string())
+    }
+
+    #[test]
+    fn default_blocklist_allows_all() {
+        let list = AddressBlocklist::default();
+        assert_eq!(list.check(&address("1a2YiGNu")), None);
+    }
+
+    #[test]
+    fn denied_and_allowed_addresses() {
+        let list = AddressBlocklist::from(AddressBlocklistConfig {
+            allowed: None,
+            denied: Some(vec![address("1b3NhsSE")]),
+        });
+
+        assert_eq!(list.check(&address("1a2YiGNu")), None);
+        assert!(list.check(&address("1b3NhsSE")).is_some());
+
+        let list = AddressBlocklist::from(AddressBlocklistConfig {
+            allowed: Some(vec![address("1a2YiGNu"), address("1b3NhsSE")]),
+            denied: Some(vec![address("1b3NhsSE")]),
+        });
+
+        assert_eq!(list.check(&address("1a2YiGNu")), None);
+        assert!(list.check(&address("FHxoGrCJ")).is_some());
+        // The deny list takes precedence.
+        assert!(list.check(&address("1b3NhsSE")).is_some());
+    }
+}
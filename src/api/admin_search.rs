@@ -0,0 +1,100 @@
+use super::JsonResult;
+use crate::adapters::admin::{MIN_SEARCH_TERM_LENGTH, SEARCH_LIMIT};
+use crate::database::Database;
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use schemars::JsonSchema;
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AdminSearchConfig {
+    // Bearer token required for admin searches.
+    pub token: String,
+}
+
+/// Search of identities for support staff, the REST counterpart of the
+/// `search` admin command.
+#[derive(Debug, Clone)]
+pub struct AdminSearch {
+    db: Database,
+    config: AdminSearchConfig,
+}
+
+impl AdminSearch {
+    pub fn new(db: Database, config: AdminSearchConfig) -> Self {
+        AdminSearch { db, config }
+    }
+    fn is_authorized(&self, req: &HttpRequest) -> bool {
+        let provided = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+
+        self.verify_token(provided)
+    }
+    /// Whether the admin token is valid, e.g. presented in a WS subscription.
+    pub fn verify_token(&self, provided: &str) -> bool {
+        constant_time_eq(provided.as_bytes(), self.config.token.as_bytes())
+    }
+}
+
+pub(super) fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
+    left.len() == right.len()
+        && left
+            .iter()
+            .zip(right.iter())
+            .fold(0, |acc, (l, r)| acc | (l ^ r))
+            == 0
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchQuery {
+    term: String,
+}
+
+pub async fn admin_search(
+    req: HttpRequest,
+    search: web::Data<Option<AdminSearch>>,
+    query: web::Query<SearchQuery>,
+) -> HttpResponse {
+    let search = match search.as_ref() {
+        Some(search) => search,
+        None => return HttpResponse::NotFound().finish(),
+    };
+
+    if !search.is_authorized(&req) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let term = query.term.trim();
+    if term.chars().count() < MIN_SEARCH_TERM_LENGTH {
+        return HttpResponse::BadRequest().json(JsonResult::<()>::Err(format!(
+            "The search term must be at least {} characters long",
+            MIN_SEARCH_TERM_LENGTH
+        )));
+    }
+
+    match search.db.search_identities(term, SEARCH_LIMIT).await {
+        Ok(results) => HttpResponse::Ok().json(JsonResult::Ok(results)),
+        Err(err) => {
+            error!("Failed to search identities: {:?}", err);
+            HttpResponse::InternalServerError().json(JsonResult::<()>::Err(
+                "Backend error, contact admin".to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_tokens() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+        assert!(!constant_time_eq(b"", b"secret"));
+    }
+}
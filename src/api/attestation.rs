@@ -0,0 +1,82 @@
+use super::JsonResult;
+use crate::adapters::admin::RawFieldName;
+use crate::database::Database;
+use crate::primitives::IdentityContext;
+use actix_web::{web, HttpResponse};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttestationOutcome {
+    // The message contains the pending challenge of the field.
+    Matches,
+    DoesNotMatch,
+    // The message contains the challenge, but the contact value is shared with
+    // other identities and the disambiguation token is missing.
+    DisambiguationRequired,
+    AlreadyVerified,
+    // Short codes are only checked when received, since every attempt counts.
+    Unsupported,
+    NotFound,
+}
+
+/// Lets third parties check whether a message would verify a field before
+/// relaying it, without modifying any state.
+#[derive(Debug, Clone)]
+pub struct Attestor {
+    db: Database,
+}
+
+impl Attestor {
+    pub fn new(db: Database) -> Self {
+        Attestor { db }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AttestMessage {
+    pub context: IdentityContext,
+    pub field: String,
+    pub message: String,
+}
+
+pub async fn attest_message(
+    req: web::Json<AttestMessage>,
+    attestor: web::Data<Attestor>,
+) -> HttpResponse {
+    let req = req.into_inner();
+
+    // Only those fields are verified with a message.
+    let field = match RawFieldName::from_str(&req.field) {
+        Ok(field @ (RawFieldName::Email | RawFieldName::Twitter | RawFieldName::Matrix)) => field,
+        _ => {
+            return HttpResponse::Ok().json(JsonResult::<AttestationOutcome>::Err(format!(
+                "Messages of field '{}' cannot be attested",
+                req.field
+            )))
+        }
+    };
+
+    let result = match attestor
+        .db
+        .attest_message(&req.context, &field, &req.message)
+        .await
+    {
+        Ok(AttestationOutcome::Unsupported) => {
+            JsonResult::Err("Short codes cannot be attested".to_string())
+        }
+        Ok(AttestationOutcome::NotFound) => {
+            JsonResult::Err("No pending judgement request with this field found".to_string())
+        }
+        Ok(outcome) => JsonResult::Ok(outcome),
+        Err(err) => {
+            error!(
+                "Failed to attest message of {:?} for {:?}: {:?}",
+                field, req.context, err
+            );
+            JsonResult::Err("Backend error, contact admin".to_string())
+        }
+    };
+
+    HttpResponse::Ok().json(result)
+}
@@ -0,0 +1,107 @@
+use super::JsonResult;
+use crate::adapters::admin::RawFieldName;
+use crate::database::Database;
+use crate::primitives::IdentityContext;
+use actix_web::{web, HttpResponse};
+use schemars::JsonSchema;
+use std::str::FromStr;
+
+// In seconds
+const DEFAULT_TTL: u64 = 7 * 86_400;
+
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ChallengeExpiryConfig {
+    // Seconds within which a challenge must be verified, defaults to seven
+    // days.
+    pub ttl: Option<u64>,
+}
+
+impl ChallengeExpiryConfig {
+    pub fn ttl(&self) -> u64 {
+        self.ttl.unwrap_or(DEFAULT_TTL)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenewalOutcome {
+    Renewed,
+    // The challenge is still valid or already verified.
+    NotExpired,
+    NotFound,
+}
+
+/// Replaces expired challenges on request of the user. Unlike regenerations,
+/// no proof of the account ownership is required since only challenges which
+/// can no longer be verified are replaced.
+#[derive(Debug, Clone)]
+pub struct ChallengeRenewal {
+    db: Database,
+    ttl: u64,
+}
+
+impl ChallengeRenewal {
+    pub fn new(db: Database, config: ChallengeExpiryConfig) -> Self {
+        ChallengeRenewal {
+            db,
+            ttl: config.ttl(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RequestNewChallenge {
+    pub context: IdentityContext,
+    pub field: String,
+}
+
+pub async fn request_new_challenge(
+    req: web::Json<RequestNewChallenge>,
+    renewal: web::Data<Option<ChallengeRenewal>>,
+) -> HttpResponse {
+    let renewal = match renewal.as_ref() {
+        Some(renewal) => renewal,
+        None => {
+            return HttpResponse::Ok().json(JsonResult::<RenewalOutcome>::Err(
+                "Challenge expiry is not enabled".to_string(),
+            ))
+        }
+    };
+
+    let req = req.into_inner();
+
+    // Only those fields are verified with a challenge.
+    let field = match RawFieldName::from_str(&req.field) {
+        Ok(field @ (RawFieldName::Email | RawFieldName::Twitter | RawFieldName::Matrix)) => field,
+        _ => {
+            return HttpResponse::Ok().json(JsonResult::<RenewalOutcome>::Err(format!(
+                "The challenge of field '{}' cannot be renewed",
+                req.field
+            )))
+        }
+    };
+
+    let result = match renewal
+        .db
+        .renew_expired_challenge(&req.context, &field, renewal.ttl)
+        .await
+    {
+        Ok(RenewalOutcome::Renewed) => JsonResult::Ok(RenewalOutcome::Renewed),
+        Ok(RenewalOutcome::NotExpired) => {
+            JsonResult::Err("The challenge has not expired".to_string())
+        }
+        Ok(RenewalOutcome::NotFound) => {
+            JsonResult::Err("No pending judgement request with this field found".to_string())
+        }
+        Err(err) => {
+            error!(
+                "Failed to renew challenge of {:?} for {:?}: {:?}",
+                field, req.context, err
+            );
+            JsonResult::Err("Backend error, contact admin".to_string())
+        }
+    };
+
+    HttpResponse::Ok().json(result)
+}
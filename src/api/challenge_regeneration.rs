@@ -0,0 +1,131 @@
+use super::challenge_token::ChallengeTokens;
+use super::JsonResult;
+use crate::adapters::admin::RawFieldName;
+use crate::database::Database;
+use crate::primitives::{IdentityContext, Timestamp};
+use actix_web::{web, HttpResponse};
+use schemars::JsonSchema;
+use std::str::FromStr;
+
+const DEFAULT_LIMIT: usize = 3;
+// In seconds
+const DEFAULT_WINDOW: u64 = 86_400;
+
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ChallengeRegenerationConfig {
+    // Maximum number of regenerations per field within the window.
+    pub limit: Option<usize>,
+    // Length of the window in seconds.
+    pub window: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegenerationOutcome {
+    // A new challenge was generated, the previous one is no longer valid.
+    Regenerated,
+    // The first challenge is verified, a new second challenge was sent.
+    SecondChallengeResent,
+    RateLimited,
+    AlreadyVerified,
+    NotFound,
+}
+
+/// Lets users replace the challenge of a field (e.g. if the email containing
+/// the second challenge was deleted), after proving the ownership of the
+/// account with a challenge token.
+#[derive(Debug, Clone)]
+pub struct ChallengeRegenerator {
+    db: Database,
+    tokens: ChallengeTokens,
+    limit: usize,
+    window: u64,
+}
+
+impl ChallengeRegenerator {
+    pub fn new(db: Database, tokens: ChallengeTokens, config: ChallengeRegenerationConfig) -> Self {
+        ChallengeRegenerator {
+            db,
+            tokens,
+            limit: config.limit.unwrap_or(DEFAULT_LIMIT),
+            window: config.window.unwrap_or(DEFAULT_WINDOW),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RegenerateChallenge {
+    pub context: IdentityContext,
+    // Token as returned by `/api/challenge_token`.
+    pub token: String,
+    pub field: String,
+}
+
+pub async fn regenerate_challenge(
+    req: web::Json<RegenerateChallenge>,
+    regenerator: web::Data<Option<ChallengeRegenerator>>,
+) -> HttpResponse {
+    let regenerator = match regenerator.as_ref() {
+        Some(regenerator) => regenerator,
+        None => {
+            return HttpResponse::Ok().json(JsonResult::<RegenerationOutcome>::Err(
+                "Challenge regeneration is not enabled".to_string(),
+            ))
+        }
+    };
+
+    let req = req.into_inner();
+    let now = Timestamp::now().raw();
+
+    if regenerator
+        .tokens
+        .verify(&req.token, &req.context, now)
+        .is_none()
+    {
+        return HttpResponse::Ok().json(JsonResult::<RegenerationOutcome>::Err(
+            "Invalid or expired challenge token".to_string(),
+        ));
+    }
+
+    // Only those fields are verified with a challenge.
+    let field = match RawFieldName::from_str(&req.field) {
+        Ok(field @ (RawFieldName::Email | RawFieldName::Twitter | RawFieldName::Matrix)) => field,
+        _ => {
+            return HttpResponse::Ok().json(JsonResult::<RegenerationOutcome>::Err(format!(
+                "The challenge of field '{}' cannot be regenerated",
+                req.field
+            )))
+        }
+    };
+
+    let since = Timestamp::from(now.saturating_sub(regenerator.window));
+    let result = match regenerator
+        .db
+        .regenerate_challenge(&req.context, &field, regenerator.limit, since)
+        .await
+    {
+        Ok(
+            outcome @ (RegenerationOutcome::Regenerated
+            | RegenerationOutcome::SecondChallengeResent),
+        ) => JsonResult::Ok(outcome),
+        Ok(RegenerationOutcome::RateLimited) => JsonResult::Err(
+            "The challenge was regenerated too often, please try again later".to_string(),
+        ),
+        Ok(RegenerationOutcome::AlreadyVerified) => {
+            JsonResult::Err("The field is already verified".to_string())
+        }
+        Ok(RegenerationOutcome::NotFound) => {
+            JsonResult::Err("No pending judgement request with this field found".to_string())
+        }
+        Err(err) => {
+            error!(
+                "Failed to regenerate challenge of {:?} for {:?}: {:?}",
+                field, req.context, err
+            );
+            JsonResult::Err("Backend error, contact admin".to_string())
+        }
+    };
+
+    HttpResponse::Ok().json(result)
+}
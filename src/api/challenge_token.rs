@@ -0,0 +1,276 @@
+use super::JsonResult;
+use crate::primitives::{IdentityContext, Timestamp};
+use crate::Result;
+use actix_web::{web, HttpResponse};
+use hmac::{Hmac, Mac};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use schemars::JsonSchema;
+use sha2::Sha256;
+use std::time::Duration;
+
+// In seconds
+const DEFAULT_TTL: u64 = 300;
+const DEFAULT_TIMEOUT: u64 = 10;
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ChallengeTokenConfig {
+    // Secret used to sign the tokens.
+    pub secret: String,
+    // Validity of tokens and ownership proofs in seconds.
+    pub ttl: Option<u64>,
+    // Endpoint which verifies the signature of an ownership proof.
+    pub verifier_endpoint: String,
+}
+
+/// Proves the ownership of an account by signing the message returned by
+/// `ownership_message` with the account key.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct OwnershipProof {
+    pub context: IdentityContext,
+    pub timestamp: u64,
+    pub signature: String,
+}
+
+/// The message which must be signed in order to receive a challenge token.
+pub fn ownership_message(context: &IdentityContext, timestamp: u64) -> String {
+    format!(
+        "registrar-challenge-token:{}:{}:{}",
+        context.chain.as_str(),
+        context.address.as_str(),
+        timestamp
+    )
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+struct VerifierRequest {
+    address: String,
+    message: String,
+    signature: String,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+struct VerifierResponse {
+    valid: bool,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ChallengeToken {
+    pub token: String,
+    pub expires: u64,
+}
+
+/// Issues and verifies the tokens which allow WS sessions to receive the
+/// expected challenge values of an identity.
+#[derive(Debug, Clone)]
+pub struct ChallengeTokens {
+    client: Client,
+    config: ChallengeTokenConfig,
+}
+
+impl ChallengeTokens {
+    pub fn new(config: ChallengeTokenConfig) -> Result<Self> {
+        if config.secret.is_empty() {
+            return Err(anyhow!("the challenge token secret must not be empty"));
+        }
+
+        let client = Client::builder()
+            .timeout(Duration::from_secs(DEFAULT_TIMEOUT))
+            .build()?;
+
+        Ok(ChallengeTokens { client, config })
+    }
+    fn ttl(&self) -> u64 {
+        self.config.ttl.unwrap_or(DEFAULT_TTL)
+    }
+    fn mac(&self, context: &IdentityContext, expires: u64) -> Hmac<Sha256> {
+        let mut mac: Hmac<Sha256> = Hmac::new_from_slice(self.config.secret.as_bytes())
+            .expect("HMAC accepts keys of any size");
+        mac.update(context.chain.as_str().as_bytes());
+        mac.update(b":");
+        mac.update(context.address.as_str().as_bytes());
+        mac.update(b":");
+        mac.update(expires.to_string().as_bytes());
+        mac
+    }
+    pub fn issue(&self, context: &IdentityContext, now: u64) -> ChallengeToken {
+        let expires = now + self.ttl();
+        let signature = hex::encode(self.mac(context, expires).finalize().into_bytes());
+
+        ChallengeToken {
+            token: format!("{}.{}", expires, signature),
+            expires,
+        }
+    }
+    /// Returns the expiry of the token if it was issued for the given context
+    /// and is still valid.
+    pub fn verify(&self, token: &str, context: &IdentityContext, now: u64) -> Option<u64> {
+        let (expires, signature) = token.split_once('.')?;
+        let expires = expires.parse::<u64>().ok()?;
+        if expires <= now {
+            return None;
+        }
+
+        let signature = hex::decode(signature).ok()?;
+        self.mac(context, expires)
+            .verify_slice(&signature)
+            .ok()
+            .map(|_| expires)
+    }
+    /// Whether a signed timestamp is recent enough to be accepted.
+    pub fn is_fresh(&self, timestamp: u64, now: u64) -> bool {
+        timestamp.abs_diff(now) <= self.ttl()
+    }
+    /// Checks the freshness of the proof and lets the configured endpoint
+    /// verify the signature.
+    pub async fn verify_ownership(&self, proof: &OwnershipProof, now: u64) -> Result<bool> {
+        if !self.is_fresh(proof.timestamp, now) {
+            return Ok(false);
+        }
+
+        self.verify_signature(
+            &proof.context,
+            ownership_message(&proof.context, proof.timestamp),
+            &proof.signature,
+        )
+        .await
+    }
+    /// Lets the configured endpoint verify that the message was signed by the
+    /// account key.
+    pub async fn verify_signature(
+        &self,
+        context: &IdentityContext,
+        message: String,
+        signature: &str,
+    ) -> Result<bool> {
+        let request = VerifierRequest {
+            address: context.address.as_str().to_string(),
+            message,
+            signature: signature.to_string(),
+        };
+
+        let resp = self
+            .client
+            .post(&self.config.verifier_endpoint)
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&request)?)
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            return Err(anyhow!(
+                "signature verifier returned status code {}",
+                resp.status()
+            ));
+        }
+
+        let body = resp.bytes().await?;
+        Ok(serde_json::from_slice::<VerifierResponse>(&body)?.valid)
+    }
+}
+
+pub async fn request_challenge_token(
+    req: web::Json<OwnershipProof>,
+    tokens: web::Data<Option<ChallengeTokens>>,
+) -> HttpResponse {
+    let tokens = match tokens.as_ref() {
+        Some(tokens) => tokens,
+        None => {
+            return HttpResponse::Ok().json(JsonResult::<ChallengeToken>::Err(
+                "Challenge tokens are not enabled".to_string(),
+            ))
+        }
+    };
+
+    let proof = req.into_inner();
+    let now = Timestamp::now().raw();
+
+    let result = match tokens.verify_ownership(&proof, now).await {
+        Ok(true) => JsonResult::Ok(tokens.issue(&proof.context, now)),
+        Ok(false) => JsonResult::Err("Invalid or expired ownership proof".to_string()),
+        Err(err) => {
+            error!(
+                "Failed to verify ownership proof of {:?}: {:?}",
+                proof.context, err
+            );
+            JsonResult::Err("Backend error, contact admin".to_string())
+        }
+    };
+
+    HttpResponse::Ok().json(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::ChainName;
+
+    fn tokens(secret: &str) -> ChallengeTokens {
+        ChallengeTokens::new(ChallengeTokenConfig {
+            secret: secret.to_string(),
+            ttl: Some(60),
+            verifier_endpoint: "http://localhost".to_string(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn issue_and_verify_token() {
+        let tokens = tokens("secret");
+        let alice = IdentityContext::alice();
+        let bob = IdentityContext::bob();
+
+        let token = tokens.issue(&alice, 1_000);
+        assert_eq!(token.expires, 1_060);
+        assert_eq!(tokens.verify(&token.token, &alice, 1_000), Some(1_060));
+        assert_eq!(tokens.verify(&token.token, &alice, 1_059), Some(1_060));
+
+        // Expired.
+        assert!(tokens.verify(&token.token, &alice, 1_060).is_none());
+        // Issued for another account or chain.
+        assert!(tokens.verify(&token.token, &bob, 1_000).is_none());
+        let mut kusama = alice.clone();
+        kusama.chain = ChainName::Kusama;
+        assert!(tokens.verify(&token.token, &kusama, 1_000).is_none());
+        // Signed with another secret.
+        assert!(self::tokens("other")
+            .verify(&token.token, &alice, 1_000)
+            .is_none());
+    }
+
+    #[test]
+    fn reject_tampered_tokens() {
+        let tokens = tokens("secret");
+        let alice = IdentityContext::alice();
+
+        let token = tokens.issue(&alice, 1_000).token;
+        let (_, signature) = token.split_once('.').unwrap();
+
+        // Extended expiry.
+        let extended = format!("{}.{}", 5_000, signature);
+        assert!(tokens.verify(&extended, &alice, 1_000).is_none());
+
+        for invalid in &["", "1060", "1060.", "abc.def", ".", "1060.zz"] {
+            assert!(tokens.verify(invalid, &alice, 1_000).is_none());
+        }
+    }
+
+    #[test]
+    fn reject_empty_secret() {
+        assert!(ChallengeTokens::new(ChallengeTokenConfig {
+            secret: String::new(),
+            ttl: None,
+            verifier_endpoint: "http://localhost".to_string(),
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn ownership_message_format() {
+        assert_eq!(
+            ownership_message(&IdentityContext::alice(), 1_000),
+            "registrar-challenge-token:polkadot:1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP:1000"
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use super::JsonResult;
+use crate::database::Database;
+use crate::primitives::{ChainName, IdentityContext, JudgementState, Timestamp};
+use crate::verification::JUDGEMENT_DELAY_RANGE;
+use crate::Result;
+use actix_web::{web, HttpResponse};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+// Seconds after which the verification statistics of a chain are refreshed.
+const STATS_REFRESH_INTERVAL: u64 = 600;
+// Only identities verified within this time are considered.
+const STATS_WINDOW: u64 = 30 * 86_400;
+const MAX_SAMPLES: i64 = 500;
+// Fewer recent verifications do not allow a meaningful estimate.
+const MIN_SAMPLES: usize = 5;
+
+// Statistics per chain and the time at which they were fetched.
+type CachedStats = HashMap<ChainName, (Timestamp, Option<VerificationStats>)>;
+
+/// Statistics of recently verified identities of a chain.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct VerificationStats {
+    // Median time in seconds from the judgement request to full verification.
+    pub median_duration: u64,
+    pub samples: usize,
+}
+
+impl VerificationStats {
+    pub fn from_durations(mut durations: Vec<u64>) -> Option<Self> {
+        if durations.len() < MIN_SAMPLES {
+            return None;
+        }
+
+        durations.sort_unstable();
+        Some(VerificationStats {
+            median_duration: durations[durations.len() / 2],
+            samples: durations.len(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct CompletionEstimate {
+    pub estimated_completion: Timestamp,
+    // Number of recent verifications the estimate is based on, zero if the
+    // identity is already verified.
+    pub samples: usize,
+}
+
+/// Why no estimate can be given for the identity, independent of the
+/// statistics.
+fn unavailable_reason(state: &JudgementState) -> Option<&'static str> {
+    if state.judgement_submitted {
+        Some("The judgement was already issued")
+    } else if state.requires_manual_review {
+        Some("The identity is reviewed manually, no estimate available")
+    } else if state.judgement_withheld.is_some() {
+        Some("Judgements are currently paused, no estimate available")
+    } else {
+        None
+    }
+}
+
+/// Estimates when the judgement of a pending identity is issued. Returns
+/// `None` if the identity is already judged, cannot be judged automatically or
+/// if there are not enough statistics for unverified identities.
+pub fn estimate_completion(
+    state: &JudgementState,
+    stats: Option<VerificationStats>,
+    now: Timestamp,
+) -> Option<CompletionEstimate> {
+    if unavailable_reason(state).is_some() {
+        return None;
+    }
+
+    let mean_delay = (JUDGEMENT_DELAY_RANGE.start + JUDGEMENT_DELAY_RANGE.end) / 2;
+
+    if state.is_fully_verified {
+        let issue_at = state.issue_judgement_at.unwrap_or_else(|| {
+            Timestamp::from(
+                state
+                    .completion_timestamp
+                    .unwrap_or(now)
+                    .raw()
+                    .saturating_add(mean_delay),
+            )
+        });
+
+        return Some(CompletionEstimate {
+            estimated_completion: issue_at.max(now),
+            samples: 0,
+        });
+    }
+
+    // Identities taking longer than usual are expected to be verified any
+    // moment.
+    let stats = stats?;
+    let verified_at = Timestamp::from(
+        state
+            .inserted_timestamp
+            .raw()
+            .saturating_add(stats.median_duration),
+    )
+    .max(now);
+
+    Some(CompletionEstimate {
+        estimated_completion: Timestamp::from(verified_at.raw().saturating_add(mean_delay)),
+        samples: stats.samples,
+    })
+}
+
+/// Provides estimates of the judgement time to front-ends, based on cached
+/// statistics of recently verified identities.
+#[derive(Debug, Clone)]
+pub struct CompletionEstimator {
+    db: Database,
+    stats: Arc<RwLock<CachedStats>>,
+}
+
+impl CompletionEstimator {
+    pub fn new(db: Database) -> Self {
+        CompletionEstimator {
+            db,
+            stats: Default::default(),
+        }
+    }
+    async fn stats(&self, chain: ChainName) -> Result<Option<VerificationStats>> {
+        let now = Timestamp::now();
+        if let Some((fetched, stats)) = self.stats.read().await.get(&chain) {
+            if now.raw().saturating_sub(fetched.raw()) < STATS_REFRESH_INTERVAL {
+                return Ok(*stats);
+            }
+        }
+
+        let since = Timestamp::from(now.raw().saturating_sub(STATS_WINDOW));
+        let durations = self
+            .db
+            .fetch_verification_durations(chain, since, MAX_SAMPLES)
+            .await?;
+        let stats = VerificationStats::from_durations(durations);
+
+        self.stats.write().await.insert(chain, (now, stats));
+        Ok(stats)
+    }
+    /// Estimates the judgement time of the identity, e.g. for the account
+    /// status. Statistics are only fetched for unverified identities.
+    pub async fn estimate_state(
+        &self,
+        state: &JudgementState,
+    ) -> Result<Option<CompletionEstimate>> {
+        if unavailable_reason(state).is_some() {
+            return Ok(None);
+        }
+
+        let stats = if state.is_fully_verified {
+            None
+        } else {
+            self.stats(state.context.chain).await?
+        };
+
+        Ok(estimate_completion(state, stats, Timestamp::now()))
+    }
+    async fn estimate(&self, context: &IdentityContext) -> Result<JsonResult<CompletionEstimate>> {
+        let state = match self.db.fetch_judgement_state(context).await? {
+            Some(state) => state,
+            None => {
+                return Ok(JsonResult::Err(
+                    "No judgement request for this identity found".to_string(),
+                ))
+            }
+        };
+
+        if let Some(reason) = unavailable_reason(&state) {
+            return Ok(JsonResult::Err(reason.to_string()));
+        }
+
+        Ok(match self.estimate_state(&state).await? {
+            Some(estimate) => JsonResult::Ok(estimate),
+            None => JsonResult::Err("No estimate available".to_string()),
+        })
+    }
+}
+
+pub async fn estimated_completion(
+    context: web::Json<IdentityContext>,
+    estimator: web::Data<CompletionEstimator>,
+) -> HttpResponse {
+    let context = context.into_inner();
+
+    let result = match estimator.estimate(&context).await {
+        Ok(result) => result,
+        Err(err) => {
+            error!(
+                "Failed to estimate completion time of {:?}: {:?}",
+                context, err
+            );
+            JsonResult::Err("Backend error, contact admin".to_string())
+        }
+    };
+
+    HttpResponse::Ok().json(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::WithheldReason;
+
+    #[test]
+    fn verification_stats() {
+        assert!(VerificationStats::from_durations(vec![10, 20, 30, 40]).is_none());
+
+        let stats = VerificationStats::from_durations(vec![500, 10, 40, 20, 30]).unwrap();
+        assert_eq!(stats.median_duration, 30);
+        assert_eq!(stats.samples, 5);
+    }
+
+    #[test]
+    fn estimate_judgement_time() {
+        let now = Timestamp::from(10_000);
+        let mean_delay = (JUDGEMENT_DELAY_RANGE.start + JUDGEMENT_DELAY_RANGE.end) / 2;
+        let stats = Some(VerificationStats {
+            median_duration: 3_600,
+            samples: 10,
+        });
+
+        let mut state = JudgementState::alice();
+        state.inserted_timestamp = Timestamp::from(9_000);
+        let estimate = estimate_completion(&state, stats, now).unwrap();
+        assert_eq!(
+            estimate.estimated_completion.raw(),
+            9_000 + 3_600 + mean_delay
+        );
+        assert_eq!(estimate.samples, 10);
+
+        // Requests taking longer than usual.
+        state.inserted_timestamp = Timestamp::from(1_000);
+        let estimate = estimate_completion(&state, stats, now).unwrap();
+        assert_eq!(estimate.estimated_completion.raw(), 10_000 + mean_delay);
+
+        assert!(estimate_completion(&state, None, now).is_none());
+
+        // Fully verified identities are judged at the scheduled time.
+        state.is_fully_verified = true;
+        state.completion_timestamp = Some(Timestamp::from(9_900));
+        state.issue_judgement_at = Some(Timestamp::from(10_100));
+        let estimate = estimate_completion(&state, None, now).unwrap();
+        assert_eq!(estimate.estimated_completion.raw(), 10_100);
+        assert_eq!(estimate.samples, 0);
+
+        state.judgement_withheld = Some(WithheldReason::SubmissionsPaused);
+        assert!(estimate_completion(&state, stats, now).is_none());
+
+        state.judgement_withheld = None;
+        state.judgement_submitted = true;
+        assert!(estimate_completion(&state, stats, now).is_none());
+    }
+}
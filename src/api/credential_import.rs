@@ -0,0 +1,266 @@
+use super::admin_search::constant_time_eq;
+use super::JsonResult;
+use crate::adapters::admin::RawFieldName;
+use crate::database::Database;
+use crate::primitives::{IdentityContext, IdentityFieldValue, Timestamp};
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use hmac::{Hmac, Mac};
+use schemars::JsonSchema;
+use sha2::Sha256;
+
+// In seconds
+const DEFAULT_MAX_AGE: u64 = 30 * 86_400;
+// Tolerated clock difference to the issuers, in seconds.
+const MAX_CLOCK_SKEW: u64 = 300;
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CredentialImportConfig {
+    // Bearer token required for imports.
+    pub token: String,
+    // Issuers whose attestations are trusted.
+    pub issuers: Vec<TrustedIssuerConfig>,
+    // Seconds after which an attestation is no longer accepted, defaults to
+    // thirty days.
+    pub max_age: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TrustedIssuerConfig {
+    pub name: String,
+    // Secret shared with the issuer, used to sign the attestations.
+    pub secret: String,
+}
+
+impl CredentialImportConfig {
+    /// Checks the signature and the age of the attestation.
+    fn verify(&self, attestation: &CredentialAttestation, now: u64) -> Result<(), String> {
+        let issuer = self
+            .issuers
+            .iter()
+            .find(|issuer| issuer.name == attestation.issuer)
+            .ok_or_else(|| format!("Unknown issuer '{}'", attestation.issuer))?;
+
+        let max_age = self.max_age.unwrap_or(DEFAULT_MAX_AGE);
+        if attestation.issued_at > now + MAX_CLOCK_SKEW
+            || now.saturating_sub(attestation.issued_at) > max_age
+        {
+            return Err("The attestation has expired".to_string());
+        }
+
+        let mut mac: Hmac<Sha256> =
+            Hmac::new_from_slice(issuer.secret.as_bytes()).expect("HMAC accepts keys of any size");
+        mac.update(attestation_message(attestation).as_bytes());
+
+        hex::decode(&attestation.signature)
+            .ok()
+            .and_then(|signature| mac.verify_slice(&signature).ok())
+            .ok_or_else(|| "Invalid signature".to_string())
+    }
+}
+
+/// A statement of a trusted issuer, e.g. another registrar or a KYC provider,
+/// that the fields of an identity were verified by them.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CredentialAttestation {
+    pub issuer: String,
+    pub context: IdentityContext,
+    pub fields: Vec<IdentityFieldValue>,
+    // Unix timestamp in seconds.
+    pub issued_at: u64,
+    // Hex encoded HMAC-SHA256 of `attestation_message`.
+    pub signature: String,
+}
+
+/// The message which is signed by the issuer, one line per attested field.
+pub fn attestation_message(attestation: &CredentialAttestation) -> String {
+    let mut message = format!(
+        "registrar-credential:{}:{}:{}:{}",
+        attestation.issuer,
+        attestation.context.chain.as_str(),
+        attestation.context.address.as_str(),
+        attestation.issued_at
+    );
+
+    for field in &attestation.fields {
+        let (_, value) = field.as_account_type();
+        message.push('\n');
+        message.push_str(field.type_name());
+        message.push(':');
+        message.push_str(&value);
+    }
+
+    message
+}
+
+/// Returns the field name if the field can be verified by an attestation.
+/// Display names are always checked against the other identities.
+pub fn importable_field(value: &IdentityFieldValue) -> Option<RawFieldName> {
+    match value {
+        IdentityFieldValue::LegalName(_) => Some(RawFieldName::LegalName),
+        IdentityFieldValue::Email(_) => Some(RawFieldName::Email),
+        IdentityFieldValue::Web(_) => Some(RawFieldName::Web),
+        IdentityFieldValue::Twitter(_) => Some(RawFieldName::Twitter),
+        IdentityFieldValue::Matrix(_) => Some(RawFieldName::Matrix),
+        _ => None,
+    }
+}
+
+/// Pre-verifies fields of identities based on attestations of trusted
+/// issuers, so users migrating between registrars are not challenged again.
+#[derive(Debug, Clone)]
+pub struct CredentialImport {
+    db: Database,
+    config: CredentialImportConfig,
+}
+
+impl CredentialImport {
+    pub fn new(db: Database, config: CredentialImportConfig) -> Self {
+        CredentialImport { db, config }
+    }
+    fn is_authorized(&self, req: &HttpRequest) -> bool {
+        let provided = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+
+        constant_time_eq(provided.as_bytes(), self.config.token.as_bytes())
+    }
+}
+
+pub async fn import_credentials(
+    req: HttpRequest,
+    attestation: web::Json<CredentialAttestation>,
+    import: web::Data<Option<CredentialImport>>,
+) -> HttpResponse {
+    let import = match import.as_ref() {
+        Some(import) => import,
+        None => return HttpResponse::NotFound().finish(),
+    };
+
+    if !import.is_authorized(&req) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let attestation = attestation.into_inner();
+
+    if attestation.fields.is_empty() {
+        return HttpResponse::BadRequest().json(JsonResult::<()>::Err(
+            "The attestation contains no fields".to_string(),
+        ));
+    }
+
+    if let Some(field) = attestation
+        .fields
+        .iter()
+        .find(|field| importable_field(field).is_none())
+    {
+        return HttpResponse::BadRequest().json(JsonResult::<()>::Err(format!(
+            "Field '{}' cannot be imported",
+            field.type_name()
+        )));
+    }
+
+    if let Err(reason) = import.config.verify(&attestation, Timestamp::now().raw()) {
+        warn!(
+            "Rejected attestation of {:?} by '{}': {}",
+            attestation.context, attestation.issuer, reason
+        );
+        return HttpResponse::BadRequest().json(JsonResult::<()>::Err(reason));
+    }
+
+    match import.db.import_credentials(&attestation).await {
+        Ok(Some(imported)) => {
+            info!(
+                "Imported {} field(s) of {:?} attested by '{}'",
+                imported.len(),
+                attestation.context,
+                attestation.issuer
+            );
+            HttpResponse::Ok().json(JsonResult::Ok(imported))
+        }
+        Ok(None) => HttpResponse::Ok().json(JsonResult::<Vec<IdentityFieldValue>>::Err(
+            "No pending judgement request of this identity found".to_string(),
+        )),
+        Err(err) => {
+            error!(
+                "Failed to import credentials of {:?}: {:?}",
+                attestation.context, err
+            );
+            HttpResponse::InternalServerError().json(JsonResult::<()>::Err(
+                "Backend error, contact admin".to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sign(attestation: &mut CredentialAttestation, secret: &str) {
+        let mut mac: Hmac<Sha256> = Hmac::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(attestation_message(attestation).as_bytes());
+        attestation.signature = hex::encode(mac.finalize().into_bytes());
+    }
+
+    #[test]
+    fn verify_attestation() {
+        let config = CredentialImportConfig {
+            token: "admin".to_string(),
+            issuers: vec![TrustedIssuerConfig {
+                name: "kyc".to_string(),
+                secret: "secret".to_string(),
+            }],
+            max_age: Some(3_600),
+        };
+
+        let mut attestation = CredentialAttestation {
+            issuer: "kyc".to_string(),
+            context: IdentityContext::alice(),
+            fields: vec![
+                IdentityFieldValue::Email("alice@example.com".to_string()),
+                IdentityFieldValue::Twitter("@alice".to_string()),
+            ],
+            issued_at: 10_000,
+            signature: String::new(),
+        };
+        assert_eq!(
+            attestation_message(&attestation),
+            "registrar-credential:kyc:polkadot:1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP:10000\n\
+             email:alice@example.com\n\
+             twitter:@alice"
+        );
+
+        sign(&mut attestation, "secret");
+        assert!(config.verify(&attestation, 10_000).is_ok());
+        assert!(config.verify(&attestation, 13_600).is_ok());
+        assert!(config.verify(&attestation, 13_601).is_err());
+        assert!(config.verify(&attestation, 9_000).is_err());
+
+        // Modified attestations.
+        let mut modified = attestation.clone();
+        modified.fields.pop();
+        assert!(config.verify(&modified, 10_000).is_err());
+
+        sign(&mut attestation, "other");
+        assert!(config.verify(&attestation, 10_000).is_err());
+
+        attestation.issuer = "unknown".to_string();
+        sign(&mut attestation, "secret");
+        assert!(config.verify(&attestation, 10_000).is_err());
+    }
+
+    #[test]
+    fn importable_fields() {
+        assert_eq!(
+            importable_field(&IdentityFieldValue::Matrix("@alice:matrix.org".to_string())),
+            Some(RawFieldName::Matrix)
+        );
+        assert!(importable_field(&IdentityFieldValue::DisplayName("Alice".to_string())).is_none());
+        assert!(importable_field(&IdentityFieldValue::Image(())).is_none());
+    }
+}
@@ -40,18 +40,9 @@ impl Handler<CheckDisplayName> for DisplayNameChecker {
         Box::pin(
             async move {
                 trace!("Received a similarities check: {:?}", msg);
-                verifier
-                    .check_similarities(msg.check.as_str(), msg.chain, None)
+                check(&verifier, &msg)
                     .await
-                    .map(|violations| {
-                        let outcome = if violations.is_empty() {
-                            Outcome::Ok
-                        } else {
-                            Outcome::Violations(violations)
-                        };
-
-                        JsonResult::Ok(outcome)
-                    })
+                    .map(JsonResult::Ok)
                     .map_err(|err| {
                         error!("Failed to check for display name similarities: {:?}", err)
                     })
@@ -62,11 +53,30 @@ impl Handler<CheckDisplayName> for DisplayNameChecker {
     }
 }
 
+/// Reserved names are rejected regardless of the similarity.
+async fn check(verifier: &DisplayNameVerifier, msg: &CheckDisplayName) -> crate::Result<Outcome> {
+    if let Some(reserved) = verifier.check_reserved(msg.check.as_str()).await? {
+        return Ok(Outcome::Reserved(reserved));
+    }
+
+    let violations = verifier
+        .check_similarities(msg.check.as_str(), msg.chain, None)
+        .await?;
+
+    if violations.is_empty() {
+        Ok(Outcome::Ok)
+    } else {
+        Ok(Outcome::Violations(violations))
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "type", content = "value")]
 pub enum Outcome {
     Ok,
     Violations(Vec<DisplayNameEntry>),
+    // The name or pattern reserved by the registrar.
+    Reserved(String),
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Message)]
@@ -0,0 +1,203 @@
+use super::{JsonResult, ServerShutdown};
+use crate::connector::DisplayNameEntry;
+use crate::database::Database;
+use crate::primitives::ChainName;
+use actix::prelude::*;
+use actix_broker::BrokerSubscribe;
+use actix_web_actors::ws;
+use futures::StreamExt;
+use serde::Serialize;
+use std::collections::HashMap;
+use tokio::time::{sleep, Duration};
+
+// In seconds
+const WATCH_RESTART_DELAY: u64 = 5;
+
+type Subscriber = Recipient<JsonResult<DisplayNameChange>>;
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Message)]
+#[serde(rename_all = "snake_case", tag = "type", content = "value")]
+#[rtype(result = "()")]
+pub enum DisplayNameChange {
+    Added(DisplayNameEntry),
+    Removed(DisplayNameEntry),
+}
+
+impl DisplayNameChange {
+    pub fn chain(&self) -> ChainName {
+        match self {
+            DisplayNameChange::Added(entry) | DisplayNameChange::Removed(entry) => {
+                entry.context.chain
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Message)]
+#[rtype(result = "()")]
+pub struct SubscribeDisplayNames {
+    pub subscriber: Subscriber,
+    pub chain: ChainName,
+}
+
+/// Forwards changes of the display name pool to the subscribers of the
+/// corresponding chain, based on MongoDB change streams.
+pub struct DisplayNamePool {
+    db: Database,
+    sessions: HashMap<ChainName, Vec<Subscriber>>,
+}
+
+impl Default for DisplayNamePool {
+    fn default() -> Self {
+        panic!("DisplayNamePool is not initialized");
+    }
+}
+
+impl DisplayNamePool {
+    pub fn new(db: Database) -> Self {
+        DisplayNamePool {
+            db,
+            sessions: Default::default(),
+        }
+    }
+}
+
+impl SystemService for DisplayNamePool {}
+impl Supervised for DisplayNamePool {}
+
+impl Actor for DisplayNamePool {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let db = self.db.clone();
+        let addr = ctx.address();
+
+        actix::spawn(async move {
+            loop {
+                match db.watch_display_names().await {
+                    Ok(mut stream) => {
+                        while let Some(change) = stream.next().await {
+                            match change {
+                                Ok(change) => addr.do_send(change),
+                                Err(err) => {
+                                    error!("Error in display name change stream: {:?}", err);
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        error!("Failed to watch display name changes: {:?}", err);
+                    }
+                }
+
+                sleep(Duration::from_secs(WATCH_RESTART_DELAY)).await;
+            }
+        });
+    }
+}
+
+impl Handler<SubscribeDisplayNames> for DisplayNamePool {
+    type Result = ();
+
+    fn handle(&mut self, msg: SubscribeDisplayNames, _ctx: &mut Self::Context) -> Self::Result {
+        self.sessions
+            .entry(msg.chain)
+            .or_default()
+            .push(msg.subscriber);
+    }
+}
+
+impl Handler<DisplayNameChange> for DisplayNamePool {
+    type Result = ();
+
+    fn handle(&mut self, msg: DisplayNameChange, _ctx: &mut Self::Context) -> Self::Result {
+        // Notify each subscriber, dropping those whose session is closed.
+        if let Some(subscribers) = self.sessions.get_mut(&msg.chain()) {
+            subscribers
+                .retain(|subscriber| subscriber.try_send(JsonResult::Ok(msg.clone())).is_ok());
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct WsDisplayNamePoolSession;
+
+impl Actor for WsDisplayNamePoolSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.subscribe_system_async::<ServerShutdown>(ctx);
+    }
+}
+
+impl Handler<ServerShutdown> for WsDisplayNamePoolSession {
+    type Result = ();
+
+    fn handle(&mut self, _msg: ServerShutdown, ctx: &mut Self::Context) -> Self::Result {
+        ctx.close(Some(ws::CloseCode::Away.into()));
+        ctx.stop();
+    }
+}
+
+// Handle messages from the subscriber.
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsDisplayNamePoolSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        let msg = if let Ok(msg) = msg {
+            msg
+        } else {
+            ctx.stop();
+            return;
+        };
+
+        match msg {
+            ws::Message::Text(msg) => {
+                if msg == "heartbeat" {
+                    ctx.pong(b"pong");
+                    return;
+                }
+
+                if let Ok(chain) = serde_json::from_slice::<ChainName>(msg.as_bytes()) {
+                    // Subscribe to the display name changes of the specified chain.
+                    DisplayNamePool::from_registry()
+                        .send(SubscribeDisplayNames {
+                            subscriber: ctx.address().recipient(),
+                            chain,
+                        })
+                        .into_actor(self)
+                        .then(|_, _, _| fut::ready(()))
+                        .wait(ctx);
+                } else {
+                    // Invalid message type, inform caller.
+                    match serde_json::to_string(&JsonResult::<()>::Err(
+                        "Invalid message type".to_string(),
+                    )) {
+                        Ok(m) => ctx.text(m),
+                        Err(err) => {
+                            error!("Failed to serialize WS session message response: {:?}", err)
+                        }
+                    }
+                }
+            }
+            ws::Message::Ping(b) => {
+                ctx.pong(&b);
+            }
+            ws::Message::Close(reason) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<T: Serialize> Handler<JsonResult<T>> for WsDisplayNamePoolSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: JsonResult<T>, ctx: &mut Self::Context) -> Self::Result {
+        match serde_json::to_string(&msg) {
+            Ok(m) => ctx.text(m),
+            Err(err) => error!("Failed to serialize WS session message response: {:?}", err),
+        }
+    }
+}
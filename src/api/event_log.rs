@@ -0,0 +1,142 @@
+use super::JsonResult;
+use crate::database::Database;
+use crate::primitives::{ChainAddress, ChainName, Event, Timestamp, NOTIFICATION_KINDS};
+use actix_web::{web, HttpResponse};
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 500;
+
+/// Query parameters of `GET /api/events`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct EventQuery {
+    pub chain: Option<ChainName>,
+    pub address: Option<String>,
+    // Comma separated notification kinds.
+    pub kinds: Option<String>,
+    // Only events created at or after this Unix timestamp.
+    pub since: Option<u64>,
+    pub limit: Option<usize>,
+    // The `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+}
+
+/// Server-side filter of the event log.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EventFilter {
+    pub chain: Option<ChainName>,
+    pub address: Option<ChainAddress>,
+    pub kinds: Vec<String>,
+    pub since: Option<Timestamp>,
+    pub limit: usize,
+    pub cursor: Option<String>,
+}
+
+impl EventQuery {
+    pub fn into_filter(self) -> std::result::Result<EventFilter, String> {
+        let mut kinds = vec![];
+        for kind in self.kinds.as_deref().unwrap_or_default().split(',') {
+            let kind = kind.trim();
+            if kind.is_empty() {
+                continue;
+            }
+
+            if !NOTIFICATION_KINDS.iter().any(|(known, _)| *known == kind) {
+                return Err(format!("Unknown notification kind '{}'", kind));
+            }
+
+            kinds.push(kind.to_string());
+        }
+
+        if let Some(cursor) = &self.cursor {
+            if bson::oid::ObjectId::parse_str(cursor).is_err() {
+                return Err("Invalid cursor".to_string());
+            }
+        }
+
+        Ok(EventFilter {
+            chain: self.chain,
+            address: self.address.map(ChainAddress::from),
+            kinds,
+            since: self.since.map(Timestamp::from),
+            limit: self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+            cursor: self.cursor,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct EventPage {
+    // Oldest first.
+    pub events: Vec<Event>,
+    // Set if more events might match the filter.
+    pub next_cursor: Option<String>,
+}
+
+/// Paginated access to the event log for UIs and audit tools.
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    db: Database,
+}
+
+impl EventLog {
+    pub fn new(db: Database) -> Self {
+        EventLog { db }
+    }
+}
+
+pub async fn events(query: web::Query<EventQuery>, log: web::Data<EventLog>) -> HttpResponse {
+    let filter = match query.into_inner().into_filter() {
+        Ok(filter) => filter,
+        Err(err) => return HttpResponse::BadRequest().json(JsonResult::<EventPage>::Err(err)),
+    };
+
+    match log.db.fetch_event_page(&filter).await {
+        Ok(page) => HttpResponse::Ok().json(JsonResult::Ok(page)),
+        Err(err) => {
+            error!("Failed to fetch events of {:?}: {:?}", filter, err);
+            HttpResponse::InternalServerError().json(JsonResult::<EventPage>::Err(
+                "Backend error, contact admin".to_string(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_query_into_filter() {
+        let filter = EventQuery {
+            address: Some("1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP".to_string()),
+            kinds: Some("field_verified, judgement_provided,".to_string()),
+            since: Some(1_000),
+            limit: Some(10_000),
+            ..Default::default()
+        }
+        .into_filter()
+        .unwrap();
+
+        assert_eq!(filter.kinds, vec!["field_verified", "judgement_provided"]);
+        assert_eq!(filter.since, Some(Timestamp::from(1_000)));
+        assert_eq!(filter.limit, MAX_LIMIT);
+
+        let filter = EventQuery::default().into_filter().unwrap();
+        assert!(filter.kinds.is_empty());
+        assert_eq!(filter.limit, DEFAULT_LIMIT);
+
+        assert!(EventQuery {
+            kinds: Some("identity_teleported".to_string()),
+            ..Default::default()
+        }
+        .into_filter()
+        .is_err());
+
+        assert!(EventQuery {
+            cursor: Some("not-a-cursor".to_string()),
+            ..Default::default()
+        }
+        .into_filter()
+        .is_err());
+    }
+}
@@ -0,0 +1,216 @@
+use crate::database::Database;
+use crate::instance_registry::{InstanceRegistration, HEARTBEAT_TIMEOUT};
+use crate::primitives::Timestamp;
+use actix_web::{web, HttpResponse};
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct HealthReport {
+    // Whether all critical subsystems are up, i.e. the database is reachable
+    // and every Watcher is connected.
+    pub healthy: bool,
+    pub database: DatabaseHealth,
+    pub adapters: Vec<AdapterHealth>,
+    pub watchers: Vec<WatcherHealth>,
+    pub generated_at: Timestamp,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct DatabaseHealth {
+    pub connected: bool,
+    // Commands recently failed or exceeded the slow query threshold.
+    pub degraded: bool,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct AdapterHealth {
+    // Host of the instance running the adapter.
+    pub host: String,
+    pub adapter: String,
+    pub last_fetch: Timestamp,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct WatcherHealth {
+    pub host: String,
+    pub chain: String,
+    pub connected: bool,
+    pub last_message: Option<Timestamp>,
+}
+
+impl HealthReport {
+    /// Combines the status reported by the active instances, which might run
+    /// in different processes than the API.
+    pub fn new(
+        database: DatabaseHealth,
+        instances: Vec<InstanceRegistration>,
+        now: Timestamp,
+    ) -> Self {
+        let mut adapters = vec![];
+        let mut watchers = vec![];
+        for instance in instances {
+            adapters.extend(instance.adapters.into_iter().map(|status| AdapterHealth {
+                host: instance.host.clone(),
+                adapter: status.adapter,
+                last_fetch: status.last_fetch,
+            }));
+            watchers.extend(instance.watchers.into_iter().map(|status| WatcherHealth {
+                host: instance.host.clone(),
+                chain: status.chain,
+                connected: status.connected,
+                last_message: status.last_message,
+            }));
+        }
+
+        HealthReport {
+            healthy: database.connected && watchers.iter().all(|watcher| watcher.connected),
+            database,
+            adapters,
+            watchers,
+            generated_at: now,
+        }
+    }
+}
+
+/// Reports the status of the subsystems with every request.
+#[derive(Debug, Clone)]
+pub struct HealthCheck {
+    db: Database,
+}
+
+impl HealthCheck {
+    pub fn new(db: Database) -> Self {
+        HealthCheck { db }
+    }
+    async fn report(&self) -> HealthReport {
+        let now = Timestamp::now();
+        let connected = match self.db.connectivity_check().await {
+            Ok(()) => true,
+            Err(err) => {
+                error!("Health check: database is not reachable: {:?}", err);
+                false
+            }
+        };
+
+        let instances = if connected {
+            let since = Timestamp::from(now.raw().saturating_sub(HEARTBEAT_TIMEOUT));
+            self.db
+                .fetch_active_instances(since)
+                .await
+                .unwrap_or_else(|err| {
+                    error!("Health check: failed to fetch instances: {:?}", err);
+                    vec![]
+                })
+        } else {
+            vec![]
+        };
+
+        HealthReport::new(
+            DatabaseHealth {
+                connected,
+                degraded: self.db.health().is_degraded(),
+            },
+            instances,
+            now,
+        )
+    }
+}
+
+/// Readiness of the service, responds with `503` if a critical subsystem is
+/// down.
+pub async fn healthcheck(check: web::Data<HealthCheck>) -> HttpResponse {
+    let report = check.report().await;
+    if report.healthy {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+/// Liveness of the process, independent of the subsystems.
+pub async fn liveness() -> HttpResponse {
+    HttpResponse::Ok().body("OK")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instance_registry::{AdapterStatus, WatcherStatus};
+
+    fn instance(host: &str, watchers: Vec<WatcherStatus>) -> InstanceRegistration {
+        InstanceRegistration {
+            instance_id: host.to_string(),
+            role: "adapter_listener".to_string(),
+            host: host.to_string(),
+            version: "0.1.0".to_string(),
+            chains: vec![],
+            last_seen: Timestamp::from(1_000),
+            adapters: vec![AdapterStatus {
+                adapter: "Email".to_string(),
+                last_fetch: Timestamp::from(990),
+            }],
+            watchers,
+        }
+    }
+
+    #[test]
+    fn health_report() {
+        let database = DatabaseHealth {
+            connected: true,
+            degraded: false,
+        };
+        let watcher = |chain: &str, connected: bool| WatcherStatus {
+            chain: chain.to_string(),
+            connected,
+            last_message: Some(Timestamp::from(995)),
+        };
+
+        let report = HealthReport::new(
+            database.clone(),
+            vec![
+                instance("a", vec![watcher("polkadot", true)]),
+                instance("b", vec![watcher("kusama", true)]),
+            ],
+            Timestamp::from(1_000),
+        );
+        assert!(report.healthy);
+        assert_eq!(report.adapters.len(), 2);
+        assert_eq!(report.watchers[1].host, "b");
+        assert_eq!(report.watchers[1].chain, "kusama");
+
+        // A disconnected Watcher is critical.
+        let report = HealthReport::new(
+            database.clone(),
+            vec![instance(
+                "a",
+                vec![watcher("polkadot", true), watcher("kusama", false)],
+            )],
+            Timestamp::from(1_000),
+        );
+        assert!(!report.healthy);
+
+        // So is the database, a degraded one is only reported.
+        let report = HealthReport::new(
+            DatabaseHealth {
+                connected: false,
+                degraded: true,
+            },
+            vec![],
+            Timestamp::from(1_000),
+        );
+        assert!(!report.healthy);
+
+        let report = HealthReport::new(
+            DatabaseHealth {
+                connected: true,
+                degraded: true,
+            },
+            vec![],
+            Timestamp::from(1_000),
+        );
+        assert!(report.healthy);
+    }
+}
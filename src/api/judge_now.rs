@@ -0,0 +1,128 @@
+use super::challenge_token::ChallengeTokens;
+use super::JsonResult;
+use crate::database::Database;
+use crate::primitives::{IdentityContext, Timestamp};
+use crate::verification::JUDGEMENT_DELAY_RANGE;
+use actix_web::{web, HttpResponse};
+use schemars::JsonSchema;
+
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct JudgeNowConfig {
+    // Seconds after the full verification before which a judgement is never
+    // issued, defaults to the lower bound of the random delay.
+    pub min_delay: Option<u64>,
+}
+
+/// The message which must be signed in order to request the judgement early.
+pub fn judge_now_message(context: &IdentityContext, timestamp: u64) -> String {
+    format!(
+        "registrar-judge-now:{}:{}:{}",
+        context.chain.as_str(),
+        context.address.as_str(),
+        timestamp
+    )
+}
+
+/// Lets users of fully verified identities skip the remaining random delay
+/// before the judgement is issued by signing a recent timestamp with the
+/// account key.
+#[derive(Debug, Clone)]
+pub struct JudgeNow {
+    db: Database,
+    tokens: ChallengeTokens,
+    min_delay: u64,
+}
+
+impl JudgeNow {
+    pub fn new(db: Database, tokens: ChallengeTokens, config: JudgeNowConfig) -> Self {
+        JudgeNow {
+            db,
+            tokens,
+            min_delay: config.min_delay.unwrap_or(JUDGEMENT_DELAY_RANGE.start),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct JudgeNowRequest {
+    pub context: IdentityContext,
+    pub timestamp: u64,
+    // Signature of the message returned by `judge_now_message`.
+    pub signature: String,
+}
+
+pub async fn judge_now(
+    req: web::Json<JudgeNowRequest>,
+    judge_now: web::Data<Option<JudgeNow>>,
+) -> HttpResponse {
+    let judge_now = match judge_now.as_ref() {
+        Some(judge_now) => judge_now,
+        None => {
+            return HttpResponse::Ok().json(JsonResult::<Timestamp>::Err(
+                "Early judgements are not enabled".to_string(),
+            ))
+        }
+    };
+
+    let req = req.into_inner();
+
+    let result = async {
+        // Replaying a request has no effect, so a recent timestamp suffices.
+        if !judge_now
+            .tokens
+            .is_fresh(req.timestamp, Timestamp::now().raw())
+        {
+            return Ok(JsonResult::Err("Expired request".to_string()));
+        }
+
+        let message = judge_now_message(&req.context, req.timestamp);
+        if !judge_now
+            .tokens
+            .verify_signature(&req.context, message, &req.signature)
+            .await?
+        {
+            return Ok(JsonResult::Err("Invalid signature".to_string()));
+        }
+
+        match judge_now
+            .db
+            .expedite_judgement(&req.context, judge_now.min_delay)
+            .await?
+        {
+            Some(issue_at) => {
+                info!(
+                    "Judgement of {:?} was brought forward to {:?}",
+                    req.context, issue_at
+                );
+                crate::Result::Ok(JsonResult::Ok(issue_at))
+            }
+            None => Ok(JsonResult::Err(
+                "No fully verified identity awaiting its judgement found".to_string(),
+            )),
+        }
+    }
+    .await
+    .unwrap_or_else(|err| {
+        error!(
+            "Failed to bring the judgement of {:?} forward: {:?}",
+            req.context, err
+        );
+        JsonResult::Err("Backend error, contact admin".to_string())
+    });
+
+    HttpResponse::Ok().json(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn judge_now_message_format() {
+        assert_eq!(
+            judge_now_message(&IdentityContext::alice(), 1_000),
+            "registrar-judge-now:polkadot:1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP:1000"
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use crate::database::Database;
+use crate::primitives::{Event, NotificationMessage, Timestamp};
+use actix_web::{http::header, web, HttpResponse};
+use schemars::JsonSchema;
+
+const DEFAULT_LIMIT: usize = 50;
+const DEFAULT_TITLE: &str = "Registrar judgements";
+// In seconds
+const CACHE_MAX_AGE: u64 = 60;
+
+pub const FEED_PATH: &str = "/feed/judgements.xml";
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct JudgementFeedConfig {
+    // Public URL of the API, used for the feed ID and links.
+    pub base_url: String,
+    // Title of the feed.
+    pub title: Option<String>,
+    // Maximum number of entries.
+    pub limit: Option<usize>,
+}
+
+/// Atom feed of the most recently issued judgements, based on the event log.
+#[derive(Debug, Clone)]
+pub struct JudgementFeed {
+    db: Database,
+    config: JudgementFeedConfig,
+}
+
+impl JudgementFeed {
+    pub fn new(db: Database, config: JudgementFeedConfig) -> Self {
+        JudgementFeed { db, config }
+    }
+}
+
+fn render(config: &JudgementFeedConfig, events: &[Event], now: Timestamp) -> String {
+    let base_url = config.base_url.trim_end_matches('/');
+    let feed_url = format!("{}{}", base_url, FEED_PATH);
+    let title = config.title.as_deref().unwrap_or(DEFAULT_TITLE);
+    // Events are sorted newest first.
+    let updated = events.first().map(|event| event.timestamp).unwrap_or(now);
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("  <title>{}</title>\n", escape(title)));
+    xml.push_str(&format!("  <id>{}</id>\n", escape(&feed_url)));
+    xml.push_str(&format!(
+        "  <link rel=\"self\" href=\"{}\"/>\n",
+        escape(&feed_url)
+    ));
+    xml.push_str(&format!("  <updated>{}</updated>\n", rfc3339(updated)));
+    xml.push_str(&format!(
+        "  <author><name>{}</name></author>\n",
+        escape(title)
+    ));
+
+    for event in events {
+        let context = match &event.message {
+            NotificationMessage::JudgementProvided { context } => context,
+            _ => continue,
+        };
+
+        let chain = context.chain.as_str();
+        let address = escape(context.address.as_str());
+
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!(
+            "    <title>Judgement provided for {} ({})</title>\n",
+            address, chain
+        ));
+        xml.push_str(&format!(
+            "    <id>urn:registrar:judgement:{}:{}:{}</id>\n",
+            chain,
+            address,
+            event.timestamp.raw()
+        ));
+        xml.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            rfc3339(event.timestamp)
+        ));
+        xml.push_str(&format!(
+            "    <summary>The registrar issued a judgement for the {} account {}.</summary>\n",
+            chain, address
+        ));
+        xml.push_str("  </entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Formats the UNIX timestamp as an RFC 3339 date (UTC), as required by Atom.
+fn rfc3339(timestamp: Timestamp) -> String {
+    let secs = timestamp.raw();
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Converts the days since epoch into a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+pub async fn judgement_feed(feed: web::Data<Option<JudgementFeed>>) -> HttpResponse {
+    let feed = match feed.as_ref() {
+        Some(feed) => feed,
+        None => return HttpResponse::NotFound().finish(),
+    };
+
+    let limit = feed.config.limit.unwrap_or(DEFAULT_LIMIT);
+    match feed.db.fetch_judgement_events(limit as i64).await {
+        Ok(events) => HttpResponse::Ok()
+            .content_type("application/atom+xml; charset=utf-8")
+            .insert_header((
+                header::CACHE_CONTROL,
+                format!("public, max-age={}", CACHE_MAX_AGE),
+            ))
+            .body(render(&feed.config, &events, Timestamp::now())),
+        Err(err) => {
+            error!("Failed to fetch judgement events: {:?}", err);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::IdentityContext;
+
+    #[test]
+    fn format_rfc3339() {
+        assert_eq!(rfc3339(Timestamp::from(0)), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            rfc3339(Timestamp::from(951_825_599)),
+            "2000-02-29T11:59:59Z"
+        );
+        assert_eq!(
+            rfc3339(Timestamp::from(1_700_000_000)),
+            "2023-11-14T22:13:20Z"
+        );
+    }
+
+    #[test]
+    fn escape_xml() {
+        assert_eq!(
+            escape("<a href=\"x\">Tom & Jerry's</a>"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn render_feed() {
+        let config = JudgementFeedConfig {
+            base_url: "https://registrar.example/".to_string(),
+            title: None,
+            limit: None,
+        };
+
+        let alice = IdentityContext::alice();
+        let events = vec![
+            Event {
+                timestamp: Timestamp::from(1_700_000_000),
+                message: NotificationMessage::JudgementProvided {
+                    context: alice.clone(),
+                },
+                seq: 2,
+                log_seq: 0,
+                dedup_key: None,
+            },
+            // Other events are skipped.
+            Event {
+                timestamp: Timestamp::from(1_600_000_000),
+                message: NotificationMessage::IdentityInserted { context: alice },
+                seq: 1,
+                log_seq: 0,
+                dedup_key: None,
+            },
+        ];
+
+        let xml = render(&config, &events, Timestamp::from(1_800_000_000));
+        assert!(xml.contains("<id>https://registrar.example/feed/judgements.xml</id>"));
+        assert!(xml.contains("  <updated>2023-11-14T22:13:20Z</updated>"));
+        assert!(xml.contains(
+            "<title>Judgement provided for 1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP (polkadot)</title>"
+        ));
+        assert_eq!(xml.matches("<entry>").count(), 1);
+
+        // The current time is used if there are no judgements yet.
+        let xml = render(&config, &[], Timestamp::from(0));
+        assert!(xml.contains("<updated>1970-01-01T00:00:00Z</updated>"));
+        assert!(!xml.contains("<entry>"));
+    }
+}
@@ -1,15 +1,33 @@
-use super::JsonResult;
+use super::admin_search::AdminSearch;
+use super::challenge_token::ChallengeTokens;
+use super::completion_estimate::{CompletionEstimate, CompletionEstimator};
+use super::session_limits::{
+    parse_subscription_request, SessionGuard, SessionLimits, SubscriptionRequest, Violation,
+};
+use super::ws_encoding::{WsEncoding, WsFrame};
+use super::{JsonResult, ServerShutdown};
 use crate::database::Database;
-use crate::primitives::{IdentityContext, JudgementStateBlanked, NotificationMessage};
+use crate::metrics;
+use crate::primitives::{
+    ChainName, IdentityContext, JudgementState, JudgementStateBlanked, JudgementStatus,
+    NotificationEnvelope, NotificationMessage, Timestamp,
+};
 use actix::prelude::*;
 use actix_broker::BrokerSubscribe;
 use actix_web_actors::ws;
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::RwLock;
 
-type Subscriber = Recipient<JsonResult<ResponseAccountState>>;
+// Maximum number of identities in the snapshot of a chain subscription.
+const CHAIN_SNAPSHOT_LIMIT: usize = 500;
+
+type Subscriber = Recipient<SessionResponse>;
+// Subscribed to the identities of a chain with one of the statuses, or all
+// identities if empty.
+type ChainSubscriber = (Subscriber, Vec<JudgementStatus>);
 
 #[derive(Clone, Debug, Message)]
 #[rtype(result = "()")]
@@ -18,11 +36,95 @@ pub struct SubscribeAccountState {
     pub id_context: IdentityContext,
 }
 
+/// Subscribes to multiple identities at once, optionally including all pending
+/// identities of the chains. Answered with a single `SessionSnapshot`.
+#[derive(Clone, Debug, Message)]
+#[rtype(result = "()")]
+pub struct SubscribeAccountStates {
+    pub subscriber: Subscriber,
+    pub snapshot: Recipient<SessionSnapshot>,
+    pub contexts: Vec<IdentityContext>,
+    pub chains: Vec<ChainName>,
+    // Filters the identities of the chains.
+    pub statuses: Vec<JudgementStatus>,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Message)]
 #[rtype(result = "()")]
 pub struct NotifyAccountState {
     pub state: JudgementStateBlanked,
     pub notifications: Vec<NotificationMessage>,
+    // Sequence number of the notification in the event log.
+    pub sequence: u64,
+}
+
+/// Lets clients skip notifications which were already displayed, e.g. after
+/// reconnecting.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct NotificationSequence {
+    // Sequence number of the latest notification of the identity.
+    pub sequence: u64,
+    // Notifications up to this sequence number were acknowledged.
+    pub read_sequence: u64,
+}
+
+impl NotificationSequence {
+    fn is_read(&self) -> bool {
+        self.sequence <= self.read_sequence
+    }
+}
+
+/// Sent from the server to the sessions.
+#[derive(Clone, Debug, Message)]
+#[rtype(result = "()")]
+pub struct SessionResponse {
+    pub response: JsonResult<ResponseAccountState>,
+    pub sequence: Option<NotificationSequence>,
+    pub estimate: Option<CompletionEstimate>,
+}
+
+impl SessionResponse {
+    fn error(msg: &str) -> Self {
+        SessionResponse {
+            response: JsonResult::Err(msg.to_string()),
+            sequence: None,
+            estimate: None,
+        }
+    }
+}
+
+/// The current state of all identities of a batch subscription.
+#[derive(Clone, Debug, Message)]
+#[rtype(result = "()")]
+pub struct SessionSnapshot {
+    pub states: Vec<(
+        ResponseAccountState,
+        Option<NotificationSequence>,
+        Option<CompletionEstimate>,
+    )>,
+    // Identities without a judgement request.
+    pub not_found: Vec<IdentityContext>,
+}
+
+// The snapshot as sent over the WS stream.
+#[derive(Clone, Debug, Serialize)]
+struct AccountStateSnapshot {
+    snapshot: Vec<SequencedAccountState>,
+    not_found: Vec<IdentityContext>,
+}
+
+// The account state as sent over the WS stream, including the sequence numbers
+// and the versioned notifications.
+#[derive(Clone, Debug, Serialize)]
+struct SequencedAccountState {
+    state: JudgementStateBlanked,
+    notifications: Vec<NotificationEnvelope>,
+    #[serde(flatten)]
+    sequence: Option<NotificationSequence>,
+    // When the judgement is expected to be issued, if an estimate is
+    // available.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    estimate: Option<CompletionEstimate>,
 }
 
 // Identical to `NotifyAccountState`, but gets sent from the server to the
@@ -57,7 +159,10 @@ impl From<NotifyAccountState> for ResponseAccountState {
 
 pub struct LookupServer {
     db: Database,
+    estimator: CompletionEstimator,
     sessions: Arc<RwLock<HashMap<IdentityContext, Vec<Subscriber>>>>,
+    // Sessions subscribed to all identities of a chain.
+    chain_sessions: Arc<RwLock<HashMap<ChainName, Vec<ChainSubscriber>>>>,
 }
 
 impl Default for LookupServer {
@@ -67,14 +172,48 @@ impl Default for LookupServer {
 }
 
 impl LookupServer {
-    pub fn new(db: Database) -> Self {
+    pub fn new(db: Database, estimator: CompletionEstimator) -> Self {
         LookupServer {
             db,
+            estimator,
             sessions: Default::default(),
+            chain_sessions: Default::default(),
         }
     }
 }
 
+// Estimates the judgement time of the identity. Failures are only logged, the
+// estimate is optional.
+async fn fetch_estimate(
+    estimator: &CompletionEstimator,
+    state: &JudgementState,
+) -> Option<CompletionEstimate> {
+    estimator
+        .estimate_state(state)
+        .await
+        .map_err(|err| error!("Failed to estimate completion time: {:?}", err))
+        .ok()
+        .flatten()
+}
+
+// Updates the subscription metrics after the subscribers changed.
+async fn observe_subscriptions(
+    sessions: &RwLock<HashMap<IdentityContext, Vec<Subscriber>>>,
+    chain_sessions: &RwLock<HashMap<ChainName, Vec<ChainSubscriber>>>,
+) {
+    let sessions = sessions.read().await;
+    let identity_subscriptions = sessions.values().map(|s| s.len()).sum();
+    let max_per_identity = sessions.values().map(|s| s.len()).max().unwrap_or(0);
+    let chain_subscriptions = chain_sessions.read().await.values().map(|s| s.len()).sum();
+
+    metrics::set_ws_subscriptions(
+        sessions.len(),
+        identity_subscriptions,
+        chain_subscriptions,
+        max_per_identity,
+    );
+}
+
 impl SystemService for LookupServer {}
 impl Supervised for LookupServer {}
 
@@ -91,7 +230,9 @@ impl Handler<SubscribeAccountState> for LookupServer {
 
     fn handle(&mut self, msg: SubscribeAccountState, _ctx: &mut Self::Context) -> Self::Result {
         let db = self.db.clone();
+        let estimator = self.estimator.clone();
         let sessions = Arc::clone(&self.sessions);
+        let chain_sessions = Arc::clone(&self.chain_sessions);
 
         Box::pin(
             async move {
@@ -108,10 +249,26 @@ impl Handler<SubscribeAccountState> for LookupServer {
                 };
 
                 if let Some(state) = state {
+                    let sequence = db
+                        .fetch_notification_sequence(&id)
+                        .await
+                        .map_err(|err| error!("Failed to fetch notification sequence: {:?}", err))
+                        .ok()
+                        .map(|(sequence, read_sequence)| NotificationSequence {
+                            sequence,
+                            read_sequence,
+                        });
+
+                    let estimate = fetch_estimate(&estimator, &state).await;
+
                     if subscriber
-                        .try_send(JsonResult::Ok(ResponseAccountState::with_no_notifications(
-                            state,
-                        )))
+                        .try_send(SessionResponse {
+                            response: JsonResult::Ok(ResponseAccountState::with_no_notifications(
+                                state,
+                            )),
+                            sequence,
+                            estimate,
+                        })
                         .is_ok()
                     {
                         sessions
@@ -122,13 +279,108 @@ impl Handler<SubscribeAccountState> for LookupServer {
                                 subscribers.push(subscriber.clone());
                             })
                             .or_insert_with(|| vec![subscriber]);
+
+                        observe_subscriptions(&sessions, &chain_sessions).await;
                     }
                 } else {
-                    subscriber.do_send(JsonResult::Err(
-                        "There is no judgement request from that account for this registrar"
-                            .to_string(),
+                    subscriber.do_send(SessionResponse::error(
+                        "There is no judgement request from that account for this registrar",
+                    ));
+                }
+            }
+            .into_actor(self),
+        )
+    }
+}
+
+impl Handler<SubscribeAccountStates> for LookupServer {
+    type Result = ResponseActFuture<Self, ()>;
+
+    fn handle(&mut self, msg: SubscribeAccountStates, _ctx: &mut Self::Context) -> Self::Result {
+        let db = self.db.clone();
+        let estimator = self.estimator.clone();
+        let sessions = Arc::clone(&self.sessions);
+        let chain_sessions = Arc::clone(&self.chain_sessions);
+
+        Box::pin(
+            async move {
+                let mut states = vec![];
+                let mut not_found = vec![];
+
+                let fetched = async {
+                    for id in &msg.contexts {
+                        match db.fetch_judgement_state(id).await? {
+                            Some(state) => states.push(state),
+                            None => not_found.push(id.clone()),
+                        }
+                    }
+
+                    for chain in &msg.chains {
+                        states.extend(
+                            db.fetch_chain_states(*chain, &msg.statuses, CHAIN_SNAPSHOT_LIMIT)
+                                .await?,
+                        );
+                    }
+
+                    crate::Result::Ok(())
+                }
+                .await;
+
+                if let Err(err) = fetched {
+                    error!("Failed to fetch judgement states: {:?}", err);
+                    msg.subscriber
+                        .do_send(SessionResponse::error("Backend error, contact admin"));
+                    return;
+                }
+
+                // Identities can be part of a chain and be listed explicitly.
+                let mut seen = HashSet::new();
+                states.retain(|state| seen.insert(state.context.clone()));
+
+                let mut snapshot = SessionSnapshot {
+                    states: vec![],
+                    not_found,
+                };
+
+                for state in states {
+                    let sequence = db
+                        .fetch_notification_sequence(&state.context)
+                        .await
+                        .map_err(|err| error!("Failed to fetch notification sequence: {:?}", err))
+                        .ok()
+                        .map(|(sequence, read_sequence)| NotificationSequence {
+                            sequence,
+                            read_sequence,
+                        });
+
+                    let estimate = fetch_estimate(&estimator, &state).await;
+                    snapshot.states.push((
+                        ResponseAccountState::with_no_notifications(state),
+                        sequence,
+                        estimate,
                     ));
                 }
+
+                if msg.snapshot.try_send(snapshot).is_err() {
+                    return;
+                }
+
+                {
+                    let mut sessions = sessions.write().await;
+                    for id in msg.contexts.into_iter().filter(|id| seen.contains(id)) {
+                        sessions.entry(id).or_default().push(msg.subscriber.clone());
+                    }
+
+                    let mut chain_sessions = chain_sessions.write().await;
+                    for chain in msg.chains {
+                        chain_sessions
+                            .entry(chain)
+                            .or_default()
+                            .push((msg.subscriber.clone(), msg.statuses.clone()));
+                    }
+                }
+
+                observe_subscriptions(&sessions, &chain_sessions).await;
             }
             .into_actor(self),
         )
@@ -139,7 +391,10 @@ impl Handler<NotifyAccountState> for LookupServer {
     type Result = ResponseActFuture<Self, ()>;
 
     fn handle(&mut self, msg: NotifyAccountState, _ctx: &mut Self::Context) -> Self::Result {
+        let db = self.db.clone();
+        let estimator = self.estimator.clone();
         let sessions = Arc::clone(&self.sessions);
+        let chain_sessions = Arc::clone(&self.chain_sessions);
 
         Box::pin(
             async move {
@@ -147,46 +402,265 @@ impl Handler<NotifyAccountState> for LookupServer {
                 // still have an active session open will be added back later.
                 let mut to_reinsert = vec![];
 
-                if let Some(subscribers) = sessions.read().await.get(&msg.state.context) {
+                let subscribers = sessions
+                    .read()
+                    .await
+                    .get(&msg.state.context)
+                    .cloned()
+                    .unwrap_or_default();
+                let chain = msg.state.context.chain;
+                let chain_subscribers = chain_sessions
+                    .read()
+                    .await
+                    .get(&chain)
+                    .cloned()
+                    .unwrap_or_default();
+
+                if !subscribers.is_empty() || !chain_subscribers.is_empty() {
+                    let read_sequence = db
+                        .fetch_notification_sequence(&msg.state.context)
+                        .await
+                        .map(|(_, read_sequence)| read_sequence)
+                        .map_err(|err| error!("Failed to fetch notification sequence: {:?}", err))
+                        .unwrap_or_default();
+
+                    // The notified state is blanked, the estimate requires the
+                    // full one.
+                    let estimate = match db
+                        .fetch_judgement_state(&msg.state.context)
+                        .await
+                        .map_err(|err| error!("Failed to fetch judgement state: {:?}", err))
+                    {
+                        Ok(Some(state)) => fetch_estimate(&estimator, &state).await,
+                        _ => None,
+                    };
+
+                    let resp = SessionResponse {
+                        sequence: Some(NotificationSequence {
+                            sequence: msg.sequence,
+                            read_sequence,
+                        }),
+                        response: JsonResult::Ok(msg.clone().into()),
+                        estimate,
+                    };
+
                     // Notify each subscriber.
                     for subscriber in subscribers {
-                        if subscriber
-                            .try_send(JsonResult::Ok(msg.clone().into()))
-                            .is_ok()
+                        if subscriber.try_send(resp.clone()).is_ok() {
+                            to_reinsert.push(subscriber);
+                        }
+                    }
+
+                    // Sessions subscribed to the chain with a matching status,
+                    // unless already notified above.
+                    let status = msg.state.status();
+                    let mut notified = to_reinsert.clone();
+                    let mut closed = vec![];
+                    for (subscriber, statuses) in chain_subscribers {
+                        if (!statuses.is_empty() && !statuses.contains(&status))
+                            || notified.contains(&subscriber)
                         {
-                            to_reinsert.push(subscriber.clone());
+                            continue;
+                        }
+
+                        if subscriber.try_send(resp.clone()).is_ok() {
+                            notified.push(subscriber);
+                        } else {
+                            closed.push(subscriber);
+                        }
+                    }
+
+                    if !closed.is_empty() {
+                        if let Some(subscribers) = chain_sessions.write().await.get_mut(&chain) {
+                            subscribers.retain(|(subscriber, _)| !closed.contains(subscriber));
                         }
                     }
                 }
 
-                // Reinsert active subscribers back into storage.
-                sessions
-                    .write()
-                    .await
-                    .insert(msg.state.context, to_reinsert);
+                // Reinsert active subscribers back into storage. Identities
+                // without any subscribers left are dropped.
+                if to_reinsert.is_empty() {
+                    sessions.write().await.remove(&msg.state.context);
+                } else {
+                    sessions
+                        .write()
+                        .await
+                        .insert(msg.state.context, to_reinsert);
+                }
+
+                observe_subscriptions(&sessions, &chain_sessions).await;
             }
             .into_actor(self),
         )
     }
 }
 
-#[derive(Default)]
-pub struct WsAccountStatusSession;
+pub struct WsAccountStatusSession {
+    guard: SessionGuard,
+    // If set, challenge values are only sent for identities the client
+    // presented a valid token for.
+    tokens: Option<ChallengeTokens>,
+    // Expiry of the presented tokens.
+    revealed: HashMap<IdentityContext, u64>,
+    // Verifies the admin token required for chain subscriptions.
+    admin: Option<AdminSearch>,
+    // Set once a valid admin token was presented, which reveals all
+    // challenges.
+    is_admin: bool,
+    // Why the session was closed, reported once it stopped.
+    close_reason: Option<&'static str>,
+    encoding: WsEncoding,
+}
+
+impl WsAccountStatusSession {
+    pub fn new(
+        limits: SessionLimits,
+        tokens: Option<ChallengeTokens>,
+        admin: Option<AdminSearch>,
+        encoding: WsEncoding,
+    ) -> Self {
+        WsAccountStatusSession {
+            guard: SessionGuard::new(limits),
+            tokens,
+            revealed: HashMap::new(),
+            admin,
+            is_admin: false,
+            close_reason: None,
+            encoding,
+        }
+    }
+    fn reveals_challenges(&self, context: &IdentityContext) -> bool {
+        if self.tokens.is_none() || self.is_admin {
+            return true;
+        }
+
+        self.revealed
+            .get(context)
+            .map(|expires| *expires > Timestamp::now().raw())
+            .unwrap_or(false)
+    }
+    fn apply_token(
+        &mut self,
+        ctx: &mut ws::WebsocketContext<Self>,
+        context: &IdentityContext,
+        token: Option<String>,
+    ) {
+        if let (Some(tokens), Some(token)) = (&self.tokens, token) {
+            match tokens.verify(&token, context, Timestamp::now().raw()) {
+                Some(expires) => {
+                    self.revealed.insert(context.clone(), expires);
+                }
+                None => self.send_error(ctx, "Invalid or expired challenge token".to_string()),
+            }
+        }
+    }
+    // Prepares the account state for the client.
+    fn sequenced(
+        &self,
+        mut resp: ResponseAccountState,
+        sequence: Option<NotificationSequence>,
+        estimate: Option<CompletionEstimate>,
+    ) -> SequencedAccountState {
+        if !self.reveals_challenges(&resp.state.context) {
+            resp.state.hide_challenge_values();
+        }
+
+        // Notifications which were acknowledged by the client (e.g. in
+        // another tab) are not displayed again.
+        if sequence.map(|seq| seq.is_read()).unwrap_or(false) {
+            resp.notifications.clear();
+        }
+
+        SequencedAccountState {
+            state: resp.state,
+            notifications: resp.notifications.into_iter().map(|n| n.into()).collect(),
+            sequence,
+            estimate,
+        }
+    }
+    fn send<T: Serialize>(&self, ctx: &mut ws::WebsocketContext<Self>, msg: &T, kind: &str) {
+        match self.encoding.encode(msg) {
+            Ok(WsFrame::Text(text)) => {
+                metrics::observe_ws_message(kind);
+                ctx.text(text)
+            }
+            Ok(WsFrame::Binary(bytes)) => {
+                metrics::observe_ws_message(kind);
+                ctx.binary(bytes)
+            }
+            Err(err) => error!("Failed to serialize WS session {}: {:?}", kind, err),
+        }
+    }
+    fn send_error(&self, ctx: &mut ws::WebsocketContext<Self>, msg: String) {
+        self.send(ctx, &JsonResult::<()>::Err(msg), "error");
+    }
+    fn disconnect(
+        &mut self,
+        ctx: &mut ws::WebsocketContext<Self>,
+        code: ws::CloseCode,
+        reason: &'static str,
+        description: String,
+    ) {
+        debug!("Closing WS session: {}", description);
+        self.close_reason = Some(reason);
+        ctx.close(Some(ws::CloseReason {
+            code,
+            description: Some(description),
+        }));
+        ctx.stop();
+    }
+    fn on_violation(&mut self, ctx: &mut ws::WebsocketContext<Self>, violation: Violation) {
+        self.disconnect(
+            ctx,
+            ws::CloseCode::Policy,
+            violation.as_str(),
+            violation.to_string(),
+        );
+    }
+}
 
 impl Actor for WsAccountStatusSession {
     type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        metrics::observe_ws_session_started();
+        self.subscribe_system_async::<ServerShutdown>(ctx);
+    }
+
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        // Without a reason, the connection was dropped without a close frame.
+        metrics::observe_ws_session_stopped(self.close_reason.unwrap_or("connection_lost"));
+    }
 }
 
 // Handle messages from the subscriber.
 impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsAccountStatusSession {
     fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
-        let msg = if let Ok(msg) = msg {
-            msg
-        } else {
-            ctx.stop();
-            return;
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(ws::ProtocolError::Overflow) => {
+                let description = format!(
+                    "Frame exceeds the maximum size of {} bytes",
+                    self.guard.limits().max_frame_size
+                );
+                self.disconnect(ctx, ws::CloseCode::Size, "frame_too_large", description);
+                return;
+            }
+            Err(_) => {
+                self.close_reason = Some("protocol_error");
+                ctx.stop();
+                return;
+            }
         };
 
+        // Enforce the message rate, control frames are exempted.
+        if matches!(msg, ws::Message::Text(_) | ws::Message::Binary(_)) {
+            if let Err(violation) = self.guard.on_message(Instant::now()) {
+                self.on_violation(ctx, violation);
+                return;
+            }
+        }
+
         match msg {
             ws::Message::Text(msg) => {
                 if msg == "heartbeat" {
@@ -194,32 +668,82 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsAccountStatusSe
                     return;
                 }
 
-                if let Ok(context) = serde_json::from_slice::<IdentityContext>(msg.as_bytes()) {
-                    // Subscribe the the specified identity context.
-                    LookupServer::from_registry()
-                        .send(SubscribeAccountState {
-                            subscriber: ctx.address().recipient(),
-                            id_context: context,
-                        })
-                        .into_actor(self)
-                        .then(|_, _, _| fut::ready(()))
-                        .wait(ctx);
-                } else {
-                    // Invalid message type, inform caller.
-                    match serde_json::to_string(&JsonResult::<()>::Err(
-                        "Invalid message type".to_string(),
-                    )) {
-                        Ok(m) => ctx.text(m),
-                        Err(err) => {
-                            error!("Failed to serialize WS session message response: {:?}", err)
+                let max_subscriptions = self.guard.limits().max_subscriptions;
+                match parse_subscription_request(msg.as_bytes(), max_subscriptions) {
+                    Ok(SubscriptionRequest::Single(subscription)) => {
+                        let context = subscription.context;
+                        self.apply_token(ctx, &context, subscription.token);
+
+                        // Subscribe the the specified identity context.
+                        LookupServer::from_registry()
+                            .send(SubscribeAccountState {
+                                subscriber: ctx.address().recipient(),
+                                id_context: context,
+                            })
+                            .into_actor(self)
+                            .then(|_, _, _| fut::ready(()))
+                            .wait(ctx);
+                    }
+                    Ok(SubscriptionRequest::Batch(batch)) => {
+                        let mut chains = batch.chains;
+                        if !chains.is_empty() {
+                            let authorized = match (&self.admin, &batch.admin_token) {
+                                (Some(admin), Some(token)) => admin.verify_token(token),
+                                _ => false,
+                            };
+
+                            if authorized {
+                                self.is_admin = true;
+                            } else {
+                                self.send_error(ctx, "Invalid admin token".to_string());
+                                chains.clear();
+                            }
+                        }
+
+                        let mut contexts = vec![];
+                        for subscription in batch.identities {
+                            self.apply_token(ctx, &subscription.context, subscription.token);
+                            contexts.push(subscription.context);
+                        }
+
+                        if contexts.is_empty() && chains.is_empty() {
+                            return;
+                        }
+
+                        LookupServer::from_registry()
+                            .send(SubscribeAccountStates {
+                                subscriber: ctx.address().recipient(),
+                                snapshot: ctx.address().recipient(),
+                                contexts,
+                                chains,
+                                statuses: batch.status,
+                            })
+                            .into_actor(self)
+                            .then(|_, _, _| fut::ready(()))
+                            .wait(ctx);
+                    }
+                    Err(err) => {
+                        // Invalid message, inform caller.
+                        self.send_error(ctx, err);
+
+                        if let Err(violation) = self.guard.on_invalid() {
+                            self.on_violation(ctx, violation);
                         }
                     }
                 }
             }
+            ws::Message::Binary(_) => {
+                self.send_error(ctx, "Binary messages are not supported".to_string());
+
+                if let Err(violation) = self.guard.on_invalid() {
+                    self.on_violation(ctx, violation);
+                }
+            }
             ws::Message::Ping(b) => {
                 ctx.pong(&b);
             }
             ws::Message::Close(reason) => {
+                self.close_reason = Some("client_closed");
                 ctx.close(reason);
                 ctx.stop();
             }
@@ -228,13 +752,52 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsAccountStatusSe
     }
 }
 
-impl<T: Serialize> Handler<JsonResult<T>> for WsAccountStatusSession {
+impl Handler<SessionResponse> for WsAccountStatusSession {
     type Result = ();
 
-    fn handle(&mut self, msg: JsonResult<T>, ctx: &mut Self::Context) -> Self::Result {
-        match serde_json::to_string(&msg) {
-            Ok(m) => ctx.text(m),
-            Err(err) => error!("Failed to serialize WS session message response: {:?}", err),
-        }
+    fn handle(&mut self, msg: SessionResponse, ctx: &mut Self::Context) -> Self::Result {
+        let msg = match msg.response {
+            JsonResult::Ok(resp) => {
+                JsonResult::Ok(self.sequenced(resp, msg.sequence, msg.estimate))
+            }
+            JsonResult::Err(err) => JsonResult::Err(err),
+        };
+
+        let kind = match msg {
+            JsonResult::Ok(_) => "state",
+            JsonResult::Err(_) => "error",
+        };
+
+        self.send(ctx, &msg, kind);
+    }
+}
+
+impl Handler<ServerShutdown> for WsAccountStatusSession {
+    type Result = ();
+
+    fn handle(&mut self, _msg: ServerShutdown, ctx: &mut Self::Context) -> Self::Result {
+        self.disconnect(
+            ctx,
+            ws::CloseCode::Away,
+            "server_shutdown",
+            "Server is shutting down".to_string(),
+        );
+    }
+}
+
+impl Handler<SessionSnapshot> for WsAccountStatusSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: SessionSnapshot, ctx: &mut Self::Context) -> Self::Result {
+        let snapshot = AccountStateSnapshot {
+            snapshot: msg
+                .states
+                .into_iter()
+                .map(|(resp, sequence, estimate)| self.sequenced(resp, sequence, estimate))
+                .collect(),
+            not_found: msg.not_found,
+        };
+
+        self.send(ctx, &JsonResult::Ok(snapshot), "snapshot");
     }
 }
@@ -1,21 +1,74 @@
 use self::judgement_state::WsAccountStatusSession;
 use crate::database::Database;
+use crate::primitives::{NOTIFICATION_KINDS, NOTIFICATION_VERSION};
+use crate::runtime_info::RuntimeInfo;
 use crate::{NotifierConfig, Result};
 use actix::prelude::*;
 use actix::registry::SystemRegistry;
+use actix_broker::{Broker, SystemBroker};
 use actix_cors::Cors;
+use actix_web::dev::ServerHandle;
 use actix_web::{http, web, App, Error as ActixError, HttpRequest, HttpResponse, HttpServer};
 use actix_web_actors::ws;
+use admin_search::{admin_search, AdminSearch};
+use attestation::{attest_message, Attestor};
+use challenge_expiry::{request_new_challenge, ChallengeRenewal};
+use challenge_regeneration::{regenerate_challenge, ChallengeRegenerator};
+use challenge_token::request_challenge_token;
+use completion_estimate::{estimated_completion, CompletionEstimator};
+use credential_import::{import_credentials, CredentialImport};
 use display_name_check::{check_display_name, DisplayNameChecker};
+use display_name_pool::{DisplayNamePool, WsDisplayNamePoolSession};
+use event_log::{events, EventLog};
+use health::{healthcheck, liveness, HealthCheck};
+use judge_now::{judge_now, JudgeNow};
+use judgement_feed::{judgement_feed, JudgementFeed, FEED_PATH};
+use notification_ack::{acknowledge_notifications, NotificationAcknowledger};
+use request_withdrawal::{request_withdrawal_nonce, withdraw_request, RequestWithdrawal};
 use second_challenge::{verify_second_challenge, SecondChallengeVerifier};
+use session_limits::SessionLimits;
+use tokio::task::JoinHandle;
+use ws_encoding::{WsEncoding, PROTOCOLS};
 
+mod admin_search;
+mod attestation;
+mod challenge_expiry;
+mod challenge_regeneration;
+mod challenge_token;
+mod completion_estimate;
+mod credential_import;
 mod display_name_check;
+mod display_name_pool;
+mod event_log;
+mod health;
+mod judge_now;
+mod judgement_feed;
 mod judgement_state;
+mod notification_ack;
+mod request_withdrawal;
 mod second_challenge;
+mod session_limits;
+mod ws_encoding;
 
 // Reexport
+pub use self::admin_search::AdminSearchConfig;
+pub use self::attestation::AttestationOutcome;
+pub use self::challenge_expiry::{ChallengeExpiryConfig, RenewalOutcome};
+pub use self::challenge_regeneration::{ChallengeRegenerationConfig, RegenerationOutcome};
+pub use self::challenge_token::{ChallengeTokenConfig, ChallengeTokens};
+#[cfg(test)]
+pub use self::completion_estimate::CompletionEstimate;
+pub use self::credential_import::{
+    importable_field, CredentialAttestation, CredentialImportConfig,
+};
+pub use self::display_name_pool::DisplayNameChange;
+pub use self::event_log::{EventFilter, EventPage};
+pub use self::judge_now::JudgeNowConfig;
+pub use self::judgement_feed::JudgementFeedConfig;
 pub use self::judgement_state::{LookupServer, NotifyAccountState, ResponseAccountState};
+pub use self::request_withdrawal::{RequestWithdrawalConfig, WithdrawalNonce, WithdrawnRequest};
 pub use self::second_challenge::VerifyChallenge;
+pub use self::session_limits::SessionLimitsConfig;
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, Message)]
 #[serde(rename_all = "snake_case", tag = "type", content = "message")]
@@ -25,21 +78,117 @@ pub enum JsonResult<T> {
     Err(String),
 }
 
-async fn healthcheck() -> HttpResponse {
-    HttpResponse::Ok().body("OK")
+/// Broadcast to the WS sessions on shutdown, which then close the connection.
+#[derive(Debug, Clone, Message)]
+#[rtype(result = "()")]
+struct ServerShutdown;
+
+/// Lists the notification kinds, so consumers can check which kinds they
+/// might receive.
+async fn notification_kinds() -> HttpResponse {
+    #[derive(Serialize)]
+    struct Kind {
+        kind: &'static str,
+        since: u32,
+    }
+
+    #[derive(Serialize)]
+    struct Kinds {
+        version: u32,
+        kinds: Vec<Kind>,
+    }
+
+    HttpResponse::Ok().json(Kinds {
+        version: NOTIFICATION_VERSION,
+        kinds: NOTIFICATION_KINDS
+            .iter()
+            .map(|(kind, since)| Kind {
+                kind,
+                since: *since,
+            })
+            .collect(),
+    })
 }
 
 pub async fn run_rest_api_server(
     config: NotifierConfig,
     db: Database,
-) -> Result<Addr<LookupServer>> {
+    runtime_info: RuntimeInfo,
+) -> Result<(Addr<LookupServer>, ApiHandle)> {
     let api_address = config.api_address.clone();
+    let limits = SessionLimits::from(config.session_limits);
+    let tokens = config
+        .challenge_tokens
+        .clone()
+        .map(ChallengeTokens::new)
+        .transpose()?;
+    let feed = config
+        .judgement_feed
+        .clone()
+        .map(|feed| JudgementFeed::new(db.read_only(), feed));
+    let search = config
+        .admin_search
+        .clone()
+        .map(|search| AdminSearch::new(db.read_only(), search));
+    let import = config
+        .credential_import
+        .clone()
+        .map(|import| CredentialImport::new(db.clone(), import));
+    let regenerator = match (config.challenge_regeneration, &tokens) {
+        (Some(regeneration), Some(tokens)) => Some(ChallengeRegenerator::new(
+            db.clone(),
+            tokens.clone(),
+            regeneration,
+        )),
+        (Some(_), None) => {
+            return Err(anyhow!(
+                "challenge regeneration requires `challenge_tokens` to be configured"
+            ))
+        }
+        (None, _) => None,
+    };
+    let renewal = config
+        .challenge_expiry
+        .map(|expiry| ChallengeRenewal::new(db.clone(), expiry));
+    let attestor = Attestor::new(db.read_only());
+    let estimator = CompletionEstimator::new(db.read_only());
+    let event_log = EventLog::new(db.read_only());
+    let health = HealthCheck::new(db.clone());
+    let withdrawal = match (config.request_withdrawal, &tokens) {
+        (Some(withdrawal), Some(tokens)) => Some(RequestWithdrawal::new(
+            db.clone(),
+            tokens.clone(),
+            withdrawal,
+        )),
+        (Some(_), None) => {
+            return Err(anyhow!(
+                "request withdrawals require `challenge_tokens` to be configured"
+            ))
+        }
+        (None, _) => None,
+    };
+    let early_judgement = match (config.judge_now, &tokens) {
+        (Some(judge_now), Some(tokens)) => {
+            Some(JudgeNow::new(db.clone(), tokens.clone(), judge_now))
+        }
+        (Some(_), None) => {
+            return Err(anyhow!(
+                "early judgements require `challenge_tokens` to be configured"
+            ))
+        }
+        (None, _) => None,
+    };
 
-    // Add configured actor to the registry.
-    let actor = LookupServer::new(db.clone()).start();
+    // Add configured actor to the registry. Actors that only query the
+    // database use the read-only handle.
+    let actor = LookupServer::new(db.read_only(), estimator.clone()).start();
     SystemRegistry::set(actor.clone());
     SystemRegistry::set(SecondChallengeVerifier::new(db.clone()).start());
-    SystemRegistry::set(DisplayNameChecker::new(db, config.display_name.clone()).start());
+    SystemRegistry::set(NotificationAcknowledger::new(db.clone()).start());
+    SystemRegistry::set(
+        DisplayNameChecker::new(db.read_only(), config.display_name.clone()).start(),
+    );
+    SystemRegistry::set(DisplayNamePool::new(db.read_only()).start());
 
     // Run the WS server.
     let server = HttpServer::new(move || {
@@ -60,8 +209,33 @@ pub async fn run_rest_api_server(
 
         App::new()
             .wrap(cors)
+            .app_data(web::Data::new(limits))
+            .app_data(web::Data::new(tokens.clone()))
+            .app_data(web::Data::new(feed.clone()))
+            .app_data(web::Data::new(search.clone()))
+            .app_data(web::Data::new(import.clone()))
+            .app_data(web::Data::new(regenerator.clone()))
+            .app_data(web::Data::new(renewal.clone()))
+            .app_data(web::Data::new(withdrawal.clone()))
+            .app_data(web::Data::new(early_judgement.clone()))
+            .app_data(web::Data::new(attestor.clone()))
+            .app_data(web::Data::new(estimator.clone()))
+            .app_data(web::Data::new(event_log.clone()))
+            .app_data(web::Data::new(health.clone()))
+            .app_data(web::Data::new(runtime_info.clone()))
             .route("/healthcheck", web::get().to(healthcheck))
+            .route("/healthcheck/live", web::get().to(liveness))
+            .route("/api/version", web::get().to(version))
+            .route("/api/notification_kinds", web::get().to(notification_kinds))
+            .route(FEED_PATH, web::get().to(judgement_feed))
+            .route("/api/events", web::get().to(events))
+            .route("/api/admin/search", web::get().to(admin_search))
+            .route(
+                "/api/admin/import_credentials",
+                web::post().to(import_credentials),
+            )
             .service(web::resource("/api/account_status").to(account_status_server_route))
+            .service(web::resource("/api/display_names").to(display_name_pool_route))
             .route(
                 "/api/verify_second_challenge",
                 web::post().to(verify_second_challenge),
@@ -70,21 +244,117 @@ pub async fn run_rest_api_server(
                 "/api/check_display_name",
                 web::post().to(check_display_name),
             )
+            .route(
+                "/api/challenge_token",
+                web::post().to(request_challenge_token),
+            )
+            .route(
+                "/api/regenerate_challenge",
+                web::post().to(regenerate_challenge),
+            )
+            .route(
+                "/api/request_new_challenge",
+                web::post().to(request_new_challenge),
+            )
+            .route(
+                "/api/withdrawal_nonce",
+                web::post().to(request_withdrawal_nonce),
+            )
+            .route("/api/withdraw_request", web::post().to(withdraw_request))
+            .route("/api/judge_now", web::post().to(judge_now))
+            .route("/api/attest", web::post().to(attest_message))
+            .route(
+                "/api/estimated_completion",
+                web::post().to(estimated_completion),
+            )
+            .route(
+                "/api/acknowledge_notifications",
+                web::post().to(acknowledge_notifications),
+            )
     })
-    .bind(api_address.as_str())?;
+    // Signals are handled by the shutdown coordinator.
+    .disable_signals()
+    .bind(api_address.as_str())?
+    .run();
 
+    let handle = ApiHandle::new(server.handle());
     actix::spawn(async move {
-        let _ = server.run().await;
+        let _ = server.await;
     });
 
-    Ok(actor)
+    Ok((actor, handle))
+}
+
+/// Handle of the API server and the background tasks of the session
+/// notifier.
+pub struct ApiHandle {
+    server: ServerHandle,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl ApiHandle {
+    fn new(server: ServerHandle) -> Self {
+        ApiHandle {
+            server,
+            tasks: vec![],
+        }
+    }
+    pub(crate) fn add_task(&mut self, task: JoinHandle<()>) {
+        self.tasks.push(task);
+    }
+    /// Closes the WS sessions and stops the server, requests which are in
+    /// progress are completed.
+    pub async fn shutdown(self) {
+        Broker::<SystemBroker>::issue_async(ServerShutdown);
+        self.server.stop(true).await;
+
+        for task in self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Build and configuration details, so the versions of multiple running
+/// instances can be told apart.
+async fn version(runtime_info: web::Data<RuntimeInfo>) -> HttpResponse {
+    #[derive(Serialize)]
+    struct Version<'a> {
+        #[serde(flatten)]
+        info: &'a RuntimeInfo,
+        // In seconds.
+        uptime: u64,
+    }
+
+    HttpResponse::Ok().json(Version {
+        info: &runtime_info,
+        uptime: runtime_info.uptime(),
+    })
 }
 
 async fn account_status_server_route(
     req: HttpRequest,
     stream: web::Payload,
+    limits: web::Data<SessionLimits>,
+    tokens: web::Data<Option<ChallengeTokens>>,
+    search: web::Data<Option<AdminSearch>>,
 ) -> std::result::Result<HttpResponse, ActixError> {
-    ws::start(WsAccountStatusSession::default(), &req, stream)
+    let session = WsAccountStatusSession::new(
+        **limits,
+        tokens.as_ref().clone(),
+        search.as_ref().clone(),
+        WsEncoding::negotiate(&req),
+    );
+    ws::WsResponseBuilder::new(session, &req, stream)
+        .frame_size(limits.max_frame_size)
+        .protocols(PROTOCOLS)
+        .start()
+}
+
+async fn display_name_pool_route(
+    req: HttpRequest,
+    stream: web::Payload,
+) -> std::result::Result<HttpResponse, ActixError> {
+    ws::start(WsDisplayNamePoolSession, &req, stream)
 }
 
 #[cfg(test)]
@@ -99,25 +369,73 @@ pub mod tests {
             DisplayNameConfig {
                 enabled: false,
                 limit: 0.85,
+                confusables: None,
+                reserved_names: None,
+                reserved_patterns: None,
             }
         }
     }
 
     #[cfg(test)]
     pub async fn run_test_server(db: Database) -> (TestServer, Addr<LookupServer>) {
-        let actor = LookupServer::new(db.clone()).start();
+        let estimator = CompletionEstimator::new(db.clone());
+        let actor = LookupServer::new(db.clone(), estimator.clone()).start();
 
         let t_actor = actor.clone();
         let server = start(move || {
             // Add configured actor to the registry.
             SystemRegistry::set(t_actor.clone());
             SystemRegistry::set(SecondChallengeVerifier::new(db.clone()).start());
+            SystemRegistry::set(NotificationAcknowledger::new(db.clone()).start());
             SystemRegistry::set(
                 DisplayNameChecker::new(db.clone(), DisplayNameConfig::default()).start(),
             );
+            SystemRegistry::set(DisplayNamePool::new(db.clone()).start());
 
             App::new()
+                .app_data(web::Data::new(SessionLimits::default()))
+                .app_data(web::Data::new(RuntimeInfo::test()))
+                .app_data(web::Data::new(None::<ChallengeTokens>))
+                .app_data(web::Data::new(Some(JudgementFeed::new(
+                    db.clone(),
+                    JudgementFeedConfig {
+                        base_url: "http://localhost".to_string(),
+                        title: None,
+                        limit: None,
+                    },
+                ))))
+                .app_data(web::Data::new(Some(AdminSearch::new(
+                    db.clone(),
+                    AdminSearchConfig {
+                        token: "admin".to_string(),
+                    },
+                ))))
+                .app_data(web::Data::new(Attestor::new(db.clone())))
+                .app_data(web::Data::new(estimator.clone()))
+                .app_data(web::Data::new(EventLog::new(db.clone())))
+                .app_data(web::Data::new(Some(ChallengeRegenerator::new(
+                    db.clone(),
+                    ChallengeTokens::new(ChallengeTokenConfig {
+                        secret: "secret".to_string(),
+                        ttl: None,
+                        verifier_endpoint: "http://localhost".to_string(),
+                    })
+                    .unwrap(),
+                    ChallengeRegenerationConfig {
+                        limit: Some(2),
+                        window: None,
+                    },
+                ))))
+                .app_data(web::Data::new(Some(ChallengeRenewal::new(
+                    db.clone(),
+                    ChallengeExpiryConfig { ttl: Some(3_600) },
+                ))))
+                .route("/api/version", web::get().to(version))
+                .route(FEED_PATH, web::get().to(judgement_feed))
+                .route("/api/events", web::get().to(events))
+                .route("/api/admin/search", web::get().to(admin_search))
                 .service(web::resource("/api/account_status").to(account_status_server_route))
+                .service(web::resource("/api/display_names").to(display_name_pool_route))
                 .route(
                     "/api/verify_second_challenge",
                     web::post().to(verify_second_challenge),
@@ -126,6 +444,23 @@ pub mod tests {
                     "/api/check_display_name",
                     web::post().to(check_display_name),
                 )
+                .route(
+                    "/api/acknowledge_notifications",
+                    web::post().to(acknowledge_notifications),
+                )
+                .route(
+                    "/api/regenerate_challenge",
+                    web::post().to(regenerate_challenge),
+                )
+                .route(
+                    "/api/request_new_challenge",
+                    web::post().to(request_new_challenge),
+                )
+                .route("/api/attest", web::post().to(attest_message))
+                .route(
+                    "/api/estimated_completion",
+                    web::post().to(estimated_completion),
+                )
         });
 
         (server, actor)
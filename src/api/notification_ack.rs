@@ -0,0 +1,67 @@
+use super::JsonResult;
+use crate::database::Database;
+use crate::primitives::IdentityContext;
+use actix::prelude::*;
+use actix_web::{web, HttpResponse};
+
+pub struct NotificationAcknowledger {
+    db: Database,
+}
+
+impl Default for NotificationAcknowledger {
+    fn default() -> Self {
+        panic!("NotificationAcknowledger is not initialized");
+    }
+}
+
+impl NotificationAcknowledger {
+    pub fn new(db: Database) -> Self {
+        NotificationAcknowledger { db }
+    }
+}
+
+impl SystemService for NotificationAcknowledger {}
+impl Supervised for NotificationAcknowledger {}
+
+impl Actor for NotificationAcknowledger {
+    type Context = Context<Self>;
+}
+
+impl Handler<AcknowledgeNotifications> for NotificationAcknowledger {
+    type Result = ResponseActFuture<Self, JsonResult<bool>>;
+
+    fn handle(&mut self, msg: AcknowledgeNotifications, _ctx: &mut Self::Context) -> Self::Result {
+        let db = self.db.clone();
+
+        Box::pin(
+            async move {
+                db.acknowledge_notifications(&msg.context, msg.sequence)
+                    .await
+                    .map(JsonResult::Ok)
+                    .unwrap_or_else(|err| {
+                        error!("Failed to acknowledge notifications: {:?}", err);
+                        JsonResult::Err("Backend error, contact admin".to_string())
+                    })
+            }
+            .into_actor(self),
+        )
+    }
+}
+
+/// Marks the notifications of the identity as read up to (and including) the
+/// given sequence number.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, Message)]
+#[rtype(result = "JsonResult<bool>")]
+pub struct AcknowledgeNotifications {
+    pub context: IdentityContext,
+    pub sequence: u64,
+}
+
+pub async fn acknowledge_notifications(req: web::Json<AcknowledgeNotifications>) -> HttpResponse {
+    HttpResponse::Ok().json(
+        NotificationAcknowledger::from_registry()
+            .send(req.into_inner())
+            .await
+            .unwrap(),
+    )
+}
@@ -0,0 +1,184 @@
+use super::challenge_token::ChallengeTokens;
+use super::JsonResult;
+use crate::database::Database;
+use crate::primitives::{IdentityContext, Timestamp};
+use actix_web::{web, HttpResponse};
+use schemars::JsonSchema;
+
+// In seconds
+const DEFAULT_NONCE_TTL: u64 = 300;
+
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct RequestWithdrawalConfig {
+    // Validity of withdrawal nonces in seconds.
+    pub nonce_ttl: Option<u64>,
+}
+
+/// Single-use nonce which must be signed in order to withdraw a judgement
+/// request.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct WithdrawalNonce {
+    pub context: IdentityContext,
+    pub nonce: String,
+    pub expires_at: Timestamp,
+}
+
+/// A judgement request which was withdrawn by the user. The Watcher is
+/// informed about it whenever it lists the request as pending.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct WithdrawnRequest {
+    pub context: IdentityContext,
+    pub timestamp: Timestamp,
+}
+
+/// The message which must be signed in order to withdraw a judgement request.
+pub fn withdrawal_message(context: &IdentityContext, nonce: &str) -> String {
+    format!(
+        "registrar-withdraw-request:{}:{}:{}",
+        context.chain.as_str(),
+        context.address.as_str(),
+        nonce
+    )
+}
+
+/// Lets users withdraw their pending judgement request by signing a nonce
+/// with the account key.
+#[derive(Debug, Clone)]
+pub struct RequestWithdrawal {
+    db: Database,
+    tokens: ChallengeTokens,
+    nonce_ttl: u64,
+}
+
+impl RequestWithdrawal {
+    pub fn new(db: Database, tokens: ChallengeTokens, config: RequestWithdrawalConfig) -> Self {
+        RequestWithdrawal {
+            db,
+            tokens,
+            nonce_ttl: config.nonce_ttl.unwrap_or(DEFAULT_NONCE_TTL),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct WithdrawalNonceRequest {
+    pub context: IdentityContext,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct WithdrawRequest {
+    pub context: IdentityContext,
+    pub nonce: String,
+    // Signature of the message returned by `withdrawal_message`.
+    pub signature: String,
+}
+
+pub async fn request_withdrawal_nonce(
+    req: web::Json<WithdrawalNonceRequest>,
+    withdrawal: web::Data<Option<RequestWithdrawal>>,
+) -> HttpResponse {
+    let withdrawal = match withdrawal.as_ref() {
+        Some(withdrawal) => withdrawal,
+        None => {
+            return HttpResponse::Ok().json(JsonResult::<WithdrawalNonce>::Err(
+                "Withdrawals are not enabled".to_string(),
+            ))
+        }
+    };
+
+    let nonce = WithdrawalNonce {
+        context: req.into_inner().context,
+        nonce: {
+            use rand::{thread_rng, Rng};
+
+            let random: [u8; 16] = thread_rng().gen();
+            hex::encode(random)
+        },
+        expires_at: Timestamp::with_offset(withdrawal.nonce_ttl),
+    };
+
+    let result = match withdrawal.db.insert_withdrawal_nonce(&nonce).await {
+        Ok(()) => JsonResult::Ok(nonce),
+        Err(err) => {
+            error!("Failed to insert withdrawal nonce: {:?}", err);
+            JsonResult::Err("Backend error, contact admin".to_string())
+        }
+    };
+
+    HttpResponse::Ok().json(result)
+}
+
+pub async fn withdraw_request(
+    req: web::Json<WithdrawRequest>,
+    withdrawal: web::Data<Option<RequestWithdrawal>>,
+) -> HttpResponse {
+    let withdrawal = match withdrawal.as_ref() {
+        Some(withdrawal) => withdrawal,
+        None => {
+            return HttpResponse::Ok().json(JsonResult::<bool>::Err(
+                "Withdrawals are not enabled".to_string(),
+            ))
+        }
+    };
+
+    let req = req.into_inner();
+
+    let result = async {
+        let message = withdrawal_message(&req.context, &req.nonce);
+        if !withdrawal
+            .tokens
+            .verify_signature(&req.context, message, &req.signature)
+            .await?
+        {
+            return Ok(JsonResult::Err("Invalid signature".to_string()));
+        }
+
+        // Nonces can only be used once.
+        if !withdrawal
+            .db
+            .consume_withdrawal_nonce(&req.context, &req.nonce)
+            .await?
+        {
+            return Ok(JsonResult::Err("Invalid or expired nonce".to_string()));
+        }
+
+        if !withdrawal
+            .db
+            .withdraw_judgement_request(&req.context)
+            .await?
+        {
+            return Ok(JsonResult::Err(
+                "No pending judgement request found".to_string(),
+            ));
+        }
+
+        info!("Judgement request of {:?} was withdrawn", req.context);
+        crate::Result::Ok(JsonResult::Ok(true))
+    }
+    .await
+    .unwrap_or_else(|err| {
+        error!(
+            "Failed to withdraw judgement request of {:?}: {:?}",
+            req.context, err
+        );
+        JsonResult::Err("Backend error, contact admin".to_string())
+    });
+
+    HttpResponse::Ok().json(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn withdrawal_message_format() {
+        assert_eq!(
+            withdrawal_message(&IdentityContext::alice(), "abcd"),
+            "registrar-withdraw-request:polkadot:1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP:abcd"
+        );
+    }
+}
@@ -0,0 +1,428 @@
+use crate::primitives::{ChainName, IdentityContext, JudgementStatus};
+use schemars::JsonSchema;
+use std::time::{Duration, Instant};
+
+// A subscription (identity context and token) is less than 200 bytes, so this
+// leaves enough space for a batch of `DEFAULT_MAX_SUBSCRIPTIONS`.
+const DEFAULT_MAX_FRAME_SIZE: usize = 2_048;
+const DEFAULT_MAX_MESSAGES_PER_SECOND: u32 = 10;
+const DEFAULT_MAX_INVALID_MESSAGES: u32 = 5;
+const DEFAULT_MAX_SUBSCRIPTIONS: usize = 10;
+// SS58 encoded addresses are at most 48 characters long.
+const MAX_ADDRESS_LEN: usize = 64;
+
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SessionLimitsConfig {
+    // Maximum size of an inbound frame in bytes.
+    pub max_frame_size: Option<usize>,
+    // Maximum number of inbound messages per second.
+    pub max_messages_per_second: Option<u32>,
+    // Number of invalid messages after which the session is closed.
+    pub max_invalid_messages: Option<u32>,
+    // Maximum number of identities in a single subscription message.
+    pub max_subscriptions: Option<usize>,
+}
+
+/// Limits applied to each WS session, protecting the notifier instance from
+/// misbehaving clients.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct SessionLimits {
+    pub max_frame_size: usize,
+    pub max_messages_per_second: u32,
+    pub max_invalid_messages: u32,
+    pub max_subscriptions: usize,
+}
+
+impl Default for SessionLimits {
+    fn default() -> Self {
+        SessionLimits {
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            max_messages_per_second: DEFAULT_MAX_MESSAGES_PER_SECOND,
+            max_invalid_messages: DEFAULT_MAX_INVALID_MESSAGES,
+            max_subscriptions: DEFAULT_MAX_SUBSCRIPTIONS,
+        }
+    }
+}
+
+impl From<Option<SessionLimitsConfig>> for SessionLimits {
+    fn from(config: Option<SessionLimitsConfig>) -> Self {
+        let default = SessionLimits::default();
+        let config = match config {
+            Some(config) => config,
+            None => return default,
+        };
+
+        SessionLimits {
+            max_frame_size: config.max_frame_size.unwrap_or(default.max_frame_size),
+            max_messages_per_second: config
+                .max_messages_per_second
+                .unwrap_or(default.max_messages_per_second),
+            max_invalid_messages: config
+                .max_invalid_messages
+                .unwrap_or(default.max_invalid_messages),
+            max_subscriptions: config
+                .max_subscriptions
+                .unwrap_or(default.max_subscriptions),
+        }
+    }
+}
+
+/// Why a session was closed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Violation {
+    RateExceeded,
+    TooManyInvalidMessages,
+}
+
+impl Violation {
+    /// Label of the violation, as used in metrics.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Violation::RateExceeded => "rate_exceeded",
+            Violation::TooManyInvalidMessages => "too_many_invalid_messages",
+        }
+    }
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::RateExceeded => write!(f, "Message rate limit exceeded"),
+            Violation::TooManyInvalidMessages => write!(f, "Too many invalid messages"),
+        }
+    }
+}
+
+/// Tracks the inbound messages of a single session.
+#[derive(Debug, Clone)]
+pub struct SessionGuard {
+    limits: SessionLimits,
+    window_start: Instant,
+    window_count: u32,
+    invalid_count: u32,
+}
+
+impl SessionGuard {
+    pub fn new(limits: SessionLimits) -> Self {
+        SessionGuard {
+            limits,
+            window_start: Instant::now(),
+            window_count: 0,
+            invalid_count: 0,
+        }
+    }
+    pub fn limits(&self) -> &SessionLimits {
+        &self.limits
+    }
+    /// Registers an inbound message, returning an error if the client sends
+    /// more messages per second than allowed.
+    pub fn on_message(&mut self, now: Instant) -> Result<(), Violation> {
+        if now.duration_since(self.window_start) >= Duration::from_secs(1) {
+            self.window_start = now;
+            self.window_count = 0;
+        }
+
+        self.window_count += 1;
+        if self.window_count > self.limits.max_messages_per_second {
+            return Err(Violation::RateExceeded);
+        }
+
+        Ok(())
+    }
+    /// Registers an invalid message, returning an error if the client sent
+    /// too many of those.
+    pub fn on_invalid(&mut self) -> Result<(), Violation> {
+        self.invalid_count += 1;
+        if self.invalid_count >= self.limits.max_invalid_messages {
+            return Err(Violation::TooManyInvalidMessages);
+        }
+
+        Ok(())
+    }
+}
+
+/// A subscription request, optionally carrying a challenge token which
+/// reveals the expected challenge values.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+pub struct Subscription {
+    #[serde(flatten)]
+    pub context: IdentityContext,
+    pub token: Option<String>,
+}
+
+/// A subscription to multiple identities, answered with a combined snapshot.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Deserialize)]
+pub struct BatchSubscription {
+    #[serde(default)]
+    pub identities: Vec<Subscription>,
+    // All pending identities of the chains, requires `admin_token`.
+    #[serde(default)]
+    pub chains: Vec<ChainName>,
+    // Limits the identities of the chains to those with one of the statuses.
+    // All statuses are included if empty.
+    #[serde(default)]
+    pub status: Vec<JudgementStatus>,
+    pub admin_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SubscriptionRequest {
+    Single(Subscription),
+    Batch(BatchSubscription),
+}
+
+/// Parses and validates a subscription request, returning a descriptive error
+/// which can be passed on to the client.
+pub fn parse_subscription(msg: &[u8]) -> Result<Subscription, String> {
+    let subscription = serde_json::from_slice::<Subscription>(msg)
+        .map_err(|err| format!("Invalid message type: {}", err))?;
+
+    validate_address(&subscription.context)?;
+
+    Ok(subscription)
+}
+
+/// Parses either a single subscription or a batch of subscriptions, which is
+/// recognized by the `identities` or `chains` key.
+pub fn parse_subscription_request(
+    msg: &[u8],
+    max_subscriptions: usize,
+) -> Result<SubscriptionRequest, String> {
+    let value = serde_json::from_slice::<serde_json::Value>(msg)
+        .map_err(|err| format!("Invalid message type: {}", err))?;
+
+    if value.get("identities").is_none() && value.get("chains").is_none() {
+        return parse_subscription(msg).map(SubscriptionRequest::Single);
+    }
+
+    let batch = serde_json::from_value::<BatchSubscription>(value)
+        .map_err(|err| format!("Invalid message type: {}", err))?;
+
+    if batch.identities.is_empty() && batch.chains.is_empty() {
+        return Err("Invalid subscription: no identities specified".to_string());
+    }
+
+    if batch.identities.len() > max_subscriptions {
+        return Err(format!(
+            "Invalid subscription: at most {} identities per message",
+            max_subscriptions
+        ));
+    }
+
+    if !batch.chains.is_empty() && batch.admin_token.is_none() {
+        return Err("Invalid subscription: chains require an admin token".to_string());
+    }
+
+    if batch.chains.is_empty() && !batch.status.is_empty() {
+        return Err("Invalid subscription: status filters require chains".to_string());
+    }
+
+    for subscription in &batch.identities {
+        validate_address(&subscription.context)?;
+    }
+
+    Ok(SubscriptionRequest::Batch(batch))
+}
+
+fn validate_address(context: &IdentityContext) -> Result<(), String> {
+    let address = context.address.as_str();
+    if address.is_empty() || address.len() > MAX_ADDRESS_LEN {
+        return Err(format!(
+            "Invalid address: must be between 1 and {} characters long",
+            MAX_ADDRESS_LEN
+        ));
+    }
+
+    if !address.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err("Invalid address: must be SS58 encoded".to_string());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_from_config() {
+        assert_eq!(SessionLimits::from(None), SessionLimits::default());
+
+        let limits = SessionLimits::from(Some(SessionLimitsConfig {
+            max_frame_size: Some(256),
+            max_messages_per_second: None,
+            max_invalid_messages: Some(1),
+            max_subscriptions: None,
+        }));
+
+        assert_eq!(
+            limits,
+            SessionLimits {
+                max_frame_size: 256,
+                max_messages_per_second: DEFAULT_MAX_MESSAGES_PER_SECOND,
+                max_invalid_messages: 1,
+                max_subscriptions: DEFAULT_MAX_SUBSCRIPTIONS,
+            }
+        );
+    }
+
+    #[test]
+    fn subscription_validation() {
+        let subscription = parse_subscription(
+            br#"{"address":"1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP","chain":"polkadot"}"#,
+        )
+        .unwrap();
+        assert_eq!(subscription.context, IdentityContext::alice());
+        assert!(subscription.token.is_none());
+
+        let subscription = parse_subscription(
+            br#"{"address":"1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP","chain":"polkadot","token":"1060.ab"}"#,
+        )
+        .unwrap();
+        assert_eq!(subscription.context, IdentityContext::alice());
+        assert_eq!(subscription.token.as_deref(), Some("1060.ab"));
+
+        // Malformed JSON.
+        let err = parse_subscription(b"{").unwrap_err();
+        assert!(err.starts_with("Invalid message type: EOF"));
+
+        // Unknown chain.
+        let err = parse_subscription(br#"{"address":"1a2Y","chain":"rococo"}"#).unwrap_err();
+        assert!(err.contains("unknown variant `rococo`"));
+
+        // Missing field.
+        let err = parse_subscription(br#"{"address":"1a2Y"}"#).unwrap_err();
+        assert!(err.contains("missing field `chain`"));
+
+        // Invalid addresses.
+        let err = parse_subscription(br#"{"address":"","chain":"polkadot"}"#).unwrap_err();
+        assert!(err.starts_with("Invalid address"));
+
+        let long = format!(r#"{{"address":"{}","chain":"polkadot"}}"#, "1".repeat(65));
+        assert!(parse_subscription(long.as_bytes()).is_err());
+
+        let err =
+            parse_subscription(br#"{"address":"1a2Y; DROP","chain":"polkadot"}"#).unwrap_err();
+        assert_eq!(err, "Invalid address: must be SS58 encoded");
+    }
+
+    #[test]
+    fn batch_subscription_validation() {
+        let alice =
+            r#"{"address":"1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP","chain":"polkadot"}"#;
+        let bob = r#"{"address":"1b3NhsSEqWSQwS6nPGKgCrSjv9Kp13CnhraLV5Coyd8ooXB","chain":"polkadot","token":"1060.ab"}"#;
+
+        // Single subscriptions are still accepted.
+        assert_eq!(
+            parse_subscription_request(alice.as_bytes(), 2).unwrap(),
+            SubscriptionRequest::Single(parse_subscription(alice.as_bytes()).unwrap())
+        );
+
+        let msg = format!(r#"{{"identities":[{},{}]}}"#, alice, bob);
+        match parse_subscription_request(msg.as_bytes(), 2).unwrap() {
+            SubscriptionRequest::Batch(batch) => {
+                assert_eq!(batch.identities.len(), 2);
+                assert_eq!(batch.identities[0].context, IdentityContext::alice());
+                assert_eq!(batch.identities[1].context, IdentityContext::bob());
+                assert_eq!(batch.identities[1].token.as_deref(), Some("1060.ab"));
+                assert!(batch.chains.is_empty());
+            }
+            other => panic!("unexpected request: {:?}", other),
+        }
+
+        // Too many identities.
+        let err = parse_subscription_request(msg.as_bytes(), 1).unwrap_err();
+        assert_eq!(
+            err,
+            "Invalid subscription: at most 1 identities per message"
+        );
+
+        // Empty batch.
+        let err = parse_subscription_request(br#"{"identities":[]}"#, 2).unwrap_err();
+        assert!(err.contains("no identities"));
+
+        // Invalid addresses are rejected.
+        let msg = format!(
+            r#"{{"identities":[{},{{"address":"1a2Y; DROP","chain":"polkadot"}}]}}"#,
+            alice
+        );
+        let err = parse_subscription_request(msg.as_bytes(), 2).unwrap_err();
+        assert_eq!(err, "Invalid address: must be SS58 encoded");
+
+        // Chain wildcards require an admin token.
+        let err = parse_subscription_request(br#"{"chains":["kusama"]}"#, 2).unwrap_err();
+        assert!(err.contains("admin token"));
+
+        match parse_subscription_request(br#"{"chains":["kusama"],"admin_token":"admin"}"#, 2)
+            .unwrap()
+        {
+            SubscriptionRequest::Batch(batch) => {
+                assert_eq!(batch.chains, vec![ChainName::Kusama]);
+                assert_eq!(batch.admin_token.as_deref(), Some("admin"));
+                assert!(batch.status.is_empty());
+            }
+            other => panic!("unexpected request: {:?}", other),
+        }
+
+        let msg =
+            br#"{"chains":["kusama"],"status":["verified","withheld"],"admin_token":"admin"}"#;
+        match parse_subscription_request(msg, 2).unwrap() {
+            SubscriptionRequest::Batch(batch) => {
+                assert_eq!(
+                    batch.status,
+                    vec![JudgementStatus::Verified, JudgementStatus::Withheld]
+                );
+            }
+            other => panic!("unexpected request: {:?}", other),
+        }
+
+        // Status filters only apply to chains.
+        let msg = format!(r#"{{"identities":[{}],"status":["judged"]}}"#, alice);
+        let err = parse_subscription_request(msg.as_bytes(), 2).unwrap_err();
+        assert!(err.contains("require chains"));
+
+        let err = parse_subscription_request(
+            br#"{"chains":["kusama"],"status":["unknown"],"admin_token":"admin"}"#,
+            2,
+        )
+        .unwrap_err();
+        assert!(err.starts_with("Invalid message type"));
+    }
+
+    #[test]
+    fn guard_message_rate() {
+        let mut guard = SessionGuard::new(SessionLimits {
+            max_messages_per_second: 3,
+            ..Default::default()
+        });
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert!(guard.on_message(start).is_ok());
+        }
+        assert_eq!(
+            guard.on_message(start + Duration::from_millis(500)),
+            Err(Violation::RateExceeded)
+        );
+
+        // A new window starts after one second.
+        let next = start + Duration::from_secs(1);
+        for _ in 0..3 {
+            assert!(guard.on_message(next).is_ok());
+        }
+        assert_eq!(guard.on_message(next), Err(Violation::RateExceeded));
+    }
+
+    #[test]
+    fn guard_invalid_messages() {
+        let mut guard = SessionGuard::new(SessionLimits {
+            max_invalid_messages: 3,
+            ..Default::default()
+        });
+
+        assert!(guard.on_invalid().is_ok());
+        assert!(guard.on_invalid().is_ok());
+        assert_eq!(guard.on_invalid(), Err(Violation::TooManyInvalidMessages));
+    }
+}
@@ -0,0 +1,98 @@
+use crate::Result;
+use actix_web::{http::header, HttpRequest};
+use serde::Serialize;
+
+pub const JSON_PROTOCOL: &str = "json";
+pub const MSGPACK_PROTOCOL: &str = "msgpack";
+// Subprotocols offered to clients, in the order of the server's preference.
+pub const PROTOCOLS: &[&str] = &[JSON_PROTOCOL, MSGPACK_PROTOCOL];
+
+/// Encoding of the frames sent to a WS client. Requests of the client are
+/// always JSON text frames.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum WsEncoding {
+    Json,
+    MessagePack,
+}
+
+pub enum WsFrame {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl WsEncoding {
+    /// Selects the first subprotocol of the `Sec-WebSocket-Protocol` header
+    /// which is supported, as done by the handshake. Defaults to JSON.
+    pub fn negotiate(req: &HttpRequest) -> Self {
+        req.headers()
+            .get(header::SEC_WEBSOCKET_PROTOCOL)
+            .and_then(|protocols| protocols.to_str().ok())
+            .map(Self::from_protocols)
+            .unwrap_or(WsEncoding::Json)
+    }
+    fn from_protocols(protocols: &str) -> Self {
+        let selected = protocols
+            .split(',')
+            .map(|protocol| protocol.trim())
+            .find(|protocol| PROTOCOLS.contains(protocol));
+
+        match selected {
+            Some(MSGPACK_PROTOCOL) => WsEncoding::MessagePack,
+            _ => WsEncoding::Json,
+        }
+    }
+    pub fn encode<T: Serialize>(&self, msg: &T) -> Result<WsFrame> {
+        Ok(match self {
+            WsEncoding::Json => WsFrame::Text(serde_json::to_string(msg)?),
+            // Structs are encoded as maps, so the keys match the JSON
+            // representation.
+            WsEncoding::MessagePack => WsFrame::Binary(rmp_serde::to_vec_named(msg)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::JsonResult;
+
+    #[test]
+    fn negotiate_encoding() {
+        assert_eq!(
+            WsEncoding::from_protocols("msgpack"),
+            WsEncoding::MessagePack
+        );
+        assert_eq!(
+            WsEncoding::from_protocols("unknown, msgpack, json"),
+            WsEncoding::MessagePack
+        );
+        assert_eq!(
+            WsEncoding::from_protocols("json, msgpack"),
+            WsEncoding::Json
+        );
+        assert_eq!(WsEncoding::from_protocols("cbor"), WsEncoding::Json);
+    }
+
+    #[test]
+    fn encode_frames() {
+        let msg = JsonResult::<()>::Err("Invalid message".to_string());
+
+        match WsEncoding::Json.encode(&msg).unwrap() {
+            WsFrame::Text(text) => {
+                assert_eq!(text, r#"{"type":"err","message":"Invalid message"}"#)
+            }
+            WsFrame::Binary(_) => panic!("expected a text frame"),
+        }
+
+        match WsEncoding::MessagePack.encode(&msg).unwrap() {
+            WsFrame::Binary(bytes) => {
+                let decoded: JsonResult<()> = rmp_serde::from_slice(&bytes).unwrap();
+                assert_eq!(decoded, msg);
+
+                let value: serde_json::Value = rmp_serde::from_slice(&bytes).unwrap();
+                assert_eq!(value, serde_json::to_value(&msg).unwrap());
+            }
+            WsFrame::Text(_) => panic!("expected a binary frame"),
+        }
+    }
+}
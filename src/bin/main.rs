@@ -1,7 +1,21 @@
-use system::{run, Result};
+use system::{
+    print_config_schema, run, simulate_display_name_limit, write_transparency_report, Result,
+};
 
 #[actix::main]
 async fn main() -> Result<()> {
-    run().await?;
-    unreachable!()
+    // E.g. for editor validation or CI checks of deployment configs.
+    if std::env::args().nth(1).as_deref() == Some("config-schema") {
+        return print_config_schema();
+    }
+
+    if std::env::args().nth(1).as_deref() == Some("transparency-report") {
+        return write_transparency_report(std::env::args().nth(2).as_deref()).await;
+    }
+
+    if std::env::args().nth(1).as_deref() == Some("simulate-display-name-limit") {
+        return simulate_display_name_limit(std::env::args().nth(2).as_deref()).await;
+    }
+
+    run().await
 }
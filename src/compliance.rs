@@ -0,0 +1,155 @@
+use crate::primitives::{IdentityContext, Timestamp};
+use crate::protocol::AccountType;
+use crate::Result;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use schemars::JsonSchema;
+use std::time::Duration;
+
+// In seconds
+const DEFAULT_TIMEOUT: u64 = 10;
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ComplianceConfig {
+    // Endpoint to which new judgement requests are posted.
+    pub endpoint: String,
+    // If enabled, challenges are only issued once the endpoint allowed (or
+    // flagged) the request. Otherwise the endpoint is only informed.
+    pub await_decision: bool,
+    // Request timeout in seconds.
+    pub timeout: Option<u64>,
+}
+
+/// The payload posted to the compliance endpoint. Only the context and the
+/// types of the specified fields are shared, not the field values.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ComplianceRequest {
+    pub context: IdentityContext,
+    pub fields: Vec<AccountType>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComplianceDecision {
+    Allow,
+    Deny,
+    // Proceed with the verification, but the request requires attention.
+    Flag,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+struct ComplianceResponse {
+    decision: ComplianceDecision,
+}
+
+/// The decision on a judgement request, stored for auditing and so that
+/// requests which are repeatedly sent by the Watcher are only posted once.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ComplianceRecord {
+    pub context: IdentityContext,
+    pub decision: ComplianceDecision,
+    pub timestamp: Timestamp,
+}
+
+#[derive(Debug, Clone)]
+pub struct ComplianceClient {
+    client: Client,
+    config: ComplianceConfig,
+}
+
+impl ComplianceClient {
+    pub fn new(config: ComplianceConfig) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(
+                config.timeout.unwrap_or(DEFAULT_TIMEOUT),
+            ))
+            .build()?;
+
+        Ok(ComplianceClient { client, config })
+    }
+    /// Submits a new judgement request to the compliance endpoint. If the
+    /// client does not wait for decisions, the request is posted in the
+    /// background and `Allow` is returned immediately. The caller stores the
+    /// decision, see `Database::fetch_compliance_decision`.
+    pub async fn submit(&self, request: ComplianceRequest) -> Result<ComplianceDecision> {
+        if !self.config.await_decision {
+            let client = self.clone();
+            actix::spawn(async move {
+                if let Err(err) = client.post(&request).await {
+                    error!(
+                        "Failed to submit judgement request of {:?} to compliance endpoint: {:?}",
+                        request.context, err
+                    );
+                }
+            });
+
+            return Ok(ComplianceDecision::Allow);
+        }
+
+        self.post(&request).await
+    }
+    async fn post(&self, request: &ComplianceRequest) -> Result<ComplianceDecision> {
+        let resp = self
+            .client
+            .post(&self.config.endpoint)
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(request)?)
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            return Err(anyhow!(
+                "compliance endpoint returned status code {}",
+                resp.status()
+            ));
+        }
+
+        // The response is only relevant if the client waits for decisions.
+        if !self.config.await_decision {
+            return Ok(ComplianceDecision::Allow);
+        }
+
+        let body = resp.bytes().await?;
+        Ok(serde_json::from_slice::<ComplianceResponse>(&body)?.decision)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compliance_request_payload() {
+        let request = ComplianceRequest {
+            context: IdentityContext::alice(),
+            fields: vec![AccountType::DisplayName, AccountType::Email],
+        };
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "context": {
+                    "address": "1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP",
+                    "chain": "polkadot",
+                },
+                "fields": ["display_name", "email"],
+            })
+        );
+    }
+
+    #[test]
+    fn compliance_response_decision() {
+        for (raw, decision) in &[
+            (r#"{"decision":"allow"}"#, ComplianceDecision::Allow),
+            (r#"{"decision":"deny"}"#, ComplianceDecision::Deny),
+            (r#"{"decision":"flag"}"#, ComplianceDecision::Flag),
+        ] {
+            let resp: ComplianceResponse = serde_json::from_str(raw).unwrap();
+            assert_eq!(resp.decision, *decision);
+        }
+
+        assert!(serde_json::from_str::<ComplianceResponse>(r#"{"decision":"maybe"}"#).is_err());
+    }
+}
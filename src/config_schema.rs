@@ -0,0 +1,88 @@
+use crate::{Config, Result};
+use jsonschema::JSONSchema;
+use schemars::schema_for;
+use serde_json::Value;
+
+// Increased whenever the structure of the config file changes.
+pub const SCHEMA_VERSION: u32 = 25;
+
+/// JSON schema of the config file, generated from the config types.
+pub fn config_schema() -> Value {
+    let mut schema =
+        serde_json::to_value(schema_for!(Config)).expect("Failed to serialize config schema");
+
+    if let Value::Object(schema) = &mut schema {
+        schema.insert("x-schema-version".to_string(), SCHEMA_VERSION.into());
+    }
+
+    schema
+}
+
+/// Checks the config file against the schema, returning all violations.
+pub fn validate_config(content: &str) -> Result<()> {
+    let config: Value = serde_yaml::from_str(content)
+        .map_err(|err| anyhow!("Failed to parse config: {:?}", err))?;
+
+    let schema = config_schema();
+    let compiled = JSONSchema::compile(&schema)
+        .map_err(|err| anyhow!("Failed to compile config schema: {}", err))?;
+
+    if let Err(errors) = compiled.validate(&config) {
+        let violations: Vec<String> = errors
+            .map(|err| format!("'{}': {}", err.instance_path, err))
+            .collect();
+
+        return Err(anyhow!(
+            "Config does not match schema version {}:\n{}",
+            SCHEMA_VERSION,
+            violations.join("\n")
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+log_level: info
+db:
+  uri: mongodb://localhost:27017/
+  name: registrar_db
+instance:
+  role: session_notifier
+  config:
+    notifier:
+      api_address: 0.0.0.0:8080
+      cors_allow_origin:
+        - "*"
+      display_name:
+        enabled: true
+        limit: 0.85
+      notification_window: null
+"#;
+
+    #[test]
+    fn valid_config() {
+        validate_config(CONFIG).unwrap();
+        assert_eq!(config_schema()["x-schema-version"], SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn invalid_config() {
+        let config = CONFIG
+            .replace("name: registrar_db", "name: 1")
+            .replace("log_level: info", "log_level: verbose");
+
+        let err = validate_config(&config).unwrap_err().to_string();
+        assert!(err.contains("'/db/name'"));
+        assert!(err.contains("'/log_level'"));
+
+        // Violations within the instance are reported for the whole role.
+        let config = CONFIG.replace("limit: 0.85", "limit: high");
+        let err = validate_config(&config).unwrap_err().to_string();
+        assert!(err.contains("'/instance'"));
+    }
+}
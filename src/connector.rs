@@ -1,7 +1,19 @@
+use crate::compliance::{ComplianceClient, ComplianceDecision, ComplianceRequest};
 use crate::display_name::DisplayNameVerifier;
+use crate::display_name_normalization::normalize_display_name;
+use crate::identity_chain::IdentityChain;
+use crate::identity_limits::IdentityLimits;
+use crate::metrics;
 use crate::primitives::{
-    ChainAddress, ChainName, IdentityContext, IdentityFieldValue, JudgementState, Timestamp,
+    ChainAddress, ChainName, ChallengePool, DisplayName, IdentityContext, JudgementState, Timestamp,
 };
+use crate::protocol::{
+    try_decode_hex, AccountType, AckResponse, BlockedResponse, DisplayNameEntryRaw, EventType,
+    Judgement, JudgementRequest, JudgementResponse, ManualReviewResponse, RejectedResponse,
+    ResponseMessage, WithdrawnResponse,
+};
+use crate::request_queue::{RequestQueue, RequestQueueConfig, RECONNECT_DELAY};
+use crate::retry::{Backoff, RetryConfig};
 use crate::{Database, DisplayNameConfig, Result, WatcherConfig};
 use actix::io::SinkWrite;
 use actix::io::WriteHandler;
@@ -13,17 +25,33 @@ use awc::{
     BoxedSocket, Client,
 };
 use futures::stream::{SplitSink, StreamExt};
-use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::Duration;
+use rand::{thread_rng, Rng};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc::{self, UnboundedSender};
 use tokio::sync::RwLock;
 use tokio::time::sleep;
 use tracing::Instrument;
 
 // In seconds
-const HEARTBEAT_INTERVAL: u64 = 60;
-const RECONNECTION_TIMEOUT: u64 = 10;
+const HEARTBEAT_INTERVAL: u64 = 5;
+// The connection is considered stale if the Watcher did not send anything
+// (including pongs) within this time.
+const HEARTBEAT_TIMEOUT: u64 = 20;
+
+// Unacknowledged submissions are retried after this time.
+const SUBMISSION_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_IN_FLIGHT_SUBMISSIONS: usize = 100;
+const DEFAULT_SYNC_CONCURRENCY: usize = 16;
+// Maximum number of new identities inserted with a single write.
+const BULK_INSERT_SIZE: usize = 500;
+// Challenges generated ahead of time, enough for a full batch of new
+// identities with several challenged fields each.
+const CHALLENGE_POOL_SIZE: usize = 4 * BULK_INSERT_SIZE;
+// In seconds
+const CHALLENGE_POOL_INTERVAL: u64 = 5;
 
 #[cfg(not(test))]
 const PENDING_JUDGEMENTS_INTERVAL: u64 = 120;
@@ -43,10 +71,25 @@ pub async fn run_connector(
     db: Database,
     watchers: Vec<WatcherConfig>,
     dn_config: DisplayNameConfig,
-) -> Result<()> {
+    compliance: Option<ComplianceClient>,
+    retry: RetryConfig,
+) -> Result<ConnectorHandle> {
+    let handle = ConnectorHandle::default();
     if watchers.is_empty() {
         warn!("No watcher is configured. Cannot process any requests or issue judgments");
-        return Ok(());
+        return Ok(handle);
+    }
+
+    // Each network is served by exactly one Watcher, otherwise judgements
+    // would be submitted twice.
+    let mut networks = HashSet::new();
+    for config in &watchers {
+        if !networks.insert(config.network) {
+            return Err(anyhow!(
+                "multiple watchers are configured for network {}",
+                config.network.as_str()
+            ));
+        }
     }
 
     for config in watchers {
@@ -60,9 +103,22 @@ pub async fn run_connector(
 
         async {
             // Start Connector.
+            let identity_chain = IdentityChain::new(config.network, config.identity_chain)?;
             let dn_verifier = DisplayNameVerifier::new(db.clone(), dn_config.clone());
-            let conn =
-                Connector::start(config.endpoint, config.network, db.clone(), dn_verifier).await?;
+            let conn = Connector::start(
+                config.endpoint,
+                config.network,
+                db.clone(),
+                dn_verifier,
+                compliance.clone(),
+                IdentityLimits::from(config.identity_limits),
+                identity_chain,
+                config.queue,
+                config.sync_concurrency.unwrap_or(DEFAULT_SYNC_CONCURRENCY),
+                retry.clone(),
+                handle.clone(),
+            )
+            .await?;
 
             info!("Connection initiated");
             info!("Sending pending judgements request to Watcher");
@@ -74,98 +130,63 @@ pub async fn run_connector(
         .await?;
     }
 
-    Ok(())
-}
-
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
-pub struct ResponseMessage<T> {
-    pub event: EventType,
-    pub data: T,
-}
-
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
-pub enum EventType {
-    #[serde(rename = "ack")]
-    Ack,
-    #[serde(rename = "error")]
-    Error,
-    #[serde(rename = "newJudgementRequest")]
-    NewJudgementRequest,
-    #[serde(rename = "judgementResult")]
-    JudgementResult,
-    #[serde(rename = "pendingJudgementsRequest")]
-    PendingJudgementsRequest,
-    #[serde(rename = "pendingJudgementsResponse")]
-    PendingJudgementsResponse,
-    #[serde(rename = "displayNamesRequest")]
-    DisplayNamesRequest,
-    #[serde(rename = "displayNamesResponse")]
-    DisplayNamesResponse,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct JudgementResponse {
-    pub address: ChainAddress,
-    pub judgement: Judgement,
-    pub verified: Vec<VerifiedEntry>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct VerifiedEntry {
-    #[serde(rename = "accountTy")]
-    pub account_ty: AccountType,
-    pub value: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AckResponse {
-    result: String,
-    address: Option<ChainAddress>,
+    Ok(handle)
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
-pub enum Judgement {
-    #[serde(rename = "reasonable")]
-    Reasonable,
-    #[serde(rename = "erroneous")]
-    Erroneous,
+/// Handle of the Connectors started by `run_connector`. Connectors which
+/// reconnect to the Watcher replace their previous address.
+#[derive(Clone, Default)]
+pub struct ConnectorHandle {
+    // The current Connector of each Watcher endpoint.
+    connectors: Arc<Mutex<HashMap<String, Addr<Connector>>>>,
+    stopping: Arc<AtomicBool>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct JudgementRequest {
-    pub address: ChainAddress,
-    pub accounts: HashMap<AccountType, String>,
+impl ConnectorHandle {
+    fn register(&self, endpoint: &str, addr: Addr<Connector>) {
+        self.connectors
+            .lock()
+            .unwrap()
+            .insert(endpoint.to_string(), addr);
+    }
+    fn is_stopping(&self) -> bool {
+        self.stopping.load(Ordering::SeqCst)
+    }
+    /// Closes the connections to the Watchers. The Connectors do not
+    /// reconnect afterwards.
+    pub async fn shutdown(self) {
+        self.stopping.store(true, Ordering::SeqCst);
+
+        let connectors: Vec<Addr<Connector>> = self
+            .connectors
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_, addr)| addr)
+            .collect();
+
+        for addr in connectors {
+            let _ = addr.send(Disconnect).await;
+        }
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct DisplayNameEntry {
     pub context: IdentityContext,
-    pub display_name: String,
-}
-
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
-/// The entry as sent by the Watcher. Then converted into `DisplayNameEntry`.
-pub struct DisplayNameEntryRaw {
-    pub address: ChainAddress,
-    #[serde(alias = "displayName")]
-    pub display_name: String,
-}
-
-impl DisplayNameEntryRaw {
-    /// Display names with emojis are represented in HEX form. Decode the
-    /// display name, assuming it can be decoded...
-    pub fn try_decode_hex(&mut self) {
-        try_decode_hex(&mut self.display_name);
-    }
+    pub display_name: DisplayName,
+    // Normalized form of the display name, used for comparisons. Empty for
+    // entries stored before normalization was introduced.
+    #[serde(default)]
+    pub normalized: String,
 }
 
-fn try_decode_hex(display_name: &mut String) {
-    if display_name.starts_with("0x") {
-        // Might be a false positive. Leave it as is if it cannot be decoded.
-        if let Ok(name) = hex::decode(&display_name[2..]) {
-            if let Ok(name) = String::from_utf8(name) {
-                *display_name = name;
-            }
+impl DisplayNameEntry {
+    pub fn new(context: IdentityContext, display_name: DisplayName) -> Self {
+        DisplayNameEntry {
+            normalized: normalize_display_name(display_name.as_str()),
+            context,
+            display_name,
         }
     }
 }
@@ -183,16 +204,86 @@ pub enum WatcherMessage {
 #[rtype(result = "crate::Result<()>")]
 pub enum ClientCommand {
     ProvideJudgement(JudgementState),
+    NotifyManualReview(JudgementState),
+    NotifyBlocked(IdentityContext, String),
+    NotifyWithdrawn(IdentityContext),
+    // The address as sent by the Watcher and the chain of the request.
+    NotifyRejected(ChainAddress, String),
     RequestPendingJudgements,
     RequestDisplayNames,
 }
 
+/// Tracks the judgements which were submitted to the Watcher, but not
+/// acknowledged yet.
+#[derive(Debug, Default)]
+struct InFlightSubmissions {
+    entries: HashMap<String, (IdentityContext, Instant)>,
+}
+
+impl InFlightSubmissions {
+    /// Registers a new submission and returns its correlation ID. Returns
+    /// `None` if a submission of the identity is still pending or if the
+    /// limit of in-flight submissions is reached.
+    fn submit(&mut self, context: &IdentityContext, now: Instant) -> Option<String> {
+        // Drop timed out submissions, those are retried if still relevant.
+        self.entries.retain(|_, (pending, sent)| {
+            let timed_out = now.duration_since(*sent) >= SUBMISSION_TIMEOUT;
+            if timed_out {
+                warn!("Judgement submission of {:?} was not acknowledged", pending);
+            }
+
+            !timed_out
+        });
+
+        if self.entries.values().any(|(pending, _)| pending == context)
+            || self.entries.len() >= MAX_IN_FLIGHT_SUBMISSIONS
+        {
+            return None;
+        }
+
+        let id = hex::encode(thread_rng().gen::<[u8; 16]>());
+        self.entries.insert(id.clone(), (context.clone(), now));
+
+        Some(id)
+    }
+    /// Resolves the acknowledged submission, either by the correlation ID or,
+    /// as a fallback, by the context. Returns the context together with the
+    /// submission latency, if the submission was tracked.
+    fn acknowledge(
+        &mut self,
+        correlation_id: Option<&str>,
+        context: Option<&IdentityContext>,
+        now: Instant,
+    ) -> Option<(IdentityContext, Duration)> {
+        let id = match correlation_id {
+            Some(id) => id.to_string(),
+            None => {
+                let context = context?;
+                self.entries
+                    .iter()
+                    .find(|(_, (pending, _))| pending == context)
+                    .map(|(id, _)| id.clone())?
+            }
+        };
+
+        self.entries
+            .remove(&id)
+            .map(|(context, sent)| (context, now.duration_since(sent)))
+    }
+}
+
 /// Handles incoming and outgoing websocket messages to and from the Watcher.
 struct Connector {
     #[allow(clippy::type_complexity)]
     sink: Option<SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>>,
     db: Database,
     dn_verifier: DisplayNameVerifier,
+    compliance: Option<ComplianceClient>,
+    limits: IdentityLimits,
+    // Chain hosting the identity pallet, to which the Watcher is connected.
+    identity_chain: IdentityChain,
+    // Additional source of judgement requests.
+    queue: Option<RequestQueueConfig>,
     endpoint: String,
     network: ChainName,
     outgoing: UnboundedSender<ClientCommand>,
@@ -200,14 +291,70 @@ struct Connector {
     // Tracks the last message received from the Watcher. If a certain treshold
     // was exceeded, the Connector attempts to reconnect.
     last_watcher_msg: Timestamp,
+    in_flight: InFlightSubmissions,
+    // Pending judgement requests are processed in the background, at most
+    // `sync_concurrency` at a time.
+    sync_concurrency: usize,
+    // Identities which are currently processed by a sync.
+    syncing: Arc<Mutex<HashSet<IdentityContext>>>,
+    // Challenges of new judgement states.
+    challenges: ChallengePool,
+    // Delays between reconnection attempts to the Watcher.
+    retry: RetryConfig,
+    handle: ConnectorHandle,
+}
+
+/// Whether the last message of the Watcher was received too long ago.
+fn is_stale(last_watcher_msg: Timestamp, now: Timestamp) -> bool {
+    now.raw().saturating_sub(last_watcher_msg.raw()) > HEARTBEAT_TIMEOUT
 }
 
 impl Connector {
+    /// Records that a message was received from the Watcher.
+    fn touch(&mut self) {
+        self.last_watcher_msg = Timestamp::now();
+        metrics::observe_watcher_message(self.network, self.last_watcher_msg);
+    }
+    /// Rejects the requests for chains this instance is not configured for,
+    /// which hints at a misconfigured Watcher. Requests without a chain are
+    /// kept.
+    fn reject_unsupported_chains(
+        &self,
+        requests: Vec<JudgementRequest>,
+        ctx: &mut Context<Self>,
+    ) -> Vec<JudgementRequest> {
+        requests
+            .into_iter()
+            .filter(|req| match &req.network {
+                Some(chain) if !self.identity_chain.serves(chain) => {
+                    error!(
+                        "Rejecting judgement request of {} for unsupported chain '{}', the Watcher of '{}' might be misconfigured",
+                        req.address.as_str(),
+                        chain,
+                        self.identity_chain.name()
+                    );
+                    metrics::observe_unsupported_chain(self.network, chain);
+                    ctx.address()
+                        .do_send(ClientCommand::NotifyRejected(req.address.clone(), chain.clone()));
+                    false
+                }
+                _ => true,
+            })
+            .collect()
+    }
+    #[allow(clippy::too_many_arguments)]
     async fn start(
         endpoint: String,
         network: ChainName,
         db: Database,
         dn_verifier: DisplayNameVerifier,
+        compliance: Option<ComplianceClient>,
+        limits: IdentityLimits,
+        identity_chain: IdentityChain,
+        queue: Option<RequestQueueConfig>,
+        sync_concurrency: usize,
+        retry: RetryConfig,
+        handle: ConnectorHandle,
     ) -> Result<Addr<Connector>> {
         let (_, framed) = Client::new()
             .ws(&endpoint)
@@ -233,16 +380,57 @@ impl Connector {
                 sink: Some(SinkWrite::new(sink, ctx)),
                 db,
                 dn_verifier,
+                compliance,
+                limits,
+                identity_chain,
+                queue,
                 endpoint,
                 network,
                 outgoing,
                 inserted_states: Default::default(),
                 last_watcher_msg: Timestamp::now(),
+                in_flight: Default::default(),
+                sync_concurrency,
+                syncing: Default::default(),
+                challenges: Default::default(),
+                retry,
+                handle,
             }
         });
 
         Ok(actor)
     }
+    // Ping the Watcher every couple of seconds and reconnect if it stopped
+    // responding. The Watcher answers with pongs, which (like every other
+    // message) refresh `last_watcher_msg`.
+    fn start_heartbeat_task(&self, ctx: &mut Context<Self>) {
+        info!("Starting heartbeat background task");
+
+        ctx.run_interval(Duration::new(HEARTBEAT_INTERVAL, 0), |act, ctx| {
+            // Not configured when testing.
+            let sink = match act.sink.as_mut() {
+                Some(sink) => sink,
+                None => return,
+            };
+
+            if sink.closed() {
+                warn!("Connection to the Watcher was closed, resetting connection");
+                ctx.stop();
+                return;
+            }
+
+            if is_stale(act.last_watcher_msg, Timestamp::now()) {
+                warn!("Watcher did not respond to heartbeats, resetting connection");
+                ctx.stop();
+                return;
+            }
+
+            if let Err(err) = sink.write(Message::Ping(Default::default())) {
+                warn!("Failed to send heartbeat to Watcher: {:?}", err);
+                ctx.stop();
+            }
+        });
+    }
     // Request pending judgements every couple of seconds.
     fn start_pending_judgements_task(&self, ctx: &mut Context<Self>) {
         info!("Starting pending judgement requester background task");
@@ -282,7 +470,11 @@ impl Connector {
                     match db.fetch_judgement_candidates(network).await {
                         Ok(completed) => {
                             for state in completed {
-                                info!("Notifying Watcher about judgement: {:?}", state.context);
+                                info!(
+                                    "Notifying Watcher about judgement of {} ({})",
+                                    state.context.display_address().short,
+                                    state.context.chain.as_str()
+                                );
                                 addr.do_send(ClientCommand::ProvideJudgement(state));
                             }
                         }
@@ -294,6 +486,379 @@ impl Connector {
             },
         );
     }
+    // Keep a pool of pre-generated challenges for new identities.
+    fn start_challenge_pool_task(&self, ctx: &mut Context<Self>) {
+        info!("Starting challenge pool background task");
+
+        // Refilled between syncs, which only draw from the pool.
+        self.challenges.refill(CHALLENGE_POOL_SIZE);
+        ctx.run_interval(Duration::new(CHALLENGE_POOL_INTERVAL, 0), |act, _ctx| {
+            act.challenges.refill(CHALLENGE_POOL_SIZE);
+        });
+    }
+    // Apply identity edits which settled, if a settle delay is configured.
+    fn start_settled_updates_task(&self, ctx: &mut Context<Self>) {
+        if self.db.settle_delay().is_none() {
+            return;
+        }
+
+        info!("Starting settled identity updates background task");
+
+        let db = self.db.clone();
+        let dn_verifier = self.dn_verifier.clone();
+        let addr = ctx.address();
+        let network = self.network;
+
+        ctx.run_interval(
+            Duration::new(JUDGEMENT_CANDIDATES_INTERVAL, 0),
+            move |_act, _ctx| {
+                let db = db.clone();
+                let dn_verifier = dn_verifier.clone();
+                let addr = addr.clone();
+
+                actix::spawn(async move {
+                    let updated = match db.apply_settled_updates(network).await {
+                        Ok(updated) => updated,
+                        Err(err) => {
+                            error!("Failed to apply settled identity updates: {:?}", err);
+                            return;
+                        }
+                    };
+
+                    for state in updated {
+                        info!("Applied settled update of {:?}", state.context);
+                        if let Err(err) = on_request_inserted(state, &dn_verifier, &addr).await {
+                            error!("Failed to process settled identity update: {:?}", err);
+                        }
+                    }
+                });
+            },
+        );
+    }
+    // Consume judgement requests from the queue, if configured. The task is
+    // bound to the lifetime of the actor, so a reconnected Connector takes
+    // over the queue.
+    fn start_request_queue_task(&self, ctx: &mut Context<Self>) {
+        let config = match self.queue.clone() {
+            Some(config) => config,
+            None => return,
+        };
+
+        info!("Starting request queue consumer background task");
+
+        let addr = ctx.address();
+        let consumer = format!("registrar-{}", self.network.as_str());
+
+        ctx.spawn(
+            async move {
+                loop {
+                    if let Err(err) = consume_request_queue(&addr, &config, &consumer).await {
+                        error!("Failed to consume request queue: {:?}", err);
+                    }
+
+                    sleep(Duration::from_secs(RECONNECT_DELAY)).await;
+                }
+            }
+            .into_actor(self),
+        );
+    }
+}
+
+// The judgement request of an identity after the limits were applied, together
+// with the block number at which it was requested.
+type CheckedRequest = (IdentityContext, HashMap<AccountType, String>, Option<u64>);
+
+/// Handles a judgement request, the display name must already be decoded.
+async fn process_request(
+    db: &Database,
+    request: CheckedRequest,
+    dn_verifier: &DisplayNameVerifier,
+    compliance: Option<&ComplianceClient>,
+    challenges: &ChallengePool,
+    addr: &Addr<Connector>,
+    // Only used in testing.
+    inserted_states: &Arc<RwLock<Vec<JudgementState>>>,
+) -> Result<()> {
+    let current_state = db.fetch_judgement_state(&request.0).await?;
+    let state = match screen_request(
+        db,
+        request,
+        current_state.as_ref(),
+        compliance,
+        challenges,
+        addr,
+    )
+    .await?
+    {
+        Some(state) => state,
+        None => return Ok(()),
+    };
+
+    record_inserted_state(inserted_states, &state).await;
+
+    // Insert identity into the database and verify display name if the
+    // database entry was modified (or newly inserted).
+    if db.add_judgement_request(&state).await? {
+        on_request_inserted(state, dn_verifier, addr).await?;
+    }
+
+    Ok(())
+}
+
+/// Inserts the requests of identities without a judgement state at once, with
+/// the challenges taken from the pool. Returns the requests which failed and
+/// must be processed individually.
+#[allow(clippy::too_many_arguments)]
+async fn insert_new_requests(
+    db: &Database,
+    requests: Vec<CheckedRequest>,
+    dn_verifier: &DisplayNameVerifier,
+    compliance: Option<&ComplianceClient>,
+    challenges: &ChallengePool,
+    addr: &Addr<Connector>,
+    // Only used in testing.
+    inserted_states: &Arc<RwLock<Vec<JudgementState>>>,
+    concurrency: usize,
+) -> Result<Vec<CheckedRequest>> {
+    let screened: Vec<(_, Result<Option<JudgementState>>)> = futures::stream::iter(requests)
+        .map(|request| async move {
+            let result = async {
+                // Withdrawn requests are listed as pending until the Watcher
+                // processed the withdrawal.
+                if db.check_withdrawn(&request.0).await? {
+                    addr.do_send(ClientCommand::NotifyWithdrawn(request.0.clone()));
+                    return Ok(None);
+                }
+
+                screen_request(db, request.clone(), None, compliance, challenges, addr).await
+            }
+            .await;
+
+            (request, result)
+        })
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
+
+    let mut skipped = vec![];
+    let mut states = vec![];
+    let mut requests = vec![];
+    for (request, result) in screened {
+        match result {
+            Ok(Some(state)) => {
+                states.push(state);
+                requests.push(request);
+            }
+            Ok(None) => {}
+            Err(err) => {
+                warn!(
+                    "Failed to screen judgement request of {:?}, retrying: {:?}",
+                    request.0, err
+                );
+                skipped.push(request);
+            }
+        }
+    }
+
+    let inserted = db.insert_new_judgement_requests(&states).await?;
+
+    let mut to_verify = vec![];
+    for ((state, request), inserted) in states.into_iter().zip(requests).zip(inserted) {
+        record_inserted_state(inserted_states, &state).await;
+
+        if inserted {
+            to_verify.push(state);
+            continue;
+        }
+
+        // Requests with policy or compliance decisions, or identities which
+        // were inserted in the meantime.
+        match db.add_judgement_request(&state).await {
+            Ok(true) => to_verify.push(state),
+            Ok(false) => {}
+            Err(err) => {
+                warn!(
+                    "Failed to insert judgement request of {:?}, retrying: {:?}",
+                    request.0, err
+                );
+                skipped.push(request);
+            }
+        }
+    }
+
+    futures::stream::iter(to_verify)
+        .for_each_concurrent(concurrency, |state| async move {
+            let context = state.context.clone();
+            if let Err(err) = on_request_inserted(state, dn_verifier, addr).await {
+                error!(
+                    "Failed to process inserted judgement request of {:?}: {:?}",
+                    context, err
+                );
+            }
+        })
+        .await;
+
+    Ok(skipped)
+}
+
+/// Creates the judgement state of the request, unless the request must not be
+/// inserted, e.g. because the address is blocked or nothing changed.
+async fn screen_request(
+    db: &Database,
+    request: CheckedRequest,
+    current_state: Option<&JudgementState>,
+    compliance: Option<&ComplianceClient>,
+    challenges: &ChallengePool,
+    addr: &Addr<Connector>,
+) -> Result<Option<JudgementState>> {
+    let (id, accounts, block_number) = request;
+
+    // Requests of blocked addresses are never challenged or judged.
+    if let Some(reason) = db.check_blocked(&id).await? {
+        if db.insert_blocked_request(&id, &reason).await? {
+            info!("Judgement request of {:?} is blocked: {}", id, reason);
+            addr.do_send(ClientCommand::NotifyBlocked(id, reason));
+        }
+
+        return Ok(None);
+    }
+
+    // While the registrar does not accept new requests, only identities which
+    // were already accepted are processed.
+    if current_state.is_none() && !db.accepts_requests(id.chain).await? {
+        debug!("Not accepting new judgement requests, ignoring {:?}", id);
+        return Ok(None);
+    }
+
+    if let Some(current_state) = current_state {
+        // Requests replayed from the request queue or an outdated sync must
+        // not revert a newer request of the identity.
+        if current_state.requested_after(block_number) {
+            debug!(
+                "Ignoring outdated judgement request of {:?} (block {:?})",
+                id, block_number
+            );
+            return Ok(None);
+        }
+
+        // If the fields of the request are the same as the current state,
+        // return.
+        if current_state.has_same_fields_as(&accounts) {
+            return Ok(None);
+        }
+    }
+
+    // Submit new requests to the compliance endpoint, if configured. Stored
+    // decisions are reused, so each request is only posted once.
+    let mut compliance_decision = None;
+    if let (None, Some(compliance)) = (current_state, compliance) {
+        let decision = match db.fetch_compliance_decision(&id).await? {
+            Some(decision) => decision,
+            None => {
+                let request = ComplianceRequest {
+                    context: id.clone(),
+                    fields: accounts.keys().cloned().collect(),
+                };
+
+                let decision = compliance.submit(request).await?;
+                // Other decisions are stored when the identity is inserted.
+                if decision == ComplianceDecision::Deny {
+                    db.insert_compliance_decision(&id, decision).await?;
+                }
+
+                decision
+            }
+        };
+
+        match decision {
+            ComplianceDecision::Allow => {}
+            ComplianceDecision::Flag => {
+                warn!(
+                    "Judgement request of {:?} was flagged by compliance endpoint",
+                    id
+                );
+            }
+            ComplianceDecision::Deny => {
+                info!(
+                    "Judgement request of {:?} was denied by compliance endpoint, skipping",
+                    id
+                );
+                return Ok(None);
+            }
+        }
+
+        compliance_decision = Some(decision);
+    }
+
+    let mut state = JudgementState::with_challenges(
+        id,
+        accounts.into_iter().map(|a| a.into()).collect(),
+        || challenges.next_challenge(),
+    );
+    state.requested_at_block = block_number;
+    state.compliance_decision = compliance_decision;
+    if db.web_challenges() {
+        state.challenge_web_fields();
+    }
+
+    Ok(Some(state))
+}
+
+// Adds the judgement state that's about to get inserted into the local queue
+// which is then fetched from the unit tests.
+async fn record_inserted_state(
+    inserted_states: &Arc<RwLock<Vec<JudgementState>>>,
+    state: &JudgementState,
+) {
+    #[cfg(not(test))]
+    let _ = (inserted_states, state);
+    #[cfg(test)]
+    inserted_states.write().await.push(state.clone());
+}
+
+async fn on_request_inserted(
+    state: JudgementState,
+    dn_verifier: &DisplayNameVerifier,
+    addr: &Addr<Connector>,
+) -> Result<()> {
+    dn_verifier.verify_display_name(&state).await?;
+
+    // Identities without any verifiable fields would otherwise linger, so the
+    // Watcher is informed about those.
+    if state.requires_manual_review {
+        info!(
+            "Judgement request of {:?} requires a manual review",
+            state.context
+        );
+        addr.do_send(ClientCommand::NotifyManualReview(state));
+    }
+
+    Ok(())
+}
+
+async fn consume_request_queue(
+    conn: &Addr<Connector>,
+    config: &RequestQueueConfig,
+    consumer: &str,
+) -> Result<()> {
+    let mut queue = RequestQueue::connect(config.clone(), consumer).await?;
+    info!("Connected to request queue {}", config.stream);
+
+    loop {
+        for entry in queue.next_entries().await? {
+            let parsed = entry
+                .message()
+                .map(serde_json::from_slice::<ResponseMessage<serde_json::Value>>);
+
+            match parsed {
+                Some(Ok(parsed)) => forward_watcher_message(conn, parsed).await?,
+                // Malformed entries would be delivered again and again.
+                _ => warn!("Skipping malformed queue entry {}", entry.id),
+            }
+
+            queue.acknowledge(&entry.id).await?;
+        }
+    }
 }
 
 impl Actor for Connector {
@@ -302,19 +867,34 @@ impl Actor for Connector {
     fn started(&mut self, ctx: &mut Context<Self>) {
         let span = info_span!("connector_background_tasks");
 
+        metrics::set_watcher_connected(self.network, true);
+        self.handle.register(&self.endpoint, ctx.address());
+
         span.in_scope(|| {
             debug!(
                 network = self.network.as_str(),
+                identity_chain = self.identity_chain.name(),
                 endpoint = self.endpoint.as_str()
             );
 
+            self.start_heartbeat_task(ctx);
             self.start_pending_judgements_task(ctx);
             self.start_active_display_names_task(ctx);
             self.start_judgement_candidates_task(ctx);
+            self.start_settled_updates_task(ctx);
+            self.start_request_queue_task(ctx);
+            self.start_challenge_pool_task(ctx);
         });
     }
 
     fn stopped(&mut self, _ctx: &mut Context<Self>) {
+        metrics::set_watcher_connected(self.network, false);
+
+        if self.handle.is_stopping() {
+            info!("Closed connection to Watcher at {}", self.endpoint);
+            return;
+        }
+
         let span = warn_span!("watcher_connection_drop");
         span.in_scope(|| {
             debug!(
@@ -327,25 +907,52 @@ impl Actor for Connector {
         let network = self.network;
         let db = self.db.clone();
         let dn_verifier = self.dn_verifier.clone();
+        let compliance = self.compliance.clone();
+        let limits = self.limits;
+        let identity_chain = self.identity_chain.clone();
+        let queue = self.queue.clone();
+        let sync_concurrency = self.sync_concurrency;
+        let retry = self.retry.clone();
+        let handle = self.handle.clone();
 
         actix::spawn(
             async move {
                 warn!("Watcher disconnected, trying to reconnect...");
 
                 let mut counter = 0;
+                let mut backoff = Backoff::from(&retry);
+
                 loop {
-                    if Connector::start(endpoint.clone(), network, db.clone(), dn_verifier.clone())
-                        .await
-                        .is_err()
+                    if handle.is_stopping() {
+                        break;
+                    }
+
+                    if Connector::start(
+                        endpoint.clone(),
+                        network,
+                        db.clone(),
+                        dn_verifier.clone(),
+                        compliance.clone(),
+                        limits,
+                        identity_chain.clone(),
+                        queue.clone(),
+                        sync_concurrency,
+                        retry.clone(),
+                        handle.clone(),
+                    )
+                    .await
+                    .is_err()
                     {
-                        warn!("Reconnection failed, retrying...");
+                        let delay = backoff.next_delay();
+                        warn!("Reconnection failed, retrying in {:?}...", delay);
+                        metrics::observe_retry("watcher");
 
                         counter += 1;
-                        if counter >= 10 {
+                        if counter >= retry.max_attempts {
                             error!("Cannot reconnect to Watcher after {} attempts", counter);
                         }
 
-                        sleep(Duration::from_secs(RECONNECTION_TIMEOUT)).await;
+                        sleep(delay).await;
                     } else {
                         info!("Reconnected to Watcher!");
                         break;
@@ -359,6 +966,26 @@ impl Actor for Connector {
 
 impl WriteHandler<WsProtocolError> for Connector {}
 
+/// Closes the connection to the Watcher, without reconnecting.
+#[derive(Debug, Clone, Message)]
+#[rtype(result = "()")]
+struct Disconnect;
+
+impl Handler<Disconnect> for Connector {
+    type Result = ();
+
+    fn handle(&mut self, _msg: Disconnect, ctx: &mut Context<Self>) {
+        match self.sink.as_mut() {
+            // The actor is stopped once the sink is closed.
+            Some(sink) => {
+                let _ = sink.write(Message::Close(None));
+                sink.close();
+            }
+            None => ctx.stop(),
+        }
+    }
+}
+
 // Handle messages that should be sent to the Watcher.
 impl Handler<ClientCommand> for Connector {
     type Result = crate::Result<()>;
@@ -373,6 +1000,14 @@ impl Handler<ClientCommand> for Connector {
             endpoint = self.endpoint.as_str()
         );
 
+        // The Watcher sends the pending judgements again once the database
+        // recovered.
+        if matches!(msg, ClientCommand::RequestPendingJudgements) && self.db.health().is_degraded()
+        {
+            debug!("Database is degraded, not requesting pending judgements");
+            return Ok(());
+        }
+
         // If the sink (outgoing WS stream) is not configured (i.e. when
         // testing), send the client command to the channel.
         if self.sink.is_none() {
@@ -390,7 +1025,7 @@ impl Handler<ClientCommand> for Connector {
         }
 
         // Do a timestamp check and reconnect if necessary.
-        if Timestamp::now().raw() - self.last_watcher_msg.raw() > (HEARTBEAT_INTERVAL * 2) {
+        if is_stale(self.last_watcher_msg, Timestamp::now()) {
             warn!("Last received message from the Watcher was a while ago, resetting connection");
             ctx.stop();
             return Ok(());
@@ -398,19 +1033,36 @@ impl Handler<ClientCommand> for Connector {
 
         match msg {
             ClientCommand::ProvideJudgement(state) => {
+                let correlation_id = match self.in_flight.submit(&state.context, Instant::now()) {
+                    Some(id) => id,
+                    None => {
+                        debug!(
+                            "Judgement submission of {:?} is pending or deferred",
+                            state.context
+                        );
+                        return Ok(());
+                    }
+                };
+
                 debug!(
                     "Providing judgement over websocket stream: {:?}",
                     state.context
                 );
                 let verified = state.as_verified_entries();
+                let judgement = if state.erroneous_reason.is_some() {
+                    Judgement::Erroneous
+                } else {
+                    state.judgement_type.into()
+                };
 
                 sink.write(Message::Text(
                     serde_json::to_string(&ResponseMessage {
                         event: EventType::JudgementResult,
                         data: JudgementResponse {
-                            address: state.context.address,
-                            judgement: Judgement::Reasonable,
+                            address: self.identity_chain.to_identity_chain(state.context.address),
+                            judgement,
                             verified,
+                            correlation_id,
                         },
                     })
                     .unwrap()
@@ -418,6 +1070,88 @@ impl Handler<ClientCommand> for Connector {
                 ))
                 .map_err(|err| anyhow!("failed to provide judgement: {:?}", err))?;
             }
+            ClientCommand::NotifyManualReview(state) => {
+                debug!(
+                    "Notifying about required manual review over websocket stream: {:?}",
+                    state.context
+                );
+                let fields = state.unsupported_fields();
+
+                sink.write(Message::Text(
+                    serde_json::to_string(&ResponseMessage {
+                        event: EventType::ManualReviewRequired,
+                        data: ManualReviewResponse {
+                            address: self.identity_chain.to_identity_chain(state.context.address),
+                            reason: "The identity does not contain any fields which can be verified automatically".to_string(),
+                            fields,
+                        },
+                    })
+                    .unwrap()
+                    .into(),
+                ))
+                .map_err(|err| anyhow!("failed to notify about manual review: {:?}", err))?;
+            }
+            ClientCommand::NotifyBlocked(context, reason) => {
+                debug!(
+                    "Notifying about blocked judgement request over websocket stream: {:?}",
+                    context
+                );
+
+                sink.write(Message::Text(
+                    serde_json::to_string(&ResponseMessage {
+                        event: EventType::JudgementRequestBlocked,
+                        data: BlockedResponse {
+                            address: self.identity_chain.to_identity_chain(context.address),
+                            reason,
+                        },
+                    })
+                    .unwrap()
+                    .into(),
+                ))
+                .map_err(|err| anyhow!("failed to notify about blocked request: {:?}", err))?;
+            }
+            ClientCommand::NotifyWithdrawn(context) => {
+                debug!(
+                    "Notifying about withdrawn judgement request over websocket stream: {:?}",
+                    context
+                );
+
+                sink.write(Message::Text(
+                    serde_json::to_string(&ResponseMessage {
+                        event: EventType::JudgementRequestWithdrawn,
+                        data: WithdrawnResponse {
+                            address: self.identity_chain.to_identity_chain(context.address),
+                        },
+                    })
+                    .unwrap()
+                    .into(),
+                ))
+                .map_err(|err| anyhow!("failed to notify about withdrawn request: {:?}", err))?;
+            }
+            ClientCommand::NotifyRejected(address, network) => {
+                debug!(
+                    "Notifying about rejected judgement request over websocket stream: {}",
+                    address.as_str()
+                );
+
+                sink.write(Message::Text(
+                    serde_json::to_string(&ResponseMessage {
+                        event: EventType::JudgementRequestRejected,
+                        data: RejectedResponse {
+                            address,
+                            reason: format!(
+                                "Chain '{}' is not supported, expected '{}'",
+                                network,
+                                self.identity_chain.name()
+                            ),
+                            network,
+                        },
+                    })
+                    .unwrap()
+                    .into(),
+                ))
+                .map_err(|err| anyhow!("failed to notify about rejected request: {:?}", err))?;
+            }
             ClientCommand::RequestPendingJudgements => {
                 debug!("Requesting pending judgements over websocket stream");
 
@@ -454,110 +1188,267 @@ impl Handler<ClientCommand> for Connector {
 impl Handler<WatcherMessage> for Connector {
     type Result = ResponseActFuture<Self, crate::Result<()>>;
 
-    fn handle(&mut self, msg: WatcherMessage, _ctx: &mut Context<Self>) -> Self::Result {
-        /// Handle a judgement request.
-        async fn process_request(
-            db: &Database,
-            id: IdentityContext,
-            mut accounts: HashMap<AccountType, String>,
-            dn_verifier: &DisplayNameVerifier,
-            // Only used in testing.
-            inserted_states: &Arc<RwLock<Vec<JudgementState>>>,
-        ) -> Result<()> {
-            // Decode display name if appropriate.
-            if let Some((_, val)) = accounts
-                .iter_mut()
-                .find(|(ty, _)| *ty == &AccountType::DisplayName)
-            {
-                try_decode_hex(val);
-            }
+    fn handle(&mut self, msg: WatcherMessage, ctx: &mut Context<Self>) -> Self::Result {
+        // Update timestamp
+        self.touch();
+
+        // Resolve the acknowledged submission.
+        let mut acked = None;
+        if let WatcherMessage::Ack(data) = &msg {
+            if data.result.to_lowercase().contains("judgement given") {
+                let context = data.address.clone().map(|address| {
+                    IdentityContext::new(self.identity_chain.to_relay(address), self.network)
+                });
 
-            // If the fields of the request are the same as the current state, return.
-            if let Some(current_state) = db.fetch_judgement_state(&id).await? {
-                if current_state.has_same_fields_as(&accounts) {
-                    return Ok(());
+                if let Some((context, latency)) = self.in_flight.acknowledge(
+                    data.correlation_id.as_deref(),
+                    context.as_ref(),
+                    Instant::now(),
+                ) {
+                    metrics::observe_submission(context.chain, latency);
+                    acked = Some(context);
                 }
             }
+        }
 
-            // Create judgement state and prepare to insert into database.
-            let state = JudgementState::new(id, accounts.into_iter().map(|a| a.into()).collect());
-
-            // Add the judgement state that's about to get inserted into the
-            // local queue which is then fetched from the unit tests.
-            #[cfg(not(test))]
-            let _ = inserted_states;
-            #[cfg(test)]
-            {
-                let mut l = inserted_states.write().await;
-                (*l).push(state.clone());
+        let msg = match msg {
+            WatcherMessage::NewJudgementRequest(data) => {
+                match self.reject_unsupported_chains(vec![data], ctx).pop() {
+                    Some(data) => WatcherMessage::NewJudgementRequest(data),
+                    None => return Box::pin(async { Ok(()) }.into_actor(self)),
+                }
             }
-
-            // Insert identity into the database and verify display name if the
-            // database entry was modified (or newly inserted).
-            if db.add_judgement_request(&state).await? {
-                dn_verifier.verify_display_name(&state).await?;
+            WatcherMessage::PendingJudgementsRequests(data) => {
+                WatcherMessage::PendingJudgementsRequests(self.reject_unsupported_chains(data, ctx))
             }
-
-            Ok(())
+            msg => msg,
+        };
+
+        // Judgement requests are re-sent by the Watcher, so those are ignored
+        // instead of piling up failing tasks while the database is degraded.
+        let throttled = match &msg {
+            WatcherMessage::NewJudgementRequest(_) => 1,
+            WatcherMessage::PendingJudgementsRequests(data) => data.len(),
+            _ => 0,
+        };
+        if throttled > 0 && self.db.health().is_degraded() {
+            warn!(
+                "Database is degraded, ignoring {} judgement request(s) of the Watcher",
+                throttled
+            );
+            metrics::observe_throttled_requests(self.network, throttled);
+            return Box::pin(async { Ok(()) }.into_actor(self));
         }
 
-        // Update timestamp
-        self.last_watcher_msg = Timestamp::now();
-
         let network = self.network;
         let db = self.db.clone();
         let dn_verifier = self.dn_verifier.clone();
+        let compliance = self.compliance.clone();
+        let limits = self.limits;
+        let identity_chain = self.identity_chain.clone();
         let inserted_states = Arc::clone(&self.inserted_states);
+        let sync_concurrency = self.sync_concurrency;
+        let syncing = Arc::clone(&self.syncing);
+        let challenges = self.challenges.clone();
+        let addr = ctx.address();
+
+        // Requests exceeding the limits of the identity pallet or with
+        // addresses which are not valid SS58 addresses of the chain can only
+        // stem from malformed messages.
+        let relay_chain = identity_chain.clone();
+        let check_limits = move |req: JudgementRequest| {
+            if let Err(err) = relay_chain.validate(&req.address) {
+                warn!(
+                    "Skipping judgement request with invalid address on {}: {:?}",
+                    relay_chain.name(),
+                    err
+                );
+                return None;
+            }
+
+            let context = IdentityContext::new(relay_chain.to_relay(req.address.clone()), network);
+            match limits.apply(&req.address, req.accounts) {
+                Ok(mut accounts) => {
+                    // Decode display name if appropriate.
+                    if let Some(val) = accounts.get_mut(&AccountType::DisplayName) {
+                        try_decode_hex(val);
+                    }
+
+                    Some((context, accounts, req.block_number))
+                }
+                Err(violation) => {
+                    warn!(
+                        "Skipping invalid judgement request of {:?}: {}",
+                        context, violation
+                    );
+                    None
+                }
+            }
+        };
 
         Box::pin(
             async move {
                 match msg {
                     WatcherMessage::Ack(data) => {
                         if data.result.to_lowercase().contains("judgement given") {
-                            // Create identity context.
-                            let address =
-                                data.address
-                                .ok_or_else(|| {
+                            let context = acked.or_else(|| {
+                                data.address.map(|address| IdentityContext::new(identity_chain.to_relay(address), network))
+                            })
+                            .ok_or_else(|| {
                                 anyhow!(
                                     "no address specified in 'judgement given' response from Watcher"
                                 )
                             })?;
 
-                            let context = IdentityContext::new(address, network);
-
                             info!("Marking {:?} as judged", context);
                             db.set_judged(&context).await?;
                         }
                     }
                     WatcherMessage::NewJudgementRequest(data) => {
-                        let id = IdentityContext::new(data.address, network);
-                        process_request(&db, id, data.accounts, &dn_verifier, &inserted_states).await?;
+                        if let Some(request) = check_limits(data) {
+                            // A new request after a withdrawal starts over.
+                            let id = &request.0;
+                            if db.check_withdrawn(id).await? {
+                                info!("Received new judgement request of {:?} after withdrawal", id);
+                                db.remove_withdrawal(id).await?;
+                            }
+
+                            process_request(&db, request, &dn_verifier, compliance.as_ref(), &challenges, &addr, &inserted_states).await?;
+                        }
                     }
                     WatcherMessage::PendingJudgementsRequests(data) => {
                         // Convert data.
-                        let data: Vec<(IdentityContext, HashMap<AccountType, String>)> = data
+                        let data: Vec<CheckedRequest> = data
                             .into_iter()
-                            .map(|req| (
-                                IdentityContext::new(req.address, network),
-                                req.accounts
-                            ))
+                            .filter_map(check_limits)
                             .collect();
 
-                        for (context, accounts) in data {
-                            process_request(&db, context, accounts, &dn_verifier, &inserted_states).await?;
-                        }
+                        // Identities which are still processed by a previous
+                        // sync are skipped, those are retried on the next one.
+                        let mut data: Vec<CheckedRequest> = {
+                            let mut syncing = syncing.lock().expect("sync lock poisoned");
+                            data.into_iter()
+                                .filter(|(context, _, _)| syncing.insert(context.clone()))
+                                .collect()
+                        };
+
+                        // Oldest requests first, so the processing order does
+                        // not depend on the order of the Watcher.
+                        data.sort_by_key(|(_, _, block_number)| *block_number);
+
+                        // Process the requests in the background, so a large
+                        // sync does not hold up other messages of the Watcher.
+                        actix::spawn(
+                            async move {
+                                let start = Instant::now();
+                                let total = data.len();
+                                let failed = AtomicUsize::new(0);
+                                let (db, dn_verifier, compliance, challenges, addr, inserted_states, syncing, failed) =
+                                    (&db, &dn_verifier, compliance.as_ref(), &challenges, &addr, &inserted_states, &syncing, &failed);
+
+                                // Unchanged identities, the bulk of a regular sync,
+                                // need no further processing.
+                                let contexts: Vec<IdentityContext> = data.iter().map(|(context, _, _)| context.clone()).collect();
+                                let (new, mut remaining) = match db.fetch_judgement_states(&contexts).await {
+                                    Ok(states) => {
+                                        let mut syncing = syncing.lock().expect("sync lock poisoned");
+                                        let mut new = vec![];
+                                        let mut changed = vec![];
+                                        for request in data {
+                                            match states.get(&request.0) {
+                                                Some(state) if state.has_same_fields_as(&request.1) => {
+                                                    syncing.remove(&request.0);
+                                                }
+                                                Some(_) => changed.push(request),
+                                                None => new.push(request),
+                                            }
+                                        }
+
+                                        (new, changed)
+                                    }
+                                    Err(err) => {
+                                        error!("Failed to fetch judgement states for sync: {:?}", err);
+                                        (vec![], data)
+                                    }
+                                };
+
+                                // New identities are inserted in batches, failed
+                                // ones are retried individually.
+                                for chunk in new.chunks(BULK_INSERT_SIZE) {
+                                    let skipped = insert_new_requests(db, chunk.to_vec(), dn_verifier, compliance, challenges, addr, inserted_states, sync_concurrency)
+                                        .await
+                                        .unwrap_or_else(|err| {
+                                            error!("Failed to insert new judgement requests: {:?}", err);
+                                            chunk.to_vec()
+                                        });
+
+                                    let skipped_contexts: HashSet<&IdentityContext> = skipped.iter().map(|(context, _, _)| context).collect();
+                                    let mut syncing = syncing.lock().expect("sync lock poisoned");
+                                    for (context, _, _) in chunk {
+                                        if !skipped_contexts.contains(context) {
+                                            syncing.remove(context);
+                                        }
+                                    }
+
+                                    remaining.extend(skipped);
+                                }
+
+                                futures::stream::iter(remaining)
+                                    .for_each_concurrent(sync_concurrency, move |request| {
+                                        async move {
+                                            let context = request.0.clone();
+                                            let result = async {
+                                                // Withdrawn requests are listed as pending until
+                                                // the Watcher processed the withdrawal.
+                                                if db.check_withdrawn(&context).await? {
+                                                    addr.do_send(ClientCommand::NotifyWithdrawn(context.clone()));
+                                                    return Ok(());
+                                                }
+
+                                                process_request(db, request, dn_verifier, compliance, challenges, addr, inserted_states).await
+                                            }
+                                            .await;
+
+                                            if let Err(err) = result {
+                                                error!("Failed to process pending judgement request of {:?}: {:?}", context, err);
+                                                failed.fetch_add(1, Ordering::Relaxed);
+                                            }
+
+                                            syncing.lock().expect("sync lock poisoned").remove(&context);
+                                        }
+                                    })
+                                    .await;
+
+                                info!(
+                                    "Processed {} pending judgement requests in {} ms ({} failed)",
+                                    total,
+                                    start.elapsed().as_millis(),
+                                    failed.load(Ordering::Relaxed)
+                                );
+                            }
+                            .in_current_span(),
+                        );
                     }
                     WatcherMessage::ActiveDisplayNames(data) => {
+                        let mut entries = vec![];
                         for mut name in data {
                             name.try_decode_hex();
 
-                            let context = IdentityContext::new(name.address, network);
-                            let entry = DisplayNameEntry {
-                                context,
-                                display_name: name.display_name,
-                            };
+                            let context = IdentityContext::new(identity_chain.to_relay(name.address), network);
+                            let entry = DisplayNameEntry::new(context, name.display_name);
 
                             db.insert_display_name(&entry).await?;
+                            entries.push(entry);
+                        }
+
+                        // Remove display names that are no longer in use. An
+                        // empty list is most likely an issue with the Watcher,
+                        // so the pool is left untouched.
+                        if !entries.is_empty() {
+                            let removed = db.prune_display_names(network, &entries).await?;
+                            if removed > 0 {
+                                debug!("Removed {} inactive display names", removed);
+                            }
                         }
                     }
                 }
@@ -568,6 +1459,52 @@ impl Handler<WatcherMessage> for Connector {
     }
 }
 
+/// Forwards a message of the Watcher (or of the request queue) to the
+/// `Handler<WatcherMessage>` implementation.
+async fn forward_watcher_message(
+    conn: &Addr<Connector>,
+    parsed: ResponseMessage<serde_json::Value>,
+) -> Result<()> {
+    match parsed.event {
+        EventType::Ack => {
+            debug!("Received acknowledgement from Watcher: {:?}", parsed.data);
+
+            let data: AckResponse = serde_json::from_value(parsed.data)?;
+            conn.send(WatcherMessage::Ack(data)).await??;
+        }
+        EventType::Error => {
+            error!("Received error from Watcher: {:?}", parsed.data);
+        }
+        EventType::NewJudgementRequest => {
+            info!(
+                "Received new judgement request from Watcher: {:?}",
+                parsed.data
+            );
+
+            let data: JudgementRequest = serde_json::from_value(parsed.data)?;
+            conn.send(WatcherMessage::NewJudgementRequest(data))
+                .await??;
+        }
+        EventType::PendingJudgementsResponse => {
+            let data: Vec<JudgementRequest> = serde_json::from_value(parsed.data)?;
+            debug!("Received {} pending judgments from Watcher", data.len());
+            conn.send(WatcherMessage::PendingJudgementsRequests(data))
+                .await??;
+        }
+        EventType::DisplayNamesResponse => {
+            let data: Vec<DisplayNameEntryRaw> = serde_json::from_value(parsed.data)?;
+            debug!("Received {} display names from the Watcher", data.len());
+            conn.send(WatcherMessage::ActiveDisplayNames(data))
+                .await??;
+        }
+        _ => {
+            warn!("Received unrecognized message from Watcher: {:?}", parsed);
+        }
+    }
+
+    Ok(())
+}
+
 /// Handle websocket messages received from the Watcher. Those messages will be
 /// forwarded to the `Handler<WatcherMessage>` implementation.
 impl StreamHandler<std::result::Result<Frame, WsProtocolError>> for Connector {
@@ -589,44 +1526,29 @@ impl StreamHandler<std::result::Result<Frame, WsProtocolError>> for Connector {
                 Err(err) => return Err(anyhow!("error message: {:?}", err)),
             };
 
-            match parsed.event {
-                EventType::Ack => {
-                    debug!("Received acknowledgement from Watcher: {:?}", parsed.data);
-
-                    let data: AckResponse = serde_json::from_value(parsed.data)?;
-                    conn.send(WatcherMessage::Ack(data)).await??;
-                }
-                EventType::Error => {
-                    error!("Received error from Watcher: {:?}", parsed.data);
-                }
-                EventType::NewJudgementRequest => {
-                    info!(
-                        "Received new judgement request from Watcher: {:?}",
-                        parsed.data
-                    );
+            forward_watcher_message(&conn, parsed).await
+        }
 
-                    let data: JudgementRequest = serde_json::from_value(parsed.data)?;
-                    conn.send(WatcherMessage::NewJudgementRequest(data))
-                        .await??;
-                }
-                EventType::PendingJudgementsResponse => {
-                    let data: Vec<JudgementRequest> = serde_json::from_value(parsed.data)?;
-                    debug!("Received {} pending judgments from Watcher", data.len());
-                    conn.send(WatcherMessage::PendingJudgementsRequests(data))
-                        .await??;
-                }
-                EventType::DisplayNamesResponse => {
-                    let data: Vec<DisplayNameEntryRaw> = serde_json::from_value(parsed.data)?;
-                    debug!("Received {} display names from the Watcher", data.len());
-                    conn.send(WatcherMessage::ActiveDisplayNames(data))
-                        .await??;
-                }
-                _ => {
-                    warn!("Received unrecognized message from Watcher: {:?}", parsed);
+        // Any frame proves that the connection is alive.
+        match &msg {
+            Ok(Frame::Ping(bytes)) => {
+                self.touch();
+                if let Some(sink) = self.sink.as_mut() {
+                    let _ = sink.write(Message::Pong(bytes.clone()));
                 }
+                return;
             }
-
-            Ok(())
+            Ok(Frame::Pong(_)) => {
+                self.touch();
+                return;
+            }
+            Ok(Frame::Close(reason)) => {
+                warn!("Watcher closed the connection: {:?}", reason);
+                ctx.stop();
+                return;
+            }
+            Ok(_) => self.touch(),
+            Err(_) => {}
         }
 
         let span = debug_span!("handling_websocket_message");
@@ -649,46 +1571,6 @@ impl StreamHandler<std::result::Result<Frame, WsProtocolError>> for Connector {
     }
 }
 
-#[derive(Eq, PartialEq, Hash, Clone, Debug, Serialize, Deserialize)]
-pub enum AccountType {
-    #[serde(rename = "legal_name")]
-    LegalName,
-    #[serde(rename = "display_name")]
-    DisplayName,
-    #[serde(rename = "email")]
-    Email,
-    #[serde(rename = "web")]
-    Web,
-    #[serde(rename = "twitter")]
-    Twitter,
-    #[serde(rename = "matrix")]
-    Matrix,
-    #[serde(rename = "pgpFingerprint")]
-    PGPFingerprint,
-    #[serde(rename = "image")]
-    Image,
-    #[serde(rename = "additional")]
-    Additional,
-}
-
-impl From<(AccountType, String)> for IdentityFieldValue {
-    fn from(val: (AccountType, String)) -> Self {
-        let (ty, value) = val;
-
-        match ty {
-            AccountType::LegalName => IdentityFieldValue::LegalName(value),
-            AccountType::DisplayName => IdentityFieldValue::DisplayName(value),
-            AccountType::Email => IdentityFieldValue::Email(value),
-            AccountType::Web => IdentityFieldValue::Web(value),
-            AccountType::Twitter => IdentityFieldValue::Twitter(value.to_lowercase()),
-            AccountType::Matrix => IdentityFieldValue::Matrix(value),
-            AccountType::PGPFingerprint => IdentityFieldValue::PGPFingerprint(()),
-            AccountType::Image => IdentityFieldValue::Image(()),
-            AccountType::Additional => IdentityFieldValue::Additional(()),
-        }
-    }
-}
-
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -707,6 +1589,8 @@ pub mod tests {
                     (AccountType::Twitter, "@alice".to_string()),
                     (AccountType::Matrix, "@alice:matrix.org".to_string()),
                 ]),
+                block_number: None,
+                network: None,
             }
         }
         pub fn bob() -> Self {
@@ -720,6 +1604,8 @@ pub mod tests {
                     (AccountType::Twitter, "@bob".to_string()),
                     (AccountType::Matrix, "@bob:matrix.org".to_string()),
                 ]),
+                block_number: None,
+                network: None,
             }
         }
     }
@@ -742,6 +1628,9 @@ pub mod tests {
             let dn_config = DisplayNameConfig {
                 enabled: false,
                 limit: 0.85,
+                confusables: None,
+                reserved_names: None,
+                reserved_patterns: None,
             };
 
             let dn_verifier = DisplayNameVerifier::new(db.clone(), dn_config);
@@ -771,6 +1660,10 @@ pub mod tests {
             while let Ok(msg) = self.queue.try_recv() {
                 match msg {
                     ClientCommand::ProvideJudgement(_) => counter.provide_judgement += 1,
+                    ClientCommand::NotifyManualReview(_) => counter.notify_manual_review += 1,
+                    ClientCommand::NotifyBlocked(_, _) => counter.notify_blocked += 1,
+                    ClientCommand::NotifyWithdrawn(_) => counter.notify_withdrawn += 1,
+                    ClientCommand::NotifyRejected(_, _) => counter.notify_rejected += 1,
                     ClientCommand::RequestPendingJudgements => {
                         counter.request_pending_judgements += 1
                     }
@@ -787,6 +1680,10 @@ pub mod tests {
     #[derive(Default)]
     pub struct OutgoingCounter {
         pub provide_judgement: usize,
+        pub notify_manual_review: usize,
+        pub notify_blocked: usize,
+        pub notify_withdrawn: usize,
+        pub notify_rejected: usize,
         pub request_pending_judgements: usize,
         pub request_display_names: usize,
     }
@@ -810,15 +1707,87 @@ pub mod tests {
                 sink: None,
                 db,
                 dn_verifier,
+                compliance: None,
+                limits: Default::default(),
+                identity_chain: IdentityChain::relay(network),
+                queue: None,
                 endpoint: "".to_string(),
                 network,
                 outgoing,
                 inserted_states: Arc::clone(&inserted_states),
                 last_watcher_msg: Timestamp::now(),
+                in_flight: Default::default(),
+                sync_concurrency: DEFAULT_SYNC_CONCURRENCY,
+                syncing: Default::default(),
+                challenges: Default::default(),
+                retry: Default::default(),
+                handle: Default::default(),
             }
             .start();
 
             (addr, recv, inserted_states)
         }
     }
+
+    #[test]
+    fn stale_watcher_connection() {
+        let last = Timestamp::now();
+
+        assert!(!is_stale(last, last));
+        assert!(!is_stale(
+            last,
+            Timestamp::with_offset(HEARTBEAT_TIMEOUT - 1)
+        ));
+        assert!(is_stale(
+            last,
+            Timestamp::with_offset(HEARTBEAT_TIMEOUT + 1)
+        ));
+        // Clock adjustments do not cause a reconnect.
+        assert!(!is_stale(Timestamp::with_offset(60), last));
+    }
+
+    #[test]
+    fn in_flight_submissions() {
+        let mut in_flight = InFlightSubmissions::default();
+        let alice = IdentityContext::alice();
+        let bob = IdentityContext::bob();
+        let now = Instant::now();
+
+        let id = in_flight.submit(&alice, now).unwrap();
+        // Pending submissions are not sent twice.
+        assert!(in_flight.submit(&alice, now).is_none());
+        assert!(in_flight.submit(&bob, now).is_some());
+
+        // Acknowledged by correlation ID.
+        let later = now + Duration::from_secs(2);
+        let (context, latency) = in_flight.acknowledge(Some(&id), None, later).unwrap();
+        assert_eq!(context, alice);
+        assert_eq!(latency, Duration::from_secs(2));
+        assert!(in_flight.acknowledge(Some(&id), None, later).is_none());
+
+        // Acknowledged by address.
+        let (context, _) = in_flight.acknowledge(None, Some(&bob), later).unwrap();
+        assert_eq!(context, bob);
+        assert!(in_flight.entries.is_empty());
+    }
+
+    #[test]
+    fn in_flight_submission_limits() {
+        let mut in_flight = InFlightSubmissions::default();
+        let alice = IdentityContext::alice();
+        let now = Instant::now();
+
+        for _ in 0..MAX_IN_FLIGHT_SUBMISSIONS {
+            let mut context = alice.clone();
+            context.address = hex::encode(thread_rng().gen::<[u8; 16]>()).into();
+            assert!(in_flight.submit(&context, now).is_some());
+        }
+
+        assert!(in_flight.submit(&alice, now).is_none());
+
+        // Timed out submissions are dropped and can be retried.
+        let later = now + SUBMISSION_TIMEOUT;
+        assert!(in_flight.submit(&alice, later).is_some());
+        assert_eq!(in_flight.entries.len(), 1);
+    }
 }
@@ -0,0 +1,120 @@
+use crate::primitives::IdentityContext;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+/// Serializes the state transitions of an identity, e.g. a message of an
+/// adapter and an admin command verifying the same field, so those do not
+/// produce conflicting events. Only covers the transitions of this process,
+/// concurrent transactions of other instances still fail with a write
+/// conflict.
+#[derive(Debug, Default)]
+pub struct ContextLocks {
+    locks: Mutex<HashMap<IdentityContext, Arc<AsyncMutex<()>>>>,
+}
+
+impl ContextLocks {
+    /// Waits until no other transition of the identity is in progress.
+    pub async fn lock(self: &Arc<Self>, context: &IdentityContext) -> ContextGuard {
+        self.lock_all(vec![context.clone()]).await
+    }
+    /// Locks multiple identities at once, e.g. all identities sharing the
+    /// contact value of a message. The identities are always locked in the
+    /// same order, so concurrent calls cannot deadlock.
+    pub async fn lock_all(self: &Arc<Self>, mut contexts: Vec<IdentityContext>) -> ContextGuard {
+        contexts.sort_by(|a, b| {
+            (a.chain.as_str(), a.address.as_str()).cmp(&(b.chain.as_str(), b.address.as_str()))
+        });
+        contexts.dedup();
+
+        let mut guards = Vec::with_capacity(contexts.len());
+        for context in &contexts {
+            let lock = Arc::clone(
+                self.locks
+                    .lock()
+                    .expect("context locks poisoned")
+                    .entry(context.clone())
+                    .or_default(),
+            );
+
+            guards.push(lock.lock_owned().await);
+        }
+
+        ContextGuard {
+            locks: Arc::clone(self),
+            contexts,
+            guards,
+        }
+    }
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.locks.lock().expect("context locks poisoned").len()
+    }
+}
+
+/// Releases the identities when dropped.
+pub struct ContextGuard {
+    locks: Arc<ContextLocks>,
+    contexts: Vec<IdentityContext>,
+    guards: Vec<OwnedMutexGuard<()>>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        self.guards.clear();
+
+        // Remove the locks nobody else holds or waits for.
+        let mut locks = self.locks.locks.lock().expect("context locks poisoned");
+        for context in &self.contexts {
+            if locks
+                .get(context)
+                .map(|lock| Arc::strong_count(lock) == 1)
+                .unwrap_or(false)
+            {
+                locks.remove(context);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::time::{sleep, timeout};
+
+    #[tokio::test]
+    async fn serialize_transitions() {
+        let locks = Arc::new(ContextLocks::default());
+        let alice = IdentityContext::alice();
+        let bob = IdentityContext::bob();
+
+        let guard = locks.lock(&alice).await;
+
+        // Other identities are not affected.
+        let other = timeout(Duration::from_millis(100), locks.lock(&bob)).await;
+        assert!(other.is_ok());
+        drop(other);
+
+        // The same identity waits until released.
+        let waiting = {
+            let locks = Arc::clone(&locks);
+            let contexts = vec![bob.clone(), alice.clone()];
+            tokio::spawn(async move {
+                let _guard = locks.lock_all(contexts).await;
+            })
+        };
+
+        sleep(Duration::from_millis(100)).await;
+        assert!(!waiting.is_finished());
+
+        drop(guard);
+        timeout(Duration::from_millis(100), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Released locks are removed.
+        assert_eq!(locks.len(), 0);
+    }
+}
@@ -1,23 +1,89 @@
-use crate::adapters::admin::RawFieldName;
-use crate::api::VerifyChallenge;
+use crate::adapters::admin::{PendingApproval, PendingOverview, RawFieldName, SearchResult};
+use crate::adapters::email::SecondChallengeCodeConfig;
+use crate::adapters::matrix::MatrixHandle;
+use crate::address_blocklist::{
+    AddressBlocklist, BlockedAddress, BlockedRequest, BlocklistAction, BlocklistAuditEntry,
+};
+use crate::api::importable_field;
+use crate::api::{
+    AttestationOutcome, CredentialAttestation, DisplayNameChange, EventFilter, EventPage,
+    RegenerationOutcome, RenewalOutcome, VerifyChallenge, WithdrawalNonce, WithdrawnRequest,
+};
+use crate::compliance::{ComplianceDecision, ComplianceRecord};
 use crate::connector::DisplayNameEntry;
+use crate::context_locks::{ContextGuard, ContextLocks};
+use crate::db_health::DbHealth;
+use crate::disposable_emails::{DisposableEmailAction, DisposableEmails};
+use crate::failure_alerts::FailureAlert;
+use crate::instance_registry::InstanceRegistration;
+use crate::matrix_policy::MatrixPolicy;
+use crate::message_rate_limit::{MessageRateLimitConfig, OriginAttempts, RateLimitDecision};
+use crate::metrics;
 use crate::primitives::{
-    ChainName, ChallengeType, Event, ExpectedMessage, ExternalMessage, IdentityContext,
-    IdentityFieldValue, JudgementState, NotificationMessage, Timestamp,
+    ChainName, ChallengeType, DisplayName, Event, ExpectedMessage, ExternalMessage,
+    ExternalMessageType, FieldFailure, IdentityContext, IdentityFieldValue, ImportedCredential,
+    JudgementState, JudgementStatus, JudgementType, MessageId, NotificationMessage,
+    OutboundMessage, OutboxEntry, Timestamp, VerificationOutcome, VerificationRecord,
+    WithheldReason,
 };
+use crate::query_log::QueryLog;
+use crate::reserved_names::{ReservedDisplayName, ReservedKind};
+use crate::scripting::ScriptHook;
+use crate::verification::{self, MessageOutcome, SecondChallengeOutcome, VerificationTransition};
 use crate::Result;
-use bson::{doc, from_document, to_bson, to_document, Bson, Document};
+use bson::{doc, from_bson, from_document, to_bson, to_document, Bson, Document};
+use futures::stream::BoxStream;
 use futures::StreamExt;
-use mongodb::options::{IndexOptions, TransactionOptions, UpdateOptions};
+use mongodb::change_stream::event::OperationType;
+use mongodb::error::{BulkWriteFailure, ErrorKind, WriteFailure};
+use mongodb::options::{
+    ChangeStreamOptions, ClientOptions, FindOneAndUpdateOptions, FindOptions,
+    FullDocumentBeforeChangeType, IndexOptions, InsertManyOptions, ReturnDocument,
+    TransactionOptions, UpdateOptions,
+};
 use mongodb::{Client, ClientSession, Database as MongoDb, IndexModel};
-use rand::{thread_rng, Rng};
 use serde::Serialize;
-use std::collections::HashMap;
-use std::time::Duration;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
 
 const IDENTITY_COLLECTION: &str = "identities";
 const EVENT_COLLECTION: &str = "event_log";
+const EVENT_LOG_SEQUENCE: &str = "event_log_sequence";
 const DISPLAY_NAMES: &str = "display_names";
+const PENDING_APPROVALS: &str = "pending_approvals";
+const WATERMARKS: &str = "watermarks";
+const MATRIX_ROOMS: &str = "matrix_rooms";
+const TWITTER_IDS: &str = "twitter_ids";
+const OUTBOUND_ARCHIVE: &str = "outbound_archive";
+const VERIFICATION_RECORDS: &str = "verification_records";
+const OUTBOX: &str = "outbox";
+const NOTIFICATION_SEQUENCES: &str = "notification_sequences";
+const BLOCKED_ADDRESSES: &str = "blocked_addresses";
+const BLOCKED_REQUESTS: &str = "blocked_requests";
+const BLOCKLIST_AUDIT: &str = "blocklist_audit";
+const CHALLENGE_REGENERATIONS: &str = "challenge_regenerations";
+const WITHDRAWAL_NONCES: &str = "withdrawal_nonces";
+const WITHDRAWN_REQUESTS: &str = "withdrawn_requests";
+const PENDING_UPDATES: &str = "pending_updates";
+const PAUSED_CHAINS: &str = "paused_chains";
+const PAUSED_ADAPTERS: &str = "paused_adapters";
+const PAUSED_REQUESTS: &str = "paused_requests";
+const INSTANCES: &str = "instances";
+const MESSAGE_RATE_LIMITS: &str = "message_rate_limits";
+const PROCESSED_MESSAGES: &str = "processed_messages";
+const PROCESSED_OPERATIONS: &str = "processed_operations";
+const IMPORTED_CREDENTIALS: &str = "imported_credentials";
+const FAILURE_ALERTS: &str = "failure_alerts";
+const RESERVED_DISPLAY_NAMES: &str = "reserved_display_names";
+const COMPLIANCE_DECISIONS: &str = "compliance_decisions";
+
+// Raised by unique indexes.
+const DUPLICATE_KEY_ERROR: i32 = 11000;
+// Interval in which the completion of pending commands is checked on
+// shutdown.
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
 
 /// Convenience trait. Converts a value to BSON.
 trait ToBson {
@@ -34,31 +100,78 @@ impl<T: Serialize> ToBson for T {
     }
 }
 
-// Keeps track of the latest, fetched events to avoid sending old messages or
-// duplicates.
-pub struct EventCursor {
-    timestamp: Timestamp,
-    fetched_ids: HashMap<String, Timestamp>,
+/// The latest edit of an identity which is applied once no further edits
+/// were received within the settle delay.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingUpdate {
+    context: IdentityContext,
+    request: JudgementState,
+    apply_at: Timestamp,
 }
 
-impl EventCursor {
-    pub fn new() -> Self {
-        EventCursor {
-            timestamp: Timestamp::now(),
-            fetched_ids: HashMap::new(),
-        }
-    }
+/// A network for which no judgements are submitted until an admin resumes
+/// submissions.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct PausedChain {
+    pub chain: ChainName,
+    pub paused_by: MatrixHandle,
+    pub timestamp: Timestamp,
+}
+
+/// An adapter service (e.g. `twitter`) which neither fetches nor sends
+/// messages until an admin resumes it.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct PausedAdapter {
+    pub adapter: String,
+    pub paused_by: MatrixHandle,
+    pub timestamp: Timestamp,
+}
+
+/// New judgement requests of a network (or of all networks, if no chain is
+/// set) are ignored until an admin resumes them. Identities which were already
+/// accepted are still verified and judged.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct PausedRequests {
+    pub chain: Option<ChainName>,
+    pub paused_by: MatrixHandle,
+    pub timestamp: Timestamp,
 }
 
 #[derive(Debug, Clone)]
 pub struct Database {
     client: Client,
     db: MongoDb,
+    // Optional deployment for read-only query paths, such as replicas.
+    read: Option<(Client, MongoDb)>,
+    // Operator provided rules, consulted before fields or identities are
+    // verified.
+    hook: Option<ScriptHook>,
+    disposable_emails: Option<DisposableEmails>,
+    matrix_policy: Option<MatrixPolicy>,
+    blocklist: Option<AddressBlocklist>,
+    // Edits of existing identities are deferred until no further edit was
+    // received within this delay.
+    settle_delay: Option<Duration>,
+    // Second challenges are replaced by short codes when sent.
+    second_challenge_codes: Option<SecondChallengeCodeConfig>,
+    // Web fields of new requests are challenged instead of reviewed manually.
+    web_challenges: bool,
+    // Networks for which new judgement requests are refused by config.
+    refused_requests: Vec<ChainName>,
+    // Messages of senders exceeding the limit are ignored for a while.
+    message_rate_limit: Option<MessageRateLimitConfig>,
+    // Shared by the primary and the read-only client.
+    query_log: Arc<QueryLog>,
+    context_locks: Arc<ContextLocks>,
 }
 
 impl Database {
     pub async fn new(uri: &str, db: &str) -> Result<Self> {
-        let client = Client::with_uri_str(uri).await?;
+        let query_log = Arc::new(QueryLog::default());
+        let client = instrumented_client(uri, &query_log).await?;
         let db = client.database(db);
 
         // Create collection if not exist (required for index creation)
@@ -85,7 +198,330 @@ impl Database {
             .create_index(model, None)
             .await?;
 
-        Ok(Database { client, db })
+        // Indexes for admin searches and judgement candidates.
+        for keys in [
+            doc! { "context.address": 1 },
+            doc! { "fields.value.value": 1 },
+            doc! { "context.chain": 1, "is_fully_verified": 1, "judgement_submitted": 1 },
+        ] {
+            db.collection::<JudgementState>(IDENTITY_COLLECTION)
+                .create_index(IndexModel::builder().keys(keys).build(), None)
+                .await?;
+        }
+
+        db.collection::<Event>(EVENT_COLLECTION)
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "message.value.context.address": 1 })
+                    .build(),
+                None,
+            )
+            .await?;
+
+        // Indexes for the event log queries.
+        for keys in [
+            doc! { "timestamp": 1 },
+            doc! { "log_seq": 1 },
+            doc! { "message.type": 1, "_id": 1 },
+        ] {
+            db.collection::<Event>(EVENT_COLLECTION)
+                .create_index(IndexModel::builder().keys(keys).build(), None)
+                .await?;
+        }
+
+        // Events inserted before deduplication keys were introduced have none.
+        db.collection::<Event>(EVENT_COLLECTION)
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "dedup_key": 1 })
+                    .options({
+                        let mut ops = IndexOptions::default();
+                        ops.unique = Some(true);
+                        ops.sparse = Some(true);
+                        ops
+                    })
+                    .build(),
+                None,
+            )
+            .await?;
+
+        db.collection::<OutboundMessage>(OUTBOUND_ARCHIVE)
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "context": 1, "timestamp": 1 })
+                    .build(),
+                None,
+            )
+            .await?;
+
+        db.collection::<OutboxEntry>(OUTBOX)
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "adapter": 1, "done": 1, "created": 1 })
+                    .build(),
+                None,
+            )
+            .await?;
+
+        db.collection::<VerificationRecord>(VERIFICATION_RECORDS)
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "context": 1, "timestamp": 1 })
+                    .build(),
+                None,
+            )
+            .await?;
+
+        db.collection::<VerificationRecord>(VERIFICATION_RECORDS)
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "outcome": 1, "timestamp": 1 })
+                    .build(),
+                None,
+            )
+            .await?;
+
+        db.collection::<FailureAlert>(FAILURE_ALERTS)
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "origin": 1, "raised_at": 1 })
+                    .build(),
+                None,
+            )
+            .await?;
+
+        db.collection::<ImportedCredential>(IMPORTED_CREDENTIALS)
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "context": 1, "imported_at": 1 })
+                    .build(),
+                None,
+            )
+            .await?;
+
+        // Those collections are written within transactions, which cannot
+        // create collections on older MongoDB versions.
+        for name in [
+            DISPLAY_NAMES,
+            NOTIFICATION_SEQUENCES,
+            BLOCKED_ADDRESSES,
+            BLOCKED_REQUESTS,
+            BLOCKLIST_AUDIT,
+            CHALLENGE_REGENERATIONS,
+            WITHDRAWN_REQUESTS,
+            PENDING_UPDATES,
+            VERIFICATION_RECORDS,
+            OUTBOX,
+            MESSAGE_RATE_LIMITS,
+            IMPORTED_CREDENTIALS,
+            COMPLIANCE_DECISIONS,
+            PROCESSED_OPERATIONS,
+        ] {
+            if !db
+                .list_collection_names(None)
+                .await?
+                .iter()
+                .any(|e| e == name)
+            {
+                db.create_collection(name, None).await?;
+            }
+        }
+
+        let model = IndexModel::builder()
+            .keys(doc! { "context": 1 })
+            .options({
+                let mut ops = IndexOptions::default();
+                ops.unique = Some(true);
+                ops
+            })
+            .build();
+
+        db.collection::<()>(NOTIFICATION_SEQUENCES)
+            .create_index(model, None)
+            .await?;
+
+        // Messages received by several adapters of a service are processed
+        // once.
+        db.collection::<()>(PROCESSED_MESSAGES)
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "service": 1, "message_id": 1 })
+                    .options({
+                        let mut ops = IndexOptions::default();
+                        ops.unique = Some(true);
+                        ops
+                    })
+                    .build(),
+                None,
+            )
+            .await?;
+
+        // State transitions of an operation, e.g. the processing of a
+        // message, are applied once.
+        db.collection::<()>(PROCESSED_OPERATIONS)
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "operation": 1 })
+                    .options({
+                        let mut ops = IndexOptions::default();
+                        ops.unique = Some(true);
+                        ops
+                    })
+                    .build(),
+                None,
+            )
+            .await?;
+
+        db.collection::<ReservedDisplayName>(RESERVED_DISPLAY_NAMES)
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "kind": 1, "value": 1 })
+                    .options({
+                        let mut ops = IndexOptions::default();
+                        ops.unique = Some(true);
+                        ops
+                    })
+                    .build(),
+                None,
+            )
+            .await?;
+
+        db.collection::<OriginAttempts>(MESSAGE_RATE_LIMITS)
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "origin": 1 })
+                    .options({
+                        let mut ops = IndexOptions::default();
+                        ops.unique = Some(true);
+                        ops
+                    })
+                    .build(),
+                None,
+            )
+            .await?;
+
+        // Keep the state of deleted display names, required for streaming
+        // removals to subscribers. Only supported by MongoDB 6.0 or later.
+        if let Err(err) = db
+            .run_command(
+                doc! {
+                    "collMod": DISPLAY_NAMES,
+                    "changeStreamPreAndPostImages": { "enabled": true },
+                },
+                None,
+            )
+            .await
+        {
+            warn!(
+                "Failed to enable pre-images for display names, removals will not be streamed: {:?}",
+                err
+            );
+        }
+
+        Ok(Database {
+            client,
+            db,
+            read: None,
+            hook: None,
+            disposable_emails: None,
+            matrix_policy: None,
+            blocklist: None,
+            settle_delay: None,
+            second_challenge_codes: None,
+            web_challenges: false,
+            refused_requests: vec![],
+            message_rate_limit: None,
+            query_log,
+            context_locks: Default::default(),
+        })
+    }
+    /// Uses a separate deployment (e.g. a read-only replica or a URI with a
+    /// `readPreference`) for the handle returned by `read_only`. Transactional
+    /// writes always go to the primary URI.
+    pub async fn with_read_uri(mut self, uri: &str) -> Result<Self> {
+        let client = instrumented_client(uri, &self.query_log).await?;
+        let db = client.database(self.db.name());
+
+        db.list_collection_names(None)
+            .await
+            .map_err(|err| anyhow!("Failed to connect to read-only database: {:?}", err))?;
+
+        self.read = Some((client, db));
+        Ok(self)
+    }
+    /// Commands taking longer than the threshold are logged.
+    pub fn with_slow_query_threshold(self, threshold: Duration) -> Self {
+        self.query_log.set_threshold(threshold);
+        self
+    }
+    /// Whether commands recently failed or exceeded the slow query threshold.
+    pub fn health(&self) -> &DbHealth {
+        self.query_log.health()
+    }
+    pub fn with_script_hook(mut self, hook: ScriptHook) -> Self {
+        self.hook = Some(hook);
+        self
+    }
+    pub fn with_disposable_emails(mut self, list: DisposableEmails) -> Self {
+        self.disposable_emails = Some(list);
+        self
+    }
+    pub fn with_matrix_policy(mut self, policy: MatrixPolicy) -> Self {
+        self.matrix_policy = Some(policy);
+        self
+    }
+    pub fn with_address_blocklist(mut self, blocklist: AddressBlocklist) -> Self {
+        self.blocklist = Some(blocklist);
+        self
+    }
+    pub fn with_settle_delay(mut self, delay: Duration) -> Self {
+        self.settle_delay = Some(delay);
+        self
+    }
+    pub fn with_second_challenge_codes(mut self, config: SecondChallengeCodeConfig) -> Self {
+        self.second_challenge_codes = Some(config);
+        self
+    }
+    pub fn with_web_challenges(mut self) -> Self {
+        self.web_challenges = true;
+        self
+    }
+    pub fn with_refused_requests(mut self, chains: Vec<ChainName>) -> Self {
+        self.refused_requests = chains;
+        self
+    }
+    pub fn with_message_rate_limit(mut self, config: MessageRateLimitConfig) -> Self {
+        self.message_rate_limit = Some(config);
+        self
+    }
+    pub fn settle_delay(&self) -> Option<Duration> {
+        self.settle_delay
+    }
+    pub fn web_challenges(&self) -> bool {
+        self.web_challenges
+    }
+    /// Returns a handle for query paths which only read from the database. If
+    /// no read-only URI is configured, this is the same as the primary handle.
+    pub fn read_only(&self) -> Database {
+        match &self.read {
+            Some((client, db)) => Database {
+                client: client.clone(),
+                db: db.clone(),
+                read: None,
+                hook: self.hook.clone(),
+                disposable_emails: self.disposable_emails.clone(),
+                matrix_policy: self.matrix_policy.clone(),
+                blocklist: self.blocklist.clone(),
+                settle_delay: self.settle_delay,
+                second_challenge_codes: self.second_challenge_codes,
+                web_challenges: self.web_challenges,
+                refused_requests: self.refused_requests.clone(),
+                message_rate_limit: self.message_rate_limit,
+                query_log: Arc::clone(&self.query_log),
+                context_locks: Arc::clone(&self.context_locks),
+            },
+            None => self.clone(),
+        }
     }
     async fn start_transaction(&self) -> Result<ClientSession> {
         let mut options = TransactionOptions::default();
@@ -95,6 +531,31 @@ impl Database {
         session.start_transaction(Some(options)).await?;
         Ok(session)
     }
+    /// Locks the identities matching the filter, before their state is read
+    /// within a transaction.
+    async fn lock_matching(&self, filter: Document) -> Result<ContextGuard> {
+        let mut cursor = self
+            .db
+            .collection::<Document>(IDENTITY_COLLECTION)
+            .find(
+                filter,
+                FindOptions::builder()
+                    .projection(doc! { "context": 1 })
+                    .build(),
+            )
+            .await?;
+
+        let mut contexts = vec![];
+        while let Some(doc) = cursor.next().await {
+            contexts.push(from_bson::<IdentityContext>(
+                doc?.get("context")
+                    .cloned()
+                    .ok_or_else(|| anyhow!("identity without context"))?,
+            )?);
+        }
+
+        Ok(self.context_locks.lock_all(contexts).await)
+    }
     /// Simply checks if a connection could be established to the database.
     pub async fn connectivity_check(&self) -> Result<()> {
         self.db
@@ -103,9 +564,90 @@ impl Database {
             .map_err(|err| anyhow!("Failed to connect to database: {:?}", err))
             .map(|_| ())
     }
+    /// Waits until the commands in progress completed, at most for the
+    /// timeout. Returns the number of commands which did not complete.
+    pub async fn flush(&self, timeout: Duration) -> usize {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let pending = self.query_log.pending_commands();
+            if pending == 0 || Instant::now() >= deadline {
+                return pending;
+            }
+
+            sleep(FLUSH_INTERVAL).await;
+        }
+    }
+    /// Checks the fields against the configured policies, returning the
+    /// rejected fields together with the event explaining the decision.
+    async fn policy_events(
+        &self,
+        state: &JudgementState,
+    ) -> Vec<(IdentityFieldValue, NotificationMessage)> {
+        let mut events = vec![];
+
+        for field in &state.fields {
+            if let Some(list) = &self.disposable_emails {
+                if let Some(domain) = list.matches(&field.value).await {
+                    events.push((
+                        field.value.clone(),
+                        NotificationMessage::DisposableEmail {
+                            context: state.context.clone(),
+                            field: field.value.clone(),
+                            domain,
+                            action: list.action(),
+                        },
+                    ));
+                }
+            }
+
+            if let Some(policy) = &self.matrix_policy {
+                if let Some(rejection) = policy.check_field(&field.value) {
+                    events.push((
+                        field.value.clone(),
+                        NotificationMessage::MatrixAccountRejected {
+                            context: state.context.clone(),
+                            field: field.value.clone(),
+                            reason: rejection.to_string(),
+                        },
+                    ));
+                }
+            }
+        }
+
+        events
+    }
+    /// Inserts a new judgement request or updates the fields of an existing
+    /// one. With a settle delay configured, updates are deferred (see
+    /// `apply_settled_updates`) and `false` is returned.
     pub async fn add_judgement_request(&self, request: &JudgementState) -> Result<bool> {
+        self.insert_judgement_request(request, self.settle_delay)
+            .await
+    }
+    async fn insert_judgement_request(
+        &self,
+        request: &JudgementState,
+        settle_delay: Option<Duration>,
+    ) -> Result<bool> {
+        // Identities with fields rejected by a policy (e.g. disposable email
+        // addresses) always require a manual review, independent of whether
+        // the field can still be verified.
+        let policy_events = self.policy_events(request).await;
+        let mut request = request.clone();
+        if !policy_events.is_empty() {
+            request.requires_manual_review = true;
+        }
+
+        // Flagged requests are only judged after a manual review.
+        if request.compliance_decision == Some(ComplianceDecision::Flag) {
+            request.requires_manual_review = true;
+        }
+
+        let _guard = self.context_locks.lock(&request.context).await;
         let mut session = self.start_transaction().await?;
         let coll = self.db.collection(IDENTITY_COLLECTION);
+        let mut known_fields = vec![];
+        let mut inserted = false;
 
         // Check if a request of the same address exists yet (occurs when a
         // field gets updated during pending judgement process).
@@ -133,33 +675,70 @@ impl Database {
                 if let Some(current_field) = current
                     .fields
                     .iter()
-                    .find(|current| current.value == new_field.value)
+                    .find(|current| current.value.is_equivalent(&new_field.value))
                 {
                     to_add.push(current_field.clone());
                 } else {
-                    to_add.push(new_field.clone());
+                    let mut new_field = new_field.clone();
+                    new_field.mark_challenge_sent(Timestamp::now());
+                    to_add.push(new_field);
                     has_changed = true;
                 }
             }
 
             // If nothing was modified, return (detect removed entries).
             if !has_changed && request.fields.len() == current.fields.len() {
+                // Edits which were reverted in the meantime are dropped.
+                if settle_delay.is_some() {
+                    self.db
+                        .collection::<PendingUpdate>(PENDING_UPDATES)
+                        .delete_one_with_session(
+                            doc! {
+                                "context": request.context.to_bson()?,
+                            },
+                            None,
+                            &mut session,
+                        )
+                        .await?;
+
+                    session.commit_transaction().await?;
+                }
+
+                return Ok(false);
+            }
+
+            if let Some(delay) = settle_delay {
+                self.defer_update(&request, delay, &mut session).await?;
+                session.commit_transaction().await?;
                 return Ok(false);
             }
 
+            known_fields = current
+                .fields
+                .iter()
+                .map(|field| field.value.clone())
+                .collect();
+
             // Set new fields.
             current.fields = to_add;
 
             // Update the final fields in the database. All deprecated fields
             // are overwritten.
+            let mut update = doc! {
+                "fields": current.fields.to_bson()?,
+                "requires_manual_review": request.requires_manual_review
+                    || current.compliance_decision == Some(ComplianceDecision::Flag),
+            };
+            if let Some(block_number) = request.requested_at_block {
+                update.insert("requested_at_block", block_number.to_bson()?);
+            }
+
             coll.update_one_with_session(
                 doc! {
                     "context": request.context.to_bson()?
                 },
                 doc! {
-                    "$set": {
-                        "fields": current.fields.to_bson()?
-                    }
+                    "$set": update,
                 },
                 None,
                 &mut session,
@@ -179,63 +758,284 @@ impl Database {
             self.process_fully_verified(&current.context, &mut session)
                 .await?;
         } else {
-            // Insert new identity.
-            coll.update_one_with_session(
-                doc! {
-                    "context": request.context.to_bson()?,
-                },
-                doc! {
-                    "$setOnInsert": request.to_document()?,
-                },
-                {
-                    let mut opt = UpdateOptions::default();
-                    opt.upsert = Some(true);
-                    Some(opt)
-                },
-                &mut session,
-            )
-            .await?;
+            // Insert new identity, its challenges are handed out from now on.
+            request.mark_challenges_sent(Timestamp::now());
+            let res = coll
+                .update_one_with_session(
+                    doc! {
+                        "context": request.context.to_bson()?,
+                    },
+                    doc! {
+                        "$setOnInsert": request.to_document()?,
+                    },
+                    {
+                        let mut opt = UpdateOptions::default();
+                        opt.upsert = Some(true);
+                        Some(opt)
+                    },
+                    &mut session,
+                )
+                .await?;
+
+            inserted = res.upserted_id.is_some();
+            if let (true, Some(decision)) = (inserted, request.compliance_decision) {
+                self.record_compliance_decision(&request.context, decision, &mut session)
+                    .await?;
+            }
+        }
+
+        // Record the decisions once per field value.
+        for (field, event) in policy_events {
+            if known_fields
+                .iter()
+                .any(|known: &IdentityFieldValue| known.is_equivalent(&field))
+            {
+                continue;
+            }
+
+            info!("Policy decision for {:?}: {:?}", request.context, event);
+            self.insert_event(event, &mut session).await?;
         }
 
         session.commit_transaction().await?;
 
+        if inserted {
+            metrics::observe_challenge_sent(request.context.chain, request.inserted_timestamp);
+        }
+
         Ok(true)
     }
-    #[cfg(test)]
-    pub async fn delete_judgement(&self, context: &IdentityContext) -> Result<()> {
-        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+    /// Inserts the judgement requests of identities which are not known yet
+    /// with a single write, e.g. on the initial sync with the Watcher. Requests
+    /// with policy or compliance decisions are skipped, as are identities which
+    /// were inserted in the meantime. Those must be passed to
+    /// `add_judgement_request`.
+    /// Returns whether each request was inserted.
+    pub async fn insert_new_judgement_requests(
+        &self,
+        requests: &[JudgementState],
+    ) -> Result<Vec<bool>> {
+        let mut inserted = vec![false; requests.len()];
 
-        let res = coll
-            .delete_one(
+        let mut candidates = vec![];
+        for (index, request) in requests.iter().enumerate() {
+            if request.compliance_decision.is_none() && self.policy_events(request).await.is_empty()
+            {
+                candidates.push(index);
+            }
+        }
+
+        if candidates.is_empty() {
+            return Ok(inserted);
+        }
+
+        let now = Timestamp::now();
+        let res = self
+            .db
+            .collection::<JudgementState>(IDENTITY_COLLECTION)
+            .insert_many(
+                candidates.iter().map(|index| {
+                    let mut request = requests[*index].clone();
+                    request.mark_challenges_sent(now);
+                    request
+                }),
+                InsertManyOptions::builder().ordered(false).build(),
+            )
+            .await;
+
+        // Indexes into `candidates`.
+        let duplicates: HashSet<usize> = match res {
+            Ok(_) => HashSet::new(),
+            Err(err) => match *err.kind {
+                ErrorKind::BulkWrite(BulkWriteFailure {
+                    write_errors: Some(ref errors),
+                    write_concern_error: None,
+                    ..
+                }) if errors.iter().all(|error| error.code == DUPLICATE_KEY_ERROR) => {
+                    errors.iter().map(|error| error.index).collect()
+                }
+                _ => return Err(err.into()),
+            },
+        };
+
+        for (position, index) in candidates.into_iter().enumerate() {
+            inserted[index] = !duplicates.contains(&position);
+            if inserted[index] {
+                let request = &requests[index];
+                metrics::observe_challenge_sent(request.context.chain, request.inserted_timestamp);
+            }
+        }
+
+        Ok(inserted)
+    }
+    /// Stores the edit as the pending update of the identity. The settle delay
+    /// only restarts if the edit differs from the pending one, since the
+    /// Watcher repeatedly sends the current on-chain state.
+    async fn defer_update(
+        &self,
+        request: &JudgementState,
+        delay: Duration,
+        session: &mut ClientSession,
+    ) -> Result<()> {
+        let coll = self.db.collection::<PendingUpdate>(PENDING_UPDATES);
+
+        let pending = coll
+            .find_one_with_session(
                 doc! {
-                    "context": context.to_bson()?,
+                    "context": request.context.to_bson()?,
                 },
                 None,
+                session,
             )
             .await?;
 
-        if res.deleted_count != 1 {
-            panic!()
+        if let Some(pending) = pending {
+            if pending.request.has_equivalent_fields(request) {
+                return Ok(());
+            }
         }
 
+        let update = PendingUpdate {
+            context: request.context.clone(),
+            request: request.clone(),
+            apply_at: Timestamp::with_offset(delay.as_secs()),
+        };
+
+        coll.update_one_with_session(
+            doc! {
+                "context": request.context.to_bson()?,
+            },
+            doc! {
+                "$set": update.to_document()?,
+            },
+            {
+                let mut opt = UpdateOptions::default();
+                opt.upsert = Some(true);
+                Some(opt)
+            },
+            session,
+        )
+        .await?;
+
+        debug!(
+            "Deferred update of {:?} until {:?}",
+            request.context, update.apply_at
+        );
+
         Ok(())
     }
-    pub async fn verify_manually(
+    /// Time at which the deferred edit of the identity is applied, if any.
+    pub async fn fetch_pending_update_time(
         &self,
         context: &IdentityContext,
-        field: &RawFieldName,
-        // Whether it should check if the idenity has been fully verified.
-        full_check: bool,
-        provided_session: Option<&mut ClientSession>,
-    ) -> Result<Option<()>> {
-        // If no `session` is provided, create a new local session.
-        let mut local_session = self.start_transaction().await?;
-        let should_commit;
+    ) -> Result<Option<Timestamp>> {
+        let coll = self.db.collection::<PendingUpdate>(PENDING_UPDATES);
 
-        let session = if let Some(session) = provided_session {
-            should_commit = false;
-            std::mem::drop(local_session);
-            session
+        Ok(coll
+            .find_one(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                None,
+            )
+            .await?
+            .map(|update| update.apply_at))
+    }
+    /// Applies the pending updates of the chain which did not receive further
+    /// edits within the settle delay. Returns the updated requests.
+    pub async fn apply_settled_updates(&self, chain: ChainName) -> Result<Vec<JudgementState>> {
+        let coll = self.db.collection::<PendingUpdate>(PENDING_UPDATES);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "context.chain": chain.to_bson()?,
+                    "apply_at": {
+                        "$lte": Timestamp::now().to_bson()?,
+                    },
+                },
+                None,
+            )
+            .await?;
+
+        let mut due = vec![];
+        while let Some(update) = cursor.next().await {
+            due.push(update?);
+        }
+
+        let mut applied = vec![];
+        for update in due {
+            // Skip the update if it was replaced by a newer edit in the
+            // meantime.
+            let res = coll
+                .delete_one(
+                    doc! {
+                        "context": update.context.to_bson()?,
+                        "apply_at": update.apply_at.to_bson()?,
+                    },
+                    None,
+                )
+                .await?;
+
+            if res.deleted_count == 0 {
+                continue;
+            }
+
+            // The identity might have been judged or withdrawn in the meantime.
+            if self.fetch_judgement_state(&update.context).await?.is_none() {
+                continue;
+            }
+
+            if self.insert_judgement_request(&update.request, None).await? {
+                applied.push(update.request);
+            }
+        }
+
+        Ok(applied)
+    }
+    #[cfg(test)]
+    pub async fn delete_judgement(&self, context: &IdentityContext) -> Result<()> {
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let res = coll
+            .delete_one(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                None,
+            )
+            .await?;
+
+        if res.deleted_count != 1 {
+            panic!()
+        }
+
+        Ok(())
+    }
+    pub async fn verify_manually(
+        &self,
+        context: &IdentityContext,
+        field: &RawFieldName,
+        // Whether it should check if the idenity has been fully verified.
+        full_check: bool,
+        provided_session: Option<&mut ClientSession>,
+    ) -> Result<Option<()>> {
+        // A provided session belongs to a transition which already locked the
+        // identity.
+        let _guard = if provided_session.is_none() {
+            Some(self.context_locks.lock(context).await)
+        } else {
+            None
+        };
+
+        // If no `session` is provided, create a new local session.
+        let mut local_session = self.start_transaction().await?;
+        let should_commit;
+
+        let session = if let Some(session) = provided_session {
+            should_commit = false;
+            std::mem::drop(local_session);
+            session
         } else {
             should_commit = true;
             &mut local_session
@@ -243,6 +1043,26 @@ impl Database {
 
         let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
 
+        // Web fields are only challenged if enabled when the identity was
+        // inserted.
+        let web_challenged = *field == RawFieldName::Web
+            && coll
+                .find_one_with_session(
+                    doc! {
+                        "context": context.to_bson()?,
+                        "fields": {
+                            "$elemMatch": {
+                                "value.type": "web",
+                                "challenge.type": "expected_message",
+                            }
+                        },
+                    },
+                    None,
+                    &mut *session,
+                )
+                .await?
+                .is_some();
+
         // Set the appropriate types for verification.
         let update = match field {
             // For "ChallengeType::ExpectedMessage".
@@ -270,6 +1090,14 @@ impl Database {
                     }
                 }
             }
+            // For "ChallengeType::ExpectedMessage".
+            RawFieldName::Web if web_challenged => {
+                doc! {
+                    "$set": {
+                        "fields.$.challenge.content.expected.is_verified": true,
+                    }
+                }
+            }
             // For "ChallengeType::Unsupported".
             RawFieldName::LegalName | RawFieldName::Web => {
                 doc! {
@@ -338,115 +1166,384 @@ impl Database {
 
         Ok(Some(()))
     }
-    pub async fn verify_message(&self, message: &ExternalMessage) -> Result<()> {
+    /// Verifies the unverified fields of a pending identity which match the
+    /// values of an attestation and records the attestation as their
+    /// provenance. Returns the imported fields, or `None` if the identity has
+    /// no pending judgement request.
+    pub async fn import_credentials(
+        &self,
+        attestation: &CredentialAttestation,
+    ) -> Result<Option<Vec<IdentityFieldValue>>> {
+        let context = &attestation.context;
+        let _guard = self.context_locks.lock(context).await;
         let mut session = self.start_transaction().await?;
-        let coll = self.db.collection(IDENTITY_COLLECTION);
 
-        // Fetch the current field state based on the message origin.
-        let mut cursor = coll
-            .find_with_session(
+        let state = match self
+            .db
+            .collection::<JudgementState>(IDENTITY_COLLECTION)
+            .find_one_with_session(
                 doc! {
-                    "fields.value": message.origin.to_bson()?,
+                    "context": context.to_bson()?,
+                    "judgement_submitted": false,
                 },
                 None,
                 &mut session,
             )
+            .await?
+        {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+
+        let now = Timestamp::now();
+        let mut imported = vec![];
+        for field in &state.fields {
+            let raw = match importable_field(&field.value) {
+                Some(raw) => raw,
+                None => continue,
+            };
+
+            // Values which differ from the attested ones were changed since,
+            // or were never verified by the issuer.
+            if field.challenge.is_verified()
+                || !attestation
+                    .fields
+                    .iter()
+                    .any(|attested| attested.is_equivalent(&field.value))
+            {
+                continue;
+            }
+
+            if self
+                .verify_manually(context, &raw, false, Some(&mut session))
+                .await?
+                .is_none()
+            {
+                continue;
+            }
+
+            self.db
+                .collection::<ImportedCredential>(IMPORTED_CREDENTIALS)
+                .insert_one_with_session(
+                    ImportedCredential {
+                        context: context.clone(),
+                        field: field.value.clone(),
+                        issuer: attestation.issuer.clone(),
+                        issued_at: Timestamp::from(attestation.issued_at),
+                        signature: attestation.signature.clone(),
+                        imported_at: now,
+                    },
+                    None,
+                    &mut session,
+                )
+                .await?;
+
+            self.insert_event(
+                NotificationMessage::CredentialImported {
+                    context: context.clone(),
+                    field: field.value.clone(),
+                    issuer: attestation.issuer.clone(),
+                },
+                &mut session,
+            )
             .await?;
 
-        // If a field was found, update it.
+            imported.push(field.value.clone());
+        }
+
+        if !imported.is_empty() {
+            self.process_fully_verified(context, &mut session).await?;
+        }
+
+        session.commit_transaction().await?;
+
+        Ok(Some(imported))
+    }
+    #[cfg(test)]
+    pub async fn fetch_imported_credentials(
+        &self,
+        context: &IdentityContext,
+    ) -> Result<Vec<ImportedCredential>> {
+        let mut cursor = self
+            .db
+            .collection::<ImportedCredential>(IMPORTED_CREDENTIALS)
+            .find(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                FindOptions::builder()
+                    .sort(doc! { "imported_at": 1, "_id": 1 })
+                    .build(),
+            )
+            .await?;
+
+        let mut credentials = vec![];
+        while let Some(credential) = cursor.next().await {
+            credentials.push(credential?);
+        }
+
+        Ok(credentials)
+    }
+    pub async fn verify_message(&self, message: &ExternalMessage) -> Result<()> {
+        // Fetch the current field state based on the message origin. Web
+        // fields of identities inserted before web challenges were enabled
+        // are not challenged.
+        let filter = doc! {
+            "fields": {
+                "$elemMatch": {
+                    "value": message.origin.to_bson()?,
+                    "challenge.type": "expected_message",
+                }
+            },
+        };
+
+        // A message which is processed again, e.g. after a redelivery or by
+        // another instance, must not apply its state transitions twice.
+        let operation = message.operation_key();
+
+        let _guard = self.lock_matching(filter.clone()).await?;
+        let mut session = self.start_transaction().await?;
+        let coll = self.db.collection(IDENTITY_COLLECTION);
+
+        let mut cursor = coll.find_with_session(filter, None, &mut session).await?;
+
+        let mut states = vec![];
         while let Some(doc) = cursor.next(&mut session).await {
-            let state: JudgementState = from_document(doc?)?;
+            states.push(from_document::<JudgementState>(doc?)?);
+        }
+
+        if !states.is_empty() && !self.claim_operation(&operation, &mut session).await? {
+            debug!("Skipping message {:?}, already processed", message.id);
+            return Ok(());
+        }
+
+        // Senders exceeding the rate limit are ignored for a while, so they
+        // cannot grow the failed attempts and the event log without bounds.
+        if let (Some(config), false) = (&self.message_rate_limit, states.is_empty()) {
+            match self
+                .register_message_attempt(&message.origin, config, &mut session)
+                .await?
+            {
+                RateLimitDecision::Allowed => {}
+                RateLimitDecision::Blocked => {
+                    session.commit_transaction().await?;
+                    return Ok(());
+                }
+                RateLimitDecision::BlockedNow { until } => {
+                    warn!(
+                        "Blocking messages of {:?} until {:?}, rate limit exceeded",
+                        message.origin, until
+                    );
+
+                    for state in &states {
+                        if let Some(field) = state
+                            .fields
+                            .iter()
+                            .find(|field| field.value.matches_origin(message))
+                        {
+                            self.insert_event(
+                                Event::from(NotificationMessage::MessagesRateLimited {
+                                    context: state.context.clone(),
+                                    field: field.value.clone(),
+                                    until,
+                                })
+                                .with_operation(&operation),
+                                &mut session,
+                            )
+                            .await?;
+                        }
+                    }
+
+                    session.commit_transaction().await?;
+                    return Ok(());
+                }
+            }
+        }
+
+        // The contact value might be specified by multiple pending identities,
+        // in which case the message must also contain the disambiguation token
+        // of the intended address.
+        let shared: Vec<IdentityContext> = states
+            .iter()
+            .filter(|state| {
+                state.fields.iter().any(|field| {
+                    field.value.matches_origin(message) && !field.challenge.is_verified()
+                })
+            })
+            .map(|state| state.context.clone())
+            .collect();
+
+        // If a field was found, update it.
+        for state in states {
             let field_state = state
                 .fields
                 .iter()
                 .find(|field| field.value.matches_origin(message))
                 .unwrap();
 
+            let context = state.context.clone();
+            let field_value = field_state.value.clone();
+
+            let token = if shared.len() > 1 && shared.contains(&context) {
+                Some(verification::disambiguation_token(&context.address))
+            } else {
+                None
+            };
+
             // If the message contains the challenge, set it as valid (or
             // invalid if otherwise).
+            let mut outcome = verification::verify_message(&field_state.challenge, message, token)?;
+            if let (MessageOutcome::Verified { .. }, Some(list)) =
+                (&outcome, &self.disposable_emails)
+            {
+                if list.action() == DisposableEmailAction::Refuse
+                    && list.matches(&field_value).await.is_some()
+                {
+                    outcome = MessageOutcome::Vetoed {
+                        reason: Some("Disposable email addresses are not accepted".to_string()),
+                    };
+                }
+            }
 
-            let context = state.context.clone();
-            let field_value = field_state.value.clone();
+            if let (MessageOutcome::Verified { .. }, Some(policy)) = (&outcome, &self.matrix_policy)
+            {
+                if let Some(rejection) = policy.check_field(&field_value) {
+                    outcome = MessageOutcome::Vetoed {
+                        reason: Some(rejection.to_string()),
+                    };
+                }
+            }
 
-            let challenge = &field_state.challenge;
-            if !challenge.is_verified() {
-                match challenge {
-                    ChallengeType::ExpectedMessage { expected, second } => {
-                        // Only proceed if the expected challenge has not been verified yet.
-                        if !expected.is_verified {
-                            if expected.is_message_valid(message) {
-                                // Update field state. Be more specific with the query in order
-                                // to verify the correct field (in theory, there could be
-                                // multiple pending requests with the same external account
-                                // specified).
-                                coll.update_one_with_session(
-                                    doc! {
-                                        "context": context.to_bson()?,
-                                        "fields.value": message.origin.to_bson()?,
-                                    },
-                                    doc! {
-                                        "$set": {
-                                            "fields.$.challenge.content.expected.is_verified": true,
-                                        }
-                                    },
-                                    None,
-                                    &mut session,
-                                )
-                                .await?;
-
-                                self.insert_event(
-                                    NotificationMessage::FieldVerified {
-                                        context: context.clone(),
-                                        field: field_value.clone(),
-                                    },
-                                    &mut session,
-                                )
-                                .await?;
-
-                                if second.is_some() {
-                                    self.insert_event(
-                                        NotificationMessage::AwaitingSecondChallenge {
-                                            context: context.clone(),
-                                            field: field_value,
-                                        },
-                                        &mut session,
-                                    )
-                                    .await?;
-                                }
-                            } else {
-                                // Update field state.
-                                coll.update_many_with_session(
-                                    doc! {
-                                        "context": context.to_bson()?,
-                                        "fields.value": message.origin.to_bson()?,
-                                    },
-                                    doc! {
-                                        "$inc": {
-                                            "fields.$.failed_attempts": 1isize.to_bson()?,
-                                        }
-                                    },
-                                    None,
-                                    &mut session,
-                                )
-                                .await?;
-
-                                self.insert_event(
-                                    NotificationMessage::FieldVerificationFailed {
-                                        context: context.clone(),
-                                        field: field_value,
-                                    },
-                                    &mut session,
-                                )
-                                .await?;
+            if let (MessageOutcome::Verified { .. }, Some(hook)) = (&outcome, &self.hook) {
+                let decision = hook.on_message(&context, &field_value, &message.values);
+                self.annotate(&context, &decision.notes, &mut session)
+                    .await?;
+
+                if decision.veto {
+                    outcome = MessageOutcome::Vetoed {
+                        reason: decision.reason,
+                    };
+                }
+            }
+
+            let cause = outcome.failure_cause(&field_state.challenge, message);
+
+            let recorded = match outcome {
+                MessageOutcome::Verified { .. } => Some(VerificationOutcome::Verified),
+                MessageOutcome::Failed => Some(VerificationOutcome::Failed),
+                MessageOutcome::Vetoed { .. } => Some(VerificationOutcome::Vetoed),
+                MessageOutcome::Expired => Some(VerificationOutcome::Expired),
+                MessageOutcome::AlreadyVerified | MessageOutcome::DisambiguationRequired => None,
+            };
+
+            if let Some(outcome) = recorded {
+                self.db
+                    .collection::<VerificationRecord>(VERIFICATION_RECORDS)
+                    .insert_one_with_session(
+                        VerificationRecord {
+                            context: context.clone(),
+                            origin: message.origin.clone(),
+                            message_id: message.id,
+                            outcome,
+                            cause,
+                            content_hash: cause.map(|_| message.content_hash()),
+                            metadata: message.metadata.clone(),
+                            timestamp: message.timestamp,
+                        },
+                        None,
+                        &mut session,
+                    )
+                    .await?;
+            }
+
+            match outcome {
+                MessageOutcome::AlreadyVerified
+                | MessageOutcome::DisambiguationRequired
+                | MessageOutcome::Expired => {}
+                MessageOutcome::Vetoed { .. } => {
+                    self.flag_for_manual_review(&context, &mut session).await?;
+                }
+                MessageOutcome::Verified { .. } => {
+                    // Identities inserted before the timestamp was recorded
+                    // fall back to the request.
+                    metrics::observe_field_verified(
+                        context.chain,
+                        &field_value,
+                        field_state
+                            .challenge_sent
+                            .unwrap_or(state.inserted_timestamp),
+                    );
+
+                    // Update field state. Be more specific with the query in order
+                    // to verify the correct field (in theory, there could be
+                    // multiple pending requests with the same external account
+                    // specified).
+                    coll.update_one_with_session(
+                        doc! {
+                            "context": context.to_bson()?,
+                            "fields.value": message.origin.to_bson()?,
+                        },
+                        doc! {
+                            "$set": {
+                                "fields.$.challenge.content.expected.is_verified": true,
+                            },
+                            "$unset": {
+                                "fields.$.last_failure": "",
                             }
-                        }
-                    }
-                    _ => {
-                        return Err(anyhow!(
-                            "Invalid challenge type when verifying message. This is a bug"
-                        ))
-                    }
+                        },
+                        None,
+                        &mut session,
+                    )
+                    .await?;
                 }
+                MessageOutcome::Failed => {
+                    // Update field state.
+                    coll.update_many_with_session(
+                        doc! {
+                            "context": context.to_bson()?,
+                            "fields.value": message.origin.to_bson()?,
+                        },
+                        doc! {
+                            "$inc": {
+                                "fields.$.failed_attempts": 1isize.to_bson()?,
+                            }
+                        },
+                        None,
+                        &mut session,
+                    )
+                    .await?;
+                }
+            }
+
+            if let Some(cause) = cause {
+                coll.update_one_with_session(
+                    doc! {
+                        "context": context.to_bson()?,
+                        "fields.value": message.origin.to_bson()?,
+                    },
+                    doc! {
+                        "$set": {
+                            "fields.$.last_failure": FieldFailure::new(cause, message).to_bson()?,
+                        }
+                    },
+                    None,
+                    &mut session,
+                )
+                .await?;
+            }
+
+            let mut events = outcome.events(&context, &field_value);
+            if token.is_some() {
+                events.extend(
+                    verification::shared_contact_events(&shared, &field_value)
+                        .into_iter()
+                        .filter(|event| event.context() == &context),
+                );
+            }
+
+            for event in events {
+                self.insert_event(Event::from(event).with_operation(&operation), &mut session)
+                    .await?;
             }
 
             // Check if the identity is fully verified.
@@ -458,6 +1555,41 @@ impl Database {
 
         Ok(())
     }
+    /// Counts the message towards the rate limit of its sender.
+    async fn register_message_attempt(
+        &self,
+        origin: &ExternalMessageType,
+        config: &MessageRateLimitConfig,
+        session: &mut ClientSession,
+    ) -> Result<RateLimitDecision> {
+        let coll = self.db.collection::<OriginAttempts>(MESSAGE_RATE_LIMITS);
+        let now = Timestamp::now();
+
+        let mut attempts = coll
+            .find_one_with_session(doc! { "origin": origin.to_bson()? }, None, session)
+            .await?
+            .unwrap_or_else(|| OriginAttempts::new(origin.clone(), now));
+
+        let decision = attempts.register(config, now);
+
+        coll.update_one_with_session(
+            doc! {
+                "origin": origin.to_bson()?,
+            },
+            doc! {
+                "$set": attempts.to_document()?,
+            },
+            {
+                let mut opt = UpdateOptions::default();
+                opt.upsert = Some(true);
+                Some(opt)
+            },
+            session,
+        )
+        .await?;
+
+        Ok(decision)
+    }
     /// Check if all fields have been verified.
     async fn process_fully_verified(
         &self,
@@ -478,13 +1610,32 @@ impl Database {
             .await?
             .expect("Failed to retrieve full state for processing (this is a bug)");
 
-        if state.check_full_verification() {
-            // Create a timed delay for issuing judgments. Between 30 seconds to
-            // 5 minutes. This is used to prevent timing attacks where a user
-            // updates the identity right before the judgement is issued.
+        if verification::verification_transition(&state) == VerificationTransition::FullyVerified {
+            if let (false, Some(hook)) = (state.is_fully_verified, &self.hook) {
+                let decision = hook.on_judgement(&state);
+                self.annotate(&state.context, &decision.notes, session)
+                    .await?;
+
+                if decision.veto {
+                    if self.flag_for_manual_review(&state.context, session).await? {
+                        self.insert_event(
+                            NotificationMessage::VerificationVetoed {
+                                context: state.context.clone(),
+                                field: None,
+                                reason: decision.reason,
+                            },
+                            session,
+                        )
+                        .await?;
+                    }
+
+                    return Ok(());
+                }
+            }
+
+            // Create a timed delay for issuing judgments.
             let now = Timestamp::now();
-            let offset = thread_rng().gen_range(30..300);
-            let issue_at = Timestamp::with_offset(offset);
+            let issue_at = Timestamp::with_offset(verification::judgement_delay());
 
             let res = coll
                 .update_one_with_session(
@@ -535,89 +1686,137 @@ impl Database {
 
         Ok(())
     }
-    pub async fn verify_second_challenge(&self, mut request: VerifyChallenge) -> Result<bool> {
-        let mut session = self.start_transaction().await?;
-        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+    /// Attaches the notes of the script hook to the identity.
+    async fn annotate(
+        &self,
+        context: &IdentityContext,
+        notes: &[String],
+        session: &mut ClientSession,
+    ) -> Result<()> {
+        if notes.is_empty() {
+            return Ok(());
+        }
 
-        let mut verified = false;
+        info!("Script hook annotated {:?}: {:?}", context, notes);
 
-        // Trim received challenge, just in case.
-        request.challenge = request.challenge.trim().to_string();
+        self.db
+            .collection::<JudgementState>(IDENTITY_COLLECTION)
+            .update_one_with_session(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                doc! {
+                    "$addToSet": {
+                        "annotations": {
+                            "$each": notes.to_bson()?,
+                        }
+                    }
+                },
+                None,
+                session,
+            )
+            .await?;
 
-        // Query database.
-        let mut cursor = coll
-            .find_with_session(
+        Ok(())
+    }
+    /// Returns whether the identity was not flagged before.
+    async fn flag_for_manual_review(
+        &self,
+        context: &IdentityContext,
+        session: &mut ClientSession,
+    ) -> Result<bool> {
+        let res = self
+            .db
+            .collection::<JudgementState>(IDENTITY_COLLECTION)
+            .update_one_with_session(
+                doc! {
+                    "context": context.to_bson()?,
+                    "requires_manual_review": { "$ne": true },
+                },
                 doc! {
-                    "fields.value": request.entry.to_bson()?,
+                    "$set": {
+                        "requires_manual_review": true,
+                    }
                 },
                 None,
-                &mut session,
+                session,
             )
             .await?;
 
+        Ok(res.modified_count != 0)
+    }
+    pub async fn verify_second_challenge(&self, mut request: VerifyChallenge) -> Result<bool> {
+        let filter = doc! {
+            "fields.value": request.entry.to_bson()?,
+        };
+
+        let _guard = self.lock_matching(filter.clone()).await?;
+        let mut session = self.start_transaction().await?;
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let mut verified = false;
+
+        // Trim received challenge, just in case.
+        request.challenge = request.challenge.trim().to_string();
+
+        // Query database.
+        let mut cursor = coll.find_with_session(filter, None, &mut session).await?;
+
         while let Some(state) = cursor.next(&mut session).await {
-            let mut state = state?;
+            let state = state?;
             let field_state = state
                 .fields
-                .iter_mut()
+                .iter()
                 .find(|field| field.value == request.entry)
                 .unwrap();
 
             let context = state.context.clone();
             let field_value = field_state.value.clone();
 
-            match &mut field_state.challenge {
-                ChallengeType::ExpectedMessage {
-                    expected: _,
-                    second,
-                } => {
-                    // This should never happens, but the provided field value
-                    // depends on user input, so...
-                    if second.is_none() {
-                        continue;
-                    }
+            let outcome =
+                verification::verify_second_challenge(&field_state.challenge, &request.challenge)?;
 
-                    let second = second.as_mut().unwrap();
-                    if request.challenge.contains(&second.value) {
-                        verified = true;
+            if outcome == SecondChallengeOutcome::Verified {
+                verified = true;
 
-                        coll.update_one_with_session(
-                            doc! {
-                                "fields.value": request.entry.to_bson()?,
-                                "fields.challenge.content.second.value": request.challenge.to_bson()?,
-                            },
-                            doc! {
-                                "$set": {
-                                    "fields.$.challenge.content.second.is_verified": true.to_bson()?,
-                                }
-                            },
-                            None,
-                            &mut session
-                        )
-                        .await?;
+                coll.update_one_with_session(
+                    doc! {
+                        "fields.value": request.entry.to_bson()?,
+                        "fields.challenge.content.second.value": request.challenge.to_bson()?,
+                    },
+                    doc! {
+                        "$set": {
+                            "fields.$.challenge.content.second.is_verified": true.to_bson()?,
+                        }
+                    },
+                    None,
+                    &mut session,
+                )
+                .await?;
+            } else if outcome == SecondChallengeOutcome::Failed
+                && matches!(&field_state.challenge, ChallengeType::ExpectedMessage {
+                    second: Some(second),
+                    ..
+                } if second.is_code())
+            {
+                coll.update_one_with_session(
+                    doc! {
+                        "context": context.to_bson()?,
+                        "fields.value": request.entry.to_bson()?,
+                    },
+                    doc! {
+                        "$inc": {
+                            "fields.$.challenge.content.second.remaining_attempts": -1,
+                        }
+                    },
+                    None,
+                    &mut session,
+                )
+                .await?;
+            }
 
-                        self.insert_event(
-                            NotificationMessage::SecondFieldVerified {
-                                context: context.clone(),
-                                field: field_value.clone(),
-                            },
-                            &mut session,
-                        )
-                        .await?;
-                    } else {
-                        self.insert_event(
-                            NotificationMessage::SecondFieldVerificationFailed {
-                                context: context.clone(),
-                                field: field_value.clone(),
-                            },
-                            &mut session,
-                        )
-                        .await?;
-                    }
-                }
-                _ => {
-                    panic!("Invalid challenge type when verifying message");
-                }
+            for event in outcome.events(&context, &field_value) {
+                self.insert_event(event, &mut session).await?;
             }
 
             // Check if the identity is fully verified.
@@ -629,6 +1828,47 @@ impl Database {
 
         Ok(verified)
     }
+    /// Returns the second challenge which is sent to the user. If short codes
+    /// are configured, a new code is generated and replaces the previous one.
+    pub async fn issue_second_challenge(
+        &self,
+        context: &IdentityContext,
+        field: &IdentityFieldValue,
+    ) -> Result<ExpectedMessage> {
+        let config = match &self.second_challenge_codes {
+            Some(config) => config,
+            None => return self.fetch_second_challenge(context, field).await,
+        };
+
+        let code = config.generate();
+        let res = self
+            .db
+            .collection::<JudgementState>(IDENTITY_COLLECTION)
+            .update_one(
+                doc! {
+                    "context": context.to_bson()?,
+                    "fields": {
+                        "$elemMatch": {
+                            "value": field.to_bson()?,
+                            "challenge.content.second.is_verified": false,
+                        }
+                    },
+                },
+                doc! {
+                    "$set": {
+                        "fields.$.challenge.content.second": code.to_bson()?,
+                    }
+                },
+                None,
+            )
+            .await?;
+
+        if res.matched_count == 0 {
+            return Err(anyhow!("No pending second challenge found for {:?}", field));
+        }
+
+        Ok(code)
+    }
     pub async fn fetch_second_challenge(
         &self,
         context: &IdentityContext,
@@ -673,200 +1913,2789 @@ impl Database {
             Err(anyhow!("No entry found for {:?}", field))
         }
     }
-    pub async fn fetch_events(
-        &mut self,
-        event_tracker: &mut EventCursor,
-    ) -> Result<Vec<NotificationMessage>> {
-        #[derive(Debug, Deserialize)]
-        struct EventWrapper {
-            #[serde(rename = "_id")]
-            id: bson::oid::ObjectId,
-            #[serde(flatten)]
-            event: Event,
-        }
-
-        let coll = self.db.collection(EVENT_COLLECTION);
+    /// Checks whether the message contains the pending challenge of the
+    /// field, the same way as `verify_message` does, without modifying any
+    /// state.
+    pub async fn attest_message(
+        &self,
+        context: &IdentityContext,
+        field: &RawFieldName,
+        message: &str,
+    ) -> Result<AttestationOutcome> {
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
 
-        let mut cursor = coll
-            .find(
+        let state = coll
+            .find_one(
                 doc! {
-                    "timestamp": {
-                        "$gte": event_tracker.timestamp.raw().to_bson()?,
-                    }
+                    "context": context.to_bson()?,
+                    "judgement_submitted": false,
                 },
                 None,
             )
             .await?;
 
-        let mut events = vec![];
-
-        while let Some(doc) = cursor.next().await {
-            let wrapper = from_document::<EventWrapper>(doc?)?;
-            let hex_id = wrapper.id.to_hex();
-
-            if event_tracker.fetched_ids.contains_key(&hex_id) {
-                continue;
-            }
+        let field_state = match state.as_ref().and_then(|state| {
+            state
+                .fields
+                .iter()
+                .find(|f| f.value.type_name() == field.to_string())
+        }) {
+            Some(field_state) => field_state,
+            None => return Ok(AttestationOutcome::NotFound),
+        };
 
-            // Save event
-            let timestamp = wrapper.event.timestamp;
-            events.push(wrapper);
+        let expected = match &field_state.challenge {
+            ChallengeType::ExpectedMessage { expected, .. } => expected,
+            _ => return Ok(AttestationOutcome::NotFound),
+        };
 
-            // Track event in EventCursor
-            event_tracker.fetched_ids.insert(hex_id, timestamp);
-            event_tracker.timestamp = event_tracker.timestamp.max(timestamp);
+        if expected.is_verified {
+            return Ok(AttestationOutcome::AlreadyVerified);
+        } else if expected.is_code() {
+            return Ok(AttestationOutcome::Unsupported);
+        } else if !message.contains(expected.value.as_str()) {
+            return Ok(AttestationOutcome::DoesNotMatch);
         }
 
-        // Clean cache, only keep ids of the last 10 seconds.
-        let current = event_tracker.timestamp.raw();
-        event_tracker
-            .fetched_ids
-            .retain(|_, timestamp| timestamp.raw() > current - 10);
+        // The disambiguation token is only required if other identities are
+        // pending with the same contact value.
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "fields.value": field_state.value.to_bson()?,
+                },
+                None,
+            )
+            .await?;
 
-        // Sort by id, ascending.
-        events.sort_by(|a, b| a.id.cmp(&b.id));
+        let mut shared = 0;
+        while let Some(state) = cursor.next().await {
+            if state?
+                .fields
+                .iter()
+                .any(|f| f.value == field_state.value && !f.challenge.is_verified())
+            {
+                shared += 1;
+            }
+        }
 
-        Ok(events
-            .into_iter()
-            .map(|wrapper| wrapper.event.message)
-            .collect())
+        if shared > 1 && !message.contains(verification::disambiguation_token(&context.address)) {
+            Ok(AttestationOutcome::DisambiguationRequired)
+        } else {
+            Ok(AttestationOutcome::Matches)
+        }
     }
-    pub async fn fetch_judgement_state(
+    /// Replaces the challenge of a pending field, unless it was already
+    /// regenerated `limit` times since the given timestamp. If only the second
+    /// challenge is pending, just that one is replaced and sent again.
+    pub async fn regenerate_challenge(
         &self,
         context: &IdentityContext,
-    ) -> Result<Option<JudgementState>> {
-        let coll = self.db.collection(IDENTITY_COLLECTION);
+        field: &RawFieldName,
+        limit: usize,
+        since: Timestamp,
+    ) -> Result<RegenerationOutcome> {
+        let _guard = self.context_locks.lock(context).await;
+        let mut session = self.start_transaction().await?;
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
 
-        // Find the context.
-        let doc = coll
-            .find_one(
+        let state = coll
+            .find_one_with_session(
                 doc! {
                     "context": context.to_bson()?,
+                    "judgement_submitted": false,
                 },
                 None,
+                &mut session,
             )
             .await?;
 
-        if let Some(doc) = doc {
-            Ok(Some(from_document(doc)?))
-        } else {
-            // Not active request exists.
-            Ok(None)
-        }
-    }
-    pub async fn fetch_judgement_candidates(
-        &self,
-        network: ChainName,
-    ) -> Result<Vec<JudgementState>> {
-        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+        let field_state = match state.as_ref().and_then(|state| {
+            state
+                .fields
+                .iter()
+                .find(|f| f.value.type_name() == field.to_string())
+        }) {
+            Some(field_state) => field_state,
+            None => return Ok(RegenerationOutcome::NotFound),
+        };
 
-        let mut cursor = coll
-            .find(
+        let (challenge, outcome) = match &field_state.challenge {
+            ChallengeType::ExpectedMessage { expected, second } if !expected.is_verified => (
+                ChallengeType::ExpectedMessage {
+                    expected: ExpectedMessage::random(),
+                    second: second.as_ref().map(|_| ExpectedMessage::random()),
+                },
+                RegenerationOutcome::Regenerated,
+            ),
+            ChallengeType::ExpectedMessage {
+                expected,
+                second: Some(second),
+            } if !second.is_verified => (
+                ChallengeType::ExpectedMessage {
+                    expected: expected.clone(),
+                    second: Some(ExpectedMessage::random()),
+                },
+                RegenerationOutcome::SecondChallengeResent,
+            ),
+            _ => return Ok(RegenerationOutcome::AlreadyVerified),
+        };
+
+        let regenerations = self.db.collection::<Document>(CHALLENGE_REGENERATIONS);
+        let count = regenerations
+            .count_documents_with_session(
                 doc! {
-                    "context.chain": network.as_str().to_bson()?,
-                    "is_fully_verified": true,
-                    "judgement_submitted": false,
-                    "issue_judgement_at": {
-                        "$lt": Timestamp::now().to_bson()?,
+                    "context": context.to_bson()?,
+                    "field": field.to_string(),
+                    "timestamp": {
+                        "$gt": since.to_bson()?,
                     }
                 },
                 None,
+                &mut session,
             )
             .await?;
 
-        let mut completed = vec![];
-        while let Some(state) = cursor.next().await {
-            completed.push(state?);
+        if count as usize >= limit {
+            return Ok(RegenerationOutcome::RateLimited);
         }
 
-        Ok(completed)
-    }
-    // (Warning) This fully verifies the identity without having to verify
-    // individual fields.
-    pub async fn full_manual_verification(&self, context: &IdentityContext) -> Result<bool> {
-        let mut session = self.start_transaction().await?;
-        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+        let mut update = doc! {
+            "fields.$.challenge": challenge.to_bson()?,
+            "fields.$.failed_attempts": 0_i64,
+        };
+        if outcome == RegenerationOutcome::Regenerated {
+            update.insert("fields.$.challenge_sent", Timestamp::now().to_bson()?);
+        }
 
-        // Create a timed delay for issuing judgments. Between 30 seconds to
-        // 5 minutes. This is used to prevent timing attacks where a user
-        // updates the identity right before the judgement is issued.
-        let now = Timestamp::now();
-        let offset = thread_rng().gen_range(30..300);
-        let issue_at = Timestamp::with_offset(offset);
+        coll.update_one_with_session(
+            doc! {
+                "context": context.to_bson()?,
+                "fields.value": field_state.value.to_bson()?,
+            },
+            doc! {
+                "$set": update,
+                "$unset": {
+                    "fields.$.last_failure": "",
+                }
+            },
+            None,
+            &mut session,
+        )
+        .await?;
 
-        let res = coll
-            .update_one_with_session(
+        regenerations
+            .insert_one_with_session(
                 doc! {
                     "context": context.to_bson()?,
-                },
-                doc! {
-                    "$set": {
-                        "is_fully_verified": true,
-                        "judgement_submitted": false,
-                        "completion_timestamp": now.to_bson()?,
-                        "issue_judgement_at": issue_at.to_bson()?,
-                    }
+                    "field": field.to_string(),
+                    "timestamp": Timestamp::now().to_bson()?,
                 },
                 None,
                 &mut session,
             )
             .await?;
 
-        // Create event.
-        if res.modified_count == 1 {
-            // Verify all possible fields. Unused fields are silently ignored.
-            let _ = self
-                .verify_manually(context, &RawFieldName::LegalName, false, Some(&mut session))
-                .await?;
-            let _ = self
-                .verify_manually(
-                    context,
-                    &RawFieldName::DisplayName,
-                    false,
-                    Some(&mut session),
+        let event = match outcome {
+            RegenerationOutcome::SecondChallengeResent => {
+                NotificationMessage::AwaitingSecondChallenge {
+                    context: context.clone(),
+                    field: field_state.value.clone(),
+                }
+            }
+            _ => NotificationMessage::ChallengeRegenerated {
+                context: context.clone(),
+                field: field_state.value.clone(),
+            },
+        };
+
+        self.insert_event(event, &mut session).await?;
+        session.commit_transaction().await?;
+
+        Ok(outcome)
+    }
+    /// Applies the expiry to the unverified challenges of pending identities
+    /// and informs the users about expired challenges. Returns the number of
+    /// challenges which expired.
+    pub async fn expire_challenges(&self, ttl: u64, now: Timestamp) -> Result<usize> {
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "judgement_submitted": false,
+                    "is_fully_verified": false,
+                    "fields": {
+                        "$elemMatch": {
+                            "challenge.type": "expected_message",
+                            "challenge.content.expected.is_verified": false,
+                            "challenge.content.expected.expired": {
+                                "$ne": true,
+                            },
+                        }
+                    },
+                },
+                None,
+            )
+            .await?;
+
+        let mut states = vec![];
+        while let Some(state) = cursor.next().await {
+            states.push(state?);
+        }
+
+        let mut expired = 0;
+        for state in states {
+            let _guard = self.context_locks.lock(&state.context).await;
+            let mut session = self.start_transaction().await?;
+
+            for mut field in state.fields {
+                if !verification::expire_challenge(&mut field.challenge, ttl, now) {
+                    continue;
+                }
+
+                let expected = match &field.challenge {
+                    ChallengeType::ExpectedMessage { expected, .. } => expected,
+                    _ => continue,
+                };
+
+                // The field might have been verified in the meantime.
+                let res = coll
+                    .update_one_with_session(
+                        doc! {
+                            "context": state.context.to_bson()?,
+                            "fields": {
+                                "$elemMatch": {
+                                    "value": field.value.to_bson()?,
+                                    "challenge.content.expected.is_verified": false,
+                                }
+                            },
+                        },
+                        doc! {
+                            "$set": {
+                                "fields.$.challenge.content.expected.valid_until": expected.valid_until.to_bson()?,
+                                "fields.$.challenge.content.expected.expired": expected.expired,
+                            }
+                        },
+                        None,
+                        &mut session,
+                    )
+                    .await?;
+
+                if res.modified_count > 0 && expected.expired {
+                    self.insert_event(
+                        NotificationMessage::ChallengeExpired {
+                            context: state.context.clone(),
+                            field: field.value.clone(),
+                        },
+                        &mut session,
+                    )
+                    .await?;
+
+                    expired += 1;
+                }
+            }
+
+            session.commit_transaction().await?;
+        }
+
+        Ok(expired)
+    }
+    /// Schedules a reminder for pending identities requested before `before`
+    /// with a single unverified field, sent via each of their verified
+    /// contact fields. Every identity is reminded at most once. Returns the
+    /// number of reminded identities.
+    pub async fn schedule_nudges(&self, before: Timestamp, now: Timestamp) -> Result<usize> {
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "judgement_submitted": false,
+                    "is_fully_verified": false,
+                    "requires_manual_review": {
+                        "$ne": true,
+                    },
+                    "nudged_at": null,
+                    "inserted_timestamp": {
+                        "$lt": before.to_bson()?,
+                    },
+                },
+                None,
+            )
+            .await?;
+
+        let mut states = vec![];
+        while let Some(state) = cursor.next().await {
+            states.push(state?);
+        }
+
+        let mut nudged = 0;
+        for state in states {
+            if state.last_unverified_field().is_none() {
+                continue;
+            }
+
+            let entries: Vec<OutboxEntry> = state
+                .verified_channels()
+                .into_iter()
+                .filter_map(|field| OutboxEntry::nudge(state.context.clone(), field.clone()))
+                .collect();
+
+            if entries.is_empty() {
+                continue;
+            }
+
+            let _guard = self.context_locks.lock(&state.context).await;
+            let mut session = self.start_transaction().await?;
+
+            // Another instance might have scheduled the reminder already.
+            let res = coll
+                .update_one_with_session(
+                    doc! {
+                        "context": state.context.to_bson()?,
+                        "nudged_at": null,
+                    },
+                    doc! {
+                        "$set": {
+                            "nudged_at": now.to_bson()?,
+                        }
+                    },
+                    None,
+                    &mut session,
                 )
                 .await?;
-            let _ = self
-                .verify_manually(context, &RawFieldName::Email, false, Some(&mut session))
-                .await?;
-            let _ = self
-                .verify_manually(context, &RawFieldName::Web, false, Some(&mut session))
-                .await?;
-            let _ = self
-                .verify_manually(context, &RawFieldName::Twitter, false, Some(&mut session))
-                .await?;
-            let _ = self
-                .verify_manually(context, &RawFieldName::Matrix, false, Some(&mut session))
-                .await?;
 
-            self.insert_event(
-                NotificationMessage::FullManualVerification {
-                    context: context.clone(),
+            if res.modified_count > 0 {
+                self.db
+                    .collection::<OutboxEntry>(OUTBOX)
+                    .insert_many_with_session(entries, None, &mut session)
+                    .await?;
+
+                nudged += 1;
+            }
+
+            session.commit_transaction().await?;
+        }
+
+        Ok(nudged)
+    }
+    /// Replaces the expired challenge of the field with a new one, valid for
+    /// `ttl` seconds.
+    pub async fn renew_expired_challenge(
+        &self,
+        context: &IdentityContext,
+        field: &RawFieldName,
+        ttl: u64,
+    ) -> Result<RenewalOutcome> {
+        let _guard = self.context_locks.lock(context).await;
+        let mut session = self.start_transaction().await?;
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let state = coll
+            .find_one_with_session(
+                doc! {
+                    "context": context.to_bson()?,
+                    "judgement_submitted": false,
                 },
+                None,
                 &mut session,
             )
             .await?;
 
-            session.commit_transaction().await?;
-            Ok(true)
+        let field_state = match state.as_ref().and_then(|state| {
+            state
+                .fields
+                .iter()
+                .find(|f| f.value.type_name() == field.to_string())
+        }) {
+            Some(field_state) => field_state,
+            None => return Ok(RenewalOutcome::NotFound),
+        };
+
+        let challenge = match &field_state.challenge {
+            ChallengeType::ExpectedMessage { expected, second }
+                if expected.expired && !expected.is_verified =>
+            {
+                ChallengeType::ExpectedMessage {
+                    expected: ExpectedMessage {
+                        valid_until: Some(Timestamp::with_offset(ttl)),
+                        ..ExpectedMessage::random()
+                    },
+                    second: second.as_ref().map(|_| ExpectedMessage::random()),
+                }
+            }
+            _ => return Ok(RenewalOutcome::NotExpired),
+        };
+
+        coll.update_one_with_session(
+            doc! {
+                "context": context.to_bson()?,
+                "fields.value": field_state.value.to_bson()?,
+            },
+            doc! {
+                "$set": {
+                    "fields.$.challenge": challenge.to_bson()?,
+                    "fields.$.failed_attempts": 0_i64,
+                    "fields.$.challenge_sent": Timestamp::now().to_bson()?,
+                },
+                "$unset": {
+                    "fields.$.last_failure": "",
+                }
+            },
+            None,
+            &mut session,
+        )
+        .await?;
+
+        self.insert_event(
+            NotificationMessage::ChallengeRegenerated {
+                context: context.clone(),
+                field: field_state.value.clone(),
+            },
+            &mut session,
+        )
+        .await?;
+
+        session.commit_transaction().await?;
+
+        Ok(RenewalOutcome::Renewed)
+    }
+    /// Returns the events with a higher log sequence number than `seq`,
+    /// ordered by it.
+    pub async fn events_since(&self, seq: u64, limit: i64) -> Result<Vec<Event>> {
+        let mut cursor = self
+            .db
+            .collection::<Event>(EVENT_COLLECTION)
+            .find(
+                doc! {
+                    "log_seq": {
+                        "$gt": seq.to_bson()?,
+                    }
+                },
+                FindOptions::builder()
+                    .sort(doc! { "log_seq": 1 })
+                    .limit(limit)
+                    .build(),
+            )
+            .await?;
+
+        let mut events = vec![];
+        while let Some(event) = cursor.next().await {
+            events.push(event?);
+        }
+
+        Ok(events)
+    }
+    /// Returns the log sequence number of the latest event.
+    pub async fn latest_event_seq(&self) -> Result<u64> {
+        #[derive(Debug, Deserialize)]
+        struct Sequence {
+            seq: u64,
+        }
+
+        Ok(self
+            .db
+            .collection::<Sequence>(EVENT_LOG_SEQUENCE)
+            .find_one(doc! { "_id": EVENT_COLLECTION }, None)
+            .await?
+            .map(|sequence| sequence.seq)
+            .unwrap_or(0))
+    }
+    /// Returns the events matching the filter, oldest first. The cursor is the
+    /// ID of the last event of the previous page.
+    pub async fn fetch_event_page(&self, filter: &EventFilter) -> Result<EventPage> {
+        #[derive(Debug, Deserialize)]
+        struct EventWrapper {
+            #[serde(rename = "_id")]
+            id: bson::oid::ObjectId,
+            #[serde(flatten)]
+            event: Event,
+        }
+
+        let mut query = doc! {};
+        if let Some(chain) = filter.chain {
+            query.insert("message.value.context.chain", chain.to_bson()?);
+        }
+        if let Some(address) = &filter.address {
+            query.insert("message.value.context.address", address.to_bson()?);
+        }
+        if !filter.kinds.is_empty() {
+            query.insert("message.type", doc! { "$in": filter.kinds.to_bson()? });
+        }
+        if let Some(since) = filter.since {
+            query.insert("timestamp", doc! { "$gte": since.raw().to_bson()? });
+        }
+        if let Some(cursor) = &filter.cursor {
+            query.insert(
+                "_id",
+                doc! { "$gt": bson::oid::ObjectId::parse_str(cursor)? },
+            );
+        }
+
+        // One more event than requested indicates whether there is another
+        // page.
+        let mut cursor = self
+            .db
+            .collection::<EventWrapper>(EVENT_COLLECTION)
+            .find(
+                query,
+                FindOptions::builder()
+                    .sort(doc! { "_id": 1 })
+                    .limit(filter.limit as i64 + 1)
+                    .build(),
+            )
+            .await?;
+
+        let mut wrappers = vec![];
+        while let Some(wrapper) = cursor.next().await {
+            wrappers.push(wrapper?);
+        }
+
+        let next_cursor = if wrappers.len() > filter.limit {
+            wrappers.truncate(filter.limit);
+            wrappers.last().map(|wrapper| wrapper.id.to_hex())
         } else {
-            session.commit_transaction().await?;
-            Ok(false)
+            None
+        };
+
+        Ok(EventPage {
+            events: wrappers.into_iter().map(|wrapper| wrapper.event).collect(),
+            next_cursor,
+        })
+    }
+    /// Returns the most recent `JudgementProvided` events, newest first.
+    pub async fn fetch_judgement_events(&self, limit: i64) -> Result<Vec<Event>> {
+        let coll = self.db.collection::<Event>(EVENT_COLLECTION);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "message.type": "judgement_provided",
+                },
+                FindOptions::builder()
+                    .sort(doc! { "timestamp": -1, "_id": -1 })
+                    .limit(limit)
+                    .build(),
+            )
+            .await?;
+
+        let mut events = vec![];
+        while let Some(event) = cursor.next().await {
+            events.push(event?);
+        }
+
+        Ok(events)
+    }
+    /// Returns the judgement and manual verification events within the time
+    /// range, for the transparency report.
+    pub async fn fetch_report_events(&self, from: Timestamp, to: Timestamp) -> Result<Vec<Event>> {
+        let coll = self.db.collection::<Event>(EVENT_COLLECTION);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "timestamp": {
+                        "$gte": from.to_bson()?,
+                        "$lt": to.to_bson()?,
+                    },
+                    "message.type": {
+                        "$in": ["judgement_provided", "manually_verified", "full_manual_verification"],
+                    },
+                },
+                FindOptions::builder()
+                    .sort(doc! { "timestamp": 1, "_id": 1 })
+                    .build(),
+            )
+            .await?;
+
+        let mut events = vec![];
+        while let Some(event) = cursor.next().await {
+            events.push(event?);
+        }
+
+        Ok(events)
+    }
+    /// Returns the identities requested within the time range of which the
+    /// display name violates existing ones.
+    pub async fn fetch_rejected_display_names(
+        &self,
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Result<Vec<IdentityContext>> {
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "inserted_timestamp": {
+                        "$gte": from.to_bson()?,
+                        "$lt": to.to_bson()?,
+                    },
+                    "fields": {
+                        "$elemMatch": {
+                            "value.type": "display_name",
+                            "challenge.content.violations.0": { "$exists": true },
+                        }
+                    },
+                },
+                None,
+            )
+            .await?;
+
+        let mut contexts = vec![];
+        while let Some(state) = cursor.next().await {
+            contexts.push(state?.context);
+        }
+
+        Ok(contexts)
+    }
+    /// Fetches the states of multiple identities with a single query.
+    /// Identities without a judgement request are omitted.
+    pub async fn fetch_judgement_states(
+        &self,
+        contexts: &[IdentityContext],
+    ) -> Result<HashMap<IdentityContext, JudgementState>> {
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "context": {
+                        "$in": contexts.to_bson()?,
+                    }
+                },
+                None,
+            )
+            .await?;
+
+        let mut states = HashMap::new();
+        while let Some(state) = cursor.next().await {
+            let state = state?;
+            states.insert(state.context.clone(), state);
+        }
+
+        Ok(states)
+    }
+    pub async fn fetch_judgement_state(
+        &self,
+        context: &IdentityContext,
+    ) -> Result<Option<JudgementState>> {
+        let coll = self.db.collection(IDENTITY_COLLECTION);
+
+        // Find the context.
+        let doc = coll
+            .find_one(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                None,
+            )
+            .await?;
+
+        if let Some(doc) = doc {
+            Ok(Some(from_document(doc)?))
+        } else {
+            // Not active request exists.
+            Ok(None)
+        }
+    }
+    /// Twitter handles of pending identities whose challenge was not verified
+    /// yet.
+    pub async fn fetch_unverified_twitter_handles(&self) -> Result<Vec<String>> {
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "fields.value.type": "twitter",
+                    "is_fully_verified": false,
+                },
+                None,
+            )
+            .await?;
+
+        let mut handles = vec![];
+        while let Some(state) = cursor.next().await {
+            for field in state?.fields {
+                if let IdentityFieldValue::Twitter(handle) = field.value {
+                    if !field.challenge.is_verified() && !handles.contains(&handle) {
+                        handles.push(handle);
+                    }
+                }
+            }
+        }
+
+        Ok(handles)
+    }
+    /// Websites of pending identities whose challenge was not verified yet.
+    pub async fn fetch_unverified_websites(&self) -> Result<Vec<String>> {
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "fields": {
+                        "$elemMatch": {
+                            "value.type": "web",
+                            "challenge.type": "expected_message",
+                        }
+                    },
+                    "is_fully_verified": false,
+                },
+                None,
+            )
+            .await?;
+
+        let mut websites = vec![];
+        while let Some(state) = cursor.next().await {
+            for field in state?.fields {
+                if let (IdentityFieldValue::Web(website), ChallengeType::ExpectedMessage { .. }) =
+                    (field.value, &field.challenge)
+                {
+                    if !field.challenge.is_verified() && !websites.contains(&website) {
+                        websites.push(website);
+                    }
+                }
+            }
+        }
+
+        Ok(websites)
+    }
+    /// Searches addresses and field values (including display names) of the
+    /// identities for the term, case-insensitive. Identities which only appear
+    /// in the event log (e.g. judged and removed) are returned without state.
+    pub async fn search_identities(&self, term: &str, limit: usize) -> Result<Vec<SearchResult>> {
+        let pattern = doc! {
+            "$regex": escape_regex(term),
+            "$options": "i",
+        };
+
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "$or": [
+                        { "context.address": pattern.clone() },
+                        { "fields.value.value": pattern.clone() },
+                    ]
+                },
+                FindOptions::builder().limit(limit as i64).build(),
+            )
+            .await?;
+
+        let mut results = vec![];
+        while let Some(state) = cursor.next().await {
+            let state = state?;
+            results.push(SearchResult {
+                context: state.context.clone(),
+                state: Some(state.into()),
+            });
+        }
+
+        let contexts = self
+            .db
+            .collection::<Event>(EVENT_COLLECTION)
+            .distinct(
+                "message.value.context",
+                doc! {
+                    "message.value.context.address": pattern,
+                },
+                None,
+            )
+            .await?;
+
+        for context in contexts {
+            if results.len() >= limit {
+                break;
+            }
+
+            let context: IdentityContext = from_bson(context)?;
+            if !results.iter().any(|result| result.context == context) {
+                results.push(SearchResult {
+                    context,
+                    state: None,
+                });
+            }
+        }
+
+        Ok(results)
+    }
+    pub async fn fetch_judgement_candidates(
+        &self,
+        network: ChainName,
+    ) -> Result<Vec<JudgementState>> {
+        // Candidates are kept until submissions are resumed.
+        if self.is_submission_paused(network).await? {
+            self.withhold_judgements(network, WithheldReason::SubmissionsPaused)
+                .await?;
+            return Ok(vec![]);
+        }
+
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "context.chain": network.as_str().to_bson()?,
+                    "is_fully_verified": true,
+                    "judgement_submitted": false,
+                    "issue_judgement_at": {
+                        "$lt": Timestamp::now().to_bson()?,
+                    }
+                },
+                // Oldest requests first, identities without a known block
+                // number are ordered by the time they were received.
+                FindOptions::builder()
+                    .sort(doc! { "requested_at_block": 1, "inserted_timestamp": 1 })
+                    .build(),
+            )
+            .await?;
+
+        let mut completed = vec![];
+        while let Some(state) = cursor.next().await {
+            completed.push(state?);
+        }
+
+        Ok(completed)
+    }
+    /// Returns the time in seconds it took to fully verify the most recently
+    /// completed identities of the chain, excluding manual reviews.
+    pub async fn fetch_verification_durations(
+        &self,
+        chain: ChainName,
+        since: Timestamp,
+        limit: i64,
+    ) -> Result<Vec<u64>> {
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "context.chain": chain.to_bson()?,
+                    "is_fully_verified": true,
+                    "requires_manual_review": {
+                        "$ne": true,
+                    },
+                    "completion_timestamp": {
+                        "$gte": since.to_bson()?,
+                    }
+                },
+                FindOptions::builder()
+                    .sort(doc! { "completion_timestamp": -1 })
+                    .limit(limit)
+                    .build(),
+            )
+            .await?;
+
+        let mut durations = vec![];
+        while let Some(state) = cursor.next().await {
+            let state = state?;
+            if let Some(completed) = state.completion_timestamp {
+                durations.push(
+                    completed
+                        .raw()
+                        .saturating_sub(state.inserted_timestamp.raw()),
+                );
+            }
+        }
+
+        Ok(durations)
+    }
+    /// Fetches the identities which only contain fields that cannot be verified
+    /// automatically and were not judged by an admin yet.
+    pub async fn fetch_manual_review_states(&self) -> Result<Vec<JudgementState>> {
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "requires_manual_review": true,
+                    "is_fully_verified": false,
+                },
+                None,
+            )
+            .await?;
+
+        let mut states = vec![];
+        while let Some(state) = cursor.next().await {
+            states.push(state?);
+        }
+
+        Ok(states)
+    }
+    /// Returns the identities of the chain with one of the statuses, oldest
+    /// first. Without statuses, the identities which were not judged yet are
+    /// returned.
+    pub async fn fetch_chain_states(
+        &self,
+        chain: ChainName,
+        statuses: &[JudgementStatus],
+        limit: usize,
+    ) -> Result<Vec<JudgementState>> {
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let mut filter = doc! {
+            "context.chain": chain.to_bson()?,
+        };
+
+        if statuses.is_empty() {
+            filter.insert("judgement_submitted", false);
+        } else {
+            let conditions: Vec<Document> = statuses
+                .iter()
+                .map(|status| match status {
+                    JudgementStatus::Pending => doc! {
+                        "judgement_submitted": false,
+                        "is_fully_verified": false,
+                        "requires_manual_review": { "$ne": true },
+                    },
+                    JudgementStatus::ManualReview => doc! {
+                        "judgement_submitted": false,
+                        "is_fully_verified": false,
+                        "requires_manual_review": true,
+                    },
+                    JudgementStatus::Verified => doc! {
+                        "judgement_submitted": false,
+                        "is_fully_verified": true,
+                        "judgement_withheld": Bson::Null,
+                    },
+                    JudgementStatus::Withheld => doc! {
+                        "judgement_submitted": false,
+                        "is_fully_verified": true,
+                        "judgement_withheld": { "$ne": Bson::Null },
+                    },
+                    JudgementStatus::Judged => doc! {
+                        "judgement_submitted": true,
+                    },
+                })
+                .collect();
+
+            filter.insert("$or", conditions);
+        }
+
+        let mut cursor = coll
+            .find(
+                filter,
+                FindOptions::builder()
+                    .sort(doc! { "inserted_timestamp": 1 })
+                    .limit(limit as i64)
+                    .build(),
+            )
+            .await?;
+
+        let mut states = vec![];
+        while let Some(state) = cursor.next().await {
+            states.push(state?);
+        }
+
+        Ok(states)
+    }
+    /// Counts the identities which were not judged yet per chain, together
+    /// with their unverified fields per field type.
+    pub async fn fetch_pending_overview(&self) -> Result<PendingOverview> {
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "judgement_submitted": false,
+                },
+                None,
+            )
+            .await?;
+
+        let mut overview = PendingOverview::default();
+        while let Some(state) = cursor.next().await {
+            let state = state?;
+
+            *overview
+                .per_chain
+                .entry(state.context.chain.as_str().to_string())
+                .or_default() += 1;
+
+            for field in &state.fields {
+                if !field.challenge.is_verified() {
+                    *overview
+                        .unverified_fields
+                        .entry(field.value.type_name().to_string())
+                        .or_default() += 1;
+                }
+            }
+        }
+
+        Ok(overview)
+    }
+    // (Warning) This fully verifies the identity without having to verify
+    // individual fields.
+    pub async fn full_manual_verification(&self, context: &IdentityContext) -> Result<bool> {
+        let _guard = self.context_locks.lock(context).await;
+        let mut session = self.start_transaction().await?;
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        // Create a timed delay for issuing judgments.
+        let now = Timestamp::now();
+        let issue_at = Timestamp::with_offset(verification::judgement_delay());
+
+        let res = coll
+            .update_one_with_session(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                doc! {
+                    "$set": {
+                        "is_fully_verified": true,
+                        "judgement_submitted": false,
+                        "completion_timestamp": now.to_bson()?,
+                        "issue_judgement_at": issue_at.to_bson()?,
+                    }
+                },
+                None,
+                &mut session,
+            )
+            .await?;
+
+        // Create event.
+        if res.modified_count == 1 {
+            // Verify all possible fields. Unused fields are silently ignored.
+            let _ = self
+                .verify_manually(context, &RawFieldName::LegalName, false, Some(&mut session))
+                .await?;
+            let _ = self
+                .verify_manually(
+                    context,
+                    &RawFieldName::DisplayName,
+                    false,
+                    Some(&mut session),
+                )
+                .await?;
+            let _ = self
+                .verify_manually(context, &RawFieldName::Email, false, Some(&mut session))
+                .await?;
+            let _ = self
+                .verify_manually(context, &RawFieldName::Web, false, Some(&mut session))
+                .await?;
+            let _ = self
+                .verify_manually(context, &RawFieldName::Twitter, false, Some(&mut session))
+                .await?;
+            let _ = self
+                .verify_manually(context, &RawFieldName::Matrix, false, Some(&mut session))
+                .await?;
+
+            self.insert_event(
+                NotificationMessage::FullManualVerification {
+                    context: context.clone(),
+                },
+                &mut session,
+            )
+            .await?;
+
+            session.commit_transaction().await?;
+            Ok(true)
+        } else {
+            session.commit_transaction().await?;
+            Ok(false)
+        }
+    }
+    /// Marks the pending identity to be judged as erroneous, which is
+    /// submitted with the next run regardless of the verification status.
+    /// Returns `false` if no pending identity was found.
+    pub async fn judge_erroneous(&self, context: &IdentityContext, reason: &str) -> Result<bool> {
+        let _guard = self.context_locks.lock(context).await;
+        let mut session = self.start_transaction().await?;
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let res = coll
+            .update_one_with_session(
+                doc! {
+                    "context": context.to_bson()?,
+                    "judgement_submitted": false,
+                },
+                doc! {
+                    "$set": {
+                        "is_fully_verified": true,
+                        "erroneous_reason": reason.to_bson()?,
+                        "issue_judgement_at": Timestamp::now().to_bson()?,
+                    }
+                },
+                None,
+                &mut session,
+            )
+            .await?;
+
+        if res.matched_count == 0 {
+            session.commit_transaction().await?;
+            return Ok(false);
+        }
+
+        self.insert_event(
+            NotificationMessage::JudgedErroneous {
+                context: context.clone(),
+                reason: reason.to_string(),
+            },
+            &mut session,
+        )
+        .await?;
+
+        session.commit_transaction().await?;
+        Ok(true)
+    }
+    /// Brings the issuance of a fully verified judgement forward on request of
+    /// the user, see `verification::expedited_issue_time`. Returns the new
+    /// issuance time or `None` if no judgement of the identity is awaited.
+    pub async fn expedite_judgement(
+        &self,
+        context: &IdentityContext,
+        min_delay: u64,
+    ) -> Result<Option<Timestamp>> {
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let state = match coll
+            .find_one(doc! { "context": context.to_bson()? }, None)
+            .await?
+        {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+
+        let issue_at = match verification::expedited_issue_time(&state, min_delay, Timestamp::now())
+        {
+            Some(issue_at) => issue_at,
+            None => return Ok(None),
+        };
+
+        // The scheduled time is only replaced if it did not change in the
+        // meantime, e.g. by a submission or reset.
+        let res = coll
+            .update_one(
+                doc! {
+                    "context": context.to_bson()?,
+                    "is_fully_verified": true,
+                    "judgement_submitted": false,
+                    "issue_judgement_at": state.issue_judgement_at.to_bson()?,
+                },
+                doc! {
+                    "$set": {
+                        "issue_judgement_at": issue_at.to_bson()?,
+                    }
+                },
+                None,
+            )
+            .await?;
+
+        Ok((res.matched_count == 1).then_some(issue_at))
+    }
+    /// Sets the judgement which is submitted once the pending identity is
+    /// fully verified. Returns `false` if no pending identity was found.
+    pub async fn set_judgement_type(
+        &self,
+        context: &IdentityContext,
+        judgement_type: JudgementType,
+    ) -> Result<bool> {
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let res = coll
+            .update_one(
+                doc! {
+                    "context": context.to_bson()?,
+                    "judgement_submitted": false,
+                },
+                doc! {
+                    "$set": {
+                        "judgement_type": judgement_type.to_bson()?,
+                    }
+                },
+                None,
+            )
+            .await?;
+
+        Ok(res.matched_count > 0)
+    }
+    pub async fn set_judged(&self, context: &IdentityContext) -> Result<()> {
+        let _guard = self.context_locks.lock(context).await;
+        let mut session = self.start_transaction().await?;
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        // Returns the state before the update, if it was not judged yet.
+        let state = coll
+            .find_one_and_update_with_session(
+                doc! {
+                    "context": context.to_bson()?,
+                    "judgement_submitted": false,
+                },
+                doc! {
+                    "$set": {
+                        "judgement_submitted": true,
+                        "judgement_withheld": Bson::Null,
+                    }
+                },
+                None,
+                &mut session,
+            )
+            .await?;
+
+        // Create event.
+        if let Some(state) = state {
+            if let Some(completed) = state.completion_timestamp {
+                metrics::observe_judged(context.chain, completed);
+            }
+
+            self.insert_event(
+                NotificationMessage::JudgementProvided {
+                    context: context.clone(),
+                },
+                &mut session,
+            )
+            .await?;
+        }
+
+        session.commit_transaction().await?;
+
+        Ok(())
+    }
+    pub async fn insert_display_name(&self, name: &DisplayNameEntry) -> Result<()> {
+        let coll = self.db.collection::<DisplayNameEntry>(DISPLAY_NAMES);
+
+        coll.update_one(
+            doc! {
+                "display_name": name.display_name.to_bson()?,
+                "context": name.context.to_bson()?,
+            },
+            doc! {
+                "$setOnInsert": {
+                    "display_name": name.display_name.to_bson()?,
+                    "context": name.context.to_bson()?,
+                },
+                // Backfills the normalized form of existing entries.
+                "$set": {
+                    "normalized": name.normalized.to_bson()?,
+                },
+            },
+            {
+                let mut opt = UpdateOptions::default();
+                opt.upsert = Some(true);
+                Some(opt)
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+    pub async fn fetch_display_names(&self, chain: ChainName) -> Result<Vec<DisplayNameEntry>> {
+        let coll = self.db.collection::<DisplayNameEntry>(DISPLAY_NAMES);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "context.chain": chain.to_bson()?,
+                },
+                None,
+            )
+            .await?;
+
+        let mut names = vec![];
+        while let Some(doc) = cursor.next().await {
+            names.push(doc?);
+        }
+
+        Ok(names)
+    }
+    /// Returns the display names of pending requests of the chain which
+    /// violate existing ones.
+    pub async fn fetch_rejected_pending_display_names(
+        &self,
+        chain: ChainName,
+    ) -> Result<Vec<DisplayNameEntry>> {
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "context.chain": chain.to_bson()?,
+                    "judgement_submitted": false,
+                    "fields": {
+                        "$elemMatch": {
+                            "value.type": "display_name",
+                            "challenge.content.violations.0": { "$exists": true },
+                        }
+                    },
+                },
+                None,
+            )
+            .await?;
+
+        let mut names = vec![];
+        while let Some(state) = cursor.next().await {
+            let state = state?;
+            if let Some(name) = state.display_name() {
+                names.push(DisplayNameEntry::new(
+                    state.context.clone(),
+                    DisplayName::from(name.to_string()),
+                ));
+            }
+        }
+
+        Ok(names)
+    }
+    /// Removes all display names of the given chain which are not part of
+    /// `active`. Returns the number of removed entries.
+    pub async fn prune_display_names(
+        &self,
+        chain: ChainName,
+        active: &[DisplayNameEntry],
+    ) -> Result<usize> {
+        let coll = self.db.collection::<DisplayNameEntry>(DISPLAY_NAMES);
+
+        let mut removed = 0;
+        for name in self.fetch_display_names(chain).await? {
+            if active
+                .iter()
+                .any(|a| a.context == name.context && a.display_name == name.display_name)
+            {
+                continue;
+            }
+
+            let res = coll
+                .delete_one(
+                    doc! {
+                        "display_name": name.display_name.to_bson()?,
+                        "context": name.context.to_bson()?,
+                    },
+                    None,
+                )
+                .await?;
+
+            removed += res.deleted_count as usize;
+        }
+
+        Ok(removed)
+    }
+    /// Streams added and removed display names. Removals are only tracked if
+    /// pre-images are supported by the server.
+    pub async fn watch_display_names(
+        &self,
+    ) -> Result<BoxStream<'static, Result<DisplayNameChange>>> {
+        let coll = self.db.collection::<DisplayNameEntry>(DISPLAY_NAMES);
+
+        let options = ChangeStreamOptions::builder()
+            .full_document_before_change(Some(FullDocumentBeforeChangeType::WhenAvailable))
+            .build();
+
+        let stream = coll
+            .watch(
+                vec![doc! {
+                    "$match": {
+                        "operationType": { "$in": ["insert", "delete"] }
+                    }
+                }],
+                options,
+            )
+            .await?;
+
+        Ok(stream
+            .filter_map(|event| async move {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(err) => return Some(Err(err.into())),
+                };
+
+                match event.operation_type {
+                    OperationType::Insert => event.full_document.map(DisplayNameChange::Added),
+                    OperationType::Delete => event
+                        .full_document_before_change
+                        .map(DisplayNameChange::Removed),
+                    _ => None,
+                }
+                .map(Ok)
+            })
+            .boxed())
+    }
+    pub async fn set_display_name_valid(&self, state: &JudgementState) -> Result<()> {
+        let _guard = self.context_locks.lock(&state.context).await;
+        let mut session = self.start_transaction().await?;
+        let coll = self.db.collection::<()>(IDENTITY_COLLECTION);
+
+        let res = coll
+            .update_one_with_session(
+                doc! {
+                    "context": state.context.to_bson()?,
+                    "fields.value.type": "display_name",
+                    "fields.challenge.content.passed": false
+                },
+                doc! {
+                    "$set": {
+                        "fields.$.challenge.content.passed": true,
+                    },
+                    "$unset": {
+                        "fields.$.challenge.content.reserved": "",
+                    },
+                },
+                None,
+                &mut session,
+            )
+            .await?;
+
+        if res.modified_count == 0 {
+            return Ok(());
+        }
+
+        // Create event
+        self.insert_event(
+            NotificationMessage::FieldVerified {
+                context: state.context.clone(),
+                field: state
+                    .fields
+                    .iter()
+                    .find(|field| matches!(field.value, IdentityFieldValue::DisplayName(_)))
+                    .map(|field| field.value.clone())
+                    .expect("Failed to retrieve display name. This is a bug"),
+            },
+            &mut session,
+        )
+        .await?;
+
+        self.process_fully_verified(&state.context, &mut session)
+            .await?;
+
+        session.commit_transaction().await?;
+
+        Ok(())
+    }
+    pub async fn insert_display_name_violations(
+        &self,
+        context: &IdentityContext,
+        violations: &Vec<DisplayNameEntry>,
+    ) -> Result<()> {
+        let _guard = self.context_locks.lock(context).await;
+        let mut session = self.start_transaction().await?;
+        let coll = self.db.collection::<()>(IDENTITY_COLLECTION);
+
+        coll.update_one_with_session(
+            doc! {
+                "context": context.to_bson()?,
+                "fields.value.type": "display_name",
+            },
+            doc! {
+                "$set": {
+                    "fields.$.challenge.content.violations": violations.to_bson()?
+                },
+                "$unset": {
+                    "fields.$.challenge.content.reserved": "",
+                },
+            },
+            None,
+            &mut session,
+        )
+        .await?;
+
+        session.commit_transaction().await?;
+
+        Ok(())
+    }
+    /// Rejects the display name of the identity because it matches the
+    /// reserved name or pattern.
+    pub async fn set_display_name_reserved(
+        &self,
+        context: &IdentityContext,
+        reserved: &str,
+    ) -> Result<()> {
+        let _guard = self.context_locks.lock(context).await;
+        let mut session = self.start_transaction().await?;
+        let coll = self.db.collection::<()>(IDENTITY_COLLECTION);
+
+        coll.update_one_with_session(
+            doc! {
+                "context": context.to_bson()?,
+                "fields.value.type": "display_name",
+            },
+            doc! {
+                "$set": {
+                    "fields.$.challenge.content.violations": [],
+                    "fields.$.challenge.content.reserved": reserved,
+                }
+            },
+            None,
+            &mut session,
+        )
+        .await?;
+
+        session.commit_transaction().await?;
+
+        Ok(())
+    }
+    /// Reserves the display name or pattern. Returns `false` if it was
+    /// already reserved.
+    pub async fn reserve_display_name(
+        &self,
+        kind: ReservedKind,
+        value: &str,
+        admin: &MatrixHandle,
+    ) -> Result<bool> {
+        let res = self
+            .db
+            .collection::<ReservedDisplayName>(RESERVED_DISPLAY_NAMES)
+            .update_one(
+                doc! {
+                    "kind": kind.to_bson()?,
+                    "value": value,
+                },
+                doc! {
+                    "$setOnInsert": ReservedDisplayName {
+                        kind,
+                        value: value.to_string(),
+                        reserved_by: admin.clone(),
+                        timestamp: Timestamp::now(),
+                    }
+                    .to_bson()?,
+                },
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+
+        if res.upserted_id.is_some() {
+            info!(
+                "Admin {} reserved the display name {:?} {}",
+                admin, kind, value
+            );
+        }
+
+        Ok(res.upserted_id.is_some())
+    }
+    /// Removes the reserved name or pattern. Returns `false` if it was not
+    /// reserved by an admin.
+    pub async fn unreserve_display_name(&self, value: &str, admin: &MatrixHandle) -> Result<bool> {
+        let res = self
+            .db
+            .collection::<ReservedDisplayName>(RESERVED_DISPLAY_NAMES)
+            .delete_many(
+                doc! {
+                    "value": value,
+                },
+                None,
+            )
+            .await?;
+
+        if res.deleted_count > 0 {
+            info!(
+                "Admin {} removed the reserved display name {}",
+                admin, value
+            );
+        }
+
+        Ok(res.deleted_count > 0)
+    }
+    pub async fn fetch_reserved_display_names(&self) -> Result<Vec<ReservedDisplayName>> {
+        let coll = self
+            .db
+            .collection::<ReservedDisplayName>(RESERVED_DISPLAY_NAMES);
+
+        let mut cursor = coll
+            .find(
+                None,
+                FindOptions::builder()
+                    .sort(doc! { "timestamp": 1, "_id": 1 })
+                    .build(),
+            )
+            .await?;
+
+        let mut reserved = vec![];
+        while let Some(doc) = cursor.next().await {
+            reserved.push(doc?);
+        }
+
+        Ok(reserved)
+    }
+    async fn insert_event<T: Into<Event>>(
+        &self,
+        event: T,
+        session: &mut ClientSession,
+    ) -> Result<()> {
+        #[derive(Debug, Deserialize)]
+        struct Sequence {
+            seq: u64,
+        }
+
+        let mut event = <T as Into<Event>>::into(event);
+
+        // The same event was already inserted by the same operation, e.g. a
+        // message processed by another instance. Concurrent insertions fail
+        // on the unique index.
+        let coll = self.db.collection::<Event>(EVENT_COLLECTION);
+        if let Some(dedup_key) = &event.dedup_key {
+            if coll
+                .find_one_with_session(doc! { "dedup_key": dedup_key }, None, session)
+                .await?
+                .is_some()
+            {
+                debug!("Skipping duplicate event {}", dedup_key);
+                return Ok(());
+            }
+        }
+
+        // Assign the next sequence number of the identity.
+        let sequence = self
+            .db
+            .collection::<Sequence>(NOTIFICATION_SEQUENCES)
+            .find_one_and_update_with_session(
+                doc! {
+                    "context": event.message.context().to_bson()?,
+                },
+                doc! {
+                    "$inc": {
+                        "seq": 1_i64,
+                    },
+                },
+                FindOneAndUpdateOptions::builder()
+                    .upsert(true)
+                    .return_document(ReturnDocument::After)
+                    .build(),
+                session,
+            )
+            .await?
+            .ok_or_else(|| anyhow!("failed to assign sequence number to event"))?;
+
+        event.seq = sequence.seq;
+
+        // Assigned outside of the transaction, so concurrent transactions do
+        // not conflict on the counter. Events can therefore be committed out
+        // of order and aborted transactions leave gaps.
+        let log_sequence = self
+            .db
+            .collection::<Sequence>(EVENT_LOG_SEQUENCE)
+            .find_one_and_update(
+                doc! {
+                    "_id": EVENT_COLLECTION,
+                },
+                doc! {
+                    "$inc": {
+                        "seq": 1_i64,
+                    },
+                },
+                FindOneAndUpdateOptions::builder()
+                    .upsert(true)
+                    .return_document(ReturnDocument::After)
+                    .build(),
+            )
+            .await?
+            .ok_or_else(|| anyhow!("failed to assign log sequence number to event"))?;
+
+        event.log_seq = log_sequence.seq;
+
+        coll.insert_one_with_session(&event, None, session).await?;
+
+        // Messages required by the event are sent by the adapters.
+        if let Some(entry) = OutboxEntry::for_notification(&event.message) {
+            self.db
+                .collection::<OutboxEntry>(OUTBOX)
+                .insert_one_with_session(entry, None, session)
+                .await?;
+        }
+
+        Ok(())
+    }
+    #[cfg(test)]
+    pub async fn insert_events(&self, events: Vec<Event>) -> Result<()> {
+        let mut session = self.start_transaction().await?;
+        for event in events {
+            self.insert_event(event, &mut session).await?;
+        }
+        session.commit_transaction().await?;
+
+        Ok(())
+    }
+    /// Returns the sequence number of the latest notification of the identity
+    /// and the sequence number up to which notifications were acknowledged.
+    pub async fn fetch_notification_sequence(
+        &self,
+        context: &IdentityContext,
+    ) -> Result<(u64, u64)> {
+        #[derive(Debug, Deserialize)]
+        struct Sequence {
+            #[serde(default)]
+            seq: u64,
+            #[serde(default)]
+            read_seq: u64,
+        }
+
+        let coll = self.db.collection::<Sequence>(NOTIFICATION_SEQUENCES);
+
+        Ok(coll
+            .find_one(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                None,
+            )
+            .await?
+            .map(|sequence| (sequence.seq, sequence.read_seq))
+            .unwrap_or_default())
+    }
+    /// Marks the notifications of the identity as read up to the given
+    /// sequence number. The marker never moves backwards, nor beyond the
+    /// latest notification.
+    pub async fn acknowledge_notifications(
+        &self,
+        context: &IdentityContext,
+        seq: u64,
+    ) -> Result<bool> {
+        let coll = self.db.collection::<()>(NOTIFICATION_SEQUENCES);
+
+        let res = coll
+            .update_one(
+                doc! {
+                    "context": context.to_bson()?,
+                    "seq": {
+                        "$gte": seq.to_bson()?,
+                    },
+                },
+                doc! {
+                    "$max": {
+                        "read_seq": seq.to_bson()?,
+                    },
+                },
+                None,
+            )
+            .await?;
+
+        Ok(res.matched_count > 0)
+    }
+    /// Fetches the ID of the last processed message of the given adapter.
+    pub async fn fetch_watermark(&self, adapter: &str) -> Result<Option<MessageId>> {
+        #[derive(Debug, Deserialize)]
+        struct Watermark {
+            message_id: MessageId,
+        }
+
+        let coll = self.db.collection::<Watermark>(WATERMARKS);
+
+        Ok(coll
+            .find_one(
+                doc! {
+                    "adapter": adapter,
+                },
+                None,
+            )
+            .await?
+            .map(|watermark| watermark.message_id))
+    }
+    /// Updates the ID of the last processed message of the given adapter. The
+    /// watermark never moves backwards.
+    pub async fn update_watermark(&self, adapter: &str, id: MessageId) -> Result<()> {
+        let coll = self.db.collection::<()>(WATERMARKS);
+
+        coll.update_one(
+            doc! {
+                "adapter": adapter,
+            },
+            doc! {
+                "$max": {
+                    "message_id": id.to_bson()?,
+                },
+                "$set": {
+                    "timestamp": Timestamp::now().to_bson()?,
+                }
+            },
+            {
+                let mut opt = UpdateOptions::default();
+                opt.upsert = Some(true);
+                Some(opt)
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+    /// Stores the direct message room of the given Matrix user, replacing any
+    /// previous room.
+    pub async fn store_matrix_room(&self, user: &str, room_id: &str) -> Result<()> {
+        let coll = self.db.collection::<()>(MATRIX_ROOMS);
+
+        coll.update_one(
+            doc! {
+                "user": user,
+            },
+            doc! {
+                "$set": {
+                    "room_id": room_id,
+                    "timestamp": Timestamp::now().to_bson()?,
+                }
+            },
+            {
+                let mut opt = UpdateOptions::default();
+                opt.upsert = Some(true);
+                Some(opt)
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+    pub async fn fetch_matrix_room(&self, user: &str) -> Result<Option<String>> {
+        #[derive(Debug, Deserialize)]
+        struct MatrixRoom {
+            room_id: String,
+        }
+
+        let coll = self.db.collection::<MatrixRoom>(MATRIX_ROOMS);
+
+        Ok(coll
+            .find_one(
+                doc! {
+                    "user": user,
+                },
+                None,
+            )
+            .await?
+            .map(|room| room.room_id))
+    }
+    /// Removes the room from all users, e.g. after the bot left it. Returns the
+    /// number of removed mappings.
+    pub async fn remove_matrix_room(&self, room_id: &str) -> Result<usize> {
+        let coll = self.db.collection::<()>(MATRIX_ROOMS);
+
+        let res = coll
+            .delete_many(
+                doc! {
+                    "room_id": room_id,
+                },
+                None,
+            )
+            .await?;
+
+        Ok(res.deleted_count as usize)
+    }
+    /// Removes all rooms which are not part of `joined`. Returns the number of
+    /// removed mappings.
+    pub async fn prune_matrix_rooms(&self, joined: &[String]) -> Result<usize> {
+        let coll = self.db.collection::<()>(MATRIX_ROOMS);
+
+        let res = coll
+            .delete_many(
+                doc! {
+                    "room_id": {
+                        "$nin": joined.to_bson()?,
+                    }
+                },
+                None,
+            )
+            .await?;
+
+        Ok(res.deleted_count as usize)
+    }
+    /// Caches the handle of the Twitter account. Since handles can be
+    /// changed and then taken by other accounts, the handle is removed from
+    /// any other account.
+    pub async fn store_twitter_handle(&self, twitter_id: &str, handle: &str) -> Result<()> {
+        let coll = self.db.collection::<()>(TWITTER_IDS);
+
+        coll.delete_many(
+            doc! {
+                "handle": handle,
+                "twitter_id": {
+                    "$ne": twitter_id,
+                },
+            },
+            None,
+        )
+        .await?;
+
+        coll.update_one(
+            doc! {
+                "twitter_id": twitter_id,
+            },
+            doc! {
+                "$set": {
+                    "handle": handle,
+                    "timestamp": Timestamp::now().to_bson()?,
+                }
+            },
+            {
+                let mut opt = UpdateOptions::default();
+                opt.upsert = Some(true);
+                Some(opt)
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+    /// Fetches the cached handles of the Twitter accounts, together with the
+    /// time of the lookup. Entries cached before `since` are ignored.
+    pub async fn fetch_twitter_handles(
+        &self,
+        twitter_ids: &[String],
+        since: Timestamp,
+    ) -> Result<HashMap<String, (String, Timestamp)>> {
+        #[derive(Debug, Deserialize)]
+        struct TwitterHandle {
+            twitter_id: String,
+            handle: String,
+            timestamp: Timestamp,
+        }
+
+        let coll = self.db.collection::<TwitterHandle>(TWITTER_IDS);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "twitter_id": {
+                        "$in": twitter_ids.to_bson()?,
+                    },
+                    "timestamp": {
+                        "$gte": since.to_bson()?,
+                    }
+                },
+                None,
+            )
+            .await?;
+
+        let mut handles = HashMap::new();
+        while let Some(entry) = cursor.next().await {
+            let entry = entry?;
+            handles.insert(entry.twitter_id, (entry.handle, entry.timestamp));
+        }
+
+        Ok(handles)
+    }
+    pub async fn archive_outbound_message(&self, message: &OutboundMessage) -> Result<()> {
+        let coll = self.db.collection::<OutboundMessage>(OUTBOUND_ARCHIVE);
+        coll.insert_one(message, None).await?;
+
+        Ok(())
+    }
+    /// Fetches the archived messages which were sent to the identity, oldest
+    /// first.
+    pub async fn fetch_outbound_messages(
+        &self,
+        context: &IdentityContext,
+    ) -> Result<Vec<OutboundMessage>> {
+        let coll = self.db.collection::<OutboundMessage>(OUTBOUND_ARCHIVE);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                FindOptions::builder()
+                    .sort(doc! { "timestamp": 1, "_id": 1 })
+                    .build(),
+            )
+            .await?;
+
+        let mut messages = vec![];
+        while let Some(message) = cursor.next().await {
+            messages.push(message?);
+        }
+
+        Ok(messages)
+    }
+    /// Fetches the outcomes of the messages which were received for the
+    /// identity, oldest first.
+    pub async fn fetch_verification_records(
+        &self,
+        context: &IdentityContext,
+    ) -> Result<Vec<VerificationRecord>> {
+        let coll = self
+            .db
+            .collection::<VerificationRecord>(VERIFICATION_RECORDS);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                FindOptions::builder()
+                    .sort(doc! { "timestamp": 1, "_id": 1 })
+                    .build(),
+            )
+            .await?;
+
+        let mut records = vec![];
+        while let Some(record) = cursor.next().await {
+            records.push(record?);
+        }
+
+        Ok(records)
+    }
+    /// Fetches the messages received since the given timestamp which did not
+    /// verify the field, e.g. because of a wrong challenge.
+    pub async fn fetch_failed_verification_records(
+        &self,
+        since: Timestamp,
+    ) -> Result<Vec<VerificationRecord>> {
+        let coll = self
+            .db
+            .collection::<VerificationRecord>(VERIFICATION_RECORDS);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "outcome": VerificationOutcome::Failed.to_bson()?,
+                    "timestamp": {
+                        "$gte": since.to_bson()?,
+                    }
+                },
+                FindOptions::builder()
+                    .sort(doc! { "timestamp": 1, "_id": 1 })
+                    .build(),
+            )
+            .await?;
+
+        let mut records = vec![];
+        while let Some(record) = cursor.next().await {
+            records.push(record?);
+        }
+
+        Ok(records)
+    }
+    pub async fn insert_failure_alert(&self, alert: &FailureAlert) -> Result<()> {
+        self.db
+            .collection::<FailureAlert>(FAILURE_ALERTS)
+            .insert_one(alert, None)
+            .await?;
+
+        Ok(())
+    }
+    /// Fetches the alerts raised since the given timestamp, oldest first.
+    pub async fn fetch_failure_alerts(&self, since: Timestamp) -> Result<Vec<FailureAlert>> {
+        let mut cursor = self
+            .db
+            .collection::<FailureAlert>(FAILURE_ALERTS)
+            .find(
+                doc! {
+                    "raised_at": {
+                        "$gte": since.to_bson()?,
+                    }
+                },
+                FindOptions::builder()
+                    .sort(doc! { "raised_at": 1, "_id": 1 })
+                    .build(),
+            )
+            .await?;
+
+        let mut alerts = vec![];
+        while let Some(alert) = cursor.next().await {
+            alerts.push(alert?);
+        }
+
+        Ok(alerts)
+    }
+    /// Removes archived messages which were sent before the given timestamp.
+    /// Returns the number of removed messages.
+    pub async fn prune_outbound_messages(&self, before: Timestamp) -> Result<usize> {
+        let coll = self.db.collection::<OutboundMessage>(OUTBOUND_ARCHIVE);
+
+        let res = coll
+            .delete_many(
+                doc! {
+                    "timestamp": {
+                        "$lt": before.to_bson()?,
+                    }
+                },
+                None,
+            )
+            .await?;
+
+        Ok(res.deleted_count as usize)
+    }
+    /// Claims the oldest pending outbox entry of the adapter for `lease`
+    /// seconds, during which no other dispatcher picks it up.
+    pub async fn claim_outbox_entry(
+        &self,
+        adapter: &str,
+        lease: u64,
+    ) -> Result<Option<OutboxEntry>> {
+        let coll = self.db.collection::<OutboxEntry>(OUTBOX);
+        let now = Timestamp::now();
+
+        let entry = coll
+            .find_one_and_update(
+                doc! {
+                    "adapter": adapter,
+                    "done": false,
+                    "$or": [
+                        { "locked_until": null },
+                        { "locked_until": { "$lte": now.to_bson()? } },
+                    ],
+                },
+                doc! {
+                    "$set": {
+                        "locked_until": Timestamp::from(now.raw() + lease).to_bson()?,
+                    }
+                },
+                FindOneAndUpdateOptions::builder()
+                    .sort(doc! { "created": 1 })
+                    .return_document(ReturnDocument::After)
+                    .build(),
+            )
+            .await?;
+
+        Ok(entry)
+    }
+    pub async fn complete_outbox_entry(&self, id: &str) -> Result<()> {
+        let coll = self.db.collection::<OutboxEntry>(OUTBOX);
+
+        coll.update_one(
+            doc! {
+                "id": id,
+            },
+            doc! {
+                "$set": {
+                    "done": true,
+                    "locked_until": null,
+                }
+            },
+            None,
+        )
+        .await?;
+
+        Ok(())
+    }
+    /// Stores the second challenge issued for the outbox entry, which is sent
+    /// again if the delivery is retried.
+    pub async fn set_outbox_challenge(&self, id: &str, challenge: &ExpectedMessage) -> Result<()> {
+        let coll = self.db.collection::<OutboxEntry>(OUTBOX);
+
+        coll.update_one(
+            doc! {
+                "id": id,
+            },
+            doc! {
+                "$set": {
+                    "challenge": challenge.to_bson()?,
+                }
+            },
+            None,
+        )
+        .await?;
+
+        Ok(())
+    }
+    /// Records a failed delivery, the entry is dispatched again after
+    /// `retry_at`.
+    pub async fn retry_outbox_entry(&self, id: &str, retry_at: Timestamp) -> Result<()> {
+        let coll = self.db.collection::<OutboxEntry>(OUTBOX);
+
+        coll.update_one(
+            doc! {
+                "id": id,
+            },
+            doc! {
+                "$inc": {
+                    "attempts": 1_i64,
+                },
+                "$set": {
+                    "locked_until": retry_at.to_bson()?,
+                }
+            },
+            None,
+        )
+        .await?;
+
+        Ok(())
+    }
+    #[cfg(test)]
+    pub async fn fetch_outbox_entries(
+        &self,
+        context: &IdentityContext,
+    ) -> Result<Vec<OutboxEntry>> {
+        let coll = self.db.collection::<OutboxEntry>(OUTBOX);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                FindOptions::builder().sort(doc! { "created": 1 }).build(),
+            )
+            .await?;
+
+        let mut entries = vec![];
+        while let Some(entry) = cursor.next().await {
+            entries.push(entry?);
+        }
+
+        Ok(entries)
+    }
+    /// Removes dispatched outbox entries which were created before the given
+    /// timestamp. Returns the number of removed entries.
+    pub async fn prune_outbox(&self, before: Timestamp) -> Result<usize> {
+        let coll = self.db.collection::<OutboxEntry>(OUTBOX);
+
+        let res = coll
+            .delete_many(
+                doc! {
+                    "done": true,
+                    "created": {
+                        "$lt": before.to_bson()?,
+                    }
+                },
+                None,
+            )
+            .await?;
+
+        Ok(res.deleted_count as usize)
+    }
+    /// Records that the message of the service is processed. Returns `false`
+    /// if it was already claimed, e.g. by another account of the service.
+    pub async fn claim_message(&self, service: &str, id: MessageId) -> Result<bool> {
+        let coll = self.db.collection::<Document>(PROCESSED_MESSAGES);
+
+        let res = coll
+            .insert_one(
+                doc! {
+                    "service": service,
+                    "message_id": id.to_bson()?,
+                    "timestamp": Timestamp::now().to_bson()?,
+                },
+                None,
+            )
+            .await;
+
+        match res {
+            Ok(_) => Ok(true),
+            Err(err) => match *err.kind {
+                ErrorKind::Write(WriteFailure::WriteError(ref error))
+                    if error.code == DUPLICATE_KEY_ERROR =>
+                {
+                    Ok(false)
+                }
+                _ => Err(err.into()),
+            },
+        }
+    }
+    /// Records that the operation is processed within the transaction.
+    /// Returns `false` if it was already processed.
+    async fn claim_operation(&self, operation: &str, session: &mut ClientSession) -> Result<bool> {
+        let coll = self.db.collection::<Document>(PROCESSED_OPERATIONS);
+
+        let processed = coll
+            .find_one_with_session(
+                doc! {
+                    "operation": operation,
+                },
+                None,
+                session,
+            )
+            .await?;
+
+        if processed.is_some() {
+            return Ok(false);
+        }
+
+        coll.insert_one_with_session(
+            doc! {
+                "operation": operation,
+                "timestamp": Timestamp::now().to_bson()?,
+            },
+            None,
+            session,
+        )
+        .await?;
+
+        Ok(true)
+    }
+    /// Removes the IDs of claimed messages and the keys of processed
+    /// operations which are older than the given timestamp.
+    pub async fn prune_processed_messages(&self, before: Timestamp) -> Result<usize> {
+        let filter = doc! {
+            "timestamp": {
+                "$lt": before.to_bson()?,
+            }
+        };
+
+        let mut pruned = 0;
+        for name in [PROCESSED_MESSAGES, PROCESSED_OPERATIONS] {
+            let res = self
+                .db
+                .collection::<Document>(name)
+                .delete_many(filter.clone(), None)
+                .await?;
+
+            pruned += res.deleted_count as usize;
+        }
+
+        Ok(pruned)
+    }
+    pub async fn insert_pending_approval(&self, approval: &PendingApproval) -> Result<()> {
+        let coll = self.db.collection::<PendingApproval>(PENDING_APPROVALS);
+
+        coll.insert_one(approval, None).await?;
+
+        Ok(())
+    }
+    /// Fetches all approvals which have not expired yet.
+    pub async fn fetch_pending_approvals(&self) -> Result<Vec<PendingApproval>> {
+        let coll = self.db.collection::<PendingApproval>(PENDING_APPROVALS);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "expires_at": {
+                        "$gt": Timestamp::now().to_bson()?,
+                    }
+                },
+                None,
+            )
+            .await?;
+
+        let mut approvals = vec![];
+        while let Some(doc) = cursor.next().await {
+            approvals.push(doc?);
+        }
+
+        Ok(approvals)
+    }
+    pub async fn fetch_pending_approval(&self, id: &str) -> Result<Option<PendingApproval>> {
+        let coll = self.db.collection::<PendingApproval>(PENDING_APPROVALS);
+
+        let approval = coll
+            .find_one(
+                doc! {
+                    "id": id,
+                    "expires_at": {
+                        "$gt": Timestamp::now().to_bson()?,
+                    }
+                },
+                None,
+            )
+            .await?;
+
+        Ok(approval)
+    }
+    /// Returns `false` if the approval was already removed.
+    pub async fn remove_pending_approval(&self, id: &str) -> Result<bool> {
+        let coll = self.db.collection::<PendingApproval>(PENDING_APPROVALS);
+
+        let res = coll
+            .delete_one(
+                doc! {
+                    "id": id,
+                },
+                None,
+            )
+            .await?;
+
+        Ok(res.deleted_count == 1)
+    }
+    /// Returns the reason if judgement requests of the address are refused,
+    /// either by the config or by an admin.
+    pub async fn check_blocked(&self, context: &IdentityContext) -> Result<Option<String>> {
+        if let Some(reason) = self
+            .blocklist
+            .as_ref()
+            .and_then(|list| list.check(&context.address))
+        {
+            return Ok(Some(reason));
+        }
+
+        let coll = self.db.collection::<BlockedAddress>(BLOCKED_ADDRESSES);
+        let blocked = coll
+            .find_one(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                None,
+            )
+            .await?;
+
+        Ok(blocked.map(|blocked| blocked.reason))
+    }
+    /// Places the judgement request in the terminal blocked state, removing a
+    /// pending judgement state. Returns `false` if the request was already
+    /// blocked before.
+    pub async fn insert_blocked_request(
+        &self,
+        context: &IdentityContext,
+        reason: &str,
+    ) -> Result<bool> {
+        let mut session = self.start_transaction().await?;
+
+        let res = self
+            .db
+            .collection::<BlockedRequest>(BLOCKED_REQUESTS)
+            .update_one_with_session(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                doc! {
+                    "$setOnInsert": BlockedRequest {
+                        context: context.clone(),
+                        reason: reason.to_string(),
+                        timestamp: Timestamp::now(),
+                    }
+                    .to_bson()?,
+                },
+                {
+                    let mut opt = UpdateOptions::default();
+                    opt.upsert = Some(true);
+                    Some(opt)
+                },
+                &mut session,
+            )
+            .await?;
+
+        // Judged identities are kept.
+        self.db
+            .collection::<JudgementState>(IDENTITY_COLLECTION)
+            .delete_one_with_session(
+                doc! {
+                    "context": context.to_bson()?,
+                    "judgement_submitted": false,
+                },
+                None,
+                &mut session,
+            )
+            .await?;
+
+        session.commit_transaction().await?;
+
+        Ok(res.upserted_id.is_some())
+    }
+    /// The stored decision of the compliance endpoint on the judgement request
+    /// of the identity, if it was already submitted.
+    pub async fn fetch_compliance_decision(
+        &self,
+        context: &IdentityContext,
+    ) -> Result<Option<ComplianceDecision>> {
+        let record = self
+            .db
+            .collection::<ComplianceRecord>(COMPLIANCE_DECISIONS)
+            .find_one(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                None,
+            )
+            .await?;
+
+        Ok(record.map(|record| record.decision))
+    }
+    /// Stores the decision on a judgement request which is not inserted, i.e.
+    /// which was denied. Other decisions are stored together with the
+    /// identity, see `JudgementState::compliance_decision`.
+    pub async fn insert_compliance_decision(
+        &self,
+        context: &IdentityContext,
+        decision: ComplianceDecision,
+    ) -> Result<()> {
+        let mut session = self.start_transaction().await?;
+        self.record_compliance_decision(context, decision, &mut session)
+            .await?;
+        session.commit_transaction().await?;
+
+        Ok(())
+    }
+    async fn record_compliance_decision(
+        &self,
+        context: &IdentityContext,
+        decision: ComplianceDecision,
+        session: &mut ClientSession,
+    ) -> Result<()> {
+        self.db
+            .collection::<ComplianceRecord>(COMPLIANCE_DECISIONS)
+            .update_one_with_session(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                doc! {
+                    "$setOnInsert": ComplianceRecord {
+                        context: context.clone(),
+                        decision,
+                        timestamp: Timestamp::now(),
+                    }
+                    .to_bson()?,
+                },
+                {
+                    let mut opt = UpdateOptions::default();
+                    opt.upsert = Some(true);
+                    Some(opt)
+                },
+                session,
+            )
+            .await?;
+
+        Ok(())
+    }
+    /// Blocks the address and removes its pending judgement state, so it is
+    /// never judged. Returns `false` if the address was already blocked.
+    pub async fn block_address(
+        &self,
+        context: &IdentityContext,
+        reason: &str,
+        admin: &MatrixHandle,
+    ) -> Result<bool> {
+        let mut session = self.start_transaction().await?;
+
+        let res = self
+            .db
+            .collection::<BlockedAddress>(BLOCKED_ADDRESSES)
+            .update_one_with_session(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                doc! {
+                    "$setOnInsert": BlockedAddress {
+                        context: context.clone(),
+                        reason: reason.to_string(),
+                        blocked_by: admin.clone(),
+                        timestamp: Timestamp::now(),
+                    }
+                    .to_bson()?,
+                },
+                {
+                    let mut opt = UpdateOptions::default();
+                    opt.upsert = Some(true);
+                    Some(opt)
+                },
+                &mut session,
+            )
+            .await?;
+
+        if res.upserted_id.is_none() {
+            session.commit_transaction().await?;
+            return Ok(false);
+        }
+
+        // The request is placed in the blocked state (and the Watcher
+        // informed) once the Watcher sends it again.
+        self.db
+            .collection::<JudgementState>(IDENTITY_COLLECTION)
+            .delete_one_with_session(
+                doc! {
+                    "context": context.to_bson()?,
+                    "judgement_submitted": false,
+                },
+                None,
+                &mut session,
+            )
+            .await?;
+
+        self.insert_blocklist_audit(
+            BlocklistAuditEntry {
+                action: BlocklistAction::Block,
+                context: context.clone(),
+                admin: admin.clone(),
+                reason: Some(reason.to_string()),
+                timestamp: Timestamp::now(),
+            },
+            &mut session,
+        )
+        .await?;
+
+        session.commit_transaction().await?;
+
+        Ok(true)
+    }
+    /// Unblocks the address, its judgement request is processed again once
+    /// the Watcher sends it. Returns `false` if the address was not blocked.
+    pub async fn unblock_address(
+        &self,
+        context: &IdentityContext,
+        admin: &MatrixHandle,
+    ) -> Result<bool> {
+        let mut session = self.start_transaction().await?;
+
+        let res = self
+            .db
+            .collection::<BlockedAddress>(BLOCKED_ADDRESSES)
+            .delete_one_with_session(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                None,
+                &mut session,
+            )
+            .await?;
+
+        if res.deleted_count == 0 {
+            session.commit_transaction().await?;
+            return Ok(false);
+        }
+
+        self.db
+            .collection::<BlockedRequest>(BLOCKED_REQUESTS)
+            .delete_one_with_session(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                None,
+                &mut session,
+            )
+            .await?;
+
+        self.insert_blocklist_audit(
+            BlocklistAuditEntry {
+                action: BlocklistAction::Unblock,
+                context: context.clone(),
+                admin: admin.clone(),
+                reason: None,
+                timestamp: Timestamp::now(),
+            },
+            &mut session,
+        )
+        .await?;
+
+        session.commit_transaction().await?;
+
+        Ok(true)
+    }
+    async fn insert_blocklist_audit(
+        &self,
+        entry: BlocklistAuditEntry,
+        session: &mut ClientSession,
+    ) -> Result<()> {
+        info!(
+            "Admin {} {:?} {:?}: {:?}",
+            entry.admin, entry.action, entry.context, entry.reason
+        );
+
+        self.db
+            .collection::<BlocklistAuditEntry>(BLOCKLIST_AUDIT)
+            .insert_one_with_session(entry, None, session)
+            .await?;
+
+        Ok(())
+    }
+    pub async fn fetch_blocked_addresses(&self) -> Result<Vec<BlockedAddress>> {
+        let coll = self.db.collection::<BlockedAddress>(BLOCKED_ADDRESSES);
+
+        let mut cursor = coll.find(None, None).await?;
+
+        let mut blocked = vec![];
+        while let Some(doc) = cursor.next().await {
+            blocked.push(doc?);
+        }
+
+        Ok(blocked)
+    }
+    pub async fn fetch_blocklist_audit(
+        &self,
+        context: &IdentityContext,
+    ) -> Result<Vec<BlocklistAuditEntry>> {
+        let coll = self.db.collection::<BlocklistAuditEntry>(BLOCKLIST_AUDIT);
+
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                FindOptions::builder()
+                    .sort(doc! { "timestamp": 1, "_id": 1 })
+                    .build(),
+            )
+            .await?;
+
+        let mut entries = vec![];
+        while let Some(doc) = cursor.next().await {
+            entries.push(doc?);
+        }
+
+        Ok(entries)
+    }
+    /// Pauses the submission of judgements for the network. Returns `false` if
+    /// submissions were already paused.
+    pub async fn pause_submissions(&self, chain: ChainName, admin: &MatrixHandle) -> Result<bool> {
+        let res = self
+            .db
+            .collection::<PausedChain>(PAUSED_CHAINS)
+            .update_one(
+                doc! {
+                    "chain": chain.to_bson()?,
+                },
+                doc! {
+                    "$setOnInsert": PausedChain {
+                        chain,
+                        paused_by: admin.clone(),
+                        timestamp: Timestamp::now(),
+                    }
+                    .to_bson()?,
+                },
+                {
+                    let mut opt = UpdateOptions::default();
+                    opt.upsert = Some(true);
+                    Some(opt)
+                },
+            )
+            .await?;
+
+        if res.upserted_id.is_none() {
+            return Ok(false);
+        }
+
+        self.withhold_judgements(chain, WithheldReason::SubmissionsPaused)
+            .await?;
+
+        Ok(true)
+    }
+    /// Returns `false` if submissions were not paused.
+    pub async fn resume_submissions(&self, chain: ChainName) -> Result<bool> {
+        let res = self
+            .db
+            .collection::<PausedChain>(PAUSED_CHAINS)
+            .delete_one(
+                doc! {
+                    "chain": chain.to_bson()?,
+                },
+                None,
+            )
+            .await?;
+
+        if res.deleted_count == 0 {
+            return Ok(false);
+        }
+
+        self.db
+            .collection::<JudgementState>(IDENTITY_COLLECTION)
+            .update_many(
+                doc! {
+                    "context.chain": chain.to_bson()?,
+                    "judgement_withheld": { "$ne": Bson::Null },
+                },
+                doc! {
+                    "$set": {
+                        "judgement_withheld": Bson::Null,
+                    }
+                },
+                None,
+            )
+            .await?;
+
+        Ok(true)
+    }
+    /// Attaches the reason to the fully verified identities of the network
+    /// which were not judged yet. Identities which already carry the reason are
+    /// skipped, so the event is only created once.
+    async fn withhold_judgements(&self, chain: ChainName, reason: WithheldReason) -> Result<()> {
+        let mut session = self.start_transaction().await?;
+        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
+
+        let mut cursor = coll
+            .find_with_session(
+                doc! {
+                    "context.chain": chain.to_bson()?,
+                    "is_fully_verified": true,
+                    "judgement_submitted": false,
+                    "judgement_withheld": { "$ne": reason.to_bson()? },
+                },
+                None,
+                &mut session,
+            )
+            .await?;
+
+        let mut contexts = vec![];
+        while let Some(state) = cursor.next(&mut session).await {
+            contexts.push(state?.context);
         }
-    }
-    pub async fn set_judged(&self, context: &IdentityContext) -> Result<()> {
-        let mut session = self.start_transaction().await?;
-        let coll = self.db.collection::<JudgementState>(IDENTITY_COLLECTION);
 
-        let res = coll
-            .update_one_with_session(
+        for context in contexts {
+            coll.update_one_with_session(
                 doc! {
                     "context": context.to_bson()?,
-                    "judgement_submitted": false,
                 },
                 doc! {
                     "$set": {
-                        "judgement_submitted": true,
+                        "judgement_withheld": reason.to_bson()?,
                     }
                 },
                 None,
@@ -874,12 +4703,8 @@ impl Database {
             )
             .await?;
 
-        // Create event.
-        if res.modified_count == 1 {
             self.insert_event(
-                NotificationMessage::JudgementProvided {
-                    context: context.clone(),
-                },
+                NotificationMessage::JudgementWithheld { context, reason },
                 &mut session,
             )
             .await?;
@@ -889,131 +4714,386 @@ impl Database {
 
         Ok(())
     }
-    pub async fn insert_display_name(&self, name: &DisplayNameEntry) -> Result<()> {
-        let coll = self.db.collection::<DisplayNameEntry>(DISPLAY_NAMES);
+    pub async fn is_submission_paused(&self, chain: ChainName) -> Result<bool> {
+        Ok(self
+            .db
+            .collection::<PausedChain>(PAUSED_CHAINS)
+            .find_one(
+                doc! {
+                    "chain": chain.to_bson()?,
+                },
+                None,
+            )
+            .await?
+            .is_some())
+    }
+    pub async fn fetch_paused_chains(&self) -> Result<Vec<PausedChain>> {
+        let coll = self.db.collection::<PausedChain>(PAUSED_CHAINS);
 
-        coll.update_one(
-            doc! {
-                "display_name": name.display_name.to_bson()?,
-                "context": name.context.to_bson()?,
-            },
-            doc! {
-                "$setOnInsert": name.to_bson()?,
-            },
-            {
-                let mut opt = UpdateOptions::default();
-                opt.upsert = Some(true);
-                Some(opt)
-            },
-        )
-        .await?;
+        let mut cursor = coll
+            .find(
+                None,
+                FindOptions::builder().sort(doc! { "chain": 1 }).build(),
+            )
+            .await?;
 
-        Ok(())
+        let mut paused = vec![];
+        while let Some(doc) = cursor.next().await {
+            paused.push(doc?);
+        }
+
+        Ok(paused)
     }
-    pub async fn fetch_display_names(&self, chain: ChainName) -> Result<Vec<DisplayNameEntry>> {
-        let coll = self.db.collection::<DisplayNameEntry>(DISPLAY_NAMES);
+    /// Returns `false` if the adapter was already paused.
+    pub async fn pause_adapter(&self, adapter: &str, admin: &MatrixHandle) -> Result<bool> {
+        let res = self
+            .db
+            .collection::<PausedAdapter>(PAUSED_ADAPTERS)
+            .update_one(
+                doc! {
+                    "adapter": adapter,
+                },
+                doc! {
+                    "$setOnInsert": PausedAdapter {
+                        adapter: adapter.to_string(),
+                        paused_by: admin.clone(),
+                        timestamp: Timestamp::now(),
+                    }
+                    .to_bson()?,
+                },
+                {
+                    let mut opt = UpdateOptions::default();
+                    opt.upsert = Some(true);
+                    Some(opt)
+                },
+            )
+            .await?;
+
+        Ok(res.upserted_id.is_some())
+    }
+    /// Returns `false` if the adapter was not paused.
+    pub async fn resume_adapter(&self, adapter: &str) -> Result<bool> {
+        let res = self
+            .db
+            .collection::<PausedAdapter>(PAUSED_ADAPTERS)
+            .delete_one(
+                doc! {
+                    "adapter": adapter,
+                },
+                None,
+            )
+            .await?;
+
+        Ok(res.deleted_count > 0)
+    }
+    pub async fn is_adapter_paused(&self, adapter: &str) -> Result<bool> {
+        Ok(self
+            .db
+            .collection::<PausedAdapter>(PAUSED_ADAPTERS)
+            .find_one(
+                doc! {
+                    "adapter": adapter,
+                },
+                None,
+            )
+            .await?
+            .is_some())
+    }
+    pub async fn fetch_paused_adapters(&self) -> Result<Vec<PausedAdapter>> {
+        let coll = self.db.collection::<PausedAdapter>(PAUSED_ADAPTERS);
 
         let mut cursor = coll
             .find(
+                None,
+                FindOptions::builder().sort(doc! { "adapter": 1 }).build(),
+            )
+            .await?;
+
+        let mut paused = vec![];
+        while let Some(doc) = cursor.next().await {
+            paused.push(doc?);
+        }
+
+        Ok(paused)
+    }
+    /// Stops accepting new judgement requests of the network, or of all
+    /// networks if `chain` is `None`. Returns `false` if those were already
+    /// paused.
+    pub async fn pause_requests(
+        &self,
+        chain: Option<ChainName>,
+        admin: &MatrixHandle,
+    ) -> Result<bool> {
+        let res = self
+            .db
+            .collection::<PausedRequests>(PAUSED_REQUESTS)
+            .update_one(
                 doc! {
-                    "context.chain": chain.to_bson()?,
+                    "chain": chain.to_bson()?,
+                },
+                doc! {
+                    "$setOnInsert": PausedRequests {
+                        chain,
+                        paused_by: admin.clone(),
+                        timestamp: Timestamp::now(),
+                    }
+                    .to_bson()?,
+                },
+                {
+                    let mut opt = UpdateOptions::default();
+                    opt.upsert = Some(true);
+                    Some(opt)
+                },
+            )
+            .await?;
+
+        Ok(res.upserted_id.is_some())
+    }
+    /// Returns `false` if requests were not paused. Requests refused by config
+    /// stay refused.
+    pub async fn resume_requests(&self, chain: Option<ChainName>) -> Result<bool> {
+        let res = self
+            .db
+            .collection::<PausedRequests>(PAUSED_REQUESTS)
+            .delete_one(
+                doc! {
+                    "chain": chain.to_bson()?,
                 },
                 None,
             )
             .await?;
 
-        let mut names = vec![];
+        Ok(res.deleted_count > 0)
+    }
+    /// Whether new judgement requests of the network are accepted, i.e. they
+    /// are neither refused by config nor paused by an admin.
+    pub async fn accepts_requests(&self, chain: ChainName) -> Result<bool> {
+        if self.refused_requests.contains(&chain) {
+            return Ok(false);
+        }
+
+        Ok(self
+            .db
+            .collection::<PausedRequests>(PAUSED_REQUESTS)
+            .find_one(
+                doc! {
+                    "$or": [
+                        { "chain": chain.to_bson()? },
+                        { "chain": Bson::Null },
+                    ]
+                },
+                None,
+            )
+            .await?
+            .is_none())
+    }
+    pub async fn fetch_paused_requests(&self) -> Result<Vec<PausedRequests>> {
+        let coll = self.db.collection::<PausedRequests>(PAUSED_REQUESTS);
+
+        let mut cursor = coll
+            .find(
+                None,
+                FindOptions::builder().sort(doc! { "chain": 1 }).build(),
+            )
+            .await?;
+
+        let mut paused = vec![];
         while let Some(doc) = cursor.next().await {
-            names.push(doc?);
+            paused.push(doc?);
         }
 
-        Ok(names)
+        Ok(paused)
     }
-    pub async fn set_display_name_valid(&self, state: &JudgementState) -> Result<()> {
-        let mut session = self.start_transaction().await?;
-        let coll = self.db.collection::<()>(IDENTITY_COLLECTION);
+    pub async fn insert_withdrawal_nonce(&self, nonce: &WithdrawalNonce) -> Result<()> {
+        let coll = self.db.collection::<WithdrawalNonce>(WITHDRAWAL_NONCES);
+
+        coll.insert_one(nonce, None).await?;
+
+        Ok(())
+    }
+    /// Removes the nonce, returns `false` if it does not exist or expired.
+    pub async fn consume_withdrawal_nonce(
+        &self,
+        context: &IdentityContext,
+        nonce: &str,
+    ) -> Result<bool> {
+        let coll = self.db.collection::<WithdrawalNonce>(WITHDRAWAL_NONCES);
 
         let res = coll
-            .update_one_with_session(
+            .delete_one(
                 doc! {
-                    "context": state.context.to_bson()?,
-                    "fields.value.type": "display_name",
-                    "fields.challenge.content.passed": false
+                    "context": context.to_bson()?,
+                    "nonce": nonce,
+                    "expires_at": {
+                        "$gt": Timestamp::now().to_bson()?,
+                    }
                 },
+                None,
+            )
+            .await?;
+
+        Ok(res.deleted_count == 1)
+    }
+    /// Removes the pending judgement state and marks the request as withdrawn.
+    /// Returns `false` if there is no pending judgement request.
+    pub async fn withdraw_judgement_request(&self, context: &IdentityContext) -> Result<bool> {
+        let _guard = self.context_locks.lock(context).await;
+        let mut session = self.start_transaction().await?;
+
+        let res = self
+            .db
+            .collection::<JudgementState>(IDENTITY_COLLECTION)
+            .delete_one_with_session(
                 doc! {
-                    "$set": {
-                        "fields.$.challenge.content.passed": true,
-                    }
+                    "context": context.to_bson()?,
+                    "judgement_submitted": false,
                 },
                 None,
                 &mut session,
             )
             .await?;
 
-        if res.modified_count == 0 {
-            return Ok(());
+        if res.deleted_count == 0 {
+            return Ok(false);
         }
 
-        // Create event
-        self.insert_event(
-            NotificationMessage::FieldVerified {
-                context: state.context.clone(),
-                field: state
-                    .fields
-                    .iter()
-                    .find(|field| matches!(field.value, IdentityFieldValue::DisplayName(_)))
-                    .map(|field| field.value.clone())
-                    .expect("Failed to retrieve display name. This is a bug"),
-            },
-            &mut session,
-        )
-        .await?;
-
-        self.process_fully_verified(&state.context, &mut session)
+        self.db
+            .collection::<WithdrawnRequest>(WITHDRAWN_REQUESTS)
+            .update_one_with_session(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                doc! {
+                    "$set": WithdrawnRequest {
+                        context: context.clone(),
+                        timestamp: Timestamp::now(),
+                    }
+                    .to_bson()?,
+                },
+                {
+                    let mut opt = UpdateOptions::default();
+                    opt.upsert = Some(true);
+                    Some(opt)
+                },
+                &mut session,
+            )
             .await?;
 
         session.commit_transaction().await?;
 
+        Ok(true)
+    }
+    pub async fn check_withdrawn(&self, context: &IdentityContext) -> Result<bool> {
+        let coll = self.db.collection::<WithdrawnRequest>(WITHDRAWN_REQUESTS);
+
+        let withdrawn = coll
+            .find_one(
+                doc! {
+                    "context": context.to_bson()?,
+                },
+                None,
+            )
+            .await?;
+
+        Ok(withdrawn.is_some())
+    }
+    pub async fn remove_withdrawal(&self, context: &IdentityContext) -> Result<()> {
+        let coll = self.db.collection::<WithdrawnRequest>(WITHDRAWN_REQUESTS);
+
+        coll.delete_one(
+            doc! {
+                "context": context.to_bson()?,
+            },
+            None,
+        )
+        .await?;
+
         Ok(())
     }
-    pub async fn insert_display_name_violations(
+    pub async fn store_instance_registration(
         &self,
-        context: &IdentityContext,
-        violations: &Vec<DisplayNameEntry>,
+        registration: &InstanceRegistration,
     ) -> Result<()> {
-        let mut session = self.start_transaction().await?;
-        let coll = self.db.collection::<()>(IDENTITY_COLLECTION);
+        let coll = self.db.collection::<InstanceRegistration>(INSTANCES);
 
-        coll.update_one_with_session(
+        coll.update_one(
             doc! {
-                "context": context.to_bson()?,
-                "fields.value.type": "display_name",
+                "instance_id": registration.instance_id.as_str(),
             },
             doc! {
-                "$set": {
-                    "fields.$.challenge.content.violations": violations.to_bson()?
-                }
+                "$set": registration.to_document()?,
+            },
+            {
+                let mut opt = UpdateOptions::default();
+                opt.upsert = Some(true);
+                Some(opt)
             },
-            None,
-            &mut session,
         )
         .await?;
 
-        session.commit_transaction().await?;
-
         Ok(())
     }
-    async fn insert_event<T: Into<Event>>(
+    /// Instances which sent a heartbeat since the given timestamp.
+    pub async fn fetch_active_instances(
         &self,
-        event: T,
-        session: &mut ClientSession,
-    ) -> Result<()> {
-        let coll = self.db.collection(EVENT_COLLECTION);
+        since: Timestamp,
+    ) -> Result<Vec<InstanceRegistration>> {
+        let coll = self.db.collection::<InstanceRegistration>(INSTANCES);
 
-        let event = <T as Into<Event>>::into(event);
-        coll.insert_one_with_session(event.to_bson()?, None, session)
+        let mut cursor = coll
+            .find(
+                doc! {
+                    "last_seen": {
+                        "$gte": since.to_bson()?,
+                    }
+                },
+                None,
+            )
             .await?;
 
-        Ok(())
+        let mut instances = vec![];
+        while let Some(instance) = cursor.next().await {
+            instances.push(instance?);
+        }
+
+        Ok(instances)
+    }
+    /// Removes the registrations of instances without a heartbeat since the
+    /// given timestamp. Returns the number of removed registrations.
+    pub async fn prune_instance_registrations(&self, before: Timestamp) -> Result<usize> {
+        let coll = self.db.collection::<InstanceRegistration>(INSTANCES);
+
+        let res = coll
+            .delete_many(
+                doc! {
+                    "last_seen": {
+                        "$lt": before.to_bson()?,
+                    }
+                },
+                None,
+            )
+            .await?;
+
+        Ok(res.deleted_count as usize)
     }
 }
+
+async fn instrumented_client(uri: &str, query_log: &Arc<QueryLog>) -> Result<Client> {
+    let mut options = ClientOptions::parse(uri).await?;
+    options.command_event_handler = Some(Arc::clone(query_log) as _);
+    Ok(Client::with_options(options)?)
+}
+
+/// Escapes the term, so it is matched literally by `$regex`.
+fn escape_regex(term: &str) -> String {
+    let mut escaped = String::with_capacity(term.len());
+    for c in term.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+
+        escaped.push(c);
+    }
+
+    escaped
+}
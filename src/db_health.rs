@@ -0,0 +1,89 @@
+use crate::metrics;
+use crate::primitives::Timestamp;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+// Consecutive failed or slow commands after which the database is considered
+// degraded.
+const UNHEALTHY_THRESHOLD: u32 = 5;
+// Seconds the database is considered degraded after the last failed or slow
+// command.
+const COOL_DOWN: u64 = 30;
+
+/// Tracks whether MongoDB commands fail or slow down. While degraded, the
+/// connector neither requests nor processes judgement requests, since those
+/// are re-sent by the Watcher anyway.
+#[derive(Debug, Default)]
+pub struct DbHealth {
+    unhealthy_streak: AtomicU32,
+    // UNIX time until which the database is considered degraded.
+    degraded_until: AtomicU64,
+    // The state which was last logged and exported.
+    reported: AtomicBool,
+}
+
+impl DbHealth {
+    pub fn record(&self, healthy: bool) {
+        self.record_at(healthy, Timestamp::now());
+    }
+    fn record_at(&self, healthy: bool, now: Timestamp) {
+        if healthy {
+            self.unhealthy_streak.store(0, Ordering::Relaxed);
+            return;
+        }
+
+        let streak = self.unhealthy_streak.fetch_add(1, Ordering::Relaxed) + 1;
+        if streak >= UNHEALTHY_THRESHOLD {
+            self.degraded_until
+                .store(now.raw() + COOL_DOWN, Ordering::Relaxed);
+        }
+    }
+    pub fn is_degraded(&self) -> bool {
+        self.is_degraded_at(Timestamp::now())
+    }
+    fn is_degraded_at(&self, now: Timestamp) -> bool {
+        let degraded = now.raw() < self.degraded_until.load(Ordering::Relaxed);
+
+        if self.reported.swap(degraded, Ordering::Relaxed) != degraded {
+            if degraded {
+                warn!("MongoDB is degraded, throttling judgement requests");
+            } else {
+                info!("MongoDB recovered, processing judgement requests again");
+            }
+
+            metrics::set_db_degraded(degraded);
+        }
+
+        degraded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn degrade_and_recover() {
+        let health = DbHealth::default();
+        let now = Timestamp::from(1_000);
+
+        for _ in 0..UNHEALTHY_THRESHOLD - 1 {
+            health.record_at(false, now);
+        }
+        assert!(!health.is_degraded_at(now));
+
+        // A successful command resets the streak.
+        health.record_at(true, now);
+        health.record_at(false, now);
+        assert!(!health.is_degraded_at(now));
+
+        for _ in 0..UNHEALTHY_THRESHOLD {
+            health.record_at(false, now);
+        }
+        assert!(health.is_degraded_at(now));
+
+        // Stays degraded until the cool down passed.
+        health.record_at(true, now);
+        assert!(health.is_degraded_at(Timestamp::from(1_000 + COOL_DOWN - 1)));
+        assert!(!health.is_degraded_at(Timestamp::from(1_000 + COOL_DOWN)));
+    }
+}
@@ -1,6 +1,8 @@
 use crate::connector::DisplayNameEntry;
 use crate::database::Database;
+use crate::display_name_normalization::{confusable_skeleton, normalize_display_name};
 use crate::primitives::{ChainName, IdentityContext, JudgementState};
+use crate::reserved_names::ReservedNames;
 use crate::{DisplayNameConfig, Result};
 use strsim::jaro;
 
@@ -10,11 +12,36 @@ const VIOLATIONS_CAP: usize = 5;
 pub struct DisplayNameVerifier {
     db: Database,
     config: DisplayNameConfig,
+    // Names reserved by the config, the ones reserved by admins are fetched
+    // with every check.
+    reserved: ReservedNames,
 }
 
 impl DisplayNameVerifier {
     pub fn new(db: Database, config: DisplayNameConfig) -> Self {
-        DisplayNameVerifier { db, config }
+        let reserved = ReservedNames::from_config(
+            &config.reserved_names.clone().unwrap_or_default(),
+            &config.reserved_patterns.clone().unwrap_or_default(),
+        );
+
+        DisplayNameVerifier {
+            db,
+            config,
+            reserved,
+        }
+    }
+    /// Returns the reserved name or pattern matched by the name, if any.
+    pub async fn check_reserved(&self, name: &str) -> Result<Option<String>> {
+        if let Some(reserved) = self.reserved.matches(name) {
+            return Ok(Some(reserved));
+        }
+
+        let mut reserved = ReservedNames::default();
+        for entry in self.db.fetch_reserved_display_names().await? {
+            reserved.add(&entry);
+        }
+
+        Ok(reserved.matches(name))
     }
     pub async fn check_similarities(
         &self,
@@ -25,6 +52,12 @@ impl DisplayNameVerifier {
         skip: Option<&IdentityContext>,
     ) -> Result<Vec<DisplayNameEntry>> {
         let current = self.db.fetch_display_names(chain).await?;
+        let skeleton = self
+            .config
+            .confusables
+            .unwrap_or(false)
+            .then(|| confusable_skeleton(name));
+        let name = normalize_display_name(name);
 
         let mut violations = vec![];
         for existing in current {
@@ -35,7 +68,12 @@ impl DisplayNameVerifier {
                 }
             }
 
-            if is_too_similar(name, &existing.display_name, self.config.limit) {
+            let confusable = skeleton
+                .as_ref()
+                .map(|skeleton| is_confusable(skeleton, existing.display_name.as_str()))
+                .unwrap_or(false);
+
+            if confusable || is_too_similar(&name, &normalized(&existing), self.config.limit) {
                 // Only show up to `VIOLATIONS_CAP` violations.
                 if violations.len() == VIOLATIONS_CAP {
                     break;
@@ -58,6 +96,15 @@ impl DisplayNameVerifier {
             return Ok(());
         };
 
+        // Reserved names are rejected regardless of the similarity.
+        if let Some(reserved) = self.check_reserved(name).await? {
+            self.db
+                .set_display_name_reserved(&state.context, &reserved)
+                .await?;
+
+            return Ok(());
+        }
+
         let violations = self
             .check_similarities(name, state.context.chain, Some(&state.context))
             .await?;
@@ -74,16 +121,93 @@ impl DisplayNameVerifier {
     }
 }
 
+fn normalized(entry: &DisplayNameEntry) -> String {
+    if entry.normalized.is_empty() {
+        normalize_display_name(entry.display_name.as_str())
+    } else {
+        entry.normalized.clone()
+    }
+}
+
+/// Expects both names to be normalized.
 fn is_too_similar(existing: &str, new: &str, limit: f64) -> bool {
-    let name_str = existing.to_lowercase();
-    let account_str = new.to_lowercase();
+    similarity(existing, new) > limit
+}
 
-    let similarities = [
-        jaro(&name_str, &account_str),
-        jaro_words(&name_str, &account_str, &[" ", "-", "_"]),
-    ];
+/// Whether the existing name is rendered like the name with the given
+/// confusable skeleton.
+fn is_confusable(skeleton: &str, existing: &str) -> bool {
+    !skeleton.is_empty() && skeleton == confusable_skeleton(existing)
+}
+
+fn similarity(existing: &str, new: &str) -> f64 {
+    jaro(existing, new).max(jaro_words(existing, new, &[" ", "-", "_"]))
+}
 
-    similarities.iter().any(|&s| s > limit)
+/// The effect of changing the similarity limit on the display names of a
+/// chain.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LimitSimulation {
+    pub chain: ChainName,
+    pub current_limit: f64,
+    pub proposed_limit: f64,
+    // Number of accepted display names.
+    pub accepted: usize,
+    // Accepted display names which would violate other accepted ones.
+    pub newly_rejected: Vec<DisplayNameEntry>,
+    // Rejected display names of pending requests which would no longer
+    // violate any accepted one.
+    pub newly_accepted: Vec<DisplayNameEntry>,
+}
+
+/// Re-evaluates the accepted display names against each other and the
+/// rejected ones against the accepted names with both limits.
+pub fn simulate_limit(
+    chain: ChainName,
+    accepted: &[DisplayNameEntry],
+    rejected: &[DisplayNameEntry],
+    current_limit: f64,
+    proposed_limit: f64,
+) -> LimitSimulation {
+    let names: Vec<String> = accepted.iter().map(normalized).collect();
+
+    // Highest similarity to any accepted name of another identity.
+    let max_similarity = |entry: &DisplayNameEntry, name: &str| {
+        accepted
+            .iter()
+            .zip(&names)
+            .filter(|(other, _)| other.context != entry.context)
+            .map(|(_, other)| similarity(name, other))
+            .fold(0.0, f64::max)
+    };
+
+    let newly_rejected = accepted
+        .iter()
+        .zip(&names)
+        .filter(|(entry, name)| {
+            let similarity = max_similarity(entry, name);
+            similarity > proposed_limit && similarity <= current_limit
+        })
+        .map(|(entry, _)| entry.clone())
+        .collect();
+
+    let newly_accepted = rejected
+        .iter()
+        .filter(|entry| {
+            let similarity = max_similarity(entry, &normalized(entry));
+            similarity <= proposed_limit && similarity > current_limit
+        })
+        .cloned()
+        .collect();
+
+    LimitSimulation {
+        chain,
+        current_limit,
+        proposed_limit,
+        accepted: accepted.len(),
+        newly_rejected,
+        newly_accepted,
+    }
 }
 
 fn jaro_words(left: &str, right: &str, delimiter: &[&str]) -> f64 {
@@ -124,3 +248,82 @@ fn jaro_words(left: &str, right: &str, delimiter: &[&str]) -> f64 {
 
     total as f64 / left_words.len().max(right_words.len()) as f64
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{ChainAddress, DisplayName};
+
+    fn entry(address: &str, name: &str) -> DisplayNameEntry {
+        DisplayNameEntry::new(
+            IdentityContext::new(ChainAddress::from(address.to_string()), ChainName::Polkadot),
+            DisplayName::from(name.to_string()),
+        )
+    }
+
+    #[test]
+    fn confusable_names() {
+        let skeleton = confusable_skeleton("\u{13AC}v\u{0435}");
+
+        // Not caught by the similarity alone.
+        assert!(!is_too_similar(
+            "eve",
+            &normalize_display_name("\u{13AC}v\u{0435}"),
+            0.85
+        ));
+        assert!(is_confusable(&skeleton, "Eve"));
+        assert!(is_confusable(
+            &confusable_skeleton("\u{13F4}in\u{0430}nce"),
+            "Binance"
+        ));
+        assert!(is_confusable(&confusable_skeleton("rnoonbeam"), "Moonbeam"));
+        assert!(!is_confusable(&skeleton, "Ave"));
+        assert!(!is_confusable("", "\u{200B}"));
+    }
+
+    #[test]
+    fn simulate_limit_changes() {
+        let accepted = vec![
+            entry("1", "Alice"),
+            entry("2", "Alice Smith"),
+            entry("3", "Completely different"),
+        ];
+        let rejected = vec![entry("4", "Alice Smyth")];
+
+        let similarity = |left: &str, right: &str| {
+            similarity(
+                &normalize_display_name(left),
+                &normalize_display_name(right),
+            )
+        };
+        let accepted_pair = similarity("Alice", "Alice Smith");
+        let rejected_pair = similarity("Alice Smyth", "Alice Smith");
+        assert!(accepted_pair < rejected_pair);
+
+        // A stricter limit rejects accepted names.
+        let simulation = simulate_limit(
+            ChainName::Polkadot,
+            &accepted,
+            &rejected,
+            rejected_pair - 0.01,
+            accepted_pair - 0.01,
+        );
+        assert_eq!(simulation.accepted, 3);
+        assert_eq!(
+            simulation.newly_rejected,
+            vec![accepted[0].clone(), accepted[1].clone()]
+        );
+        assert!(simulation.newly_accepted.is_empty());
+
+        // A looser limit accepts rejected names.
+        let simulation = simulate_limit(
+            ChainName::Polkadot,
+            &accepted,
+            &rejected,
+            rejected_pair - 0.01,
+            rejected_pair,
+        );
+        assert!(simulation.newly_rejected.is_empty());
+        assert_eq!(simulation.newly_accepted, rejected);
+    }
+}
@@ -0,0 +1,247 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// Normalizes the display name for comparisons, so lookalike names (e.g. with
+/// Cyrillic letters or zero-width characters) match the original name. The
+/// result is only used for comparisons, never displayed.
+pub fn normalize_display_name(name: &str) -> String {
+    let normalized: String = name
+        .nfkc()
+        .filter(|&c| !is_invisible(c))
+        .flat_map(char::to_lowercase)
+        .map(skeleton)
+        .collect();
+
+    normalized
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// The confusable skeleton of the display name, in the spirit of the UTS #39
+/// skeleton: two names with the same skeleton are rendered (almost) the same,
+/// e.g. "Ᏼinance" and "Binance" or "rnoon" and "moon". Compared to
+/// `normalize_display_name`, diacritics, separators and whitespace are
+/// dropped and further homoglyphs are mapped, so the result is only suited
+/// for equality checks.
+pub fn confusable_skeleton(name: &str) -> String {
+    let skeleton: String = name
+        .nfkd()
+        .filter(|&c| !is_invisible(c) && !is_combining_mark(c) && !is_separator(c))
+        .map(homoglyph)
+        .flat_map(char::to_lowercase)
+        .map(skeleton)
+        .collect();
+
+    // Letter sequences which render like a single letter.
+    skeleton.replace("rn", "m").replace("vv", "w")
+}
+
+/// Diacritics, which are separate characters after the decomposition.
+fn is_combining_mark(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '-' | '_' | '.' | '\'' | '`' | '·')
+}
+
+/// Maps characters of other scripts and symbols to the (uppercase or
+/// lowercase) Latin letters they are confused with, before the name is
+/// lowercased.
+fn homoglyph(c: char) -> char {
+    match c {
+        // Digits and symbols
+        '0' => 'o',
+        '1' | '|' | 'I' | 'ǀ' => 'l',
+        '5' => 's',
+        '$' => 's',
+        '@' => 'a',
+        // Cherokee
+        'Ꭺ' => 'A',
+        'Ᏼ' | 'Ᏸ' => 'B',
+        'Ꮯ' => 'C',
+        'Ꭰ' => 'D',
+        'Ꭼ' => 'E',
+        'Ꮐ' => 'G',
+        'Ꮋ' => 'H',
+        'Ꭻ' => 'J',
+        'Ꮶ' => 'K',
+        'Ꮮ' => 'L',
+        'Ꮇ' => 'M',
+        'Ꮲ' => 'P',
+        'Ꮪ' => 'S',
+        'Ꭲ' => 'T',
+        'Ꮩ' => 'V',
+        'Ꮃ' => 'W',
+        'Ꮓ' => 'Z',
+        // Latin letters without decomposition
+        'ı' => 'i',
+        'ȷ' => 'j',
+        'ł' | 'Ł' => 'l',
+        'ø' | 'Ø' => 'o',
+        'đ' | 'Đ' => 'd',
+        'ħ' => 'h',
+        'ƅ' => 'b',
+        'ɑ' => 'a',
+        'ɡ' => 'g',
+        'ɩ' => 'i',
+        'ʏ' => 'y',
+        // Armenian
+        'օ' => 'o',
+        'ս' => 'u',
+        'հ' => 'h',
+        'ո' => 'n',
+        'ց' => 'g',
+        'զ' => 'q',
+        'Տ' => 'S',
+        'Օ' => 'O',
+        _ => c,
+    }
+}
+
+/// Zero-width, formatting and control characters which are not visible in
+/// the rendered name.
+fn is_invisible(c: char) -> bool {
+    c.is_control()
+        || matches!(
+            c,
+            '\u{00AD}'
+                | '\u{034F}'
+                | '\u{061C}'
+                | '\u{115F}'
+                | '\u{1160}'
+                | '\u{17B4}'
+                | '\u{17B5}'
+                | '\u{180B}'..='\u{180E}'
+                | '\u{200B}'..='\u{200F}'
+                | '\u{202A}'..='\u{202E}'
+                | '\u{2060}'..='\u{2064}'
+                | '\u{206A}'..='\u{206F}'
+                | '\u{3164}'
+                | '\u{FE00}'..='\u{FE0F}'
+                | '\u{FEFF}'
+                | '\u{FFA0}'
+        )
+}
+
+/// Maps (lowercase) Cyrillic and Greek letters to the Latin letters they are
+/// commonly confused with.
+fn skeleton(c: char) -> char {
+    match c {
+        // Cyrillic
+        'а' => 'a',
+        'в' => 'b',
+        'с' => 'c',
+        'ԁ' => 'd',
+        'е' | 'ё' => 'e',
+        'һ' | 'н' => 'h',
+        'і' | 'ї' => 'i',
+        'ј' => 'j',
+        'к' => 'k',
+        'м' => 'm',
+        'о' => 'o',
+        'р' => 'p',
+        'ԛ' => 'q',
+        'ѕ' => 's',
+        'т' => 't',
+        'у' => 'y',
+        'ԝ' => 'w',
+        'х' => 'x',
+        // Greek
+        'α' => 'a',
+        'β' => 'b',
+        'ε' => 'e',
+        'η' => 'n',
+        'ι' => 'i',
+        'κ' => 'k',
+        'ν' => 'v',
+        'ο' => 'o',
+        'ρ' => 'p',
+        'τ' => 't',
+        'υ' => 'u',
+        'χ' => 'x',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_case_and_whitespace() {
+        assert_eq!(normalize_display_name("Alice"), "alice");
+        assert_eq!(
+            normalize_display_name("  Alice \t in   Wonderland "),
+            "alice in wonderland"
+        );
+        // Emojis are kept as is.
+        assert_eq!(normalize_display_name("Alice 🦀"), "alice 🦀");
+    }
+
+    #[test]
+    fn normalize_compatibility_characters() {
+        // Fullwidth and mathematical letters.
+        assert_eq!(normalize_display_name("Ａｌｉｃｅ"), "alice");
+        assert_eq!(normalize_display_name("𝐀𝐥𝐢𝐜𝐞"), "alice");
+        // Ligatures.
+        assert_eq!(normalize_display_name("ﬁnn"), "finn");
+    }
+
+    #[test]
+    fn normalize_lookalikes() {
+        // Cyrillic 'а' and 'е'.
+        assert_eq!(normalize_display_name("\u{0430}lic\u{0435}"), "alice");
+        // Uppercase Cyrillic and Greek letters.
+        assert_eq!(normalize_display_name("\u{0410}LICE"), "alice");
+        assert_eq!(normalize_display_name("B\u{039F}B"), "bob");
+        // Zero-width characters.
+        assert_eq!(
+            normalize_display_name("Al\u{200B}i\u{200D}ce\u{FEFF}"),
+            "alice"
+        );
+        assert_eq!(normalize_display_name("Alice\u{00AD}"), "alice");
+    }
+
+    #[test]
+    fn confusable_skeletons() {
+        let binance = confusable_skeleton("Binance");
+        assert_eq!(binance, "binance");
+
+        // Cherokee, Cyrillic and Armenian lookalikes.
+        assert_eq!(confusable_skeleton("\u{13F4}inance"), binance);
+        assert_eq!(confusable_skeleton("Bin\u{0430}nce"), binance);
+        assert_eq!(confusable_skeleton("Binan\u{0441}\u{0435}"), binance);
+        assert_eq!(confusable_skeleton("Bi\u{0578}ance"), binance);
+        // Zero-width characters, diacritics and separators.
+        assert_eq!(confusable_skeleton("Bi\u{200B}nan\u{200D}ce"), binance);
+        assert_eq!(confusable_skeleton("B\u{00ED}n\u{00E1}nce"), binance);
+        assert_eq!(confusable_skeleton("Bi-nance"), binance);
+        // Uppercase 'I' and lowercase 'l'.
+        assert_eq!(confusable_skeleton("AIice"), confusable_skeleton("Alice"));
+
+        // Digits, symbols and letter sequences.
+        assert_eq!(confusable_skeleton("P0lkad0t"), "polkadot");
+        assert_eq!(confusable_skeleton("Po1kadot"), "polkadot");
+        assert_eq!(
+            confusable_skeleton("Kraken"),
+            confusable_skeleton("Krak\u{0435}n")
+        );
+        assert_eq!(confusable_skeleton("rnoon"), confusable_skeleton("moon"));
+        assert_eq!(confusable_skeleton("vveb3"), confusable_skeleton("web3"));
+
+        // Different names remain different.
+        assert_ne!(
+            confusable_skeleton("Binance"),
+            confusable_skeleton("Finance")
+        );
+        assert_ne!(confusable_skeleton("Alice"), confusable_skeleton("Bob"));
+    }
+}
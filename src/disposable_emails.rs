@@ -0,0 +1,215 @@
+use crate::primitives::IdentityFieldValue;
+use crate::Result;
+use schemars::JsonSchema;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::time::{sleep, Duration};
+
+// In seconds
+const DEFAULT_REFRESH_INTERVAL: u64 = 86_400;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DisposableEmailAction {
+    // Messages of the email address are never accepted, only admins can
+    // verify the field.
+    Refuse,
+    // The email address can be verified, but the identity is flagged for a
+    // manual review.
+    ManualReview,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct DisposableEmailConfig {
+    pub action: DisposableEmailAction,
+    // Domains which are always listed.
+    pub domains: Option<Vec<String>>,
+    // URL or file path of a list with one domain per line.
+    pub source: Option<String>,
+    // Refresh interval of the source in seconds.
+    pub refresh_interval: Option<u64>,
+}
+
+/// The list of disposable (or free-mail) domains, shared between the handles
+/// and refreshed in the background.
+#[derive(Debug, Clone)]
+pub struct DisposableEmails {
+    config: DisposableEmailConfig,
+    domains: Arc<RwLock<HashSet<String>>>,
+}
+
+impl DisposableEmails {
+    pub async fn new(config: DisposableEmailConfig) -> Result<Self> {
+        let list = DisposableEmails {
+            config,
+            domains: Default::default(),
+        };
+
+        let count = list.refresh().await?;
+        info!("Loaded {} disposable email domains", count);
+
+        Ok(list)
+    }
+    pub fn action(&self) -> DisposableEmailAction {
+        self.config.action
+    }
+    /// Reloads the configured source, returning the number of listed domains.
+    pub async fn refresh(&self) -> Result<usize> {
+        let mut domains =
+            parse_domains(&self.config.domains.clone().unwrap_or_default().join("\n"));
+
+        if let Some(source) = &self.config.source {
+            let content = if source.starts_with("http://") || source.starts_with("https://") {
+                let resp = reqwest::get(source).await?;
+                if !resp.status().is_success() {
+                    return Err(anyhow!(
+                        "disposable email source returned status code {}",
+                        resp.status()
+                    ));
+                }
+
+                resp.text().await?
+            } else {
+                tokio::fs::read_to_string(source).await?
+            };
+
+            domains.extend(parse_domains(&content));
+        }
+
+        let count = domains.len();
+        *self.domains.write().await = domains;
+
+        Ok(count)
+    }
+    /// Periodically refreshes the list. On failure, the previous list is kept.
+    pub fn start_refresh_task(&self) {
+        if self.config.source.is_none() {
+            return;
+        }
+
+        let list = self.clone();
+        let interval = self
+            .config
+            .refresh_interval
+            .unwrap_or(DEFAULT_REFRESH_INTERVAL);
+
+        actix::spawn(async move {
+            loop {
+                sleep(Duration::from_secs(interval)).await;
+
+                match list.refresh().await {
+                    Ok(count) => debug!("Refreshed {} disposable email domains", count),
+                    Err(err) => error!("Failed to refresh disposable email domains: {:?}", err),
+                }
+            }
+        });
+    }
+    /// Returns the listed domain if the field is an email address of a
+    /// disposable domain (or one of its subdomains).
+    pub async fn matches(&self, field: &IdentityFieldValue) -> Option<String> {
+        let email = match field {
+            IdentityFieldValue::Email(email) => email,
+            _ => return None,
+        };
+
+        is_listed(&*self.domains.read().await, email)
+    }
+}
+
+fn parse_domains(content: &str) -> HashSet<String> {
+    content
+        .lines()
+        .map(|line| line.trim().trim_start_matches('@').to_lowercase())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect()
+}
+
+fn is_listed(domains: &HashSet<String>, email: &str) -> Option<String> {
+    let (_, domain) = email.trim().rsplit_once('@')?;
+    let mut domain = domain.trim_end_matches('.').to_lowercase();
+
+    loop {
+        if domains.contains(&domain) {
+            return Some(domain);
+        }
+
+        // Check the parent domain.
+        match domain.split_once('.') {
+            Some((_, parent)) if parent.contains('.') => domain = parent.to_string(),
+            _ => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_domain_list() {
+        let domains =
+            parse_domains("# Disposable domains\nmailinator.com\n\n  @Guerrillamail.com \n");
+
+        let expected: HashSet<String> = ["mailinator.com", "guerrillamail.com"]
+            .iter()
+            .map(|domain| domain.to_string())
+            .collect();
+
+        assert_eq!(domains, expected);
+    }
+
+    #[test]
+    fn listed_email_domains() {
+        let domains = parse_domains("mailinator.com\ncom");
+
+        assert_eq!(
+            is_listed(&domains, "alice@mailinator.com"),
+            Some("mailinator.com".to_string())
+        );
+        assert_eq!(
+            is_listed(&domains, " Alice@MAILINATOR.com. "),
+            Some("mailinator.com".to_string())
+        );
+        assert_eq!(
+            is_listed(&domains, "alice@eu.mailinator.com"),
+            Some("mailinator.com".to_string())
+        );
+
+        assert_eq!(is_listed(&domains, "alice@email.com"), None);
+        assert_eq!(is_listed(&domains, "alice@notmailinator.com"), None);
+        assert_eq!(is_listed(&domains, "mailinator.com"), None);
+        // Top-level domains are never matched.
+        assert_eq!(is_listed(&domains, "alice@example.com"), None);
+    }
+
+    #[actix::test]
+    async fn match_email_fields() {
+        let list = DisposableEmails::new(DisposableEmailConfig {
+            action: DisposableEmailAction::Refuse,
+            domains: Some(vec!["mailinator.com".to_string()]),
+            source: None,
+            refresh_interval: None,
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            list.matches(&IdentityFieldValue::Email(
+                "alice@mailinator.com".to_string()
+            ))
+            .await,
+            Some("mailinator.com".to_string())
+        );
+        assert_eq!(list.matches(&IdentityFieldValue::ALICE_EMAIL()).await, None);
+        // Only email fields are considered.
+        assert_eq!(
+            list.matches(&IdentityFieldValue::Matrix(
+                "@alice:mailinator.com".to_string()
+            ))
+            .await,
+            None
+        );
+    }
+}
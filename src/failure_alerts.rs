@@ -0,0 +1,364 @@
+use crate::adapters::matrix::{MatrixClient, MatrixHandle};
+use crate::database::Database;
+use crate::metrics;
+use crate::primitives::{ExternalMessageType, IdentityContext, Timestamp, VerificationRecord};
+use crate::retry::{retry, RetryConfig};
+use crate::webhooks::{sign_payload, SIGNATURE_HEADER};
+use crate::Result;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use schemars::JsonSchema;
+use std::collections::{HashMap, HashSet};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
+
+// In seconds
+const DEFAULT_WINDOW: u64 = 3_600;
+const DEFAULT_TIMEOUT: u64 = 10;
+const CHECK_INTERVAL: u64 = 60;
+// Identities listed in the text of an alert, the webhook payload contains all.
+const LISTED_IDENTITIES: usize = 10;
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct FailureAlertsConfig {
+    // Different identities a single sender must have failed to verify within
+    // the window to raise an alert.
+    pub min_identities: usize,
+    // Window in seconds, defaults to one hour. A sender is alerted at most
+    // once per window.
+    pub window: Option<u64>,
+    // Send alerts to the Matrix admins, defaults to true.
+    pub matrix: Option<bool>,
+    // Endpoints to which the alerts are posted, disabled if not set.
+    pub webhook: Option<AlertWebhookConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AlertWebhookConfig {
+    pub urls: Vec<String>,
+    // Secret used to sign the payloads, as for the notification webhooks.
+    pub secret: String,
+    // Request timeout in seconds.
+    pub timeout: Option<u64>,
+    // Retries of failed deliveries, defaults are used if not set.
+    pub retry: Option<RetryConfig>,
+}
+
+/// A sender which failed to verify many different identities within the
+/// window, e.g. someone probing the challenges.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct FailureAlert {
+    pub origin: ExternalMessageType,
+    // The identities the sender failed to verify, in order of the first
+    // failure.
+    pub identities: Vec<IdentityContext>,
+    // Failed messages of the sender within the window.
+    pub failures: usize,
+    pub first_failure: Timestamp,
+    pub last_failure: Timestamp,
+    pub raised_at: Timestamp,
+}
+
+impl FailureAlert {
+    /// The text sent to the Matrix admins.
+    pub fn describe(&self) -> String {
+        let (adapter, sender) = sender(&self.origin);
+        let mut identities: Vec<String> = self
+            .identities
+            .iter()
+            .take(LISTED_IDENTITIES)
+            .map(|context| {
+                format!(
+                    "{}:{}",
+                    context.chain.as_str(),
+                    context.display_address().short
+                )
+            })
+            .collect();
+        if self.identities.len() > LISTED_IDENTITIES {
+            identities.push(format!(
+                "and {} more",
+                self.identities.len() - LISTED_IDENTITIES
+            ));
+        }
+
+        format!(
+            "Possible probing of challenges: {} sender {} sent {} failed messages for {} identities within {} minutes ({})",
+            adapter,
+            sender,
+            self.failures,
+            self.identities.len(),
+            (self.last_failure.raw() - self.first_failure.raw()) / 60,
+            identities.join(", ")
+        )
+    }
+}
+
+fn sender(origin: &ExternalMessageType) -> (&'static str, &str) {
+    match origin {
+        ExternalMessageType::Email(from) => ("email", from),
+        ExternalMessageType::Twitter(from) => ("twitter", from),
+        ExternalMessageType::Matrix(from) => ("matrix", from),
+        ExternalMessageType::Web(from) => ("web", from),
+    }
+}
+
+/// Groups the failed messages by sender and returns an alert for each sender
+/// which failed to verify at least `min_identities` different identities,
+/// most identities first.
+pub fn detect_anomalies(
+    records: &[VerificationRecord],
+    min_identities: usize,
+    now: Timestamp,
+) -> Vec<FailureAlert> {
+    let mut senders: HashMap<&ExternalMessageType, Vec<&VerificationRecord>> = HashMap::new();
+    for record in records {
+        senders.entry(&record.origin).or_default().push(record);
+    }
+
+    let mut alerts: Vec<FailureAlert> = senders
+        .into_iter()
+        .filter_map(|(origin, records)| {
+            let mut identities: Vec<IdentityContext> = vec![];
+            for record in &records {
+                if !identities.contains(&record.context) {
+                    identities.push(record.context.clone());
+                }
+            }
+
+            if identities.len() < min_identities {
+                return None;
+            }
+
+            let timestamps = records.iter().map(|record| record.timestamp.raw());
+            Some(FailureAlert {
+                origin: origin.clone(),
+                identities,
+                failures: records.len(),
+                first_failure: Timestamp::from(timestamps.clone().min().unwrap_or_default()),
+                last_failure: Timestamp::from(timestamps.max().unwrap_or_default()),
+                raised_at: now,
+            })
+        })
+        .collect();
+
+    alerts.sort_by(|a, b| {
+        b.identities
+            .len()
+            .cmp(&a.identities.len())
+            .then(a.first_failure.raw().cmp(&b.first_failure.raw()))
+    });
+
+    alerts
+}
+
+/// Checks the failed verification attempts in the background and informs the
+/// admins about suspicious senders.
+pub struct FailureAlerts {
+    db: Database,
+    min_identities: usize,
+    window: u64,
+    matrix: Option<(MatrixClient, Vec<MatrixHandle>)>,
+    webhook: Option<(Client, AlertWebhookConfig)>,
+    notify_matrix: bool,
+}
+
+impl FailureAlerts {
+    pub fn new(db: Database, config: FailureAlertsConfig) -> Result<Self> {
+        if config.min_identities < 2 {
+            return Err(anyhow!(
+                "failure alerts require `min_identities` of at least 2"
+            ));
+        }
+
+        let webhook = match config.webhook {
+            Some(webhook) => {
+                if webhook.secret.is_empty() {
+                    return Err(anyhow!(
+                        "the failure alert webhook secret must not be empty"
+                    ));
+                }
+
+                let client = Client::builder()
+                    .timeout(Duration::from_secs(
+                        webhook.timeout.unwrap_or(DEFAULT_TIMEOUT),
+                    ))
+                    .build()?;
+
+                Some((client, webhook))
+            }
+            None => None,
+        };
+
+        Ok(FailureAlerts {
+            db,
+            min_identities: config.min_identities,
+            window: config.window.unwrap_or(DEFAULT_WINDOW),
+            matrix: None,
+            webhook,
+            notify_matrix: config.matrix.unwrap_or(true),
+        })
+    }
+    /// Sends the alerts to the admins via the Matrix account handling the
+    /// admin commands.
+    pub fn with_matrix(self, client: MatrixClient, admins: Vec<MatrixHandle>) -> Self {
+        if !self.notify_matrix || admins.is_empty() {
+            return self;
+        }
+
+        FailureAlerts {
+            matrix: Some((client, admins)),
+            ..self
+        }
+    }
+    /// Records an alert for every sender which exceeded the threshold within
+    /// the window and was not alerted within the window already.
+    pub async fn check(&self, now: Timestamp) -> Result<Vec<FailureAlert>> {
+        let since = Timestamp::from(now.raw().saturating_sub(self.window));
+        let records = self.db.fetch_failed_verification_records(since).await?;
+        let alerted: HashSet<ExternalMessageType> = self
+            .db
+            .fetch_failure_alerts(since)
+            .await?
+            .into_iter()
+            .map(|alert| alert.origin)
+            .collect();
+
+        let mut raised = vec![];
+        for alert in detect_anomalies(&records, self.min_identities, now) {
+            if alerted.contains(&alert.origin) {
+                continue;
+            }
+
+            self.db.insert_failure_alert(&alert).await?;
+            metrics::observe_failure_alert(sender(&alert.origin).0);
+            raised.push(alert);
+        }
+
+        Ok(raised)
+    }
+    async fn post(&self, client: &Client, secret: &str, url: &str, body: &[u8]) -> Result<()> {
+        let resp = client
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, sign_payload(secret, body))
+            .body(body.to_vec())
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            return Err(anyhow!(
+                "alert endpoint returned status code {}",
+                resp.status()
+            ));
+        }
+
+        Ok(())
+    }
+    async fn notify(&self, alert: &FailureAlert) {
+        if let Some((client, admins)) = &self.matrix {
+            let text = alert.describe();
+            for admin in admins {
+                if let Err(err) = client.send_text(&admin.to_string(), &text).await {
+                    error!("Failed to send failure alert to {}: {:?}", admin, err);
+                }
+            }
+        }
+
+        if let Some((client, config)) = &self.webhook {
+            let body = match serde_json::to_vec(alert) {
+                Ok(body) => body,
+                Err(err) => {
+                    error!("Failed to serialize failure alert: {:?}", err);
+                    return;
+                }
+            };
+
+            let retry_config = config.retry.clone().unwrap_or_default();
+            for url in &config.urls {
+                let name = format!("Failure alert delivery to {}", url);
+                if let Err(err) = retry(&retry_config, "failure_alert", &name, || {
+                    self.post(client, &config.secret, url, &body)
+                })
+                .await
+                {
+                    error!("Failed to deliver failure alert to {}: {:?}", url, err);
+                }
+            }
+        }
+    }
+}
+
+pub fn start_alert_task(alerts: FailureAlerts) -> JoinHandle<()> {
+    actix::spawn(async move {
+        loop {
+            match alerts.check(Timestamp::now()).await {
+                Ok(raised) => {
+                    for alert in raised {
+                        warn!("{}", alert.describe());
+                        alerts.notify(&alert).await;
+                    }
+                }
+                Err(err) => error!("Failed to check failed verification attempts: {:?}", err),
+            }
+
+            sleep(Duration::from_secs(CHECK_INTERVAL)).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{FailureCause, MessageId, VerificationOutcome};
+
+    fn failure(sender: &str, context: IdentityContext, timestamp: u64) -> VerificationRecord {
+        VerificationRecord {
+            context,
+            origin: ExternalMessageType::Matrix(sender.to_string()),
+            message_id: MessageId::from(timestamp),
+            outcome: VerificationOutcome::Failed,
+            cause: Some(FailureCause::WrongChallenge),
+            content_hash: None,
+            metadata: None,
+            timestamp: Timestamp::from(timestamp),
+        }
+    }
+
+    #[test]
+    fn detect_probing_senders() {
+        let alice = IdentityContext::alice();
+        let bob = IdentityContext::bob();
+
+        let records = vec![
+            failure("@eve:matrix.org", alice.clone(), 1_000),
+            failure("@eve:matrix.org", alice.clone(), 1_100),
+            failure("@alice:matrix.org", alice.clone(), 1_150),
+            failure("@alice:matrix.org", alice.clone(), 1_160),
+            failure("@eve:matrix.org", bob.clone(), 1_200),
+        ];
+
+        let alerts = detect_anomalies(&records, 2, Timestamp::from(2_000));
+        assert_eq!(alerts.len(), 1);
+
+        let alert = &alerts[0];
+        assert_eq!(
+            alert.origin,
+            ExternalMessageType::Matrix("@eve:matrix.org".to_string())
+        );
+        assert_eq!(alert.identities, vec![alice, bob]);
+        assert_eq!(alert.failures, 3);
+        assert_eq!(alert.first_failure, Timestamp::from(1_000));
+        assert_eq!(alert.last_failure, Timestamp::from(1_200));
+        assert_eq!(alert.raised_at, Timestamp::from(2_000));
+        assert!(alert
+            .describe()
+            .starts_with("Possible probing of challenges: matrix sender @eve:matrix.org sent 3 failed messages for 2 identities within 3 minutes"));
+
+        // Below the threshold.
+        assert!(detect_anomalies(&records, 3, Timestamp::from(2_000)).is_empty());
+    }
+}
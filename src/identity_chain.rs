@@ -0,0 +1,154 @@
+use crate::primitives::{ChainAddress, ChainName};
+use crate::Result;
+use schemars::JsonSchema;
+
+// Prefixes above this value are encoded with two bytes, which is not supported.
+const MAX_SS58_PREFIX: u8 = 63;
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct IdentityChainConfig {
+    // Name of the system parachain hosting the identity pallet, e.g.
+    // `polkadot-people`. Used in logs.
+    pub name: String,
+    // SS58 prefix of the addresses sent to and expected by the Watcher,
+    // defaults to the prefix of the relay chain.
+    pub ss58_prefix: Option<u8>,
+}
+
+/// The chain hosting the identity pallet of a network. Identities are stored
+/// under the relay chain (`network`) with relay chain addresses, while the
+/// Watcher might be connected to a system parachain after the identity
+/// migration. Addresses are re-encoded when crossing between the two.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct IdentityChain {
+    network: ChainName,
+    // Name and SS58 prefix of the parachain, if configured.
+    parachain: Option<(String, u8)>,
+}
+
+impl IdentityChain {
+    /// The identity pallet lives on the relay chain itself, addresses are kept
+    /// as they are.
+    pub fn relay(network: ChainName) -> Self {
+        IdentityChain {
+            network,
+            parachain: None,
+        }
+    }
+    pub fn new(network: ChainName, config: Option<IdentityChainConfig>) -> Result<Self> {
+        let config = match config {
+            Some(config) => config,
+            None => return Ok(IdentityChain::relay(network)),
+        };
+
+        let prefix = config.ss58_prefix.unwrap_or_else(|| network.ss58_prefix());
+        if prefix > MAX_SS58_PREFIX {
+            return Err(anyhow!(
+                "SS58 prefix {} of identity chain '{}' is not supported, must be at most {}",
+                prefix,
+                config.name,
+                MAX_SS58_PREFIX
+            ));
+        }
+
+        Ok(IdentityChain {
+            network,
+            parachain: Some((config.name, prefix)),
+        })
+    }
+    pub fn name(&self) -> &str {
+        match &self.parachain {
+            Some((name, _)) => name.as_str(),
+            None => self.network.as_str(),
+        }
+    }
+    /// Whether judgement requests for the chain are served, by name of the
+    /// network or of the identity chain.
+    pub fn serves(&self, chain: &str) -> bool {
+        chain.eq_ignore_ascii_case(self.network.as_str()) || chain.eq_ignore_ascii_case(self.name())
+    }
+    /// The SS58 prefix of the addresses sent by the Watcher.
+    pub fn ss58_prefix(&self) -> u8 {
+        match self.parachain {
+            Some((_, prefix)) => prefix,
+            None => self.network.ss58_prefix(),
+        }
+    }
+    /// Fails if an address of the Watcher has an invalid checksum or is not
+    /// encoded with the prefix of the chain hosting the identity pallet.
+    pub fn validate(&self, address: &ChainAddress) -> Result<()> {
+        address.validate_ss58_prefix(self.ss58_prefix())
+    }
+    /// Converts an address of the Watcher into the relay chain format.
+    /// Addresses which cannot be decoded are kept as they are.
+    pub fn to_relay(&self, address: ChainAddress) -> ChainAddress {
+        match self.parachain {
+            Some(_) => address.canonical(self.network),
+            None => address,
+        }
+    }
+    /// Converts a stored relay chain address into the format of the chain
+    /// hosting the identity pallet, as expected by the Watcher.
+    pub fn to_identity_chain(&self, address: ChainAddress) -> ChainAddress {
+        match self.parachain {
+            Some((_, prefix)) => address.with_ss58_prefix(prefix).unwrap_or(address),
+            None => address,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_addresses() {
+        let generic =
+            ChainAddress::from("5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY".to_string());
+        let kusama =
+            ChainAddress::from("HNZata7iMYWmk5RvZRTiAsSDhV8366zq2YGb3tLH5Upf74F".to_string());
+
+        // Without a parachain, addresses are passed through.
+        let relay = IdentityChain::relay(ChainName::Kusama);
+        assert_eq!(relay.name(), "kusama");
+        assert_eq!(relay.to_relay(generic.clone()), generic);
+        assert_eq!(relay.to_identity_chain(generic.clone()), generic);
+
+        let people = IdentityChain::new(
+            ChainName::Kusama,
+            Some(IdentityChainConfig {
+                name: "kusama-people".to_string(),
+                ss58_prefix: Some(42),
+            }),
+        )
+        .unwrap();
+        assert_eq!(people.name(), "kusama-people");
+        assert!(people.serves("kusama-people"));
+        assert!(people.serves("Kusama"));
+        assert!(!people.serves("polkadot"));
+        assert!(!relay.serves("polkadot-people"));
+        assert_eq!(people.to_relay(generic.clone()), kusama);
+        assert_eq!(people.to_identity_chain(kusama.clone()), generic);
+
+        // Addresses are expected in the format of the identity chain.
+        assert!(relay.validate(&kusama).is_ok());
+        assert!(relay.validate(&generic).is_err());
+        assert!(people.validate(&generic).is_ok());
+        assert!(people.validate(&kusama).is_err());
+
+        // Invalid addresses are kept.
+        let invalid = ChainAddress::from("1a2b".to_string());
+        assert_eq!(people.to_relay(invalid.clone()), invalid);
+        assert_eq!(people.to_identity_chain(invalid.clone()), invalid);
+
+        assert!(IdentityChain::new(
+            ChainName::Kusama,
+            Some(IdentityChainConfig {
+                name: "kusama-people".to_string(),
+                ss58_prefix: Some(64),
+            }),
+        )
+        .is_err());
+    }
+}
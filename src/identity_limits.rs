@@ -0,0 +1,240 @@
+use crate::primitives::ChainAddress;
+use crate::protocol::AccountType;
+use schemars::JsonSchema;
+use std::collections::HashMap;
+
+// `Data::Raw` values of the identity pallet (Polkadot and Kusama runtimes)
+// hold at most 32 bytes.
+const DEFAULT_MAX_FIELD_LENGTH: usize = 32;
+// SS58 encoded addresses are at most 48 characters long.
+const DEFAULT_MAX_ADDRESS_LENGTH: usize = 48;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitAction {
+    // The whole request is skipped.
+    Reject,
+    // Values which are too long are truncated to the limit.
+    Truncate,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct IdentityLimitsConfig {
+    // Maximum length of a field value in bytes, as set in the runtime.
+    pub max_field_length: Option<usize>,
+    // Maximum length of an account address.
+    pub max_address_length: Option<usize>,
+    // What to do with values exceeding the field length, defaults to `reject`.
+    pub action: Option<LimitAction>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum LimitViolation {
+    InvalidAddress,
+    FieldTooLong { field: AccountType, length: usize },
+}
+
+impl std::fmt::Display for LimitViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitViolation::InvalidAddress => write!(f, "the address is invalid"),
+            LimitViolation::FieldTooLong { field, length } => {
+                write!(f, "the {:?} field is {} bytes long", field, length)
+            }
+        }
+    }
+}
+
+/// Validates judgement requests of the Watcher against the limits of the
+/// on-chain identity pallet, so malformed messages are not processed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct IdentityLimits {
+    max_field_length: usize,
+    max_address_length: usize,
+    action: LimitAction,
+}
+
+impl Default for IdentityLimits {
+    fn default() -> Self {
+        IdentityLimits {
+            max_field_length: DEFAULT_MAX_FIELD_LENGTH,
+            max_address_length: DEFAULT_MAX_ADDRESS_LENGTH,
+            action: LimitAction::Reject,
+        }
+    }
+}
+
+impl From<Option<IdentityLimitsConfig>> for IdentityLimits {
+    fn from(config: Option<IdentityLimitsConfig>) -> Self {
+        let default = IdentityLimits::default();
+
+        match config {
+            Some(config) => IdentityLimits {
+                max_field_length: config.max_field_length.unwrap_or(default.max_field_length),
+                max_address_length: config
+                    .max_address_length
+                    .unwrap_or(default.max_address_length),
+                action: config.action.unwrap_or(default.action),
+            },
+            None => default,
+        }
+    }
+}
+
+impl IdentityLimits {
+    /// Checks the address and the field values of the request. Depending on
+    /// the configured action, values exceeding the field length are either
+    /// rejected or truncated.
+    pub fn apply(
+        &self,
+        address: &ChainAddress,
+        mut accounts: HashMap<AccountType, String>,
+    ) -> Result<HashMap<AccountType, String>, LimitViolation> {
+        if !address.is_well_formed(self.max_address_length) {
+            return Err(LimitViolation::InvalidAddress);
+        }
+
+        for (ty, value) in accounts.iter_mut() {
+            // The values of those fields are not processed.
+            if matches!(
+                ty,
+                AccountType::PGPFingerprint | AccountType::Image | AccountType::Additional
+            ) {
+                continue;
+            }
+
+            let length = raw_len(value);
+            if length <= self.max_field_length {
+                continue;
+            }
+
+            match self.action {
+                LimitAction::Reject => {
+                    return Err(LimitViolation::FieldTooLong {
+                        field: ty.clone(),
+                        length,
+                    })
+                }
+                LimitAction::Truncate => truncate(value, self.max_field_length),
+            }
+        }
+
+        Ok(accounts)
+    }
+}
+
+/// Hex encoded values (e.g. display names with emojis) are measured by their
+/// decoded length.
+fn is_hex(value: &str) -> bool {
+    value.starts_with("0x") && hex::decode(&value[2..]).is_ok()
+}
+
+fn raw_len(value: &str) -> usize {
+    if is_hex(value) {
+        (value.len() - 2) / 2
+    } else {
+        value.len()
+    }
+}
+
+fn truncate(value: &mut String, max: usize) {
+    if is_hex(value) {
+        value.truncate(2 + max * 2);
+        return;
+    }
+
+    let mut end = max;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    value.truncate(end);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP";
+
+    fn limits(action: LimitAction) -> IdentityLimits {
+        IdentityLimits::from(Some(IdentityLimitsConfig {
+            max_field_length: Some(8),
+            max_address_length: None,
+            action: Some(action),
+        }))
+    }
+
+    fn accounts(values: &[(AccountType, &str)]) -> HashMap<AccountType, String> {
+        values
+            .iter()
+            .map(|(ty, value)| (ty.clone(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn accept_valid_requests() {
+        let limits = IdentityLimits::default();
+        let request = accounts(&[
+            (AccountType::DisplayName, "Alice"),
+            (AccountType::Email, "alice@email.com"),
+            // 32 bytes, hex encoded.
+            (AccountType::LegalName, &format!("0x{}", "ab".repeat(32))),
+        ]);
+
+        assert_eq!(
+            limits.apply(&ADDRESS.to_string().into(), request.clone()),
+            Ok(request)
+        );
+    }
+
+    #[test]
+    fn reject_invalid_addresses() {
+        let limits = IdentityLimits::default();
+
+        for address in &["", "1a2Y-iGNu", &"a".repeat(49)] {
+            assert_eq!(
+                limits.apply(&address.to_string().into(), HashMap::new()),
+                Err(LimitViolation::InvalidAddress)
+            );
+        }
+    }
+
+    #[test]
+    fn reject_or_truncate_long_values() {
+        let request = accounts(&[
+            (AccountType::DisplayName, "Alice in Wonderland"),
+            (AccountType::Twitter, "@alice"),
+            // Ignored, the value is not processed.
+            (AccountType::Additional, "some additional value"),
+        ]);
+
+        assert_eq!(
+            limits(LimitAction::Reject).apply(&ADDRESS.to_string().into(), request.clone()),
+            Err(LimitViolation::FieldTooLong {
+                field: AccountType::DisplayName,
+                length: 19,
+            })
+        );
+
+        let truncated = limits(LimitAction::Truncate)
+            .apply(&ADDRESS.to_string().into(), request)
+            .unwrap();
+        assert_eq!(truncated[&AccountType::DisplayName], "Alice in");
+        assert_eq!(truncated[&AccountType::Twitter], "@alice");
+        assert_eq!(truncated[&AccountType::Additional], "some additional value");
+    }
+
+    #[test]
+    fn truncate_at_char_boundaries() {
+        let mut value = "Alice 🦀🦀".to_string();
+        truncate(&mut value, 8);
+        assert_eq!(value, "Alice ");
+
+        let mut value = format!("0x{}", "ab".repeat(10));
+        assert_eq!(raw_len(&value), 10);
+        truncate(&mut value, 8);
+        assert_eq!(value, format!("0x{}", "ab".repeat(8)));
+    }
+}
@@ -0,0 +1,258 @@
+use crate::database::Database;
+use crate::metrics;
+use crate::primitives::{ChainName, Timestamp};
+use crate::runtime_info::RuntimeInfo;
+use crate::Result;
+use rand::{thread_rng, Rng};
+use schemars::JsonSchema;
+use std::collections::HashSet;
+use tokio::time::{sleep, Duration};
+
+// Interval in seconds between two heartbeats of an instance.
+const HEARTBEAT_INTERVAL: u64 = 30;
+// Instances without a heartbeat within this time are considered stopped.
+pub const HEARTBEAT_TIMEOUT: u64 = 90;
+// Registrations of stopped instances are removed after this time.
+const REGISTRATION_RETENTION: u64 = 86_400;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateInstanceAction {
+    // Log a warning for as long as both instances are active.
+    Warn,
+    // Do not start if another instance is already active.
+    Refuse,
+}
+
+/// The role of an instance sharing the database, refreshed with every
+/// heartbeat.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct InstanceRegistration {
+    pub instance_id: String,
+    pub role: String,
+    pub host: String,
+    pub version: String,
+    pub chains: Vec<ChainName>,
+    pub last_seen: Timestamp,
+    // Status of the subsystems running in this instance, as of `last_seen`.
+    #[serde(default)]
+    pub adapters: Vec<AdapterStatus>,
+    #[serde(default)]
+    pub watchers: Vec<WatcherStatus>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct AdapterStatus {
+    pub adapter: String,
+    // Time of the last successful fetch of messages.
+    pub last_fetch: Timestamp,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct WatcherStatus {
+    pub chain: String,
+    // Whether the websocket connection is established.
+    pub connected: bool,
+    // Time of the last message (including pings) received from the Watcher.
+    pub last_message: Option<Timestamp>,
+}
+
+impl InstanceRegistration {
+    pub fn new(info: &RuntimeInfo, host: String) -> Self {
+        InstanceRegistration {
+            instance_id: hex::encode(thread_rng().gen::<[u8; 16]>()),
+            role: info.instance.clone(),
+            host,
+            version: info.version.clone(),
+            chains: info.chains.clone(),
+            last_seen: Timestamp::now(),
+            adapters: vec![],
+            watchers: vec![],
+        }
+    }
+    /// Updates the heartbeat and the status of the subsystems.
+    fn refresh(&mut self) {
+        let last_messages = metrics::watcher_last_messages();
+
+        self.last_seen = Timestamp::now();
+        self.adapters = metrics::adapter_last_fetches()
+            .into_iter()
+            .map(|(adapter, last_fetch)| AdapterStatus {
+                adapter,
+                last_fetch,
+            })
+            .collect();
+        self.watchers = metrics::watcher_connections()
+            .into_iter()
+            .map(|(chain, connected)| WatcherStatus {
+                last_message: last_messages
+                    .iter()
+                    .find(|(other, _)| other == &chain)
+                    .map(|(_, time)| *time),
+                chain,
+                connected,
+            })
+            .collect();
+    }
+    // Only instances running the adapters send challenges to users.
+    fn runs_adapters(&self) -> bool {
+        matches!(self.role.as_str(), "adapter_listener" | "single_instance")
+    }
+    /// Whether both instances would send challenges for the same chain. A
+    /// previous run on the same host is considered a restart.
+    pub fn conflicts_with(&self, other: &InstanceRegistration) -> bool {
+        self.instance_id != other.instance_id
+            && self.host != other.host
+            && self.runs_adapters()
+            && other.runs_adapters()
+            && self.chains.iter().any(|chain| other.chains.contains(chain))
+    }
+}
+
+fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+async fn find_conflicts(
+    db: &Database,
+    registration: &InstanceRegistration,
+) -> Result<Vec<InstanceRegistration>> {
+    let since = Timestamp::from(Timestamp::now().raw().saturating_sub(HEARTBEAT_TIMEOUT));
+
+    Ok(db
+        .fetch_active_instances(since)
+        .await?
+        .into_iter()
+        .filter(|other| registration.conflicts_with(other))
+        .collect())
+}
+
+fn describe(instance: &InstanceRegistration) -> String {
+    let chains: Vec<&str> = instance.chains.iter().map(|chain| chain.as_str()).collect();
+
+    format!(
+        "{} on {} (v{}, chains: {})",
+        instance.role,
+        instance.host,
+        instance.version,
+        chains.join(", ")
+    )
+}
+
+/// Registers the instance in the database and keeps the registration alive.
+/// Fails if another adapter listener of the same chain is active and the
+/// action is `refuse`, otherwise the conflict is logged.
+pub async fn register_instance(
+    db: &Database,
+    info: &RuntimeInfo,
+    action: DuplicateInstanceAction,
+) -> Result<()> {
+    let mut registration = InstanceRegistration::new(info, hostname());
+
+    let conflicts = find_conflicts(db, &registration).await?;
+    if !conflicts.is_empty() && action == DuplicateInstanceAction::Refuse {
+        let active: Vec<String> = conflicts.iter().map(describe).collect();
+        return Err(anyhow!(
+            "another instance sending challenges for the same chain is active: {}",
+            active.join("; ")
+        ));
+    }
+
+    db.store_instance_registration(&registration).await?;
+    info!("Registered instance as {}", describe(&registration));
+
+    let db = db.clone();
+    actix::spawn(async move {
+        // Conflicts are only logged when first seen.
+        let mut reported = HashSet::new();
+
+        loop {
+            match find_conflicts(&db, &registration).await {
+                Ok(conflicts) => {
+                    for other in &conflicts {
+                        if reported.insert(other.instance_id.clone()) {
+                            warn!(
+                                "Another instance sending challenges for the same chain is active: {}",
+                                describe(other)
+                            );
+                        }
+                    }
+
+                    reported.retain(|id| conflicts.iter().any(|other| &other.instance_id == id));
+                }
+                Err(err) => error!("Failed to check for duplicate instances: {:?}", err),
+            }
+
+            sleep(Duration::from_secs(HEARTBEAT_INTERVAL)).await;
+
+            registration.refresh();
+            if let Err(err) = db.store_instance_registration(&registration).await {
+                error!("Failed to refresh instance registration: {:?}", err);
+            }
+
+            let before = Timestamp::from(
+                Timestamp::now()
+                    .raw()
+                    .saturating_sub(REGISTRATION_RETENTION),
+            );
+            if let Err(err) = db.prune_instance_registrations(before).await {
+                error!("Failed to remove stale instance registrations: {:?}", err);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registration(role: &str, host: &str, chains: Vec<ChainName>) -> InstanceRegistration {
+        InstanceRegistration {
+            instance_id: hex::encode(thread_rng().gen::<[u8; 16]>()),
+            role: role.to_string(),
+            host: host.to_string(),
+            version: "0.1.0".to_string(),
+            chains,
+            last_seen: Timestamp::now(),
+            adapters: vec![],
+            watchers: vec![],
+        }
+    }
+
+    #[test]
+    fn conflicting_instances() {
+        let listener = registration("adapter_listener", "a", vec![ChainName::Polkadot]);
+
+        // Same chain on a different host.
+        let other = registration(
+            "single_instance",
+            "b",
+            vec![ChainName::Kusama, ChainName::Polkadot],
+        );
+        assert!(listener.conflicts_with(&other));
+        assert!(other.conflicts_with(&listener));
+        assert!(!listener.conflicts_with(&listener));
+
+        // Restart on the same host.
+        let restarted = registration("adapter_listener", "a", vec![ChainName::Polkadot]);
+        assert!(!listener.conflicts_with(&restarted));
+
+        // Different chain.
+        let kusama = registration("adapter_listener", "b", vec![ChainName::Kusama]);
+        assert!(!listener.conflicts_with(&kusama));
+
+        // Session notifiers do not send challenges.
+        let notifier = registration("session_notifier", "b", vec![ChainName::Polkadot]);
+        assert!(!listener.conflicts_with(&notifier));
+    }
+}
@@ -6,40 +6,104 @@ extern crate anyhow;
 extern crate serde;
 #[macro_use]
 extern crate async_trait;
+#[macro_use]
+extern crate lazy_static;
 
-use actix::clock::sleep;
+use adapters::admin::CommandSeverity;
+use adapters::email::{ProcessedAction, SecondChallengeCodeConfig};
 use adapters::matrix::MatrixHandle;
+use address_blocklist::{AddressBlocklist, AddressBlocklistConfig};
+use api::{
+    AdminSearchConfig, ChallengeExpiryConfig, ChallengeRegenerationConfig, ChallengeTokenConfig,
+    CredentialImportConfig, JudgeNowConfig, JudgementFeedConfig, RequestWithdrawalConfig,
+    SessionLimitsConfig,
+};
+use compliance::{ComplianceClient, ComplianceConfig};
+use disposable_emails::{DisposableEmailConfig, DisposableEmails};
+use failure_alerts::FailureAlertsConfig;
+use identity_chain::IdentityChainConfig;
+use identity_limits::IdentityLimitsConfig;
+use instance_registry::DuplicateInstanceAction;
+use matrix_policy::{MatrixPolicy, MatrixPolicyConfig};
+use message_rate_limit::MessageRateLimitConfig;
 use primitives::ChainName;
+use request_queue::RequestQueueConfig;
+use retry::RetryConfig;
+use runtime_info::RuntimeInfo;
+use schemars::JsonSchema;
+use scripting::{ScriptHook, ScriptHookConfig};
 use std::fs;
+use std::path::PathBuf;
 use std::time::Duration;
+use transparency_report::{ReportMonth, TransparencyReportConfig};
+use webhooks::{run_webhooks, WebhookConfig, Webhooks};
 
 pub type Result<T> = std::result::Result<T, anyhow::Error>;
 
 use adapters::run_adapters;
 use api::run_rest_api_server;
 use connector::run_connector;
-use database::Database;
-use notifier::run_session_notifier;
+use notifier::{run_challenge_expiry, run_session_notifier, EventLogConfig};
+
+// Reexport, for embedding the challenger in other binaries.
+pub use adapters::AdaptersHandle;
+pub use api::ApiHandle;
+pub use connector::ConnectorHandle;
+pub use database::Database;
+pub use shutdown::{wait_for_signal, ShutdownCoordinator};
 
 mod adapters;
+mod address_blocklist;
 mod api;
+mod compliance;
+mod config_schema;
 mod connector;
+mod context_locks;
 mod database;
+mod db_health;
 mod display_name;
+mod display_name_normalization;
+mod disposable_emails;
+mod failure_alerts;
+mod identity_chain;
+mod identity_limits;
+mod instance_registry;
+mod matrix_policy;
+mod message_rate_limit;
+mod message_template;
+mod metrics;
 mod notifier;
 mod primitives;
+mod protocol;
+mod query_log;
+mod request_queue;
+mod reserved_names;
+mod retry;
+mod runtime_info;
+mod scripting;
+mod shutdown;
 #[cfg(test)]
 mod tests;
+mod transparency_report;
+mod verification;
+mod webhooks;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 struct Config {
     pub log_level: LogLevel,
     pub db: DatabaseConfig,
     pub instance: InstanceType,
+    pub metrics: Option<MetricsConfig>,
+    // What to do if another instance already sends challenges for the same
+    // chain on the shared database, defaults to `warn`.
+    pub duplicate_instances: Option<DuplicateInstanceAction>,
+    // Seconds within which the instance must stop after receiving SIGTERM or
+    // SIGINT, defaults to 30.
+    pub shutdown_timeout: Option<u64>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum LogLevel {
     Error,
@@ -61,7 +125,7 @@ impl LogLevel {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case", tag = "role", content = "config")]
 enum InstanceType {
     AdapterListener(AdapterNotifierConfig),
@@ -69,29 +133,68 @@ enum InstanceType {
     SingleInstance(AdapterNotifierConfig),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 struct AdapterNotifierConfig {
     pub adapter: Option<AdapterConfig>,
     pub notifier: Option<NotifierConfig>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-struct DatabaseConfig {
+pub struct DatabaseConfig {
     pub uri: String,
     pub name: String,
+    // Separate URI for API queries, e.g. pointing to read-only replicas.
+    pub read_uri: Option<String>,
+    // Commands taking longer than this are logged, in milliseconds. Defaults
+    // to 500.
+    pub slow_query_threshold: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+struct MetricsConfig {
+    // Address of the Prometheus metrics endpoint (`/metrics`).
+    pub api_address: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct NotifierConfig {
     pub api_address: String,
     pub cors_allow_origin: Vec<String>,
     pub display_name: DisplayNameConfig,
+    // Limits of the WS sessions, defaults are used if not set.
+    pub session_limits: Option<SessionLimitsConfig>,
+    // Only reveal challenge values to clients which proved account ownership.
+    pub challenge_tokens: Option<ChallengeTokenConfig>,
+    // Atom feed of recently issued judgements.
+    pub judgement_feed: Option<JudgementFeedConfig>,
+    // Identity search for support staff, disabled if not set.
+    pub admin_search: Option<AdminSearchConfig>,
+    // Pre-verifies fields attested by trusted issuers, disabled if not set.
+    pub credential_import: Option<CredentialImportConfig>,
+    // Lets users regenerate challenges, requires `challenge_tokens`.
+    pub challenge_regeneration: Option<ChallengeRegenerationConfig>,
+    // Challenges which are not verified in time expire and must be renewed by
+    // the user. Disabled if not set.
+    pub challenge_expiry: Option<ChallengeExpiryConfig>,
+    // Lets users withdraw their judgement request, requires `challenge_tokens`.
+    pub request_withdrawal: Option<RequestWithdrawalConfig>,
+    // Lets users of fully verified identities request their judgement early,
+    // requires `challenge_tokens`.
+    pub judge_now: Option<JudgeNowConfig>,
+    // Notifications of an identity are combined and sent at most once within
+    // this window, in seconds. Disabled if not set.
+    pub notification_window: Option<u64>,
+    // Polling of the event log, defaults are used if not set.
+    pub event_log: Option<EventLogConfig>,
+    // Posts notifications to external endpoints, disabled if not set.
+    pub webhooks: Option<WebhookConfig>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct AdapterConfig {
     pub watcher: Vec<WatcherConfig>,
@@ -99,21 +202,90 @@ pub struct AdapterConfig {
     pub twitter: TwitterConfig,
     pub email: EmailConfig,
     pub display_name: DisplayNameConfig,
+    // Retry and circuit breaker settings for calls to external services.
+    pub retry: Option<RetryConfig>,
+    // Endpoint which is informed about new judgement requests.
+    pub compliance: Option<ComplianceConfig>,
+    // Rhai script which can veto or annotate verifications.
+    pub script_hook: Option<ScriptHookConfig>,
+    // Disposable email domains which are refused or flagged for review.
+    pub disposable_emails: Option<DisposableEmailConfig>,
+    // Addresses whose judgement requests are (not) processed.
+    pub address_blocklist: Option<AddressBlocklistConfig>,
+    // Edits of existing identities are applied once no further edit was
+    // received within this delay, in seconds. Disabled if not set.
+    pub settle_delay: Option<u64>,
+    // Messages sent to users are kept in the outbound archive for this many
+    // days. Kept forever if not set.
+    pub outbound_archive_retention: Option<u64>,
+    // Monthly anonymized report of the issued judgements.
+    pub transparency_report: Option<TransparencyReportConfig>,
+    // Verification of web fields via a challenge file on the website.
+    pub web: Option<WebConfig>,
+    // Messages of a sender are ignored for a while once it exceeds this
+    // limit. Disabled if not set.
+    pub message_rate_limit: Option<MessageRateLimitConfig>,
+    // Informs the admins about senders which fail to verify many different
+    // identities. Disabled if not set.
+    pub failure_alerts: Option<FailureAlertsConfig>,
+    // Reminds users once a single field remains unverified. Enabled with the
+    // default delay if not set.
+    pub nudges: Option<NudgeConfig>,
+    // Ignore new judgement requests of all networks, e.g. when winding down
+    // the registrar. Already accepted identities are still judged.
+    pub refuse_new_requests: Option<bool>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct WatcherConfig {
     pub network: ChainName,
     pub endpoint: String,
+    // Limits of the identity pallet of the chain runtime, the defaults match
+    // Polkadot and Kusama.
+    pub identity_limits: Option<IdentityLimitsConfig>,
+    // Redis stream from which judgement requests are consumed in addition to
+    // the Watcher, e.g. published by an indexer.
+    pub queue: Option<RequestQueueConfig>,
+    // Number of pending judgement requests which are processed concurrently
+    // when syncing with the Watcher.
+    pub sync_concurrency: Option<usize>,
+    // System parachain hosting the identity pallet of the network, e.g. after
+    // the identity migration to the people chain. Identities are still stored
+    // under the network with relay chain addresses.
+    pub identity_chain: Option<IdentityChainConfig>,
+    // Ignore new judgement requests of this network, already accepted
+    // identities are still judged.
+    pub refuse_new_requests: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct NudgeConfig {
+    // Set to false to not send any reminders.
+    pub enabled: bool,
+    // Seconds after the judgement request before the reminder is sent,
+    // defaults to one day.
+    pub delay: Option<u64>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 pub struct DisplayNameConfig {
     pub enabled: bool,
     pub limit: f64,
+    // Also reject names which are confusable with an existing name, e.g.
+    // "Ᏼinance" for "Binance", even if their similarity is below the limit.
+    // Disabled if not set.
+    pub confusables: Option<bool>,
+    // Names which always fail the check, regardless of the similarity, e.g.
+    // "Web3 Foundation". Lookalikes of the names are matched as well.
+    // Admins can reserve further names with `reserve <NAME>`.
+    pub reserved_names: Option<Vec<String>>,
+    // Regular expressions matched case-insensitively against the normalized
+    // name, e.g. `^binance\b`.
+    pub reserved_patterns: Option<Vec<String>>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct MatrixConfig {
     pub enabled: bool,
@@ -122,20 +294,74 @@ pub struct MatrixConfig {
     pub password: String,
     pub db_path: String,
     pub admins: Option<Vec<MatrixHandle>>,
+    // Time window in seconds during which a second admin must approve
+    // high-impact actions. Disabled if not set.
+    pub approval_window: Option<u64>,
+    // Admin commands of at least this severity (`read_only`, `modifying` or
+    // `destructive`) are only executed once the admin sends back a code,
+    // which is sent to the same room. Disabled if not set.
+    pub confirm_commands: Option<CommandSeverity>,
+    // Log and archive outgoing messages instead of delivering them.
+    pub sandbox: Option<bool>,
+    // Homeservers and accounts which can (not) be verified.
+    pub policy: Option<MatrixPolicyConfig>,
+    // Further bot accounts, e.g. on other homeservers, for redundancy and to
+    // spread rate limits. Admin commands are only handled by the account
+    // above.
+    pub accounts: Option<Vec<MatrixAccountConfig>>,
+}
+
+impl MatrixConfig {
+    /// All bot accounts, starting with the one handling admin commands.
+    pub fn accounts(&self) -> Vec<MatrixAccountConfig> {
+        let mut accounts = vec![MatrixAccountConfig {
+            homeserver: self.homeserver.clone(),
+            username: self.username.clone(),
+            password: self.password.clone(),
+            db_path: self.db_path.clone(),
+        }];
+        accounts.extend(self.accounts.clone().unwrap_or_default());
+        accounts
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MatrixAccountConfig {
+    pub homeserver: String,
+    pub username: String,
+    pub password: String,
+    // Must differ between accounts.
+    pub db_path: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct TwitterConfig {
     pub enabled: bool,
-    pub api_key: String,
-    pub api_secret: String,
-    pub token: String,
-    pub token_secret: String,
+    // OAuth 1.0a user context credentials, not required if `bearer_token` is
+    // set.
+    pub api_key: Option<String>,
+    pub api_secret: Option<String>,
+    pub token: Option<String>,
+    pub token_secret: Option<String>,
+    // OAuth 2.0 user context access token (with the `dm.read`, `users.read`
+    // and `tweet.read` scopes), used instead of the OAuth 1.0a credentials.
+    pub bearer_token: Option<String>,
     pub request_interval: u64,
+    // If set, the interval is increased up to this value (in seconds) while
+    // no messages are received.
+    pub max_request_interval: Option<u64>,
+    pub sandbox: Option<bool>,
+    // If set, challenges placed in the profile bio or pinned tweet of pending
+    // accounts are verified as well, polled at this interval in seconds.
+    pub profile_interval: Option<u64>,
+    // Seconds for which the handles of Twitter IDs are cached, defaults to a
+    // week.
+    pub id_cache_ttl: Option<u64>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct EmailConfig {
     pub enabled: bool,
@@ -145,6 +371,34 @@ pub struct EmailConfig {
     pub user: String,
     pub password: String,
     pub request_interval: u64,
+    // If set, the interval is increased up to this value (in seconds) while
+    // no messages are received.
+    pub max_request_interval: Option<u64>,
+    pub sandbox: Option<bool>,
+    // Short numeric codes are sent as second challenge, if set.
+    pub second_challenge_code: Option<SecondChallengeCodeConfig>,
+    // Body of the second challenge email, with `{variable}` placeholders.
+    pub template: Option<String>,
+    // Link to the verification page, available as `{verification_url}`.
+    pub verification_url: Option<String>,
+    // What happens to processed emails in the inbox, defaults to `keep`.
+    pub processed_action: Option<ProcessedAction>,
+    // Folder processed emails are moved to with the `move` action.
+    pub processed_folder: Option<String>,
+    // Waits for new emails with IMAP IDLE instead of polling, enabled by
+    // default. The `request_interval` is then the maximum time between two
+    // fetches.
+    pub idle: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct WebConfig {
+    pub enabled: bool,
+    // Interval in seconds between checks of the pending websites.
+    pub request_interval: u64,
+    // Seconds after which a request to a website is aborted, defaults to 10.
+    pub request_timeout: Option<u64>,
 }
 
 fn open_config() -> Result<Config> {
@@ -155,6 +409,10 @@ fn open_config() -> Result<Config> {
             anyhow!("Failed to open config at 'config.yaml' or '/etc/registrar/config.yaml'.")
         })?;
 
+    // Report all violations of the schema at once, instead of the first
+    // error of the parser.
+    config_schema::validate_config(&content)?;
+
     // Parse config file as JSON.
     let config = serde_yaml::from_str::<Config>(&content)
         .map_err(|err| anyhow!("Failed to parse config: {:?}", err))?;
@@ -162,56 +420,314 @@ fn open_config() -> Result<Config> {
     Ok(config)
 }
 
-async fn config_adapter_listener(db: Database, config: AdapterConfig) -> Result<()> {
-    let watchers = config.watcher.clone();
-    let dn_config = config.display_name.clone();
-    run_adapters(config.clone(), db.clone()).await?;
-    run_connector(db, watchers, dn_config).await
+/// Applies the adapter config to the database, e.g. the verification
+/// policies.
+async fn adapter_database(db: Database, config: &AdapterConfig) -> Result<Database> {
+    let db = match config.script_hook.clone() {
+        Some(hook_config) => {
+            info!("Loading script hook from {}", hook_config.path);
+            db.with_script_hook(ScriptHook::new(hook_config)?)
+        }
+        None => db,
+    };
+
+    let db = match config.matrix.policy.clone() {
+        Some(policy_config) => db.with_matrix_policy(MatrixPolicy::from(policy_config)),
+        None => db,
+    };
+
+    let db = match config.address_blocklist.clone() {
+        Some(list_config) => db.with_address_blocklist(AddressBlocklist::from(list_config)),
+        None => db,
+    };
+
+    let db = match config.email.second_challenge_code {
+        Some(code_config) => db.with_second_challenge_codes(code_config),
+        None => db,
+    };
+
+    let db = match config.settle_delay {
+        Some(delay) => db.with_settle_delay(Duration::from_secs(delay)),
+        None => db,
+    };
+
+    let db = match &config.web {
+        Some(web_config) if web_config.enabled => db.with_web_challenges(),
+        _ => db,
+    };
+
+    let refused_requests: Vec<ChainName> = config
+        .watcher
+        .iter()
+        .filter(|watcher| {
+            config.refuse_new_requests.unwrap_or(false)
+                || watcher.refuse_new_requests.unwrap_or(false)
+        })
+        .map(|watcher| watcher.network)
+        .collect();
+
+    if !refused_requests.is_empty() {
+        warn!("Refusing new judgement requests of {:?}", refused_requests);
+    }
+
+    let db = db.with_refused_requests(refused_requests);
+
+    let db = match config.message_rate_limit {
+        Some(limit_config) => db.with_message_rate_limit(limit_config),
+        None => db,
+    };
+
+    let db = match config.disposable_emails.clone() {
+        Some(list_config) => {
+            let list = DisposableEmails::new(list_config).await?;
+            list.start_refresh_task();
+            db.with_disposable_emails(list)
+        }
+        None => db,
+    };
+
+    Ok(db)
+}
+
+async fn connect_watchers(db: Database, config: &AdapterConfig) -> Result<ConnectorHandle> {
+    let compliance = config
+        .compliance
+        .clone()
+        .map(ComplianceClient::new)
+        .transpose()?;
+
+    run_connector(
+        db,
+        config.watcher.clone(),
+        config.display_name.clone(),
+        compliance,
+        config.retry.clone().unwrap_or_default(),
+    )
+    .await
+}
+
+async fn config_adapter_listener(
+    db: Database,
+    config: AdapterConfig,
+) -> Result<(AdaptersHandle, ConnectorHandle)> {
+    let db = adapter_database(db, &config).await?;
+    let adapters = run_adapters(config.clone(), db.clone()).await?;
+    let connector = connect_watchers(db, &config).await?;
+
+    Ok((adapters, connector))
+}
+
+async fn config_session_notifier(
+    db: Database,
+    not_config: NotifierConfig,
+    runtime_info: RuntimeInfo,
+) -> Result<ApiHandle> {
+    let window = Duration::from_secs(not_config.notification_window.unwrap_or(0));
+    let expiry = not_config.challenge_expiry;
+    let event_log = not_config.event_log.unwrap_or_default();
+    let webhooks = not_config.webhooks.clone().map(Webhooks::new).transpose()?;
+    let (lookup, mut handle) = run_rest_api_server(not_config, db.clone(), runtime_info).await?;
+
+    if let Some(expiry) = expiry {
+        let db = db.clone();
+        handle.add_task(actix::spawn(async move {
+            run_challenge_expiry(db, expiry.ttl()).await
+        }));
+    }
+
+    if let Some(webhooks) = webhooks {
+        let db = db.clone();
+        handle.add_task(actix::spawn(async move {
+            run_webhooks(db, webhooks, event_log).await
+        }));
+    }
+
+    handle.add_task(actix::spawn(async move {
+        run_session_notifier(db, lookup, window, event_log).await
+    }));
+
+    Ok(handle)
+}
+
+/// Connects to the database as configured, e.g. with the read-only replica.
+pub async fn connect_database(config: &DatabaseConfig) -> Result<Database> {
+    let mut db = Database::new(&config.uri, &config.name).await?;
+    db.connectivity_check().await?;
+
+    if let Some(threshold) = config.slow_query_threshold {
+        db = db.with_slow_query_threshold(Duration::from_millis(threshold));
+    }
+
+    if let Some(read_uri) = &config.read_uri {
+        info!("Initializing connection to read-only database");
+        db = db.with_read_uri(read_uri).await?;
+    }
+
+    Ok(db)
+}
+
+/// Starts the adapters of the config, which verify the messages sent by
+/// users. Must be called within a running actix system, like the other entry
+/// points.
+pub async fn start_adapters(db: Database, config: AdapterConfig) -> Result<AdaptersHandle> {
+    let db = adapter_database(db, &config).await?;
+    run_adapters(config, db).await
+}
+
+/// Connects to the configured Watchers, which forward the judgement requests
+/// and submit the judgements.
+pub async fn start_connector(db: Database, config: AdapterConfig) -> Result<ConnectorHandle> {
+    let db = adapter_database(db, &config).await?;
+    connect_watchers(db, &config).await
+}
+
+/// Starts the API server together with the session notifier, which sends the
+/// updates of the identities to the subscribed clients.
+pub async fn start_api(db: Database, config: NotifierConfig) -> Result<ApiHandle> {
+    config_session_notifier(db, config, RuntimeInfo::embedded()).await
+}
+
+/// Writes the transparency report of the month (`YYYY-MM`, the previous month
+/// by default) to the configured directory, or the current one.
+pub async fn write_transparency_report(month: Option<&str>) -> Result<()> {
+    let root = open_config()?;
+    let month = match month {
+        Some(month) => month.parse::<ReportMonth>()?,
+        None => ReportMonth::containing(primitives::Timestamp::now()).previous(),
+    };
+
+    let dir = match &root.instance {
+        InstanceType::AdapterListener(config)
+        | InstanceType::SessionNotifier(config)
+        | InstanceType::SingleInstance(config) => config
+            .adapter
+            .as_ref()
+            .and_then(|config| config.transparency_report.as_ref())
+            .map(|config| PathBuf::from(&config.output_dir))
+            .unwrap_or_else(|| PathBuf::from(".")),
+    };
+
+    let db = Database::new(&root.db.uri, &root.db.name).await?;
+    let (json, csv) = transparency_report::write_report(&db, &dir, month).await?;
+    println!("{}\n{}", json.display(), csv.display());
+
+    Ok(())
 }
 
-async fn config_session_notifier(db: Database, not_config: NotifierConfig) -> Result<()> {
-    let lookup = run_rest_api_server(not_config, db.clone()).await?;
+/// Prints how the accepted and rejected display names of the configured
+/// chains would change with the proposed similarity limit.
+pub async fn simulate_display_name_limit(limit: Option<&str>) -> Result<()> {
+    let proposed = limit
+        .ok_or_else(|| anyhow!("the proposed limit must be specified"))?
+        .parse::<f64>()?;
+    if !(0.0..=1.0).contains(&proposed) {
+        return Err(anyhow!("the limit must be between 0 and 1"));
+    }
+
+    let root = open_config()?;
+    let config = match &root.instance {
+        InstanceType::AdapterListener(config)
+        | InstanceType::SessionNotifier(config)
+        | InstanceType::SingleInstance(config) => config
+            .adapter
+            .as_ref()
+            .ok_or_else(|| anyhow!("the adapter config is required"))?,
+    };
+
+    let mut chains: Vec<ChainName> = vec![];
+    for watcher in &config.watcher {
+        if !chains.contains(&watcher.network) {
+            chains.push(watcher.network);
+        }
+    }
+
+    let db = Database::new(&root.db.uri, &root.db.name).await?;
+    let mut simulations = vec![];
+    for chain in chains {
+        let accepted = db.fetch_display_names(chain).await?;
+        let rejected = db.fetch_rejected_pending_display_names(chain).await?;
+        simulations.push(display_name::simulate_limit(
+            chain,
+            &accepted,
+            &rejected,
+            config.display_name.limit,
+            proposed,
+        ));
+    }
 
-    actix::spawn(async move { run_session_notifier(db, lookup).await });
+    println!("{}", serde_json::to_string_pretty(&simulations)?);
 
     Ok(())
 }
 
+/// Prints the JSON schema of the config file.
+pub fn print_config_schema() -> Result<()> {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&config_schema::config_schema())?
+    );
+    Ok(())
+}
+
 pub async fn run() -> Result<()> {
     let root = open_config()?;
-    let (db_config, instance) = (root.db, root.instance);
+    let (db_config, instance, metrics_config) = (root.db, root.instance, root.metrics);
+    let shutdown_timeout = Duration::from_secs(
+        root.shutdown_timeout
+            .unwrap_or(shutdown::DEFAULT_SHUTDOWN_TIMEOUT),
+    );
 
     tracing_subscriber::fmt()
         .with_env_filter(format!("system={}", root.log_level.as_str()))
         .init();
 
-    info!("Starting registrar service");
+    let runtime_info = RuntimeInfo::from_instance(&instance);
+    info!("Starting {}", runtime_info);
 
     info!("Initializing connection to database");
-    let db = Database::new(&db_config.uri, &db_config.name).await?;
-    db.connectivity_check().await?;
-
-    match instance {
+    let db = connect_database(&db_config).await?;
+
+    instance_registry::register_instance(
+        &db,
+        &runtime_info,
+        root.duplicate_instances
+            .unwrap_or(DuplicateInstanceAction::Warn),
+    )
+    .await?;
+
+    let coordinator = ShutdownCoordinator::new(db.clone(), shutdown_timeout);
+    let coordinator = match instance {
         InstanceType::AdapterListener(config) => {
             info!("Starting adapter listener instance");
-            config_adapter_listener(db, config.adapter.unwrap()).await?;
+            let (adapters, connector) =
+                config_adapter_listener(db, config.adapter.unwrap()).await?;
+            coordinator.adapters(adapters).connector(connector)
         }
         InstanceType::SessionNotifier(config) => {
             info!("Starting session notifier instance");
-            config_session_notifier(db, config.notifier.unwrap()).await?;
+            let api = config_session_notifier(db, config.notifier.unwrap(), runtime_info).await?;
+            coordinator.api(api)
         }
         InstanceType::SingleInstance(config) => {
             info!("Starting adapter listener and session notifier instances");
-            let (adapter_config, notifier_config) = (config.adapter.unwrap(), config.notifier.unwrap());
+            let (adapter_config, notifier_config) =
+                (config.adapter.unwrap(), config.notifier.unwrap());
 
-            config_adapter_listener(db.clone(), adapter_config).await?;
-            config_session_notifier(db, notifier_config).await?;
+            let (adapters, connector) = config_adapter_listener(db.clone(), adapter_config).await?;
+            let api = config_session_notifier(db, notifier_config, runtime_info).await?;
+            coordinator.adapters(adapters).connector(connector).api(api)
         }
+    };
+
+    if let Some(config) = metrics_config {
+        info!("Starting metrics server on {}", config.api_address);
+        metrics::run_metrics_server(&config.api_address).await?;
     }
 
     info!("Setup completed");
 
-    loop {
-        sleep(Duration::from_secs(u64::MAX)).await;
-    }
+    wait_for_signal().await?;
+    coordinator.shutdown().await;
+
+    Ok(())
 }
@@ -0,0 +1,188 @@
+use crate::primitives::IdentityFieldValue;
+use schemars::JsonSchema;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MatrixPolicyConfig {
+    // If set, only accounts of these homeservers can be verified.
+    pub allowed_homeservers: Option<Vec<String>>,
+    // Accounts of these homeservers are never verified, e.g. spam sources.
+    pub denied_homeservers: Option<Vec<String>>,
+    // Specific accounts which are never verified.
+    pub denied_users: Option<Vec<String>>,
+}
+
+/// Why a Matrix account cannot be verified automatically.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum MatrixRejection {
+    InvalidId,
+    DeniedUser,
+    DeniedHomeserver(String),
+    HomeserverNotAllowed(String),
+}
+
+impl std::fmt::Display for MatrixRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatrixRejection::InvalidId => write!(f, "The Matrix ID is invalid"),
+            MatrixRejection::DeniedUser => {
+                write!(f, "The Matrix account is not accepted by this registrar")
+            }
+            MatrixRejection::DeniedHomeserver(server) => write!(
+                f,
+                "Accounts of the homeserver {} are not accepted by this registrar",
+                server
+            ),
+            MatrixRejection::HomeserverNotAllowed(server) => write!(
+                f,
+                "The homeserver {} is not on the list of accepted homeservers",
+                server
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MatrixPolicy {
+    allowed_homeservers: Option<HashSet<String>>,
+    denied_homeservers: HashSet<String>,
+    denied_users: HashSet<String>,
+}
+
+impl From<MatrixPolicyConfig> for MatrixPolicy {
+    fn from(config: MatrixPolicyConfig) -> Self {
+        fn normalize(values: Vec<String>) -> HashSet<String> {
+            values
+                .into_iter()
+                .map(|value| value.trim().to_lowercase())
+                .collect()
+        }
+
+        MatrixPolicy {
+            allowed_homeservers: config.allowed_homeservers.map(normalize),
+            denied_homeservers: normalize(config.denied_homeservers.unwrap_or_default()),
+            denied_users: normalize(config.denied_users.unwrap_or_default()),
+        }
+    }
+}
+
+impl MatrixPolicy {
+    /// Checks the Matrix ID (`@user:homeserver`) against the configured lists.
+    pub fn check(&self, mxid: &str) -> Result<(), MatrixRejection> {
+        let mxid = mxid.trim().to_lowercase();
+        let server = match mxid.strip_prefix('@').and_then(|id| id.split_once(':')) {
+            Some((user, server)) if !user.is_empty() && !server.is_empty() => server,
+            _ => return Err(MatrixRejection::InvalidId),
+        };
+
+        if self.denied_users.contains(&mxid) {
+            return Err(MatrixRejection::DeniedUser);
+        }
+
+        if self.denied_homeservers.contains(server) {
+            return Err(MatrixRejection::DeniedHomeserver(server.to_string()));
+        }
+
+        if let Some(allowed) = &self.allowed_homeservers {
+            if !allowed.contains(server) {
+                return Err(MatrixRejection::HomeserverNotAllowed(server.to_string()));
+            }
+        }
+
+        Ok(())
+    }
+    /// Returns the rejection if the field is a Matrix account which cannot be
+    /// verified.
+    pub fn check_field(&self, field: &IdentityFieldValue) -> Option<MatrixRejection> {
+        match field {
+            IdentityFieldValue::Matrix(mxid) => self.check(mxid).err(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allowed: Option<&[&str]>, denied: &[&str], users: &[&str]) -> MatrixPolicy {
+        let to_vec = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+
+        MatrixPolicy::from(MatrixPolicyConfig {
+            allowed_homeservers: allowed.map(to_vec),
+            denied_homeservers: Some(to_vec(denied)),
+            denied_users: Some(to_vec(users)),
+        })
+    }
+
+    #[test]
+    fn default_policy_accepts_valid_ids() {
+        let policy = MatrixPolicy::default();
+
+        assert_eq!(policy.check("@alice:matrix.org"), Ok(()));
+        assert_eq!(policy.check("@alice:example.com:8448"), Ok(()));
+
+        for invalid in &["alice:matrix.org", "@alice", "@:matrix.org", "@alice:", ""] {
+            assert_eq!(policy.check(invalid), Err(MatrixRejection::InvalidId));
+        }
+    }
+
+    #[test]
+    fn denied_homeservers_and_users() {
+        let policy = policy(None, &["Spam.Example"], &["@bob:matrix.org"]);
+
+        assert_eq!(policy.check("@alice:matrix.org"), Ok(()));
+        assert_eq!(
+            policy.check("@alice:spam.example"),
+            Err(MatrixRejection::DeniedHomeserver(
+                "spam.example".to_string()
+            ))
+        );
+        assert_eq!(
+            policy.check("@Bob:Matrix.org"),
+            Err(MatrixRejection::DeniedUser)
+        );
+    }
+
+    #[test]
+    fn allowed_homeservers() {
+        let policy = policy(
+            Some(&["matrix.org", "web3.foundation"]),
+            &["web3.foundation"],
+            &[],
+        );
+
+        assert_eq!(policy.check("@alice:matrix.org"), Ok(()));
+        assert_eq!(
+            policy.check("@alice:example.com"),
+            Err(MatrixRejection::HomeserverNotAllowed(
+                "example.com".to_string()
+            ))
+        );
+        // The deny list takes precedence.
+        assert_eq!(
+            policy.check("@alice:web3.foundation"),
+            Err(MatrixRejection::DeniedHomeserver(
+                "web3.foundation".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn only_matrix_fields_are_checked() {
+        let policy = policy(Some(&["matrix.org"]), &[], &[]);
+
+        assert!(policy
+            .check_field(&IdentityFieldValue::Email("alice@example.com".to_string()))
+            .is_none());
+        assert_eq!(
+            policy.check_field(&IdentityFieldValue::Matrix(
+                "@alice:example.com".to_string()
+            )),
+            Some(MatrixRejection::HomeserverNotAllowed(
+                "example.com".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use crate::primitives::{ExternalMessageType, Timestamp};
+use schemars::JsonSchema;
+
+// In seconds
+const WINDOW: u64 = 3_600;
+const DEFAULT_BLOCK_DURATION: u64 = 3_600;
+
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MessageRateLimitConfig {
+    // Messages of a single sender which are verified per hour.
+    pub attempts_per_hour: u64,
+    // Seconds during which further messages of a sender exceeding the limit
+    // are ignored, defaults to one hour.
+    pub block_duration: Option<u64>,
+}
+
+impl MessageRateLimitConfig {
+    pub fn block_duration(&self) -> u64 {
+        self.block_duration.unwrap_or(DEFAULT_BLOCK_DURATION)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RateLimitDecision {
+    Allowed,
+    // The sender is still blocked.
+    Blocked,
+    // The sender exceeded the limit with this message.
+    BlockedNow { until: Timestamp },
+}
+
+/// Messages received from a sender within the current window.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct OriginAttempts {
+    pub origin: ExternalMessageType,
+    pub window_start: Timestamp,
+    pub attempts: u64,
+    pub blocked_until: Option<Timestamp>,
+}
+
+impl OriginAttempts {
+    pub fn new(origin: ExternalMessageType, now: Timestamp) -> Self {
+        OriginAttempts {
+            origin,
+            window_start: now,
+            attempts: 0,
+            blocked_until: None,
+        }
+    }
+    /// Counts a message of the sender and decides whether it is verified.
+    pub fn register(
+        &mut self,
+        config: &MessageRateLimitConfig,
+        now: Timestamp,
+    ) -> RateLimitDecision {
+        if let Some(until) = self.blocked_until {
+            if now.raw() < until.raw() {
+                return RateLimitDecision::Blocked;
+            }
+
+            // Start over once the block is lifted.
+            self.blocked_until = None;
+            self.window_start = now;
+            self.attempts = 0;
+        }
+
+        if now.raw().saturating_sub(self.window_start.raw()) >= WINDOW {
+            self.window_start = now;
+            self.attempts = 0;
+        }
+
+        self.attempts += 1;
+        if self.attempts > config.attempts_per_hour {
+            let until = Timestamp::from(now.raw() + config.block_duration());
+            self.blocked_until = Some(until);
+            return RateLimitDecision::BlockedNow { until };
+        }
+
+        RateLimitDecision::Allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_senders_exceeding_the_limit() {
+        let config = MessageRateLimitConfig {
+            attempts_per_hour: 2,
+            block_duration: Some(600),
+        };
+        let origin = ExternalMessageType::Email("alice@example.com".to_string());
+        let mut attempts = OriginAttempts::new(origin, Timestamp::from(1_000));
+
+        assert_eq!(
+            attempts.register(&config, Timestamp::from(1_000)),
+            RateLimitDecision::Allowed
+        );
+        assert_eq!(
+            attempts.register(&config, Timestamp::from(1_100)),
+            RateLimitDecision::Allowed
+        );
+        assert_eq!(
+            attempts.register(&config, Timestamp::from(1_200)),
+            RateLimitDecision::BlockedNow {
+                until: Timestamp::from(1_800)
+            }
+        );
+        assert_eq!(
+            attempts.register(&config, Timestamp::from(1_799)),
+            RateLimitDecision::Blocked
+        );
+
+        // The block is lifted.
+        assert_eq!(
+            attempts.register(&config, Timestamp::from(1_800)),
+            RateLimitDecision::Allowed
+        );
+        assert_eq!(attempts.attempts, 1);
+    }
+
+    #[test]
+    fn reset_attempts_after_window() {
+        let config = MessageRateLimitConfig {
+            attempts_per_hour: 1,
+            block_duration: None,
+        };
+        let origin = ExternalMessageType::Matrix("@alice:matrix.org".to_string());
+        let mut attempts = OriginAttempts::new(origin, Timestamp::from(0));
+
+        assert_eq!(
+            attempts.register(&config, Timestamp::from(0)),
+            RateLimitDecision::Allowed
+        );
+        assert_eq!(
+            attempts.register(&config, Timestamp::from(WINDOW)),
+            RateLimitDecision::Allowed
+        );
+        assert_eq!(
+            attempts.register(&config, Timestamp::from(WINDOW + 1)),
+            RateLimitDecision::BlockedNow {
+                until: Timestamp::from(2 * WINDOW + 1)
+            }
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use crate::primitives::{ExpectedMessage, JudgementState, Timestamp};
+use crate::verification::JUDGEMENT_DELAY_RANGE;
+use crate::Result;
+use std::collections::HashMap;
+
+// Variables which can be used in templates, e.g. `{remaining_fields}`.
+const VARIABLES: &[&str] = &[
+    "challenge",
+    "valid_for",
+    "chain",
+    "address",
+    "short_address",
+    "verified_fields",
+    "remaining_fields",
+    "total_fields",
+    "verification_url",
+    "estimated_wait",
+];
+
+// Positions and names of the `{variable}` placeholders. Braces which do not
+// enclose a lowercase name are left as they are.
+fn placeholders(template: &str) -> Vec<(usize, usize, &str)> {
+    let mut found = vec![];
+    let mut offset = 0;
+
+    while let Some(start) = template[offset..].find('{').map(|idx| offset + idx) {
+        let end = match template[start..].find('}') {
+            Some(idx) => start + idx,
+            None => break,
+        };
+
+        let name = &template[start + 1..end];
+        if !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+            found.push((start, end + 1, name));
+            offset = end + 1;
+        } else {
+            offset = start + 1;
+        }
+    }
+
+    found
+}
+
+/// A message body with `{variable}` placeholders, which are replaced with
+/// values from the judgement state of the recipient.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MessageTemplate {
+    template: String,
+    verification_url: Option<String>,
+}
+
+impl MessageTemplate {
+    /// Fails on unknown variables, or if `{verification_url}` is used without
+    /// an URL being configured.
+    pub fn new(template: &str, verification_url: Option<String>) -> Result<Self> {
+        for (_, _, name) in placeholders(template) {
+            if !VARIABLES.contains(&name) {
+                return Err(anyhow!(
+                    "unknown template variable '{{{}}}', expected one of: {}",
+                    name,
+                    VARIABLES.join(", ")
+                ));
+            }
+
+            if name == "verification_url" && verification_url.is_none() {
+                return Err(anyhow!(
+                    "the template uses '{{verification_url}}', but no `verification_url` is configured"
+                ));
+            }
+        }
+
+        Ok(MessageTemplate {
+            template: template.to_string(),
+            verification_url,
+        })
+    }
+    pub fn render(&self, state: &JudgementState, challenge: &ExpectedMessage) -> String {
+        let verified = state
+            .fields
+            .iter()
+            .filter(|field| field.challenge.is_verified())
+            .count();
+        let total = state.fields.len();
+
+        let mut values: HashMap<&str, String> = HashMap::new();
+        values.insert("challenge", challenge.value.to_string());
+        values.insert(
+            "valid_for",
+            challenge
+                .expires_at
+                .map(|expires_at| {
+                    format!(
+                        "{} minutes",
+                        expires_at.raw().saturating_sub(Timestamp::now().raw()) / 60
+                    )
+                })
+                .unwrap_or_default(),
+        );
+        values.insert("chain", state.context.chain.as_str().to_string());
+        let address = state.context.display_address();
+        values.insert("address", address.address.as_str().to_string());
+        values.insert("short_address", address.short);
+        values.insert("verified_fields", verified.to_string());
+        values.insert("remaining_fields", (total - verified).to_string());
+        values.insert("total_fields", total.to_string());
+        values.insert(
+            "verification_url",
+            self.verification_url.clone().unwrap_or_default(),
+        );
+        // The judgement is issued with a random delay after full
+        // verification.
+        values.insert(
+            "estimated_wait",
+            format!("{} minutes", JUDGEMENT_DELAY_RANGE.end / 60),
+        );
+
+        let mut rendered = String::new();
+        let mut offset = 0;
+        for (start, end, name) in placeholders(&self.template) {
+            rendered.push_str(&self.template[offset..start]);
+            rendered.push_str(values.get(name).map(|v| v.as_str()).unwrap_or_default());
+            offset = end;
+        }
+
+        rendered.push_str(&self.template[offset..]);
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::IdentityFieldValue;
+
+    #[test]
+    fn render_template() {
+        let template = MessageTemplate::new(
+            "Your challenge for {chain} is {challenge}, {remaining_fields} of {total_fields} fields remaining. See {verification_url} {not a variable}",
+            Some("https://registrar.example.com".to_string()),
+        )
+        .unwrap();
+
+        let mut state = JudgementState::alice();
+        *state
+            .get_field_mut(&IdentityFieldValue::ALICE_TWITTER())
+            .expected_message_mut() = {
+            let mut expected = ExpectedMessage::random();
+            expected.set_verified();
+            expected
+        };
+        state
+            .get_field_mut(&IdentityFieldValue::ALICE_DISPLAY_NAME())
+            .challenge = crate::primitives::ChallengeType::DisplayNameCheck {
+            passed: true,
+            violations: vec![],
+            reserved: None,
+        };
+
+        let challenge = ExpectedMessage::random();
+        assert_eq!(
+            template.render(&state, &challenge),
+            format!(
+                "Your challenge for polkadot is {}, 2 of 4 fields remaining. See https://registrar.example.com {{not a variable}}",
+                challenge.value
+            )
+        );
+    }
+
+    #[test]
+    fn validate_template() {
+        assert!(MessageTemplate::new("{challenge} {estimated_wait}", None).is_ok());
+        assert!(MessageTemplate::new("{unknown}", None).is_err());
+        assert!(MessageTemplate::new("{verification_url}", None).is_err());
+        assert!(MessageTemplate::new("{{challenge}", None).is_ok());
+    }
+}
@@ -0,0 +1,634 @@
+use crate::primitives::{ChainName, IdentityFieldValue, Timestamp};
+use crate::Result;
+use actix_web::{web, App, HttpResponse, HttpServer};
+use prometheus::core::Collector;
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use std::time::Duration;
+
+// Buckets in seconds, from one minute up to one month.
+const FUNNEL_BUCKETS: &[f64] = &[
+    60.0,
+    300.0,
+    900.0,
+    3_600.0,
+    10_800.0,
+    21_600.0,
+    43_200.0,
+    86_400.0,
+    259_200.0,
+    604_800.0,
+    2_592_000.0,
+];
+
+// Buckets in seconds, for the duration of database commands.
+const DB_COMMAND_BUCKETS: &[f64] = &[0.005, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+// Buckets in seconds, for the round trip of judgement submissions.
+const SUBMISSION_BUCKETS: &[f64] = &[0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+lazy_static! {
+    static ref REGISTRY: Registry = Registry::new();
+    static ref REQUEST_TO_CHALLENGE_SENT: HistogramVec = register(
+        "registrar_request_to_challenge_sent_seconds",
+        "Time from the judgement request until the challenges were handed out to the user",
+        &["chain"],
+        FUNNEL_BUCKETS,
+    );
+    static ref CHALLENGE_TO_FIELD_VERIFIED: HistogramVec = register(
+        "registrar_challenge_to_field_verified_seconds",
+        "Time from handing out the challenge of a field until the field was verified",
+        &["chain", "field"],
+        FUNNEL_BUCKETS,
+    );
+    static ref VERIFIED_TO_JUDGED: HistogramVec = register(
+        "registrar_verified_to_judged_seconds",
+        "Time from full verification until the judgement was submitted",
+        &["chain"],
+        FUNNEL_BUCKETS,
+    );
+    static ref SUBMISSION_LATENCY: HistogramVec = register(
+        "registrar_judgement_submission_seconds",
+        "Time from submitting a judgement to the Watcher until it was acknowledged",
+        &["chain"],
+        SUBMISSION_BUCKETS,
+    );
+    static ref ADAPTER_MESSAGES: IntCounterVec = register_collector(
+        IntCounterVec::new(
+            Opts::new(
+                "registrar_adapter_messages_total",
+                "Number of inbound messages fetched by the adapter",
+            ),
+            &["adapter"],
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref DB_COMMAND_DURATION: HistogramVec = register(
+        "registrar_db_command_seconds",
+        "Duration of MongoDB commands, including transaction commits",
+        &["command"],
+        DB_COMMAND_BUCKETS,
+    );
+    static ref DB_SLOW_COMMANDS: IntCounterVec = register_collector(
+        IntCounterVec::new(
+            Opts::new(
+                "registrar_db_slow_commands_total",
+                "Number of MongoDB commands which exceeded the slow query threshold",
+            ),
+            &["command"],
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref DB_FAILED_COMMANDS: IntCounterVec = register_collector(
+        IntCounterVec::new(
+            Opts::new(
+                "registrar_db_failed_commands_total",
+                "Number of MongoDB commands which failed",
+            ),
+            &["command"],
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref DB_DEGRADED: IntGauge = register_collector(
+        IntGauge::new(
+            "registrar_db_degraded",
+            "Whether judgement requests are throttled because MongoDB is degraded",
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref THROTTLED_REQUESTS: IntCounterVec = register_collector(
+        IntCounterVec::new(
+            Opts::new(
+                "registrar_throttled_requests_total",
+                "Number of judgement requests of the Watcher which were ignored while MongoDB was degraded",
+            ),
+            &["chain"],
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref UNSUPPORTED_CHAIN_REQUESTS: IntCounterVec = register_collector(
+        IntCounterVec::new(
+            Opts::new(
+                "registrar_unsupported_chain_requests_total",
+                "Number of judgement requests of the Watcher which were rejected, since they were sent for another chain",
+            ),
+            &["chain", "received_chain"],
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref FAILURE_ALERTS: IntCounterVec = register_collector(
+        IntCounterVec::new(
+            Opts::new(
+                "registrar_failure_alerts_total",
+                "Number of senders which failed to verify many different identities",
+            ),
+            &["adapter"],
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref RETRIES: IntCounterVec = register_collector(
+        IntCounterVec::new(
+            Opts::new(
+                "registrar_retries_total",
+                "Number of failed calls to external services which were retried",
+            ),
+            &["service"],
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref CIRCUIT_OPEN: IntGaugeVec = register_collector(
+        IntGaugeVec::new(
+            Opts::new(
+                "registrar_circuit_open",
+                "Whether calls to the external service are skipped after too many failures",
+            ),
+            &["circuit"],
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref CIRCUIT_TRIPS: IntCounterVec = register_collector(
+        IntCounterVec::new(
+            Opts::new(
+                "registrar_circuit_trips_total",
+                "Number of times the circuit of the external service was opened",
+            ),
+            &["circuit"],
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref CIRCUIT_REJECTIONS: IntCounterVec = register_collector(
+        IntCounterVec::new(
+            Opts::new(
+                "registrar_circuit_rejected_calls_total",
+                "Number of calls which were skipped, since the circuit was open",
+            ),
+            &["circuit"],
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref ADAPTER_POLL_INTERVAL: GaugeVec = register_collector(
+        GaugeVec::new(
+            Opts::new(
+                "registrar_adapter_poll_interval_seconds",
+                "Current polling interval of the adapter",
+            ),
+            &["adapter"],
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref ADAPTER_QUEUE_DEPTH: IntGaugeVec = register_collector(
+        IntGaugeVec::new(
+            Opts::new(
+                "registrar_adapter_queue_depth",
+                "Number of fetched messages which were not processed yet",
+            ),
+            &["adapter"],
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref ADAPTER_LAST_FETCH: IntGaugeVec = register_collector(
+        IntGaugeVec::new(
+            Opts::new(
+                "registrar_adapter_last_fetch_timestamp",
+                "UNIX time of the last successful fetch of the adapter",
+            ),
+            &["adapter"],
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref WATCHER_CONNECTED: IntGaugeVec = register_collector(
+        IntGaugeVec::new(
+            Opts::new(
+                "registrar_watcher_connected",
+                "Whether the websocket connection to the Watcher is established",
+            ),
+            &["chain"],
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref WATCHER_LAST_MESSAGE: IntGaugeVec = register_collector(
+        IntGaugeVec::new(
+            Opts::new(
+                "registrar_watcher_last_message_timestamp",
+                "UNIX time of the last message received from the Watcher",
+            ),
+            &["chain"],
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref WS_SESSIONS: IntGauge = register_collector(
+        IntGauge::new("registrar_ws_sessions", "Number of open WS sessions")
+            .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref WS_SUBSCRIPTIONS: IntGaugeVec = register_collector(
+        IntGaugeVec::new(
+            Opts::new(
+                "registrar_ws_subscriptions",
+                "Number of active subscriptions of WS sessions to identities or chains",
+            ),
+            &["kind"],
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref WS_SUBSCRIBED_IDENTITIES: IntGauge = register_collector(
+        IntGauge::new(
+            "registrar_ws_subscribed_identities",
+            "Number of identities with at least one subscribed WS session",
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref WS_MAX_SESSIONS_PER_IDENTITY: IntGauge = register_collector(
+        IntGauge::new(
+            "registrar_ws_max_sessions_per_identity",
+            "Highest number of WS sessions subscribed to a single identity",
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref WS_MESSAGES: IntCounterVec = register_collector(
+        IntCounterVec::new(
+            Opts::new(
+                "registrar_ws_messages_total",
+                "Number of messages pushed to WS sessions",
+            ),
+            &["kind"],
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+    static ref WS_DISCONNECTS: IntCounterVec = register_collector(
+        IntCounterVec::new(
+            Opts::new(
+                "registrar_ws_disconnects_total",
+                "Number of closed WS sessions",
+            ),
+            &["reason"],
+        )
+        .expect("Invalid metric definition (this is a bug)"),
+    );
+}
+
+fn register(name: &str, help: &str, labels: &[&str], buckets: &[f64]) -> HistogramVec {
+    let histogram = HistogramVec::new(
+        HistogramOpts::new(name, help).buckets(buckets.to_vec()),
+        labels,
+    )
+    .expect("Invalid metric definition (this is a bug)");
+
+    register_collector(histogram)
+}
+
+fn register_collector<T: Collector + Clone + 'static>(metric: T) -> T {
+    REGISTRY
+        .register(Box::new(metric.clone()))
+        .expect("Metric registered twice (this is a bug)");
+
+    metric
+}
+
+fn elapsed_since(since: Timestamp) -> f64 {
+    Timestamp::now().raw().saturating_sub(since.raw()) as f64
+}
+
+/// The challenges of a newly inserted identity were handed out to the user.
+pub fn observe_challenge_sent(chain: ChainName, requested: Timestamp) {
+    REQUEST_TO_CHALLENGE_SENT
+        .with_label_values(&[chain.as_str()])
+        .observe(elapsed_since(requested));
+}
+
+/// A field of the identity was verified.
+pub fn observe_field_verified(
+    chain: ChainName,
+    field: &IdentityFieldValue,
+    challenge_sent: Timestamp,
+) {
+    CHALLENGE_TO_FIELD_VERIFIED
+        .with_label_values(&[chain.as_str(), field.type_name()])
+        .observe(elapsed_since(challenge_sent));
+}
+
+/// The judgement of a fully verified identity was submitted.
+pub fn observe_judged(chain: ChainName, completed: Timestamp) {
+    VERIFIED_TO_JUDGED
+        .with_label_values(&[chain.as_str()])
+        .observe(elapsed_since(completed));
+}
+
+/// The Watcher acknowledged a judgement submission.
+pub fn observe_submission(chain: ChainName, latency: Duration) {
+    SUBMISSION_LATENCY
+        .with_label_values(&[chain.as_str()])
+        .observe(latency.as_secs_f64());
+}
+
+/// The adapter fetched inbound messages and will poll again after `interval`.
+pub fn observe_adapter_poll(adapter: &str, messages: usize, interval: Duration) {
+    ADAPTER_MESSAGES
+        .with_label_values(&[adapter])
+        .inc_by(messages as u64);
+    ADAPTER_POLL_INTERVAL
+        .with_label_values(&[adapter])
+        .set(interval.as_secs_f64());
+    ADAPTER_LAST_FETCH
+        .with_label_values(&[adapter])
+        .set(Timestamp::now().raw() as i64);
+}
+
+/// Number of messages of the adapter which still need to be processed.
+pub fn set_adapter_queue_depth(adapter: &str, depth: usize) {
+    ADAPTER_QUEUE_DEPTH
+        .with_label_values(&[adapter])
+        .set(depth as i64);
+}
+
+/// A message (including pings and pongs) was received from the Watcher.
+pub fn observe_watcher_message(chain: ChainName, received: Timestamp) {
+    WATCHER_LAST_MESSAGE
+        .with_label_values(&[chain.as_str()])
+        .set(received.raw() as i64);
+}
+
+/// The websocket connection to the Watcher was established or dropped.
+pub fn set_watcher_connected(chain: ChainName, connected: bool) {
+    WATCHER_CONNECTED
+        .with_label_values(&[chain.as_str()])
+        .set(connected as i64);
+}
+
+/// Current queue depth of each adapter, ordered by name.
+pub fn adapter_queue_depths() -> Vec<(String, i64)> {
+    gauge_values(&ADAPTER_QUEUE_DEPTH)
+}
+
+/// Time of the last message received from the Watcher of each chain.
+pub fn watcher_last_messages() -> Vec<(String, Timestamp)> {
+    gauge_values(&WATCHER_LAST_MESSAGE)
+        .into_iter()
+        .map(|(chain, time)| (chain, Timestamp::from(time.max(0) as u64)))
+        .collect()
+}
+
+/// Time of the last successful fetch of each adapter, ordered by name.
+pub fn adapter_last_fetches() -> Vec<(String, Timestamp)> {
+    gauge_values(&ADAPTER_LAST_FETCH)
+        .into_iter()
+        .map(|(adapter, time)| (adapter, Timestamp::from(time.max(0) as u64)))
+        .collect()
+}
+
+/// Whether the Watcher of each chain is connected, ordered by chain.
+pub fn watcher_connections() -> Vec<(String, bool)> {
+    gauge_values(&WATCHER_CONNECTED)
+        .into_iter()
+        .map(|(chain, connected)| (chain, connected > 0))
+        .collect()
+}
+
+// Values of a gauge with a single label, ordered by the label.
+fn gauge_values(gauge: &IntGaugeVec) -> Vec<(String, i64)> {
+    let mut values: Vec<(String, i64)> = gauge
+        .collect()
+        .iter()
+        .flat_map(|family| family.get_metric())
+        .filter_map(|metric| {
+            metric.get_label().first().map(|label| {
+                (
+                    label.get_value().to_string(),
+                    metric.get_gauge().get_value() as i64,
+                )
+            })
+        })
+        .collect();
+
+    values.sort();
+    values
+}
+
+/// A WS session was opened.
+pub fn observe_ws_session_started() {
+    WS_SESSIONS.inc();
+}
+
+/// A WS session was closed for the given reason.
+pub fn observe_ws_session_stopped(reason: &str) {
+    WS_SESSIONS.dec();
+    WS_DISCONNECTS.with_label_values(&[reason]).inc();
+}
+
+/// A message was pushed to a WS session.
+pub fn observe_ws_message(kind: &str) {
+    WS_MESSAGES.with_label_values(&[kind]).inc();
+}
+
+/// Current subscriptions of the WS sessions, as tracked by the lookup server.
+pub fn set_ws_subscriptions(
+    identities: usize,
+    identity_subscriptions: usize,
+    chain_subscriptions: usize,
+    max_per_identity: usize,
+) {
+    WS_SUBSCRIBED_IDENTITIES.set(identities as i64);
+    WS_SUBSCRIPTIONS
+        .with_label_values(&["identity"])
+        .set(identity_subscriptions as i64);
+    WS_SUBSCRIPTIONS
+        .with_label_values(&["chain"])
+        .set(chain_subscriptions as i64);
+    WS_MAX_SESSIONS_PER_IDENTITY.set(max_per_identity as i64);
+}
+
+/// A MongoDB command completed.
+pub fn observe_db_command(command: &str, duration: Duration, slow: bool, failed: bool) {
+    DB_COMMAND_DURATION
+        .with_label_values(&[command])
+        .observe(duration.as_secs_f64());
+
+    if slow {
+        DB_SLOW_COMMANDS.with_label_values(&[command]).inc();
+    }
+
+    if failed {
+        DB_FAILED_COMMANDS.with_label_values(&[command]).inc();
+    }
+}
+
+pub fn set_db_degraded(degraded: bool) {
+    DB_DEGRADED.set(i64::from(degraded));
+}
+
+/// Judgement requests were ignored, since MongoDB is degraded.
+pub fn observe_throttled_requests(chain: ChainName, requests: usize) {
+    THROTTLED_REQUESTS
+        .with_label_values(&[chain.as_str()])
+        .inc_by(requests as u64);
+}
+
+/// A judgement request was rejected, since the Watcher sent it for another
+/// chain.
+pub fn observe_unsupported_chain(chain: ChainName, received_chain: &str) {
+    UNSUPPORTED_CHAIN_REQUESTS
+        .with_label_values(&[chain.as_str(), received_chain])
+        .inc();
+}
+
+/// An alert was raised for a sender which failed to verify many identities.
+pub fn observe_failure_alert(adapter: &str) {
+    FAILURE_ALERTS.with_label_values(&[adapter]).inc();
+}
+
+/// A failed call to an external service is retried.
+pub fn observe_retry(service: &str) {
+    RETRIES.with_label_values(&[service]).inc();
+}
+
+/// The circuit of the external service was opened after too many failures.
+pub fn observe_circuit_opened(circuit: &str) {
+    CIRCUIT_TRIPS.with_label_values(&[circuit]).inc();
+    set_circuit_open(circuit, true);
+}
+
+/// The circuit of the external service was opened or closed again.
+pub fn set_circuit_open(circuit: &str, open: bool) {
+    CIRCUIT_OPEN
+        .with_label_values(&[circuit])
+        .set(i64::from(open));
+}
+
+/// A call was skipped, since the circuit of the external service is open.
+pub fn observe_circuit_rejection(circuit: &str) {
+    CIRCUIT_REJECTIONS.with_label_values(&[circuit]).inc();
+}
+
+/// Current state of each circuit, ordered by name.
+#[cfg(test)]
+pub fn circuit_states() -> Vec<(String, bool)> {
+    gauge_values(&CIRCUIT_OPEN)
+        .into_iter()
+        .map(|(circuit, open)| (circuit, open != 0))
+        .collect()
+}
+
+/// Encodes all metrics in the Prometheus text format.
+pub fn gather() -> Result<String> {
+    let mut buffer = vec![];
+    TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+async fn metrics_route() -> HttpResponse {
+    match gather() {
+        Ok(metrics) => HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4")
+            .body(metrics),
+        Err(err) => {
+            error!("Failed to encode metrics: {:?}", err);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+pub async fn run_metrics_server(address: &str) -> Result<()> {
+    let server = HttpServer::new(|| App::new().route("/metrics", web::get().to(metrics_route)))
+        .disable_signals()
+        .bind(address)?;
+
+    actix::spawn(async move {
+        let _ = server.run().await;
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gather_funnel_metrics() {
+        // Other tests running in the same process might record metrics too,
+        // so only the increase of the sample counts is checked.
+        let challenge_sent = REQUEST_TO_CHALLENGE_SENT.with_label_values(&["polkadot"]);
+        let field_verified = CHALLENGE_TO_FIELD_VERIFIED.with_label_values(&["kusama", "email"]);
+        let judged = VERIFIED_TO_JUDGED.with_label_values(&["polkadot"]);
+        let submission = SUBMISSION_LATENCY.with_label_values(&["kusama"]);
+
+        let before = (
+            challenge_sent.get_sample_count(),
+            field_verified.get_sample_count(),
+            judged.get_sample_count(),
+            submission.get_sample_count(),
+        );
+
+        let inserted = Timestamp::now();
+        observe_challenge_sent(ChainName::Polkadot, inserted);
+        observe_field_verified(
+            ChainName::Kusama,
+            &IdentityFieldValue::Email("alice@email.com".to_string()),
+            inserted,
+        );
+        observe_judged(ChainName::Polkadot, inserted);
+        observe_submission(ChainName::Kusama, Duration::from_millis(250));
+
+        assert!(challenge_sent.get_sample_count() > before.0);
+        assert!(field_verified.get_sample_count() > before.1);
+        assert!(judged.get_sample_count() > before.2);
+        assert!(submission.get_sample_count() > before.3);
+
+        let metrics = gather().unwrap();
+        assert!(metrics
+            .contains(r#"registrar_request_to_challenge_sent_seconds_count{chain="polkadot"}"#));
+        assert!(metrics.contains(
+            r#"registrar_challenge_to_field_verified_seconds_count{chain="kusama",field="email"}"#
+        ));
+        assert!(metrics.contains(r#"registrar_verified_to_judged_seconds_count{chain="polkadot"}"#));
+
+        observe_adapter_poll("email", 2, Duration::from_secs(5));
+        assert!(ADAPTER_MESSAGES.with_label_values(&["email"]).get() >= 2);
+        let metrics = gather().unwrap();
+        assert!(metrics.contains(r#"registrar_adapter_messages_total{adapter="email"}"#));
+        assert!(metrics.contains(r#"registrar_adapter_poll_interval_seconds{adapter="email"}"#));
+        assert!(adapter_last_fetches()
+            .iter()
+            .any(|(adapter, fetched)| adapter == "email" && fetched.raw() > 0));
+
+        observe_db_command("commitTransaction", Duration::from_secs(1), true, false);
+        assert!(
+            DB_SLOW_COMMANDS
+                .with_label_values(&["commitTransaction"])
+                .get()
+                >= 1
+        );
+        let metrics = gather().unwrap();
+        assert!(
+            metrics.contains(r#"registrar_db_command_seconds_count{command="commitTransaction"}"#)
+        );
+
+        // Field values are never exposed.
+        assert!(!metrics.contains("alice@email.com"));
+    }
+
+    #[test]
+    fn gather_ws_metrics() {
+        let client_closed = WS_DISCONNECTS.with_label_values(&["client_closed"]);
+        let state = WS_MESSAGES.with_label_values(&["state"]);
+        let before = (client_closed.get(), state.get());
+
+        observe_ws_session_started();
+        observe_ws_message("state");
+        observe_ws_session_stopped("client_closed");
+        set_ws_subscriptions(2, 3, 1, 2);
+
+        assert!(client_closed.get() > before.0);
+        assert!(state.get() > before.1);
+
+        let metrics = gather().unwrap();
+        assert!(metrics.contains("registrar_ws_sessions"));
+        assert!(metrics.contains(r#"registrar_ws_subscriptions{kind="identity"}"#));
+        assert!(metrics.contains(r#"registrar_ws_subscriptions{kind="chain"}"#));
+        assert!(metrics.contains("registrar_ws_subscribed_identities"));
+        assert!(metrics.contains("registrar_ws_max_sessions_per_identity"));
+        assert!(metrics.contains(r#"registrar_ws_messages_total{kind="state"}"#));
+        assert!(metrics.contains(r#"registrar_ws_disconnects_total{reason="client_closed"}"#));
+    }
+}
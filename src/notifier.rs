@@ -1,32 +1,223 @@
 use crate::api::{LookupServer, NotifyAccountState};
-use crate::database::{Database, EventCursor};
-use crate::primitives::{IdentityContext, JudgementState};
+use crate::database::Database;
+use crate::primitives::{Event, IdentityContext, JudgementState, NotificationMessage, Timestamp};
 use crate::Result;
 use actix::prelude::*;
-use std::collections::HashMap;
+use schemars::JsonSchema;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
 use tokio::time::{sleep, Duration};
 
-pub async fn run_session_notifier(mut db: Database, server: Addr<LookupServer>) {
+// Seconds between two checks for expired challenges.
+const CHALLENGE_EXPIRY_INTERVAL: u64 = 60;
+// In seconds
+const DEFAULT_LOOKBACK: u64 = 10;
+const DEFAULT_PAGE_SIZE: u64 = 500;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct EventLogConfig {
+    // Seconds during which events committed out of order are still fetched,
+    // defaults to ten seconds.
+    pub lookback: Option<u64>,
+    // Maximum number of events fetched per query, defaults to 500.
+    pub page_size: Option<u64>,
+}
+
+/// Reads the event log in the order of the log sequence numbers. Since the
+/// numbers are assigned before the events are committed, an event can become
+/// visible after events with higher numbers. Such gaps are checked again
+/// until they are older than the lookback, e.g. if the transaction was
+/// aborted.
+pub struct EventCursor {
+    seq: u64,
+    // Missing sequence numbers below `seq` and when they were noticed.
+    gaps: BTreeMap<u64, Timestamp>,
+    lookback: u64,
+    page_size: u64,
+}
+
+impl EventCursor {
+    /// Starts after the latest event of the log.
+    pub async fn new(db: &Database, config: EventLogConfig) -> Result<Self> {
+        Ok(Self::starting_at(db.latest_event_seq().await?, config))
+    }
+    fn starting_at(seq: u64, config: EventLogConfig) -> Self {
+        EventCursor {
+            seq,
+            gaps: BTreeMap::new(),
+            lookback: config.lookback.unwrap_or(DEFAULT_LOOKBACK),
+            page_size: config.page_size.unwrap_or(DEFAULT_PAGE_SIZE).max(1),
+        }
+    }
+    fn next_seq(&self) -> u64 {
+        self.gaps
+            .keys()
+            .next()
+            .map(|gap| gap - 1)
+            .unwrap_or(self.seq)
+    }
+    /// Returns the events which were not returned before and records the
+    /// gaps.
+    fn track(&mut self, events: Vec<Event>, now: Timestamp) -> Vec<Event> {
+        let mut unseen = vec![];
+        for event in events {
+            if event.log_seq > self.seq {
+                for missing in self.seq + 1..event.log_seq {
+                    self.gaps.insert(missing, now);
+                }
+
+                self.seq = event.log_seq;
+                unseen.push(event);
+            } else if self.gaps.remove(&event.log_seq).is_some() {
+                unseen.push(event);
+            }
+        }
+
+        unseen
+    }
+    fn expire_gaps(&mut self, now: Timestamp) {
+        let lookback = self.lookback;
+        self.gaps
+            .retain(|_, noticed| now.raw().saturating_sub(noticed.raw()) <= lookback);
+    }
+    /// Fetches the new events, page by page.
+    pub async fn fetch(&mut self, db: &Database) -> Result<Vec<Event>> {
+        let now = Timestamp::now();
+        let mut from = self.next_seq();
+        let mut events = vec![];
+
+        loop {
+            let page = db.events_since(from, self.page_size as i64).await?;
+            let is_full = page.len() as u64 == self.page_size;
+            if let Some(last) = page.last() {
+                from = last.log_seq;
+            }
+
+            events.extend(self.track(page, now));
+
+            if !is_full {
+                break;
+            }
+        }
+
+        self.expire_gaps(now);
+
+        Ok(events)
+    }
+}
+
+/// Notifications of an identity which were not sent yet.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+struct PendingNotifications {
+    notifications: Vec<NotificationMessage>,
+    // Sequence number of the latest event.
+    sequence: u64,
+}
+
+/// Combines the notifications of an identity, so clients receive at most one
+/// update per window. Identical notifications (e.g. repeatedly failed
+/// verifications) are only sent once. The event log retains all events.
+struct NotificationCoalescer {
+    window: Duration,
+    pending: HashMap<IdentityContext, PendingNotifications>,
+    last_sent: HashMap<IdentityContext, Instant>,
+}
+
+impl NotificationCoalescer {
+    fn new(window: Duration) -> Self {
+        NotificationCoalescer {
+            window,
+            pending: HashMap::new(),
+            last_sent: HashMap::new(),
+        }
+    }
+    fn push(&mut self, event: Event) {
+        let pending = self
+            .pending
+            .entry(event.message.context().clone())
+            .or_default();
+
+        if !pending.notifications.contains(&event.message) {
+            pending.notifications.push(event.message);
+        }
+
+        pending.sequence = pending.sequence.max(event.seq);
+    }
+    /// Removes the pending notifications of identities which did not receive
+    /// an update within the window.
+    fn take_ready(&mut self, now: Instant) -> Vec<(IdentityContext, PendingNotifications)> {
+        let window = self.window;
+        self.last_sent
+            .retain(|_, sent| now.duration_since(*sent) < window);
+
+        let ready: Vec<IdentityContext> = self
+            .pending
+            .keys()
+            .filter(|context| !self.last_sent.contains_key(*context))
+            .cloned()
+            .collect();
+
+        ready
+            .into_iter()
+            .filter_map(|context| {
+                self.last_sent.insert(context.clone(), now);
+                self.pending
+                    .remove(&context)
+                    .map(|pending| (context, pending))
+            })
+            .collect()
+    }
+}
+
+pub async fn run_session_notifier(
+    db: Database,
+    server: Addr<LookupServer>,
+    // Notifications are sent immediately if zero.
+    window: Duration,
+    event_log: EventLogConfig,
+) {
     async fn local(
-        db: &mut Database,
+        db: &Database,
         server: &Addr<LookupServer>,
         cursor: &mut EventCursor,
+        coalescer: Option<&mut NotificationCoalescer>,
     ) -> Result<()> {
-        let events = db.fetch_events(cursor).await?;
+        let events = cursor.fetch(db).await?;
+
+        if let Some(coalescer) = coalescer {
+            for event in events {
+                coalescer.push(event);
+            }
+
+            for (context, pending) in coalescer.take_ready(Instant::now()) {
+                let state = db
+                    .fetch_judgement_state(&context)
+                    .await?
+                    .ok_or_else(|| anyhow!("No identity state found for context: {:?}", context))?;
+
+                server.do_send(NotifyAccountState {
+                    state: state.into(),
+                    notifications: pending.notifications,
+                    sequence: pending.sequence,
+                });
+            }
+
+            return Ok(());
+        }
+
         let mut cache: HashMap<IdentityContext, JudgementState> = HashMap::new();
 
         for event in events {
-            let state = match cache.get(event.context()) {
+            let context = event.message.context();
+            let state = match cache.get(context) {
                 Some(state) => state.clone(),
                 None => {
-                    let state = db
-                        .fetch_judgement_state(event.context())
-                        .await?
-                        .ok_or_else(|| {
-                            anyhow!("No identity state found for context: {:?}", event.context())
-                        })?;
+                    let state = db.fetch_judgement_state(context).await?.ok_or_else(|| {
+                        anyhow!("No identity state found for context: {:?}", context)
+                    })?;
 
-                    cache.insert(event.context().clone(), state.clone());
+                    cache.insert(context.clone(), state.clone());
 
                     state
                 }
@@ -34,16 +225,31 @@ pub async fn run_session_notifier(mut db: Database, server: Addr<LookupServer>)
 
             server.do_send(NotifyAccountState {
                 state: state.into(),
-                notifications: vec![event],
+                notifications: vec![event.message],
+                sequence: event.seq,
             });
         }
 
         Ok(())
     }
 
-    let mut cursor = EventCursor::new();
+    let mut cursor = loop {
+        match EventCursor::new(&db, event_log).await {
+            Ok(cursor) => break cursor,
+            Err(err) => {
+                error!("Failed to fetch the latest event: {:?}", err);
+                sleep(Duration::from_secs(1)).await;
+            }
+        }
+    };
+    let mut coalescer = if window.is_zero() {
+        None
+    } else {
+        Some(NotificationCoalescer::new(window))
+    };
+
     loop {
-        if let Err(err) = local(&mut db, &server, &mut cursor).await {
+        if let Err(err) = local(&db, &server, &mut cursor, coalescer.as_mut()).await {
             error!("Error in session notifier event loop: {:?}", err);
         }
 
@@ -53,3 +259,120 @@ pub async fn run_session_notifier(mut db: Database, server: Addr<LookupServer>)
         sleep(Duration::from_secs(1)).await;
     }
 }
+
+/// Periodically applies the expiry to pending challenges. Users are informed
+/// about expired challenges via the event log.
+pub async fn run_challenge_expiry(db: Database, ttl: u64) {
+    loop {
+        match db.expire_challenges(ttl, Timestamp::now()).await {
+            Ok(0) => {}
+            Ok(expired) => debug!("{} challenges expired", expired),
+            Err(err) => error!("Failed to apply challenge expiry: {:?}", err),
+        }
+
+        sleep(Duration::from_secs(CHALLENGE_EXPIRY_INTERVAL)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::IdentityFieldValue;
+
+    fn failed(context: IdentityContext, seq: u64) -> Event {
+        Event {
+            seq,
+            ..Event::new(NotificationMessage::FieldVerificationFailed {
+                context,
+                field: IdentityFieldValue::ALICE_EMAIL(),
+            })
+        }
+    }
+
+    #[test]
+    fn coalesce_notifications() {
+        let alice = IdentityContext::alice();
+        let bob = IdentityContext::bob();
+        let mut coalescer = NotificationCoalescer::new(Duration::from_secs(5));
+        let start = Instant::now();
+
+        coalescer.push(failed(alice.clone(), 1));
+        coalescer.push(failed(alice.clone(), 2));
+        coalescer.push(Event {
+            seq: 3,
+            ..Event::new(NotificationMessage::IdentityUpdated {
+                context: alice.clone(),
+            })
+        });
+
+        // Identical notifications are combined.
+        let ready = coalescer.take_ready(start);
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].0, alice);
+        assert_eq!(ready[0].1.notifications.len(), 2);
+        assert_eq!(ready[0].1.sequence, 3);
+
+        // Further notifications are held back until the window elapsed.
+        coalescer.push(failed(alice.clone(), 4));
+        coalescer.push(failed(bob.clone(), 1));
+
+        let ready = coalescer.take_ready(start + Duration::from_secs(1));
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].0, bob);
+
+        coalescer.push(failed(alice.clone(), 5));
+        let ready = coalescer.take_ready(start + Duration::from_secs(5));
+        assert_eq!(
+            ready,
+            vec![(
+                alice,
+                PendingNotifications {
+                    notifications: vec![failed(IdentityContext::alice(), 0).message],
+                    sequence: 5,
+                }
+            )]
+        );
+
+        assert!(coalescer
+            .take_ready(start + Duration::from_secs(6))
+            .is_empty());
+    }
+
+    #[test]
+    fn event_cursor_gaps() {
+        let logged = |log_seq: u64| Event {
+            log_seq,
+            ..failed(IdentityContext::alice(), log_seq)
+        };
+        let seqs = |events: Vec<Event>| events.iter().map(|e| e.log_seq).collect::<Vec<_>>();
+
+        let mut cursor = EventCursor::starting_at(
+            10,
+            EventLogConfig {
+                lookback: Some(5),
+                page_size: None,
+            },
+        );
+        assert_eq!(cursor.next_seq(), 10);
+
+        // Event 12 is committed before event 11.
+        let now = Timestamp::from(1_000);
+        assert_eq!(seqs(cursor.track(vec![logged(12)], now)), vec![12]);
+        assert_eq!(cursor.next_seq(), 10);
+
+        // Fetched events are only returned once.
+        assert_eq!(
+            seqs(cursor.track(vec![logged(11), logged(12), logged(13)], now)),
+            vec![11, 13]
+        );
+        assert_eq!(cursor.next_seq(), 13);
+
+        // Gaps which are not filled within the lookback are given up.
+        assert_eq!(seqs(cursor.track(vec![logged(16)], now)), vec![16]);
+        cursor.expire_gaps(Timestamp::from(1_005));
+        assert_eq!(cursor.next_seq(), 13);
+        cursor.expire_gaps(Timestamp::from(1_006));
+        assert_eq!(cursor.next_seq(), 16);
+        assert!(cursor.track(vec![logged(14)], now).is_empty());
+    }
+}
@@ -1,7 +1,13 @@
 use crate::adapters::admin::RawFieldName;
-use crate::connector::{AccountType, DisplayNameEntry, VerifiedEntry};
+use crate::compliance::ComplianceDecision;
+use crate::connector::DisplayNameEntry;
+use crate::disposable_emails::DisposableEmailAction;
+use crate::protocol::{AccountType, VerifiedEntry};
 use actix::Message;
-use std::collections::HashMap;
+use blake2::{Blake2b512, Digest};
+use schemars::JsonSchema;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -17,9 +23,34 @@ impl IdentityContext {
             chain: network,
         }
     }
+    /// The address in the SS58 format of the network, together with its
+    /// shortened form, for messages and API payloads.
+    pub fn display_address(&self) -> DisplayAddress {
+        let address = self.address.canonical(self.chain);
+        DisplayAddress {
+            short: address.short(),
+            address,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+// Length of the public key of an account.
+const SS58_ACCOUNT_LENGTH: usize = 32;
+// Length of the checksum suffix.
+const SS58_CHECKSUM_LENGTH: usize = 2;
+// Characters kept on each side of shortened addresses.
+const SHORT_ADDRESS_CHARS: usize = 6;
+
+fn ss58_checksum(data: &[u8]) -> [u8; SS58_CHECKSUM_LENGTH] {
+    let mut hasher = Blake2b512::new();
+    hasher.update(b"SS58PRE");
+    hasher.update(data);
+    let hash = hasher.finalize();
+
+    [hash[0], hash[1]]
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct ChainAddress(String);
 
@@ -27,6 +58,141 @@ impl ChainAddress {
     pub fn as_str(&self) -> &str {
         self.0.as_str()
     }
+    /// Re-encodes the SS58 address with the prefix of the network. Fails if
+    /// the address is not a valid SS58 encoded account.
+    pub fn to_ss58(&self, chain: ChainName) -> crate::Result<ChainAddress> {
+        self.with_ss58_prefix(chain.ss58_prefix())
+    }
+    /// Like `to_ss58`, but with an arbitrary (single byte) prefix, e.g. of a
+    /// parachain.
+    pub fn with_ss58_prefix(&self, prefix: u8) -> crate::Result<ChainAddress> {
+        let (_, account) = self.decode_ss58()?;
+        Ok(ChainAddress::from_account_id(&account, prefix))
+    }
+    /// Encodes the public key of an account with the (single byte) prefix.
+    pub fn from_account_id(account: &[u8], prefix: u8) -> ChainAddress {
+        let mut encoded = vec![prefix];
+        encoded.extend_from_slice(account);
+        let checksum = ss58_checksum(&encoded);
+        encoded.extend_from_slice(&checksum);
+
+        ChainAddress(bs58::encode(encoded).into_string())
+    }
+    /// The SS58 prefix the address is encoded with. Fails if the address is
+    /// not a valid SS58 encoded account.
+    pub fn ss58_prefix(&self) -> crate::Result<u16> {
+        self.decode_ss58().map(|(prefix, _)| prefix)
+    }
+    /// Fails if the address is not a valid SS58 encoded account of the
+    /// network, i.e. is encoded with the prefix of another network.
+    pub fn validate(&self, chain: ChainName) -> crate::Result<()> {
+        self.validate_ss58_prefix(chain.ss58_prefix())
+    }
+    /// Like `validate`, but with an arbitrary (single byte) prefix, e.g. of a
+    /// parachain.
+    pub fn validate_ss58_prefix(&self, expected: u8) -> crate::Result<()> {
+        let prefix = self.ss58_prefix()?;
+        if prefix != u16::from(expected) {
+            return Err(anyhow!(
+                "address {} has SS58 prefix {}, expected {}",
+                self.0,
+                prefix,
+                expected
+            ));
+        }
+
+        Ok(())
+    }
+    /// Decodes the address into its prefix and the public key of the account,
+    /// verifying the checksum.
+    fn decode_ss58(&self) -> crate::Result<(u16, Vec<u8>)> {
+        let data = bs58::decode(&self.0).into_vec()?;
+
+        // Prefixes up to 63 take one byte, larger ones two.
+        let (prefix, prefix_len) = match data.as_slice() {
+            [first @ 0..=63, ..] => (u16::from(*first), 1),
+            [first @ 64..=127, second, ..] => {
+                let lower = ((first & 0b0011_1111) << 2) | (second >> 6);
+                let upper = second & 0b0011_1111;
+                (u16::from(lower) | (u16::from(upper) << 8), 2)
+            }
+            _ => return Err(anyhow!("invalid SS58 prefix of address {}", self.0)),
+        };
+
+        if data.len() != prefix_len + SS58_ACCOUNT_LENGTH + SS58_CHECKSUM_LENGTH {
+            return Err(anyhow!("invalid length of SS58 address {}", self.0));
+        }
+
+        let (payload, checksum) = data.split_at(data.len() - SS58_CHECKSUM_LENGTH);
+        if ss58_checksum(payload) != checksum {
+            return Err(anyhow!("invalid checksum of SS58 address {}", self.0));
+        }
+
+        Ok((prefix, payload[prefix_len..].to_vec()))
+    }
+    /// Whether the address is non-empty, alphanumeric (as SS58 encoded
+    /// addresses are) and does not exceed the length. The checksum is not
+    /// verified, see `to_ss58`.
+    pub fn is_well_formed(&self, max_length: usize) -> bool {
+        !self.0.is_empty()
+            && self.0.len() <= max_length
+            && self.0.chars().all(|c| c.is_ascii_alphanumeric())
+    }
+    /// Like `to_ss58`, but keeps the address as is if it cannot be decoded.
+    pub fn canonical(&self, chain: ChainName) -> ChainAddress {
+        self.to_ss58(chain).unwrap_or_else(|_| self.clone())
+    }
+    /// Shortened form for display, e.g. `1a2YiG…GhZP`.
+    pub fn short(&self) -> String {
+        let chars: Vec<char> = self.0.chars().collect();
+        if chars.len() <= SHORT_ADDRESS_CHARS * 2 + 1 {
+            return self.0.clone();
+        }
+
+        format!(
+            "{}…{}",
+            chars[..SHORT_ADDRESS_CHARS].iter().collect::<String>(),
+            chars[chars.len() - SHORT_ADDRESS_CHARS..]
+                .iter()
+                .collect::<String>()
+        )
+    }
+}
+
+/// The display name of an identity. Kept apart from addresses and field
+/// values, so those cannot be mixed up.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct DisplayName(String);
+
+impl DisplayName {
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl From<String> for DisplayName {
+    fn from(v: String) -> Self {
+        DisplayName(v)
+    }
+}
+
+impl From<&str> for DisplayName {
+    fn from(v: &str) -> Self {
+        DisplayName(v.to_string())
+    }
+}
+
+impl std::fmt::Display for DisplayName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct DisplayAddress {
+    pub address: ChainAddress,
+    pub short: String,
 }
 
 impl From<String> for ChainAddress {
@@ -35,20 +201,50 @@ impl From<String> for ChainAddress {
     }
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ChainName {
     Polkadot,
     Kusama,
+    Westend,
 }
 
 impl ChainName {
+    /// All supported networks. Which of those are served is determined by the
+    /// configured Watchers.
+    pub const ALL: [ChainName; 3] = [ChainName::Polkadot, ChainName::Kusama, ChainName::Westend];
+
     pub fn as_str(&self) -> &str {
         match self {
             ChainName::Polkadot => "polkadot",
             ChainName::Kusama => "kusama",
+            ChainName::Westend => "westend",
         }
     }
+    pub fn ss58_prefix(&self) -> u8 {
+        match self {
+            ChainName::Polkadot => 0,
+            ChainName::Kusama => 2,
+            ChainName::Westend => 42,
+        }
+    }
+    /// The network using the SS58 prefix, if any.
+    pub fn from_ss58_prefix(prefix: u16) -> Option<ChainName> {
+        ChainName::ALL
+            .into_iter()
+            .find(|chain| u16::from(chain.ss58_prefix()) == prefix)
+    }
+}
+
+impl std::str::FromStr for ChainName {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ChainName::ALL
+            .into_iter()
+            .find(|chain| chain.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| anyhow!("unknown chain {}", s))
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -57,10 +253,25 @@ pub struct IdentityField {
     pub value: IdentityFieldValue,
     pub challenge: ChallengeType,
     pub failed_attempts: usize,
+    // The last received message which did not verify the field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_failure: Option<FieldFailure>,
+    // When the challenge the user must send was handed out, i.e. when the
+    // field was inserted or its challenge regenerated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub challenge_sent: Option<Timestamp>,
 }
 
 impl IdentityField {
     pub fn new(val: IdentityFieldValue) -> Self {
+        Self::with_challenges(val, &mut ExpectedMessage::random)
+    }
+    /// Creates the field with challenges from `next_challenge`, e.g. taken
+    /// from a `ChallengePool`.
+    pub fn with_challenges(
+        val: IdentityFieldValue,
+        next_challenge: &mut impl FnMut() -> ExpectedMessage,
+    ) -> Self {
         use IdentityFieldValue::*;
 
         let challenge = {
@@ -73,17 +284,18 @@ impl IdentityField {
                 DisplayName(_) => ChallengeType::DisplayNameCheck {
                     passed: false,
                     violations: vec![],
+                    reserved: None,
                 },
                 Email(_) => ChallengeType::ExpectedMessage {
-                    expected: ExpectedMessage::random(),
-                    second: Some(ExpectedMessage::random()),
+                    expected: next_challenge(),
+                    second: Some(next_challenge()),
                 },
                 Twitter(_) => ChallengeType::ExpectedMessage {
-                    expected: ExpectedMessage::random(),
+                    expected: next_challenge(),
                     second: None,
                 },
                 Matrix(_) => ChallengeType::ExpectedMessage {
-                    expected: ExpectedMessage::random(),
+                    expected: next_challenge(),
                     second: None,
                 },
             }
@@ -93,6 +305,15 @@ impl IdentityField {
             value: val,
             challenge,
             failed_attempts: 0,
+            last_failure: None,
+            challenge_sent: None,
+        }
+    }
+    /// Records when the challenge was handed out to the user, if the field
+    /// has a challenge the user must send and none was recorded yet.
+    pub fn mark_challenge_sent(&mut self, at: Timestamp) {
+        if let ChallengeType::ExpectedMessage { .. } = self.challenge {
+            self.challenge_sent.get_or_insert(at);
         }
     }
 }
@@ -107,6 +328,9 @@ pub enum ChallengeType {
     DisplayNameCheck {
         passed: bool,
         violations: Vec<DisplayNameEntry>,
+        // The reserved name or pattern matched by the display name.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reserved: Option<String>,
     },
     Unsupported {
         // For manual judgements via the admin interface.
@@ -124,20 +348,61 @@ impl ChallengeType {
                     expected.is_verified
                 }
             }
-            ChallengeType::DisplayNameCheck {
-                passed,
-                violations: _,
-            } => *passed,
+            ChallengeType::DisplayNameCheck { passed, .. } => *passed,
             ChallengeType::Unsupported { is_verified } => is_verified.unwrap_or(false),
         }
     }
 }
 
+/// The challenge which must be sent by the user, either a random string or a
+/// short numeric code.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ChallengeValue(String);
+
+impl ChallengeValue {
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for ChallengeValue {
+    fn from(v: String) -> Self {
+        ChallengeValue(v)
+    }
+}
+
+impl std::fmt::Display for ChallengeValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct ExpectedMessage {
-    pub value: String,
+    pub value: ChallengeValue,
     pub is_verified: bool,
+    // Only set for short numeric codes, which are no longer accepted after
+    // this time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<Timestamp>,
+    // Only set for short numeric codes, which are no longer accepted once no
+    // attempts are left.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remaining_attempts: Option<u32>,
+    // Only set for first challenges if challenge expiry is enabled. Expired
+    // challenges are no longer accepted and must be renewed by the user.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_until: Option<Timestamp>,
+    // Set once the challenge expired and the user was notified.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub expired: bool,
 }
 
 impl ExpectedMessage {
@@ -146,13 +411,45 @@ impl ExpectedMessage {
 
         let random: [u8; 16] = thread_rng().gen();
         ExpectedMessage {
-            value: hex::encode(random),
+            value: hex::encode(random).into(),
+            is_verified: false,
+            expires_at: None,
+            remaining_attempts: None,
+            valid_until: None,
+            expired: false,
+        }
+    }
+    /// A short numeric code, which is easier to type than the random string.
+    pub fn code(digits: usize, ttl: u64, attempts: u32) -> Self {
+        use rand::{thread_rng, Rng};
+
+        let mut rng = thread_rng();
+        ExpectedMessage {
+            value: (0..digits)
+                .map(|_| char::from(b'0' + rng.gen_range(0..10)))
+                .collect::<String>()
+                .into(),
             is_verified: false,
+            expires_at: Some(Timestamp::with_offset(ttl)),
+            remaining_attempts: Some(attempts),
+            valid_until: None,
+            expired: false,
         }
     }
+    pub fn is_code(&self) -> bool {
+        self.expires_at.is_some()
+    }
+    /// Whether the code expired or no attempts are left. Always `false` for
+    /// random strings.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .map(|expires_at| Timestamp::now().raw() >= expires_at.raw())
+            .unwrap_or(false)
+            || self.remaining_attempts == Some(0)
+    }
     pub fn is_message_valid(&self, message: &ExternalMessage) -> bool {
         for value in &message.values {
-            if value.0.contains(&self.value) {
+            if value.0.contains(self.value.as_str()) {
                 return true;
             }
         }
@@ -165,7 +462,47 @@ impl ExpectedMessage {
     }
     #[cfg(test)]
     pub fn to_message_parts(&self) -> Vec<MessagePart> {
-        vec![self.value.clone().into()]
+        vec![self.value.to_string().into()]
+    }
+}
+
+/// Challenges generated ahead of time, so the judgement states of a sync with
+/// the Watcher are built without generating challenges per identity. Each
+/// challenge is handed out once. If the pool runs dry, challenges are
+/// generated on demand.
+#[derive(Debug, Clone, Default)]
+pub struct ChallengePool {
+    challenges: Arc<Mutex<Vec<ExpectedMessage>>>,
+}
+
+impl ChallengePool {
+    /// Generates challenges until the pool holds `size` challenges.
+    pub fn refill(&self, size: usize) {
+        let missing = size.saturating_sub(self.available());
+        if missing == 0 {
+            return;
+        }
+
+        // Generated without holding the lock.
+        let fresh: Vec<ExpectedMessage> = (0..missing).map(|_| ExpectedMessage::random()).collect();
+        self.challenges
+            .lock()
+            .expect("challenge pool poisoned")
+            .extend(fresh);
+    }
+    pub fn next_challenge(&self) -> ExpectedMessage {
+        self.challenges
+            .lock()
+            .expect("challenge pool poisoned")
+            .pop()
+            .unwrap_or_else(ExpectedMessage::random)
+    }
+    /// Number of challenges left in the pool.
+    pub fn available(&self) -> usize {
+        self.challenges
+            .lock()
+            .expect("challenge pool poisoned")
+            .len()
     }
 }
 
@@ -184,6 +521,19 @@ pub enum IdentityFieldValue {
 }
 
 impl IdentityFieldValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            IdentityFieldValue::LegalName(_) => "legal_name",
+            IdentityFieldValue::DisplayName(_) => "display_name",
+            IdentityFieldValue::Email(_) => "email",
+            IdentityFieldValue::Web(_) => "web",
+            IdentityFieldValue::Twitter(_) => "twitter",
+            IdentityFieldValue::Matrix(_) => "matrix",
+            IdentityFieldValue::PGPFingerprint(_) => "pgp_fingerprint",
+            IdentityFieldValue::Image(_) => "image",
+            IdentityFieldValue::Additional(_) => "additional",
+        }
+    }
     pub fn as_account_type(&self) -> (AccountType, String) {
         match self {
             IdentityFieldValue::LegalName(val) => (AccountType::LegalName, val.to_string()),
@@ -198,17 +548,25 @@ impl IdentityFieldValue {
         }
     }
     pub fn matches_type(&self, ty: &AccountType, value: &str) -> bool {
-        match (self, ty) {
-            (IdentityFieldValue::LegalName(val), AccountType::LegalName) => val == value,
-            (IdentityFieldValue::DisplayName(val), AccountType::DisplayName) => val == value,
-            (IdentityFieldValue::Email(val), AccountType::Email) => val == value,
-            (IdentityFieldValue::Web(val), AccountType::Web) => val == value,
-            (IdentityFieldValue::Twitter(val), AccountType::Twitter) => val == value,
-            (IdentityFieldValue::Matrix(val), AccountType::Matrix) => val == value,
-            (IdentityFieldValue::PGPFingerprint(_), AccountType::PGPFingerprint) => true,
-            (IdentityFieldValue::Image(_), AccountType::Image) => true,
-            (IdentityFieldValue::Additional(_), AccountType::Additional) => true,
-            _ => false,
+        self.is_equivalent(&(ty.clone(), value.to_string()).into())
+    }
+    /// Whether both values only differ cosmetically, e.g. in surrounding
+    /// whitespace or in the case of case-insensitive values. Such changes
+    /// must not reset the verification of a field.
+    pub fn is_equivalent(&self, other: &IdentityFieldValue) -> bool {
+        self.canonical() == other.canonical()
+    }
+    fn canonical(&self) -> IdentityFieldValue {
+        use IdentityFieldValue::*;
+
+        match self {
+            LegalName(val) => LegalName(val.trim().to_string()),
+            DisplayName(val) => DisplayName(val.trim().to_string()),
+            Email(val) => Email(val.trim().to_lowercase()),
+            Web(val) => Web(val.trim().trim_end_matches('/').to_lowercase()),
+            Twitter(val) => Twitter(val.trim().to_lowercase()),
+            Matrix(val) => Matrix(val.trim().to_string()),
+            other => other.clone(),
         }
     }
     pub fn matches_origin(&self, message: &ExternalMessage) -> bool {
@@ -225,6 +583,10 @@ impl IdentityFieldValue {
                 ExternalMessageType::Matrix(n2) => n1 == n2,
                 _ => false,
             },
+            IdentityFieldValue::Web(n1) => match &message.origin {
+                ExternalMessageType::Web(n2) => n1 == n2,
+                _ => false,
+            },
             _ => false,
         }
     }
@@ -241,7 +603,17 @@ pub struct JudgementStateBlanked {
     pub inserted_timestamp: Timestamp,
     pub completion_timestamp: Option<Timestamp>,
     pub judgement_submitted: bool,
+    pub requires_manual_review: bool,
+    pub judgement_withheld: Option<WithheldReason>,
+    #[serde(default)]
+    pub requested_at_block: Option<u64>,
+    #[serde(default)]
+    pub is_erroneous: bool,
+    #[serde(default)]
+    pub judgement_type: JudgementType,
     pub fields: Vec<IdentityFieldBlanked>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_address: Option<DisplayAddress>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -250,6 +622,8 @@ pub struct IdentityFieldBlanked {
     pub value: IdentityFieldValue,
     pub challenge: ChallengeTypeBlanked,
     failed_attempts: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_failure: Option<FieldFailure>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -262,6 +636,9 @@ pub enum ChallengeTypeBlanked {
     DisplayNameCheck {
         passed: bool,
         violations: Vec<DisplayNameEntry>,
+        // The reserved name or pattern matched by the display name.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reserved: Option<String>,
     },
     Unsupported {
         // For manual judgements via the admin interface.
@@ -274,16 +651,64 @@ pub struct ExpectedMessageBlanked {
     // IMPORTANT: This value is blanked.
     // pub value: String,
     pub is_verified: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<Timestamp>,
+}
+
+impl JudgementStateBlanked {
+    /// Empties the expected challenge values, the verification status of each
+    /// field is kept.
+    pub fn hide_challenge_values(&mut self) {
+        for field in &mut self.fields {
+            if let ChallengeTypeBlanked::ExpectedMessage { expected, .. } = &mut field.challenge {
+                expected.value = ChallengeValue::default();
+            }
+        }
+    }
+    pub fn status(&self) -> JudgementStatus {
+        if self.judgement_submitted {
+            JudgementStatus::Judged
+        } else if self.is_fully_verified && self.judgement_withheld.is_some() {
+            JudgementStatus::Withheld
+        } else if self.is_fully_verified {
+            JudgementStatus::Verified
+        } else if self.requires_manual_review {
+            JudgementStatus::ManualReview
+        } else {
+            JudgementStatus::Pending
+        }
+    }
+}
+
+/// Coarse progress of an identity, e.g. to filter subscriptions.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JudgementStatus {
+    // Waiting for the user to complete the challenges.
+    Pending,
+    // Can only be verified by an admin.
+    ManualReview,
+    // Fully verified, the judgement is about to be submitted.
+    Verified,
+    // Fully verified, but the judgement is withheld.
+    Withheld,
+    Judged,
 }
 
 impl From<JudgementState> for JudgementStateBlanked {
     fn from(s: JudgementState) -> Self {
         JudgementStateBlanked {
+            display_address: Some(s.context.display_address()),
             context: s.context,
             is_fully_verified: s.is_fully_verified,
             inserted_timestamp: s.inserted_timestamp,
             completion_timestamp: s.completion_timestamp,
             judgement_submitted: s.judgement_submitted,
+            requires_manual_review: s.requires_manual_review,
+            judgement_withheld: s.judgement_withheld,
+            requested_at_block: s.requested_at_block,
+            is_erroneous: s.erroneous_reason.is_some(),
+            judgement_type: s.judgement_type,
             fields: s
                 .fields
                 .into_iter()
@@ -296,18 +721,26 @@ impl From<JudgementState> for JudgementStateBlanked {
                                     expected,
                                     second: second.map(|s| ExpectedMessageBlanked {
                                         is_verified: s.is_verified,
+                                        expires_at: s.expires_at,
                                     }),
                                 }
                             }
-                            ChallengeType::DisplayNameCheck { passed, violations } => {
-                                ChallengeTypeBlanked::DisplayNameCheck { passed, violations }
-                            }
+                            ChallengeType::DisplayNameCheck {
+                                passed,
+                                violations,
+                                reserved,
+                            } => ChallengeTypeBlanked::DisplayNameCheck {
+                                passed,
+                                violations,
+                                reserved,
+                            },
                             ChallengeType::Unsupported { is_verified } => {
                                 ChallengeTypeBlanked::Unsupported { is_verified }
                             }
                         }
                     },
                     failed_attempts: f.failed_attempts,
+                    last_failure: f.last_failure,
                 })
                 .collect(),
         }
@@ -323,11 +756,83 @@ pub struct JudgementState {
     pub completion_timestamp: Option<Timestamp>,
     pub judgement_submitted: bool,
     pub issue_judgement_at: Option<Timestamp>,
+    // Set if none of the fields can be verified automatically, so the identity
+    // can only be judged by an admin.
+    #[serde(default)]
+    pub requires_manual_review: bool,
+    // Notes of the script hook, only visible to admins.
+    #[serde(default)]
+    pub annotations: Vec<String>,
+    // Set while the identity is fully verified, but not judged.
+    #[serde(default)]
+    pub judgement_withheld: Option<WithheldReason>,
+    // Block number of the chain at which the judgement was requested, if
+    // reported by the Watcher.
+    #[serde(default)]
+    pub requested_at_block: Option<u64>,
+    // Set if an admin judged the identity as erroneous, e.g. because it is
+    // malicious. The judgement is then submitted as `Erroneous`.
+    #[serde(default)]
+    pub erroneous_reason: Option<String>,
+    // Judgement submitted once the identity is fully verified, as decided by
+    // an admin.
+    #[serde(default)]
+    pub judgement_type: JudgementType,
+    // When the reminder about the last unverified field was scheduled, it is
+    // sent at most once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nudged_at: Option<Timestamp>,
+    // Decision of the compliance endpoint on the judgement request, if
+    // configured. Flagged identities require a manual review.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compliance_decision: Option<ComplianceDecision>,
     pub fields: Vec<IdentityField>,
 }
 
+/// The judgement of a verified identity. Erroneous judgements are issued
+/// regardless of the verification status, see `erroneous_reason`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JudgementType {
+    #[default]
+    Reasonable,
+    KnownGood,
+    LowQuality,
+}
+
+/// Why the judgement of a fully verified identity is not submitted.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WithheldReason {
+    // An admin paused judgement submissions for the network.
+    SubmissionsPaused,
+}
+
+/// Whether at least one of the fields can be verified automatically.
+pub fn has_verifiable_fields(fields: &[IdentityField]) -> bool {
+    fields
+        .iter()
+        .any(|field| !matches!(field.challenge, ChallengeType::Unsupported { .. }))
+}
+
 impl JudgementState {
     pub fn new(context: IdentityContext, fields: Vec<IdentityFieldValue>) -> Self {
+        Self::with_challenges(context, fields, ExpectedMessage::random)
+    }
+    /// Creates the state with challenges from `next_challenge`, e.g. taken
+    /// from a `ChallengePool`.
+    pub fn with_challenges(
+        context: IdentityContext,
+        fields: Vec<IdentityFieldValue>,
+        mut next_challenge: impl FnMut() -> ExpectedMessage,
+    ) -> Self {
+        let mut fields: Vec<IdentityField> = fields
+            .into_iter()
+            .map(|field| IdentityField::with_challenges(field, &mut next_challenge))
+            .collect();
+        // Requests are built from maps, the order must not matter.
+        fields.sort_by_key(|field| field.value.type_name());
+
         JudgementState {
             context,
             is_fully_verified: false,
@@ -335,7 +840,43 @@ impl JudgementState {
             completion_timestamp: None,
             judgement_submitted: false,
             issue_judgement_at: None,
-            fields: fields.into_iter().map(IdentityField::new).collect(),
+            requires_manual_review: !has_verifiable_fields(&fields),
+            annotations: vec![],
+            judgement_withheld: None,
+            requested_at_block: None,
+            erroneous_reason: None,
+            judgement_type: JudgementType::Reasonable,
+            nudged_at: None,
+            compliance_decision: None,
+            fields,
+        }
+    }
+    /// Whether the state stems from a request of a later block than the given
+    /// one. Unknown block numbers are never considered older.
+    pub fn requested_after(&self, block_number: Option<u64>) -> bool {
+        matches!(
+            (self.requested_at_block, block_number),
+            (Some(current), Some(other)) if current > other
+        )
+    }
+    /// Challenges the web fields with a message which is placed on the
+    /// website, instead of leaving those to a manual review.
+    pub fn challenge_web_fields(&mut self) {
+        for field in &mut self.fields {
+            if let IdentityFieldValue::Web(_) = field.value {
+                field.challenge = ChallengeType::ExpectedMessage {
+                    expected: ExpectedMessage::random(),
+                    second: None,
+                };
+            }
+        }
+
+        self.requires_manual_review = !has_verifiable_fields(&self.fields);
+    }
+    /// See `IdentityField::mark_challenge_sent`.
+    pub fn mark_challenges_sent(&mut self, at: Timestamp) {
+        for field in &mut self.fields {
+            field.mark_challenge_sent(at);
         }
     }
     pub fn check_full_verification(&self) -> bool {
@@ -343,6 +884,32 @@ impl JudgementState {
             .iter()
             .all(|field| field.challenge.is_verified())
     }
+    /// The only field which is not verified yet, if it can be verified by
+    /// the user.
+    pub fn last_unverified_field(&self) -> Option<&IdentityField> {
+        let mut unverified = self
+            .fields
+            .iter()
+            .filter(|field| !field.challenge.is_verified());
+
+        match (unverified.next(), unverified.next()) {
+            (Some(field), None)
+                if !matches!(field.challenge, ChallengeType::Unsupported { .. }) =>
+            {
+                Some(field)
+            }
+            _ => None,
+        }
+    }
+    /// The verified fields via which the user can be contacted.
+    pub fn verified_channels(&self) -> Vec<&IdentityFieldValue> {
+        self.fields
+            .iter()
+            .filter(|field| field.challenge.is_verified())
+            .map(|field| &field.value)
+            .filter(|value| OutboxEntry::nudge_adapter(value).is_some())
+            .collect()
+    }
     pub fn display_name(&self) -> Option<&str> {
         self.fields
             .iter()
@@ -352,6 +919,14 @@ impl JudgementState {
                 _ => panic!("Failed to get display name. This is a bug."),
             })
     }
+    /// Returns the fields which cannot be verified automatically.
+    pub fn unsupported_fields(&self) -> Vec<AccountType> {
+        self.fields
+            .iter()
+            .filter(|field| matches!(field.challenge, ChallengeType::Unsupported { .. }))
+            .map(|field| field.value.as_account_type().0)
+            .collect()
+    }
     pub fn has_same_fields_as(&self, other: &HashMap<AccountType, String>) -> bool {
         if other.len() != self.fields.len() {
             return false;
@@ -369,6 +944,17 @@ impl JudgementState {
 
         true
     }
+    /// Whether both states contain equivalent fields, independent of the
+    /// order.
+    pub fn has_equivalent_fields(&self, other: &JudgementState) -> bool {
+        self.fields.len() == other.fields.len()
+            && self.fields.iter().all(|field| {
+                other
+                    .fields
+                    .iter()
+                    .any(|other| other.value.is_equivalent(&field.value))
+            })
+    }
     pub fn as_verified_entries(&self) -> Vec<VerifiedEntry> {
         let mut list = vec![];
 
@@ -381,6 +967,116 @@ impl JudgementState {
     }
 }
 
+/// A message which must be sent by an adapter. Entries are written in the same
+/// transaction as the state change requiring the message, so no message is
+/// lost if the service stops in between.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct OutboxEntry {
+    pub id: String,
+    pub adapter: String,
+    pub context: IdentityContext,
+    pub field: IdentityFieldValue,
+    pub kind: OutboxKind,
+    // Failed delivery attempts.
+    pub attempts: u32,
+    pub done: bool,
+    // The entry is not dispatched before this time, either because another
+    // dispatcher claimed it or because a failed delivery is retried later.
+    #[serde(default)]
+    pub locked_until: Option<Timestamp>,
+    pub created: Timestamp,
+    // The second challenge, stored with the first delivery attempt so retries
+    // resend the same one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<ExpectedMessage>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxKind {
+    SecondChallenge,
+    // Reminder that a single field remains unverified.
+    Nudge,
+}
+
+impl OutboxEntry {
+    /// The entry which must be written along with the notification, if any.
+    pub fn for_notification(message: &NotificationMessage) -> Option<Self> {
+        use rand::{thread_rng, Rng};
+
+        match message {
+            NotificationMessage::AwaitingSecondChallenge {
+                context,
+                field: field @ IdentityFieldValue::Email(_),
+            } => {
+                let random: [u8; 8] = thread_rng().gen();
+                Some(OutboxEntry {
+                    id: hex::encode(random),
+                    adapter: "email".to_string(),
+                    context: context.clone(),
+                    field: field.clone(),
+                    kind: OutboxKind::SecondChallenge,
+                    attempts: 0,
+                    done: false,
+                    locked_until: None,
+                    created: Timestamp::now(),
+                    challenge: None,
+                })
+            }
+            _ => None,
+        }
+    }
+    /// The adapter sending reminders to the field, if it can be contacted.
+    pub fn nudge_adapter(field: &IdentityFieldValue) -> Option<&'static str> {
+        match field {
+            IdentityFieldValue::Email(_) => Some("email"),
+            IdentityFieldValue::Matrix(_) => Some("Matrix"),
+            _ => None,
+        }
+    }
+    /// A reminder sent to the verified field, if it can be contacted.
+    pub fn nudge(context: IdentityContext, field: IdentityFieldValue) -> Option<Self> {
+        use rand::{thread_rng, Rng};
+
+        let adapter = OutboxEntry::nudge_adapter(&field)?;
+        let random: [u8; 8] = thread_rng().gen();
+        Some(OutboxEntry {
+            id: hex::encode(random),
+            adapter: adapter.to_string(),
+            context,
+            field,
+            kind: OutboxKind::Nudge,
+            attempts: 0,
+            done: false,
+            locked_until: None,
+            created: Timestamp::now(),
+            challenge: None,
+        })
+    }
+}
+
+/// A message sent (or, in sandbox mode, only recorded) by an adapter.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct OutboundMessage {
+    pub adapter: String,
+    pub context: IdentityContext,
+    pub to: String,
+    // Type of the field the message belongs to, missing for messages which
+    // were archived before it was recorded.
+    #[serde(default)]
+    pub field: Option<String>,
+    // The text as sent to the user.
+    pub content: String,
+    pub sandbox: bool,
+    pub delivered: bool,
+    // Why the delivery failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub timestamp: Timestamp,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, Message)]
 #[serde(rename_all = "snake_case")]
 #[rtype(result = "()")]
@@ -389,20 +1085,157 @@ pub struct ExternalMessage {
     pub id: MessageId,
     pub timestamp: Timestamp,
     pub values: Vec<MessagePart>,
+    // Provider artifacts of the message, recorded with the verification
+    // outcome.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ProviderMetadata>,
+}
+
+impl ExternalMessage {
+    /// Truncated hash of the message content, which identifies the message
+    /// without storing its content.
+    pub fn content_hash(&self) -> String {
+        let mut hasher = Blake2b512::new();
+        for part in &self.values {
+            hasher.update(part.as_str().as_bytes());
+            hasher.update([0]);
+        }
+
+        hex::encode(&hasher.finalize()[..8])
+    }
+    /// Identifies the processing of this message. Processing the same message
+    /// again, e.g. after a retry or by another instance, yields the same key.
+    pub fn operation_key(&self) -> String {
+        let origin =
+            serde_json::to_vec(&self.origin).expect("message origins are always serializable");
+
+        let mut hasher = Blake2b512::new();
+        hasher.update(&origin);
+        hasher.update(self.id.raw().to_be_bytes());
+        hasher.update(self.timestamp.raw().to_be_bytes());
+        hasher.update(self.content_hash().as_bytes());
+
+        format!("message:{}", hex::encode(&hasher.finalize()[..16]))
+    }
+}
+
+/// Identifies a received message at its provider, so a verification can be
+/// traced back to e.g. the Matrix event or the email headers.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ProviderMetadata {
+    // The Matrix event ID, the email `Message-ID`, the DM or tweet ID or the
+    // URL of the web page.
+    #[serde(default)]
+    pub provider_id: Option<String>,
+    // The Matrix homeserver of the sender.
+    #[serde(default)]
+    pub homeserver: Option<String>,
+    // Selected email headers, keyed by the lowercase header name, or other
+    // provider specific details.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub details: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationOutcome {
+    Verified,
+    Failed,
+    Vetoed,
+    Expired,
+}
+
+/// Why a received message did not verify the field.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureCause {
+    // The message contains a challenge, but not the expected one.
+    WrongChallenge,
+    // The message contains nothing in the format of the challenge.
+    NoChallenge,
+    // The contact value is shared with other identities and the message lacks
+    // the disambiguation token.
+    MissingDisambiguationToken,
+    Expired,
+    Vetoed,
+}
+
+/// The message which caused the last failed verification of a field, so
+/// users and support can tell a wrong challenge from a malformed message.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct FieldFailure {
+    pub cause: FailureCause,
+    pub message_id: MessageId,
+    // See `ExternalMessage::content_hash`.
+    pub content_hash: String,
+    // When the message was received.
+    pub timestamp: Timestamp,
+}
+
+impl FieldFailure {
+    pub fn new(cause: FailureCause, message: &ExternalMessage) -> Self {
+        FieldFailure {
+            cause,
+            message_id: message.id,
+            content_hash: message.content_hash(),
+            timestamp: message.timestamp,
+        }
+    }
+}
+
+/// A field verified based on the attestation of a trusted issuer, kept as
+/// the provenance of the verification.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ImportedCredential {
+    pub context: IdentityContext,
+    pub field: IdentityFieldValue,
+    pub issuer: String,
+    pub issued_at: Timestamp,
+    pub signature: String,
+    pub imported_at: Timestamp,
 }
 
+/// The outcome of verifying a received message, kept for audits.
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct VerificationRecord {
+    pub context: IdentityContext,
+    pub origin: ExternalMessageType,
+    pub message_id: MessageId,
+    pub outcome: VerificationOutcome,
+    // Only set for messages which did not verify the field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cause: Option<FailureCause>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ProviderMetadata>,
+    // When the message was received.
+    pub timestamp: Timestamp,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "type", content = "value")]
 pub enum ExternalMessageType {
     Email(String),
     Twitter(String),
     Matrix(String),
+    Web(String),
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct MessageId(u64);
 
+impl MessageId {
+    pub fn raw(&self) -> u64 {
+        self.0
+    }
+}
+
 impl From<u64> for MessageId {
     fn from(val: u64) -> Self {
         MessageId(val)
@@ -442,15 +1275,37 @@ impl Timestamp {
             other
         }
     }
+    pub fn min(self, other: Timestamp) -> Self {
+        if self.0 <= other.0 {
+            self
+        } else {
+            other
+        }
+    }
     pub fn raw(&self) -> u64 {
         self.0
     }
 }
 
+impl From<u64> for Timestamp {
+    fn from(val: u64) -> Self {
+        Timestamp(val)
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct MessagePart(String);
 
+impl MessagePart {
+    pub fn contains(&self, value: &str) -> bool {
+        self.0.contains(value)
+    }
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
 impl From<String> for MessagePart {
     fn from(val: String) -> Self {
         MessagePart(val)
@@ -462,6 +1317,18 @@ impl From<String> for MessagePart {
 pub struct Event {
     pub timestamp: Timestamp,
     pub message: NotificationMessage,
+    // Sequence number of the event per identity, starting at one. Events which
+    // were created before sequence numbers were introduced have none.
+    #[serde(default)]
+    pub seq: u64,
+    // Position of the event in the log across all identities, see
+    // `EventCursor`. Zero for events created before it was introduced.
+    #[serde(default)]
+    pub log_seq: u64,
+    // Only set for events of an identifiable operation, see
+    // `Event::with_operation`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dedup_key: Option<String>,
 }
 
 impl Event {
@@ -469,8 +1336,31 @@ impl Event {
         Event {
             timestamp: Timestamp::now(),
             message,
+            seq: 0,
+            log_seq: 0,
+            dedup_key: None,
         }
     }
+    /// Marks the event as created by the operation, e.g. the processing of a
+    /// message (see `ExternalMessage::operation_key`). The same event of the
+    /// same operation is only inserted once, while identical events of
+    /// different operations are all kept.
+    pub fn with_operation(mut self, operation: &str) -> Self {
+        let context = self.message.context();
+        let content = serde_json::to_vec(&self.message)
+            .expect("notification messages are always serializable");
+
+        self.dedup_key = Some(format!(
+            "{}:{}:{}:{}:{}",
+            operation,
+            context.chain.as_str(),
+            context.address.as_str(),
+            self.message.kind(),
+            hex::encode(&Blake2b512::digest(&content)[..16]),
+        ));
+
+        self
+    }
 }
 
 impl From<NotificationMessage> for Event {
@@ -522,6 +1412,76 @@ pub enum NotificationMessage {
     FullManualVerification {
         context: IdentityContext,
     },
+    ContactValueShared {
+        context: IdentityContext,
+        field: IdentityFieldValue,
+        // Truncated addresses of the other identities.
+        shared_with: Vec<String>,
+    },
+    DisambiguationRequired {
+        context: IdentityContext,
+        field: IdentityFieldValue,
+        token: String,
+    },
+    // The email address belongs to a listed disposable domain.
+    DisposableEmail {
+        context: IdentityContext,
+        field: IdentityFieldValue,
+        domain: String,
+        action: DisposableEmailAction,
+    },
+    // The Matrix account is rejected by the homeserver or user lists.
+    MatrixAccountRejected {
+        context: IdentityContext,
+        field: IdentityFieldValue,
+        reason: String,
+    },
+    // The script hook vetoed the verification of the field or, if no field
+    // is specified, of the identity.
+    VerificationVetoed {
+        context: IdentityContext,
+        field: Option<IdentityFieldValue>,
+        reason: Option<String>,
+    },
+    // The user requested a new challenge, the previous one is no longer valid.
+    ChallengeRegenerated {
+        context: IdentityContext,
+        field: IdentityFieldValue,
+    },
+    // The second challenge code expired or too many attempts failed, a new
+    // code must be requested.
+    SecondChallengeExpired {
+        context: IdentityContext,
+        field: IdentityFieldValue,
+    },
+    // The identity is fully verified, but the judgement is not submitted.
+    JudgementWithheld {
+        context: IdentityContext,
+        reason: WithheldReason,
+    },
+    // An admin judged the identity as erroneous.
+    JudgedErroneous {
+        context: IdentityContext,
+        reason: String,
+    },
+    // The challenge was not verified in time, a new one must be requested.
+    ChallengeExpired {
+        context: IdentityContext,
+        field: IdentityFieldValue,
+    },
+    // Too many messages were received from the account, further messages
+    // are ignored until the block is lifted.
+    MessagesRateLimited {
+        context: IdentityContext,
+        field: IdentityFieldValue,
+        until: Timestamp,
+    },
+    // The field was verified based on the attestation of a trusted issuer.
+    CredentialImported {
+        context: IdentityContext,
+        field: IdentityFieldValue,
+        issuer: String,
+    },
 }
 
 impl NotificationMessage {
@@ -540,10 +1500,168 @@ impl NotificationMessage {
             JudgementProvided { context } => context,
             ManuallyVerified { context, field: _ } => context,
             FullManualVerification { context } => context,
+            ContactValueShared { context, .. } => context,
+            DisambiguationRequired { context, .. } => context,
+            DisposableEmail { context, .. } => context,
+            MatrixAccountRejected { context, .. } => context,
+            VerificationVetoed { context, .. } => context,
+            ChallengeRegenerated { context, field: _ } => context,
+            SecondChallengeExpired { context, field: _ } => context,
+            JudgementWithheld { context, reason: _ } => context,
+            JudgedErroneous { context, reason: _ } => context,
+            ChallengeExpired { context, field: _ } => context,
+            MessagesRateLimited { context, .. } => context,
+            CredentialImported { context, .. } => context,
+        }
+    }
+}
+
+// Version of the notification envelope. Must be increased whenever the
+// payload of an existing kind changes, so consumers can detect it.
+pub const NOTIFICATION_VERSION: u32 = 8;
+
+/// Generates the stable kind names of the notifications, together with the
+/// envelope version which introduced them. Every variant must be listed.
+macro_rules! notification_kinds {
+    ($($variant:ident => $kind:literal since $version:literal,)*) => {
+        impl NotificationMessage {
+            pub fn kind(&self) -> &'static str {
+                match self {
+                    $(NotificationMessage::$variant { .. } => $kind,)*
+                }
+            }
+        }
+
+        pub const NOTIFICATION_KINDS: &[(&'static str, u32)] = &[$(($kind, $version),)*];
+
+        #[cfg(test)]
+        const NOTIFICATION_VARIANTS: &[(&str, &str)] = &[$((stringify!($variant), $kind),)*];
+    };
+}
+
+notification_kinds! {
+    IdentityInserted => "identity_inserted" since 1,
+    IdentityUpdated => "identity_updated" since 1,
+    FieldVerified => "field_verified" since 1,
+    FieldVerificationFailed => "field_verification_failed" since 1,
+    SecondFieldVerified => "second_field_verified" since 1,
+    SecondFieldVerificationFailed => "second_field_verification_failed" since 1,
+    AwaitingSecondChallenge => "awaiting_second_challenge" since 1,
+    IdentityFullyVerified => "identity_fully_verified" since 1,
+    JudgementProvided => "judgement_provided" since 1,
+    ManuallyVerified => "manually_verified" since 1,
+    FullManualVerification => "full_manual_verification" since 1,
+    ContactValueShared => "contact_value_shared" since 1,
+    DisambiguationRequired => "disambiguation_required" since 1,
+    DisposableEmail => "disposable_email" since 1,
+    MatrixAccountRejected => "matrix_account_rejected" since 1,
+    VerificationVetoed => "verification_vetoed" since 1,
+    ChallengeRegenerated => "challenge_regenerated" since 2,
+    SecondChallengeExpired => "second_challenge_expired" since 3,
+    JudgementWithheld => "judgement_withheld" since 4,
+    JudgedErroneous => "judged_erroneous" since 5,
+    ChallengeExpired => "challenge_expired" since 6,
+    MessagesRateLimited => "messages_rate_limited" since 7,
+    CredentialImported => "credential_imported" since 8,
+}
+
+/// Lists the notification kinds per envelope version, in Markdown.
+#[cfg(test)]
+pub fn notification_changelog() -> String {
+    let mut changelog = "# Notification kinds\n".to_string();
+
+    for version in 1..=NOTIFICATION_VERSION {
+        changelog.push_str(&format!("\n## Version {}\n\n", version));
+
+        for (kind, _) in NOTIFICATION_KINDS
+            .iter()
+            .filter(|(_, since)| *since == version)
+        {
+            changelog.push_str(&format!("* `{}`\n", kind));
+        }
+    }
+
+    changelog
+}
+
+/// The versioned representation of a notification as sent to clients. Next to
+/// the `type` and `value` of the notification, it contains the `kind` and the
+/// envelope `version`. Kinds which are unknown to this version (e.g. sent by a
+/// newer server) are deserialized without a message rather than failing.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NotificationEnvelope {
+    pub version: u32,
+    pub kind: String,
+    pub message: Option<NotificationMessage>,
+}
+
+impl From<NotificationMessage> for NotificationEnvelope {
+    fn from(val: NotificationMessage) -> Self {
+        NotificationEnvelope {
+            version: NOTIFICATION_VERSION,
+            kind: val.kind().to_string(),
+            message: Some(val),
         }
     }
 }
 
+impl serde::Serialize for NotificationEnvelope {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Envelope<'a> {
+            version: u32,
+            kind: &'a str,
+            #[serde(flatten)]
+            message: &'a Option<NotificationMessage>,
+        }
+
+        Envelope {
+            version: self.version,
+            kind: &self.kind,
+            message: &self.message,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for NotificationEnvelope {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Envelope {
+            // Notifications without an envelope are treated as the first version.
+            #[serde(default = "first_version")]
+            version: u32,
+            kind: Option<String>,
+            #[serde(flatten)]
+            rest: serde_json::Map<String, serde_json::Value>,
+        }
+
+        fn first_version() -> u32 {
+            1
+        }
+
+        let Envelope {
+            version,
+            kind,
+            rest,
+        } = <Envelope as serde::Deserialize>::deserialize(deserializer)?;
+
+        let kind = kind
+            .or_else(|| {
+                rest.get("type")
+                    .and_then(|ty| ty.as_str())
+                    .map(|ty| ty.to_string())
+            })
+            .ok_or_else(|| serde::de::Error::missing_field("kind"))?;
+
+        Ok(NotificationEnvelope {
+            version,
+            kind,
+            message: serde_json::from_value(serde_json::Value::Object(rest)).ok(),
+        })
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct IdentityJudged {
@@ -583,6 +1701,14 @@ mod tests {
                 completion_timestamp: None,
                 judgement_submitted: false,
                 issue_judgement_at: None,
+                requires_manual_review: false,
+                annotations: vec![],
+                judgement_withheld: None,
+                requested_at_block: None,
+                erroneous_reason: None,
+                judgement_type: JudgementType::Reasonable,
+                nudged_at: None,
+                compliance_decision: None,
                 fields: vec![
                     IdentityField::new(IdentityFieldValue::ALICE_DISPLAY_NAME()),
                     IdentityField::new(IdentityFieldValue::ALICE_EMAIL()),
@@ -608,6 +1734,7 @@ mod tests {
                 ExternalMessageType::Email(n) => IdentityFieldValue::Email(n),
                 ExternalMessageType::Twitter(n) => IdentityFieldValue::Twitter(n),
                 ExternalMessageType::Matrix(n) => IdentityFieldValue::Matrix(n),
+                ExternalMessageType::Web(n) => IdentityFieldValue::Web(n),
             }
         }
     }
@@ -677,12 +1804,17 @@ mod tests {
         pub fn failed_attempts_mut(&mut self) -> &mut usize {
             &mut self.failed_attempts
         }
+        pub fn last_failure_mut(&mut self) -> &mut Option<FieldFailure> {
+            &mut self.last_failure
+        }
         // rename, without "expected"
         pub fn expected_display_name_check_mut(
             &mut self,
         ) -> (&mut bool, &mut Vec<DisplayNameEntry>) {
             match &mut self.challenge {
-                ChallengeType::DisplayNameCheck { passed, violations } => (passed, violations),
+                ChallengeType::DisplayNameCheck {
+                    passed, violations, ..
+                } => (passed, violations),
                 _ => panic!(),
             }
         }
@@ -732,5 +1864,438 @@ mod tests {
 
         assert!(!state.has_same_fields_as(&accounts_trimmed));
         assert!(state.has_same_fields_as(&accounts));
+
+        let accounts_cosmetic: HashMap<AccountType, String> = [
+            (AccountType::LegalName, "Alice ".to_string()),
+            (AccountType::DisplayName, "alice".to_string()),
+            (AccountType::Email, "Alice@Gmail.com".to_string()),
+            (AccountType::Twitter, "@Alice".to_string()),
+        ]
+        .into();
+
+        assert!(state.has_same_fields_as(&accounts_cosmetic));
+    }
+
+    #[test]
+    fn requested_after() {
+        let mut state = JudgementState::alice();
+        assert!(!state.requested_after(Some(10)));
+
+        state.requested_at_block = Some(10);
+        assert!(state.requested_after(Some(9)));
+        assert!(!state.requested_after(Some(10)));
+        assert!(!state.requested_after(Some(11)));
+        // Requests without a block number are never considered stale.
+        assert!(!state.requested_after(None));
+    }
+
+    #[test]
+    fn reencode_ss58_address() {
+        let generic =
+            ChainAddress::from("5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY".to_string());
+        let polkadot =
+            ChainAddress::from("15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5".to_string());
+        let kusama =
+            ChainAddress::from("HNZata7iMYWmk5RvZRTiAsSDhV8366zq2YGb3tLH5Upf74F".to_string());
+
+        assert_eq!(generic.to_ss58(ChainName::Polkadot).unwrap(), polkadot);
+        assert_eq!(generic.to_ss58(ChainName::Kusama).unwrap(), kusama);
+        assert_eq!(kusama.to_ss58(ChainName::Polkadot).unwrap(), polkadot);
+        assert_eq!(polkadot.to_ss58(ChainName::Polkadot).unwrap(), polkadot);
+
+        // Invalid checksum.
+        let invalid =
+            ChainAddress::from("15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp6".to_string());
+        assert!(invalid.to_ss58(ChainName::Polkadot).is_err());
+        assert_eq!(invalid.canonical(ChainName::Polkadot), invalid);
+
+        let context = IdentityContext::new(generic, ChainName::Kusama);
+        assert_eq!(
+            context.display_address(),
+            DisplayAddress {
+                address: kusama,
+                short: "HNZata…Upf74F".to_string(),
+            }
+        );
+        assert_eq!(ChainAddress::from("1a2b".to_string()).short(), "1a2b");
+    }
+
+    #[test]
+    fn validate_ss58_prefix() {
+        let generic =
+            ChainAddress::from("5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY".to_string());
+        let polkadot =
+            ChainAddress::from("15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp5".to_string());
+        let kusama =
+            ChainAddress::from("HNZata7iMYWmk5RvZRTiAsSDhV8366zq2YGb3tLH5Upf74F".to_string());
+
+        assert_eq!(polkadot.ss58_prefix().unwrap(), 0);
+        assert_eq!(kusama.ss58_prefix().unwrap(), 2);
+        assert_eq!(generic.ss58_prefix().unwrap(), 42);
+
+        assert!(polkadot.validate(ChainName::Polkadot).is_ok());
+        assert!(kusama.validate(ChainName::Kusama).is_ok());
+        assert!(generic.validate(ChainName::Westend).is_ok());
+        assert!(polkadot.validate(ChainName::Kusama).is_err());
+        assert!(generic.validate(ChainName::Polkadot).is_err());
+
+        // Invalid checksum.
+        let invalid =
+            ChainAddress::from("15oF4uVJwmo4TdGW7VfQxNLavjCXviqxT9S1MgbjMNHr6Sp6".to_string());
+        assert!(invalid.ss58_prefix().is_err());
+        assert!(invalid.validate(ChainName::Polkadot).is_err());
+
+        // Encoded from the public key.
+        let (_, account) = generic.decode_ss58().unwrap();
+        assert_eq!(ChainAddress::from_account_id(&account, 0), polkadot);
+        assert_eq!(ChainAddress::from_account_id(&account, 42), generic);
+
+        assert_eq!(ChainName::from_ss58_prefix(2), Some(ChainName::Kusama));
+        assert_eq!(ChainName::from_ss58_prefix(42), Some(ChainName::Westend));
+        assert_eq!(ChainName::from_ss58_prefix(7), None);
+        assert_eq!("Westend".parse::<ChainName>().unwrap(), ChainName::Westend);
+        assert!("rococo".parse::<ChainName>().is_err());
+    }
+
+    #[test]
+    fn newtypes_serialize_as_strings() {
+        let mut expected = ExpectedMessage::random();
+        expected.value = ChallengeValue::from("1a2b3c".to_string());
+        let value = serde_json::to_value(&expected).unwrap();
+        assert_eq!(value["value"], "1a2b3c");
+        assert_eq!(
+            serde_json::from_value::<ExpectedMessage>(value).unwrap(),
+            expected
+        );
+
+        let name = DisplayName::from("Alice");
+        assert_eq!(serde_json::to_value(&name).unwrap(), "Alice");
+        assert_eq!(bson::to_bson(&name).unwrap(), bson::Bson::from("Alice"));
+
+        let address = IdentityContext::alice().address;
+        assert!(address.is_well_formed(48));
+        assert!(!address.is_well_formed(10));
+        assert!(!ChainAddress::from("1a2Y-iGNu".to_string()).is_well_formed(48));
+        assert!(!ChainAddress::from(String::new()).is_well_formed(48));
+    }
+
+    #[test]
+    fn equivalent_field_values() {
+        use IdentityFieldValue::*;
+
+        let equivalent = |a: IdentityFieldValue, b: IdentityFieldValue| a.is_equivalent(&b);
+
+        assert!(equivalent(
+            Email("alice@email.com".to_string()),
+            Email(" Alice@Email.com".to_string())
+        ));
+        assert!(equivalent(
+            Web("https://alice.com/".to_string()),
+            Web("https://Alice.com".to_string())
+        ));
+        assert!(equivalent(
+            DisplayName("Alice\n".to_string()),
+            DisplayName("Alice".to_string())
+        ));
+        assert!(equivalent(PGPFingerprint(()), PGPFingerprint(())));
+
+        // The case of names is significant.
+        assert!(!equivalent(
+            DisplayName("alice".to_string()),
+            DisplayName("Alice".to_string())
+        ));
+        assert!(!equivalent(
+            Email("alice@email.com".to_string()),
+            Twitter("alice@email.com".to_string())
+        ));
+
+        // Field order does not matter.
+        let fields = vec![
+            IdentityFieldValue::ALICE_TWITTER(),
+            IdentityFieldValue::ALICE_EMAIL(),
+            IdentityFieldValue::ALICE_DISPLAY_NAME(),
+        ];
+        let state = JudgementState::new(IdentityContext::alice(), fields.clone());
+        let reversed =
+            JudgementState::new(IdentityContext::alice(), fields.into_iter().rev().collect());
+        assert_eq!(
+            state.fields.iter().map(|f| &f.value).collect::<Vec<_>>(),
+            reversed.fields.iter().map(|f| &f.value).collect::<Vec<_>>()
+        );
+        assert!(state.has_equivalent_fields(&reversed));
+    }
+
+    #[test]
+    fn challenge_pool() {
+        let pool = ChallengePool::default();
+        pool.refill(4);
+        assert_eq!(pool.available(), 4);
+
+        // Email fields take two challenges, the display name none.
+        let state = JudgementState::with_challenges(
+            IdentityContext::alice(),
+            vec![
+                IdentityFieldValue::ALICE_DISPLAY_NAME(),
+                IdentityFieldValue::ALICE_EMAIL(),
+                IdentityFieldValue::ALICE_TWITTER(),
+            ],
+            || pool.next_challenge(),
+        );
+        assert_eq!(pool.available(), 1);
+
+        // Each challenge is handed out once.
+        let mut values: std::collections::HashSet<ChallengeValue> = state
+            .fields
+            .iter()
+            .flat_map(|field| match &field.challenge {
+                ChallengeType::ExpectedMessage { expected, second } => std::iter::once(expected)
+                    .chain(second)
+                    .map(|challenge| challenge.value.clone())
+                    .collect(),
+                _ => vec![],
+            })
+            .collect();
+        values.insert(pool.next_challenge().value);
+        assert_eq!(values.len(), 4);
+
+        // Generated on demand once the pool is empty.
+        assert_eq!(pool.available(), 0);
+        assert_ne!(pool.next_challenge(), pool.next_challenge());
+
+        pool.refill(2);
+        pool.refill(2);
+        assert_eq!(pool.available(), 2);
+    }
+
+    #[test]
+    fn mark_challenges_sent() {
+        let mut state = JudgementState::alice();
+        state.mark_challenges_sent(Timestamp::from(1_000));
+
+        let matrix = state.get_field(&IdentityFieldValue::ALICE_MATRIX());
+        assert_eq!(matrix.challenge_sent, Some(Timestamp::from(1_000)));
+
+        // Display names are checked, not sent.
+        let display_name = state.get_field(&IdentityFieldValue::ALICE_DISPLAY_NAME());
+        assert_eq!(display_name.challenge_sent, None);
+
+        // Recorded timestamps are kept.
+        state.mark_challenges_sent(Timestamp::from(2_000));
+        let matrix = state.get_field(&IdentityFieldValue::ALICE_MATRIX());
+        assert_eq!(matrix.challenge_sent, Some(Timestamp::from(1_000)));
+    }
+
+    #[test]
+    fn requires_manual_review() {
+        let state = JudgementState::new(
+            IdentityContext::alice(),
+            vec![
+                IdentityFieldValue::LegalName("Alice".to_string()),
+                IdentityFieldValue::Web("alice.com".to_string()),
+            ],
+        );
+        assert!(state.requires_manual_review);
+        assert_eq!(
+            state.unsupported_fields(),
+            vec![AccountType::LegalName, AccountType::Web]
+        );
+
+        let state = JudgementState::new(IdentityContext::alice(), vec![]);
+        assert!(state.requires_manual_review);
+
+        let state = JudgementState::new(
+            IdentityContext::alice(),
+            vec![
+                IdentityFieldValue::LegalName("Alice".to_string()),
+                IdentityFieldValue::ALICE_EMAIL(),
+            ],
+        );
+        assert!(!state.requires_manual_review);
+        assert_eq!(state.unsupported_fields(), vec![AccountType::LegalName]);
+
+        // Web fields can be verified once challenged.
+        let mut state = JudgementState::new(
+            IdentityContext::alice(),
+            vec![
+                IdentityFieldValue::LegalName("Alice".to_string()),
+                IdentityFieldValue::Web("alice.com".to_string()),
+            ],
+        );
+        state.challenge_web_fields();
+        assert!(!state.requires_manual_review);
+        assert_eq!(state.unsupported_fields(), vec![AccountType::LegalName]);
+    }
+
+    #[test]
+    fn judgement_status() {
+        let status = |state: &JudgementState| JudgementStateBlanked::from(state.clone()).status();
+
+        let mut state = JudgementState::alice();
+        assert_eq!(status(&state), JudgementStatus::Pending);
+
+        state.requires_manual_review = true;
+        assert_eq!(status(&state), JudgementStatus::ManualReview);
+
+        state.is_fully_verified = true;
+        assert_eq!(status(&state), JudgementStatus::Verified);
+
+        state.judgement_withheld = Some(WithheldReason::SubmissionsPaused);
+        assert_eq!(status(&state), JudgementStatus::Withheld);
+
+        state.judgement_submitted = true;
+        assert_eq!(status(&state), JudgementStatus::Judged);
+    }
+
+    #[test]
+    fn notification_kinds_match_serialization() {
+        for (variant, kind) in NOTIFICATION_VARIANTS {
+            let mut snake_case = String::new();
+            for (i, c) in variant.chars().enumerate() {
+                if c.is_uppercase() && i > 0 {
+                    snake_case.push('_');
+                }
+                snake_case.push(c.to_ascii_lowercase());
+            }
+
+            assert_eq!(&snake_case, kind);
+        }
+
+        let message = NotificationMessage::FieldVerified {
+            context: IdentityContext::alice(),
+            field: IdentityFieldValue::ALICE_EMAIL(),
+        };
+        assert_eq!(
+            serde_json::to_value(&message).unwrap()["type"],
+            message.kind()
+        );
+    }
+
+    #[test]
+    fn notification_envelope() {
+        let message = NotificationMessage::FieldVerified {
+            context: IdentityContext::alice(),
+            field: IdentityFieldValue::ALICE_EMAIL(),
+        };
+
+        let value = serde_json::to_value(NotificationEnvelope::from(message.clone())).unwrap();
+        assert_eq!(value["version"], NOTIFICATION_VERSION);
+        assert_eq!(value["kind"], "field_verified");
+        // Consumers of the plain notifications are unaffected.
+        assert_eq!(value["type"], "field_verified");
+        assert_eq!(
+            serde_json::from_value::<NotificationMessage>(value.clone()).unwrap(),
+            message
+        );
+
+        let envelope = serde_json::from_value::<NotificationEnvelope>(value).unwrap();
+        assert_eq!(envelope, NotificationEnvelope::from(message.clone()));
+
+        // Notifications without an envelope are of the first version.
+        let value = serde_json::to_value(&message).unwrap();
+        assert_eq!(
+            serde_json::from_value::<NotificationEnvelope>(value).unwrap(),
+            NotificationEnvelope {
+                version: 1,
+                kind: "field_verified".to_string(),
+                message: Some(message),
+            }
+        );
+
+        // Unknown kinds of newer versions.
+        let value = serde_json::json!({
+            "version": 3,
+            "kind": "identity_teleported",
+            "type": "identity_teleported",
+            "value": { "to": "kusama" },
+        });
+        assert_eq!(
+            serde_json::from_value::<NotificationEnvelope>(value).unwrap(),
+            NotificationEnvelope {
+                version: 3,
+                kind: "identity_teleported".to_string(),
+                message: None,
+            }
+        );
+    }
+
+    #[test]
+    fn notification_changelog_is_up_to_date() {
+        assert_eq!(
+            notification_changelog(),
+            include_str!("../NOTIFICATIONS.md"),
+            "NOTIFICATIONS.md is outdated, update it with the output of `notification_changelog`"
+        );
+    }
+
+    #[test]
+    fn dedup_key_of_event() {
+        let event = |context: IdentityContext, timestamp: u64| Event {
+            timestamp: Timestamp::from(timestamp),
+            ..Event::new(NotificationMessage::IdentityUpdated { context })
+        };
+
+        assert_eq!(event(IdentityContext::alice(), 1_000).dedup_key, None);
+
+        let key = event(IdentityContext::alice(), 1_000)
+            .with_operation("op")
+            .dedup_key
+            .unwrap();
+        assert!(key.starts_with(
+            "op:polkadot:1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP:identity_updated:"
+        ));
+
+        // The time of creation is irrelevant, only the operation and the
+        // content identify the event.
+        assert_eq!(
+            event(IdentityContext::alice(), 5_000)
+                .with_operation("op")
+                .dedup_key,
+            Some(key.clone())
+        );
+        assert_ne!(
+            event(IdentityContext::alice(), 1_000)
+                .with_operation("other")
+                .dedup_key,
+            Some(key.clone())
+        );
+        assert_ne!(
+            event(IdentityContext::bob(), 1_000)
+                .with_operation("op")
+                .dedup_key,
+            Some(key.clone())
+        );
+
+        let other = Event::new(NotificationMessage::IdentityInserted {
+            context: IdentityContext::alice(),
+        })
+        .with_operation("op");
+        assert_ne!(other.dedup_key, Some(key));
+    }
+
+    #[test]
+    fn operation_key_of_message() {
+        let message = ExternalMessage {
+            origin: ExternalMessageType::Email("alice@email.com".to_string()),
+            id: MessageId::from(1u64),
+            timestamp: Timestamp::from(1_000),
+            values: vec![MessagePart::from("challenge".to_string())],
+            metadata: None,
+        };
+
+        let key = message.operation_key();
+        assert_eq!(message.clone().operation_key(), key);
+
+        // Another message with the same content.
+        let other = ExternalMessage {
+            id: MessageId::from(2u64),
+            ..message.clone()
+        };
+        assert_ne!(other.operation_key(), key);
+
+        let other = ExternalMessage {
+            origin: ExternalMessageType::Matrix("@alice:matrix.org".to_string()),
+            ..message
+        };
+        assert_ne!(other.operation_key(), key);
     }
 }
@@ -0,0 +1,433 @@
+use crate::primitives::{ChainAddress, DisplayName, IdentityFieldValue, JudgementType};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ResponseMessage<T> {
+    pub event: EventType,
+    pub data: T,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum EventType {
+    #[serde(rename = "ack")]
+    Ack,
+    #[serde(rename = "error")]
+    Error,
+    #[serde(rename = "newJudgementRequest")]
+    NewJudgementRequest,
+    #[serde(rename = "judgementResult")]
+    JudgementResult,
+    #[serde(rename = "pendingJudgementsRequest")]
+    PendingJudgementsRequest,
+    #[serde(rename = "pendingJudgementsResponse")]
+    PendingJudgementsResponse,
+    #[serde(rename = "displayNamesRequest")]
+    DisplayNamesRequest,
+    #[serde(rename = "displayNamesResponse")]
+    DisplayNamesResponse,
+    #[serde(rename = "manualReviewRequired")]
+    ManualReviewRequired,
+    #[serde(rename = "judgementRequestBlocked")]
+    JudgementRequestBlocked,
+    #[serde(rename = "judgementRequestWithdrawn")]
+    JudgementRequestWithdrawn,
+    #[serde(rename = "judgementRequestRejected")]
+    JudgementRequestRejected,
+    // Events introduced by newer Watcher versions are logged and ignored.
+    #[serde(other, skip_serializing)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JudgementResponse {
+    pub address: ChainAddress,
+    pub judgement: Judgement,
+    pub verified: Vec<VerifiedEntry>,
+    // Echoed by the Watcher in the corresponding ack.
+    #[serde(rename = "correlationId")]
+    pub correlation_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifiedEntry {
+    #[serde(rename = "accountTy")]
+    pub account_ty: AccountType,
+    pub value: String,
+}
+
+/// Informs the Watcher that the identity cannot be verified automatically.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManualReviewResponse {
+    pub address: ChainAddress,
+    pub reason: String,
+    // The fields which must be reviewed by an admin.
+    pub fields: Vec<AccountType>,
+}
+
+/// Informs the Watcher that the judgement request was refused, because the
+/// address is blocked. The request is never judged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockedResponse {
+    pub address: ChainAddress,
+    pub reason: String,
+}
+
+/// Informs the Watcher that the user withdrew the judgement request, so it can
+/// be reflected as unhandled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WithdrawnResponse {
+    pub address: ChainAddress,
+}
+
+/// Informs the Watcher that the judgement request was rejected, because it
+/// was sent for a chain this instance is not configured for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectedResponse {
+    pub address: ChainAddress,
+    // The chain of the request.
+    pub network: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AckResponse {
+    pub result: String,
+    #[serde(default)]
+    pub address: Option<ChainAddress>,
+    // Older Watcher versions do not echo the correlation ID, in which case
+    // the submission is matched by address.
+    #[serde(default, rename = "correlationId")]
+    pub correlation_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Judgement {
+    #[serde(rename = "reasonable")]
+    Reasonable,
+    #[serde(rename = "erroneous")]
+    Erroneous,
+    #[serde(rename = "knownGood")]
+    KnownGood,
+    #[serde(rename = "lowQuality")]
+    LowQuality,
+}
+
+impl From<JudgementType> for Judgement {
+    fn from(ty: JudgementType) -> Self {
+        match ty {
+            JudgementType::Reasonable => Judgement::Reasonable,
+            JudgementType::KnownGood => Judgement::KnownGood,
+            JudgementType::LowQuality => Judgement::LowQuality,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JudgementRequest {
+    pub address: ChainAddress,
+    #[serde(deserialize_with = "known_accounts")]
+    pub accounts: HashMap<AccountType, String>,
+    // Block at which the judgement was requested. Not sent by older Watcher
+    // versions.
+    #[serde(
+        default,
+        rename = "blockNumber",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub block_number: Option<u64>,
+    // Chain for which the judgement was requested. Not sent by older Watcher
+    // versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+}
+
+// Skips account types which are not supported (yet), instead of rejecting the
+// whole judgement request.
+fn known_accounts<'de, D>(deserializer: D) -> Result<HashMap<AccountType, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let raw: HashMap<String, String> = HashMap::deserialize(deserializer)?;
+
+    Ok(raw
+        .into_iter()
+        .filter_map(|(ty, value)| {
+            match serde_json::from_value(serde_json::Value::String(ty.clone())) {
+                Ok(ty) => Some((ty, value)),
+                Err(_) => {
+                    warn!("Ignoring unsupported account type from Watcher: {}", ty);
+                    None
+                }
+            }
+        })
+        .collect())
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+/// The entry as sent by the Watcher. Then converted into `DisplayNameEntry`.
+pub struct DisplayNameEntryRaw {
+    pub address: ChainAddress,
+    #[serde(alias = "displayName")]
+    pub display_name: DisplayName,
+}
+
+impl DisplayNameEntryRaw {
+    /// Display names with emojis are represented in HEX form. Decode the
+    /// display name, assuming it can be decoded...
+    pub fn try_decode_hex(&mut self) {
+        let mut name = self.display_name.to_string();
+        try_decode_hex(&mut name);
+        self.display_name = name.into();
+    }
+}
+
+pub(crate) fn try_decode_hex(display_name: &mut String) {
+    if display_name.starts_with("0x") {
+        // Might be a false positive. Leave it as is if it cannot be decoded.
+        if let Ok(name) = hex::decode(&display_name[2..]) {
+            if let Ok(name) = String::from_utf8(name) {
+                *display_name = name;
+            }
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Hash, Clone, Debug, Serialize, Deserialize)]
+pub enum AccountType {
+    #[serde(rename = "legal_name")]
+    LegalName,
+    #[serde(rename = "display_name")]
+    DisplayName,
+    #[serde(rename = "email")]
+    Email,
+    #[serde(rename = "web")]
+    Web,
+    #[serde(rename = "twitter")]
+    Twitter,
+    #[serde(rename = "matrix")]
+    Matrix,
+    #[serde(rename = "pgpFingerprint")]
+    PGPFingerprint,
+    #[serde(rename = "image")]
+    Image,
+    #[serde(rename = "additional")]
+    Additional,
+}
+
+impl From<(AccountType, String)> for IdentityFieldValue {
+    fn from(val: (AccountType, String)) -> Self {
+        let (ty, value) = val;
+
+        match ty {
+            AccountType::LegalName => IdentityFieldValue::LegalName(value),
+            AccountType::DisplayName => IdentityFieldValue::DisplayName(value),
+            AccountType::Email => IdentityFieldValue::Email(value),
+            AccountType::Web => IdentityFieldValue::Web(value),
+            AccountType::Twitter => IdentityFieldValue::Twitter(value.to_lowercase()),
+            AccountType::Matrix => IdentityFieldValue::Matrix(value),
+            AccountType::PGPFingerprint => IdentityFieldValue::PGPFingerprint(()),
+            AccountType::Image => IdentityFieldValue::Image(()),
+            AccountType::Additional => IdentityFieldValue::Additional(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    // Payloads as sent by the Watcher.
+    const NEW_JUDGEMENT_REQUEST: &str = include_str!("fixtures/new_judgement_request.json");
+    const PENDING_JUDGEMENTS_RESPONSE: &str =
+        include_str!("fixtures/pending_judgements_response.json");
+    const DISPLAY_NAMES_RESPONSE: &str = include_str!("fixtures/display_names_response.json");
+    const ACK: &str = include_str!("fixtures/ack.json");
+    const ACK_LEGACY: &str = include_str!("fixtures/ack_legacy.json");
+    const UNKNOWN_EVENT: &str = include_str!("fixtures/unknown_event.json");
+
+    fn parse<T: serde::de::DeserializeOwned>(payload: &str) -> (EventType, T) {
+        let msg: ResponseMessage<Value> = serde_json::from_str(payload).unwrap();
+        (msg.event, serde_json::from_value(msg.data).unwrap())
+    }
+
+    // Serializing the parsed message again must not lose any known field.
+    fn assert_round_trip<T>(event: EventType, data: &T)
+    where
+        T: Serialize + serde::de::DeserializeOwned + std::fmt::Debug,
+    {
+        let serialized = serde_json::to_string(&ResponseMessage {
+            event: event.clone(),
+            data,
+        })
+        .unwrap();
+        let (parsed_event, parsed): (_, T) = parse(&serialized);
+
+        assert_eq!(parsed_event, event);
+        assert_eq!(
+            serde_json::to_value(&parsed).unwrap(),
+            serde_json::to_value(data).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_new_judgement_request() {
+        let (event, req): (_, JudgementRequest) = parse(NEW_JUDGEMENT_REQUEST);
+
+        assert_eq!(event, EventType::NewJudgementRequest);
+        assert_eq!(
+            req.address.as_str(),
+            "1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP"
+        );
+        assert_eq!(req.block_number, Some(19283746));
+        assert_eq!(req.network.as_deref(), Some("polkadot"));
+        // The unsupported `github` account is skipped.
+        assert_eq!(
+            req.accounts,
+            HashMap::from([
+                (AccountType::DisplayName, "Alice".to_string()),
+                (AccountType::LegalName, "Alice Doe".to_string()),
+                (AccountType::Email, "alice@email.com".to_string()),
+                (AccountType::Twitter, "@alice".to_string()),
+                (AccountType::Matrix, "@alice:matrix.org".to_string()),
+                (AccountType::Web, "alice.com".to_string()),
+            ])
+        );
+
+        assert_round_trip(event, &req);
+    }
+
+    #[test]
+    fn parse_pending_judgements_response() {
+        let (event, reqs): (_, Vec<JudgementRequest>) = parse(PENDING_JUDGEMENTS_RESPONSE);
+
+        assert_eq!(event, EventType::PendingJudgementsResponse);
+        assert_eq!(reqs.len(), 2);
+        assert_eq!(reqs[0].block_number, None);
+        assert_eq!(reqs[0].network, None);
+        assert_eq!(reqs[1].accounts.len(), 1);
+        assert_eq!(reqs[1].block_number, Some(19283801));
+
+        assert_round_trip(event, &reqs);
+    }
+
+    #[test]
+    fn parse_display_names_response() {
+        let (event, mut entries): (_, Vec<DisplayNameEntryRaw>) = parse(DISPLAY_NAMES_RESPONSE);
+
+        assert_eq!(event, EventType::DisplayNamesResponse);
+        assert_eq!(entries.len(), 2);
+        assert_round_trip(event, &entries);
+
+        entries.iter_mut().for_each(|entry| entry.try_decode_hex());
+        assert_eq!(entries[0].display_name.as_str(), "Alice");
+        assert_eq!(entries[1].display_name.as_str(), "Bob \u{1f600}");
+    }
+
+    #[test]
+    fn parse_acks() {
+        let (event, ack): (_, AckResponse) = parse(ACK);
+        assert_eq!(event, EventType::Ack);
+        assert_eq!(ack.result, "judgement given");
+        assert_eq!(ack.correlation_id.as_deref(), Some("9f8c6d1be0a14e6f"));
+        assert_round_trip(event, &ack);
+
+        // Older Watcher versions neither send an address nor a correlation ID.
+        let (event, ack): (_, AckResponse) = parse(ACK_LEGACY);
+        assert_eq!(event, EventType::Ack);
+        assert!(ack.address.is_none());
+        assert!(ack.correlation_id.is_none());
+        assert_round_trip(event, &ack);
+    }
+
+    #[test]
+    fn parse_unknown_event() {
+        let msg: ResponseMessage<Value> = serde_json::from_str(UNKNOWN_EVENT).unwrap();
+        assert_eq!(msg.event, EventType::Unknown);
+    }
+
+    #[test]
+    fn serialize_outgoing_messages() {
+        let address = ChainAddress::from("1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP");
+
+        assert_eq!(
+            serde_json::to_value(&ResponseMessage {
+                event: EventType::JudgementResult,
+                data: JudgementResponse {
+                    address: address.clone(),
+                    judgement: Judgement::Reasonable,
+                    verified: vec![VerifiedEntry {
+                        account_ty: AccountType::Email,
+                        value: "alice@email.com".to_string(),
+                    }],
+                    correlation_id: "9f8c6d1be0a14e6f".to_string(),
+                },
+            })
+            .unwrap(),
+            serde_json::json!({
+                "event": "judgementResult",
+                "data": {
+                    "address": "1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP",
+                    "judgement": "reasonable",
+                    "verified": [{ "accountTy": "email", "value": "alice@email.com" }],
+                    "correlationId": "9f8c6d1be0a14e6f",
+                },
+            })
+        );
+
+        assert_eq!(
+            serde_json::to_value(&ResponseMessage {
+                event: EventType::ManualReviewRequired,
+                data: ManualReviewResponse {
+                    address: address.clone(),
+                    reason: "display name".to_string(),
+                    fields: vec![AccountType::DisplayName],
+                },
+            })
+            .unwrap(),
+            serde_json::json!({
+                "event": "manualReviewRequired",
+                "data": {
+                    "address": "1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP",
+                    "reason": "display name",
+                    "fields": ["display_name"],
+                },
+            })
+        );
+
+        assert_eq!(
+            serde_json::to_value(&ResponseMessage {
+                event: EventType::JudgementRequestRejected,
+                data: RejectedResponse {
+                    address,
+                    network: "westend".to_string(),
+                    reason: "unsupported chain".to_string(),
+                },
+            })
+            .unwrap(),
+            serde_json::json!({
+                "event": "judgementRequestRejected",
+                "data": {
+                    "address": "1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP",
+                    "network": "westend",
+                    "reason": "unsupported chain",
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn serialize_judgement_types() {
+        for (ty, expected) in [
+            (JudgementType::Reasonable, "reasonable"),
+            (JudgementType::KnownGood, "knownGood"),
+            (JudgementType::LowQuality, "lowQuality"),
+        ] {
+            assert_eq!(
+                serde_json::to_value(Judgement::from(ty)).unwrap(),
+                serde_json::json!(expected)
+            );
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use crate::db_health::DbHealth;
+use crate::metrics;
+use bson::{Bson, Document};
+use mongodb::event::command::{
+    CommandEventHandler, CommandFailedEvent, CommandStartedEvent, CommandSucceededEvent,
+};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// In milliseconds
+const DEFAULT_THRESHOLD: u64 = 500;
+// Long polls of change streams, which are slow by design.
+const LONG_POLL_COMMAND: &str = "getMore";
+
+/// Times every command sent to MongoDB, including transaction commits, and
+/// logs the commands exceeding the threshold. Values of the logged commands
+/// are redacted, only the structure (e.g. the filtered fields) is kept.
+#[derive(Debug)]
+pub struct QueryLog {
+    // In milliseconds.
+    threshold: AtomicU64,
+    // Redacted commands which did not complete yet, by request ID.
+    pending: Mutex<HashMap<i32, Document>>,
+    health: DbHealth,
+}
+
+impl Default for QueryLog {
+    fn default() -> Self {
+        QueryLog {
+            threshold: AtomicU64::new(DEFAULT_THRESHOLD),
+            pending: Mutex::new(HashMap::new()),
+            health: DbHealth::default(),
+        }
+    }
+}
+
+impl QueryLog {
+    pub fn set_threshold(&self, threshold: Duration) {
+        self.threshold
+            .store(threshold.as_millis() as u64, Ordering::Relaxed);
+    }
+    pub fn health(&self) -> &DbHealth {
+        &self.health
+    }
+    /// Number of commands which did not complete yet, excluding long polls.
+    pub fn pending_commands(&self) -> usize {
+        self.pending
+            .lock()
+            .expect("query log lock poisoned")
+            .values()
+            .filter(|command| {
+                command.keys().next().map(|name| name.as_str()) != Some(LONG_POLL_COMMAND)
+            })
+            .count()
+    }
+    fn complete(&self, request_id: i32, name: &str, duration: Duration, failed: bool) {
+        let command = self
+            .pending
+            .lock()
+            .expect("query log lock poisoned")
+            .remove(&request_id);
+
+        let threshold = Duration::from_millis(self.threshold.load(Ordering::Relaxed));
+        let slow = duration >= threshold;
+        metrics::observe_db_command(name, duration, slow, failed);
+
+        if name != LONG_POLL_COMMAND {
+            self.health.record(!slow && !failed);
+        }
+
+        if slow {
+            warn!(
+                "Slow MongoDB command '{}' took {} ms{}: {}",
+                name,
+                duration.as_millis(),
+                if failed { " and failed" } else { "" },
+                command
+                    .map(|command| command.to_string())
+                    .unwrap_or_else(|| "<unknown>".to_string())
+            );
+        }
+    }
+}
+
+impl CommandEventHandler for QueryLog {
+    fn handle_command_started_event(&self, event: CommandStartedEvent) {
+        self.pending
+            .lock()
+            .expect("query log lock poisoned")
+            .insert(event.request_id, redact_command(&event.command));
+    }
+    fn handle_command_succeeded_event(&self, event: CommandSucceededEvent) {
+        self.complete(event.request_id, &event.command_name, event.duration, false);
+    }
+    fn handle_command_failed_event(&self, event: CommandFailedEvent) {
+        self.complete(event.request_id, &event.command_name, event.duration, true);
+    }
+}
+
+/// Replaces all values of the command with `"?"`, except the first one which
+/// names the collection (e.g. `{"find": "identities"}`). Session and cluster
+/// metadata added by the driver is removed.
+fn redact_command(command: &Document) -> Document {
+    let mut redacted = Document::new();
+
+    for (index, (key, value)) in command.iter().enumerate() {
+        if key.starts_with('$') || key == "lsid" {
+            continue;
+        }
+
+        let value = if index == 0 {
+            value.clone()
+        } else {
+            redact_value(value)
+        };
+
+        redacted.insert(key, value);
+    }
+
+    redacted
+}
+
+fn redact_value(value: &Bson) -> Bson {
+    match value {
+        Bson::Document(doc) => Bson::Document(
+            doc.iter()
+                .map(|(key, value)| (key.clone(), redact_value(value)))
+                .collect(),
+        ),
+        Bson::Array(values) => Bson::Array(values.iter().map(redact_value).collect()),
+        _ => Bson::String("?".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::doc;
+
+    #[test]
+    fn redact_commands() {
+        let command = doc! {
+            "find": "identities",
+            "filter": {
+                "context": {
+                    "address": "1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZP",
+                    "chain": "polkadot",
+                },
+                "fields.value.value": { "$in": ["alice@email.com", "@alice"] },
+            },
+            "limit": 20,
+            "lsid": { "id": 1 },
+            "$clusterTime": { "clusterTime": 1 },
+            "$db": "registrar",
+        };
+
+        assert_eq!(
+            redact_command(&command),
+            doc! {
+                "find": "identities",
+                "filter": {
+                    "context": {
+                        "address": "?",
+                        "chain": "?",
+                    },
+                    "fields.value.value": { "$in": ["?", "?"] },
+                },
+                "limit": "?",
+            }
+        );
+
+        assert_eq!(
+            redact_command(&doc! { "commitTransaction": 1, "txnNumber": 5_i64 }),
+            doc! { "commitTransaction": 1, "txnNumber": "?" }
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use crate::adapters::matrix::MatrixHandle;
+use crate::display_name_normalization::{confusable_skeleton, normalize_display_name};
+use crate::primitives::Timestamp;
+use regex::{Regex, RegexBuilder};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReservedKind {
+    // Matches display names which are equal or confusable with the name.
+    Name,
+    // Regular expression matched against the normalized display name.
+    Pattern,
+}
+
+/// A name or pattern reserved by an admin.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ReservedDisplayName {
+    pub kind: ReservedKind,
+    pub value: String,
+    pub reserved_by: MatrixHandle,
+    pub timestamp: Timestamp,
+}
+
+/// Display names which always fail the display name check, regardless of the
+/// similarity to existing names, e.g. the names of the registrar or of
+/// exchanges.
+#[derive(Debug, Clone, Default)]
+pub struct ReservedNames {
+    // The reserved name with its normalized form and confusable skeleton.
+    names: Vec<(String, String, String)>,
+    patterns: Vec<Regex>,
+}
+
+impl ReservedNames {
+    /// The list of the config. Invalid patterns are logged and skipped.
+    pub fn from_config(names: &[String], patterns: &[String]) -> Self {
+        let mut reserved = ReservedNames::default();
+        for name in names {
+            reserved.add_name(name);
+        }
+
+        for pattern in patterns {
+            if let Err(err) = reserved.add_pattern(pattern) {
+                error!("Ignoring invalid reserved display name pattern: {:?}", err);
+            }
+        }
+
+        reserved
+    }
+    /// Adds a name or pattern reserved by an admin.
+    pub fn add(&mut self, entry: &ReservedDisplayName) {
+        match entry.kind {
+            ReservedKind::Name => self.add_name(&entry.value),
+            ReservedKind::Pattern => {
+                if let Err(err) = self.add_pattern(&entry.value) {
+                    error!("Ignoring invalid reserved display name pattern: {:?}", err);
+                }
+            }
+        }
+    }
+    pub fn add_name(&mut self, name: &str) {
+        let normalized = normalize_display_name(name);
+        if normalized.is_empty() {
+            return;
+        }
+
+        self.names
+            .push((name.to_string(), normalized, confusable_skeleton(name)));
+    }
+    pub fn add_pattern(&mut self, pattern: &str) -> Result<(), regex::Error> {
+        self.patterns.push(compile_pattern(pattern)?);
+        Ok(())
+    }
+    /// Returns the reserved name or pattern matched by the display name.
+    pub fn matches(&self, name: &str) -> Option<String> {
+        let normalized = normalize_display_name(name);
+        let skeleton = confusable_skeleton(name);
+
+        self.names
+            .iter()
+            .find(|(_, reserved, reserved_skeleton)| {
+                &normalized == reserved || (!skeleton.is_empty() && &skeleton == reserved_skeleton)
+            })
+            .map(|(name, _, _)| name.clone())
+            .or_else(|| {
+                self.patterns
+                    .iter()
+                    .find(|pattern| pattern.is_match(&normalized))
+                    .map(|pattern| pattern.as_str().to_string())
+            })
+    }
+}
+
+/// Patterns are matched case-insensitively.
+pub fn compile_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(true).build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reserved() -> ReservedNames {
+        ReservedNames::from_config(
+            &["Web3 Foundation".to_string(), "Polkadot".to_string()],
+            &[r"^(binance|kraken)\b".to_string(), "(".to_string()],
+        )
+    }
+
+    #[test]
+    fn reserved_names() {
+        let reserved = reserved();
+
+        assert_eq!(reserved.matches("Polkadot"), Some("Polkadot".to_string()));
+        assert_eq!(
+            reserved.matches("  web3   FOUNDATION "),
+            Some("Web3 Foundation".to_string())
+        );
+        // Lookalikes of reserved names.
+        assert_eq!(
+            reserved.matches("P\u{043E}lkad0t"),
+            Some("Polkadot".to_string())
+        );
+        assert_eq!(
+            reserved.matches("Web3-Foundation"),
+            Some("Web3 Foundation".to_string())
+        );
+
+        // Only equal names are reserved, similar ones are left to the
+        // similarity check.
+        assert_eq!(reserved.matches("Polkadot Fan"), None);
+        assert_eq!(reserved.matches("Alice"), None);
+    }
+
+    #[test]
+    fn reserved_patterns() {
+        let reserved = reserved();
+
+        assert_eq!(
+            reserved.matches("Binance Support"),
+            Some(r"^(binance|kraken)\b".to_string())
+        );
+        assert_eq!(
+            reserved.matches("KRAKEN"),
+            Some(r"^(binance|kraken)\b".to_string())
+        );
+        // Matched against the normalized name.
+        assert_eq!(
+            reserved.matches("Bin\u{0430}nce"),
+            Some(r"^(binance|kraken)\b".to_string())
+        );
+        assert_eq!(reserved.matches("Not Binance"), None);
+        assert_eq!(reserved.matches("Binanceville"), None);
+
+        // The invalid pattern was skipped.
+        assert!(compile_pattern("(").is_err());
+        assert_eq!(reserved.patterns.len(), 1);
+    }
+}
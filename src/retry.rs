@@ -0,0 +1,315 @@
+use crate::metrics;
+use crate::Result;
+use schemars::JsonSchema;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::sleep;
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct RetryConfig {
+    // Maximum number of attempts of a single call, including the first one.
+    pub max_attempts: usize,
+    // Delay before the first retry in seconds, doubled on each further attempt.
+    pub initial_delay: u64,
+    // Upper limit of the delay between retries in seconds.
+    pub max_delay: u64,
+    // Consecutive failures after which the circuit opens and calls are skipped.
+    pub failure_threshold: usize,
+    // Time in seconds the circuit stays open before calls are allowed again.
+    pub cool_down: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_attempts: 3,
+            initial_delay: 1,
+            max_delay: 60,
+            failure_threshold: 5,
+            cool_down: 60,
+        }
+    }
+}
+
+/// Exponential backoff, doubling the delay up to a maximum.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    current: Duration,
+    max: Duration,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Backoff {
+            current: initial,
+            max: max.max(initial),
+        }
+    }
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = (self.current * 2).min(self.max);
+        delay
+    }
+}
+
+impl From<&RetryConfig> for Backoff {
+    fn from(config: &RetryConfig) -> Self {
+        Backoff::new(
+            Duration::from_secs(config.initial_delay),
+            Duration::from_secs(config.max_delay),
+        )
+    }
+}
+
+/// Executes the call and retries it with an exponential backoff on failure, up
+/// to `max_attempts`. Returns the last error if all attempts failed. Retries
+/// are counted per `service`, while `name` describes the call in the logs.
+pub async fn retry<T, F, Fut>(
+    config: &RetryConfig,
+    service: &str,
+    name: &str,
+    mut call: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut backoff = Backoff::from(config);
+    let mut attempt = 1;
+
+    loop {
+        match call().await {
+            Ok(val) => return Ok(val),
+            Err(err) if attempt >= config.max_attempts => return Err(err),
+            Err(err) => {
+                let delay = backoff.next_delay();
+                warn!(
+                    "{} failed (attempt {}/{}), retrying in {:?}: {:?}",
+                    name, attempt, config.max_attempts, delay, err
+                );
+                metrics::observe_retry(service);
+
+                sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Returned by `CircuitBreaker::call` if the call was skipped.
+#[derive(Debug, thiserror::Error)]
+#[error("circuit of {0} is open, skipping call")]
+pub struct CircuitOpen(String);
+
+#[derive(Debug, Default)]
+pub struct CircuitStats {
+    pub successes: AtomicUsize,
+    pub failures: AtomicUsize,
+    // Calls that were skipped because the circuit was open.
+    pub rejections: AtomicUsize,
+    // How often the circuit was opened.
+    pub opened: AtomicUsize,
+}
+
+#[derive(Debug, Default)]
+struct CircuitState {
+    consecutive_failures: usize,
+    open_until: Option<Instant>,
+}
+
+/// Stops calling an external service after too many consecutive failures and
+/// only tries again after a cool-down period. If that trial call fails, the
+/// circuit opens again immediately.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    name: String,
+    failure_threshold: usize,
+    cool_down: Duration,
+    state: Arc<Mutex<CircuitState>>,
+    stats: Arc<CircuitStats>,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: &str, config: &RetryConfig) -> Self {
+        CircuitBreaker {
+            name: name.to_string(),
+            failure_threshold: config.failure_threshold.max(1),
+            cool_down: Duration::from_secs(config.cool_down),
+            state: Default::default(),
+            stats: Default::default(),
+        }
+    }
+    pub fn stats(&self) -> &CircuitStats {
+        &self.stats
+    }
+    pub fn is_open(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        match state.open_until {
+            Some(until) if Instant::now() < until => true,
+            Some(_) => {
+                // Cool-down elapsed, allow a trial call. A single failure opens
+                // the circuit again.
+                state.open_until = None;
+                state.consecutive_failures = self.failure_threshold - 1;
+                metrics::set_circuit_open(&self.name, false);
+                false
+            }
+            None => false,
+        }
+    }
+    pub async fn call<T, Fut>(&self, call: Fut) -> Result<T>
+    where
+        Fut: Future<Output = Result<T>>,
+    {
+        if self.is_open() {
+            self.stats.rejections.fetch_add(1, Ordering::Relaxed);
+            metrics::observe_circuit_rejection(&self.name);
+            return Err(CircuitOpen(self.name.clone()).into());
+        }
+
+        match call.await {
+            Ok(val) => {
+                self.record_success();
+                Ok(val)
+            }
+            Err(err) => {
+                self.record_failure();
+                Err(err)
+            }
+        }
+    }
+    fn record_success(&self) {
+        self.stats.successes.fetch_add(1, Ordering::Relaxed);
+        self.state.lock().unwrap().consecutive_failures = 0;
+    }
+    fn record_failure(&self) {
+        self.stats.failures.fetch_add(1, Ordering::Relaxed);
+
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures += 1;
+
+        if state.consecutive_failures >= self.failure_threshold {
+            warn!(
+                "Opening circuit of {} after {} consecutive failures, pausing calls for {:?}",
+                self.name, state.consecutive_failures, self.cool_down
+            );
+
+            state.open_until = Some(Instant::now() + self.cool_down);
+            self.stats.opened.fetch_add(1, Ordering::Relaxed);
+            metrics::observe_circuit_opened(&self.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(max_attempts: usize, failure_threshold: usize, cool_down: u64) -> RetryConfig {
+        RetryConfig {
+            max_attempts,
+            initial_delay: 0,
+            max_delay: 0,
+            failure_threshold,
+            cool_down,
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(5));
+
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(2));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(4));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(5));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn retry_until_success() {
+        let attempts = AtomicUsize::new(0);
+
+        let res = retry(&config(3, 1, 0), "test", "test", || async {
+            if attempts.fetch_add(1, Ordering::Relaxed) < 2 {
+                Err(anyhow!("failed"))
+            } else {
+                Ok(())
+            }
+        })
+        .await;
+
+        assert!(res.is_ok());
+        assert_eq!(attempts.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_gives_up() {
+        let attempts = AtomicUsize::new(0);
+
+        let res: Result<()> = retry(&config(3, 1, 0), "test", "test", || async {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            Err(anyhow!("failed"))
+        })
+        .await;
+
+        assert!(res.is_err());
+        assert_eq!(attempts.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn circuit_opens_after_threshold() {
+        let breaker = CircuitBreaker::new("test", &config(1, 2, 60));
+
+        for _ in 0..2 {
+            let res: Result<()> = breaker.call(async { Err(anyhow!("failed")) }).await;
+            assert!(!res.unwrap_err().is::<CircuitOpen>());
+        }
+
+        // Circuit is now open, the call is skipped.
+        assert!(breaker.is_open());
+        let res = breaker.call(async { Ok(()) }).await;
+        assert!(res.unwrap_err().is::<CircuitOpen>());
+
+        let stats = breaker.stats();
+        assert_eq!(stats.successes.load(Ordering::Relaxed), 0);
+        assert_eq!(stats.failures.load(Ordering::Relaxed), 2);
+        assert_eq!(stats.rejections.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.opened.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn circuit_success_resets_failures() {
+        let breaker = CircuitBreaker::new("test", &config(1, 2, 60));
+
+        let _: Result<()> = breaker.call(async { Err(anyhow!("failed")) }).await;
+        breaker.call(async { Ok(()) }).await.unwrap();
+        let _: Result<()> = breaker.call(async { Err(anyhow!("failed")) }).await;
+
+        assert!(!breaker.is_open());
+    }
+
+    #[tokio::test]
+    async fn circuit_half_open_after_cool_down() {
+        let breaker = CircuitBreaker::new("test", &config(1, 2, 0));
+
+        for _ in 0..2 {
+            let _: Result<()> = breaker.call(async { Err(anyhow!("failed")) }).await;
+        }
+
+        // Cool-down of zero seconds, a trial call is allowed right away.
+        breaker.call(async { Ok(()) }).await.unwrap();
+
+        // The successful trial call closed the circuit, so a single failure
+        // does not open it again, only reaching the threshold does.
+        let _: Result<()> = breaker.call(async { Err(anyhow!("failed")) }).await;
+        assert_eq!(breaker.stats().opened.load(Ordering::Relaxed), 1);
+
+        let _: Result<()> = breaker.call(async { Err(anyhow!("failed")) }).await;
+        assert_eq!(breaker.stats().opened.load(Ordering::Relaxed), 2);
+    }
+}
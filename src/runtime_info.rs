@@ -0,0 +1,106 @@
+use crate::primitives::{ChainName, Timestamp};
+use crate::{AdapterConfig, InstanceType};
+use std::fmt;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const GIT_HASH: &str = env!("VERGEN_GIT_SHA");
+pub const BUILD_TIMESTAMP: &str = env!("VERGEN_BUILD_TIMESTAMP");
+
+/// Build and configuration details of the running instance, logged at startup
+/// and exposed via `/api/version`.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct RuntimeInfo {
+    pub version: String,
+    pub git_hash: String,
+    pub build_timestamp: String,
+    pub instance: String,
+    pub adapters: Vec<String>,
+    pub chains: Vec<ChainName>,
+    pub started_at: Timestamp,
+}
+
+impl RuntimeInfo {
+    fn new(instance: &str, adapter: Option<&AdapterConfig>) -> Self {
+        let (adapters, chains) = adapter
+            .map(|config| {
+                let adapters = [
+                    ("matrix", config.matrix.enabled),
+                    ("twitter", config.twitter.enabled),
+                    ("email", config.email.enabled),
+                    (
+                        "web",
+                        config.web.as_ref().map(|web| web.enabled).unwrap_or(false),
+                    ),
+                ]
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(name, _)| name.to_string())
+                .collect();
+
+                let chains = config
+                    .watcher
+                    .iter()
+                    .map(|watcher| watcher.network)
+                    .collect();
+
+                (adapters, chains)
+            })
+            .unwrap_or_default();
+
+        RuntimeInfo {
+            version: VERSION.to_string(),
+            git_hash: GIT_HASH.to_string(),
+            build_timestamp: BUILD_TIMESTAMP.to_string(),
+            instance: instance.to_string(),
+            adapters,
+            chains,
+            started_at: Timestamp::now(),
+        }
+    }
+    pub(crate) fn from_instance(instance: &InstanceType) -> Self {
+        match instance {
+            InstanceType::AdapterListener(config) => {
+                Self::new("adapter_listener", config.adapter.as_ref())
+            }
+            InstanceType::SessionNotifier(config) => {
+                Self::new("session_notifier", config.adapter.as_ref())
+            }
+            InstanceType::SingleInstance(config) => {
+                Self::new("single_instance", config.adapter.as_ref())
+            }
+        }
+    }
+    /// Instance embedded in another binary via the library entry points.
+    pub(crate) fn embedded() -> Self {
+        Self::new("embedded", None)
+    }
+    /// Uptime in seconds.
+    pub fn uptime(&self) -> u64 {
+        Timestamp::now().raw().saturating_sub(self.started_at.raw())
+    }
+}
+
+impl fmt::Display for RuntimeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let chains: Vec<&str> = self.chains.iter().map(|chain| chain.as_str()).collect();
+
+        write!(
+            f,
+            "registrar v{} ({}, built {}), instance: {}, adapters: [{}], chains: [{}]",
+            self.version,
+            self.git_hash,
+            self.build_timestamp,
+            self.instance,
+            self.adapters.join(", "),
+            chains.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+impl RuntimeInfo {
+    pub fn test() -> Self {
+        Self::new("single_instance", None)
+    }
+}
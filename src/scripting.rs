@@ -0,0 +1,275 @@
+use crate::primitives::{IdentityContext, IdentityFieldValue, JudgementState, MessagePart};
+use crate::Result;
+use rhai::serde::{from_dynamic, to_dynamic};
+use rhai::{Dynamic, Engine, Scope, AST};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::sync::Arc;
+
+// Protects the service from scripts which never terminate.
+const DEFAULT_MAX_OPERATIONS: u64 = 100_000;
+
+const ON_MESSAGE_FN: &str = "on_message";
+const ON_JUDGEMENT_FN: &str = "on_judgement";
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ScriptHookConfig {
+    // Path to the Rhai script.
+    pub path: String,
+    // Maximum number of operations per invocation.
+    pub max_operations: Option<u64>,
+}
+
+/// The decision of a script. A vetoed field (or identity) is not verified and
+/// is flagged for manual review instead.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct HookOutcome {
+    #[serde(default)]
+    pub veto: bool,
+    #[serde(default)]
+    pub reason: Option<String>,
+    // Notes which are attached to the identity for admins.
+    #[serde(default)]
+    pub notes: Vec<String>,
+}
+
+impl HookOutcome {
+    fn vetoed(reason: String) -> Self {
+        HookOutcome {
+            veto: true,
+            reason: Some(reason),
+            notes: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MessageInput<'a> {
+    context: &'a IdentityContext,
+    field: &'a IdentityFieldValue,
+    message: &'a [MessagePart],
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct JudgementInput<'a> {
+    context: &'a IdentityContext,
+    fields: Vec<&'a IdentityFieldValue>,
+}
+
+/// Operator provided Rhai script which is invoked whenever a message verified
+/// a field (`on_message`) and before an identity is marked as fully verified
+/// (`on_judgement`). Both functions are optional. A function can return `()`
+/// or `true` to allow, `false` or a string (the reason) to veto, or a map with
+/// the `veto`, `reason` and `notes` keys.
+#[derive(Clone)]
+pub struct ScriptHook {
+    engine: Arc<Engine>,
+    ast: Arc<AST>,
+}
+
+impl std::fmt::Debug for ScriptHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let functions: Vec<String> = self
+            .ast
+            .iter_functions()
+            .map(|func| func.name.to_string())
+            .collect();
+
+        f.debug_struct("ScriptHook")
+            .field("functions", &functions)
+            .finish()
+    }
+}
+
+impl ScriptHook {
+    pub fn new(config: ScriptHookConfig) -> Result<Self> {
+        let script = std::fs::read_to_string(&config.path)
+            .map_err(|err| anyhow!("failed to read script at '{}': {:?}", config.path, err))?;
+
+        Self::from_script(
+            &script,
+            config.max_operations.unwrap_or(DEFAULT_MAX_OPERATIONS),
+        )
+    }
+    pub fn from_script(script: &str, max_operations: u64) -> Result<Self> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(max_operations);
+
+        let ast = engine
+            .compile(script)
+            .map_err(|err| anyhow!("failed to compile script: {}", err))?;
+
+        Ok(ScriptHook {
+            engine: Arc::new(engine),
+            ast: Arc::new(ast),
+        })
+    }
+    pub fn on_message(
+        &self,
+        context: &IdentityContext,
+        field: &IdentityFieldValue,
+        message: &[MessagePart],
+    ) -> HookOutcome {
+        self.call(
+            ON_MESSAGE_FN,
+            &MessageInput {
+                context,
+                field,
+                message,
+            },
+        )
+    }
+    pub fn on_judgement(&self, state: &JudgementState) -> HookOutcome {
+        self.call(
+            ON_JUDGEMENT_FN,
+            &JudgementInput {
+                context: &state.context,
+                fields: state.fields.iter().map(|field| &field.value).collect(),
+            },
+        )
+    }
+    /// Invokes the given function, if it is defined. Failing scripts veto the
+    /// verification, so identities are never verified by accident.
+    fn call<T: Serialize>(&self, name: &str, input: &T) -> HookOutcome {
+        if !self.ast.iter_functions().any(|func| func.name == name) {
+            return HookOutcome::default();
+        }
+
+        let result = to_dynamic(input)
+            .and_then(|input| {
+                self.engine
+                    .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, name, (input,))
+            })
+            .map_err(|err| anyhow!("{}", err))
+            .and_then(parse_outcome);
+
+        match result {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                error!("Script hook '{}' failed: {:?}", name, err);
+                HookOutcome::vetoed(format!("script hook '{}' failed", name))
+            }
+        }
+    }
+}
+
+fn parse_outcome(value: Dynamic) -> Result<HookOutcome> {
+    if value.is_unit() {
+        Ok(HookOutcome::default())
+    } else if let Ok(allow) = value.as_bool() {
+        Ok(HookOutcome {
+            veto: !allow,
+            ..Default::default()
+        })
+    } else if value.is_string() {
+        Ok(HookOutcome::vetoed(value.into_string().unwrap_or_default()))
+    } else if value.is_map() {
+        from_dynamic::<HookOutcome>(&value).map_err(|err| anyhow!("invalid outcome: {}", err))
+    } else {
+        Err(anyhow!("unsupported return type '{}'", value.type_name()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(script: &str) -> ScriptHook {
+        ScriptHook::from_script(script, DEFAULT_MAX_OPERATIONS).unwrap()
+    }
+
+    fn on_message(hook: &ScriptHook, field: IdentityFieldValue) -> HookOutcome {
+        hook.on_message(
+            &IdentityContext::alice(),
+            &field,
+            &["challenge".to_string().into()],
+        )
+    }
+
+    #[test]
+    fn missing_functions_allow() {
+        let hook = compile("let x = 1;");
+
+        assert_eq!(
+            on_message(&hook, IdentityFieldValue::ALICE_EMAIL()),
+            HookOutcome::default()
+        );
+        assert_eq!(
+            hook.on_judgement(&JudgementState::alice()),
+            HookOutcome::default()
+        );
+    }
+
+    #[test]
+    fn veto_blocked_email_domain() {
+        let hook = compile(
+            r#"
+            fn on_message(input) {
+                if input.field.type == "email" && input.field.value.ends_with("@blocked.com") {
+                    return "blocked domain";
+                }
+                if input.message[0] != "challenge" {
+                    return false;
+                }
+                true
+            }
+            "#,
+        );
+
+        assert!(!on_message(&hook, IdentityFieldValue::ALICE_EMAIL()).veto);
+        assert!(!on_message(&hook, IdentityFieldValue::ALICE_TWITTER()).veto);
+        assert_eq!(
+            on_message(
+                &hook,
+                IdentityFieldValue::Email("alice@blocked.com".to_string())
+            ),
+            HookOutcome::vetoed("blocked domain".to_string())
+        );
+    }
+
+    #[test]
+    fn annotate_judgement() {
+        let hook = compile(
+            r#"
+            fn on_judgement(input) {
+                #{
+                    veto: input.context.chain == "kusama",
+                    notes: ["fields: " + input.fields.len()],
+                }
+            }
+            "#,
+        );
+
+        let mut state = JudgementState::alice();
+        assert_eq!(
+            hook.on_judgement(&state),
+            HookOutcome {
+                veto: false,
+                reason: None,
+                notes: vec!["fields: 4".to_string()],
+            }
+        );
+
+        state.context.chain = crate::primitives::ChainName::Kusama;
+        assert!(hook.on_judgement(&state).veto);
+    }
+
+    #[test]
+    fn failing_scripts_veto() {
+        // Runtime error.
+        let hook = compile("fn on_message(input) { input.unknown.field }");
+        assert!(on_message(&hook, IdentityFieldValue::ALICE_EMAIL()).veto);
+
+        // Unsupported return type.
+        let hook = compile("fn on_message(input) { 42 }");
+        assert!(on_message(&hook, IdentityFieldValue::ALICE_EMAIL()).veto);
+
+        // Endless loop.
+        let hook = compile("fn on_message(input) { loop {} }");
+        assert!(on_message(&hook, IdentityFieldValue::ALICE_EMAIL()).veto);
+
+        // Invalid syntax.
+        assert!(ScriptHook::from_script("fn on_message(", DEFAULT_MAX_OPERATIONS).is_err());
+    }
+}
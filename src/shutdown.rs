@@ -0,0 +1,113 @@
+use crate::adapters::AdaptersHandle;
+use crate::api::ApiHandle;
+use crate::connector::ConnectorHandle;
+use crate::database::Database;
+use crate::Result;
+use std::future::Future;
+use tokio::time::{timeout_at, Duration, Instant};
+
+// In seconds.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: u64 = 30;
+
+/// Waits until the process receives SIGTERM or SIGINT.
+#[cfg(unix)]
+pub async fn wait_for_signal() -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+
+    tokio::select! {
+        _ = terminate.recv() => info!("Received SIGTERM, shutting down"),
+        _ = interrupt.recv() => info!("Received SIGINT, shutting down"),
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub async fn wait_for_signal() -> Result<()> {
+    tokio::signal::ctrl_c().await?;
+    info!("Received Ctrl-C, shutting down");
+
+    Ok(())
+}
+
+/// Stops the started subsystems, so no message is only partially processed
+/// and clients are informed about the closed connections.
+pub struct ShutdownCoordinator {
+    db: Database,
+    timeout: Duration,
+    adapters: Option<AdaptersHandle>,
+    connector: Option<ConnectorHandle>,
+    api: Option<ApiHandle>,
+}
+
+impl ShutdownCoordinator {
+    pub fn new(db: Database, timeout: Duration) -> Self {
+        ShutdownCoordinator {
+            db,
+            timeout,
+            adapters: None,
+            connector: None,
+            api: None,
+        }
+    }
+    pub fn adapters(self, adapters: AdaptersHandle) -> Self {
+        ShutdownCoordinator {
+            adapters: Some(adapters),
+            ..self
+        }
+    }
+    pub fn connector(self, connector: ConnectorHandle) -> Self {
+        ShutdownCoordinator {
+            connector: Some(connector),
+            ..self
+        }
+    }
+    pub fn api(self, api: ApiHandle) -> Self {
+        ShutdownCoordinator {
+            api: Some(api),
+            ..self
+        }
+    }
+    /// Stops the adapters first, so no new messages are verified, then closes
+    /// the Watcher connections and the WS sessions of the API. Finally waits
+    /// for the database commands in progress. Subsystems which do not stop
+    /// within the timeout are abandoned.
+    pub async fn shutdown(self) {
+        let deadline = Instant::now() + self.timeout;
+
+        if let Some(adapters) = self.adapters {
+            stop_until("adapters", deadline, adapters.shutdown()).await;
+        }
+
+        if let Some(connector) = self.connector {
+            stop_until("Watcher connections", deadline, connector.shutdown()).await;
+        }
+
+        if let Some(api) = self.api {
+            stop_until("API server", deadline, api.shutdown()).await;
+        }
+
+        let pending = self
+            .db
+            .flush(deadline.saturating_duration_since(Instant::now()))
+            .await;
+        if pending > 0 {
+            warn!(
+                "{} database commands did not complete before shutdown",
+                pending
+            );
+        }
+
+        info!("Shutdown completed");
+    }
+}
+
+async fn stop_until<F: Future<Output = ()>>(name: &str, deadline: Instant, stop: F) {
+    info!("Stopping {}", name);
+    if timeout_at(deadline, stop).await.is_err() {
+        warn!("The {} did not stop in time", name);
+    }
+}
@@ -0,0 +1,68 @@
+use super::*;
+use crate::adapters::admin::{process_admin_with_approval, Command, Response};
+use crate::adapters::matrix::MatrixHandle;
+use crate::address_blocklist::BlocklistAction;
+
+#[actix::test]
+async fn blocked_address_is_never_challenged() {
+    let (db, mut connector, _api, _) = new_env().await;
+    let admin = MatrixHandle::from("@admin1:matrix.org");
+    let alice = IdentityContext::alice();
+
+    // Insert judgement request.
+    connector.inject(alice_judgement_request()).await;
+    sleep(Duration::from_millis(500)).await;
+    assert!(db.fetch_judgement_state(&alice).await.unwrap().is_some());
+
+    // Block the address, the pending state is removed.
+    let cmd = Command::Block(alice.clone(), Some("sanctioned".to_string()));
+    let resp = process_admin_with_approval(&db, cmd.clone(), &admin, None).await;
+    assert_eq!(resp, Response::AddressBlocked(alice.address.clone()));
+    assert!(db.fetch_judgement_state(&alice).await.unwrap().is_none());
+
+    let resp = process_admin_with_approval(&db, cmd, &admin, None).await;
+    assert_eq!(resp, Response::AddressAlreadyBlocked);
+
+    // The request is refused and the Watcher informed once.
+    connector.inject(alice_judgement_request()).await;
+    connector.inject(alice_judgement_request()).await;
+    sleep(Duration::from_millis(500)).await;
+    assert!(db.fetch_judgement_state(&alice).await.unwrap().is_none());
+
+    let (_out, counter) = connector.outgoing();
+    assert_eq!(counter.notify_blocked, 1);
+
+    let resp = process_admin_with_approval(&db, Command::Blocked, &admin, None).await;
+    match resp {
+        Response::BlockedAddresses(blocked) => {
+            assert_eq!(blocked.len(), 1);
+            assert_eq!(blocked[0].context, alice);
+            assert_eq!(blocked[0].reason, "sanctioned");
+            assert_eq!(blocked[0].blocked_by, admin);
+        }
+        _ => panic!(),
+    }
+
+    // After unblocking, the request is processed again.
+    let resp =
+        process_admin_with_approval(&db, Command::Unblock(alice.clone()), &admin, None).await;
+    assert_eq!(resp, Response::AddressUnblocked(alice.address.clone()));
+
+    connector.inject(alice_judgement_request()).await;
+    sleep(Duration::from_millis(500)).await;
+    assert!(db.fetch_judgement_state(&alice).await.unwrap().is_some());
+
+    // Both changes are recorded.
+    let resp =
+        process_admin_with_approval(&db, Command::BlocklistHistory(alice), &admin, None).await;
+    let audit = match resp {
+        Response::BlocklistHistory(audit) => audit,
+        _ => panic!(),
+    };
+    let actions: Vec<BlocklistAction> = audit.iter().map(|entry| entry.action).collect();
+    assert_eq!(
+        actions,
+        vec![BlocklistAction::Block, BlocklistAction::Unblock]
+    );
+    assert!(audit.iter().all(|entry| entry.admin == admin));
+}
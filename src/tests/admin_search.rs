@@ -0,0 +1,108 @@
+use super::*;
+use crate::adapters::admin::{process_admin, Command, Response, SearchResult};
+use crate::primitives::JudgementState;
+use actix_http::StatusCode;
+
+#[actix::test]
+async fn search_identities() {
+    let (db, _, _, _) = new_env().await;
+
+    let alice = JudgementState::alice();
+    let bob = JudgementState::new(
+        IdentityContext::bob(),
+        vec![F::Email("bob@email.com".to_string())],
+    );
+    db.add_judgement_request(&alice).await.unwrap();
+    db.add_judgement_request(&bob).await.unwrap();
+    let alice = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Partial address.
+    let resp = process_admin(&db, Command::Search("1A2YIGNU".to_string())).await;
+    assert_eq!(
+        resp,
+        Response::SearchResults(vec![SearchResult {
+            context: alice.context.clone(),
+            state: Some(alice.clone().into()),
+        }])
+    );
+
+    // Display name and email, the term is not treated as a pattern.
+    let resp = process_admin(&db, Command::Search("alic".to_string())).await;
+    assert!(matches!(resp, Response::SearchResults(results) if results.len() == 1));
+
+    let resp = process_admin(&db, Command::Search("@email.com".to_string())).await;
+    assert!(matches!(resp, Response::SearchResults(results) if results.len() == 2));
+
+    let resp = process_admin(&db, Command::Search(".*".to_string())).await;
+    assert_eq!(resp, Response::SearchResults(vec![]));
+
+    // Removed identities are found in the event log.
+    db.delete_judgement(&bob.context).await.unwrap();
+
+    let resp = process_admin(&db, Command::Search("1b3Nhs".to_string())).await;
+    assert_eq!(
+        resp,
+        Response::SearchResults(vec![SearchResult {
+            context: bob.context.clone(),
+            state: None,
+        }])
+    );
+}
+
+#[actix::test]
+async fn search_identities_rest() {
+    let (db, _, api, _) = new_env().await;
+
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+    let alice = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Requires the admin token.
+    let res = api
+        .get("/api/admin/search?term=Alice")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = api
+        .get("/api/admin/search?term=Alice")
+        .insert_header(("Authorization", "Bearer wrong"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+    let res = api
+        .get("/api/admin/search?term=Al")
+        .insert_header(("Authorization", "Bearer admin"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let mut res = api
+        .get("/api/admin/search?term=Alice")
+        .insert_header(("Authorization", "Bearer admin"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let resp: JsonResult<Vec<SearchResult>> = res.json().await.unwrap();
+    assert_eq!(
+        resp,
+        JsonResult::Ok(vec![SearchResult {
+            context: alice.context.clone(),
+            state: Some(alice.into()),
+        }])
+    );
+}
@@ -1,11 +1,14 @@
 use super::*;
+use crate::adapters::email::SecondChallengeCodeConfig;
 use crate::api::VerifyChallenge;
 use crate::api::{JsonResult, ResponseAccountState};
 use crate::connector::WatcherMessage;
 use crate::primitives::{
-    ExpectedMessage, ExternalMessage, ExternalMessageType, IdentityContext, MessageId,
-    NotificationMessage, Timestamp,
+    ChainName, ExpectedMessage, ExternalMessage, ExternalMessageType, FailureCause, FieldFailure,
+    IdentityContext, MessageId, NotificationMessage, Timestamp,
 };
+use crate::verification::{disambiguation_token, truncate_address};
+use actix_http::ws::CloseCode;
 use actix_http::StatusCode;
 use futures::{FutureExt, StreamExt};
 
@@ -230,17 +233,19 @@ async fn verify_invalid_message_bad_challenge() {
     );
 
     // Send invalid message (bad challenge).
-    injector
-        .send(ExternalMessage {
-            origin: ExternalMessageType::Email("alice@email.com".to_string()),
-            id: MessageId::from(0u32),
-            timestamp: Timestamp::now(),
-            values: ExpectedMessage::random().to_message_parts(),
-        })
-        .await;
+    let msg = ExternalMessage {
+        origin: ExternalMessageType::Email("alice@email.com".to_string()),
+        id: MessageId::from(0u32),
+        timestamp: Timestamp::now(),
+        values: ExpectedMessage::random().to_message_parts(),
+        metadata: None,
+    };
+    injector.send(msg.clone()).await;
 
     // The expected message (field verification failed).
     *alice.get_field_mut(&F::ALICE_EMAIL()).failed_attempts_mut() = 1;
+    *alice.get_field_mut(&F::ALICE_EMAIL()).last_failure_mut() =
+        Some(FieldFailure::new(FailureCause::WrongChallenge, &msg));
 
     let expected = ResponseAccountState {
         state: alice.clone().into(),
@@ -297,6 +302,7 @@ async fn verify_invalid_message_bad_origin() {
                 .get_field(&F::ALICE_EMAIL())
                 .expected_message()
                 .to_message_parts(),
+            metadata: None,
         })
         .await;
 
@@ -346,6 +352,7 @@ async fn verify_valid_message() {
                 .get_field(&F::ALICE_MATRIX())
                 .expected_message()
                 .to_message_parts(),
+            metadata: None,
         })
         .await;
 
@@ -411,42 +418,104 @@ async fn verify_valid_message_duplicate_account_name() {
         JsonResult::Ok(ResponseAccountState::with_no_notifications(alice.clone()))
     );
 
-    // Send valid message.
-    injector
-        .send(ExternalMessage {
-            origin: ExternalMessageType::Matrix("@alice:matrix.org".to_string()),
-            id: MessageId::from(0u32),
-            timestamp: Timestamp::now(),
-            values: alice
-                .get_field(&F::ALICE_MATRIX())
-                .expected_message()
-                .to_message_parts(),
+    let shared = NotificationMessage::ContactValueShared {
+        context: alice.context.clone(),
+        field: F::ALICE_MATRIX(),
+        shared_with: vec![truncate_address(&bob.context.address)],
+    };
+
+    // Send valid message, but without the disambiguation token.
+    let first = ExternalMessage {
+        origin: ExternalMessageType::Matrix("@alice:matrix.org".to_string()),
+        id: MessageId::from(0u32),
+        timestamp: Timestamp::now(),
+        values: alice
+            .get_field(&F::ALICE_MATRIX())
+            .expected_message()
+            .to_message_parts(),
+        metadata: None,
+    };
+    injector.send(first.clone()).await;
+
+    // The expected messages (disambiguation required, field stays unverified).
+    *alice.get_field_mut(&F::ALICE_MATRIX()).last_failure_mut() = Some(FieldFailure::new(
+        FailureCause::MissingDisambiguationToken,
+        &first,
+    ));
+    let token = disambiguation_token(&alice.context.address).to_string();
+    let resp: JsonResult<ResponseAccountState> = stream.next().await.into();
+    assert_eq!(
+        resp,
+        JsonResult::Ok(ResponseAccountState {
+            state: alice.clone().into(),
+            notifications: vec![NotificationMessage::DisambiguationRequired {
+                context: alice.context.clone(),
+                field: F::ALICE_MATRIX(),
+                token: token.clone(),
+            }],
         })
-        .await;
+    );
 
-    // Email account of Alice is now verified
+    let resp: JsonResult<ResponseAccountState> = stream.next().await.into();
+    assert_eq!(
+        resp,
+        JsonResult::Ok(ResponseAccountState {
+            state: alice.clone().into(),
+            notifications: vec![shared.clone()],
+        })
+    );
+
+    // Send valid message, including the disambiguation token.
+    let second = ExternalMessage {
+        origin: ExternalMessageType::Matrix("@alice:matrix.org".to_string()),
+        id: MessageId::from(1u32),
+        timestamp: Timestamp::now(),
+        values: vec![format!(
+            "{} {}",
+            token,
+            alice.get_field(&F::ALICE_MATRIX()).expected_message().value
+        )
+        .into()],
+        metadata: None,
+    };
+    injector.send(second.clone()).await;
+
+    // Matrix account of Alice is now verified
     alice
         .get_field_mut(&F::ALICE_MATRIX())
         .expected_message_mut()
         .set_verified();
+    *alice.get_field_mut(&F::ALICE_MATRIX()).last_failure_mut() = None;
 
-    // The expected message (field verified successfully).
-    let expected = ResponseAccountState {
-        state: alice.clone().into(),
-        notifications: vec![NotificationMessage::FieldVerified {
-            context: alice.context.clone(),
-            field: F::ALICE_MATRIX(),
-        }],
-    };
+    // The expected messages (field verified successfully).
+    let resp: JsonResult<ResponseAccountState> = stream.next().await.into();
+    assert_eq!(
+        resp,
+        JsonResult::Ok(ResponseAccountState {
+            state: alice.clone().into(),
+            notifications: vec![NotificationMessage::FieldVerified {
+                context: alice.context.clone(),
+                field: F::ALICE_MATRIX(),
+            }],
+        })
+    );
 
-    // Check response
     let resp: JsonResult<ResponseAccountState> = stream.next().await.into();
-    assert_eq!(resp, JsonResult::Ok(expected));
+    assert_eq!(
+        resp,
+        JsonResult::Ok(ResponseAccountState {
+            state: alice.clone().into(),
+            notifications: vec![shared],
+        })
+    );
 
-    // Other judgement states must be unaffected (Bob), but will receive a "failed attempt".
+    // Other judgement states must be unaffected (Bob), but will receive a
+    // "failed attempt" for each message.
     let resp = subscribe_context(&mut stream, IdentityContext::bob()).await;
 
-    *bob.get_field_mut(&F::ALICE_MATRIX()).failed_attempts_mut() = 1;
+    *bob.get_field_mut(&F::ALICE_MATRIX()).failed_attempts_mut() = 2;
+    *bob.get_field_mut(&F::ALICE_MATRIX()).last_failure_mut() =
+        Some(FieldFailure::new(FailureCause::WrongChallenge, &second));
 
     assert_eq!(
         resp,
@@ -488,6 +557,7 @@ async fn verify_valid_message_awaiting_second_challenge() {
                 .get_field(&F::ALICE_EMAIL())
                 .expected_message()
                 .to_message_parts(),
+            metadata: None,
         })
         .await;
 
@@ -529,7 +599,7 @@ async fn verify_valid_message_awaiting_second_challenge() {
             .get_field(&F::ALICE_EMAIL())
             .expected_second()
             .value
-            .clone(),
+            .to_string(),
     };
 
     // Send it to the API endpoint.
@@ -602,6 +672,7 @@ async fn verify_invalid_message_awaiting_second_challenge() {
                 .get_field(&F::ALICE_EMAIL())
                 .expected_message()
                 .to_message_parts(),
+            metadata: None,
         })
         .await;
 
@@ -727,6 +798,7 @@ async fn verify_full_identity() {
             .get_field(&F::ALICE_TWITTER())
             .expected_message()
             .to_message_parts(),
+        metadata: None,
     };
 
     // Verify whether message is valid.
@@ -763,6 +835,7 @@ async fn verify_full_identity() {
             .get_field(&F::ALICE_EMAIL())
             .expected_message()
             .to_message_parts(),
+        metadata: None,
     };
 
     let exp_message = alice
@@ -838,6 +911,7 @@ async fn verify_full_identity() {
             .get_field(&F::ALICE_MATRIX())
             .expected_message()
             .to_message_parts(),
+        metadata: None,
     };
 
     let exp_message = alice
@@ -895,3 +969,290 @@ async fn verify_full_identity() {
     // Empty stream.
     assert!(stream_alice.next().now_or_never().is_none());
 }
+
+#[actix::test]
+async fn invalid_messages_close_session() {
+    let (_db, _connector, mut api, _) = new_env().await;
+    let mut stream = api.ws_at("/api/account_status").await.unwrap();
+
+    // Descriptive error for invalid messages.
+    stream
+        .send(Message::Text(
+            r#"{"address":"1a2Y","chain":"rococo"}"#.into(),
+        ))
+        .await
+        .unwrap();
+
+    let resp: JsonResult<()> = stream.next().await.into();
+    match resp {
+        JsonResult::Err(err) => assert!(err.contains("unknown variant `rococo`")),
+        _ => panic!(),
+    }
+
+    // The session is closed after too many invalid messages.
+    for _ in 0..4 {
+        stream.send(Message::Text("invalid".into())).await.unwrap();
+        let resp: JsonResult<()> = stream.next().await.into();
+        assert!(matches!(resp, JsonResult::Err(_)));
+    }
+
+    match stream.next().await.unwrap().unwrap() {
+        Frame::Close(Some(reason)) => assert_eq!(reason.code, CloseCode::Policy),
+        _ => panic!(),
+    }
+}
+
+#[actix::test]
+async fn oversized_frame_closes_session() {
+    let (_db, _connector, mut api, _) = new_env().await;
+    let mut stream = api.ws_at("/api/account_status").await.unwrap();
+
+    stream
+        .send(Message::Text("a".repeat(4_096).into()))
+        .await
+        .unwrap();
+
+    match stream.next().await.unwrap().unwrap() {
+        Frame::Close(Some(reason)) => assert_eq!(reason.code, CloseCode::Size),
+        _ => panic!(),
+    }
+}
+
+#[actix::test]
+async fn batch_subscription_snapshot() {
+    let (_db, connector, mut api, _) = new_env().await;
+    let mut stream = api.ws_at("/api/account_status").await.unwrap();
+
+    connector.inject(alice_judgement_request()).await;
+    connector.inject(bob_judgement_request()).await;
+
+    let unknown = IdentityContext {
+        address: "FHxoGrCJ4xjZgjQiexjHkFHGbWbxwbNKMjFtBDAzybt7qaK"
+            .to_string()
+            .into(),
+        chain: ChainName::Kusama,
+    };
+
+    stream
+        .send(serde_json::json!({ "identities": [IdentityContext::alice(), unknown] }).to_ws())
+        .await
+        .unwrap();
+
+    let resp: JsonResult<serde_json::Value> = stream.next().await.into();
+    let snapshot = match resp {
+        JsonResult::Ok(snapshot) => snapshot,
+        JsonResult::Err(err) => panic!("unexpected error: {}", err),
+    };
+    assert_eq!(snapshot["snapshot"].as_array().unwrap().len(), 1);
+    assert_eq!(
+        snapshot["snapshot"][0]["state"]["context"],
+        serde_json::to_value(IdentityContext::alice()).unwrap()
+    );
+    assert_eq!(
+        snapshot["not_found"],
+        serde_json::to_value(vec![unknown]).unwrap()
+    );
+
+    // Chain subscriptions require a valid admin token.
+    stream
+        .send(serde_json::json!({ "chains": ["polkadot"], "admin_token": "wrong" }).to_ws())
+        .await
+        .unwrap();
+    let resp: JsonResult<serde_json::Value> = stream.next().await.into();
+    assert_eq!(resp, JsonResult::Err("Invalid admin token".to_string()));
+
+    stream
+        .send(serde_json::json!({ "chains": ["polkadot"], "admin_token": "admin" }).to_ws())
+        .await
+        .unwrap();
+    let resp: JsonResult<serde_json::Value> = stream.next().await.into();
+    match resp {
+        JsonResult::Ok(snapshot) => {
+            assert_eq!(snapshot["snapshot"].as_array().unwrap().len(), 2)
+        }
+        JsonResult::Err(err) => panic!("unexpected error: {}", err),
+    }
+
+    // Only identities with a matching status are included.
+    stream
+        .send(
+            serde_json::json!({
+                "chains": ["polkadot"],
+                "status": ["verified", "judged"],
+                "admin_token": "admin"
+            })
+            .to_ws(),
+        )
+        .await
+        .unwrap();
+    let resp: JsonResult<serde_json::Value> = stream.next().await.into();
+    match resp {
+        JsonResult::Ok(snapshot) => {
+            assert!(snapshot["snapshot"].as_array().unwrap().is_empty())
+        }
+        JsonResult::Err(err) => panic!("unexpected error: {}", err),
+    }
+}
+
+#[actix::test]
+async fn runtime_info() {
+    let (_db, _connector, api, _inj) = new_env().await;
+
+    let mut res = api.get("/api/version").send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let info: serde_json::Value = res.json().await.unwrap();
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(info["instance"], "single_instance");
+    assert!(info["git_hash"].is_string());
+    assert!(info["uptime"].is_u64());
+}
+
+#[actix::test]
+async fn verify_split_message() {
+    let (db, connector, _api, injector) = new_env().await;
+
+    // Insert judgement request.
+    connector.inject(alice_judgement_request()).await;
+    let states = connector.inserted_states().await;
+    let alice = states[0].clone();
+
+    // The challenge is split across two messages.
+    let challenge = alice
+        .get_field(&F::ALICE_MATRIX())
+        .expected_message()
+        .value
+        .to_string();
+    let (first, second) = challenge.split_at(challenge.len() / 2);
+
+    for part in [first, second] {
+        injector
+            .send(ExternalMessage {
+                origin: ExternalMessageType::Matrix("@alice:matrix.org".to_string()),
+                id: MessageId::from(0u32),
+                timestamp: Timestamp::now(),
+                values: vec![part.to_string().into()],
+                metadata: None,
+            })
+            .await;
+    }
+
+    sleep(Duration::from_secs(3)).await;
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(state.get_field(&F::ALICE_MATRIX()).challenge.is_verified());
+}
+
+#[actix::test]
+async fn verify_second_challenge_code() {
+    let (db, connector, _api, _) = new_env().await;
+    let db = db.with_second_challenge_codes(SecondChallengeCodeConfig {
+        digits: Some(8),
+        ttl: None,
+    });
+
+    // Insert judgement request.
+    connector.inject(alice_judgement_request()).await;
+    let states = connector.inserted_states().await;
+    let alice = states[0].clone();
+
+    // Verify Email (first challenge).
+    db.verify_message(&ExternalMessage {
+        origin: ExternalMessageType::Email("alice@email.com".to_string()),
+        id: MessageId::from(0u32),
+        timestamp: Timestamp::now(),
+        values: alice
+            .get_field(&F::ALICE_EMAIL())
+            .expected_message()
+            .to_message_parts(),
+        metadata: None,
+    })
+    .await
+    .unwrap();
+
+    let verify = |challenge: String| {
+        db.verify_second_challenge(VerifyChallenge {
+            entry: F::ALICE_EMAIL(),
+            challenge,
+        })
+    };
+
+    let code = db
+        .issue_second_challenge(&alice.context, &F::ALICE_EMAIL())
+        .await
+        .unwrap();
+    assert_eq!(code.value.len(), 8);
+
+    // The initial second challenge is replaced by the code.
+    let initial = alice
+        .get_field(&F::ALICE_EMAIL())
+        .expected_second()
+        .value
+        .to_string();
+    assert!(!verify(initial).await.unwrap());
+
+    // The code is no longer accepted after five failed attempts, including the
+    // one above.
+    for _ in 0..4 {
+        assert!(!verify("00000000".to_string()).await.unwrap());
+    }
+    assert!(!verify(code.value.to_string()).await.unwrap());
+
+    // A new code can be requested.
+    let code = db
+        .issue_second_challenge(&alice.context, &F::ALICE_EMAIL())
+        .await
+        .unwrap();
+    assert!(verify(code.value.to_string()).await.unwrap());
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(state.get_field(&F::ALICE_EMAIL()).challenge.is_verified());
+
+    // Nothing left to issue.
+    assert!(db
+        .issue_second_challenge(&alice.context, &F::ALICE_EMAIL())
+        .await
+        .is_err());
+}
+
+#[actix::test]
+async fn current_judgement_state_msgpack_encoding() {
+    let (_db, connector, api, _) = new_env().await;
+    let (resp, mut stream) = awc::Client::new()
+        .ws(api.url("/api/account_status"))
+        .protocols(["msgpack"])
+        .connect()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        resp.headers()
+            .get(actix_http::header::SEC_WEBSOCKET_PROTOCOL)
+            .unwrap(),
+        "msgpack"
+    );
+
+    connector.inject(alice_judgement_request()).await;
+    let states = connector.inserted_states().await;
+    let alice = states[0].clone();
+
+    // Requests are still sent as JSON.
+    stream.send(IdentityContext::alice().to_ws()).await.unwrap();
+    let resp: JsonResult<ResponseAccountState> = match stream.next().await.unwrap().unwrap() {
+        Frame::Binary(bytes) => rmp_serde::from_slice(&bytes).unwrap(),
+        _ => panic!("expected a binary frame"),
+    };
+
+    assert_eq!(
+        resp,
+        JsonResult::Ok(ResponseAccountState::with_no_notifications(alice))
+    );
+}
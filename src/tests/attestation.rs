@@ -0,0 +1,121 @@
+use super::*;
+use crate::api::AttestationOutcome;
+use crate::primitives::{
+    ExternalMessage, ExternalMessageType, JudgementState, MessageId, Timestamp,
+};
+use crate::verification::disambiguation_token;
+
+async fn attest(
+    api: &TestServer,
+    context: &IdentityContext,
+    field: &str,
+    message: &str,
+) -> JsonResult<AttestationOutcome> {
+    let mut res = api
+        .post("/api/attest")
+        .send_json(&serde_json::json!({
+            "context": context,
+            "field": field,
+            "message": message,
+        }))
+        .await
+        .unwrap();
+
+    res.json().await.unwrap()
+}
+
+#[actix::test]
+async fn attest_messages() {
+    let (db, _, api, injector) = new_env().await;
+
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+    let alice = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let expected = alice
+        .get_field(&F::ALICE_EMAIL())
+        .expected_message()
+        .value
+        .to_string();
+
+    // Only fields verified with a message can be attested.
+    let resp = attest(&api, &alice.context, "display_name", &expected).await;
+    assert!(matches!(resp, JsonResult::Err(_)));
+
+    // Unknown identity.
+    let resp = attest(&api, &IdentityContext::bob(), "email", &expected).await;
+    assert!(matches!(resp, JsonResult::Err(_)));
+
+    let resp = attest(&api, &alice.context, "email", "invalid").await;
+    assert_eq!(resp, JsonResult::Ok(AttestationOutcome::DoesNotMatch));
+
+    let message = format!("Hello, my challenge is {}", expected);
+    let resp = attest(&api, &alice.context, "email", &message).await;
+    assert_eq!(resp, JsonResult::Ok(AttestationOutcome::Matches));
+
+    // Nothing was modified.
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(state, alice);
+
+    // Once verified, the message no longer applies.
+    injector
+        .send(ExternalMessage {
+            origin: ExternalMessageType::Email("alice@email.com".to_string()),
+            id: MessageId::from(0u32),
+            timestamp: Timestamp::now(),
+            values: alice
+                .get_field(&F::ALICE_EMAIL())
+                .expected_message()
+                .to_message_parts(),
+            metadata: None,
+        })
+        .await;
+
+    let resp = attest(&api, &alice.context, "email", &expected).await;
+    assert_eq!(resp, JsonResult::Ok(AttestationOutcome::AlreadyVerified));
+}
+
+#[actix::test]
+async fn attest_shared_contact_value() {
+    let (db, _, api, _) = new_env().await;
+
+    // Bob specified the same contact values as Alice.
+    let alice = JudgementState::alice();
+    let mut bob = JudgementState::alice();
+    bob.context = IdentityContext::bob();
+    db.add_judgement_request(&alice).await.unwrap();
+    db.add_judgement_request(&bob).await.unwrap();
+
+    let alice = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    let expected = alice
+        .get_field(&F::ALICE_MATRIX())
+        .expected_message()
+        .value
+        .to_string();
+
+    let resp = attest(&api, &alice.context, "matrix", &expected).await;
+    assert_eq!(
+        resp,
+        JsonResult::Ok(AttestationOutcome::DisambiguationRequired)
+    );
+
+    let message = format!(
+        "{} {}",
+        expected,
+        disambiguation_token(&alice.context.address)
+    );
+    let resp = attest(&api, &alice.context, "matrix", &message).await;
+    assert_eq!(resp, JsonResult::Ok(AttestationOutcome::Matches));
+}
@@ -1,4 +1,6 @@
 use super::*;
+use crate::primitives::{ChainAddress, ChainName};
+use std::collections::HashMap;
 use tokio::time::{sleep, Duration};
 
 #[actix::test]
@@ -13,3 +15,76 @@ async fn background_basic_outgoing_watcher_messages() {
     assert!(counter.request_pending_judgements > 5);
     assert!(counter.request_display_names > 5);
 }
+
+#[actix::test]
+async fn background_pending_judgement_requests() {
+    let (db, connector, _api, _inj) = new_env().await;
+
+    // Processed in the background, the Connector keeps handling messages.
+    connector
+        .inject(WatcherMessage::PendingJudgementsRequests(vec![
+            JudgementRequest::alice(),
+            JudgementRequest::bob(),
+        ]))
+        .await;
+    connector
+        .inject(WatcherMessage::new_judgement_request(
+            JudgementRequest::alice(),
+        ))
+        .await;
+
+    sleep(Duration::from_millis(500)).await;
+
+    let alice = IdentityContext::alice();
+    let bob = IdentityContext::bob();
+    assert!(db.fetch_judgement_state(&alice).await.unwrap().is_some());
+    assert!(db.fetch_judgement_state(&bob).await.unwrap().is_some());
+}
+
+#[actix::test]
+async fn background_bulk_sync() {
+    let (db, connector, _api, _inj) = new_env().await;
+
+    // More identities than inserted with a single write.
+    let requests: Vec<JudgementRequest> = (0..1_200u64)
+        .map(|i| JudgementRequest {
+            address: {
+                let mut account = [0; 32];
+                account[..8].copy_from_slice(&i.to_be_bytes());
+                ChainAddress::from_account_id(&account, 0)
+            },
+            accounts: HashMap::from([
+                (AccountType::DisplayName, format!("User {}", i)),
+                (AccountType::Email, format!("user{}@email.com", i)),
+            ]),
+            block_number: Some(i),
+            network: None,
+        })
+        .collect();
+
+    connector
+        .inject(WatcherMessage::PendingJudgementsRequests(requests.clone()))
+        .await;
+    sleep(Duration::from_secs(5)).await;
+
+    let contexts: Vec<IdentityContext> = requests
+        .iter()
+        .map(|req| IdentityContext::new(req.address.clone(), ChainName::Polkadot))
+        .collect();
+    let states = db.fetch_judgement_states(&contexts).await.unwrap();
+    assert_eq!(states.len(), 1_200);
+
+    // Unchanged identities keep their challenges.
+    let first = states[&contexts[0]].clone();
+    connector
+        .inject(WatcherMessage::PendingJudgementsRequests(requests))
+        .await;
+    sleep(Duration::from_secs(2)).await;
+
+    let state = db
+        .fetch_judgement_state(&contexts[0])
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(state.fields, first.fields);
+}
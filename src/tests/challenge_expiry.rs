@@ -0,0 +1,138 @@
+use super::*;
+use crate::api::RenewalOutcome;
+use crate::primitives::{
+    ExternalMessage, ExternalMessageType, JudgementState, MessageId, NotificationMessage, Timestamp,
+};
+
+async fn request_new_challenge(
+    api: &TestServer,
+    context: &IdentityContext,
+    field: &str,
+) -> JsonResult<RenewalOutcome> {
+    let mut res = api
+        .post("/api/request_new_challenge")
+        .send_json(&serde_json::json!({
+            "context": context,
+            "field": field,
+        }))
+        .await
+        .unwrap();
+
+    res.json().await.unwrap()
+}
+
+fn email_message(state: &JudgementState) -> ExternalMessage {
+    ExternalMessage {
+        origin: ExternalMessageType::Email("alice@email.com".to_string()),
+        id: MessageId::from(0u32),
+        timestamp: Timestamp::now(),
+        values: state
+            .get_field(&F::ALICE_EMAIL())
+            .expected_message()
+            .to_message_parts(),
+        metadata: None,
+    }
+}
+
+#[actix::test]
+async fn expire_and_renew_challenges() {
+    let (db, _, mut api, _) = new_env().await;
+    let mut stream = api.ws_at("/api/account_status").await.unwrap();
+
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+    let _ = subscribe_context(&mut stream, alice.context.clone()).await;
+
+    // The expiry is assigned first.
+    let now = Timestamp::now();
+    assert_eq!(db.expire_challenges(3_600, now).await.unwrap(), 0);
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    let valid_until = state
+        .get_field(&F::ALICE_EMAIL())
+        .expected_message()
+        .valid_until;
+    assert_eq!(valid_until, Some(Timestamp::from(now.raw() + 3_600)));
+
+    // Nothing to renew yet.
+    let resp = request_new_challenge(&api, &alice.context, "email").await;
+    assert!(matches!(resp, JsonResult::Err(_)));
+
+    // Email, Twitter and Matrix challenges expire.
+    let later = Timestamp::from(now.raw() + 3_600);
+    assert_eq!(db.expire_challenges(3_600, later).await.unwrap(), 3);
+    assert_eq!(db.expire_challenges(3_600, later).await.unwrap(), 0);
+
+    let resp: JsonResult<ResponseAccountState> = stream.next().await.into();
+    match resp {
+        JsonResult::Ok(resp) => assert!(resp.notifications.iter().all(|notification| matches!(
+            notification,
+            NotificationMessage::ChallengeExpired { .. }
+        ))),
+        JsonResult::Err(err) => panic!("{}", err),
+    }
+
+    // The expired challenge is no longer accepted.
+    let expired = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(
+        expired
+            .get_field(&F::ALICE_EMAIL())
+            .expected_message()
+            .expired
+    );
+
+    db.verify_message(&email_message(&expired)).await.unwrap();
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!state.get_field(&F::ALICE_EMAIL()).challenge.is_verified());
+
+    // Only challenges can be renewed.
+    let resp = request_new_challenge(&api, &alice.context, "display_name").await;
+    assert!(matches!(resp, JsonResult::Err(_)));
+
+    let resp = request_new_challenge(&api, &alice.context, "email").await;
+    assert_eq!(resp, JsonResult::Ok(RenewalOutcome::Renewed));
+    let resp = request_new_challenge(&api, &alice.context, "email").await;
+    assert!(matches!(resp, JsonResult::Err(_)));
+
+    let renewed = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    let challenge = renewed.get_field(&F::ALICE_EMAIL()).expected_message();
+    assert!(!challenge.expired);
+    assert_ne!(
+        challenge.value,
+        expired
+            .get_field(&F::ALICE_EMAIL())
+            .expected_message()
+            .value
+    );
+
+    db.verify_message(&email_message(&renewed)).await.unwrap();
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(state.get_field(&F::ALICE_EMAIL()).challenge.is_verified());
+    // Other fields stay expired.
+    assert!(
+        state
+            .get_field(&F::ALICE_MATRIX())
+            .expected_message()
+            .expired
+    );
+}
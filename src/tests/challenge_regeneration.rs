@@ -0,0 +1,186 @@
+use super::*;
+use crate::adapters::admin::RawFieldName;
+use crate::api::{ChallengeTokenConfig, ChallengeTokens, RegenerationOutcome};
+use crate::primitives::{
+    ExternalMessage, ExternalMessageType, JudgementState, MessageId, Timestamp,
+};
+
+// Issued with the same secret as used by the test server.
+fn token(context: &IdentityContext) -> String {
+    ChallengeTokens::new(ChallengeTokenConfig {
+        secret: "secret".to_string(),
+        ttl: None,
+        verifier_endpoint: "http://localhost".to_string(),
+    })
+    .unwrap()
+    .issue(context, Timestamp::now().raw())
+    .token
+}
+
+async fn regenerate(
+    api: &TestServer,
+    context: &IdentityContext,
+    token: &str,
+    field: &str,
+) -> JsonResult<RegenerationOutcome> {
+    let mut res = api
+        .post("/api/regenerate_challenge")
+        .send_json(&serde_json::json!({
+            "context": context,
+            "token": token,
+            "field": field,
+        }))
+        .await
+        .unwrap();
+
+    res.json().await.unwrap()
+}
+
+#[actix::test]
+async fn regenerate_challenges() {
+    let (db, _, api, _) = new_env().await;
+
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+    let alice = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Requires a token of the account.
+    let resp = regenerate(&api, &alice.context, "1.abc", "email").await;
+    assert!(matches!(resp, JsonResult::Err(_)));
+    let resp = regenerate(
+        &api,
+        &alice.context,
+        &token(&IdentityContext::bob()),
+        "email",
+    )
+    .await;
+    assert!(matches!(resp, JsonResult::Err(_)));
+
+    let token = token(&alice.context);
+
+    // Only fields with challenges can be regenerated.
+    let resp = regenerate(&api, &alice.context, &token, "display_name").await;
+    assert!(matches!(resp, JsonResult::Err(_)));
+
+    let resp = regenerate(&api, &alice.context, &token, "email").await;
+    assert_eq!(resp, JsonResult::Ok(RegenerationOutcome::Regenerated));
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    let old = alice.get_field(&F::ALICE_EMAIL());
+    let new = state.get_field(&F::ALICE_EMAIL());
+    assert_ne!(old.expected_message(), new.expected_message());
+    assert_ne!(old.expected_second(), new.expected_second());
+    // Other fields are not affected.
+    assert_eq!(
+        alice.get_field(&F::ALICE_MATRIX()),
+        state.get_field(&F::ALICE_MATRIX())
+    );
+
+    // The previous challenge is no longer valid.
+    db.verify_message(&ExternalMessage {
+        origin: ExternalMessageType::Email("alice@email.com".to_string()),
+        id: MessageId::from(0u32),
+        timestamp: Timestamp::now(),
+        values: old.expected_message().to_message_parts(),
+        metadata: None,
+    })
+    .await
+    .unwrap();
+
+    db.verify_message(&ExternalMessage {
+        origin: ExternalMessageType::Email("alice@email.com".to_string()),
+        id: MessageId::from(1u32),
+        timestamp: Timestamp::now(),
+        values: new.expected_message().to_message_parts(),
+        metadata: None,
+    })
+    .await
+    .unwrap();
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(
+        state
+            .get_field(&F::ALICE_EMAIL())
+            .expected_message()
+            .is_verified
+    );
+
+    // Only the second challenge is replaced once the first one is verified.
+    let resp = regenerate(&api, &alice.context, &token, "email").await;
+    assert_eq!(
+        resp,
+        JsonResult::Ok(RegenerationOutcome::SecondChallengeResent)
+    );
+
+    let regenerated = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    let field = regenerated.get_field(&F::ALICE_EMAIL());
+    assert_eq!(
+        field.expected_message(),
+        state.get_field(&F::ALICE_EMAIL()).expected_message()
+    );
+    assert_ne!(
+        field.expected_second(),
+        state.get_field(&F::ALICE_EMAIL()).expected_second()
+    );
+
+    // The test server allows two regenerations per field.
+    let resp = regenerate(&api, &alice.context, &token, "email").await;
+    assert!(matches!(resp, JsonResult::Err(_)));
+
+    let resp = regenerate(&api, &alice.context, &token, "matrix").await;
+    assert_eq!(resp, JsonResult::Ok(RegenerationOutcome::Regenerated));
+}
+
+#[actix::test]
+async fn regenerate_verified_challenge() {
+    let (db, _, _, _) = new_env().await;
+
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+
+    db.verify_message(&ExternalMessage {
+        origin: ExternalMessageType::Matrix("@alice:matrix.org".to_string()),
+        id: MessageId::from(0u32),
+        timestamp: Timestamp::now(),
+        values: alice
+            .get_field(&F::ALICE_MATRIX())
+            .expected_message()
+            .to_message_parts(),
+        metadata: None,
+    })
+    .await
+    .unwrap();
+
+    let outcome = db
+        .regenerate_challenge(&alice.context, &RawFieldName::Matrix, 3, Timestamp::from(0))
+        .await
+        .unwrap();
+    assert_eq!(outcome, RegenerationOutcome::AlreadyVerified);
+
+    let outcome = db
+        .regenerate_challenge(
+            &IdentityContext::bob(),
+            &RawFieldName::Matrix,
+            3,
+            Timestamp::from(0),
+        )
+        .await
+        .unwrap();
+    assert_eq!(outcome, RegenerationOutcome::NotFound);
+}
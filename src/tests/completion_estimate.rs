@@ -0,0 +1,130 @@
+use super::*;
+use crate::api::CompletionEstimate;
+use crate::primitives::{ChainName, JudgementState, Timestamp};
+
+async fn estimate(api: &TestServer, context: &IdentityContext) -> JsonResult<CompletionEstimate> {
+    let mut res = api
+        .post("/api/estimated_completion")
+        .send_json(context)
+        .await
+        .unwrap();
+
+    res.json().await.unwrap()
+}
+
+async fn account_status(
+    stream: &mut Framed<impl AsyncRead + AsyncWrite + std::marker::Unpin, Codec>,
+    context: &IdentityContext,
+) -> serde_json::Value {
+    stream.send(context.to_ws()).await.unwrap();
+    match stream.next().await.unwrap().unwrap() {
+        Frame::Text(text) => serde_json::from_slice(&text).unwrap(),
+        _ => panic!(),
+    }
+}
+
+#[actix::test]
+async fn estimate_completion_time() {
+    let (db, _, api, _) = new_env().await;
+
+    // Unknown identity.
+    let resp = estimate(&api, &IdentityContext::alice()).await;
+    assert!(matches!(resp, JsonResult::Err(_)));
+
+    // Not enough verified identities for an estimate.
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+    let resp = estimate(&api, &alice.context).await;
+    assert!(matches!(resp, JsonResult::Err(_)));
+
+    // Fully verified identities are judged after the issuance delay.
+    db.full_manual_verification(&alice.context).await.unwrap();
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    let resp = estimate(&api, &alice.context).await;
+    assert_eq!(
+        resp,
+        JsonResult::Ok(CompletionEstimate {
+            estimated_completion: state.issue_judgement_at.unwrap(),
+            samples: 0,
+        })
+    );
+
+    db.set_judged(&alice.context).await.unwrap();
+    let resp = estimate(&api, &alice.context).await;
+    assert!(matches!(resp, JsonResult::Err(_)));
+}
+
+#[actix::test]
+async fn account_status_estimate() {
+    let (db, _, mut api, _) = new_env().await;
+
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+
+    let mut stream = api.ws_at("/api/account_status").await.unwrap();
+
+    // Not enough verified identities for an estimate.
+    let frame = account_status(&mut stream, &alice.context).await;
+    assert!(frame["message"].get("estimate").is_none());
+
+    db.full_manual_verification(&alice.context).await.unwrap();
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    // Notifications of earlier events might still be pending.
+    let mut frame = account_status(&mut stream, &alice.context).await;
+    while frame["message"]["state"]["is_fully_verified"] != true {
+        frame = match stream.next().await.unwrap().unwrap() {
+            Frame::Text(text) => serde_json::from_slice(&text).unwrap(),
+            _ => panic!(),
+        };
+    }
+    assert_eq!(
+        frame["message"]["estimate"],
+        serde_json::to_value(CompletionEstimate {
+            estimated_completion: state.issue_judgement_at.unwrap(),
+            samples: 0,
+        })
+        .unwrap()
+    );
+}
+
+#[actix::test]
+async fn fetch_verification_durations() {
+    let (db, _, _, _) = new_env().await;
+
+    let alice = JudgementState::alice();
+    let mut bob = JudgementState::alice();
+    bob.context = IdentityContext::bob();
+    bob.inserted_timestamp = Timestamp::from(Timestamp::now().raw() - 3_600);
+    db.add_judgement_request(&alice).await.unwrap();
+    db.add_judgement_request(&bob).await.unwrap();
+
+    // Pending identities are not included.
+    let since = Timestamp::from(0);
+    let durations = db
+        .fetch_verification_durations(ChainName::Polkadot, since, 10)
+        .await
+        .unwrap();
+    assert!(durations.is_empty());
+
+    db.full_manual_verification(&bob.context).await.unwrap();
+    let durations = db
+        .fetch_verification_durations(ChainName::Polkadot, since, 10)
+        .await
+        .unwrap();
+    assert_eq!(durations.len(), 1);
+    assert!(durations[0] >= 3_600);
+
+    let durations = db
+        .fetch_verification_durations(ChainName::Kusama, since, 10)
+        .await
+        .unwrap();
+    assert!(durations.is_empty());
+}
@@ -0,0 +1,61 @@
+use super::*;
+use crate::compliance::ComplianceDecision;
+use crate::primitives::{ChainName, JudgementState};
+
+#[actix::test]
+async fn stored_compliance_decisions() {
+    let (db, _, _, _) = new_env().await;
+
+    let mut alice = JudgementState::alice();
+    alice.compliance_decision = Some(ComplianceDecision::Flag);
+
+    // Requests with decisions are not inserted in bulk.
+    let inserted = db
+        .insert_new_judgement_requests(&[alice.clone()])
+        .await
+        .unwrap();
+    assert_eq!(inserted, vec![false]);
+
+    db.add_judgement_request(&alice).await.unwrap();
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(state.requires_manual_review);
+    assert_eq!(state.compliance_decision, Some(ComplianceDecision::Flag));
+    assert_eq!(
+        db.fetch_compliance_decision(&alice.context).await.unwrap(),
+        Some(ComplianceDecision::Flag)
+    );
+
+    // Edits of the identity do not lift the manual review.
+    let mut edited = JudgementState::alice();
+    edited.fields.pop();
+    db.add_judgement_request(&edited).await.unwrap();
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(state.fields.len(), alice.fields.len() - 1);
+    assert!(state.requires_manual_review);
+
+    // Denied requests are only recorded.
+    let bob = IdentityContext::bob();
+    db.insert_compliance_decision(&bob, ComplianceDecision::Deny)
+        .await
+        .unwrap();
+    assert_eq!(
+        db.fetch_compliance_decision(&bob).await.unwrap(),
+        Some(ComplianceDecision::Deny)
+    );
+    assert!(db.fetch_judgement_state(&bob).await.unwrap().is_none());
+
+    // Unknown requests were not decided yet.
+    let unknown = IdentityContext {
+        chain: ChainName::Kusama,
+        ..IdentityContext::alice()
+    };
+    assert_eq!(db.fetch_compliance_decision(&unknown).await.unwrap(), None);
+}
@@ -0,0 +1,52 @@
+use super::*;
+use crate::adapters::admin::RawFieldName;
+use crate::primitives::{
+    ExternalMessage, ExternalMessageType, JudgementState, MessageId, Timestamp,
+};
+use futures::future::join_all;
+
+#[actix::test]
+async fn concurrent_transitions_are_serialized() {
+    let (db, _, _, _) = new_env().await;
+
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+    let alice = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let message = ExternalMessage {
+        origin: ExternalMessageType::Matrix("@alice:matrix.org".to_string()),
+        id: MessageId::from(0u32),
+        timestamp: Timestamp::now(),
+        values: alice
+            .get_field(&F::ALICE_MATRIX())
+            .expected_message()
+            .to_message_parts(),
+        metadata: None,
+    };
+
+    // Without serialization, the transactions would fail with write
+    // conflicts.
+    let messages = join_all((0..5).map(|_| db.verify_message(&message))).await;
+    let manual = join_all(
+        [RawFieldName::Email, RawFieldName::Twitter]
+            .iter()
+            .map(|field| db.verify_manually(&alice.context, field, true, None)),
+    )
+    .await;
+
+    assert!(messages.iter().all(|res| res.is_ok()));
+    assert!(manual.iter().all(|res| res.is_ok()));
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(state.get_field(&F::ALICE_MATRIX()).challenge.is_verified());
+    assert!(state.get_field(&F::ALICE_EMAIL()).challenge.is_verified());
+    assert!(state.get_field(&F::ALICE_TWITTER()).challenge.is_verified());
+}
@@ -0,0 +1,95 @@
+use super::*;
+use crate::adapters::admin::RawFieldName;
+use crate::api::CredentialAttestation;
+use crate::notifier::EventCursor;
+use crate::primitives::{IdentityFieldValue as F, JudgementState, NotificationMessage, Timestamp};
+
+fn attestation(fields: Vec<F>) -> CredentialAttestation {
+    CredentialAttestation {
+        issuer: "kyc".to_string(),
+        context: IdentityContext::alice(),
+        fields,
+        issued_at: 1_000,
+        signature: "00".to_string(),
+    }
+}
+
+#[actix::test]
+async fn import_attested_fields() {
+    let (db, _, _, _) = new_env().await;
+    let alice = JudgementState::alice();
+    let mut cursor = EventCursor::new(&db, Default::default()).await.unwrap();
+
+    // Unknown identity.
+    assert!(db
+        .import_credentials(&attestation(vec![F::ALICE_EMAIL()]))
+        .await
+        .unwrap()
+        .is_none());
+
+    db.add_judgement_request(&alice).await.unwrap();
+    db.verify_manually(&alice.context, &RawFieldName::DisplayName, false, None)
+        .await
+        .unwrap();
+
+    // Only matching values are imported, case differences are ignored.
+    let imported = db
+        .import_credentials(&attestation(vec![
+            F::Email("Alice@Email.com".to_string()),
+            F::Twitter("@bob".to_string()),
+            F::ALICE_MATRIX(),
+        ]))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(imported, vec![F::ALICE_EMAIL(), F::ALICE_MATRIX()]);
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(state.get_field(&F::ALICE_EMAIL()).challenge.is_verified());
+    assert!(state.get_field(&F::ALICE_MATRIX()).challenge.is_verified());
+    assert!(!state.get_field(&F::ALICE_TWITTER()).challenge.is_verified());
+    assert!(!state.is_fully_verified);
+
+    // The provenance is recorded.
+    let credentials = db.fetch_imported_credentials(&alice.context).await.unwrap();
+    assert_eq!(credentials.len(), 2);
+    assert_eq!(credentials[0].field, F::ALICE_EMAIL());
+    assert_eq!(credentials[0].issuer, "kyc");
+    assert_eq!(credentials[0].issued_at, Timestamp::from(1_000));
+
+    // Already verified fields are not imported again.
+    let imported = db
+        .import_credentials(&attestation(vec![F::ALICE_EMAIL(), F::ALICE_TWITTER()]))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(imported, vec![F::ALICE_TWITTER()]);
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(state.is_fully_verified);
+
+    let events = cursor.fetch(&db).await.unwrap();
+    let imported: Vec<_> = events
+        .into_iter()
+        .filter_map(|event| match event.message {
+            NotificationMessage::CredentialImported { field, issuer, .. } => Some((field, issuer)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        imported,
+        vec![
+            (F::ALICE_EMAIL(), "kyc".to_string()),
+            (F::ALICE_MATRIX(), "kyc".to_string()),
+            (F::ALICE_TWITTER(), "kyc".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,43 @@
+use super::*;
+use crate::api::{DisplayNameChange, JsonResult};
+use crate::connector::DisplayNameEntry;
+use crate::primitives::{ChainName, DisplayName, IdentityContext};
+use futures::StreamExt;
+
+#[actix::test]
+async fn subscribe_display_name_changes() {
+    let (db, _, mut api, _) = new_env().await;
+    let mut stream = api.ws_at("/api/display_names").await.unwrap();
+
+    // Subscribe to endpoint.
+    stream.send(ChainName::Polkadot.to_ws()).await.unwrap();
+    sleep(Duration::from_secs(1)).await;
+
+    // Insert display names.
+    let alice = DisplayNameEntry::new(IdentityContext::alice(), DisplayName::from("Alice"));
+    let bob = DisplayNameEntry::new(IdentityContext::bob(), DisplayName::from("Bob"));
+
+    db.insert_display_name(&alice).await.unwrap();
+    db.insert_display_name(&bob).await.unwrap();
+
+    // Check changes.
+    let resp: JsonResult<DisplayNameChange> = stream.next().await.into();
+    assert_eq!(
+        resp,
+        JsonResult::Ok(DisplayNameChange::Added(alice.clone()))
+    );
+
+    let resp: JsonResult<DisplayNameChange> = stream.next().await.into();
+    assert_eq!(resp, JsonResult::Ok(DisplayNameChange::Added(bob.clone())));
+
+    // Remove inactive display name.
+    // (the removal itself is only streamed if the server supports pre-images).
+    let removed = db
+        .prune_display_names(ChainName::Polkadot, std::slice::from_ref(&alice))
+        .await
+        .unwrap();
+    assert_eq!(removed, 1);
+
+    let names = db.fetch_display_names(ChainName::Polkadot).await.unwrap();
+    assert_eq!(names, vec![alice]);
+}
@@ -1,18 +1,15 @@
 use super::*;
 use crate::api::{JsonResult, ResponseAccountState};
 use crate::connector::DisplayNameEntry;
-use crate::display_name::DisplayNameVerifier;
-use crate::primitives::{IdentityContext, IdentityFieldValue};
+use crate::display_name::{simulate_limit, DisplayNameVerifier};
+use crate::primitives::{ChainName, IdentityContext, IdentityFieldValue, JudgementState};
 use crate::DisplayNameConfig;
 use futures::StreamExt;
 
 impl From<&str> for DisplayNameEntry {
     fn from(val: &str) -> Self {
-        DisplayNameEntry {
-            display_name: val.to_string(),
-            // Filler value.
-            context: IdentityContext::bob(),
-        }
+        // Filler value for the context.
+        DisplayNameEntry::new(IdentityContext::bob(), val.into())
     }
 }
 
@@ -20,6 +17,9 @@ fn config() -> DisplayNameConfig {
     DisplayNameConfig {
         enabled: true,
         limit: 0.85,
+        confusables: None,
+        reserved_names: None,
+        reserved_patterns: None,
     }
 }
 
@@ -103,3 +103,82 @@ async fn invalid_display_name() {
     // Empty stream.
     assert!(stream.next().now_or_never().is_none());
 }
+
+#[actix::test]
+async fn lookalike_display_names() {
+    let (db, _, _, _) = new_env().await;
+    let verifier = DisplayNameVerifier::new(db.clone(), config());
+
+    // Cyrillic 'А' and 'е', joined by zero-width characters.
+    let lookalike = DisplayNameEntry::from("\u{0410}l\u{200B}ic\u{0435}");
+    assert_eq!(lookalike.normalized, "alice");
+    db.insert_display_name(&lookalike).await.unwrap();
+
+    let violations = verifier
+        .check_similarities("Alice", ChainName::Polkadot, None)
+        .await
+        .unwrap();
+
+    assert_eq!(violations, vec![lookalike]);
+}
+
+#[actix::test]
+async fn confusable_display_names() {
+    let (db, _, _, _) = new_env().await;
+
+    let existing = DisplayNameEntry::from("Eve");
+    db.insert_display_name(&existing).await.unwrap();
+
+    // Cherokee 'Ꭼ' and Cyrillic 'е', below the similarity limit.
+    let lookalike = "\u{13AC}v\u{0435}";
+    let verifier = DisplayNameVerifier::new(db.clone(), config());
+    let violations = verifier
+        .check_similarities(lookalike, ChainName::Polkadot, None)
+        .await
+        .unwrap();
+    assert!(violations.is_empty());
+
+    let verifier = DisplayNameVerifier::new(
+        db.clone(),
+        DisplayNameConfig {
+            confusables: Some(true),
+            ..config()
+        },
+    );
+    let violations = verifier
+        .check_similarities(lookalike, ChainName::Polkadot, None)
+        .await
+        .unwrap();
+    assert_eq!(violations, vec![existing]);
+}
+
+#[actix::test]
+async fn simulate_display_name_limit() {
+    let (db, _, _, _) = new_env().await;
+    let verifier = DisplayNameVerifier::new(db.clone(), config());
+
+    let existing = DisplayNameEntry::from("Alice");
+    db.insert_display_name(&existing).await.unwrap();
+
+    // Alice's display name violates the existing one.
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+    verifier.verify_display_name(&alice).await.unwrap();
+
+    let rejected = db
+        .fetch_rejected_pending_display_names(ChainName::Polkadot)
+        .await
+        .unwrap();
+    assert_eq!(rejected.len(), 1);
+    assert_eq!(rejected[0].context, alice.context);
+    assert!(db
+        .fetch_rejected_pending_display_names(ChainName::Kusama)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let accepted = db.fetch_display_names(ChainName::Polkadot).await.unwrap();
+    let simulation = simulate_limit(ChainName::Polkadot, &accepted, &rejected, 0.85, 1.0);
+    assert_eq!(simulation.accepted, 1);
+    assert_eq!(simulation.newly_accepted, rejected);
+}
@@ -0,0 +1,112 @@
+use super::*;
+use crate::disposable_emails::{DisposableEmailAction, DisposableEmailConfig, DisposableEmails};
+use crate::primitives::{
+    ExternalMessage, ExternalMessageType, JudgementState, MessageId, Timestamp,
+};
+
+async fn list(action: DisposableEmailAction) -> DisposableEmails {
+    DisposableEmails::new(DisposableEmailConfig {
+        action,
+        domains: Some(vec!["mailinator.com".to_string()]),
+        source: None,
+        refresh_interval: None,
+    })
+    .await
+    .unwrap()
+}
+
+fn alice_with_email(email: &str) -> JudgementState {
+    JudgementState::new(
+        IdentityContext::alice(),
+        vec![
+            F::ALICE_DISPLAY_NAME(),
+            IdentityFieldValue::Email(email.to_string()),
+        ],
+    )
+}
+
+fn email_message(state: &JudgementState, email: &str) -> ExternalMessage {
+    ExternalMessage {
+        origin: ExternalMessageType::Email(email.to_string()),
+        id: MessageId::from(0u32),
+        timestamp: Timestamp::now(),
+        values: state
+            .get_field(&IdentityFieldValue::Email(email.to_string()))
+            .expected_message()
+            .to_message_parts(),
+        metadata: None,
+    }
+}
+
+#[actix::test]
+async fn disposable_email_refused() {
+    let (db, _, _, _) = new_env().await;
+    let db = db.with_disposable_emails(list(DisposableEmailAction::Refuse).await);
+
+    let email = "alice@mailinator.com";
+    let alice = alice_with_email(email);
+    assert!(db.add_judgement_request(&alice).await.unwrap());
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(state.requires_manual_review);
+
+    // The valid challenge is not accepted.
+    db.verify_message(&email_message(&alice, email))
+        .await
+        .unwrap();
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    let field = IdentityFieldValue::Email(email.to_string());
+    assert!(!state.get_field(&field).challenge.is_verified());
+}
+
+#[actix::test]
+async fn disposable_email_manual_review() {
+    let (db, _, _, _) = new_env().await;
+    let db = db.with_disposable_emails(list(DisposableEmailAction::ManualReview).await);
+
+    let email = "alice@mailinator.com";
+    let alice = alice_with_email(email);
+    assert!(db.add_judgement_request(&alice).await.unwrap());
+
+    // The challenge is accepted, but the identity requires a review.
+    db.verify_message(&email_message(&alice, email))
+        .await
+        .unwrap();
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    let field = IdentityFieldValue::Email(email.to_string());
+    assert!(state.requires_manual_review);
+    assert!(matches!(
+        &state.get_field(&field).challenge,
+        crate::primitives::ChallengeType::ExpectedMessage { expected, .. } if expected.is_verified
+    ));
+}
+
+#[actix::test]
+async fn regular_email_not_flagged() {
+    let (db, _, _, _) = new_env().await;
+    let db = db.with_disposable_emails(list(DisposableEmailAction::Refuse).await);
+
+    let alice = alice_with_email("alice@email.com");
+    assert!(db.add_judgement_request(&alice).await.unwrap());
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!state.requires_manual_review);
+}
@@ -0,0 +1,119 @@
+use super::*;
+use crate::api::EventFilter;
+use crate::notifier::EventCursor;
+use crate::primitives::{
+    Event, ExternalMessage, ExternalMessageType, IdentityContext, JudgementState, MessageId,
+    NotificationMessage, Timestamp,
+};
+
+#[actix::test]
+async fn duplicate_events_are_inserted_once() {
+    let (db, _, _, _) = new_env().await;
+    let alice = IdentityContext::alice();
+    let mut cursor = EventCursor::new(&db, Default::default()).await.unwrap();
+
+    let event = Event::new(NotificationMessage::IdentityUpdated {
+        context: alice.clone(),
+    });
+    // A retried insertion of the same operation.
+    db.insert_events(vec![
+        event.clone().with_operation("op"),
+        event.clone().with_operation("op"),
+    ])
+    .await
+    .unwrap();
+    db.insert_events(vec![event.clone().with_operation("op")])
+        .await
+        .unwrap();
+
+    // Identical events of other operations and events without an operation
+    // are kept.
+    db.insert_events(vec![
+        event.clone().with_operation("other"),
+        event.clone(),
+        event.clone(),
+    ])
+    .await
+    .unwrap();
+
+    let (latest, _) = db.fetch_notification_sequence(&alice).await.unwrap();
+    assert_eq!(latest, 4);
+
+    let events: Vec<Event> = cursor
+        .fetch(&db)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|event| event.message.context() == &alice)
+        .collect();
+    assert_eq!(events.len(), 4);
+    assert!(events.iter().all(|e| e.message == event.message));
+    assert_eq!(
+        events[0].dedup_key,
+        event.clone().with_operation("op").dedup_key
+    );
+    assert_eq!(events[2].dedup_key, None);
+}
+
+#[actix::test]
+async fn reprocessed_message_is_recorded_once() {
+    let (db, _, _, _) = new_env().await;
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+
+    let failed = |id: u64| ExternalMessage {
+        origin: ExternalMessageType::Email("alice@email.com".to_string()),
+        id: MessageId::from(id),
+        timestamp: Timestamp::from(1_000),
+        values: vec!["INVALID".to_string().into()],
+        metadata: None,
+    };
+
+    let filter = EventFilter {
+        chain: None,
+        address: Some(alice.context.address.clone()),
+        kinds: vec!["field_verification_failed".to_string()],
+        since: None,
+        limit: 10,
+        cursor: None,
+    };
+    let failures = || async { db.fetch_event_page(&filter).await.unwrap().events.len() };
+
+    // The same message processed twice, e.g. by two instances.
+    db.verify_message(&failed(1)).await.unwrap();
+    db.verify_message(&failed(1)).await.unwrap();
+    assert_eq!(failures().await, 1);
+
+    // Another message with the same content within the same second.
+    db.verify_message(&failed(2)).await.unwrap();
+    assert_eq!(failures().await, 2);
+}
+
+#[actix::test]
+async fn redelivered_message_is_applied_once() {
+    let (db, _, _, injector) = new_env().await;
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+
+    let failed = ExternalMessage {
+        origin: ExternalMessageType::Email("alice@email.com".to_string()),
+        id: MessageId::from(1u32),
+        timestamp: Timestamp::from(1_000),
+        values: vec!["INVALID".to_string().into()],
+        metadata: None,
+    };
+
+    // The same message delivered twice, e.g. after a retried fetch.
+    injector.send(failed.clone()).await;
+    injector.send(failed).await;
+
+    // Give the adapter listener time to process the messages.
+    sleep(Duration::from_secs(3)).await;
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(state.get_field(&F::ALICE_EMAIL()).failed_attempts, 1);
+}
@@ -0,0 +1,86 @@
+use super::*;
+use crate::api::EventPage;
+use crate::primitives::{JudgementState, NotificationMessage, Timestamp};
+use actix_http::StatusCode;
+
+#[actix::test]
+async fn paginate_and_filter_events() {
+    let (db, _, api, _) = new_env().await;
+
+    let alice = JudgementState::alice();
+    let bob = JudgementState::new(
+        IdentityContext::bob(),
+        vec![F::Email("bob@email.com".to_string())],
+    );
+    db.add_judgement_request(&alice).await.unwrap();
+    db.add_judgement_request(&bob).await.unwrap();
+    db.set_judged(&alice.context).await.unwrap();
+    db.set_judged(&bob.context).await.unwrap();
+
+    let fetch = |query: String| {
+        let req = api.get(format!("/api/events?{}", query));
+        async move {
+            let mut res = req.send().await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+            match res.json::<JsonResult<EventPage>>().await.unwrap() {
+                JsonResult::Ok(page) => page,
+                JsonResult::Err(err) => panic!("{}", err),
+            }
+        }
+    };
+
+    // Events of a single identity.
+    let page = fetch(format!(
+        "chain=polkadot&address={}",
+        alice.context.address.as_str()
+    ))
+    .await;
+    assert!(!page.events.is_empty());
+    assert!(page
+        .events
+        .iter()
+        .all(|event| event.message.context() == &alice.context));
+    assert!(page.next_cursor.is_none());
+
+    // Events of a kind, one per page.
+    let page = fetch("kinds=judgement_provided&limit=1".to_string()).await;
+    assert_eq!(
+        page.events[0].message,
+        NotificationMessage::JudgementProvided {
+            context: alice.context.clone()
+        }
+    );
+    let cursor = page.next_cursor.unwrap();
+
+    let page = fetch(format!(
+        "kinds=judgement_provided&limit=1&cursor={}",
+        cursor
+    ))
+    .await;
+    assert_eq!(
+        page.events[0].message,
+        NotificationMessage::JudgementProvided {
+            context: bob.context.clone()
+        }
+    );
+    let cursor = page.next_cursor.unwrap();
+
+    let page = fetch(format!(
+        "kinds=judgement_provided&limit=1&cursor={}",
+        cursor
+    ))
+    .await;
+    assert!(page.events.is_empty());
+    assert!(page.next_cursor.is_none());
+
+    // Events created later.
+    let page = fetch(format!("since={}", Timestamp::now().raw() + 60)).await;
+    assert!(page.events.is_empty());
+
+    let res = api
+        .get("/api/events?kinds=identity_teleported")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,71 @@
+use super::*;
+use crate::failure_alerts::{FailureAlerts, FailureAlertsConfig};
+use crate::primitives::{
+    ExpectedMessage, ExternalMessage, ExternalMessageType, JudgementState, MessageId, Timestamp,
+};
+
+#[actix::test]
+async fn alert_probing_senders() {
+    let (db, _, _, _) = new_env().await;
+
+    // Bob lists the Matrix account of Alice as well.
+    let alice = JudgementState::alice();
+    let bob = JudgementState::new(IdentityContext::bob(), vec![F::ALICE_MATRIX()]);
+    db.add_judgement_request(&alice).await.unwrap();
+    db.add_judgement_request(&bob).await.unwrap();
+
+    let alerts = FailureAlerts::new(
+        db.clone(),
+        FailureAlertsConfig {
+            min_identities: 2,
+            window: Some(600),
+            matrix: None,
+            webhook: None,
+        },
+    )
+    .unwrap();
+
+    let now = Timestamp::now();
+    assert!(alerts.check(now).await.unwrap().is_empty());
+
+    // Wrong challenges for both identities.
+    db.verify_message(&ExternalMessage {
+        origin: ExternalMessageType::Matrix("@alice:matrix.org".to_string()),
+        id: MessageId::from(0u32),
+        timestamp: now,
+        values: ExpectedMessage::random().to_message_parts(),
+        metadata: None,
+    })
+    .await
+    .unwrap();
+
+    let raised = alerts.check(now).await.unwrap();
+    assert_eq!(raised.len(), 1);
+    assert_eq!(
+        raised[0].origin,
+        ExternalMessageType::Matrix("@alice:matrix.org".to_string())
+    );
+    assert_eq!(raised[0].identities.len(), 2);
+    assert!(raised[0].identities.contains(&alice.context));
+    assert!(raised[0].identities.contains(&bob.context));
+    assert_eq!(db.fetch_failure_alerts(now).await.unwrap(), raised);
+
+    // The sender is alerted once per window.
+    assert!(alerts.check(now).await.unwrap().is_empty());
+
+    // Failures outside the window are not considered.
+    let later = Timestamp::from(now.raw() + 601);
+    assert!(alerts.check(later).await.unwrap().is_empty());
+
+    // Failures for a single identity are no anomaly.
+    assert!(FailureAlerts::new(
+        db.clone(),
+        FailureAlertsConfig {
+            min_identities: 1,
+            window: None,
+            matrix: None,
+            webhook: None,
+        },
+    )
+    .is_err());
+}
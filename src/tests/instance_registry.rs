@@ -0,0 +1,65 @@
+use super::*;
+use crate::instance_registry::{AdapterStatus, InstanceRegistration, WatcherStatus};
+use crate::primitives::{ChainName, Timestamp};
+
+#[actix::test]
+async fn register_instances() {
+    let (db, _, _, _) = new_env().await;
+
+    let mut listener = InstanceRegistration {
+        instance_id: "a".to_string(),
+        role: "adapter_listener".to_string(),
+        host: "registrar-1".to_string(),
+        version: "0.1.0".to_string(),
+        chains: vec![ChainName::Polkadot],
+        last_seen: Timestamp::from(1_000),
+        adapters: vec![AdapterStatus {
+            adapter: "Email".to_string(),
+            last_fetch: Timestamp::from(990),
+        }],
+        watchers: vec![WatcherStatus {
+            chain: "polkadot".to_string(),
+            connected: true,
+            last_message: Some(Timestamp::from(995)),
+        }],
+    };
+    let notifier = InstanceRegistration {
+        instance_id: "b".to_string(),
+        role: "session_notifier".to_string(),
+        host: "registrar-2".to_string(),
+        version: "0.1.0".to_string(),
+        chains: vec![],
+        last_seen: Timestamp::from(2_000),
+        adapters: vec![],
+        watchers: vec![],
+    };
+
+    db.store_instance_registration(&listener).await.unwrap();
+    db.store_instance_registration(&notifier).await.unwrap();
+
+    let active = db
+        .fetch_active_instances(Timestamp::from(1_500))
+        .await
+        .unwrap();
+    assert_eq!(active, vec![notifier.clone()]);
+
+    // Heartbeats refresh the registration.
+    listener.last_seen = Timestamp::from(3_000);
+    db.store_instance_registration(&listener).await.unwrap();
+
+    let active = db
+        .fetch_active_instances(Timestamp::from(1_500))
+        .await
+        .unwrap();
+    assert_eq!(active.len(), 2);
+    assert!(active.contains(&listener));
+
+    let pruned = db
+        .prune_instance_registrations(Timestamp::from(2_500))
+        .await
+        .unwrap();
+    assert_eq!(pruned, 1);
+
+    let active = db.fetch_active_instances(Timestamp::from(0)).await.unwrap();
+    assert_eq!(active, vec![listener]);
+}
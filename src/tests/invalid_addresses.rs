@@ -0,0 +1,39 @@
+use super::*;
+use crate::primitives::{ChainAddress, ChainName};
+
+#[actix::test]
+async fn requests_with_invalid_addresses_are_skipped() {
+    let (db, connector, _api, _) = new_env().await;
+
+    // Invalid checksum.
+    let mut invalid = JudgementRequest::alice();
+    invalid.address =
+        ChainAddress::from("1a2YiGNu1UUhJtihq8961c7FZtWGQuWDVMWTNBKJdmpGhZQ".to_string());
+    // Kusama address on the Polkadot Watcher.
+    let mut kusama = JudgementRequest::bob();
+    kusama.address =
+        ChainAddress::from("HNZata7iMYWmk5RvZRTiAsSDhV8366zq2YGb3tLH5Upf74F".to_string());
+
+    connector
+        .inject(WatcherMessage::new_judgement_request(invalid.clone()))
+        .await;
+    connector
+        .inject(WatcherMessage::PendingJudgementsRequests(vec![
+            kusama.clone(),
+            JudgementRequest::bob(),
+        ]))
+        .await;
+    sleep(Duration::from_millis(500)).await;
+
+    for address in [invalid.address, kusama.address] {
+        let context = IdentityContext::new(address, ChainName::Polkadot);
+        assert!(db.fetch_judgement_state(&context).await.unwrap().is_none());
+    }
+
+    // Valid requests of the same batch are processed.
+    assert!(db
+        .fetch_judgement_state(&IdentityContext::bob())
+        .await
+        .unwrap()
+        .is_some());
+}
@@ -0,0 +1,61 @@
+use super::*;
+use crate::primitives::{JudgementState, Timestamp};
+
+#[actix::test]
+async fn expedite_judgement_issuance() {
+    let (db, _, _, _) = new_env().await;
+    let alice = JudgementState::alice();
+
+    // Unknown and unverified identities.
+    assert!(db
+        .expedite_judgement(&alice.context, 0)
+        .await
+        .unwrap()
+        .is_none());
+    db.add_judgement_request(&alice).await.unwrap();
+    assert!(db
+        .expedite_judgement(&alice.context, 0)
+        .await
+        .unwrap()
+        .is_none());
+
+    db.full_manual_verification(&alice.context).await.unwrap();
+    let scheduled = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap()
+        .issue_judgement_at
+        .unwrap();
+
+    // The minimum delay is not skipped.
+    let issue_at = db
+        .expedite_judgement(&alice.context, 3_600)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(issue_at, scheduled);
+
+    let issue_at = db
+        .expedite_judgement(&alice.context, 0)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(issue_at.raw() < scheduled.raw());
+    assert!(issue_at.raw() <= Timestamp::now().raw());
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(state.issue_judgement_at, Some(issue_at));
+
+    // Already judged.
+    db.set_judged(&alice.context).await.unwrap();
+    assert!(db
+        .expedite_judgement(&alice.context, 0)
+        .await
+        .unwrap()
+        .is_none());
+}
@@ -0,0 +1,39 @@
+use super::*;
+use crate::primitives::JudgementState;
+use actix_http::StatusCode;
+
+#[actix::test]
+async fn feed_lists_issued_judgements() {
+    let (db, _, api, _) = new_env().await;
+
+    let alice = JudgementState::alice();
+    let bob = JudgementState::new(
+        IdentityContext::bob(),
+        vec![F::Email("bob@email.com".to_string())],
+    );
+    db.add_judgement_request(&alice).await.unwrap();
+    db.add_judgement_request(&bob).await.unwrap();
+
+    // Only Alice is judged.
+    db.set_judged(&alice.context).await.unwrap();
+
+    let mut res = api.get("/feed/judgements.xml").send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers().get("content-type").unwrap(),
+        "application/atom+xml; charset=utf-8"
+    );
+
+    let body = res.body().await.unwrap();
+    let xml = std::str::from_utf8(&body).unwrap();
+
+    assert_eq!(xml.matches("<entry>").count(), 1);
+    assert!(xml.contains(alice.context.address.as_str()));
+    assert!(!xml.contains(bob.context.address.as_str()));
+
+    // Judging again does not create another entry.
+    db.set_judged(&alice.context).await.unwrap();
+
+    let events = db.fetch_judgement_events(10).await.unwrap();
+    assert_eq!(events.len(), 1);
+}
@@ -1,9 +1,10 @@
 use crate::adapters::tests::MessageInjector;
-use crate::adapters::AdapterListener;
+use crate::adapters::{AdapterListener, PollingInterval};
 use crate::database::Database;
 use crate::primitives::{
     ExpectedMessage, ExternalMessage, ExternalMessageType, JudgementState, MessageId, Timestamp,
 };
+use crate::runtime_info::RuntimeInfo;
 use crate::tests::F;
 use crate::{config_session_notifier, DatabaseConfig, DisplayNameConfig, NotifierConfig, Result};
 use rand::{thread_rng, Rng};
@@ -23,6 +24,8 @@ async fn run_mocker() -> Result<()> {
     let db_config = DatabaseConfig {
         uri: "mongodb://localhost:27017/?replicaSet=rs0".to_string(),
         name: format!("registrar_test_{}", rng.gen_range(u32::MIN..u32::MAX)),
+        read_uri: None,
+        slow_query_threshold: None,
     };
 
     let notifier_config = NotifierConfig {
@@ -31,7 +34,22 @@ async fn run_mocker() -> Result<()> {
         display_name: DisplayNameConfig {
             enabled: true,
             limit: 0.85,
+            confusables: None,
+            reserved_names: None,
+            reserved_patterns: None,
         },
+        session_limits: None,
+        challenge_tokens: None,
+        judgement_feed: None,
+        admin_search: None,
+        credential_import: None,
+        challenge_regeneration: None,
+        challenge_expiry: None,
+        request_withdrawal: None,
+        judge_now: None,
+        notification_window: None,
+        event_log: None,
+        webhooks: None,
     };
 
     info!("Starting mock adapter and session notifier instances");
@@ -39,12 +57,14 @@ async fn run_mocker() -> Result<()> {
     // Setup database
     let db = Database::new(&db_config.uri, &db_config.name).await?;
 
-    config_session_notifier(db.clone(), notifier_config).await?;
+    config_session_notifier(db.clone(), notifier_config, RuntimeInfo::test()).await?;
 
     // Setup message verifier and injector.
     let injector = MessageInjector::new();
-    let listener = AdapterListener::new(db.clone()).await;
-    listener.start_message_adapter(injector.clone(), 1).await;
+    let listener = AdapterListener::new(db.clone(), Default::default()).await;
+    listener
+        .start_message_adapter(injector.clone(), PollingInterval::fixed(1), false)
+        .await;
 
     info!("Mocker setup completed");
 
@@ -132,6 +152,7 @@ async fn run_mocker() -> Result<()> {
                 id: MessageId::from(0u32),
                 timestamp: Timestamp::now(),
                 values,
+                metadata: None,
             })
             .await;
 
@@ -0,0 +1,84 @@
+use super::*;
+use crate::matrix_policy::{MatrixPolicy, MatrixPolicyConfig};
+use crate::primitives::{
+    ExternalMessage, ExternalMessageType, JudgementState, MessageId, Timestamp,
+};
+
+#[actix::test]
+async fn denied_homeserver_not_verified() {
+    let (db, _, _, _) = new_env().await;
+    let db = db.with_matrix_policy(MatrixPolicy::from(MatrixPolicyConfig {
+        denied_homeservers: Some(vec!["spam.example".to_string()]),
+        ..Default::default()
+    }));
+
+    let mxid = "@alice:spam.example";
+    let field = IdentityFieldValue::Matrix(mxid.to_string());
+    let alice = JudgementState::new(
+        IdentityContext::alice(),
+        vec![F::ALICE_DISPLAY_NAME(), field.clone()],
+    );
+    assert!(db.add_judgement_request(&alice).await.unwrap());
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(state.requires_manual_review);
+
+    // The valid challenge is not accepted.
+    let msg = ExternalMessage {
+        origin: ExternalMessageType::Matrix(mxid.to_string()),
+        id: MessageId::from(0u32),
+        timestamp: Timestamp::now(),
+        values: alice
+            .get_field(&field)
+            .expected_message()
+            .to_message_parts(),
+        metadata: None,
+    };
+    db.verify_message(&msg).await.unwrap();
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!state.get_field(&field).challenge.is_verified());
+}
+
+#[actix::test]
+async fn allowed_homeserver_verified() {
+    let (db, _, _, _) = new_env().await;
+    let db = db.with_matrix_policy(MatrixPolicy::from(MatrixPolicyConfig {
+        allowed_homeservers: Some(vec!["matrix.org".to_string()]),
+        ..Default::default()
+    }));
+
+    let alice = JudgementState::new(
+        IdentityContext::alice(),
+        vec![F::ALICE_DISPLAY_NAME(), F::ALICE_MATRIX()],
+    );
+    assert!(db.add_judgement_request(&alice).await.unwrap());
+
+    let msg = ExternalMessage {
+        origin: ExternalMessageType::Matrix("@alice:matrix.org".to_string()),
+        id: MessageId::from(0u32),
+        timestamp: Timestamp::now(),
+        values: alice
+            .get_field(&F::ALICE_MATRIX())
+            .expected_message()
+            .to_message_parts(),
+        metadata: None,
+    };
+    db.verify_message(&msg).await.unwrap();
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!state.requires_manual_review);
+    assert!(state.get_field(&F::ALICE_MATRIX()).challenge.is_verified());
+}
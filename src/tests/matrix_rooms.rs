@@ -0,0 +1,69 @@
+use super::*;
+use crate::primitives::{MessageId, Timestamp};
+
+#[actix::test]
+async fn store_and_prune_matrix_rooms() {
+    let (db, _, _, _) = new_env().await;
+
+    // No room available.
+    let res = db.fetch_matrix_room("@alice:matrix.org").await.unwrap();
+    assert!(res.is_none());
+
+    db.store_matrix_room("@alice:matrix.org", "!alice:matrix.org")
+        .await
+        .unwrap();
+    db.store_matrix_room("@bob:matrix.org", "!bob:matrix.org")
+        .await
+        .unwrap();
+
+    let res = db.fetch_matrix_room("@alice:matrix.org").await.unwrap();
+    assert_eq!(res, Some("!alice:matrix.org".to_string()));
+
+    // A new room replaces the previous one.
+    db.store_matrix_room("@alice:matrix.org", "!alice2:matrix.org")
+        .await
+        .unwrap();
+    let res = db.fetch_matrix_room("@alice:matrix.org").await.unwrap();
+    assert_eq!(res, Some("!alice2:matrix.org".to_string()));
+
+    // The bot left the room.
+    let removed = db.remove_matrix_room("!alice2:matrix.org").await.unwrap();
+    assert_eq!(removed, 1);
+    let res = db.fetch_matrix_room("@alice:matrix.org").await.unwrap();
+    assert!(res.is_none());
+
+    // Only joined rooms are kept.
+    db.store_matrix_room("@eve:matrix.org", "!eve:matrix.org")
+        .await
+        .unwrap();
+    let removed = db
+        .prune_matrix_rooms(&["!eve:matrix.org".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(removed, 1);
+
+    let res = db.fetch_matrix_room("@bob:matrix.org").await.unwrap();
+    assert!(res.is_none());
+    let res = db.fetch_matrix_room("@eve:matrix.org").await.unwrap();
+    assert_eq!(res, Some("!eve:matrix.org".to_string()));
+}
+
+#[actix::test]
+async fn shared_messages_are_claimed_once() {
+    let (db, _, _, _) = new_env().await;
+    let id = MessageId::from(42u32);
+
+    assert!(db.claim_message("matrix", id).await.unwrap());
+    assert!(!db.claim_message("matrix", id).await.unwrap());
+
+    // IDs are scoped per service.
+    assert!(db.claim_message("twitter", id).await.unwrap());
+
+    assert_eq!(
+        db.prune_processed_messages(Timestamp::with_offset(1))
+            .await
+            .unwrap(),
+        2
+    );
+    assert!(db.claim_message("matrix", id).await.unwrap());
+}
@@ -0,0 +1,94 @@
+use super::*;
+use crate::message_rate_limit::MessageRateLimitConfig;
+use crate::notifier::EventCursor;
+use crate::primitives::{
+    ExpectedMessage, ExternalMessage, ExternalMessageType, JudgementState, MessageId,
+    NotificationMessage, Timestamp,
+};
+
+#[actix::test]
+async fn rate_limited_sender_is_ignored() {
+    let (db, _, _, _) = new_env().await;
+    let db = db.with_message_rate_limit(MessageRateLimitConfig {
+        attempts_per_hour: 2,
+        block_duration: None,
+    });
+    let mut cursor = EventCursor::new(&db, Default::default()).await.unwrap();
+
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+
+    let invalid = ExternalMessage {
+        origin: ExternalMessageType::Email("alice@email.com".to_string()),
+        id: MessageId::from(0u32),
+        timestamp: Timestamp::now(),
+        values: ExpectedMessage::random().to_message_parts(),
+        metadata: None,
+    };
+    for id in 0..4u32 {
+        db.verify_message(&ExternalMessage {
+            id: MessageId::from(id),
+            ..invalid.clone()
+        })
+        .await
+        .unwrap();
+    }
+
+    // Only the messages within the limit are counted.
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(state.get_field(&F::ALICE_EMAIL()).failed_attempts, 2);
+
+    let events = cursor.fetch(&db).await.unwrap();
+    let limited: Vec<_> = events
+        .iter()
+        .filter(|event| {
+            matches!(
+                &event.message,
+                NotificationMessage::MessagesRateLimited { context, field, .. }
+                    if context == &alice.context && field == &F::ALICE_EMAIL()
+            )
+        })
+        .collect();
+    assert_eq!(limited.len(), 1);
+
+    // Even valid messages are ignored while blocked.
+    let valid = ExternalMessage {
+        values: alice
+            .get_field(&F::ALICE_EMAIL())
+            .expected_message()
+            .to_message_parts(),
+        ..invalid
+    };
+    db.verify_message(&valid).await.unwrap();
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!state.get_field(&F::ALICE_EMAIL()).challenge.is_verified());
+
+    // Other senders are not affected.
+    let matrix = ExternalMessage {
+        origin: ExternalMessageType::Matrix("@alice:matrix.org".to_string()),
+        id: MessageId::from(1u32),
+        timestamp: Timestamp::now(),
+        values: alice
+            .get_field(&F::ALICE_MATRIX())
+            .expected_message()
+            .to_message_parts(),
+        metadata: None,
+    };
+    db.verify_message(&matrix).await.unwrap();
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(state.get_field(&F::ALICE_MATRIX()).challenge.is_verified());
+}
@@ -1,10 +1,11 @@
 use crate::adapters::tests::MessageInjector;
-use crate::adapters::AdapterListener;
+use crate::adapters::{AdapterListener, PollingInterval};
 use crate::api::{JsonResult, ResponseAccountState};
-use crate::connector::{AccountType, JudgementRequest, WatcherMessage};
+use crate::connector::WatcherMessage;
 use crate::database::Database;
 use crate::notifier::run_session_notifier;
 use crate::primitives::{IdentityContext, IdentityFieldValue};
+use crate::protocol::{AccountType, JudgementRequest};
 use crate::{api::tests::run_test_server, connector::tests::ConnectorMocker};
 use actix_codec::{AsyncRead, AsyncWrite, Framed};
 use actix_http::ws::Codec;
@@ -17,12 +18,52 @@ use serde::de::DeserializeOwned;
 use serde::Serialize;
 use tokio::time::{sleep, Duration};
 
+mod address_blocklist;
+mod admin_search;
 mod api_judgement_state;
+mod attestation;
 mod background_tasks;
+mod challenge_expiry;
+mod challenge_regeneration;
+mod completion_estimate;
+mod compliance_decisions;
+mod concurrent_transitions;
+mod credential_import;
+mod display_name_pool;
 mod display_name_verification;
+mod disposable_emails;
+mod event_dedup;
+mod event_log;
 mod explicit;
+mod failure_alerts;
+mod instance_registry;
+mod invalid_addresses;
+mod judge_now;
+mod judgement_feed;
 mod live_mocker;
+mod matrix_policy;
+mod matrix_rooms;
+mod message_rate_limit;
+mod notification_ack;
+mod nudges;
+mod outbound_archive;
+mod outbox;
+mod paused_adapters;
+mod paused_requests;
+mod paused_submissions;
 mod process_admin_cmds;
+mod request_withdrawal;
+mod reserved_display_names;
+mod retry_config;
+mod script_hook;
+mod settle_delay;
+mod shutdown;
+mod transparency_report;
+mod twitter_ids;
+mod unsupported_chains;
+mod verification_records;
+mod watermarks;
+mod web_challenges;
 
 // Convenience type
 pub type F = IdentityFieldValue;
@@ -91,12 +132,14 @@ async fn new_env() -> (Database, ConnectorMocker, TestServer, MessageInjector) {
 
     // Setup message verifier and injector.
     let injector = MessageInjector::new();
-    let listener = AdapterListener::new(db.clone()).await;
-    listener.start_message_adapter(injector.clone(), 1).await;
+    let listener = AdapterListener::new(db.clone(), Default::default()).await;
+    listener
+        .start_message_adapter(injector.clone(), PollingInterval::fixed(1), false)
+        .await;
 
     let t_db = db.clone();
     actix::spawn(async move {
-        run_session_notifier(t_db, actor).await;
+        run_session_notifier(t_db, actor, Duration::from_secs(0), Default::default()).await;
     });
 
     // Setup connector mocker
@@ -0,0 +1,77 @@
+use super::*;
+use crate::primitives::JudgementState;
+use actix_http::StatusCode;
+use serde_json::json;
+
+async fn acknowledge(api: &mut TestServer, context: &IdentityContext, sequence: u64) -> bool {
+    let mut res = api
+        .post("/api/acknowledge_notifications")
+        .send_json(&json!({
+            "context": context,
+            "sequence": sequence,
+        }))
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    match res.json::<JsonResult<bool>>().await.unwrap() {
+        JsonResult::Ok(acknowledged) => acknowledged,
+        JsonResult::Err(err) => panic!("{}", err),
+    }
+}
+
+#[actix::test]
+async fn acknowledge_notifications() {
+    let (db, _, mut api, _) = new_env().await;
+
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+
+    // The insertion created the first event.
+    let (sequence, read_sequence) = db
+        .fetch_notification_sequence(&alice.context)
+        .await
+        .unwrap();
+    assert!(sequence >= 1);
+    assert_eq!(read_sequence, 0);
+
+    // Sequence numbers are part of the account state.
+    let mut stream = api.ws_at("/api/account_status").await.unwrap();
+    stream.send(alice.context.to_ws()).await.unwrap();
+    let frame = match stream.next().await.unwrap().unwrap() {
+        Frame::Text(text) => serde_json::from_slice::<serde_json::Value>(&text).unwrap(),
+        _ => panic!(),
+    };
+    assert_eq!(frame["message"]["sequence"], json!(sequence));
+    assert_eq!(frame["message"]["read_sequence"], json!(0));
+
+    assert!(acknowledge(&mut api, &alice.context, sequence).await);
+    assert_eq!(
+        db.fetch_notification_sequence(&alice.context)
+            .await
+            .unwrap(),
+        (sequence, sequence)
+    );
+
+    // Acknowledging older notifications has no effect.
+    assert!(acknowledge(&mut api, &alice.context, 0).await);
+    assert_eq!(
+        db.fetch_notification_sequence(&alice.context)
+            .await
+            .unwrap(),
+        (sequence, sequence)
+    );
+
+    // Notifications which do not exist yet cannot be acknowledged.
+    assert!(!acknowledge(&mut api, &alice.context, sequence + 1).await);
+    assert!(!acknowledge(&mut api, &IdentityContext::bob(), 1).await);
+
+    // New events continue the sequence.
+    db.set_judged(&alice.context).await.unwrap();
+    assert_eq!(
+        db.fetch_notification_sequence(&alice.context)
+            .await
+            .unwrap(),
+        (sequence + 1, sequence)
+    );
+}
@@ -0,0 +1,159 @@
+use super::*;
+use crate::adapters::admin::RawFieldName;
+use crate::adapters::Adapter;
+use crate::primitives::{ExternalMessage, JudgementState, OutboxKind, Timestamp};
+use crate::Result;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+#[derive(Clone, Default)]
+struct EmailRecorder {
+    sent: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+#[async_trait]
+impl Adapter for EmailRecorder {
+    type MessageType = ();
+
+    fn name(&self) -> &'static str {
+        "email"
+    }
+    async fn fetch_messages(&mut self) -> Result<Vec<ExternalMessage>> {
+        Ok(vec![])
+    }
+    async fn send_message(&mut self, _to: &str, _content: Self::MessageType) -> Result<()> {
+        Ok(())
+    }
+    async fn send_notification(&mut self, to: &str, text: &str) -> Result<()> {
+        self.sent
+            .lock()
+            .await
+            .push((to.to_string(), text.to_string()));
+        Ok(())
+    }
+}
+
+#[actix::test]
+async fn nudge_last_unverified_field() {
+    let (db, _, _, _) = new_env().await;
+
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+
+    let before = Timestamp::with_offset(60);
+
+    // Multiple fields remain unverified.
+    db.verify_manually(&alice.context, &RawFieldName::DisplayName, false, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        db.schedule_nudges(before, Timestamp::now()).await.unwrap(),
+        0
+    );
+
+    db.verify_manually(&alice.context, &RawFieldName::Email, false, None)
+        .await
+        .unwrap();
+    db.verify_manually(&alice.context, &RawFieldName::Matrix, false, None)
+        .await
+        .unwrap();
+
+    // Requested too recently.
+    assert_eq!(
+        db.schedule_nudges(Timestamp::from(0), Timestamp::now())
+            .await
+            .unwrap(),
+        0
+    );
+
+    // Only Twitter remains, reminded via email and Matrix.
+    assert_eq!(
+        db.schedule_nudges(before, Timestamp::now()).await.unwrap(),
+        1
+    );
+
+    let entries = db.fetch_outbox_entries(&alice.context).await.unwrap();
+    let mut nudges: Vec<(String, F)> = entries
+        .into_iter()
+        .filter(|entry| entry.kind == OutboxKind::Nudge)
+        .map(|entry| (entry.adapter, entry.field))
+        .collect();
+    nudges.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(
+        nudges,
+        vec![
+            ("Matrix".to_string(), F::ALICE_MATRIX()),
+            ("email".to_string(), F::ALICE_EMAIL()),
+        ]
+    );
+
+    let state = db.fetch_judgement_state(&alice.context).await.unwrap();
+    assert!(state.unwrap().nudged_at.is_some());
+
+    // Sent only once.
+    assert_eq!(
+        db.schedule_nudges(before, Timestamp::now()).await.unwrap(),
+        0
+    );
+
+    let adapter = EmailRecorder::default();
+    AdapterListener::new(db.clone(), Default::default())
+        .await
+        .start_message_adapter(adapter.clone(), PollingInterval::fixed(1), false)
+        .await;
+    sleep(Duration::from_secs(3)).await;
+
+    let sent = adapter.sent.lock().await.clone();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].0, "alice@email.com");
+    assert!(sent[0].1.contains("only the twitter field"));
+
+    let archived = db.fetch_outbound_messages(&alice.context).await.unwrap();
+    assert!(archived
+        .iter()
+        .any(|message| message.delivered && message.content == sent[0].1));
+}
+
+#[actix::test]
+async fn drop_outdated_nudge() {
+    let (db, _, _, _) = new_env().await;
+
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+
+    for field in [
+        RawFieldName::DisplayName,
+        RawFieldName::Email,
+        RawFieldName::Matrix,
+    ] {
+        db.verify_manually(&alice.context, &field, false, None)
+            .await
+            .unwrap();
+    }
+
+    assert_eq!(
+        db.schedule_nudges(Timestamp::with_offset(60), Timestamp::now())
+            .await
+            .unwrap(),
+        1
+    );
+
+    // The last field is verified before the reminder is dispatched.
+    db.verify_manually(&alice.context, &RawFieldName::Twitter, true, None)
+        .await
+        .unwrap();
+
+    let adapter = EmailRecorder::default();
+    AdapterListener::new(db.clone(), Default::default())
+        .await
+        .start_message_adapter(adapter.clone(), PollingInterval::fixed(1), false)
+        .await;
+    sleep(Duration::from_secs(3)).await;
+
+    assert!(adapter.sent.lock().await.is_empty());
+    let entries = db.fetch_outbox_entries(&alice.context).await.unwrap();
+    assert!(entries
+        .iter()
+        .filter(|entry| entry.kind == OutboxKind::Nudge && entry.adapter == "email")
+        .all(|entry| entry.done));
+}
@@ -0,0 +1,99 @@
+use super::*;
+use crate::adapters::admin::{process_admin, Command, Response};
+use crate::primitives::{OutboundMessage, Timestamp};
+
+#[actix::test]
+async fn archive_outbound_messages() {
+    let (db, _, _, _) = new_env().await;
+
+    // Nothing archived yet.
+    let res = db
+        .fetch_outbound_messages(&IdentityContext::alice())
+        .await
+        .unwrap();
+    assert!(res.is_empty());
+
+    let sandboxed = OutboundMessage {
+        adapter: "email".to_string(),
+        context: IdentityContext::alice(),
+        to: "alice@email.com".to_string(),
+        field: Some("email".to_string()),
+        content: "1a2b3c".to_string(),
+        sandbox: true,
+        delivered: false,
+        error: None,
+        timestamp: Timestamp::now(),
+    };
+
+    let delivered = OutboundMessage {
+        sandbox: false,
+        delivered: true,
+        ..sandboxed.clone()
+    };
+
+    db.archive_outbound_message(&sandboxed).await.unwrap();
+    db.archive_outbound_message(&delivered).await.unwrap();
+
+    let res = db
+        .fetch_outbound_messages(&IdentityContext::alice())
+        .await
+        .unwrap();
+    assert_eq!(res, vec![sandboxed, delivered]);
+
+    // Messages are tracked per identity.
+    let res = db
+        .fetch_outbound_messages(&IdentityContext::bob())
+        .await
+        .unwrap();
+    assert!(res.is_empty());
+}
+
+#[actix::test]
+async fn prune_outbound_messages() {
+    let (db, _, _, _) = new_env().await;
+
+    let now = Timestamp::now().raw();
+    let old = OutboundMessage {
+        adapter: "email".to_string(),
+        context: IdentityContext::alice(),
+        to: "alice@email.com".to_string(),
+        field: Some("email".to_string()),
+        content: "1a2b3c".to_string(),
+        sandbox: false,
+        delivered: false,
+        error: Some("connection refused".to_string()),
+        timestamp: Timestamp::from(now - 86_400 * 30),
+    };
+
+    let recent = OutboundMessage {
+        delivered: true,
+        error: None,
+        timestamp: Timestamp::from(now),
+        ..old.clone()
+    };
+
+    db.archive_outbound_message(&recent).await.unwrap();
+    db.archive_outbound_message(&old).await.unwrap();
+
+    // Messages are returned oldest first.
+    let res = db
+        .fetch_outbound_messages(&IdentityContext::alice())
+        .await
+        .unwrap();
+    assert_eq!(res, vec![old.clone(), recent.clone()]);
+
+    let resp = process_admin(&db, Command::Sent(IdentityContext::alice())).await;
+    assert_eq!(resp, Response::SentMessages(vec![old, recent.clone()]));
+
+    let pruned = db
+        .prune_outbound_messages(Timestamp::from(now - 86_400 * 7))
+        .await
+        .unwrap();
+    assert_eq!(pruned, 1);
+
+    let res = db
+        .fetch_outbound_messages(&IdentityContext::alice())
+        .await
+        .unwrap();
+    assert_eq!(res, vec![recent]);
+}
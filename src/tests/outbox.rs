@@ -0,0 +1,173 @@
+use super::*;
+use crate::adapters::email::SecondChallengeCodeConfig;
+use crate::adapters::Adapter;
+use crate::primitives::{
+    ExpectedMessage, ExternalMessage, ExternalMessageType, JudgementState, MessageId, OutboxKind,
+    Timestamp,
+};
+use crate::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+#[derive(Clone, Default)]
+struct EmailRecorder {
+    sent: Arc<Mutex<Vec<(String, ExpectedMessage)>>>,
+    fail: Arc<AtomicBool>,
+}
+
+#[async_trait]
+impl Adapter for EmailRecorder {
+    type MessageType = ExpectedMessage;
+
+    fn name(&self) -> &'static str {
+        "email"
+    }
+    async fn fetch_messages(&mut self) -> Result<Vec<ExternalMessage>> {
+        Ok(vec![])
+    }
+    async fn send_message(&mut self, to: &str, content: Self::MessageType) -> Result<()> {
+        if self.fail.load(Ordering::Relaxed) {
+            return Err(anyhow!("connection refused"));
+        }
+
+        self.sent.lock().await.push((to.to_string(), content));
+        Ok(())
+    }
+}
+
+async fn verify_first_email_challenge(db: &Database) -> JudgementState {
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+
+    db.verify_message(&ExternalMessage {
+        origin: ExternalMessageType::Email("alice@email.com".to_string()),
+        id: MessageId::from(0u32),
+        timestamp: Timestamp::now(),
+        values: alice
+            .get_field(&F::ALICE_EMAIL())
+            .expected_message()
+            .to_message_parts(),
+        metadata: None,
+    })
+    .await
+    .unwrap();
+
+    alice
+}
+
+async fn start_email_adapter(db: &Database, adapter: EmailRecorder) {
+    AdapterListener::new(db.clone(), Default::default())
+        .await
+        .start_message_adapter(adapter, PollingInterval::fixed(1), false)
+        .await;
+}
+
+#[actix::test]
+async fn dispatch_second_challenge() {
+    let (db, _, _, _) = new_env().await;
+    let adapter = EmailRecorder::default();
+
+    // The entry is written with the state change, even if no adapter runs.
+    let alice = verify_first_email_challenge(&db).await;
+    let entries = db.fetch_outbox_entries(&alice.context).await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].adapter, "email");
+    assert_eq!(entries[0].kind, OutboxKind::SecondChallenge);
+    assert_eq!(entries[0].field, F::ALICE_EMAIL());
+    assert!(!entries[0].done);
+
+    start_email_adapter(&db, adapter.clone()).await;
+    sleep(Duration::from_secs(3)).await;
+
+    let sent = adapter.sent.lock().await.clone();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].0, "alice@email.com");
+    assert_eq!(
+        &sent[0].1,
+        alice.get_field(&F::ALICE_EMAIL()).expected_second()
+    );
+
+    let entries = db.fetch_outbox_entries(&alice.context).await.unwrap();
+    assert!(entries[0].done);
+    assert_eq!(entries[0].attempts, 0);
+
+    let archived = db.fetch_outbound_messages(&alice.context).await.unwrap();
+    assert_eq!(archived.len(), 1);
+    assert!(archived[0].delivered);
+}
+
+#[actix::test]
+async fn retry_failed_dispatch() {
+    let (db, _, _, _) = new_env().await;
+    let adapter = EmailRecorder::default();
+    adapter.fail.store(true, Ordering::Relaxed);
+    start_email_adapter(&db, adapter.clone()).await;
+
+    let alice = verify_first_email_challenge(&db).await;
+    sleep(Duration::from_secs(3)).await;
+
+    // Retried later instead of being dropped.
+    let entries = db.fetch_outbox_entries(&alice.context).await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert!(!entries[0].done);
+    assert_eq!(entries[0].attempts, 1);
+    assert!(entries[0].locked_until.unwrap().raw() > Timestamp::now().raw());
+    assert!(db.claim_outbox_entry("email", 60).await.unwrap().is_none());
+
+    let archived = db.fetch_outbound_messages(&alice.context).await.unwrap();
+    assert_eq!(archived.len(), 1);
+    assert_eq!(archived[0].error.as_deref(), Some("connection refused"));
+}
+
+#[actix::test]
+async fn retried_dispatch_resends_challenge() {
+    let (db, _, _, _) = new_env().await;
+    // Codes are replaced whenever a second challenge is issued.
+    let db = db.with_second_challenge_codes(SecondChallengeCodeConfig {
+        digits: Some(8),
+        ttl: None,
+    });
+    let adapter = EmailRecorder::default();
+    adapter.fail.store(true, Ordering::Relaxed);
+    start_email_adapter(&db, adapter.clone()).await;
+
+    let alice = verify_first_email_challenge(&db).await;
+    sleep(Duration::from_secs(3)).await;
+
+    let entries = db.fetch_outbox_entries(&alice.context).await.unwrap();
+    assert_eq!(entries[0].attempts, 1);
+    let issued = entries[0].challenge.clone().unwrap();
+    assert!(issued.is_code());
+
+    // Deliver on the next attempt, without waiting for the retry delay.
+    adapter.fail.store(false, Ordering::Relaxed);
+    db.retry_outbox_entry(&entries[0].id, Timestamp::now())
+        .await
+        .unwrap();
+    sleep(Duration::from_secs(3)).await;
+
+    let sent = adapter.sent.lock().await.clone();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].1.value, issued.value);
+
+    let entries = db.fetch_outbox_entries(&alice.context).await.unwrap();
+    assert!(entries[0].done);
+    assert_eq!(entries[0].challenge.as_ref(), Some(&issued));
+
+    // The delivered code is the one accepted by the challenger.
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        state.get_field(&F::ALICE_EMAIL()).expected_second().value,
+        issued.value
+    );
+
+    let archived = db.fetch_outbound_messages(&alice.context).await.unwrap();
+    assert_eq!(archived.len(), 2);
+    assert_eq!(archived[0].error.as_deref(), Some("connection refused"));
+    assert!(archived[1].delivered);
+}
@@ -0,0 +1,85 @@
+use super::*;
+use crate::adapters::admin::{process_admin, process_admin_with_approval, Command, Response};
+use crate::adapters::matrix::MatrixHandle;
+use crate::primitives::{
+    ExternalMessage, ExternalMessageType, JudgementState, MessageId, Timestamp,
+};
+
+#[actix::test]
+async fn pause_and_resume_adapter() {
+    let (db, _, _, _) = new_env().await;
+    let admin = MatrixHandle::from("@admin1:matrix.org");
+
+    let cmd = Command::PauseAdapter("twitter".to_string());
+    let resp = process_admin_with_approval(&db, cmd.clone(), &admin, None).await;
+    assert_eq!(resp, Response::AdapterPaused("twitter".to_string()));
+    let resp = process_admin_with_approval(&db, cmd, &admin, None).await;
+    assert_eq!(resp, Response::AdapterAlreadyPaused);
+
+    assert!(db.is_adapter_paused("twitter").await.unwrap());
+    assert!(!db.is_adapter_paused("matrix").await.unwrap());
+
+    let paused = db.fetch_paused_adapters().await.unwrap();
+    assert_eq!(paused.len(), 1);
+    assert_eq!(paused[0].paused_by, admin);
+
+    let cmd = Command::ResumeAdapter("twitter".to_string());
+    let resp = process_admin(&db, cmd.clone()).await;
+    assert_eq!(resp, Response::AdapterResumed("twitter".to_string()));
+    let resp = process_admin(&db, cmd).await;
+    assert_eq!(resp, Response::AdapterNotPaused);
+
+    assert!(!db.is_adapter_paused("twitter").await.unwrap());
+}
+
+#[actix::test]
+async fn paused_adapter_does_not_fetch_messages() {
+    let (db, _, _, injector) = new_env().await;
+    let admin = MatrixHandle::from("@admin1:matrix.org");
+
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+    let alice = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+
+    db.pause_adapter("test_state_injector", &admin)
+        .await
+        .unwrap();
+    sleep(Duration::from_secs(2)).await;
+
+    injector
+        .send(ExternalMessage {
+            origin: ExternalMessageType::Matrix("@alice:matrix.org".to_string()),
+            id: MessageId::from(0u32),
+            timestamp: Timestamp::now(),
+            values: alice
+                .get_field(&F::ALICE_MATRIX())
+                .expected_message()
+                .to_message_parts(),
+            metadata: None,
+        })
+        .await;
+
+    sleep(Duration::from_secs(3)).await;
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!state.get_field(&F::ALICE_MATRIX()).challenge.is_verified());
+
+    // The message is processed once resumed.
+    db.resume_adapter("test_state_injector").await.unwrap();
+    sleep(Duration::from_secs(3)).await;
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(state.get_field(&F::ALICE_MATRIX()).challenge.is_verified());
+}
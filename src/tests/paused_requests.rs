@@ -0,0 +1,78 @@
+use super::*;
+use crate::adapters::admin::{process_admin_with_approval, Command, Response};
+use crate::adapters::matrix::MatrixHandle;
+use crate::primitives::ChainName;
+
+#[actix::test]
+async fn paused_requests_are_ignored() {
+    let (db, mut connector, _api, _) = new_env().await;
+    let admin = MatrixHandle::from("@admin1:matrix.org");
+    let alice = IdentityContext::alice();
+    let bob = IdentityContext::bob();
+
+    // Bob was accepted before new requests were paused.
+    connector.inject(bob_judgement_request()).await;
+    sleep(Duration::from_millis(500)).await;
+    assert!(db.fetch_judgement_state(&bob).await.unwrap().is_some());
+
+    let cmd = Command::PauseRequests(None);
+    let resp = process_admin_with_approval(&db, cmd.clone(), &admin, None).await;
+    assert_eq!(resp, Response::RequestsPaused(None));
+    let resp = process_admin_with_approval(&db, cmd, &admin, None).await;
+    assert_eq!(resp, Response::RequestsAlreadyPaused);
+
+    let paused = db.fetch_paused_requests().await.unwrap();
+    assert_eq!(paused.len(), 1);
+    assert_eq!(paused[0].chain, None);
+    assert_eq!(paused[0].paused_by, admin);
+    assert!(!db.accepts_requests(ChainName::Kusama).await.unwrap());
+
+    connector.inject(alice_judgement_request()).await;
+
+    // Edits of accepted identities are still processed.
+    let mut request = JudgementRequest::bob();
+    request.accounts.remove(&AccountType::Email);
+    connector
+        .inject(WatcherMessage::new_judgement_request(request))
+        .await;
+    sleep(Duration::from_millis(500)).await;
+
+    assert!(db.fetch_judgement_state(&alice).await.unwrap().is_none());
+    let state = db.fetch_judgement_state(&bob).await.unwrap().unwrap();
+    assert!(!state
+        .fields
+        .iter()
+        .any(|field| matches!(field.value, IdentityFieldValue::Email(_))));
+
+    // Resume requests, the next request of Alice is accepted.
+    let cmd = Command::ResumeRequests(None);
+    let resp = process_admin_with_approval(&db, cmd.clone(), &admin, None).await;
+    assert_eq!(resp, Response::RequestsResumed(None));
+    let resp = process_admin_with_approval(&db, cmd, &admin, None).await;
+    assert_eq!(resp, Response::RequestsNotPaused);
+
+    connector.inject(alice_judgement_request()).await;
+    sleep(Duration::from_millis(500)).await;
+
+    assert!(db.fetch_judgement_state(&alice).await.unwrap().is_some());
+}
+
+#[actix::test]
+async fn paused_requests_per_chain() {
+    let (db, _, _, _) = new_env().await;
+    let admin = MatrixHandle::from("@admin1:matrix.org");
+
+    assert!(db
+        .pause_requests(Some(ChainName::Polkadot), &admin)
+        .await
+        .unwrap());
+    assert!(!db.accepts_requests(ChainName::Polkadot).await.unwrap());
+    assert!(db.accepts_requests(ChainName::Kusama).await.unwrap());
+    assert!(!db.resume_requests(None).await.unwrap());
+
+    // Requests refused by config can not be resumed by admins.
+    let db = db.with_refused_requests(vec![ChainName::Kusama]);
+    assert!(db.resume_requests(Some(ChainName::Polkadot)).await.unwrap());
+    assert!(db.accepts_requests(ChainName::Polkadot).await.unwrap());
+    assert!(!db.accepts_requests(ChainName::Kusama).await.unwrap());
+}
@@ -0,0 +1,79 @@
+use super::*;
+use crate::adapters::admin::{process_admin_with_approval, Command, Response};
+use crate::adapters::matrix::MatrixHandle;
+use crate::notifier::EventCursor;
+use crate::primitives::{
+    ChainName, JudgementState, NotificationMessage, Timestamp, WithheldReason,
+};
+
+#[actix::test]
+async fn paused_chain_has_no_judgement_candidates() {
+    let (db, _, _, _) = new_env().await;
+    let admin = MatrixHandle::from("@admin1:matrix.org");
+    let mut cursor = EventCursor::new(&db, Default::default()).await.unwrap();
+
+    let mut alice = JudgementState::alice();
+    alice.is_fully_verified = true;
+    alice.judgement_submitted = false;
+    alice.issue_judgement_at = Some(Timestamp::from(Timestamp::now().raw() - 10));
+    db.add_judgement_request(&alice).await.unwrap();
+
+    let candidates = db
+        .fetch_judgement_candidates(ChainName::Polkadot)
+        .await
+        .unwrap();
+    assert_eq!(candidates.len(), 1);
+
+    // Pause submissions.
+    let cmd = Command::PauseSubmissions(ChainName::Polkadot);
+    let resp = process_admin_with_approval(&db, cmd.clone(), &admin, None).await;
+    assert_eq!(resp, Response::SubmissionsPaused(ChainName::Polkadot));
+    let resp = process_admin_with_approval(&db, cmd, &admin, None).await;
+    assert_eq!(resp, Response::SubmissionsAlreadyPaused);
+
+    let paused = db.fetch_paused_chains().await.unwrap();
+    assert_eq!(paused.len(), 1);
+    assert_eq!(paused[0].chain, ChainName::Polkadot);
+    assert_eq!(paused[0].paused_by, admin);
+
+    assert!(db
+        .fetch_judgement_candidates(ChainName::Polkadot)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // The reason is attached to the state and announced once.
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        state.judgement_withheld,
+        Some(WithheldReason::SubmissionsPaused)
+    );
+
+    let events = cursor.fetch(&db).await.unwrap();
+    let withheld: Vec<_> = events
+        .into_iter()
+        .filter(|event| matches!(event.message, NotificationMessage::JudgementWithheld { .. }))
+        .collect();
+    assert_eq!(withheld.len(), 1);
+    // Other networks are not affected.
+    assert!(!db.is_submission_paused(ChainName::Kusama).await.unwrap());
+
+    // Resume submissions, the candidate is returned again.
+    let cmd = Command::ResumeSubmissions(ChainName::Polkadot);
+    let resp = process_admin_with_approval(&db, cmd.clone(), &admin, None).await;
+    assert_eq!(resp, Response::SubmissionsResumed(ChainName::Polkadot));
+    let resp = process_admin_with_approval(&db, cmd, &admin, None).await;
+    assert_eq!(resp, Response::SubmissionsNotPaused);
+
+    let candidates = db
+        .fetch_judgement_candidates(ChainName::Polkadot)
+        .await
+        .unwrap();
+    assert_eq!(candidates.len(), 1);
+    assert_eq!(candidates[0].context, alice.context);
+    assert_eq!(candidates[0].judgement_withheld, None);
+}
@@ -1,10 +1,15 @@
 use super::*;
-use crate::adapters::admin::{process_admin, Command, RawFieldName, Response};
+use crate::adapters::admin::{
+    process_admin, process_admin_with_approval, Command, RawFieldName, Response,
+};
+use crate::adapters::matrix::MatrixHandle;
 use crate::api::{JsonResult, ResponseAccountState};
 use crate::primitives::{
-    IdentityContext, IdentityFieldValue, JudgementStateBlanked, NotificationMessage,
+    IdentityContext, IdentityFieldValue, JudgementStateBlanked, JudgementType, NotificationMessage,
 };
+use crate::protocol::{AccountType, JudgementRequest};
 use futures::{FutureExt, StreamExt};
+use std::collections::HashMap;
 
 #[actix::test]
 async fn command_status() {
@@ -16,7 +21,7 @@ async fn command_status() {
     let alice = states[0].clone();
 
     // Request status.
-    let res = process_admin(&db, Command::Status(alice.context.address.clone())).await;
+    let res = process_admin(&db, Command::Status(alice.context.clone())).await;
     assert_eq!(res, Response::Status(JudgementStateBlanked::from(alice)));
 }
 
@@ -43,7 +48,7 @@ async fn command_verify_multiple_challenge_types() {
     let resp = process_admin(
         &db,
         Command::Verify(
-            alice.context.address.clone(),
+            alice.context.clone(),
             vec![RawFieldName::DisplayName, RawFieldName::Email],
         ),
     )
@@ -100,7 +105,7 @@ async fn command_verify_multiple_challenge_types() {
     // Manually verify twitter field.
     let resp = process_admin(
         &db,
-        Command::Verify(alice.context.address.clone(), vec![RawFieldName::Twitter]),
+        Command::Verify(alice.context.clone(), vec![RawFieldName::Twitter]),
     )
     .await;
 
@@ -160,7 +165,7 @@ async fn command_verify_unsupported_field() {
     // Manually verify.
     let resp = process_admin(
         &db,
-        Command::Verify(alice.context.address.clone(), vec![RawFieldName::Web]),
+        Command::Verify(alice.context.clone(), vec![RawFieldName::Web]),
     )
     .await;
 
@@ -213,7 +218,7 @@ async fn command_verify_all() {
     // Manually verify.
     let resp = process_admin(
         &db,
-        Command::Verify(alice.context.address.clone(), vec![RawFieldName::All]),
+        Command::Verify(alice.context.clone(), vec![RawFieldName::All]),
     )
     .await;
 
@@ -277,6 +282,66 @@ async fn command_verify_all() {
     assert!(stream.next().now_or_never().is_none());
 }
 
+#[actix::test]
+async fn command_erroneous() {
+    let (db, connector, mut api, _) = new_env().await;
+    let mut stream = api.ws_at("/api/account_status").await.unwrap();
+
+    // Unknown identity.
+    let resp = process_admin(&db, Command::Erroneous(IdentityContext::alice(), None)).await;
+    assert_eq!(resp, Response::IdentityNotFound);
+
+    connector.inject(alice_judgement_request()).await;
+    let states = connector.inserted_states().await;
+    let mut alice = states[0].clone();
+
+    let resp = subscribe_context(&mut stream, IdentityContext::alice()).await;
+    assert_eq!(
+        resp,
+        JsonResult::Ok(ResponseAccountState::with_no_notifications(alice.clone()))
+    );
+
+    let resp = process_admin(
+        &db,
+        Command::Erroneous(alice.context.clone(), Some("impersonation".to_string())),
+    )
+    .await;
+    assert_eq!(
+        resp,
+        Response::JudgedErroneous(alice.context.address.clone())
+    );
+
+    // The fields are left as they are.
+    alice.is_fully_verified = true;
+    alice.erroneous_reason = Some("impersonation".to_string());
+
+    let resp: JsonResult<ResponseAccountState> = stream.next().await.into();
+    assert_eq!(
+        resp,
+        JsonResult::Ok(ResponseAccountState {
+            state: alice.clone().into(),
+            notifications: vec![NotificationMessage::JudgedErroneous {
+                context: alice.context.clone(),
+                reason: "impersonation".to_string(),
+            }],
+        })
+    );
+
+    // Submitted with the next run.
+    sleep(Duration::from_secs(1)).await;
+    let candidates = db
+        .fetch_judgement_candidates(alice.context.chain)
+        .await
+        .unwrap();
+    assert_eq!(candidates.len(), 1);
+    assert!(candidates[0].erroneous_reason.is_some());
+
+    // Judged identities cannot be judged again.
+    db.set_judged(&alice.context).await.unwrap();
+    let resp = process_admin(&db, Command::Erroneous(alice.context.clone(), None)).await;
+    assert_eq!(resp, Response::IdentityNotFound);
+}
+
 #[actix::test]
 async fn command_verify_missing_field() {
     let (db, connector, mut api, _) = new_env().await;
@@ -305,7 +370,7 @@ async fn command_verify_missing_field() {
     // Manually verify a field that does not exist.
     let resp = process_admin(
         &db,
-        Command::Verify(alice.context.address.clone(), vec![RawFieldName::Email]),
+        Command::Verify(alice.context.clone(), vec![RawFieldName::Email]),
     )
     .await;
 
@@ -314,3 +379,222 @@ async fn command_verify_missing_field() {
     // Empty stream.
     assert!(stream.next().now_or_never().is_none());
 }
+
+#[actix::test]
+async fn command_verify_all_with_approval() {
+    let (db, connector, mut api, _) = new_env().await;
+    let mut stream = api.ws_at("/api/account_status").await.unwrap();
+
+    let (admin1, admin2) = (
+        MatrixHandle::from("@admin1:matrix.org"),
+        MatrixHandle::from("@admin2:matrix.org"),
+    );
+
+    // Insert judgement request.
+    connector.inject(alice_judgement_request()).await;
+    let states = connector.inserted_states().await;
+    let alice = states[0].clone();
+
+    // Subscribe to endpoint.
+    let resp = subscribe_context(&mut stream, IdentityContext::alice()).await;
+
+    // Check current state.
+    assert_eq!(
+        resp,
+        JsonResult::Ok(ResponseAccountState::with_no_notifications(alice.clone()))
+    );
+
+    // Propose full verification.
+    let cmd = Command::Verify(alice.context.clone(), vec![RawFieldName::All]);
+    let resp = process_admin_with_approval(&db, cmd, &admin1, Some(60)).await;
+
+    let approval = match resp {
+        Response::ApprovalRequested(approval) => approval,
+        _ => panic!(),
+    };
+
+    // Nothing was applied yet.
+    assert!(stream.next().now_or_never().is_none());
+
+    // The approval is pending.
+    let resp = process_admin_with_approval(&db, Command::Approvals, &admin2, Some(60)).await;
+    assert_eq!(resp, Response::PendingApprovals(vec![approval.clone()]));
+
+    // The proposer cannot approve its own action.
+    let cmd = Command::Approve(approval.id.clone());
+    let resp = process_admin_with_approval(&db, cmd, &admin1, Some(60)).await;
+    assert_eq!(resp, Response::ApprovalSameAdmin);
+
+    // A second admin approves the action.
+    let cmd = Command::Approve(approval.id.clone());
+    let resp = process_admin_with_approval(&db, cmd, &admin2, Some(60)).await;
+    assert_eq!(resp, Response::FullyVerified(alice.context.address.clone()));
+
+    // Expected event on stream.
+    let resp: JsonResult<ResponseAccountState> = stream.next().await.into();
+    match resp {
+        JsonResult::Ok(resp) => {
+            assert!(resp.state.is_fully_verified);
+            assert_eq!(
+                resp.notifications,
+                vec![NotificationMessage::FullManualVerification {
+                    context: alice.context.clone(),
+                }]
+            );
+        }
+        _ => panic!(),
+    }
+
+    // The approval can only be used once.
+    let cmd = Command::Approve(approval.id.clone());
+    let resp = process_admin_with_approval(&db, cmd, &admin2, Some(60)).await;
+    assert_eq!(resp, Response::ApprovalNotFound);
+
+    // Empty stream.
+    assert!(stream.next().now_or_never().is_none());
+}
+
+#[actix::test]
+async fn command_reviews_unsupported_fields_only() {
+    let (db, mut connector, _api, _) = new_env().await;
+
+    // Insert judgement request which only contains unsupported fields.
+    connector
+        .inject(WatcherMessage::new_judgement_request(JudgementRequest {
+            address: IdentityContext::alice().address,
+            accounts: HashMap::from([
+                (AccountType::LegalName, "Alice".to_string()),
+                (AccountType::Web, "alice.com".to_string()),
+            ]),
+            block_number: None,
+            network: None,
+        }))
+        .await;
+
+    let states = connector.inserted_states().await;
+    let alice = states[0].clone();
+    assert!(alice.requires_manual_review);
+
+    // Insert a regular judgement request.
+    connector.inject(bob_judgement_request()).await;
+
+    // The Watcher was informed about the manual review.
+    sleep(Duration::from_millis(500)).await;
+    let (_out, counter) = connector.outgoing();
+    assert_eq!(counter.notify_manual_review, 1);
+
+    // Only the identity requiring a manual review is listed.
+    let resp = process_admin(&db, Command::Reviews).await;
+    assert_eq!(resp, Response::ManualReviews(vec![alice.clone()]));
+
+    // Fully verified identities are removed from the list.
+    let resp = process_admin(
+        &db,
+        Command::Verify(alice.context.clone(), vec![RawFieldName::All]),
+    )
+    .await;
+    assert_eq!(resp, Response::FullyVerified(alice.context.address.clone()));
+
+    let resp = process_admin(&db, Command::Reviews).await;
+    assert_eq!(resp, Response::ManualReviews(vec![]));
+}
+
+#[actix::test]
+async fn command_diag() {
+    let (db, connector, _api, _) = new_env().await;
+
+    connector.inject(alice_judgement_request()).await;
+    connector.inject(bob_judgement_request()).await;
+
+    let resp = process_admin(&db, Command::Diagnostics).await;
+    let diag = match resp {
+        Response::Diagnostics(diag) => diag,
+        resp => panic!("unexpected response: {:?}", resp),
+    };
+
+    assert!(diag.db_connected);
+    let pending = diag.pending.unwrap();
+    assert_eq!(pending.per_chain.get("polkadot"), Some(&2));
+    assert_eq!(pending.unverified_fields.get("email"), Some(&2));
+    assert_eq!(pending.unverified_fields.get("matrix"), Some(&2));
+}
+
+#[actix::test]
+async fn command_explain() {
+    let (db, connector, _api, _) = new_env().await;
+
+    let resp = process_admin(&db, Command::Explain(IdentityContext::alice())).await;
+    assert_eq!(resp, Response::IdentityNotFound);
+
+    connector.inject(alice_judgement_request()).await;
+    let alice = connector.inserted_states().await[0].clone();
+    let alice = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let resp = process_admin(&db, Command::Explain(alice.context.clone())).await;
+    let explanation = match resp {
+        Response::Explanation(explanation) => explanation,
+        resp => panic!("unexpected response: {:?}", resp),
+    };
+
+    assert_eq!(explanation.context, alice.context);
+    assert_eq!(explanation.fields.len(), alice.fields.len());
+    assert!(explanation.decision.starts_with("waiting for the user"));
+
+    // Nothing was modified.
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(state, alice);
+}
+
+#[actix::test]
+async fn command_set_judgement() {
+    let (db, connector, _, _) = new_env().await;
+
+    // Unknown identity.
+    let resp = process_admin(
+        &db,
+        Command::SetJudgement(IdentityContext::alice(), JudgementType::KnownGood),
+    )
+    .await;
+    assert_eq!(resp, Response::IdentityNotFound);
+
+    connector.inject(alice_judgement_request()).await;
+    let states = connector.inserted_states().await;
+    let alice = states[0].clone();
+    assert_eq!(alice.judgement_type, JudgementType::Reasonable);
+
+    let resp = process_admin(
+        &db,
+        Command::SetJudgement(alice.context.clone(), JudgementType::KnownGood),
+    )
+    .await;
+    assert_eq!(
+        resp,
+        Response::JudgementTypeSet(alice.context.address.clone(), JudgementType::KnownGood)
+    );
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(state.judgement_type, JudgementType::KnownGood);
+    // The verification status is not affected.
+    assert!(!state.is_fully_verified);
+
+    // Judged identities cannot be changed.
+    db.set_judged(&alice.context).await.unwrap();
+    let resp = process_admin(
+        &db,
+        Command::SetJudgement(alice.context.clone(), JudgementType::LowQuality),
+    )
+    .await;
+    assert_eq!(resp, Response::IdentityNotFound);
+}
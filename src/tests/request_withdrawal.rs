@@ -0,0 +1,73 @@
+use super::*;
+use crate::api::WithdrawalNonce;
+use crate::primitives::Timestamp;
+
+#[actix::test]
+async fn withdrawal_nonces_are_single_use() {
+    let (db, _, _, _) = new_env().await;
+    let alice = IdentityContext::alice();
+
+    let nonce = WithdrawalNonce {
+        context: alice.clone(),
+        nonce: "abcd".to_string(),
+        expires_at: Timestamp::with_offset(60),
+    };
+    db.insert_withdrawal_nonce(&nonce).await.unwrap();
+
+    // Only valid for the same context.
+    assert!(!db
+        .consume_withdrawal_nonce(&IdentityContext::bob(), "abcd")
+        .await
+        .unwrap());
+    assert!(db.consume_withdrawal_nonce(&alice, "abcd").await.unwrap());
+    assert!(!db.consume_withdrawal_nonce(&alice, "abcd").await.unwrap());
+
+    // Expired nonce.
+    let nonce = WithdrawalNonce {
+        context: alice.clone(),
+        nonce: "efgh".to_string(),
+        expires_at: Timestamp::now(),
+    };
+    db.insert_withdrawal_nonce(&nonce).await.unwrap();
+    assert!(!db.consume_withdrawal_nonce(&alice, "efgh").await.unwrap());
+}
+
+#[actix::test]
+async fn withdrawn_request_is_reported_to_watcher() {
+    let (db, mut connector, _api, _) = new_env().await;
+    let alice = IdentityContext::alice();
+
+    // Nothing to withdraw yet.
+    assert!(!db.withdraw_judgement_request(&alice).await.unwrap());
+
+    // Insert judgement request.
+    connector.inject(alice_judgement_request()).await;
+    sleep(Duration::from_millis(500)).await;
+    assert!(db.fetch_judgement_state(&alice).await.unwrap().is_some());
+
+    assert!(db.withdraw_judgement_request(&alice).await.unwrap());
+    assert!(db.fetch_judgement_state(&alice).await.unwrap().is_none());
+    assert!(!db.withdraw_judgement_request(&alice).await.unwrap());
+
+    // The request is still listed as pending by the Watcher, which is
+    // informed about the withdrawal instead.
+    let pending = || WatcherMessage::PendingJudgementsRequests(vec![JudgementRequest::alice()]);
+    connector.inject(pending()).await;
+    connector.inject(pending()).await;
+    sleep(Duration::from_millis(500)).await;
+    assert!(db.fetch_judgement_state(&alice).await.unwrap().is_none());
+
+    let (_out, counter) = connector.outgoing();
+    assert_eq!(counter.notify_withdrawn, 2);
+
+    // A new request starts over.
+    connector.inject(alice_judgement_request()).await;
+    sleep(Duration::from_millis(500)).await;
+    assert!(db.fetch_judgement_state(&alice).await.unwrap().is_some());
+    assert!(!db.check_withdrawn(&alice).await.unwrap());
+
+    connector.inject(pending()).await;
+    sleep(Duration::from_millis(500)).await;
+    let (_out, counter) = connector.outgoing();
+    assert_eq!(counter.notify_withdrawn, 0);
+}
@@ -0,0 +1,103 @@
+use super::*;
+use crate::adapters::admin::{process_admin_with_approval, Command, Response};
+use crate::adapters::matrix::MatrixHandle;
+use crate::display_name::DisplayNameVerifier;
+use crate::primitives::{ChallengeType, JudgementState};
+use crate::reserved_names::ReservedKind;
+use crate::DisplayNameConfig;
+
+fn config(reserved_names: Vec<&str>) -> DisplayNameConfig {
+    DisplayNameConfig {
+        enabled: true,
+        limit: 0.85,
+        confusables: None,
+        reserved_names: Some(reserved_names.into_iter().map(String::from).collect()),
+        reserved_patterns: None,
+    }
+}
+
+fn display_name_check(state: &JudgementState) -> (bool, Option<String>) {
+    match &state.get_field(&F::ALICE_DISPLAY_NAME()).challenge {
+        ChallengeType::DisplayNameCheck {
+            passed, reserved, ..
+        } => (*passed, reserved.clone()),
+        _ => panic!(),
+    }
+}
+
+#[actix::test]
+async fn reserved_by_config() {
+    let (db, _, _, _) = new_env().await;
+    let verifier = DisplayNameVerifier::new(db.clone(), config(vec!["Alice"]));
+
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+    verifier.verify_display_name(&alice).await.unwrap();
+
+    let state = db.fetch_judgement_state(&alice.context).await.unwrap();
+    assert_eq!(
+        display_name_check(&state.unwrap()),
+        (false, Some("Alice".to_string()))
+    );
+
+    // Lookalikes are reserved as well.
+    assert_eq!(
+        verifier
+            .check_reserved("\u{0410}lic\u{0435}")
+            .await
+            .unwrap(),
+        Some("Alice".to_string())
+    );
+    assert_eq!(verifier.check_reserved("Bob").await.unwrap(), None);
+
+    // Not listed by the admin command.
+    let admin = MatrixHandle::from("@admin1:matrix.org");
+    let resp = process_admin_with_approval(&db, Command::Reserved, &admin, None).await;
+    assert_eq!(resp, Response::ReservedDisplayNames(vec![]));
+}
+
+#[actix::test]
+async fn reserved_by_admin() {
+    let (db, _, _, _) = new_env().await;
+    let verifier = DisplayNameVerifier::new(db.clone(), config(vec![]));
+    let admin = MatrixHandle::from("@admin1:matrix.org");
+
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+
+    let cmd = Command::ReservePattern("^ali".to_string());
+    let resp = process_admin_with_approval(&db, cmd.clone(), &admin, None).await;
+    assert_eq!(resp, Response::DisplayNameReserved("^ali".to_string()));
+    let resp = process_admin_with_approval(&db, cmd, &admin, None).await;
+    assert_eq!(resp, Response::DisplayNameAlreadyReserved);
+
+    let resp = process_admin_with_approval(&db, Command::Reserved, &admin, None).await;
+    match resp {
+        Response::ReservedDisplayNames(reserved) => {
+            assert_eq!(reserved.len(), 1);
+            assert_eq!(reserved[0].kind, ReservedKind::Pattern);
+            assert_eq!(reserved[0].value, "^ali");
+            assert_eq!(reserved[0].reserved_by, admin);
+        }
+        _ => panic!(),
+    }
+
+    verifier.verify_display_name(&alice).await.unwrap();
+    let state = db.fetch_judgement_state(&alice.context).await.unwrap();
+    assert_eq!(
+        display_name_check(&state.unwrap()),
+        (false, Some("^ali".to_string()))
+    );
+
+    // Once removed, the display name passes the check again.
+    let cmd = Command::Unreserve("^ali".to_string());
+    let resp = process_admin_with_approval(&db, cmd.clone(), &admin, None).await;
+    assert_eq!(resp, Response::DisplayNameUnreserved("^ali".to_string()));
+    let resp = process_admin_with_approval(&db, cmd, &admin, None).await;
+    assert_eq!(resp, Response::DisplayNameNotReserved);
+
+    assert_eq!(verifier.check_reserved("Alice").await.unwrap(), None);
+    verifier.verify_display_name(&alice).await.unwrap();
+    let state = db.fetch_judgement_state(&alice.context).await.unwrap();
+    assert_eq!(display_name_check(&state.unwrap()), (true, None));
+}
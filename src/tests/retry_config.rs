@@ -0,0 +1,51 @@
+use super::*;
+use crate::adapters::Adapter;
+use crate::metrics;
+use crate::primitives::{ExpectedMessage, ExternalMessage};
+use crate::retry::RetryConfig;
+use crate::Result;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[derive(Clone, Default)]
+struct FailingAdapter {
+    fetches: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl Adapter for FailingAdapter {
+    type MessageType = ExpectedMessage;
+
+    fn name(&self) -> &'static str {
+        "failing"
+    }
+    async fn fetch_messages(&mut self) -> Result<Vec<ExternalMessage>> {
+        self.fetches.fetch_add(1, Ordering::Relaxed);
+        Err(anyhow!("connection refused"))
+    }
+    async fn send_message(&mut self, _to: &str, _content: Self::MessageType) -> Result<()> {
+        unimplemented!()
+    }
+}
+
+#[actix::test]
+async fn configured_circuit_breaker() {
+    let (db, _, _, _) = new_env().await;
+    let adapter = FailingAdapter::default();
+
+    // Open the circuit after the first failure, far below the default.
+    let config = RetryConfig {
+        failure_threshold: 1,
+        cool_down: 3_600,
+        ..Default::default()
+    };
+    AdapterListener::new(db.clone(), config)
+        .await
+        .start_message_adapter(adapter.clone(), PollingInterval::fixed(1), false)
+        .await;
+    sleep(Duration::from_secs(3)).await;
+
+    // Further fetches are skipped during the cool-down.
+    assert_eq!(adapter.fetches.load(Ordering::Relaxed), 1);
+    assert!(metrics::circuit_states().contains(&("failing".to_string(), true)));
+}
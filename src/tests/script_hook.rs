@@ -0,0 +1,77 @@
+use super::*;
+use crate::primitives::{
+    ExternalMessage, ExternalMessageType, JudgementState, MessageId, Timestamp,
+};
+use crate::scripting::ScriptHook;
+
+const SCRIPT: &str = r#"
+fn on_message(input) {
+    if input.field.type == "twitter" {
+        return #{ veto: true, reason: "twitter is not accepted", notes: ["vetoed twitter"] };
+    }
+    #{ notes: ["verified " + input.field.type] }
+}
+"#;
+
+fn message(state: &JudgementState, origin: ExternalMessageType) -> ExternalMessage {
+    let field = IdentityFieldValue::from(origin.clone());
+
+    ExternalMessage {
+        origin,
+        id: MessageId::from(0u32),
+        timestamp: Timestamp::now(),
+        values: state
+            .get_field(&field)
+            .expected_message()
+            .to_message_parts(),
+        metadata: None,
+    }
+}
+
+#[actix::test]
+async fn script_hook_vetoes_and_annotates() {
+    let (db, connector, _api, _) = new_env().await;
+    let db = db.with_script_hook(ScriptHook::from_script(SCRIPT, 10_000).unwrap());
+
+    // Insert judgement request.
+    connector.inject(alice_judgement_request()).await;
+    let states = connector.inserted_states().await;
+    let alice = states[0].clone();
+
+    // The Matrix field is verified and annotated.
+    db.verify_message(&message(
+        &alice,
+        ExternalMessageType::Matrix("@alice:matrix.org".to_string()),
+    ))
+    .await
+    .unwrap();
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(state.get_field(&F::ALICE_MATRIX()).challenge.is_verified());
+    assert!(!state.requires_manual_review);
+    assert_eq!(state.annotations, vec!["verified matrix".to_string()]);
+
+    // The Twitter field is vetoed, despite the valid challenge.
+    db.verify_message(&message(
+        &alice,
+        ExternalMessageType::Twitter("@alice".to_string()),
+    ))
+    .await
+    .unwrap();
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!state.get_field(&F::ALICE_TWITTER()).challenge.is_verified());
+    assert!(state.requires_manual_review);
+    assert_eq!(
+        state.annotations,
+        vec!["verified matrix".to_string(), "vetoed twitter".to_string()]
+    );
+}
@@ -0,0 +1,127 @@
+use super::*;
+use crate::adapters::admin::RawFieldName;
+use crate::primitives::{ChainName, JudgementState};
+
+fn edit(email: &str) -> JudgementState {
+    JudgementState::new(
+        IdentityContext::alice(),
+        vec![
+            IdentityFieldValue::ALICE_DISPLAY_NAME(),
+            IdentityFieldValue::Email(email.to_string()),
+            IdentityFieldValue::ALICE_TWITTER(),
+            IdentityFieldValue::ALICE_MATRIX(),
+        ],
+    )
+}
+
+fn has_email(state: &JudgementState, email: &str) -> bool {
+    state
+        .fields
+        .iter()
+        .any(|field| field.value == IdentityFieldValue::Email(email.to_string()))
+}
+
+#[actix::test]
+async fn identity_edits_are_applied_once_settled() {
+    let (db, _, _, _) = new_env().await;
+    let db = db.with_settle_delay(Duration::from_secs(2));
+    let alice = IdentityContext::alice();
+
+    // New requests are not delayed.
+    assert!(db
+        .add_judgement_request(&JudgementState::alice())
+        .await
+        .unwrap());
+
+    // Multiple edits in quick succession.
+    assert!(!db
+        .add_judgement_request(&edit("first@email.com"))
+        .await
+        .unwrap());
+    assert!(!db
+        .add_judgement_request(&edit("second@email.com"))
+        .await
+        .unwrap());
+    assert!(!db
+        .add_judgement_request(&edit("second@email.com"))
+        .await
+        .unwrap());
+
+    assert!(db
+        .apply_settled_updates(ChainName::Polkadot)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let state = db.fetch_judgement_state(&alice).await.unwrap().unwrap();
+    assert!(has_email(&state, "alice@email.com"));
+
+    // Only the latest edit is applied.
+    sleep(Duration::from_secs(3)).await;
+    let applied = db.apply_settled_updates(ChainName::Polkadot).await.unwrap();
+    assert_eq!(applied.len(), 1);
+
+    let state = db.fetch_judgement_state(&alice).await.unwrap().unwrap();
+    assert!(has_email(&state, "second@email.com"));
+    assert!(!has_email(&state, "first@email.com"));
+
+    assert!(db
+        .apply_settled_updates(ChainName::Polkadot)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Reverted edits are dropped.
+    assert!(!db
+        .add_judgement_request(&edit("third@email.com"))
+        .await
+        .unwrap());
+    assert!(!db
+        .add_judgement_request(&edit("second@email.com"))
+        .await
+        .unwrap());
+
+    sleep(Duration::from_secs(3)).await;
+    assert!(db
+        .apply_settled_updates(ChainName::Polkadot)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let state = db.fetch_judgement_state(&alice).await.unwrap().unwrap();
+    assert!(has_email(&state, "second@email.com"));
+}
+
+#[actix::test]
+async fn cosmetic_edits_keep_verification() {
+    let (db, _, _, _) = new_env().await;
+    let alice = IdentityContext::alice();
+
+    assert!(db
+        .add_judgement_request(&JudgementState::alice())
+        .await
+        .unwrap());
+    db.verify_manually(&alice, &RawFieldName::Email, false, None)
+        .await
+        .unwrap();
+
+    // Different case and whitespace, in a different field order.
+    let mut cosmetic = edit(" Alice@Email.com");
+    cosmetic.fields.reverse();
+    assert!(!db.add_judgement_request(&cosmetic).await.unwrap());
+
+    let state = db.fetch_judgement_state(&alice).await.unwrap().unwrap();
+    assert!(has_email(&state, "alice@email.com"));
+    assert!(state
+        .get_field(&IdentityFieldValue::ALICE_EMAIL())
+        .challenge
+        .is_verified());
+
+    // Actual changes still reset the field.
+    assert!(db
+        .add_judgement_request(&edit("alice2@email.com"))
+        .await
+        .unwrap());
+    let state = db.fetch_judgement_state(&alice).await.unwrap().unwrap();
+    assert!(has_email(&state, "alice2@email.com"));
+}
@@ -0,0 +1,56 @@
+use super::*;
+use crate::primitives::{
+    ExternalMessage, ExternalMessageType, JudgementState, MessageId, Timestamp,
+};
+use crate::ShutdownCoordinator;
+
+#[actix::test]
+async fn stopped_adapter_does_not_fetch_messages() {
+    let (db, _, _, _) = new_env().await;
+
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+    let alice = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let injector = MessageInjector::new();
+    let listener = AdapterListener::new(db.clone(), Default::default()).await;
+    listener
+        .start_message_adapter(injector.clone(), PollingInterval::fixed(60), false)
+        .await;
+
+    // The adapter does not wait for the end of the polling interval.
+    let coordinator =
+        ShutdownCoordinator::new(db.clone(), Duration::from_secs(5)).adapters(listener.handle());
+    tokio::time::timeout(Duration::from_secs(3), coordinator.shutdown())
+        .await
+        .unwrap();
+
+    injector
+        .send(ExternalMessage {
+            origin: ExternalMessageType::Matrix("@alice:matrix.org".to_string()),
+            id: MessageId::from(0u32),
+            timestamp: Timestamp::now(),
+            values: alice
+                .get_field(&F::ALICE_MATRIX())
+                .expected_message()
+                .to_message_parts(),
+            metadata: None,
+        })
+        .await;
+
+    sleep(Duration::from_secs(2)).await;
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!state.get_field(&F::ALICE_MATRIX()).challenge.is_verified());
+
+    // No commands are left after the shutdown.
+    assert_eq!(db.flush(Duration::from_secs(1)).await, 0);
+}
@@ -0,0 +1,26 @@
+use super::*;
+use crate::primitives::{JudgementState, Timestamp};
+use crate::transparency_report::{generate_report, ReportMonth};
+
+#[actix::test]
+async fn report_of_current_month() {
+    let (db, _, _, _) = new_env().await;
+    let month = ReportMonth::containing(Timestamp::now());
+
+    let mut alice = JudgementState::alice();
+    alice.is_fully_verified = true;
+    db.add_judgement_request(&alice).await.unwrap();
+    db.full_manual_verification(&alice.context).await.unwrap();
+    db.set_judged(&alice.context).await.unwrap();
+
+    let report = generate_report(&db, month).await.unwrap();
+    let figures = &report.chains["polkadot"];
+    assert_eq!(figures.judgements_issued, 1);
+    assert_eq!(figures.manual_interventions, 1);
+    assert!(figures.average_time_to_judgement.is_some());
+    assert_eq!(report.chains["kusama"].judgements_issued, 0);
+
+    // Nothing happened in the previous month.
+    let report = generate_report(&db, month.previous()).await.unwrap();
+    assert_eq!(report.chains["polkadot"].judgements_issued, 0);
+}
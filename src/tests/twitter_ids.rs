@@ -0,0 +1,51 @@
+use super::*;
+use crate::primitives::Timestamp;
+
+#[actix::test]
+async fn cache_twitter_handles() {
+    let (db, _, _, _) = new_env().await;
+    let ids = vec!["1".to_string(), "2".to_string()];
+
+    let res = db
+        .fetch_twitter_handles(&ids, Timestamp::from(0))
+        .await
+        .unwrap();
+    assert!(res.is_empty());
+
+    db.store_twitter_handle("1", "@alice").await.unwrap();
+    db.store_twitter_handle("2", "@bob").await.unwrap();
+
+    let res = db
+        .fetch_twitter_handles(&ids, Timestamp::from(0))
+        .await
+        .unwrap();
+    assert_eq!(res.len(), 2);
+    assert_eq!(res["1"].0, "@alice");
+    assert_eq!(res["2"].0, "@bob");
+
+    // Expired entries are ignored.
+    let res = db
+        .fetch_twitter_handles(&ids, Timestamp::with_offset(60))
+        .await
+        .unwrap();
+    assert!(res.is_empty());
+
+    // Alice changed the handle, Bob took over the old one.
+    db.store_twitter_handle("1", "@alice_new").await.unwrap();
+    db.store_twitter_handle("2", "@alice").await.unwrap();
+
+    let res = db
+        .fetch_twitter_handles(&ids, Timestamp::from(0))
+        .await
+        .unwrap();
+    assert_eq!(res["1"].0, "@alice_new");
+    assert_eq!(res["2"].0, "@alice");
+
+    // A handle belongs to a single account only.
+    db.store_twitter_handle("3", "@alice").await.unwrap();
+    let res = db
+        .fetch_twitter_handles(&ids, Timestamp::from(0))
+        .await
+        .unwrap();
+    assert_eq!(res.len(), 1);
+}
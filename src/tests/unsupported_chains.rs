@@ -0,0 +1,55 @@
+use super::*;
+use crate::connector::ClientCommand;
+
+#[actix::test]
+async fn requests_for_other_chains_are_rejected() {
+    let (db, mut connector, _api, _) = new_env().await;
+    let alice = IdentityContext::alice();
+
+    let mut kusama = JudgementRequest::alice();
+    kusama.network = Some("kusama".to_string());
+    connector
+        .inject(WatcherMessage::new_judgement_request(kusama))
+        .await;
+
+    let mut bob = JudgementRequest::bob();
+    bob.network = Some("westend".to_string());
+    connector
+        .inject(WatcherMessage::PendingJudgementsRequests(vec![bob]))
+        .await;
+    sleep(Duration::from_millis(500)).await;
+
+    assert!(db.fetch_judgement_state(&alice).await.unwrap().is_none());
+    assert!(db
+        .fetch_judgement_state(&IdentityContext::bob())
+        .await
+        .unwrap()
+        .is_none());
+
+    let (out, counter) = connector.outgoing();
+    assert_eq!(counter.notify_rejected, 2);
+    assert!(out.iter().any(|msg| matches!(
+        msg,
+        ClientCommand::NotifyRejected(address, network)
+            if address == &alice.address && network == "kusama"
+    )));
+
+    // Requests of the configured chain or without a chain are processed.
+    let mut polkadot = JudgementRequest::alice();
+    polkadot.network = Some("Polkadot".to_string());
+    connector
+        .inject(WatcherMessage::new_judgement_request(polkadot))
+        .await;
+    connector.inject(bob_judgement_request()).await;
+    sleep(Duration::from_millis(500)).await;
+
+    assert!(db.fetch_judgement_state(&alice).await.unwrap().is_some());
+    assert!(db
+        .fetch_judgement_state(&IdentityContext::bob())
+        .await
+        .unwrap()
+        .is_some());
+
+    let (_, counter) = connector.outgoing();
+    assert_eq!(counter.notify_rejected, 0);
+}
@@ -0,0 +1,126 @@
+use super::*;
+use crate::adapters::admin::{process_admin, Command, Response};
+use crate::primitives::{
+    ExpectedMessage, ExternalMessage, ExternalMessageType, FailureCause, FieldFailure,
+    JudgementState, MessageId, ProviderMetadata, Timestamp, VerificationOutcome,
+};
+
+#[actix::test]
+async fn record_verification_outcomes() {
+    let (db, _, _, _) = new_env().await;
+
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+
+    let metadata = ProviderMetadata {
+        provider_id: Some("$1a2b3c".to_string()),
+        homeserver: Some("matrix.org".to_string()),
+        ..Default::default()
+    };
+
+    // Invalid message.
+    db.verify_message(&ExternalMessage {
+        origin: ExternalMessageType::Matrix("@alice:matrix.org".to_string()),
+        id: MessageId::from(0u32),
+        timestamp: Timestamp::from(1_000),
+        values: ExpectedMessage::random().to_message_parts(),
+        metadata: None,
+    })
+    .await
+    .unwrap();
+
+    // Valid message, verified once.
+    let valid = ExternalMessage {
+        origin: ExternalMessageType::Matrix("@alice:matrix.org".to_string()),
+        id: MessageId::from(1u32),
+        timestamp: Timestamp::from(2_000),
+        values: alice
+            .get_field(&F::ALICE_MATRIX())
+            .expected_message()
+            .to_message_parts(),
+        metadata: Some(metadata.clone()),
+    };
+    db.verify_message(&valid).await.unwrap();
+    db.verify_message(&valid).await.unwrap();
+
+    let records = db.fetch_verification_records(&alice.context).await.unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].outcome, VerificationOutcome::Failed);
+    assert_eq!(records[0].metadata, None);
+    assert_eq!(records[1].outcome, VerificationOutcome::Verified);
+    assert_eq!(records[1].message_id, MessageId::from(1u32));
+    assert_eq!(records[1].metadata, Some(metadata));
+
+    // Other identities are not affected.
+    assert!(db
+        .fetch_verification_records(&IdentityContext::bob())
+        .await
+        .unwrap()
+        .is_empty());
+
+    let resp = process_admin(&db, Command::Received(alice.context.clone())).await;
+    assert_eq!(resp, Response::VerificationRecords(records));
+}
+
+#[actix::test]
+async fn record_failure_causes() {
+    let (db, _, _, _) = new_env().await;
+
+    let alice = JudgementState::alice();
+    db.add_judgement_request(&alice).await.unwrap();
+
+    let message = |id: u32, content: String| ExternalMessage {
+        origin: ExternalMessageType::Matrix("@alice:matrix.org".to_string()),
+        id: MessageId::from(id),
+        timestamp: Timestamp::from(1_000 + id as u64),
+        values: vec![content.into()],
+        metadata: None,
+    };
+
+    let wrong = message(0, ExpectedMessage::random().value.to_string());
+    let malformed = message(1, "Hello, this is Alice".to_string());
+    db.verify_message(&wrong).await.unwrap();
+    db.verify_message(&malformed).await.unwrap();
+
+    let records = db.fetch_verification_records(&alice.context).await.unwrap();
+    assert_eq!(records[0].cause, Some(FailureCause::WrongChallenge));
+    assert_eq!(records[0].content_hash, Some(wrong.content_hash()));
+    assert_eq!(records[1].cause, Some(FailureCause::NoChallenge));
+    assert_ne!(records[0].content_hash, records[1].content_hash);
+
+    // Only the last failure is kept per field.
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    let field = state.get_field(&F::ALICE_MATRIX());
+    assert_eq!(field.failed_attempts, 2);
+    assert_eq!(
+        field.last_failure,
+        Some(FieldFailure::new(FailureCause::NoChallenge, &malformed))
+    );
+    assert!(state.get_field(&F::ALICE_TWITTER()).last_failure.is_none());
+
+    // Cleared once the field is verified.
+    let valid = message(
+        2,
+        alice
+            .get_field(&F::ALICE_MATRIX())
+            .expected_message()
+            .value
+            .to_string(),
+    );
+    db.verify_message(&valid).await.unwrap();
+
+    let records = db.fetch_verification_records(&alice.context).await.unwrap();
+    assert_eq!(records[2].cause, None);
+    assert_eq!(records[2].content_hash, None);
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(state.get_field(&F::ALICE_MATRIX()).last_failure.is_none());
+}
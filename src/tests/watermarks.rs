@@ -0,0 +1,34 @@
+use super::*;
+use crate::primitives::MessageId;
+
+#[actix::test]
+async fn update_watermark() {
+    let (db, _, _, _) = new_env().await;
+
+    // No watermark available.
+    let res = db.fetch_watermark("email").await.unwrap();
+    assert!(res.is_none());
+
+    db.update_watermark("email", MessageId::from(5u64))
+        .await
+        .unwrap();
+    let res = db.fetch_watermark("email").await.unwrap();
+    assert_eq!(res, Some(MessageId::from(5u64)));
+
+    // The watermark never moves backwards.
+    db.update_watermark("email", MessageId::from(3u64))
+        .await
+        .unwrap();
+    let res = db.fetch_watermark("email").await.unwrap();
+    assert_eq!(res, Some(MessageId::from(5u64)));
+
+    db.update_watermark("email", MessageId::from(8u64))
+        .await
+        .unwrap();
+    let res = db.fetch_watermark("email").await.unwrap();
+    assert_eq!(res, Some(MessageId::from(8u64)));
+
+    // Watermarks are tracked per adapter.
+    let res = db.fetch_watermark("Twitter").await.unwrap();
+    assert!(res.is_none());
+}
@@ -0,0 +1,63 @@
+use super::*;
+use crate::adapters::admin::RawFieldName;
+use crate::primitives::{
+    ExternalMessage, ExternalMessageType, IdentityField, JudgementState, MessageId, Timestamp,
+};
+
+#[actix::test]
+async fn verify_web_challenge() {
+    let (db, _, _, injector) = new_env().await;
+    let website = F::Web("alice.com".to_string());
+
+    let mut alice = JudgementState::alice();
+    alice.fields.push(IdentityField::new(website.clone()));
+    alice.challenge_web_fields();
+    db.add_judgement_request(&alice).await.unwrap();
+
+    // Identities inserted before web challenges were enabled are left out.
+    let mut bob = JudgementState::alice();
+    bob.context = IdentityContext::bob();
+    bob.fields
+        .push(IdentityField::new(F::Web("bob.com".to_string())));
+    db.add_judgement_request(&bob).await.unwrap();
+
+    let websites = db.fetch_unverified_websites().await.unwrap();
+    assert_eq!(websites, vec!["alice.com".to_string()]);
+
+    let expected = alice.get_field(&website).expected_message().clone();
+    injector
+        .send(ExternalMessage {
+            origin: ExternalMessageType::Web("alice.com".to_string()),
+            id: MessageId::from(0u32),
+            timestamp: Timestamp::now(),
+            values: expected.to_message_parts(),
+            metadata: None,
+        })
+        .await;
+
+    // Give the adapter listener time to process the message.
+    sleep(Duration::from_secs(3)).await;
+
+    let state = db
+        .fetch_judgement_state(&alice.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(state.get_field(&website).challenge.is_verified());
+    assert!(db.fetch_unverified_websites().await.unwrap().is_empty());
+
+    // Unchallenged web fields are still verified manually.
+    db.verify_manually(&bob.context, &RawFieldName::Web, false, None)
+        .await
+        .unwrap()
+        .unwrap();
+    let state = db
+        .fetch_judgement_state(&bob.context)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(state
+        .get_field(&F::Web("bob.com".to_string()))
+        .challenge
+        .is_verified());
+}
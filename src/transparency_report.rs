@@ -0,0 +1,389 @@
+use crate::database::Database;
+use crate::primitives::{ChainName, Event, IdentityContext, NotificationMessage, Timestamp};
+use crate::Result;
+use schemars::JsonSchema;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
+
+// Interval in seconds between checks whether the report of the previous month
+// was written.
+const REPORT_INTERVAL: u64 = 3_600;
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct TransparencyReportConfig {
+    // Directory to which the monthly JSON and CSV reports are written.
+    pub output_dir: String,
+}
+
+/// A calendar month (UTC).
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ReportMonth {
+    year: i64,
+    month: u32,
+}
+
+impl ReportMonth {
+    pub fn containing(timestamp: Timestamp) -> Self {
+        let (year, month) = civil_from_days((timestamp.raw() / 86_400) as i64);
+        ReportMonth { year, month }
+    }
+    pub fn previous(&self) -> Self {
+        if self.month == 1 {
+            ReportMonth {
+                year: self.year - 1,
+                month: 12,
+            }
+        } else {
+            ReportMonth {
+                year: self.year,
+                month: self.month - 1,
+            }
+        }
+    }
+    pub fn next(&self) -> Self {
+        if self.month == 12 {
+            ReportMonth {
+                year: self.year + 1,
+                month: 1,
+            }
+        } else {
+            ReportMonth {
+                year: self.year,
+                month: self.month + 1,
+            }
+        }
+    }
+    pub fn start(&self) -> Timestamp {
+        Timestamp::from(days_from_civil(self.year, self.month) as u64 * 86_400)
+    }
+    /// The start of the next month, exclusive.
+    pub fn end(&self) -> Timestamp {
+        self.next().start()
+    }
+}
+
+impl FromStr for ReportMonth {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("invalid month '{}', expected YYYY-MM", s);
+
+        let (year, month) = s.split_once('-').ok_or_else(invalid)?;
+        let year: i64 = year.parse().map_err(|_| invalid())?;
+        let month: u32 = month.parse().map_err(|_| invalid())?;
+        if !(1970..=9999).contains(&year) || !(1..=12).contains(&month) {
+            return Err(invalid());
+        }
+
+        Ok(ReportMonth { year, month })
+    }
+}
+
+impl fmt::Display for ReportMonth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}", self.year, self.month)
+    }
+}
+
+// Year and month of the days since epoch, see
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month as u32)
+}
+
+// Days since epoch of the first day of the month, see
+// http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: i64, month: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (i64::from(month) + 9) % 12;
+    let doy = (153 * mp + 2) / 5;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146_097 + doe - 719_468
+}
+
+/// Figures of a single network. Contains no addresses or field values, so the
+/// report can be published.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ChainFigures {
+    pub judgements_issued: usize,
+    // From the judgement request to the judgement, in seconds. Only covers
+    // identities which were not removed in the meantime.
+    pub average_time_to_judgement: Option<u64>,
+    // Fields or identities verified by an admin.
+    pub manual_interventions: usize,
+    // Identities of which the display name was rejected as too similar to an
+    // existing one.
+    pub display_name_rejections: usize,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct TransparencyReport {
+    pub month: String,
+    pub generated_at: Timestamp,
+    pub chains: BTreeMap<String, ChainFigures>,
+}
+
+impl TransparencyReport {
+    /// Aggregates the events of the month. `judged` contains the request time
+    /// of the judged identities.
+    pub fn new(
+        month: ReportMonth,
+        events: &[Event],
+        judged: &HashMap<IdentityContext, Timestamp>,
+        rejected_display_names: &[IdentityContext],
+        generated_at: Timestamp,
+    ) -> Self {
+        let mut chains: BTreeMap<String, ChainFigures> = [ChainName::Polkadot, ChainName::Kusama]
+            .iter()
+            .map(|chain| (chain.as_str().to_string(), ChainFigures::default()))
+            .collect();
+
+        let mut durations: HashMap<String, Vec<u64>> = HashMap::new();
+        for event in events {
+            let chain = event.message.context().chain.as_str().to_string();
+            let figures = chains.entry(chain.clone()).or_default();
+
+            match &event.message {
+                NotificationMessage::JudgementProvided { context } => {
+                    figures.judgements_issued += 1;
+
+                    if let Some(inserted) = judged.get(context) {
+                        durations
+                            .entry(chain)
+                            .or_default()
+                            .push(event.timestamp.raw().saturating_sub(inserted.raw()));
+                    }
+                }
+                NotificationMessage::ManuallyVerified { .. }
+                | NotificationMessage::FullManualVerification { .. } => {
+                    figures.manual_interventions += 1;
+                }
+                _ => {}
+            }
+        }
+
+        for (chain, durations) in durations {
+            if let Some(figures) = chains.get_mut(&chain) {
+                figures.average_time_to_judgement =
+                    Some(durations.iter().sum::<u64>() / durations.len() as u64);
+            }
+        }
+
+        for context in rejected_display_names {
+            chains
+                .entry(context.chain.as_str().to_string())
+                .or_default()
+                .display_name_rejections += 1;
+        }
+
+        TransparencyReport {
+            month: month.to_string(),
+            generated_at,
+            chains,
+        }
+    }
+    pub fn to_csv(&self) -> String {
+        let mut csv = "month,chain,judgements_issued,average_time_to_judgement,manual_interventions,display_name_rejections\n".to_string();
+        for (chain, figures) in &self.chains {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                self.month,
+                chain,
+                figures.judgements_issued,
+                figures
+                    .average_time_to_judgement
+                    .map(|secs| secs.to_string())
+                    .unwrap_or_default(),
+                figures.manual_interventions,
+                figures.display_name_rejections
+            ));
+        }
+
+        csv
+    }
+}
+
+pub async fn generate_report(db: &Database, month: ReportMonth) -> Result<TransparencyReport> {
+    let (start, end) = (month.start(), month.end());
+
+    let events = db.fetch_report_events(start, end).await?;
+    let contexts: Vec<IdentityContext> = events
+        .iter()
+        .filter(|event| matches!(event.message, NotificationMessage::JudgementProvided { .. }))
+        .map(|event| event.message.context().clone())
+        .collect();
+
+    let judged = db
+        .fetch_judgement_states(&contexts)
+        .await?
+        .into_iter()
+        .map(|(context, state)| (context, state.inserted_timestamp))
+        .collect();
+
+    let rejected = db.fetch_rejected_display_names(start, end).await?;
+
+    Ok(TransparencyReport::new(
+        month,
+        &events,
+        &judged,
+        &rejected,
+        Timestamp::now(),
+    ))
+}
+
+fn report_path(dir: &Path, month: ReportMonth, extension: &str) -> PathBuf {
+    dir.join(format!("transparency-report-{}.{}", month, extension))
+}
+
+/// Writes the report of the month as JSON and CSV to the directory, returning
+/// the paths of both files.
+pub async fn write_report(
+    db: &Database,
+    dir: &Path,
+    month: ReportMonth,
+) -> Result<(PathBuf, PathBuf)> {
+    let report = generate_report(db, month).await?;
+
+    std::fs::create_dir_all(dir)?;
+    let (json, csv) = (
+        report_path(dir, month, "json"),
+        report_path(dir, month, "csv"),
+    );
+    std::fs::write(&json, serde_json::to_string_pretty(&report)?)?;
+    std::fs::write(&csv, report.to_csv())?;
+
+    Ok((json, csv))
+}
+
+/// Writes the report of the previous month once it is complete. Reports which
+/// already exist are not overwritten.
+pub fn start_report_task(db: Database, config: TransparencyReportConfig) -> JoinHandle<()> {
+    actix::spawn(async move {
+        let dir = PathBuf::from(config.output_dir);
+
+        loop {
+            let month = ReportMonth::containing(Timestamp::now()).previous();
+            if !report_path(&dir, month, "json").exists() {
+                match write_report(&db, &dir, month).await {
+                    Ok((json, _)) => info!("Wrote transparency report to {}", json.display()),
+                    Err(err) => error!("Failed to write transparency report: {:?}", err),
+                }
+            }
+
+            sleep(Duration::from_secs(REPORT_INTERVAL)).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapters::admin::RawFieldName;
+
+    #[test]
+    fn report_month_bounds() {
+        let month: ReportMonth = "2024-02".parse().unwrap();
+        assert_eq!(month.start(), Timestamp::from(1_706_745_600));
+        assert_eq!(month.end(), Timestamp::from(1_709_251_200));
+        assert_eq!(month.previous().to_string(), "2024-01");
+        assert_eq!(
+            "2023-12".parse::<ReportMonth>().unwrap().next().to_string(),
+            "2024-01"
+        );
+        assert_eq!(
+            ReportMonth::containing(Timestamp::from(1_709_251_199)),
+            month
+        );
+
+        assert!("2024-13".parse::<ReportMonth>().is_err());
+        assert!("2024".parse::<ReportMonth>().is_err());
+        assert!("february".parse::<ReportMonth>().is_err());
+    }
+
+    #[test]
+    fn aggregate_report() {
+        let month: ReportMonth = "2024-02".parse().unwrap();
+        let alice = IdentityContext::alice();
+        let bob = IdentityContext::bob();
+        let event = |message, timestamp| Event {
+            timestamp: Timestamp::from(timestamp),
+            message,
+            seq: 0,
+            log_seq: 0,
+            dedup_key: None,
+        };
+
+        let events = vec![
+            event(
+                NotificationMessage::JudgementProvided {
+                    context: alice.clone(),
+                },
+                1_706_800_000,
+            ),
+            event(
+                NotificationMessage::JudgementProvided { context: bob },
+                1_706_900_000,
+            ),
+            event(
+                NotificationMessage::ManuallyVerified {
+                    context: alice.clone(),
+                    field: RawFieldName::Email,
+                },
+                1_706_790_000,
+            ),
+            event(
+                NotificationMessage::IdentityInserted {
+                    context: alice.clone(),
+                },
+                1_706_780_000,
+            ),
+        ];
+
+        // Bob's identity was removed after the judgement.
+        let judged = HashMap::from([(alice.clone(), Timestamp::from(1_706_770_000))]);
+        let report = TransparencyReport::new(
+            month,
+            &events,
+            &judged,
+            &[alice],
+            Timestamp::from(1_709_251_200),
+        );
+
+        assert_eq!(
+            report.chains["polkadot"],
+            ChainFigures {
+                judgements_issued: 2,
+                average_time_to_judgement: Some(30_000),
+                manual_interventions: 1,
+                display_name_rejections: 1,
+            }
+        );
+        assert_eq!(report.chains["kusama"], ChainFigures::default());
+
+        assert_eq!(
+            report.to_csv(),
+            "month,chain,judgements_issued,average_time_to_judgement,manual_interventions,display_name_rejections\n\
+             2024-02,kusama,0,,0,0\n\
+             2024-02,polkadot,2,30000,1,1\n"
+        );
+    }
+}
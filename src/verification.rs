@@ -0,0 +1,872 @@
+// Storage-agnostic verification logic. The functions in this module only decide
+// on what the outcome of a verification step is and which events should be
+// created, the `Database` then persists those decisions.
+
+use crate::primitives::{
+    ChainAddress, ChallengeType, ExpectedMessage, ExternalMessage, FailureCause, IdentityContext,
+    IdentityFieldValue, JudgementState, NotificationMessage, Timestamp,
+};
+use crate::Result;
+use rand::{thread_rng, Rng};
+use std::ops::Range;
+
+/// The range (in seconds) of the timed delay for issuing judgements. This is
+/// used to prevent timing attacks where a user updates the identity right
+/// before the judgement is issued.
+pub const JUDGEMENT_DELAY_RANGE: Range<u64> = 30..300;
+
+/// The length of the address prefix that must be included in a message if the
+/// same contact value is used by multiple pending identities.
+pub const DISAMBIGUATION_TOKEN_LEN: usize = 8;
+
+/// The outcome of verifying an external message against the (first) challenge
+/// of a field.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum MessageOutcome {
+    /// The challenge was verified before, nothing to do.
+    AlreadyVerified,
+    /// The message contains the expected challenge.
+    Verified { awaiting_second: bool },
+    /// The message does not contain the expected challenge.
+    Failed,
+    /// The message contains the expected challenge, but the contact value is
+    /// shared with other identities and the disambiguation token is missing.
+    DisambiguationRequired,
+    /// The message contains the expected challenge, but the script hook
+    /// vetoed the verification.
+    Vetoed { reason: Option<String> },
+    /// The challenge expired, the user must request a new one.
+    Expired,
+}
+
+impl MessageOutcome {
+    /// The events that must be created for this outcome.
+    pub fn events(
+        &self,
+        context: &IdentityContext,
+        field: &IdentityFieldValue,
+    ) -> Vec<NotificationMessage> {
+        match self {
+            MessageOutcome::AlreadyVerified => vec![],
+            MessageOutcome::Verified { awaiting_second } => {
+                let mut events = vec![NotificationMessage::FieldVerified {
+                    context: context.clone(),
+                    field: field.clone(),
+                }];
+
+                if *awaiting_second {
+                    events.push(NotificationMessage::AwaitingSecondChallenge {
+                        context: context.clone(),
+                        field: field.clone(),
+                    });
+                }
+
+                events
+            }
+            MessageOutcome::Failed => vec![NotificationMessage::FieldVerificationFailed {
+                context: context.clone(),
+                field: field.clone(),
+            }],
+            MessageOutcome::DisambiguationRequired => {
+                vec![NotificationMessage::DisambiguationRequired {
+                    context: context.clone(),
+                    field: field.clone(),
+                    token: disambiguation_token(&context.address).to_string(),
+                }]
+            }
+            MessageOutcome::Vetoed { reason } => vec![NotificationMessage::VerificationVetoed {
+                context: context.clone(),
+                field: Some(field.clone()),
+                reason: reason.clone(),
+            }],
+            MessageOutcome::Expired => vec![NotificationMessage::ChallengeExpired {
+                context: context.clone(),
+                field: field.clone(),
+            }],
+        }
+    }
+    /// Why the message did not verify the field, if it did not.
+    pub fn failure_cause(
+        &self,
+        challenge: &ChallengeType,
+        message: &ExternalMessage,
+    ) -> Option<FailureCause> {
+        match self {
+            MessageOutcome::AlreadyVerified | MessageOutcome::Verified { .. } => None,
+            MessageOutcome::Failed => match challenge {
+                ChallengeType::ExpectedMessage { expected, .. }
+                    if contains_challenge_format(expected, message) =>
+                {
+                    Some(FailureCause::WrongChallenge)
+                }
+                _ => Some(FailureCause::NoChallenge),
+            },
+            MessageOutcome::DisambiguationRequired => {
+                Some(FailureCause::MissingDisambiguationToken)
+            }
+            MessageOutcome::Vetoed { .. } => Some(FailureCause::Vetoed),
+            MessageOutcome::Expired => Some(FailureCause::Expired),
+        }
+    }
+}
+
+/// Whether the message contains a word in the format of the challenge, i.e.
+/// hex strings or numeric codes of the same length.
+fn contains_challenge_format(expected: &ExpectedMessage, message: &ExternalMessage) -> bool {
+    let len = expected.value.as_str().len();
+    let is_challenge_char = |c: char| {
+        if expected.is_code() {
+            c.is_ascii_digit()
+        } else {
+            c.is_ascii_hexdigit()
+        }
+    };
+
+    message.values.iter().any(|part| {
+        part.as_str()
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .any(|word| word.len() == len && word.chars().all(is_challenge_char))
+    })
+}
+
+/// Checks the external message against the challenge of the field. If `token`
+/// is specified, the message must contain it as well.
+pub fn verify_message(
+    challenge: &ChallengeType,
+    message: &ExternalMessage,
+    token: Option<&str>,
+) -> Result<MessageOutcome> {
+    match challenge {
+        ChallengeType::ExpectedMessage { expected, second } => {
+            // Only proceed if the expected challenge has not been verified yet.
+            if expected.is_verified {
+                Ok(MessageOutcome::AlreadyVerified)
+            } else if expected.expired {
+                Ok(MessageOutcome::Expired)
+            } else if expected.is_message_valid(message) {
+                if let Some(token) = token {
+                    if !message.values.iter().any(|part| part.contains(token)) {
+                        return Ok(MessageOutcome::DisambiguationRequired);
+                    }
+                }
+
+                Ok(MessageOutcome::Verified {
+                    awaiting_second: second.is_some(),
+                })
+            } else {
+                Ok(MessageOutcome::Failed)
+            }
+        }
+        _ => Err(anyhow!(
+            "Invalid challenge type when verifying message. This is a bug"
+        )),
+    }
+}
+
+/// Applies the expiry to the unverified first challenge of a field. Challenges
+/// without expiry are valid for `ttl` seconds from now on, which also covers
+/// challenges created before expiry was enabled. Returns `true` if the
+/// challenge changed.
+pub fn expire_challenge(challenge: &mut ChallengeType, ttl: u64, now: Timestamp) -> bool {
+    let expected = match challenge {
+        ChallengeType::ExpectedMessage { expected, .. }
+            if !expected.is_verified && !expected.expired =>
+        {
+            expected
+        }
+        _ => return false,
+    };
+
+    match expected.valid_until {
+        Some(valid_until) if now.raw() >= valid_until.raw() => {
+            expected.expired = true;
+            true
+        }
+        Some(_) => false,
+        None => {
+            expected.valid_until = Some(Timestamp::from(now.raw().saturating_add(ttl)));
+            true
+        }
+    }
+}
+
+/// The token that must be included in messages for the given address if its
+/// contact value is shared with other pending identities.
+pub fn disambiguation_token(address: &ChainAddress) -> &str {
+    let address = address.as_str();
+    address
+        .char_indices()
+        .nth(DISAMBIGUATION_TOKEN_LEN)
+        .map(|(idx, _)| &address[..idx])
+        .unwrap_or(address)
+}
+
+/// Shortened address for display purposes, e.g. `1a2YiG...dmpGhZP`.
+pub fn truncate_address(address: &ChainAddress) -> String {
+    let address = address.as_str();
+    let chars: Vec<char> = address.chars().collect();
+
+    if chars.len() <= 13 {
+        return address.to_string();
+    }
+
+    format!(
+        "{}...{}",
+        chars[..6].iter().collect::<String>(),
+        chars[chars.len() - 7..].iter().collect::<String>()
+    )
+}
+
+/// Informs each of the given identities that the contact value is shared with
+/// the other identities.
+pub fn shared_contact_events(
+    contexts: &[IdentityContext],
+    field: &IdentityFieldValue,
+) -> Vec<NotificationMessage> {
+    if contexts.len() < 2 {
+        return vec![];
+    }
+
+    contexts
+        .iter()
+        .map(|context| NotificationMessage::ContactValueShared {
+            context: context.clone(),
+            field: field.clone(),
+            shared_with: contexts
+                .iter()
+                .filter(|other| *other != context)
+                .map(|other| truncate_address(&other.address))
+                .collect(),
+        })
+        .collect()
+}
+
+/// The outcome of verifying the second challenge of a field, as provided by
+/// the user via the API.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SecondChallengeOutcome {
+    /// The field has no second challenge. This should never happen, but the
+    /// provided field value depends on user input.
+    NotAvailable,
+    Verified,
+    Failed,
+    /// The second challenge is a code which expired or has no attempts left.
+    Expired,
+}
+
+impl SecondChallengeOutcome {
+    /// The events that must be created for this outcome.
+    pub fn events(
+        &self,
+        context: &IdentityContext,
+        field: &IdentityFieldValue,
+    ) -> Vec<NotificationMessage> {
+        match self {
+            SecondChallengeOutcome::NotAvailable => vec![],
+            SecondChallengeOutcome::Verified => vec![NotificationMessage::SecondFieldVerified {
+                context: context.clone(),
+                field: field.clone(),
+            }],
+            SecondChallengeOutcome::Failed => {
+                vec![NotificationMessage::SecondFieldVerificationFailed {
+                    context: context.clone(),
+                    field: field.clone(),
+                }]
+            }
+            SecondChallengeOutcome::Expired => {
+                vec![NotificationMessage::SecondChallengeExpired {
+                    context: context.clone(),
+                    field: field.clone(),
+                }]
+            }
+        }
+    }
+}
+
+/// Checks the (already trimmed) user provided value against the second
+/// challenge of the field. Short codes must match exactly, since they could
+/// otherwise be guessed by providing many digits at once.
+pub fn verify_second_challenge(
+    challenge: &ChallengeType,
+    provided: &str,
+) -> Result<SecondChallengeOutcome> {
+    match challenge {
+        ChallengeType::ExpectedMessage {
+            expected: _,
+            second,
+        } => match second {
+            Some(second) if second.is_expired() => Ok(SecondChallengeOutcome::Expired),
+            Some(second) if second.is_code() && provided == second.value.as_str() => {
+                Ok(SecondChallengeOutcome::Verified)
+            }
+            Some(second) if !second.is_code() && provided.contains(second.value.as_str()) => {
+                Ok(SecondChallengeOutcome::Verified)
+            }
+            Some(_) => Ok(SecondChallengeOutcome::Failed),
+            None => Ok(SecondChallengeOutcome::NotAvailable),
+        },
+        _ => Err(anyhow!(
+            "Invalid challenge type when verifying second challenge. This is a bug"
+        )),
+    }
+}
+
+/// The state transition of an identity after one of its fields changed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VerificationTransition {
+    /// All fields are verified, the identity must be marked as fully verified
+    /// (if it isn't already).
+    FullyVerified,
+    /// Not all fields are verified, the verification state must be reset (if
+    /// it was marked as fully verified before).
+    Reset,
+}
+
+pub fn verification_transition(state: &JudgementState) -> VerificationTransition {
+    if state.check_full_verification() {
+        VerificationTransition::FullyVerified
+    } else {
+        VerificationTransition::Reset
+    }
+}
+
+/// Returns a random delay (in seconds) after which the judgement should be
+/// issued, within `JUDGEMENT_DELAY_RANGE`.
+pub fn judgement_delay() -> u64 {
+    thread_rng().gen_range(JUDGEMENT_DELAY_RANGE)
+}
+
+/// Returns the shortened issuance time of a fully verified identity whose
+/// judgement was requested early by the user. The judgement is never issued
+/// sooner than `min_delay` seconds after the completion, nor later than
+/// originally scheduled. Returns `None` if the judgement is not awaited.
+pub fn expedited_issue_time(
+    state: &JudgementState,
+    min_delay: u64,
+    now: Timestamp,
+) -> Option<Timestamp> {
+    if !state.is_fully_verified || state.judgement_submitted || state.requires_manual_review {
+        return None;
+    }
+
+    let scheduled = state.issue_judgement_at?;
+    let floor = Timestamp::from(
+        state
+            .completion_timestamp
+            .unwrap_or(now)
+            .raw()
+            .saturating_add(min_delay),
+    )
+    .max(now);
+
+    Some(scheduled.min(floor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{ExpectedMessage, ExternalMessageType, MessageId};
+
+    fn message(content: &str) -> ExternalMessage {
+        ExternalMessage {
+            origin: ExternalMessageType::Email("alice@email.com".to_string()),
+            id: MessageId::from(0u32),
+            timestamp: Timestamp::now(),
+            values: vec![content.to_string().into()],
+            metadata: None,
+        }
+    }
+
+    fn expected_message(verified: bool, second: Option<bool>) -> ChallengeType {
+        let mut expected = ExpectedMessage::random();
+        expected.is_verified = verified;
+
+        ChallengeType::ExpectedMessage {
+            expected,
+            second: second.map(|verified| {
+                let mut second = ExpectedMessage::random();
+                second.is_verified = verified;
+                second
+            }),
+        }
+    }
+
+    fn expected_value(challenge: &ChallengeType) -> String {
+        match challenge {
+            ChallengeType::ExpectedMessage { expected, .. } => expected.value.to_string(),
+            _ => panic!(),
+        }
+    }
+
+    fn second_value(challenge: &ChallengeType) -> String {
+        match challenge {
+            ChallengeType::ExpectedMessage { second, .. } => {
+                second.as_ref().unwrap().value.to_string()
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn verify_message_valid() {
+        let challenge = expected_message(false, None);
+        let msg = message(&expected_value(&challenge));
+
+        assert_eq!(
+            verify_message(&challenge, &msg, None).unwrap(),
+            MessageOutcome::Verified {
+                awaiting_second: false
+            }
+        );
+
+        // Challenge is embedded in other content.
+        let msg = message(&format!("Hello {} there", expected_value(&challenge)));
+        assert_eq!(
+            verify_message(&challenge, &msg, None).unwrap(),
+            MessageOutcome::Verified {
+                awaiting_second: false
+            }
+        );
+    }
+
+    #[test]
+    fn verify_message_valid_with_second() {
+        let challenge = expected_message(false, Some(false));
+        let msg = message(&expected_value(&challenge));
+
+        assert_eq!(
+            verify_message(&challenge, &msg, None).unwrap(),
+            MessageOutcome::Verified {
+                awaiting_second: true
+            }
+        );
+
+        // The second challenge does not verify the first one.
+        let msg = message(&second_value(&challenge));
+        assert_eq!(
+            verify_message(&challenge, &msg, None).unwrap(),
+            MessageOutcome::Failed
+        );
+    }
+
+    #[test]
+    fn verify_message_invalid() {
+        let challenge = expected_message(false, None);
+        let msg = message(ExpectedMessage::random().value.as_str());
+
+        assert_eq!(
+            verify_message(&challenge, &msg, None).unwrap(),
+            MessageOutcome::Failed
+        );
+
+        let msg = message("");
+        assert_eq!(
+            verify_message(&challenge, &msg, None).unwrap(),
+            MessageOutcome::Failed
+        );
+    }
+
+    #[test]
+    fn failure_cause_of_message() {
+        let challenge = expected_message(false, None);
+        let cause = |content: &str| {
+            let msg = message(content);
+            verify_message(&challenge, &msg, None)
+                .unwrap()
+                .failure_cause(&challenge, &msg)
+        };
+
+        assert_eq!(cause(&expected_value(&challenge)), None);
+        assert_eq!(
+            cause(&format!("Hi, {}!", ExpectedMessage::random().value)),
+            Some(FailureCause::WrongChallenge)
+        );
+        assert_eq!(cause("Hi there"), Some(FailureCause::NoChallenge));
+        // Truncated challenges are not recognized as such.
+        assert_eq!(
+            cause(&expected_value(&challenge)[..31]),
+            Some(FailureCause::NoChallenge)
+        );
+
+        assert_eq!(
+            MessageOutcome::DisambiguationRequired.failure_cause(&challenge, &message("")),
+            Some(FailureCause::MissingDisambiguationToken)
+        );
+        assert_eq!(
+            MessageOutcome::AlreadyVerified.failure_cause(&challenge, &message("")),
+            None
+        );
+    }
+
+    #[test]
+    fn expire_challenges() {
+        let ttl = 3_600;
+        let mut challenge = expected_message(false, Some(false));
+
+        // The expiry is assigned when first seen.
+        assert!(expire_challenge(
+            &mut challenge,
+            ttl,
+            Timestamp::from(1_000)
+        ));
+        assert!(!expire_challenge(
+            &mut challenge,
+            ttl,
+            Timestamp::from(4_599)
+        ));
+
+        let msg = message(&expected_value(&challenge));
+        assert!(matches!(
+            verify_message(&challenge, &msg, None).unwrap(),
+            MessageOutcome::Verified { .. }
+        ));
+
+        assert!(expire_challenge(
+            &mut challenge,
+            ttl,
+            Timestamp::from(4_600)
+        ));
+        assert_eq!(
+            verify_message(&challenge, &msg, None).unwrap(),
+            MessageOutcome::Expired
+        );
+
+        // Expired challenges are only marked once.
+        assert!(!expire_challenge(
+            &mut challenge,
+            ttl,
+            Timestamp::from(10_000)
+        ));
+
+        // Verified challenges never expire.
+        let mut challenge = expected_message(true, Some(false));
+        assert!(!expire_challenge(
+            &mut challenge,
+            ttl,
+            Timestamp::from(1_000)
+        ));
+        assert!(!expire_challenge(
+            &mut ChallengeType::Unsupported { is_verified: None },
+            ttl,
+            Timestamp::from(1_000)
+        ));
+    }
+
+    #[test]
+    fn verify_message_already_verified() {
+        let challenge = expected_message(true, None);
+        let msg = message(&expected_value(&challenge));
+        assert_eq!(
+            verify_message(&challenge, &msg, None).unwrap(),
+            MessageOutcome::AlreadyVerified
+        );
+
+        // Invalid messages are ignored, too.
+        let msg = message(ExpectedMessage::random().value.as_str());
+        assert_eq!(
+            verify_message(&challenge, &msg, None).unwrap(),
+            MessageOutcome::AlreadyVerified
+        );
+
+        // Even if the second challenge is still pending.
+        let challenge = expected_message(true, Some(false));
+        let msg = message(&expected_value(&challenge));
+        assert_eq!(
+            verify_message(&challenge, &msg, None).unwrap(),
+            MessageOutcome::AlreadyVerified
+        );
+    }
+
+    #[test]
+    fn verify_message_invalid_challenge_type() {
+        let msg = message("");
+
+        let challenge = ChallengeType::DisplayNameCheck {
+            passed: false,
+            violations: vec![],
+            reserved: None,
+        };
+        assert!(verify_message(&challenge, &msg, None).is_err());
+
+        let challenge = ChallengeType::Unsupported { is_verified: None };
+        assert!(verify_message(&challenge, &msg, None).is_err());
+    }
+
+    #[test]
+    fn message_outcome_events() {
+        let context = IdentityContext::alice();
+        let field = IdentityFieldValue::ALICE_EMAIL();
+
+        assert!(MessageOutcome::AlreadyVerified
+            .events(&context, &field)
+            .is_empty());
+
+        assert_eq!(
+            MessageOutcome::Verified {
+                awaiting_second: false
+            }
+            .events(&context, &field),
+            vec![NotificationMessage::FieldVerified {
+                context: context.clone(),
+                field: field.clone(),
+            }]
+        );
+
+        assert_eq!(
+            MessageOutcome::Verified {
+                awaiting_second: true
+            }
+            .events(&context, &field),
+            vec![
+                NotificationMessage::FieldVerified {
+                    context: context.clone(),
+                    field: field.clone(),
+                },
+                NotificationMessage::AwaitingSecondChallenge {
+                    context: context.clone(),
+                    field: field.clone(),
+                }
+            ]
+        );
+
+        assert_eq!(
+            MessageOutcome::Failed.events(&context, &field),
+            vec![NotificationMessage::FieldVerificationFailed {
+                context: context.clone(),
+                field: field.clone(),
+            }]
+        );
+    }
+
+    #[test]
+    fn verify_message_with_token() {
+        let challenge = expected_message(false, None);
+        let token = disambiguation_token(&IdentityContext::alice().address).to_string();
+
+        // Challenge without the token.
+        let msg = message(&expected_value(&challenge));
+        assert_eq!(
+            verify_message(&challenge, &msg, Some(&token)).unwrap(),
+            MessageOutcome::DisambiguationRequired
+        );
+
+        // Challenge with the token.
+        let msg = message(&format!("{} {}", token, expected_value(&challenge)));
+        assert_eq!(
+            verify_message(&challenge, &msg, Some(&token)).unwrap(),
+            MessageOutcome::Verified {
+                awaiting_second: false
+            }
+        );
+
+        // Token without the challenge.
+        let msg = message(&token);
+        assert_eq!(
+            verify_message(&challenge, &msg, Some(&token)).unwrap(),
+            MessageOutcome::Failed
+        );
+    }
+
+    #[test]
+    fn shared_contact_notifications() {
+        let alice = IdentityContext::alice();
+        let bob = IdentityContext::bob();
+        let field = IdentityFieldValue::ALICE_EMAIL();
+
+        assert_eq!(disambiguation_token(&alice.address), "1a2YiGNu");
+        assert_eq!(truncate_address(&alice.address), "1a2YiG...dmpGhZP");
+
+        // A single identity does not share the contact value.
+        assert!(shared_contact_events(std::slice::from_ref(&alice), &field).is_empty());
+
+        assert_eq!(
+            shared_contact_events(&[alice.clone(), bob.clone()], &field),
+            vec![
+                NotificationMessage::ContactValueShared {
+                    context: alice.clone(),
+                    field: field.clone(),
+                    shared_with: vec![truncate_address(&bob.address)],
+                },
+                NotificationMessage::ContactValueShared {
+                    context: bob,
+                    field: field.clone(),
+                    shared_with: vec![truncate_address(&alice.address)],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn verify_second_challenge_outcomes() {
+        let challenge = expected_message(true, Some(false));
+
+        assert_eq!(
+            verify_second_challenge(&challenge, &second_value(&challenge)).unwrap(),
+            SecondChallengeOutcome::Verified
+        );
+        assert_eq!(
+            verify_second_challenge(&challenge, ExpectedMessage::random().value.as_str()).unwrap(),
+            SecondChallengeOutcome::Failed
+        );
+        // The first challenge does not verify the second one.
+        assert_eq!(
+            verify_second_challenge(&challenge, &expected_value(&challenge)).unwrap(),
+            SecondChallengeOutcome::Failed
+        );
+
+        let challenge = expected_message(false, None);
+        assert_eq!(
+            verify_second_challenge(&challenge, &expected_value(&challenge)).unwrap(),
+            SecondChallengeOutcome::NotAvailable
+        );
+
+        let challenge = ChallengeType::Unsupported { is_verified: None };
+        assert!(verify_second_challenge(&challenge, "").is_err());
+    }
+
+    #[test]
+    fn verify_second_challenge_codes() {
+        let code = |expected: ExpectedMessage| ChallengeType::ExpectedMessage {
+            expected: {
+                let mut first = ExpectedMessage::random();
+                first.is_verified = true;
+                first
+            },
+            second: Some(expected),
+        };
+
+        let challenge = code(ExpectedMessage::code(6, 900, 5));
+        let value = second_value(&challenge);
+        assert_eq!(value.len(), 6);
+        assert!(value.chars().all(|c| c.is_ascii_digit()));
+
+        assert_eq!(
+            verify_second_challenge(&challenge, &value).unwrap(),
+            SecondChallengeOutcome::Verified
+        );
+        // Codes must match exactly.
+        assert_eq!(
+            verify_second_challenge(&challenge, &format!("0{}", value)).unwrap(),
+            SecondChallengeOutcome::Failed
+        );
+
+        let mut expired = ExpectedMessage::code(6, 900, 5);
+        expired.expires_at = Some(Timestamp::now());
+        let challenge = code(expired);
+        assert_eq!(
+            verify_second_challenge(&challenge, &second_value(&challenge)).unwrap(),
+            SecondChallengeOutcome::Expired
+        );
+
+        let challenge = code(ExpectedMessage::code(8, 900, 0));
+        assert_eq!(
+            verify_second_challenge(&challenge, &second_value(&challenge)).unwrap(),
+            SecondChallengeOutcome::Expired
+        );
+    }
+
+    #[test]
+    fn second_challenge_outcome_events() {
+        let context = IdentityContext::alice();
+        let field = IdentityFieldValue::ALICE_EMAIL();
+
+        assert!(SecondChallengeOutcome::NotAvailable
+            .events(&context, &field)
+            .is_empty());
+        assert_eq!(
+            SecondChallengeOutcome::Verified.events(&context, &field),
+            vec![NotificationMessage::SecondFieldVerified {
+                context: context.clone(),
+                field: field.clone(),
+            }]
+        );
+        assert_eq!(
+            SecondChallengeOutcome::Failed.events(&context, &field),
+            vec![NotificationMessage::SecondFieldVerificationFailed {
+                context: context.clone(),
+                field: field.clone(),
+            }]
+        );
+    }
+
+    #[test]
+    fn transition_after_field_changes() {
+        let mut alice = JudgementState::alice();
+        assert_eq!(
+            verification_transition(&alice),
+            VerificationTransition::Reset
+        );
+
+        // Verify all fields.
+        *alice
+            .get_field_mut(&IdentityFieldValue::ALICE_DISPLAY_NAME())
+            .expected_display_name_check_mut()
+            .0 = true;
+
+        for field in &[
+            IdentityFieldValue::ALICE_EMAIL(),
+            IdentityFieldValue::ALICE_TWITTER(),
+            IdentityFieldValue::ALICE_MATRIX(),
+        ] {
+            alice
+                .get_field_mut(field)
+                .expected_message_mut()
+                .set_verified();
+        }
+
+        // The second email challenge is still pending.
+        assert_eq!(
+            verification_transition(&alice),
+            VerificationTransition::Reset
+        );
+
+        alice
+            .get_field_mut(&IdentityFieldValue::ALICE_EMAIL())
+            .expected_second_mut()
+            .set_verified();
+
+        assert_eq!(
+            verification_transition(&alice),
+            VerificationTransition::FullyVerified
+        );
+    }
+
+    #[test]
+    fn judgement_delay_within_range() {
+        for _ in 0..100 {
+            assert!(JUDGEMENT_DELAY_RANGE.contains(&judgement_delay()));
+        }
+    }
+
+    #[test]
+    fn expedite_judgement_issuance() {
+        let now = Timestamp::from(10_000);
+        let mut state = JudgementState::alice();
+        state.completion_timestamp = Some(Timestamp::from(9_990));
+        state.issue_judgement_at = Some(Timestamp::from(10_200));
+
+        // Not fully verified yet.
+        assert!(expedited_issue_time(&state, 30, now).is_none());
+
+        state.is_fully_verified = true;
+        assert_eq!(
+            expedited_issue_time(&state, 30, now),
+            Some(Timestamp::from(10_020))
+        );
+
+        // The floor has passed already.
+        state.completion_timestamp = Some(Timestamp::from(9_000));
+        assert_eq!(expedited_issue_time(&state, 30, now), Some(now));
+
+        // Never later than scheduled.
+        state.completion_timestamp = Some(Timestamp::from(9_990));
+        state.issue_judgement_at = Some(Timestamp::from(10_010));
+        assert_eq!(
+            expedited_issue_time(&state, 30, now),
+            Some(Timestamp::from(10_010))
+        );
+
+        state.judgement_submitted = true;
+        assert!(expedited_issue_time(&state, 30, now).is_none());
+    }
+}
@@ -0,0 +1,243 @@
+use crate::database::Database;
+use crate::notifier::{EventCursor, EventLogConfig};
+use crate::primitives::{Event, NotificationEnvelope, Timestamp, NOTIFICATION_KINDS};
+use crate::retry::{retry, RetryConfig};
+use crate::Result;
+use hmac::{Hmac, Mac};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use schemars::JsonSchema;
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+
+// In seconds
+const DEFAULT_TIMEOUT: u64 = 10;
+
+pub const SIGNATURE_HEADER: &str = "X-Registrar-Signature";
+
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct WebhookConfig {
+    // Endpoints to which the events are posted.
+    pub urls: Vec<String>,
+    // Secret used to sign the payloads.
+    pub secret: String,
+    // Notification kinds which are posted, all if not set.
+    pub kinds: Option<Vec<String>>,
+    // Request timeout in seconds.
+    pub timeout: Option<u64>,
+    // Retries of failed deliveries, defaults are used if not set.
+    pub retry: Option<RetryConfig>,
+}
+
+/// The body posted to the webhook endpoints.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct WebhookPayload {
+    pub timestamp: Timestamp,
+    // Sequence number of the event per identity.
+    pub seq: u64,
+    // Sequence number of the event in the log, deliveries are not ordered.
+    pub log_seq: u64,
+    pub notification: NotificationEnvelope,
+}
+
+impl From<Event> for WebhookPayload {
+    fn from(event: Event) -> Self {
+        WebhookPayload {
+            timestamp: event.timestamp,
+            seq: event.seq,
+            log_seq: event.log_seq,
+            notification: event.message.into(),
+        }
+    }
+}
+
+/// Hex encoded HMAC-SHA256 of the body, sent as `sha256=<signature>`.
+pub fn sign_payload(secret: &str, body: &[u8]) -> String {
+    let mut mac: Hmac<Sha256> =
+        Hmac::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Posts the events of the event log to the configured endpoints, so external
+/// systems can react to them without a WS session.
+#[derive(Debug, Clone)]
+pub struct Webhooks {
+    client: Client,
+    config: Arc<WebhookConfig>,
+    retry: RetryConfig,
+}
+
+impl Webhooks {
+    pub fn new(config: WebhookConfig) -> Result<Self> {
+        if config.secret.is_empty() {
+            return Err(anyhow!("the webhook secret must not be empty"));
+        }
+
+        for kind in config.kinds.iter().flatten() {
+            if !NOTIFICATION_KINDS.iter().any(|(known, _)| known == kind) {
+                return Err(anyhow!("unknown notification kind '{}' for webhooks", kind));
+            }
+        }
+
+        let client = Client::builder()
+            .timeout(Duration::from_secs(
+                config.timeout.unwrap_or(DEFAULT_TIMEOUT),
+            ))
+            .build()?;
+
+        Ok(Webhooks {
+            client,
+            retry: config.retry.clone().unwrap_or_default(),
+            config: Arc::new(config),
+        })
+    }
+    fn is_subscribed(&self, event: &Event) -> bool {
+        self.config
+            .kinds
+            .as_ref()
+            .map(|kinds| kinds.iter().any(|kind| kind == event.message.kind()))
+            .unwrap_or(true)
+    }
+    async fn post(&self, url: &str, body: &[u8]) -> Result<()> {
+        let resp = self
+            .client
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, sign_payload(&self.config.secret, body))
+            .body(body.to_vec())
+            .send()
+            .await?;
+
+        if !resp.status().is_success() {
+            return Err(anyhow!(
+                "webhook endpoint returned status code {}",
+                resp.status()
+            ));
+        }
+
+        Ok(())
+    }
+    /// Posts the event to every endpoint in the background, so slow
+    /// endpoints do not delay other deliveries.
+    fn deliver(&self, event: Event) -> Result<()> {
+        let body = Arc::new(serde_json::to_vec(&WebhookPayload::from(event))?);
+
+        for url in &self.config.urls {
+            let webhooks = self.clone();
+            let url = url.clone();
+            let body = Arc::clone(&body);
+
+            actix::spawn(async move {
+                let name = format!("Webhook delivery to {}", url);
+                if let Err(err) = retry(&webhooks.retry, "webhook", &name, || {
+                    webhooks.post(&url, &body)
+                })
+                .await
+                {
+                    error!("Failed to deliver webhook to {}: {:?}", url, err);
+                }
+            });
+        }
+
+        Ok(())
+    }
+}
+
+pub async fn run_webhooks(db: Database, webhooks: Webhooks, event_log: EventLogConfig) {
+    async fn local(db: &Database, webhooks: &Webhooks, cursor: &mut EventCursor) -> Result<()> {
+        for event in cursor.fetch(db).await? {
+            if webhooks.is_subscribed(&event) {
+                webhooks.deliver(event)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    let mut cursor = loop {
+        match EventCursor::new(&db, event_log).await {
+            Ok(cursor) => break cursor,
+            Err(err) => {
+                error!("Failed to fetch the latest event: {:?}", err);
+                sleep(Duration::from_secs(1)).await;
+            }
+        }
+    };
+
+    loop {
+        if let Err(err) = local(&db, &webhooks, &mut cursor).await {
+            error!("Error in webhook event loop: {:?}", err);
+        }
+
+        sleep(Duration::from_secs(1)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{IdentityContext, NotificationMessage};
+
+    fn config(kinds: Option<Vec<String>>) -> WebhookConfig {
+        WebhookConfig {
+            urls: vec!["http://localhost:8080/hook".to_string()],
+            secret: "secret".to_string(),
+            kinds,
+            timeout: None,
+            retry: None,
+        }
+    }
+
+    #[test]
+    fn subscribed_kinds() {
+        let verified = Event::new(NotificationMessage::IdentityFullyVerified {
+            context: IdentityContext::alice(),
+        });
+        let updated = Event::new(NotificationMessage::IdentityUpdated {
+            context: IdentityContext::alice(),
+        });
+
+        let webhooks = Webhooks::new(config(None)).unwrap();
+        assert!(webhooks.is_subscribed(&verified));
+        assert!(webhooks.is_subscribed(&updated));
+
+        let webhooks =
+            Webhooks::new(config(Some(vec!["identity_fully_verified".to_string()]))).unwrap();
+        assert!(webhooks.is_subscribed(&verified));
+        assert!(!webhooks.is_subscribed(&updated));
+
+        assert!(Webhooks::new(config(Some(vec!["identity_teleported".to_string()]))).is_err());
+    }
+
+    #[test]
+    fn webhook_payload() {
+        let event = Event {
+            timestamp: Timestamp::from(1_000),
+            seq: 2,
+            log_seq: 30,
+            ..Event::new(NotificationMessage::JudgementProvided {
+                context: IdentityContext::alice(),
+            })
+        };
+
+        let value = serde_json::to_value(WebhookPayload::from(event)).unwrap();
+        assert_eq!(value["timestamp"], 1_000);
+        assert_eq!(value["seq"], 2);
+        assert_eq!(value["log_seq"], 30);
+        assert_eq!(value["notification"]["kind"], "judgement_provided");
+        assert_eq!(
+            value["notification"]["value"]["context"]["chain"],
+            "polkadot"
+        );
+
+        // Signatures of known payloads.
+        assert_eq!(
+            sign_payload("key", b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+}
@@ -138,6 +138,9 @@ export class ContentManager {
                 let challenge: DisplayNameChallenge = field.challenge.content;
                 if (challenge.passed) {
                     this.setDisplayNameVerification(field.value.value, BadgeValid);
+                } else if (challenge.reserved) {
+                    validity = BadgeInvalid;
+                    this.setDisplayNameReserved(field.value.value);
                 } else {
                     validity = BadgeInvalid;
                     this.setDisplayNameViolation(field.value.value, challenge.violations, true);
@@ -206,6 +209,16 @@ export class ContentManager {
 
         document.getElementById("display-name-strong")!.textContent = name;
     }
+    setDisplayNameReserved(name: string) {
+        this.div_display_name_overview.innerHTML = `
+            <div class="col-10 ">
+                <h2>Display name check</h2>
+                <p>The display name <strong id="display-name-strong"></strong> is ${BadgeInvalid}. It's reserved by the registrar, please choose a different display name.</p>
+            </div>
+        `;
+
+        document.getElementById("display-name-strong")!.textContent = name;
+    }
     setVerificationOverviewContent(table: string) {
         this.div_verification_overview.innerHTML = `
             <div class="col-10 table-responsive ">
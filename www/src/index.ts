@@ -81,8 +81,11 @@ class ActionListerner {
             .addEventListener("input", (_: Event) => {
                 this.manager.resetButton();
 
+                // Guess the network by the first character of the SS58 prefix.
                 if (this.search_bar.value.startsWith("1")) {
                     this.specify_network.innerText = "Polkadot";
+                } else if (this.search_bar.value.startsWith("5")) {
+                    this.specify_network.innerText = "Westend";
                 } else {
                     this.specify_network.innerText = "Kusama";
                 }
@@ -177,6 +180,8 @@ class ActionListerner {
             let check: CheckDisplayNameResult = data.message;
             if (check.type == "ok") {
                 this.manager.setDisplayNameVerification(display_name, BadgeValid);
+            } else if (check.type == "reserved") {
+                this.manager.setDisplayNameReserved(display_name);
             } else if (check.type = "violations") {
                 let violations: Violation[] = check.value;
                 this.manager.setDisplayNameViolation(display_name, violations, false);
@@ -198,6 +203,29 @@ class ActionListerner {
         this.manager.wipeEmailSecondChallengeContent();
         this.manager.wipeUnsupportedContent();
     }
+    // Marks the displayed notifications as read, so they are not displayed
+    // again after reconnecting.
+    acknowledgeNotifications(message: StateNotification) {
+        if (message.notifications.length == 0 || message.sequence === undefined) {
+            return;
+        }
+
+        let body = JSON.stringify({
+            context: message.state.context,
+            sequence: message.sequence,
+        });
+
+        fetch("https://registrar-backend.web3.foundation/api/acknowledge_notifications",
+            {
+                method: "POST",
+                headers: {
+                    "Content-Type": "application/json",
+                },
+                body: body,
+            }).catch(() => {
+                // Not critical, the notifications might just be displayed again.
+            });
+    }
     // Handles the judgement state received from the server.
     handleJudgementState(msg: MessageEvent) {
         const parsed: GenericMessage = JSON.parse(msg.data);
@@ -210,11 +238,16 @@ class ActionListerner {
             this.manager.processUnsupportedOverview(message.state);
 
             this.notifications.processNotifications(message.notifications);
+            this.acknowledgeNotifications(message);
 
             // This notification should only be displayed if no other notifications are available.
             if (message.state.is_fully_verified && message.notifications.length == 0) {
                 this.notifications.displayNotification("The identity has been fully verified!", "bg-success text-light", true)
             }
+
+            if (message.state.requires_manual_review && !message.state.is_fully_verified && message.notifications.length == 0) {
+                this.notifications.displayNotification("The identity does not contain any fields which can be verified automatically. It will be reviewed manually by the registrar.", "bg-info text-dark", true)
+            }
         } else if (parsed.type == "err") {
             let message: string = parsed.message;
             this.notifications.displayError(message);
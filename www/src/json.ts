@@ -6,11 +6,16 @@ export interface GenericMessage {
 export interface StateNotification {
     state: State;
     notifications: Notification[];
+    // Sequence number of the latest notification.
+    sequence?: number;
+    // Notifications up to this sequence number were already acknowledged.
+    read_sequence?: number;
 }
 
 export interface State {
     context: Context;
     is_fully_verified: boolean;
+    requires_manual_review: boolean;
     completion_timestamp?: any;
     fields: Field[];
 }
@@ -24,6 +29,16 @@ export interface Field {
     value: FieldValue;
     challenge: Challenge;
     failed_attempts: number;
+    last_failure?: FieldFailure;
+}
+
+export interface FieldFailure {
+    // One of "wrong_challenge", "no_challenge", "missing_disambiguation_token",
+    // "expired" or "vetoed".
+    cause: string;
+    message_id: number;
+    content_hash: string;
+    timestamp: number;
 }
 
 export interface FieldValue {
@@ -44,6 +59,7 @@ export interface Content {
 export interface DisplayNameChallenge {
     passed: boolean;
     violations: Violation[];
+    reserved?: string;
 }
 
 export interface Expected {
@@ -52,6 +68,8 @@ export interface Expected {
 }
 
 export interface Notification {
+    version?: number;
+    kind?: string;
     type: string;
     value: any;
 }
@@ -66,6 +84,37 @@ export interface ManuallyVerified {
     field: string;
 }
 
+export interface ContactValueShared {
+    context: Context;
+    field: FieldValue;
+    shared_with: string[];
+}
+
+export interface DisambiguationRequired {
+    context: Context;
+    field: FieldValue;
+    token: string;
+}
+
+export interface DisposableEmail {
+    context: Context;
+    field: FieldValue;
+    domain: string;
+    action: string;
+}
+
+export interface MatrixAccountRejected {
+    context: Context;
+    field: FieldValue;
+    reason: string;
+}
+
+export interface VerificationVetoed {
+    context: Context;
+    field: FieldValue | null;
+    reason: string | null;
+}
+
 export interface CheckDisplayNameResult {
     type: string;
     value: any;
@@ -1,5 +1,5 @@
 import { capitalizeFirstLetter } from "./content.js";
-import { Notification, NotificationFieldContext, ManuallyVerified } from "./json";
+import { Notification, NotificationFieldContext, ManuallyVerified, ContactValueShared, DisambiguationRequired, DisposableEmail, MatrixAccountRejected, VerificationVetoed } from "./json";
 
 export class NotificationHandler {
     notify_idx: number
@@ -16,7 +16,14 @@ export class NotificationHandler {
     processNotifications(notifications: Notification[]) {
         for (let notify of notifications) {
             try {
-                const [message, color] = notificationTypeResolver(notify);
+                const resolved = notificationTypeResolver(notify);
+                if (resolved == null) {
+                    // Notification kind introduced by a newer backend.
+                    console.debug(`Skipping unknown notification kind: ${notify.kind}`);
+                    continue;
+                }
+
+                const [message, color] = resolved;
                 this.displayNotification(message, color, false);
             } catch (error) {
                 this.unexpectedError("pnntr#1");
@@ -78,8 +85,8 @@ export class NotificationHandler {
     }
 }
 
-function notificationTypeResolver(notification: Notification): [string, string] {
-    switch (notification.type) {
+function notificationTypeResolver(notification: Notification): [string, string] | null {
+    switch (notification.kind ?? notification.type) {
         case "identity_inserted": {
             return [
                 `The judgement request has been discovered by the registrar service.`,
@@ -152,8 +159,56 @@ function notificationTypeResolver(notification: Notification): [string, string]
                 "bg-info text-light"
             ]
         }
+        case "contact_value_shared": {
+            let data = notification.value as ContactValueShared;
+            return [
+                `${capitalizeFirstLetter(data.field.type)} account "${data.field.value}" is also used by: ${data.shared_with.join(", ")}. Please include the first characters of your address next to the challenge.`,
+                "bg-warning text-dark"
+            ]
+        }
+        case "disambiguation_required": {
+            let data = notification.value as DisambiguationRequired;
+            return [
+                `${capitalizeFirstLetter(data.field.type)} account "${data.field.value}" is shared with other identities. Please send the challenge together with "${data.token}".`,
+                "bg-warning text-dark"
+            ]
+        }
+        case "disposable_email": {
+            let data = notification.value as DisposableEmail;
+            let consequence = data.action == "refuse"
+                ? "cannot be verified automatically"
+                : "requires a manual review by the registrar";
+            return [
+                `${capitalizeFirstLetter(data.field.type)} account "${data.field.value}" belongs to a disposable email provider (${data.domain}) and ${consequence}.`,
+                "bg-warning text-dark"
+            ]
+        }
+        case "matrix_account_rejected": {
+            let data = notification.value as MatrixAccountRejected;
+            return [
+                `${capitalizeFirstLetter(data.field.type)} account "${data.field.value}" cannot be verified automatically. ${data.reason}.`,
+                "bg-warning text-dark"
+            ]
+        }
+        case "verification_vetoed": {
+            let data = notification.value as VerificationVetoed;
+            let subject = data.field
+                ? `${capitalizeFirstLetter(data.field.type)} account "${data.field.value}"`
+                : "The identity";
+            return [
+                `${subject} requires a manual review by the registrar${data.reason ? `: ${data.reason}` : ""}.`,
+                "bg-warning text-dark"
+            ]
+        }
+        case "challenge_regenerated": {
+            let data = notification.value as NotificationFieldContext;
+            return [
+                `A new challenge was generated for ${capitalizeFirstLetter(data.field.type)} account "${data.field.value}". The previous challenge is no longer valid.`,
+                "bg-info text-dark"
+            ]
+        }
         default: {
-            throw new Error("unrecognized notification");
+            return null;
         }
     }
 }
\ No newline at end of file